@@ -17,11 +17,13 @@
 
 //! This module provide attestation public APIs in server side.
 
+use crate::cache::EndorsementCache;
 use crate::key;
 use crate::AttestationConfig;
 use crate::AttestedTlsConfig;
 use crate::EndorsedAttestationReport;
 
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::{Duration, SystemTime};
@@ -30,7 +32,7 @@ use std::time::{Duration, SystemTime};
 use std::untrusted::time::SystemTimeEx;
 
 use anyhow::{anyhow, Result};
-use log::debug;
+use log::{debug, info, warn};
 use teaclave_config::build::ATTESTATION_VALIDITY_SECS;
 
 const CERT_ISSUER: &str = "Teaclave";
@@ -39,6 +41,9 @@ const CERT_SUBJECT: &str = "CN=Teaclave";
 pub struct RemoteAttestation {
     attestation_config: Arc<AttestationConfig>,
     attested_tls_config: Option<Arc<RwLock<AttestedTlsConfig>>>,
+    endorsement_cache_path: Option<PathBuf>,
+    reuse_cached_endorsement: bool,
+    endorsement_cache: Option<EndorsementCache>,
 }
 
 impl RemoteAttestation {
@@ -47,22 +52,74 @@ impl RemoteAttestation {
         Self {
             attestation_config,
             attested_tls_config: None,
+            endorsement_cache_path: None,
+            reuse_cached_endorsement: false,
+            endorsement_cache: None,
+        }
+    }
+
+    /// Keep the last successful endorsement in a sealed file at `path`, which
+    /// is used on start if no attestation service is reachable.
+    pub fn endorsement_cache(self, path: Option<PathBuf>) -> Self {
+        Self {
+            endorsement_cache_path: path,
+            ..self
+        }
+    }
+
+    /// Use the cached endorsement on start as long as it is valid, without
+    /// contacting the attestation service.
+    pub fn reuse_cached_endorsement(self, reuse: bool) -> Self {
+        Self {
+            reuse_cached_endorsement: reuse,
+            ..self
         }
     }
 
     /// Generate a endorsed attestation report.
     pub fn generate_and_endorse(self) -> Result<Self> {
-        let attested_tls_config = Arc::new(RwLock::new(AttestedTlsConfig::new(
-            &self.attestation_config,
-        )?));
+        let endorsement_cache = self.endorsement_cache_path.clone().and_then(|path| {
+            EndorsementCache::new(path, self.attestation_config.algorithm())
+                .map_err(|e| warn!("Failed to set up the endorsement cache: {:?}", e))
+                .ok()
+        });
+        let cached = match &endorsement_cache {
+            Some(cache) if self.reuse_cached_endorsement => match cache.load() {
+                Ok(config) => {
+                    info!("Reuse the cached endorsement at {:?}", cache.path());
+                    Some(config)
+                }
+                Err(e) => {
+                    debug!("Cannot reuse the cached endorsement: {:?}", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        let attested_tls_config = match cached {
+            Some(config) => config,
+            None => endorse(&self.attestation_config, &endorsement_cache)?,
+        };
+        // The cached endorsement is refreshed before it expires.
+        let first_refresh = attested_tls_config.remaining_validity();
+        let attested_tls_config = Arc::new(RwLock::new(attested_tls_config));
         let attestation_config_ref = self.attestation_config.clone();
         let attested_tls_config_ref = attested_tls_config.clone();
+        let endorsement_cache_ref = endorsement_cache.clone();
         thread::spawn(move || {
-            AttestationFreshnessKeeper::new(attestation_config_ref, attested_tls_config_ref).start()
+            AttestationFreshnessKeeper::new(
+                attestation_config_ref,
+                attested_tls_config_ref,
+                endorsement_cache_ref,
+            )
+            .start(first_refresh)
         });
         Ok(Self {
             attestation_config: self.attestation_config,
             attested_tls_config: Some(attested_tls_config),
+            endorsement_cache_path: self.endorsement_cache_path,
+            reuse_cached_endorsement: self.reuse_cached_endorsement,
+            endorsement_cache,
         })
     }
 
@@ -70,6 +127,20 @@ impl RemoteAttestation {
     pub fn attested_tls_config(&self) -> Option<Arc<RwLock<AttestedTlsConfig>>> {
         self.attested_tls_config.clone()
     }
+
+    /// Attest again with a new key pair right away, replacing the attested TLS
+    /// config for the connections made afterwards.
+    pub fn refresh(&self) -> Result<()> {
+        let attested_tls_config = self
+            .attested_tls_config
+            .as_ref()
+            .ok_or_else(|| anyhow!("Not attested yet"))?;
+        refresh(
+            &self.attestation_config,
+            attested_tls_config,
+            &self.endorsement_cache,
+        )
+    }
 }
 
 impl AttestedTlsConfig {
@@ -99,31 +170,79 @@ impl AttestedTlsConfig {
 
         Ok(attested_tls_config)
     }
+
+    fn remaining_validity(&self) -> Duration {
+        let age = SystemTime::now()
+            .duration_since(self.time)
+            .unwrap_or_default();
+        self.validity.saturating_sub(age)
+    }
+}
+
+/// Endorse with the attestation service, or fall back to the cached
+/// endorsement if it fails.
+fn endorse(
+    attestation_config: &AttestationConfig,
+    endorsement_cache: &Option<EndorsementCache>,
+) -> Result<AttestedTlsConfig> {
+    match AttestedTlsConfig::new(attestation_config) {
+        Ok(config) => {
+            store_endorsement(endorsement_cache, &config);
+            Ok(config)
+        }
+        Err(e) => match endorsement_cache {
+            Some(cache) => {
+                warn!("Failed to endorse, fall back to the cached one: {:?}", e);
+                cache.load().map_err(|cache_error| {
+                    anyhow!(
+                        "{:?}, and no valid cached endorsement: {:?}",
+                        e,
+                        cache_error
+                    )
+                })
+            }
+            None => Err(e),
+        },
+    }
+}
+
+// Failing to cache does not fail the endorsement.
+fn store_endorsement(cache: &Option<EndorsementCache>, config: &AttestedTlsConfig) {
+    if let Some(cache) = cache {
+        if let Err(e) = cache.store(config) {
+            warn!("Failed to cache endorsement at {:?}: {:?}", cache.path(), e);
+        }
+    }
 }
 
 /// To keep attestation report fresh. Refresh current valid report periodically.
 struct AttestationFreshnessKeeper {
     attestation_config: Arc<AttestationConfig>,
     attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
+    endorsement_cache: Option<EndorsementCache>,
 }
 
 impl AttestationFreshnessKeeper {
     pub(crate) fn new(
         attestation_config: Arc<AttestationConfig>,
         attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
+        endorsement_cache: Option<EndorsementCache>,
     ) -> Self {
         Self {
             attestation_config,
             attested_tls_config,
+            endorsement_cache,
         }
     }
 
     /// Start the fresshness keeper which will periodically refresh it's
-    /// `attested_tls_config`.
-    pub(crate) fn start(&self) {
+    /// `attested_tls_config`, for the first time after `first_refresh`.
+    pub(crate) fn start(&self, first_refresh: Duration) {
         debug!("AttestationFreshnessKeeper started");
+        let mut wait = first_refresh;
         loop {
-            thread::sleep(Duration::from_secs(ATTESTATION_VALIDITY_SECS));
+            thread::sleep(wait);
+            wait = Duration::from_secs(ATTESTATION_VALIDITY_SECS);
             match self.refresh() {
                 Ok(_) => debug!("Attestation report updated successfully"),
                 Err(e) => debug!("Failed to refresh attestation report: {:?}", e),
@@ -131,17 +250,29 @@ impl AttestationFreshnessKeeper {
         }
     }
 
-    /// Get updated report form attestation service and create an updated
-    /// attested TLS config.
     fn refresh(&self) -> Result<()> {
-        debug!("begin refresh");
-        let updated_attested_tls_config = AttestedTlsConfig::new(&self.attestation_config)?;
-        let lock = self.attested_tls_config.clone();
-        let mut config = lock
-            .write()
-            .map_err(|_| anyhow!("Failed to get write lock"))?;
-        *config = updated_attested_tls_config;
-        debug!("refresh done");
-        Ok(())
+        refresh(
+            &self.attestation_config,
+            &self.attested_tls_config,
+            &self.endorsement_cache,
+        )
     }
 }
+
+/// Get updated report form attestation service and create an updated attested
+/// TLS config.
+fn refresh(
+    attestation_config: &AttestationConfig,
+    attested_tls_config: &RwLock<AttestedTlsConfig>,
+    endorsement_cache: &Option<EndorsementCache>,
+) -> Result<()> {
+    debug!("begin refresh");
+    let updated_attested_tls_config = AttestedTlsConfig::new(attestation_config)?;
+    store_endorsement(endorsement_cache, &updated_attested_tls_config);
+    let mut config = attested_tls_config
+        .write()
+        .map_err(|_| anyhow!("Failed to get write lock"))?;
+    *config = updated_attested_tls_config;
+    debug!("refresh done");
+    Ok(())
+}
@@ -0,0 +1,172 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! This module keeps the last successful endorsement, i.e., the attested TLS
+//! config including its private key, in a sealed file. The cached one is used
+//! on start when no attestation service is reachable, or right away if reusing
+//! it is enabled, and only if it is still valid. An endorsement is cached for
+//! the measurement of the enclave and the attestation algorithm, so that it is
+//! never used by another enclave, e.g., an upgraded one of the same signer, or
+//! with another way of attestation.
+
+use crate::platform;
+use crate::AttestedTlsConfig;
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+#[allow(unused_imports)]
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "mesalock_sgx")]
+fn create(path: &Path) -> std::io::Result<impl Write> {
+    // Sealed with a key derived from the enclave signer
+    sgx_tprotected_fs::SgxFile::create(path)
+}
+
+#[cfg(feature = "mesalock_sgx")]
+fn open(path: &Path) -> std::io::Result<impl Read> {
+    sgx_tprotected_fs::SgxFile::open(path)
+}
+
+// The file system of LibOS is encrypted already.
+#[cfg(all(feature = "libos", not(feature = "mesalock_sgx")))]
+fn create(path: &Path) -> std::io::Result<impl Write> {
+    std::fs::File::create(path)
+}
+
+#[cfg(all(feature = "libos", not(feature = "mesalock_sgx")))]
+fn open(path: &Path) -> std::io::Result<impl Read> {
+    std::fs::File::open(path)
+}
+
+// A TD guest has no sealing key, so the cache should be kept on an encrypted
+// file system of the guest, e.g., a LUKS volume unlocked at boot.
+#[cfg(feature = "tdx")]
+fn create(path: &Path) -> std::io::Result<impl Write> {
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(feature = "tdx")]
+fn open(path: &Path) -> std::io::Result<impl Read> {
+    std::fs::File::open(path)
+}
+
+/// What a cached endorsement is bound to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheKey {
+    /// MRENCLAVE of the enclave
+    measurement: [u8; 32],
+    /// Name of the attestation algorithm
+    algorithm: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct EndorsementCache {
+    path: PathBuf,
+    key: CacheKey,
+}
+
+impl EndorsementCache {
+    /// Cache at `path` for this enclave attesting with `algorithm`.
+    pub(crate) fn new(path: PathBuf, algorithm: &str) -> Result<Self> {
+        let measurement = platform::get_self_measurement()?;
+        Ok(Self {
+            path,
+            key: CacheKey {
+                measurement,
+                algorithm: algorithm.to_string(),
+            },
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn store(&self, config: &AttestedTlsConfig) -> Result<()> {
+        let bytes = serde_json::to_vec(&(&self.key, config))?;
+        let mut file = create(&self.path)?;
+        file.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub(crate) fn load(&self) -> Result<AttestedTlsConfig> {
+        let mut bytes = Vec::new();
+        open(&self.path)?.read_to_end(&mut bytes)?;
+        let (key, config): (CacheKey, AttestedTlsConfig) = serde_json::from_slice(&bytes)?;
+        ensure!(
+            key == self.key,
+            "Cached endorsement is for another enclave or attestation algorithm"
+        );
+        let age = SystemTime::now().duration_since(config.time)?;
+        ensure!(age < config.validity, "Cached endorsement has expired");
+        Ok(config)
+    }
+}
+
+#[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
+pub mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn attested_tls_config(time: SystemTime) -> AttestedTlsConfig {
+        AttestedTlsConfig {
+            cert: vec![1, 2, 3],
+            private_key: vec![4, 5, 6],
+            time,
+            validity: Duration::from_secs(3600),
+        }
+    }
+
+    pub fn test_endorsement_cache() {
+        let path = PathBuf::from("/tmp/teaclave_attestation_test.endorsement");
+        let cache = EndorsementCache::new(path.clone(), "sgx_epid").unwrap();
+        cache
+            .store(&attested_tls_config(SystemTime::now()))
+            .unwrap();
+        let config = cache.load().unwrap();
+        assert_eq!(config.cert, vec![1, 2, 3]);
+        assert_eq!(config.private_key, vec![4, 5, 6]);
+
+        // Not used with another attestation algorithm
+        let other = EndorsementCache::new(path.clone(), "sgx_ecdsa").unwrap();
+        assert!(other.load().is_err());
+
+        // Not used by another enclave
+        let mut other = cache.clone();
+        other.key.measurement = [0; 32];
+        assert!(other.load().is_err());
+
+        // Not used after expiration
+        let expired = SystemTime::now() - Duration::from_secs(7200);
+        cache.store(&attested_tls_config(expired)).unwrap();
+        assert!(cache.load().is_err());
+
+        std::untrusted::fs::remove_file(&path).unwrap();
+    }
+}
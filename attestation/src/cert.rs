@@ -350,7 +350,7 @@ macro_rules! asn1_set_ty {
     };
 }
 
-#[cfg(any(feature = "mesalock_sgx", feature = "libos"))]
+#[cfg(any(feature = "mesalock_sgx", feature = "libos", feature = "tdx"))]
 macro_rules! asn1_seq {
     () => { () };
     ($e: expr) => {
@@ -0,0 +1,147 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Failover among multiple attestation services.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, ensure, Result};
+use log::warn;
+
+/// An attestation service which failed is not tried again for this long,
+/// unless all the others have failed too.
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct FailoverState {
+    /// Index of the last service which succeeded
+    preferred: usize,
+    failed_at: Vec<Option<Instant>>,
+}
+
+/// URLs of the attestation services in the order of failover. The last
+/// service which succeeded is tried first, and the ones which failed recently
+/// are tried last. Clones share the health of the services.
+#[derive(Clone)]
+pub(crate) struct AttestationServiceUrls {
+    urls: Vec<url::Url>,
+    state: Arc<Mutex<FailoverState>>,
+}
+
+impl AttestationServiceUrls {
+    pub(crate) fn new(urls: Vec<url::Url>) -> Result<Self> {
+        ensure!(!urls.is_empty(), "No attestation service URL");
+        let state = FailoverState {
+            preferred: 0,
+            failed_at: vec![None; urls.len()],
+        };
+        Ok(Self {
+            urls,
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// Indices of the services in the order to try at `now`.
+    fn candidates(&self, now: Instant) -> Vec<usize> {
+        let state = self.state.lock().unwrap();
+        let is_healthy = |i: &usize| match state.failed_at[*i] {
+            Some(t) => now.duration_since(t) >= FAILURE_COOLDOWN,
+            None => true,
+        };
+        let ordered = std::iter::once(state.preferred)
+            .chain((0..self.urls.len()).filter(|i| *i != state.preferred));
+        let (healthy, unhealthy): (Vec<usize>, Vec<usize>) = ordered.partition(is_healthy);
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    fn mark_success(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.preferred = index;
+        state.failed_at[index] = None;
+    }
+
+    fn mark_failure(&self, index: usize, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.failed_at[index] = Some(now);
+    }
+
+    /// Call `f` with the URLs in turn until it succeeds.
+    pub(crate) fn try_each<T>(&self, mut f: impl FnMut(&url::Url) -> Result<T>) -> Result<T> {
+        let mut last_error = None;
+        for index in self.candidates(Instant::now()) {
+            let url = &self.urls[index];
+            match f(url) {
+                Ok(value) => {
+                    self.mark_success(index);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    warn!("Attestation service {} failed: {:?}", url, e);
+                    self.mark_failure(index, Instant::now());
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("No attestation service available")))
+    }
+}
+
+#[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
+pub mod tests {
+    use super::*;
+
+    fn urls() -> AttestationServiceUrls {
+        let urls = ["https://a:443", "https://b:443", "https://c:443"]
+            .iter()
+            .map(|u| url::Url::parse(u).unwrap())
+            .collect();
+        AttestationServiceUrls::new(urls).unwrap()
+    }
+
+    pub fn test_failover_order() {
+        let urls = urls();
+        let now = Instant::now();
+        assert_eq!(urls.candidates(now), vec![0, 1, 2]);
+
+        // A failed service is tried last until the cooldown passes
+        urls.mark_failure(0, now);
+        assert_eq!(urls.candidates(now), vec![1, 2, 0]);
+        assert_eq!(urls.candidates(now + FAILURE_COOLDOWN), vec![0, 1, 2]);
+
+        // The last service which succeeded is tried first
+        urls.mark_success(2);
+        assert_eq!(urls.candidates(now), vec![2, 1, 0]);
+    }
+
+    pub fn test_try_each() {
+        let urls = urls();
+        let host = urls
+            .try_each(|url| match url.host_str() {
+                Some("c") => Ok("c"),
+                _ => Err(anyhow!("unavailable")),
+            })
+            .unwrap();
+        assert_eq!(host, "c");
+        assert_eq!(urls.candidates(Instant::now())[0], 2);
+
+        assert!(urls
+            .try_each::<()>(|_| Err(anyhow!("unavailable")))
+            .is_err());
+        assert!(AttestationServiceUrls::new(vec![]).is_err());
+    }
+}
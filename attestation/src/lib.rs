@@ -17,7 +17,8 @@
 
 //! This crate provides TLS-based remote attestation mechanism for Teaclave,
 //! supporting both EPID and ECDSA attestation. By default, Intel Attestation
-//! Service is used for RA.
+//! Service is used for RA. ECDSA quotes can also be endorsed by Microsoft Azure
+//! Attestation.
 
 #![allow(clippy::nonstandard_macro_braces)]
 
@@ -45,6 +46,10 @@ pub enum AttestationError {
     ConnectionError,
     #[error("Attestation Service API version not compatible")]
     ApiVersionNotCompatible,
+    #[error("Nonce of the report does not match the one of the request")]
+    NonceMismatch,
+    #[error("Certificate of the peer does not carry an attestation report")]
+    NotAttested,
 }
 
 /// Remote attestation configuration
@@ -63,6 +68,8 @@ pub(crate) enum AttestationAlgorithm {
     SgxEpid,
     /// Use ECDSA
     SgxEcdsa,
+    /// Use ECDSA with Microsoft Azure Attestation
+    SgxMaa,
 }
 
 impl AttestationAlgorithm {
@@ -70,9 +77,18 @@ impl AttestationAlgorithm {
         match s {
             "sgx_epid" => Some(AttestationAlgorithm::SgxEpid),
             "sgx_ecdsa" => Some(AttestationAlgorithm::SgxEcdsa),
+            "sgx_maa" => Some(AttestationAlgorithm::SgxMaa),
             _ => None,
         }
     }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            AttestationAlgorithm::SgxEpid => "sgx_epid",
+            AttestationAlgorithm::SgxEcdsa => "sgx_ecdsa",
+            AttestationAlgorithm::SgxMaa => "sgx_maa",
+        }
+    }
 }
 
 /// Attestation Service Configuration
@@ -81,8 +97,8 @@ impl AttestationAlgorithm {
 pub struct AttestationServiceConfig {
     /// Algorithm to use
     algo: AttestationAlgorithm,
-    /// URL of attestation service
-    as_url: url::Url,
+    /// URLs of attestation services in the order of failover
+    as_urls: failover::AttestationServiceUrls,
     /// IAS API Key
     api_key: String,
     /// SPID
@@ -99,6 +115,17 @@ impl AttestationConfig {
 
     /// Creates `AttestationConfig` for attestation using given values
     pub fn new(algorithm: &str, url: &str, api_key: &str, spid_str: &str) -> Result<Arc<Self>> {
+        Self::with_urls(algorithm, &[url], api_key, spid_str)
+    }
+
+    /// Creates `AttestationConfig` for attestation with attestation services
+    /// tried in order
+    pub fn with_urls<T: AsRef<str>>(
+        algorithm: &str,
+        urls: &[T],
+        api_key: &str,
+        spid_str: &str,
+    ) -> Result<Arc<Self>> {
         if cfg!(sgx_sim) {
             return Ok(Self::no_attestation());
         }
@@ -110,9 +137,13 @@ impl AttestationConfig {
         let algo = AttestationAlgorithm::from_str(algorithm)
             .context("Unsupported remote attestation algorithm")?;
 
+        let as_urls = urls
+            .iter()
+            .map(|url| url::Url::parse(url.as_ref()).context("Invalid URL"))
+            .collect::<Result<_>>()?;
         let att_service_cfg = AttestationServiceConfig {
             algo,
-            as_url: url::Url::parse(url).context("Invalid URL")?,
+            as_urls: failover::AttestationServiceUrls::new(as_urls)?,
             api_key: api_key.to_string(),
             spid,
         };
@@ -120,12 +151,20 @@ impl AttestationConfig {
         Ok(Arc::new(Self::WithAttestation(att_service_cfg)))
     }
 
+    /// Name of the attestation algorithm, or "none" without attestation.
+    pub(crate) fn algorithm(&self) -> &'static str {
+        match self {
+            Self::NoAttestation => "none",
+            Self::WithAttestation(config) => config.algo.as_str(),
+        }
+    }
+
     /// Crate attestation config from Teaclave runtime configuration.
     pub fn from_teaclave_config(config: &teaclave_config::RuntimeConfig) -> Result<Arc<Self>> {
         let as_config = &config.attestation;
-        Self::new(
+        Self::with_urls(
             &as_config.algorithm,
-            &as_config.url,
+            &as_config.urls(),
             &as_config.key,
             &as_config.spid,
         )
@@ -143,10 +182,27 @@ pub struct EndorsedAttestationReport {
     pub signature: Vec<u8>,
     /// Certificate matching the signing key of the signature
     pub certs: Vec<Vec<u8>>,
+    /// Format of the report, which is absent in reports endorsed before
+    /// Microsoft Azure Attestation was supported
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+/// Format of the report endorsed by an attestation service
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    /// JSON report of the Intel Attestation Service API version 4, which is
+    /// also used by the DCAP server
+    #[default]
+    Ias,
+    /// Signing input of a JWT issued by Microsoft Azure Attestation, i.e., the
+    /// encoded header and claims joined by a dot
+    AzureJwt,
 }
 
 /// Configuration for TLS communication in Remote Attestation
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AttestedTlsConfig {
     pub cert: Vec<u8>,
     pub private_key: Vec<u8>,
@@ -156,15 +212,17 @@ pub struct AttestedTlsConfig {
 
 #[macro_use]
 mod cert;
+mod failover;
 pub mod report;
 pub mod verifier;
 
 cfg_if::cfg_if! {
-    if #[cfg(any(feature = "mesalock_sgx", feature = "libos"))]  {
+    if #[cfg(any(feature = "mesalock_sgx", feature = "libos", feature = "tdx"))]  {
         mod service;
         pub mod key;
         mod platform;
         mod attestation;
+        mod cache;
         pub use attestation::RemoteAttestation;
     }
 }
@@ -180,8 +238,15 @@ pub mod tests {
             platform::tests::test_create_sgx_isv_enclave_report,
             platform::tests::test_get_sgx_quote,
             report::tests::test_sgx_quote_parse_from,
+            report::tests::test_td_quote_parse_from,
             report::tests::test_attestation_report_from_cert,
-            report::tests::test_attestation_report_from_cert_api_version_not_compatible
+            report::tests::test_attestation_report_from_cert_api_version_not_compatible,
+            report::tests::test_verify_report_nonce,
+            report::tests::test_attestation_report_from_azure_token,
+            cache::tests::test_endorsement_cache,
+            verifier::tests::test_verification_policy,
+            failover::tests::test_failover_order,
+            failover::tests::test_try_each,
         )
     }
 }
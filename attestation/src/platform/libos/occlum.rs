@@ -34,6 +34,7 @@ const SGX_CMD_NUM_GEN_EPID_QUOTE: u64 = (2u32
 const IOCTL_MAX_RETRIES: u32 = 20;
 const SGXIOC_GET_DCAP_QUOTE_SIZE: u64 = 0x80047307;
 const SGXIOC_GEN_DCAP_QUOTE: u64 = 0xc0187308;
+const SGXIOC_SELF_TARGET: u64 = 0x82007303;
 
 // From occlum/src/libos/src/fs/dev_fs/dev_sgx/mod.rs
 #[repr(C)]
@@ -100,6 +101,13 @@ macro_rules! do_ioctl {
     };
 }
 
+/// MRENCLAVE of the enclave itself, from its target info.
+pub(crate) fn get_self_measurement() -> Result<[u8; 32]> {
+    let mut target_info = TargetInfo::default();
+    do_ioctl!(SGXIOC_SELF_TARGET, &mut target_info);
+    Ok(target_info.mr_enclave.m)
+}
+
 /// Get quote with attestation key ID and enclave's local report.
 pub(crate) fn get_sgx_epid_quote(spid: &Spid, report_data: ReportData) -> Result<Vec<u8>> {
     let sigrl_ptr: *const u8 = std::ptr::null();
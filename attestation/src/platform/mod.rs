@@ -18,16 +18,24 @@
 #[cfg(feature = "libos")]
 pub(crate) mod libos;
 pub(crate) mod sgx;
+#[cfg(feature = "tdx")]
+pub(crate) mod tdx;
 #[cfg(all(feature = "libos", feature = "mesalock_sgx"))]
 compile_error!("feature \"mesalock_sgx\" and feature \"libos\" cannot be enabled at the same time");
+#[cfg(all(feature = "tdx", any(feature = "mesalock_sgx", feature = "libos")))]
+compile_error!("feature \"tdx\" cannot be enabled with \"mesalock_sgx\" or \"libos\"");
 
 #[cfg(feature = "libos")]
 pub(crate) use libos::{
-    occlum::{create_sgx_report_data, get_sgx_dcap_quote, get_sgx_epid_quote},
+    occlum::{
+        create_sgx_report_data, get_self_measurement, get_sgx_dcap_quote, get_sgx_epid_quote,
+    },
     PlatformError,
 };
 #[cfg(feature = "mesalock_sgx")]
-pub(crate) use sgx::{create_sgx_isv_enclave_report, get_sgx_quote, init_sgx_quote, PlatformError};
+pub(crate) use sgx::{get_self_measurement, quote_enclave_report, PlatformError};
+#[cfg(feature = "tdx")]
+pub(crate) use tdx::{create_tdx_report_data, get_self_measurement, get_tdx_quote, PlatformError};
 
 type Result<T> = std::result::Result<T, PlatformError>;
 
@@ -17,19 +17,24 @@
 
 //! This module provides SGX platform related functions like getting local
 //! report and transform into a remotely verifiable quote.
+//!
+//! Quotes are generated by the quoting enclave managed by AESM. Where AESM is
+//! unavailable, e.g., in containers without the AESM socket, ECDSA quotes are
+//! generated by the quoting enclave loaded by the DCAP quote library in the
+//! untrusted part of this process instead.
 #![cfg(feature = "mesalock_sgx")]
 
 use super::Result;
-use log::debug;
+use log::{debug, warn};
 use sgx_crypto::ecc::EcPublicKey;
 use sgx_crypto::sha::Sha256;
 use sgx_rand::{RdRand, Rng};
 use sgx_tse::{EnclaveReport, EnclaveTarget};
-use sgx_types::error::SgxStatus;
 use sgx_types::error::SgxStatus::Success;
+use sgx_types::error::{Quote3Error, SgxStatus};
 use sgx_types::types::*;
-#[derive(thiserror::Error, Debug)]
 
+#[derive(thiserror::Error, Debug)]
 pub enum PlatformError {
     #[error("Failed to call {0}: {1}")]
     OCallError(String, SgxStatus),
@@ -52,6 +57,12 @@ pub enum PlatformError {
     SgxRngError(std::io::Error),
     #[error("Other SGX platform error: {0}")]
     Others(SgxStatus),
+    #[error("Failed to call {0} of the DCAP quote library: {1:?}")]
+    DcapQlError(String, Quote3Error),
+    #[error("The quote from the DCAP quote library does not match the report of the enclave")]
+    DcapQlQuoteMismatch,
+    #[error("No quote provider is available. AESM: {0}. DCAP quote library: {1}")]
+    NoQuoteProvider(Box<PlatformError>, Box<PlatformError>),
 }
 
 extern "C" {
@@ -78,6 +89,61 @@ extern "C" {
         p_quote: *mut u8,
         quote_size: u32,
     ) -> SgxStatus;
+
+    /// Ocall to get the target information of the quoting enclave loaded by
+    /// the DCAP quote library.
+    fn ocall_sgx_qe_get_target_info(
+        p_retval: *mut Quote3Error,
+        p_target_info: *mut TargetInfo,
+    ) -> SgxStatus;
+
+    /// Ocall to get the size of the quote from the DCAP quote library.
+    fn ocall_sgx_qe_get_quote_size(p_retval: *mut Quote3Error, p_quote_size: *mut u32)
+        -> SgxStatus;
+
+    /// Ocall to generate a quote with the DCAP quote library.
+    fn ocall_sgx_qe_get_quote(
+        p_retval: *mut Quote3Error,
+        p_report: *const Report,
+        quote_size: u32,
+        p_quote: *mut u8,
+    ) -> SgxStatus;
+}
+
+/// Offset of the report body in a quote of version 3.
+const QUOTE_REPORT_BODY_OFFSET: usize = 48;
+
+/// Quote the report of the enclave with `pub_k` in its report data. For EPID,
+/// `spid` is filled into the attestation key ID selected by AESM. ECDSA quotes
+/// are generated with the DCAP quote library if AESM is unavailable.
+pub(crate) fn quote_enclave_report(
+    pub_k: EcPublicKey,
+    spid: &Spid,
+    ecdsa: bool,
+) -> Result<Vec<u8>> {
+    let aesm_error = match init_sgx_quote() {
+        Ok((mut ak_id, qe_target_info)) => {
+            // For IAS-based attestation, we need to fill our SPID (obtained
+            // from Intel) into the attestation key id. For DCAP-based
+            // attestation, SPID should be 0
+            const SPID_OFFSET: usize = std::mem::size_of::<QlAttKeyId>();
+            ak_id.att_key_id[SPID_OFFSET..(SPID_OFFSET + spid.id.len())].clone_from_slice(&spid.id);
+
+            let sgx_report = create_sgx_isv_enclave_report(pub_k, qe_target_info)?;
+            return get_sgx_quote(&ak_id, sgx_report);
+        }
+        Err(e) if ecdsa => e,
+        Err(e) => return Err(e),
+    };
+
+    warn!(
+        "Cannot initialize quote with AESM ({}), falling back to the DCAP quote library",
+        aesm_error
+    );
+    let qe_target_info = init_dcap_ql_quote()
+        .map_err(|e| PlatformError::NoQuoteProvider(Box::new(aesm_error), Box::new(e)))?;
+    let sgx_report = create_sgx_isv_enclave_report(pub_k, qe_target_info)?;
+    get_dcap_ql_quote(sgx_report)
 }
 
 /// Initialize SGX quote, return attestation key ID selected by the platform and
@@ -103,6 +169,94 @@ pub(crate) fn init_sgx_quote() -> Result<(AttKeyId, TargetInfo)> {
     Ok((ak_id, ti))
 }
 
+/// Get the target information of the quoting enclave loaded by the DCAP quote
+/// library, for creating report that only the QE can verify.
+pub(crate) fn init_dcap_ql_quote() -> Result<TargetInfo> {
+    debug!("qe_get_target_info");
+    let mut ti = TargetInfo::default();
+    let mut rt = Quote3Error::ErrorUnexpected;
+
+    let res = unsafe { ocall_sgx_qe_get_target_info(&mut rt as _, &mut ti as _) };
+
+    if res != Success {
+        return Err(PlatformError::OCallError(
+            "ocall_sgx_qe_get_target_info".to_string(),
+            res,
+        ));
+    }
+    if rt != Quote3Error::Success {
+        return Err(PlatformError::DcapQlError(
+            "sgx_qe_get_target_info".to_string(),
+            rt,
+        ));
+    }
+
+    Ok(ti)
+}
+
+/// Get quote of the enclave's local report with the DCAP quote library.
+pub(crate) fn get_dcap_ql_quote(report: Report) -> Result<Vec<u8>> {
+    let mut rt = Quote3Error::ErrorUnexpected;
+    let mut quote_len: u32 = 0;
+
+    let res = unsafe { ocall_sgx_qe_get_quote_size(&mut rt as _, &mut quote_len as _) };
+
+    if res != Success {
+        return Err(PlatformError::OCallError(
+            "ocall_sgx_qe_get_quote_size".to_string(),
+            res,
+        ));
+    }
+    if rt != Quote3Error::Success {
+        return Err(PlatformError::DcapQlError(
+            "sgx_qe_get_quote_size".to_string(),
+            rt,
+        ));
+    }
+
+    let mut quote = vec![0; quote_len as usize];
+
+    debug!("ocall_sgx_qe_get_quote");
+    let res = unsafe {
+        ocall_sgx_qe_get_quote(&mut rt as _, &report as _, quote_len, quote.as_mut_ptr())
+    };
+
+    if res != Success {
+        return Err(PlatformError::OCallError(
+            "ocall_sgx_qe_get_quote".to_string(),
+            res,
+        ));
+    }
+    if rt != Quote3Error::Success {
+        return Err(PlatformError::DcapQlError(
+            "sgx_qe_get_quote".to_string(),
+            rt,
+        ));
+    }
+
+    // The DCAP quote library returns no QE report to check the quote against,
+    // so at least make sure that the untrusted SW stack quoted our report.
+    let report_body = unsafe {
+        std::slice::from_raw_parts(
+            &report.body as *const ReportBody as *const u8,
+            std::mem::size_of::<ReportBody>(),
+        )
+    };
+    if quote.get(QUOTE_REPORT_BODY_OFFSET..QUOTE_REPORT_BODY_OFFSET + report_body.len())
+        != Some(report_body)
+    {
+        return Err(PlatformError::DcapQlQuoteMismatch);
+    }
+
+    Ok(quote)
+}
+
+/// MRENCLAVE of the enclave itself.
+pub(crate) fn get_self_measurement() -> Result<[u8; 32]> {
+    let target_info = TargetInfo::for_self().map_err(PlatformError::GetSelfTargetInfoError)?;
+    Ok(target_info.mr_enclave.m)
+}
+
 /// Create report of the enclave with target_info.
 pub(crate) fn create_sgx_isv_enclave_report(
     pub_k: EcPublicKey,
@@ -0,0 +1,113 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! This module provides the quotes of a TDX guest, i.e., a trust domain (TD)
+//! running the services as ordinary processes. The quotes are requested from
+//! the TSM reports of the Linux configfs, which the guest kernel gets from the
+//! quoting enclave of the host for the TD.
+
+use super::Result;
+use crate::report::{SgxQuote, TDX_QUOTE_BODY_SIZE};
+use log::debug;
+use sgx_crypto::ecc::EcPublicKey;
+use std::fs;
+use std::path::Path;
+
+/// Directory of the TSM reports in configfs, available since Linux 6.7.
+const TSM_REPORT_DIR: &str = "/sys/kernel/config/tsm/report";
+/// Provider of the TSM reports in TDX guests
+const TDX_GUEST_PROVIDER: &str = "tdx_guest";
+
+#[derive(thiserror::Error, Debug)]
+pub enum PlatformError {
+    #[error("Failed to access the TSM report {0}: {1}")]
+    TsmReport(String, std::io::Error),
+    #[error("The TSM report is provided by {0}, not a TDX guest")]
+    NotTdxGuest(String),
+    #[error("The TSM report was written by another process while it was read")]
+    TsmReportRace,
+    #[error("Failed to parse the TD quote: {0}")]
+    InvalidQuote(String),
+}
+
+fn tsm_error(path: &Path) -> impl FnOnce(std::io::Error) -> PlatformError + '_ {
+    move |e| PlatformError::TsmReport(path.display().to_string(), e)
+}
+
+/// Create the report data binding the quote to `pub_k`, laid out like the
+/// report data of SGX enclaves.
+pub(crate) fn create_tdx_report_data(pub_k: EcPublicKey) -> [u8; 64] {
+    debug!("create_tdx_report_data");
+    let mut report_data = [0u8; 64];
+    let mut pub_k_gx = pub_k.public_key().gx;
+    pub_k_gx.reverse();
+    let mut pub_k_gy = pub_k.public_key().gy;
+    pub_k_gy.reverse();
+    report_data[..32].clone_from_slice(&pub_k_gx);
+    report_data[32..].clone_from_slice(&pub_k_gy);
+    report_data
+}
+
+/// Get a quote of this TD with `report_data`.
+pub(crate) fn get_tdx_quote(report_data: &[u8; 64]) -> Result<Vec<u8>> {
+    let name = format!("teaclave-{}", uuid::Uuid::new_v4().to_simple());
+    let dir = Path::new(TSM_REPORT_DIR).join(name);
+    fs::create_dir(&dir).map_err(tsm_error(&dir))?;
+    let quote = read_tsm_report(&dir, report_data);
+    // The kernel releases the report along with its directory.
+    if let Err(e) = fs::remove_dir(&dir) {
+        log::warn!("Failed to remove {}: {}", dir.display(), e);
+    }
+    quote
+}
+
+fn read_tsm_report(dir: &Path, report_data: &[u8; 64]) -> Result<Vec<u8>> {
+    let read_to_string = |name: &str| -> Result<String> {
+        let path = dir.join(name);
+        let value = fs::read_to_string(&path).map_err(tsm_error(&path))?;
+        Ok(value.trim().to_string())
+    };
+
+    let provider = read_to_string("provider")?;
+    if provider != TDX_GUEST_PROVIDER {
+        return Err(PlatformError::NotTdxGuest(provider));
+    }
+
+    let inblob = dir.join("inblob");
+    fs::write(&inblob, report_data).map_err(tsm_error(&inblob))?;
+    // The generation counts the writes to the report, so a quote of the
+    // report data of another writer is told apart.
+    let generation = read_to_string("generation")?;
+    let outblob = dir.join("outblob");
+    let quote = fs::read(&outblob).map_err(tsm_error(&outblob))?;
+    if read_to_string("generation")? != generation {
+        return Err(PlatformError::TsmReportRace);
+    }
+    Ok(quote)
+}
+
+/// Measurement of the TD itself, i.e., the digest of its MRTD and RTMRs
+/// which stands for MRENCLAVE, taken from a quote.
+pub(crate) fn get_self_measurement() -> Result<[u8; 32]> {
+    let quote = get_tdx_quote(&[0u8; 64])?;
+    let body = quote
+        .get(..TDX_QUOTE_BODY_SIZE)
+        .ok_or_else(|| PlatformError::InvalidQuote("too short".to_string()))?;
+    let quote =
+        SgxQuote::parse_from(body).map_err(|e| PlatformError::InvalidQuote(e.to_string()))?;
+    Ok(quote.isv_enclave_report.mr_enclave)
+}
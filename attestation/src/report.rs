@@ -21,6 +21,7 @@
 
 use crate::AttestationError;
 use crate::EndorsedAttestationReport;
+use crate::ReportFormat;
 
 use std::convert::TryFrom;
 use std::fmt;
@@ -49,6 +50,26 @@ static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
     &webpki::RSA_PKCS1_3072_8192_SHA384,
 ];
 
+/// Algorithms of the signatures over attestation reports: RSA for IAS and the
+/// DCAP server by default, ECDSA for the DCAP server with an EC signing key.
+static REPORT_SIG_ALGS: SignatureAlgorithms = &[
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::ECDSA_P256_SHA256,
+];
+
+/// The DEBUG flag in the first byte of the attributes of an enclave
+const SGX_FLAGS_DEBUG: u8 = 0x02;
+
+/// The DEBUG flag in the first byte of the attributes of a TD
+const TDX_ATTRIBUTES_DEBUG: u8 = 0x01;
+
+/// TEE type of TD quotes in their header
+const TDX_TEE_TYPE: u32 = 0x81;
+
+/// Size of a TD quote without its signature, i.e., the header and the TD
+/// report, which is the quote body in attestation reports.
+pub const TDX_QUOTE_BODY_SIZE: usize = 48 + 584;
+
 /// A report generated by an enclave that contains measurement, identity and
 /// other data related to enclave.
 ///
@@ -197,6 +218,183 @@ impl SgxEnclaveReport {
     }
 }
 
+/// The report of a TD, i.e., a TDX guest, in TD quotes. The measurements of
+/// a TD are SHA-384 digests, which do not fit the measurements of enclaves,
+/// so a TD is identified by the digests of them instead. See
+/// [`TdReport::mr_enclave`] and [`TdReport::mr_signer`].
+pub struct TdReport {
+    /// Security version numbers of the TDX module and its components
+    pub tee_tcb_svn: [u8; 16],
+    /// Measurement of the TDX module
+    pub mr_seam: [u8; 48],
+    /// Measurement of the signer of the TDX module
+    pub mr_signer_seam: [u8; 48],
+    /// Attributes of the TDX module
+    pub seam_attributes: [u8; 8],
+    /// Attributes of the TD, for example, whether it is debuggable
+    pub td_attributes: [u8; 8],
+    /// CPU extended features allowed in the TD
+    pub xfam: [u8; 8],
+    /// Measurement of the initial contents of the TD, i.e., its firmware
+    pub mr_td: [u8; 48],
+    /// ID of the configuration of the TD, set by the host
+    pub mr_config_id: [u8; 48],
+    /// ID of the owner of the TD, set by the host
+    pub mr_owner: [u8; 48],
+    /// ID of the configuration of the owner, set by the host
+    pub mr_owner_config: [u8; 48],
+    /// Runtime measurements extended by the firmware (0), the kernel and its
+    /// command line (1 and 2), and the applications (3)
+    pub rtmr: [[u8; 48]; 4],
+    /// Set of data used for binding the report to the TLS key
+    pub report_data: [u8; 64],
+}
+
+impl std::fmt::Debug for TdReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "tee_tcb_svn: {:?}", self.tee_tcb_svn)?;
+        writeln!(f, "mr_seam: {}", hex::encode(self.mr_seam))?;
+        writeln!(f, "td_attributes: {:?}", self.td_attributes)?;
+        writeln!(f, "mr_td: {}", hex::encode(self.mr_td))?;
+        for (i, rtmr) in self.rtmr.iter().enumerate() {
+            writeln!(f, "rtmr{}: {}", i, hex::encode(rtmr))?;
+        }
+        writeln!(f, "report_data: {:?}", &self.report_data.to_vec())
+    }
+}
+
+impl fmt::Display for TdReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "TCB version (hex): {}", hex::encode(self.tee_tcb_svn))?;
+        writeln!(
+            f,
+            "Measurement of the TDX module (hex): {}",
+            hex::encode(self.mr_seam)
+        )?;
+        writeln!(
+            f,
+            "Attributes of the TD (hex): {}",
+            hex::encode(self.td_attributes)
+        )?;
+        writeln!(f, "TD measurement (hex): {}", hex::encode(self.mr_td))?;
+        for (i, rtmr) in self.rtmr.iter().enumerate() {
+            writeln!(f, "RTMR{} (hex): {}", i, hex::encode(rtmr))?;
+        }
+        writeln!(f, "TD owner (hex): {}", hex::encode(self.mr_owner))?;
+        writeln!(
+            f,
+            "The value of REPORT (hex): {}",
+            hex::encode(self.report_data)
+        )
+    }
+}
+
+impl TdReport {
+    /// Parse bytes of a TD report of TD quotes (version 4) into `TdReport`.
+    pub fn parse_from<'a>(bytes: &'a [u8]) -> Result<Self> {
+        let mut pos: usize = 0;
+        let mut take = |n: usize| -> Result<&'a [u8]> {
+            if n > 0 && bytes.len() >= pos + n {
+                let ret = &bytes[pos..pos + n];
+                pos += n;
+                Ok(ret)
+            } else {
+                bail!("Quote parsing error.")
+            }
+        };
+
+        // off 48, size 16
+        let tee_tcb_svn = <[u8; 16]>::try_from(take(16)?)?;
+        // off 64, size 48
+        let mr_seam = <[u8; 48]>::try_from(take(48)?)?;
+        // off 112, size 48
+        let mr_signer_seam = <[u8; 48]>::try_from(take(48)?)?;
+        // off 160, size 8
+        let seam_attributes = <[u8; 8]>::try_from(take(8)?)?;
+        // off 168, size 8
+        let td_attributes = <[u8; 8]>::try_from(take(8)?)?;
+        // off 176, size 8
+        let xfam = <[u8; 8]>::try_from(take(8)?)?;
+        // off 184, size 48
+        let mr_td = <[u8; 48]>::try_from(take(48)?)?;
+        // off 232, size 48
+        let mr_config_id = <[u8; 48]>::try_from(take(48)?)?;
+        // off 280, size 48
+        let mr_owner = <[u8; 48]>::try_from(take(48)?)?;
+        // off 328, size 48
+        let mr_owner_config = <[u8; 48]>::try_from(take(48)?)?;
+        // off 376, size 48 * 4
+        let mut rtmr = [[0u8; 48]; 4];
+        for r in rtmr.iter_mut() {
+            *r = <[u8; 48]>::try_from(take(48)?)?;
+        }
+        // off 568, size 64
+        let report_data = <[u8; 64]>::try_from(take(64)?)?;
+
+        ensure!(pos == bytes.len(), "Quote parsing error.");
+
+        Ok(TdReport {
+            tee_tcb_svn,
+            mr_seam,
+            mr_signer_seam,
+            seam_attributes,
+            td_attributes,
+            xfam,
+            mr_td,
+            mr_config_id,
+            mr_owner,
+            mr_owner_config,
+            rtmr,
+            report_data,
+        })
+    }
+
+    /// The digest standing for MRENCLAVE: SHA-256 of MRTD and RTMR0 to RTMR2,
+    /// which measure the firmware, the kernel and the initial file system
+    /// including the services. RTMR3 is left to the applications.
+    pub fn mr_enclave(&self) -> [u8; 32] {
+        let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+        context.update(&self.mr_td);
+        for rtmr in &self.rtmr[..3] {
+            context.update(rtmr);
+        }
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(context.finish().as_ref());
+        digest
+    }
+
+    /// The digest standing for MRSIGNER: SHA-256 of the IDs of the owner and
+    /// the configuration of the TD, which are zero unless the host sets them.
+    pub fn mr_signer(&self) -> [u8; 32] {
+        let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+        context.update(&self.mr_config_id);
+        context.update(&self.mr_owner);
+        context.update(&self.mr_owner_config);
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(context.finish().as_ref());
+        digest
+    }
+
+    /// The report as the one of an enclave, so that TDs are verified like
+    /// enclaves against the audited measurements.
+    pub fn as_enclave_report(&self) -> SgxEnclaveReport {
+        let mut attributes = [0u8; 16];
+        if self.td_attributes[0] & TDX_ATTRIBUTES_DEBUG != 0 {
+            attributes[0] |= SGX_FLAGS_DEBUG;
+        }
+        SgxEnclaveReport {
+            cpu_svn: self.tee_tcb_svn,
+            misc_select: 0,
+            attributes,
+            mr_enclave: self.mr_enclave(),
+            mr_signer: self.mr_signer(),
+            isv_prod_id: 0,
+            isv_svn: 0,
+            report_data: self.report_data,
+        }
+    }
+}
+
 /// SGX Quote structure version
 #[derive(Debug, PartialEq)]
 pub enum SgxQuoteVersion {
@@ -206,6 +404,8 @@ pub enum SgxQuoteVersion {
     V2(SgxEpidQuoteSigType),
     /// ECDSA quote version
     V3(SgxEcdsaQuoteAkType),
+    /// ECDSA quote version of TDs
+    V4(SgxEcdsaQuoteAkType),
 }
 
 /// Intel EPID attestation signature type
@@ -232,12 +432,15 @@ impl std::fmt::Display for SgxQuoteVersion {
             SgxQuoteVersion::V3(key_type) => {
                 write!(f, "Version 3, ECDSA {:?} attestation key", key_type)
             }
+            SgxQuoteVersion::V4(key_type) => {
+                write!(f, "Version 4 (TDX), ECDSA {:?} attestation key", key_type)
+            }
         }
     }
 }
 
 /// SGX Quote status
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum SgxQuoteStatus {
     /// EPID signature of the ISV enclave QUOTE was verified correctly and the
     /// TCB level of the SGX platform is up-to-date.
@@ -369,8 +572,11 @@ pub struct SgxQuote {
     pub qe_vendor_id: Uuid,
     /// User data
     pub user_data: [u8; 20],
-    /// Report generated by the enclave
+    /// Report generated by the enclave, or the one standing for the report
+    /// of the TD in TD quotes
     pub isv_enclave_report: SgxEnclaveReport,
+    /// Report of the TD in TD quotes
+    pub td_report: Option<TdReport>,
 }
 
 impl std::fmt::Debug for SgxQuote {
@@ -381,7 +587,11 @@ impl std::fmt::Debug for SgxQuote {
         writeln!(f, "isv_svn_pce: {}", self.isv_svn_pce)?;
         writeln!(f, "qe_vendor_id: {}", self.qe_vendor_id)?;
         writeln!(f, "user_data: {:?}", &self.user_data)?;
-        write!(f, "isv_enclave_report: \n{:?}", self.isv_enclave_report)
+        write!(f, "isv_enclave_report: \n{:?}", self.isv_enclave_report)?;
+        if let Some(td_report) = &self.td_report {
+            write!(f, "\ntd_report: \n{:?}", td_report)?;
+        }
+        Ok(())
     }
 }
 
@@ -397,7 +607,11 @@ impl fmt::Display for SgxQuote {
             "Custom user-defined data (hex): {}",
             hex::encode(self.user_data)
         )?;
-        write!(f, "{}", self.isv_enclave_report)
+        write!(f, "{}", self.isv_enclave_report)?;
+        if let Some(td_report) = &self.td_report {
+            write!(f, "{}", td_report)?;
+        }
+        Ok(())
     }
 }
 
@@ -443,11 +657,26 @@ impl SgxQuote {
                 };
                 SgxQuoteVersion::V3(attestation_key_type)
             }
+            4 => {
+                let attestation_key_type = match u16::from_le_bytes(<[u8; 2]>::try_from(take(2)?)?)
+                {
+                    2 => SgxEcdsaQuoteAkType::P256_256,
+                    3 => SgxEcdsaQuoteAkType::P384_384,
+                    _ => bail!("Quote parsing error."),
+                };
+                SgxQuoteVersion::V4(attestation_key_type)
+            }
             _ => bail!("Quote parsing error."),
         };
 
-        // off 4, size 4
+        // off 4, size 4, which is the TEE type in TD quotes
         let gid = u32::from_le_bytes(<[u8; 4]>::try_from(take(4)?)?);
+        let is_td = matches!(version, SgxQuoteVersion::V4(_));
+        // Version 4 is only supported for TD quotes.
+        ensure!(
+            !is_td || gid == TDX_TEE_TYPE,
+            "Quote parsing error: unsupported TEE type."
+        );
 
         // off 8, size 2
         let isv_svn_qe = u16::from_le_bytes(<[u8; 2]>::try_from(take(2)?)?);
@@ -462,8 +691,13 @@ impl SgxQuote {
         // off 28, size 20
         let user_data = <[u8; 20]>::try_from(take(20)?)?;
 
-        // off 48, size 384
-        let isv_enclave_report = SgxEnclaveReport::parse_from(take(384)?)?;
+        // off 48, size 384 for enclaves, or 584 for TDs
+        let (isv_enclave_report, td_report) = if is_td {
+            let td_report = TdReport::parse_from(take(584)?)?;
+            (td_report.as_enclave_report(), Some(td_report))
+        } else {
+            (SgxEnclaveReport::parse_from(take(384)?)?, None)
+        };
 
         ensure!(pos == bytes.len(), "Quote parsing error.");
 
@@ -475,6 +709,7 @@ impl SgxQuote {
             qe_vendor_id,
             user_data,
             isv_enclave_report,
+            td_report,
         })
     }
 }
@@ -491,16 +726,40 @@ pub struct AttestationReport {
     pub sgx_quote_status: SgxQuoteStatus,
     /// Content of the quote
     pub sgx_quote_body: SgxQuote,
+    /// Security advisories applying to the TCB level of the platform, if
+    /// reported by the attestation service
+    pub advisory_ids: Vec<String>,
+    /// Nonce sent along with the quote to the attestation service, if any
+    pub nonce: Option<String>,
 }
 
 impl fmt::Display for AttestationReport {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Report Freshness: {:?}", self.freshness)?;
         writeln!(f, "SGX Quote status: {:?}", self.sgx_quote_status)?;
+        if !self.advisory_ids.is_empty() {
+            writeln!(f, "Advisory IDs: {}", self.advisory_ids.join(", "))?;
+        }
         write!(f, "{}", self.sgx_quote_body)
     }
 }
 
+/// Verify the signature over `report` with the algorithm matching the key of
+/// `signing_cert`.
+fn verify_report_signature(
+    signing_cert: &webpki::EndEntityCert,
+    report: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    for alg in REPORT_SIG_ALGS {
+        match signing_cert.verify_signature(alg, report, signature) {
+            Err(webpki::Error::UnsupportedSignatureAlgorithmForPublicKey) => continue,
+            result => return result.map_err(Error::from),
+        }
+    }
+    Err(webpki::Error::UnsupportedSignatureAlgorithmForPublicKey.into())
+}
+
 impl AttestationReport {
     /// Construct a AttestationReport from a X509 certificate and verify
     /// attestation report with the report_ca_cert which is from the attestation
@@ -509,8 +768,11 @@ impl AttestationReport {
         // Before we reach here, Webpki already verifed the cert is properly signed.
         use crate::cert::*;
 
-        // Extract information for attestation from TLS certification.
-        let x509 = yasna::parse_der(&certs[0].0, X509::load)?;
+        // Extract information for attestation from TLS certification, which
+        // cannot be parsed without the extension of the attestation report.
+        let cert = certs.first().ok_or(AttestationError::NotAttested)?;
+        let x509 =
+            yasna::parse_der(&cert.0, X509::load).map_err(|_| AttestationError::NotAttested)?;
         let tbs_cert: <TbsCert as Asn1Ty>::ValueTy = x509.0;
         let pub_key: <PubKey as Asn1Ty>::ValueTy = ((((((tbs_cert.1).1).1).1).1).1).0;
         let pub_k = (pub_key.1).0;
@@ -521,9 +783,39 @@ impl AttestationReport {
         // Convert to endorsed report
         let report: EndorsedAttestationReport = serde_json::from_slice(&cert_ext_payload)?;
         log::debug!("endorsed attestation report: {:?}", &report);
+        let attn_report = Self::from_endorsed_report(&report, report_ca_cert)?;
+        let sgx_quote_body = &attn_report.sgx_quote_body;
+
+        // According to RFC 5480 `Elliptic Curve Cryptography Subject Public Key
+        // Information', SEC 2.2: ``The first octet of the OCTET STRING
+        // indicates whether the key is compressed or uncompressed. The
+        // uncompressed form is indicated by 0x04 and the compressed form is
+        // indicated by either 0x02 or 0x03 (see 2.3.3 in [SEC1]). The public
+        // key MUST be rejected if any other value is included in the first
+        // octet.''
+        //
+        // We only accept the uncompressed form here.
+        let raw_pub_k = pub_k.to_bytes();
+        let is_uncompressed = raw_pub_k[0] == 4;
+        let pub_k = &raw_pub_k.as_slice()[1..];
+        if !is_uncompressed || pub_k != &sgx_quote_body.isv_enclave_report.report_data[..] {
+            bail!(AttestationError::ReportError);
+        }
+
+        Ok(attn_report)
+    }
 
+    /// Construct a AttestationReport from an endorsed report on its own, e.g.,
+    /// one exported for offline verification, after verifying its signature
+    /// with the report_ca_cert. Unlike `from_cert`, the report is not bound to
+    /// the key of any certificate.
+    pub fn from_endorsed_report(
+        report: &EndorsedAttestationReport,
+        report_ca_cert: &[u8],
+    ) -> Result<Self> {
         // Verify report's signature
-        let signing_cert = webpki::EndEntityCert::try_from(report.certs[0].as_ref())?;
+        let signing_cert = report.certs.first().ok_or(AttestationError::ReportError)?;
+        let signing_cert = webpki::EndEntityCert::try_from(signing_cert.as_slice())?;
 
         let trust_anchors = vec![webpki::TrustAnchor::try_from_cert_der(report_ca_cert)?];
         let chain: Vec<&[u8]> = if report.certs.len() > 1 {
@@ -546,14 +838,18 @@ impl AttestationReport {
         )?;
 
         // Verify the signature against the signing cert
-        signing_cert.verify_signature(
-            &webpki::RSA_PKCS1_2048_8192_SHA256,
-            &report.report,
-            &report.signature,
-        )?;
+        verify_report_signature(&signing_cert, &report.report, &report.signature)?;
 
         // Verify and extract information from attestation report
-        let attn_report: Value = serde_json::from_slice(&report.report)?;
+        match report.format {
+            ReportFormat::Ias => Self::from_ias_report(&report.report),
+            ReportFormat::AzureJwt => Self::from_azure_token(&report.report),
+        }
+    }
+
+    /// Extract information from a JSON report of IAS or the DCAP server.
+    fn from_ias_report(report: &[u8]) -> Result<Self> {
+        let attn_report: Value = serde_json::from_slice(report)?;
         log::trace!("attn_report: {}", attn_report);
 
         // Verify API version is supported
@@ -583,6 +879,21 @@ impl AttestationReport {
             SgxQuoteStatus::from(status_string)
         };
 
+        // Get advisory IDs, which are absent if the TCB level is up to date
+        let advisory_ids = match attn_report["advisoryIDs"].as_array() {
+            Some(ids) => ids
+                .iter()
+                .map(|id| {
+                    id.as_str()
+                        .map(String::from)
+                        .ok_or_else(|| Error::new(AttestationError::ReportError))
+                })
+                .collect::<Result<Vec<String>>>()?,
+            None => Vec::new(),
+        };
+
+        let nonce = attn_report["nonce"].as_str().map(String::from);
+
         // Get quote body
         let sgx_quote_body = {
             let quote_encoded = attn_report["isvEnclaveQuoteBody"]
@@ -592,30 +903,138 @@ impl AttestationReport {
             SgxQuote::parse_from(quote_raw.as_slice())?
         };
 
-        // According to RFC 5480 `Elliptic Curve Cryptography Subject Public Key
-        // Information', SEC 2.2: ``The first octet of the OCTET STRING
-        // indicates whether the key is compressed or uncompressed. The
-        // uncompressed form is indicated by 0x04 and the compressed form is
-        // indicated by either 0x02 or 0x03 (see 2.3.3 in [SEC1]). The public
-        // key MUST be rejected if any other value is included in the first
-        // octet.''
-        //
-        // We only accept the uncompressed form here.
-        let raw_pub_k = pub_k.to_bytes();
-        let is_uncompressed = raw_pub_k[0] == 4;
-        let pub_k = &raw_pub_k.as_slice()[1..];
-        if !is_uncompressed || pub_k != &sgx_quote_body.isv_enclave_report.report_data[..] {
-            bail!(AttestationError::ReportError);
+        Ok(Self {
+            freshness,
+            sgx_quote_status,
+            sgx_quote_body,
+            advisory_ids,
+            nonce,
+        })
+    }
+
+    /// Extract information from the claims of a token issued by Microsoft
+    /// Azure Attestation.
+    /// <https://learn.microsoft.com/en-us/azure/attestation/claim-sets>
+    ///
+    /// The token only carries the identity of the enclave, so the other fields
+    /// of the quote body are left zero. Azure Attestation only issues tokens
+    /// for quotes accepted by its attestation policy, whose status is OK.
+    fn from_azure_token(signing_input: &[u8]) -> Result<Self> {
+        // The signature was verified as RSASSA-PKCS1-v1_5 with SHA-256
+        let header = token_part(signing_input, 0)?;
+        ensure!(
+            header["alg"].as_str() == Some("RS256"),
+            AttestationError::ReportError
+        );
+
+        let claims = token_part(signing_input, 1)?;
+        log::trace!("claims: {}", claims);
+        ensure!(
+            claims["x-ms-attestation-type"].as_str() == Some("sgx"),
+            AttestationError::ReportError
+        );
+
+        // Get token freshness
+        let freshness = {
+            let issued_at = claims["iat"]
+                .as_u64()
+                .ok_or_else(|| Error::new(AttestationError::ReportError))?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let token_freshness = now
+                .checked_sub(issued_at)
+                .ok_or_else(|| Error::new(AttestationError::ReportError))?;
+            std::time::Duration::from_secs(token_freshness)
+        };
+
+        let hex_claim = |name: &str| -> Result<Vec<u8>> {
+            let value = claims[name]
+                .as_str()
+                .ok_or_else(|| Error::new(AttestationError::ReportError))?;
+            Ok(hex::decode(value)?)
+        };
+        let u16_claim = |name: &str| -> Result<u16> {
+            let value = claims[name]
+                .as_u64()
+                .ok_or_else(|| Error::new(AttestationError::ReportError))?;
+            Ok(u16::try_from(value)?)
+        };
+
+        // Only the DEBUG flag of the attributes is claimed
+        let mut attributes = [0u8; 16];
+        if claims["x-ms-sgx-is-debuggable"]
+            .as_bool()
+            .ok_or_else(|| Error::new(AttestationError::ReportError))?
+        {
+            attributes[0] |= SGX_FLAGS_DEBUG;
         }
 
+        let isv_enclave_report = SgxEnclaveReport {
+            cpu_svn: [0u8; 16],
+            misc_select: 0,
+            attributes,
+            mr_enclave: <[u8; 32]>::try_from(hex_claim("x-ms-sgx-mrenclave")?.as_slice())?,
+            mr_signer: <[u8; 32]>::try_from(hex_claim("x-ms-sgx-mrsigner")?.as_slice())?,
+            isv_prod_id: u16_claim("x-ms-sgx-product-id")?,
+            isv_svn: u16_claim("x-ms-sgx-svn")?,
+            report_data: <[u8; 64]>::try_from(hex_claim("x-ms-sgx-report-data")?.as_slice())?,
+        };
+        let sgx_quote_body = SgxQuote {
+            version: SgxQuoteVersion::V3(SgxEcdsaQuoteAkType::P256_256),
+            gid: 0,
+            isv_svn_qe: 0,
+            isv_svn_pce: 0,
+            qe_vendor_id: Uuid::nil(),
+            user_data: [0u8; 20],
+            isv_enclave_report,
+            td_report: None,
+        };
+
+        let nonce = claims["nonce"].as_str().map(String::from);
+
         Ok(Self {
             freshness,
-            sgx_quote_status,
+            sgx_quote_status: SgxQuoteStatus::OK,
             sgx_quote_body,
+            advisory_ids: Vec::new(),
+            nonce,
         })
     }
 }
 
+/// Decode the part at `index` of the signing input of a JWT, i.e., the header
+/// at 0 and the claims at 1.
+pub(crate) fn token_part(signing_input: &[u8], index: usize) -> Result<Value> {
+    let part = signing_input
+        .split(|b| *b == b'.')
+        .nth(index)
+        .ok_or_else(|| Error::new(AttestationError::ReportError))?;
+    let decoded = base64::decode_config(part, base64::URL_SAFE_NO_PAD)?;
+    Ok(serde_json::from_slice(&decoded)?)
+}
+
+impl EndorsedAttestationReport {
+    /// Content of the report, i.e., the JSON report of IAS or the claims of
+    /// the token of Azure Attestation, without verifying its signature.
+    pub fn body(&self) -> Result<Value> {
+        match self.format {
+            ReportFormat::Ias => Ok(serde_json::from_slice(&self.report)?),
+            ReportFormat::AzureJwt => token_part(&self.report, 1),
+        }
+    }
+}
+
+/// Check that an attestation report echoes the nonce sent along with the
+/// quote, so that a report of the same quote acquired before cannot be
+/// replayed by the untrusted network.
+pub fn verify_report_nonce(report: &EndorsedAttestationReport, nonce: &str) -> Result<()> {
+    let attn_report = report.body()?;
+    ensure!(
+        attn_report["nonce"].as_str() == Some(nonce),
+        AttestationError::NonceMismatch
+    );
+    Ok(())
+}
+
 #[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
 pub mod tests {
     use super::*;
@@ -747,6 +1166,53 @@ pub mod tests {
         );
     }
 
+    pub fn test_td_quote_parse_from() {
+        let mut quote_raw = vec![0u8; TDX_QUOTE_BODY_SIZE];
+        // Version 4, ECDSA P256 attestation key, TEE type of TDX
+        quote_raw[0] = 4;
+        quote_raw[2] = 2;
+        quote_raw[4] = 0x81;
+        // TCB version, TD attributes with DEBUG, MRTD and report data
+        quote_raw[48] = 3;
+        quote_raw[168] = 1;
+        quote_raw[184..232].copy_from_slice(&[7u8; 48]);
+        quote_raw[568..].copy_from_slice(&[9u8; 64]);
+        let sgx_quote = SgxQuote::parse_from(quote_raw.as_slice()).unwrap();
+
+        assert_eq!(
+            sgx_quote.version,
+            SgxQuoteVersion::V4(SgxEcdsaQuoteAkType::P256_256)
+        );
+        let td_report = sgx_quote.td_report.as_ref().unwrap();
+        assert_eq!(td_report.mr_td, [7u8; 48]);
+        let isv_enclave_report = &sgx_quote.isv_enclave_report;
+        assert_eq!(isv_enclave_report.cpu_svn[0], 3);
+        assert_eq!(isv_enclave_report.attributes[0], SGX_FLAGS_DEBUG);
+        assert_eq!(isv_enclave_report.mr_enclave, td_report.mr_enclave());
+        assert_eq!(isv_enclave_report.mr_signer, td_report.mr_signer());
+        assert_eq!(isv_enclave_report.report_data, [9u8; 64]);
+
+        // Changing any runtime measurement but the last changes the TD.
+        quote_raw[376] = 1;
+        let changed = SgxQuote::parse_from(quote_raw.as_slice()).unwrap();
+        assert_ne!(
+            changed.isv_enclave_report.mr_enclave,
+            isv_enclave_report.mr_enclave
+        );
+        quote_raw[376] = 0;
+        quote_raw[520] = 1;
+        let extended = SgxQuote::parse_from(quote_raw.as_slice()).unwrap();
+        assert_eq!(
+            extended.isv_enclave_report.mr_enclave,
+            isv_enclave_report.mr_enclave
+        );
+
+        // Version 4 quotes of enclaves are not supported.
+        quote_raw[4] = 0;
+        assert!(SgxQuote::parse_from(quote_raw.as_slice()).is_err());
+        assert!(SgxQuote::parse_from(&quote_raw[..TDX_QUOTE_BODY_SIZE - 1]).is_err());
+    }
+
     pub fn test_attestation_report_from_cert() {
         let tls_ra_cert = tls_ra_cert_der_v4();
         let dcap_root_ca_cert = dcap_root_ca_cert_der();
@@ -756,6 +1222,10 @@ pub mod tests {
 
         let report = report.unwrap();
         assert_eq!(report.sgx_quote_status, SgxQuoteStatus::OK);
+
+        // An endorsed report without its signing certificate is refused.
+        let unsigned = EndorsedAttestationReport::default();
+        assert!(AttestationReport::from_endorsed_report(&unsigned, &dcap_root_ca_cert).is_err());
     }
 
     pub fn test_attestation_report_from_cert_api_version_not_compatible() {
@@ -765,4 +1235,73 @@ pub mod tests {
         let report = AttestationReport::from_cert(&certs, &ias_root_ca_cert);
         assert!(report.is_err());
     }
+
+    fn endorsed_report(report: &[u8], format: ReportFormat) -> EndorsedAttestationReport {
+        EndorsedAttestationReport {
+            report: report.to_vec(),
+            format,
+            ..Default::default()
+        }
+    }
+
+    fn azure_token(claims: &Value) -> Vec<u8> {
+        let encode = |v: &Value| base64::encode_config(v.to_string(), base64::URL_SAFE_NO_PAD);
+        let header = json!({ "alg": "RS256", "kid": "key", "typ": "JWT" });
+        format!("{}.{}", encode(&header), encode(claims)).into_bytes()
+    }
+
+    fn azure_claims() -> Value {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        json!({
+            "iat": now - 10,
+            "x-ms-attestation-type": "sgx",
+            "x-ms-sgx-is-debuggable": true,
+            "x-ms-sgx-mrenclave": hex::encode([1u8; 32]),
+            "x-ms-sgx-mrsigner": hex::encode([2u8; 32]),
+            "x-ms-sgx-product-id": 3,
+            "x-ms-sgx-svn": 4,
+            "x-ms-sgx-report-data": hex::encode([5u8; 64]),
+            "nonce": "nonce",
+        })
+    }
+
+    pub fn test_verify_report_nonce() {
+        let mut attn_report = attesation_report();
+        let report = endorsed_report(attn_report.to_string().as_bytes(), ReportFormat::Ias);
+        assert!(verify_report_nonce(&report, "nonce").is_err());
+
+        attn_report["nonce"] = json!("nonce");
+        let report = endorsed_report(attn_report.to_string().as_bytes(), ReportFormat::Ias);
+        assert!(verify_report_nonce(&report, "nonce").is_ok());
+        assert!(verify_report_nonce(&report, "another nonce").is_err());
+
+        let report = endorsed_report(&azure_token(&azure_claims()), ReportFormat::AzureJwt);
+        assert!(verify_report_nonce(&report, "nonce").is_ok());
+        assert!(verify_report_nonce(&report, "another nonce").is_err());
+    }
+
+    pub fn test_attestation_report_from_azure_token() {
+        let report = AttestationReport::from_azure_token(&azure_token(&azure_claims())).unwrap();
+        let enclave_report = &report.sgx_quote_body.isv_enclave_report;
+        assert!(report.freshness >= Duration::from_secs(10));
+        assert_eq!(report.sgx_quote_status, SgxQuoteStatus::OK);
+        assert_eq!(enclave_report.attributes[0], SGX_FLAGS_DEBUG);
+        assert_eq!(enclave_report.mr_enclave, [1u8; 32]);
+        assert_eq!(enclave_report.mr_signer, [2u8; 32]);
+        assert_eq!(enclave_report.isv_prod_id, 3);
+        assert_eq!(enclave_report.isv_svn, 4);
+        assert_eq!(enclave_report.report_data, [5u8; 64]);
+        assert_eq!(report.nonce.as_deref(), Some("nonce"));
+
+        let mut claims = azure_claims();
+        claims["x-ms-attestation-type"] = json!("sevsnpvm");
+        assert!(AttestationReport::from_azure_token(&azure_token(&claims)).is_err());
+
+        let mut claims = azure_claims();
+        claims["x-ms-sgx-mrenclave"] = json!(hex::encode([1u8; 16]));
+        assert!(AttestationReport::from_azure_token(&azure_token(&claims)).is_err());
+    }
 }
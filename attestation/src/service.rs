@@ -22,6 +22,7 @@ use crate::platform;
 use crate::AttestationAlgorithm;
 use crate::AttestationServiceConfig;
 use crate::EndorsedAttestationReport;
+use crate::ReportFormat;
 
 use std::collections::HashMap;
 use std::io::{ErrorKind, Read, Write};
@@ -30,7 +31,7 @@ use std::sync::Arc;
 
 use anyhow::{anyhow, bail, Result};
 use log::{debug, trace, warn};
-use serde_json::json;
+use serde_json::{json, Value};
 use sgx_crypto::ecc::EcPublicKey;
 
 /// Root certification of the DCAP attestation service provider.
@@ -40,6 +41,13 @@ const DCAP_ROOT_CA_CERT: &str = include_str!("../../config/keys/dcap_root_ca_cer
 /// URL path to get the report from the attestation service.
 const AS_REPORT_URL: &str = "/sgx/dev/attestation/v4/report";
 
+/// URL path to attest an SGX quote with Microsoft Azure Attestation.
+const MAA_ATTEST_URL: &str = "/attest/SgxEnclave?api-version=2022-08-01";
+
+/// URL path to get the certificates of the signing keys of Microsoft Azure
+/// Attestation.
+const MAA_CERTS_URL: &str = "/certs";
+
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum AttestationServiceError {
     #[error("Invalid attestation service address.")]
@@ -75,24 +83,9 @@ impl EndorsedAttestationReport {
         att_service_cfg: &AttestationServiceConfig,
         pub_k: EcPublicKey,
     ) -> anyhow::Result<Self> {
-        let (mut ak_id, qe_target_info) = platform::init_sgx_quote()?;
-
-        // For IAS-based attestation, we need to fill our SPID (obtained from Intel)
-        // into the attestation key id. For DCAP-based attestation, SPID should be 0
-        const SPID_OFFSET: usize = std::mem::size_of::<sgx_types::types::QlAttKeyId>();
-        ak_id.att_key_id[SPID_OFFSET..(SPID_OFFSET + att_service_cfg.spid.id.len())]
-            .clone_from_slice(&att_service_cfg.spid.id);
-
-        let sgx_report = platform::create_sgx_isv_enclave_report(pub_k, qe_target_info)?;
-        let quote = platform::get_sgx_quote(&ak_id, sgx_report)?;
-        let as_report = get_report(
-            &att_service_cfg.algo,
-            &att_service_cfg.as_url,
-            &att_service_cfg.api_key,
-            &quote,
-        )?;
-
-        Ok(as_report)
+        let ecdsa = !matches!(att_service_cfg.algo, AttestationAlgorithm::SgxEpid);
+        let quote = platform::quote_enclave_report(pub_k, &att_service_cfg.spid, ecdsa)?;
+        endorse(att_service_cfg, &quote)
     }
 }
 
@@ -104,19 +97,81 @@ impl EndorsedAttestationReport {
             crate::AttestationAlgorithm::SgxEpid => {
                 platform::get_sgx_epid_quote(&att_service_cfg.spid, report_data)?
             }
-            crate::AttestationAlgorithm::SgxEcdsa => {
+            crate::AttestationAlgorithm::SgxEcdsa | crate::AttestationAlgorithm::SgxMaa => {
                 platform::get_sgx_dcap_quote(&att_service_cfg.spid, report_data)?
             }
         };
-        crate::service::get_report(
-            &att_service_cfg.algo,
-            &att_service_cfg.as_url,
-            &att_service_cfg.api_key,
-            &quote,
-        )
+        endorse(att_service_cfg, &quote)
     }
 }
 
+#[cfg(feature = "tdx")]
+impl EndorsedAttestationReport {
+    /// Get the quote of the TD endorsed by the DCAP attestation service,
+    /// which verifies TD quotes as well as the ones of SGX enclaves. TD
+    /// quotes are never signed with EPID, nor attested by Azure Attestation
+    /// with the API of SGX enclaves.
+    pub fn new(att_service_cfg: &AttestationServiceConfig, pub_k: EcPublicKey) -> Result<Self> {
+        if !matches!(att_service_cfg.algo, AttestationAlgorithm::SgxEcdsa) {
+            bail!("TDX guests only attest with the sgx_ecdsa algorithm");
+        }
+        let report_data = platform::create_tdx_report_data(pub_k);
+        let quote = platform::get_tdx_quote(&report_data)?;
+        endorse(att_service_cfg, &quote)
+    }
+}
+
+/// An attestation service which verifies quotes of the platform and endorses
+/// them with signed reports.
+trait AttestationProvider {
+    /// Get the report of `quote` from the service at `url`, which echoes
+    /// `nonce` in the signed report.
+    fn get_report(
+        &self,
+        url: &url::Url,
+        quote: &[u8],
+        nonce: &str,
+    ) -> Result<EndorsedAttestationReport>;
+}
+
+/// Attestation services with the API of Intel Attestation Service, i.e., IAS
+/// itself for EPID and the DCAP server for ECDSA.
+struct IntelAttestationService<'a> {
+    algo: &'a AttestationAlgorithm,
+    api_key: &'a str,
+}
+
+/// Microsoft Azure Attestation, which endorses ECDSA quotes with JWTs.
+struct AzureAttestation;
+
+fn provider(att_service_cfg: &AttestationServiceConfig) -> Box<dyn AttestationProvider + '_> {
+    match att_service_cfg.algo {
+        AttestationAlgorithm::SgxEpid | AttestationAlgorithm::SgxEcdsa => {
+            Box::new(IntelAttestationService {
+                algo: &att_service_cfg.algo,
+                api_key: &att_service_cfg.api_key,
+            })
+        }
+        AttestationAlgorithm::SgxMaa => Box::new(AzureAttestation),
+    }
+}
+
+/// Get `quote` endorsed by the configured attestation services in order.
+fn endorse(
+    att_service_cfg: &AttestationServiceConfig,
+    quote: &[u8],
+) -> Result<EndorsedAttestationReport> {
+    let provider = provider(att_service_cfg);
+    att_service_cfg.as_urls.try_each(|url| {
+        // The attestation service echoes the nonce, at most 32 characters, in
+        // the signed report
+        let nonce = uuid::Uuid::new_v4().to_simple().to_string();
+        let report = provider.get_report(url, quote, &nonce)?;
+        crate::report::verify_report_nonce(&report, &nonce)?;
+        Ok(report)
+    })
+}
+
 fn new_tls_stream(
     url: &url::Url,
 ) -> Result<rustls::StreamOwned<rustls::client::ClientConnection, TcpStream>> {
@@ -155,34 +210,37 @@ fn new_tls_stream(
     Ok(stream)
 }
 
-/// Get attestation report form the attestation service (e.g., Intel Attestation
-/// Service and customized DCAP attestation service).
-pub(crate) fn get_report(
-    algo: &AttestationAlgorithm,
+/// Send a request to the attestation service at `url`, and return the headers
+/// and the body of a successful response.
+fn send_request(
     url: &url::Url,
-    api_key: &str,
-    quote: &[u8],
-) -> Result<EndorsedAttestationReport> {
-    debug!("get_report");
-    let encoded_quote = base64::encode(quote);
-    let encoded_json = json!({ "isvEnclaveQuote": encoded_quote }).to_string();
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Result<(HashMap<String, String>, Vec<u8>)> {
     let host_str = url
         .host_str()
         .ok_or(AttestationServiceError::InvalidAddress)?;
+    let extra_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
 
     let request = format!(
-        "POST {} HTTP/1.1\r\n\
+        "{} {} HTTP/1.1\r\n\
          HOST: {}\r\n\
-         Ocp-Apim-Subscription-Key: {}\r\n\
+         {}\
          Connection: Close\r\n\
          Content-Length: {}\r\n\
          Content-Type: application/json\r\n\r\n\
          {}",
-        AS_REPORT_URL,
+        method,
+        path,
         host_str,
-        api_key,
-        encoded_json.len(),
-        encoded_json
+        extra_headers,
+        body.len(),
+        body
     );
     trace!("{}", request);
 
@@ -199,7 +257,8 @@ pub(crate) fn get_report(
 
     trace!("{}", String::from_utf8_lossy(&response));
 
-    let mut headers = [httparse::EMPTY_HEADER; 16];
+    // Responses of Azure Attestation carry more headers than the ones of IAS
+    let mut headers = [httparse::EMPTY_HEADER; 32];
     let mut http_response = httparse::Response::new(&mut headers);
 
     debug!("http_response.parse");
@@ -247,6 +306,15 @@ pub(crate) fn get_report(
     let header_map = parse_headers(&http_response);
     debug!("ias header_map: {:?}", header_map);
 
+    // Azure Attestation may send the body in chunks
+    if header_map
+        .get("transfer-encoding")
+        .map_or(false, |encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        let body = decode_chunked(&response[header_len..])?;
+        return Ok((header_map, body));
+    }
+
     debug!("get_content_length");
     if !header_map.contains_key("content-length")
         || header_map
@@ -261,38 +329,145 @@ pub(crate) fn get_report(
         ));
     }
 
-    debug!("get_signature");
-    let signature_header = match algo {
-        AttestationAlgorithm::SgxEpid => "x-iasreport-signature",
-        AttestationAlgorithm::SgxEcdsa => "x-dcapreport-signature",
-    };
-    let signature = header_map
-        .get(signature_header)
-        .ok_or_else(|| AttestationServiceError::MissingHeader(signature_header.to_string()))?;
-    let signature = base64::decode(signature)?;
-
-    debug!("get_signing_cert");
-    let signing_cert_header = match algo {
-        AttestationAlgorithm::SgxEpid => "x-iasreport-signing-certificate",
-        AttestationAlgorithm::SgxEcdsa => "x-dcapreport-signing-certificate",
-    };
-    let certs: Vec<Vec<u8>> = {
-        let cert_str = header_map.get(signing_cert_header).ok_or_else(|| {
-            AttestationServiceError::MissingHeader(signing_cert_header.to_string())
-        })?;
-        let decoded_cert = percent_encoding::percent_decode_str(cert_str).decode_utf8()?;
-        let certs = rustls_pemfile::certs(&mut decoded_cert.as_bytes())
-            .map_err(|_| anyhow!("pemfile error"))?;
-        certs
-    };
+    let body = response[header_len..].to_vec();
+    Ok((header_map, body))
+}
 
-    debug!("return_report");
-    let report = response[header_len..].to_vec();
-    Ok(EndorsedAttestationReport {
-        report,
-        signature,
-        certs,
-    })
+/// Decode a body sent with the chunked transfer encoding.
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let (start, size) = match httparse::parse_chunk_size(body)
+            .map_err(|_| AttestationServiceError::InvalidResponse)?
+        {
+            httparse::Status::Complete(chunk) => chunk,
+            _ => bail!(AttestationServiceError::InvalidResponse),
+        };
+        if size == 0 {
+            return Ok(decoded);
+        }
+        // Each chunk is followed by CRLF
+        let end = usize::try_from(size)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .filter(|end| end + 2 <= body.len())
+            .ok_or(AttestationServiceError::InvalidResponse)?;
+        decoded.extend_from_slice(&body[start..end]);
+        body = &body[end + 2..];
+    }
+}
+
+/// Get attestation report form the attestation service (e.g., Intel Attestation
+/// Service and customized DCAP attestation service).
+impl AttestationProvider for IntelAttestationService<'_> {
+    fn get_report(
+        &self,
+        url: &url::Url,
+        quote: &[u8],
+        nonce: &str,
+    ) -> Result<EndorsedAttestationReport> {
+        debug!("get_report");
+        let encoded_quote = base64::encode(quote);
+        let encoded_json = json!({ "isvEnclaveQuote": encoded_quote, "nonce": nonce }).to_string();
+        let (header_map, report) = send_request(
+            url,
+            "POST",
+            AS_REPORT_URL,
+            &[("Ocp-Apim-Subscription-Key", self.api_key)],
+            &encoded_json,
+        )?;
+
+        debug!("get_signature");
+        let signature_header = match self.algo {
+            AttestationAlgorithm::SgxEpid => "x-iasreport-signature",
+            _ => "x-dcapreport-signature",
+        };
+        let signature = header_map
+            .get(signature_header)
+            .ok_or_else(|| AttestationServiceError::MissingHeader(signature_header.to_string()))?;
+        let signature = base64::decode(signature)?;
+
+        debug!("get_signing_cert");
+        let signing_cert_header = match self.algo {
+            AttestationAlgorithm::SgxEpid => "x-iasreport-signing-certificate",
+            _ => "x-dcapreport-signing-certificate",
+        };
+        let certs: Vec<Vec<u8>> = {
+            let cert_str = header_map.get(signing_cert_header).ok_or_else(|| {
+                AttestationServiceError::MissingHeader(signing_cert_header.to_string())
+            })?;
+            let decoded_cert = percent_encoding::percent_decode_str(cert_str).decode_utf8()?;
+            let certs = rustls_pemfile::certs(&mut decoded_cert.as_bytes())
+                .map_err(|_| anyhow!("pemfile error"))?;
+            certs
+        };
+
+        debug!("return_report");
+        Ok(EndorsedAttestationReport {
+            report,
+            signature,
+            certs,
+            format: ReportFormat::Ias,
+        })
+    }
+}
+
+/// Get the token of an SGX quote from Azure Attestation, with the certificates
+/// of the signing key published by the same instance.
+/// <https://learn.microsoft.com/en-us/rest/api/attestation/attestation/attest-sgx-enclave>
+impl AttestationProvider for AzureAttestation {
+    fn get_report(
+        &self,
+        url: &url::Url,
+        quote: &[u8],
+        nonce: &str,
+    ) -> Result<EndorsedAttestationReport> {
+        debug!("get_token");
+        let encoded_quote = base64::encode_config(quote, base64::URL_SAFE_NO_PAD);
+        let encoded_json = json!({ "quote": encoded_quote, "nonce": nonce }).to_string();
+        let (_, response) = send_request(url, "POST", MAA_ATTEST_URL, &[], &encoded_json)?;
+        let response: Value = serde_json::from_slice(&response)?;
+        let token = response["token"]
+            .as_str()
+            .ok_or(AttestationServiceError::InvalidResponse)?;
+
+        // The signature is over the encoded header and claims
+        let (signing_input, signature) = token
+            .rsplit_once('.')
+            .ok_or(AttestationServiceError::InvalidResponse)?;
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)?;
+
+        debug!("get_signing_cert");
+        let header = crate::report::token_part(signing_input.as_bytes(), 0)?;
+        let kid = header["kid"]
+            .as_str()
+            .ok_or(AttestationServiceError::InvalidResponse)?;
+        let (_, keys) = send_request(url, "GET", MAA_CERTS_URL, &[], "")?;
+        let keys: Value = serde_json::from_slice(&keys)?;
+        let key = keys["keys"]
+            .as_array()
+            .and_then(|keys| keys.iter().find(|key| key["kid"].as_str() == Some(kid)))
+            .ok_or(AttestationServiceError::InvalidResponse)?;
+        let certs = key["x5c"]
+            .as_array()
+            .ok_or(AttestationServiceError::InvalidResponse)?
+            .iter()
+            .map(|cert| -> Result<Vec<u8>> {
+                let cert = cert
+                    .as_str()
+                    .ok_or(AttestationServiceError::InvalidResponse)?;
+                Ok(base64::decode(cert)?)
+            })
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+
+        debug!("return_report");
+        Ok(EndorsedAttestationReport {
+            report: signing_input.as_bytes().to_vec(),
+            signature,
+            certs,
+            format: ReportFormat::AzureJwt,
+        })
+    }
 }
 
 fn parse_headers(resp: &httparse::Response) -> HashMap<String, String> {
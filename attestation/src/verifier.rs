@@ -17,12 +17,15 @@
 
 //! This module provides types used to verify attestation reports.
 
-use crate::report::AttestationReport;
+use crate::report::{AttestationReport, SgxQuoteStatus};
 
+use std::sync::Arc;
+use std::time::Duration;
 use std::vec::Vec;
 
+use anyhow::{bail, ensure, Result};
 use log::{debug, error};
-use teaclave_types::EnclaveAttr;
+use teaclave_types::{EnclaveAttr, ExecutionReceipt, MetadataDump};
 
 /// User defined verification function to further verify the attestation report.
 pub type AttestationReportVerificationFn = fn(&AttestationReport) -> bool;
@@ -38,6 +41,114 @@ pub struct AttestationReportVerifier {
     pub root_ca: Vec<u8>,
     /// User defined function to verify the attestation report.
     pub verifier: AttestationReportVerificationFn,
+    /// Policy on the quote status and age of the attestation report.
+    pub policy: VerificationPolicy,
+    /// Whether clients must present an attested certificate. Otherwise, the
+    /// certificates of clients are only verified if presented.
+    pub mutual_attestation: bool,
+}
+
+/// Policy on the attestation reports of peers, which is checked besides the
+/// enclave measures and the user defined function. The default policy accepts
+/// any known quote status at any age.
+#[derive(Clone, Debug)]
+pub struct VerificationPolicy {
+    /// Accepted quote statuses, or any status except `UnknownBadStatus` if
+    /// not set.
+    pub allowed_quote_statuses: Option<Vec<SgxQuoteStatus>>,
+    /// Whether `SwHardeningNeeded` and `ConfigurationAndSwHardeningNeeded`
+    /// are accepted, even if they are allowed above.
+    pub allow_sw_hardening_needed: bool,
+    /// Maximum age of the report, i.e., its freshness.
+    pub max_report_age: Option<Duration>,
+}
+
+impl Default for VerificationPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_quote_statuses: None,
+            allow_sw_hardening_needed: true,
+            max_report_age: None,
+        }
+    }
+}
+
+impl VerificationPolicy {
+    /// Create the policy in the Teaclave runtime configuration.
+    pub fn from_teaclave_config(config: &teaclave_config::RuntimeConfig) -> Result<Self> {
+        Self::from_config(&config.verification_policy)
+    }
+
+    /// Create the policy from the `verification_policy` section of a
+    /// configuration.
+    pub fn from_config(policy_config: &teaclave_config::VerificationPolicyConfig) -> Result<Self> {
+        let allowed_quote_statuses = match &policy_config.allowed_quote_statuses {
+            Some(statuses) => Some(
+                statuses
+                    .iter()
+                    .map(|status| match SgxQuoteStatus::from(status.as_str()) {
+                        SgxQuoteStatus::UnknownBadStatus => {
+                            bail!("Unknown quote status {} in verification policy", status)
+                        }
+                        status => Ok(status),
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            None => None,
+        };
+        let max_report_age = match policy_config.max_report_age_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+
+        Ok(Self {
+            allowed_quote_statuses,
+            allow_sw_hardening_needed: policy_config.allow_sw_hardening_needed,
+            max_report_age,
+        })
+    }
+
+    /// Check the quote status and age of `report` against the policy.
+    pub fn verify(&self, report: &AttestationReport) -> bool {
+        match self.check(report) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("{}", e);
+                false
+            }
+        }
+    }
+
+    /// Check `report` like `verify`, telling why it is refused.
+    pub fn check(&self, report: &AttestationReport) -> Result<()> {
+        let status = &report.sgx_quote_status;
+        let status_allowed = match &self.allowed_quote_statuses {
+            Some(statuses) => statuses.contains(status),
+            None => *status != SgxQuoteStatus::UnknownBadStatus,
+        };
+        ensure!(status_allowed, "quote status {:?} is not allowed", status);
+
+        let sw_hardening_needed = matches!(
+            status,
+            SgxQuoteStatus::SwHardeningNeeded | SgxQuoteStatus::ConfigurationAndSwHardeningNeeded
+        );
+        ensure!(
+            !sw_hardening_needed || self.allow_sw_hardening_needed,
+            "quote status {:?} is not allowed",
+            status
+        );
+
+        if let Some(max_report_age) = self.max_report_age {
+            ensure!(
+                report.freshness <= max_report_age,
+                "report age {:?} exceeds {:?}",
+                report.freshness,
+                max_report_age
+            );
+        }
+
+        Ok(())
+    }
 }
 
 /// Checks if he quote's status is not `UnknownBadStatus`
@@ -56,6 +167,22 @@ impl AttestationReportVerifier {
             accepted_enclave_attrs,
             root_ca: root_ca.to_vec(),
             verifier,
+            policy: VerificationPolicy::default(),
+            mutual_attestation: true,
+        }
+    }
+
+    /// Verify attestation reports against `policy` instead of the default one.
+    pub fn policy(self, policy: VerificationPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Whether clients must present an attested certificate, which is
+    /// required by default.
+    pub fn mutual_attestation(self, required: bool) -> Self {
+        Self {
+            mutual_attestation: required,
+            ..self
         }
     }
 
@@ -78,30 +205,99 @@ impl AttestationReportVerifier {
     }
 
     /// Verify TLS certificate.
-    fn verify_cert(&self, certs: &[rustls::Certificate]) -> bool {
+    fn verify_cert(&self, certs: &[rustls::Certificate]) -> Result<()> {
         debug!("verify cert");
         if cfg!(sgx_sim) {
-            return true;
+            return Ok(());
         }
 
-        let report = match AttestationReport::from_cert(certs, &self.root_ca) {
-            Ok(report) => report,
-            Err(e) => {
-                error!("cert verification error {:?}", e);
-                return false;
-            }
-        };
+        let report = AttestationReport::from_cert(certs, &self.root_ca)?;
+        ensure!(
+            self.policy.verify(&report),
+            "Attestation report is refused by the verification policy"
+        );
 
         // Enclave measures are not tested in test mode since we have
         // a dedicated test enclave not known to production enclaves
-        if cfg!(test_mode) {
-            return (self.verifier)(&report);
+        if !cfg!(test_mode) {
+            ensure!(
+                self.verify_measures(&report),
+                "Enclave measures in the attestation report are not accepted"
+            );
         }
+        ensure!(
+            (self.verifier)(&report),
+            "Attestation report is refused by the verification function"
+        );
+
+        Ok(())
+    }
+
+    /// Verify an execution receipt offline: the certificate in the receipt
+    /// should carry a valid attestation report of an accepted enclave, whose
+    /// attested key signed the receipt.
+    pub fn verify_receipt(&self, receipt: &ExecutionReceipt) -> Result<AttestationReport> {
+        let (report, public_key) = self.verify_signer(
+            "Receipt",
+            &receipt.certificate,
+            &receipt.mr_enclave,
+            &receipt.mr_signer,
+        )?;
+        receipt.verify_signature(&public_key)?;
+        Ok(report)
+    }
+
+    /// Verify a metadata dump offline in the same way as a receipt.
+    pub fn verify_metadata_dump(&self, dump: &MetadataDump) -> Result<AttestationReport> {
+        let (report, public_key) = self.verify_signer(
+            "Metadata dump",
+            &dump.certificate,
+            &dump.mr_enclave,
+            &dump.mr_signer,
+        )?;
+        dump.verify_signature(&public_key)?;
+        Ok(report)
+    }
+
+    /// Check the certificate attached to a signed `what` and the measurement
+    /// it claims. Returns the report and the attested public key.
+    fn verify_signer(
+        &self,
+        what: &str,
+        certificate: &[u8],
+        mr_enclave: &str,
+        mr_signer: &str,
+    ) -> Result<(AttestationReport, Vec<u8>)> {
+        let certs = [rustls::Certificate(certificate.to_vec())];
+        let report = AttestationReport::from_cert(&certs, &self.root_ca)?;
+        ensure!(
+            self.verify_measures(&report) && (self.verifier)(&report),
+            "{} is not signed by an accepted enclave",
+            what
+        );
+
+        let enclave_report = &report.sgx_quote_body.isv_enclave_report;
+        ensure!(
+            mr_enclave == hex::encode(enclave_report.mr_enclave)
+                && mr_signer == hex::encode(enclave_report.mr_signer),
+            "{} does not match the measurement of the enclave",
+            what
+        );
 
-        self.verify_measures(&report) && (self.verifier)(&report)
+        // The report data is the uncompressed public key without the prefix.
+        let mut public_key = vec![4u8];
+        public_key.extend_from_slice(&enclave_report.report_data);
+        Ok((report, public_key))
     }
 }
 
+/// The TLS error of a certificate failing the verification, which tells the
+/// peer why.
+fn invalid_certificate(e: anyhow::Error) -> rustls::Error {
+    let reason: Box<dyn std::error::Error + Send + Sync> = e.to_string().into();
+    rustls::Error::InvalidCertificate(rustls::CertificateError::Other(Arc::from(reason)))
+}
+
 impl rustls::client::ServerCertVerifier for AttestationReportVerifier {
     fn verify_server_cert(
         &self,
@@ -114,12 +310,12 @@ impl rustls::client::ServerCertVerifier for AttestationReportVerifier {
     ) -> std::result::Result<rustls::client::ServerCertVerified, rustls::Error> {
         // This call automatically verifies certificate signature
         debug!("verify server cert");
-        if self.verify_cert(&[end_entity.to_owned()]) {
-            Ok(rustls::client::ServerCertVerified::assertion())
-        } else {
-            Err(rustls::Error::InvalidCertificate(
-                rustls::CertificateError::UnhandledCriticalExtension,
-            ))
+        match self.verify_cert(&[end_entity.to_owned()]) {
+            Ok(()) => Ok(rustls::client::ServerCertVerified::assertion()),
+            Err(e) => {
+                error!("server cert verification error: {:?}", e);
+                Err(invalid_certificate(e))
+            }
         }
     }
 }
@@ -130,6 +326,10 @@ impl rustls::server::ClientCertVerifier for AttestationReportVerifier {
         !cfg!(test_mode)
     }
 
+    fn client_auth_mandatory(&self) -> bool {
+        self.offer_client_auth() && self.mutual_attestation
+    }
+
     fn client_auth_root_subjects(&self) -> &[rustls::DistinguishedName] {
         &[]
     }
@@ -142,13 +342,72 @@ impl rustls::server::ClientCertVerifier for AttestationReportVerifier {
     ) -> std::result::Result<rustls::server::ClientCertVerified, rustls::Error> {
         // This call automatically verifies certificate signature
         debug!("verify client cert");
+        match self.verify_cert(&[end_entity.to_owned()]) {
+            Ok(()) => Ok(rustls::server::ClientCertVerified::assertion()),
+            Err(e) => {
+                error!("client cert verification error: {:?}", e);
+                Err(invalid_certificate(e))
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
+pub mod tests {
+    use super::*;
+    use crate::report::*;
 
-        if self.verify_cert(&[end_entity.to_owned()]) {
-            Ok(rustls::server::ClientCertVerified::assertion())
-        } else {
-            Err(rustls::Error::InvalidCertificate(
-                rustls::CertificateError::UnhandledCriticalExtension,
-            ))
+    fn report(sgx_quote_status: SgxQuoteStatus, freshness: Duration) -> AttestationReport {
+        let isv_enclave_report = SgxEnclaveReport {
+            cpu_svn: [0u8; 16],
+            misc_select: 0,
+            attributes: [0u8; 16],
+            mr_enclave: [0u8; 32],
+            mr_signer: [0u8; 32],
+            isv_prod_id: 0,
+            isv_svn: 0,
+            report_data: [0u8; 64],
+        };
+        AttestationReport {
+            freshness,
+            sgx_quote_status,
+            sgx_quote_body: SgxQuote {
+                version: SgxQuoteVersion::V3(SgxEcdsaQuoteAkType::P256_256),
+                gid: 0,
+                isv_svn_qe: 0,
+                isv_svn_pce: 0,
+                qe_vendor_id: uuid::Uuid::nil(),
+                user_data: [0u8; 20],
+                isv_enclave_report,
+                td_report: None,
+            },
+            advisory_ids: Vec::new(),
+            nonce: None,
         }
     }
+
+    pub fn test_verification_policy() {
+        let fresh = Duration::from_secs(10);
+        let policy = VerificationPolicy::default();
+        assert!(policy.verify(&report(SgxQuoteStatus::GroupOutOfDate, fresh)));
+        assert!(policy.verify(&report(SgxQuoteStatus::SwHardeningNeeded, fresh)));
+        assert!(!policy.verify(&report(SgxQuoteStatus::UnknownBadStatus, fresh)));
+
+        let policy = VerificationPolicy {
+            allowed_quote_statuses: Some(vec![
+                SgxQuoteStatus::OK,
+                SgxQuoteStatus::SwHardeningNeeded,
+            ]),
+            allow_sw_hardening_needed: false,
+            max_report_age: Some(Duration::from_secs(60)),
+        };
+        assert!(policy.verify(&report(SgxQuoteStatus::OK, fresh)));
+        assert!(!policy.verify(&report(SgxQuoteStatus::GroupOutOfDate, fresh)));
+        assert!(!policy.verify(&report(SgxQuoteStatus::SwHardeningNeeded, fresh)));
+        assert!(!policy.verify(&report(SgxQuoteStatus::OK, Duration::from_secs(61))));
+        let refused = policy
+            .check(&report(SgxQuoteStatus::OK, Duration::from_secs(61)))
+            .unwrap_err();
+        assert!(refused.to_string().starts_with("report age"));
+    }
 }
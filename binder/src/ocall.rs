@@ -15,7 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use sgx_types::error::SgxStatus;
+use sgx_types::error::{Quote3Error, SgxStatus};
 use sgx_types::function::{
     sgx_get_quote_ex, sgx_get_quote_size_ex, sgx_init_quote_ex, sgx_select_att_key_id,
 };
@@ -116,3 +116,57 @@ pub extern "C" fn ocall_sgx_get_quote(
         )
     }
 }
+
+// The DCAP quote library loads the quoting enclave in this process, so quotes
+// can be generated without AESM. It is only linked when built with DCAP.
+#[cfg(dcap)]
+#[link(name = "sgx_dcap_ql")]
+extern "C" {
+    fn sgx_qe_get_target_info(p_qe_target_info: *mut TargetInfo) -> Quote3Error;
+
+    fn sgx_qe_get_quote_size(p_quote_size: *mut u32) -> Quote3Error;
+
+    fn sgx_qe_get_quote(
+        p_app_report: *const Report,
+        quote_size: u32,
+        p_quote: *mut u8,
+    ) -> Quote3Error;
+}
+
+#[cfg(not(dcap))]
+unsafe fn sgx_qe_get_target_info(_p_qe_target_info: *mut TargetInfo) -> Quote3Error {
+    Quote3Error::ErrorUnexpected
+}
+
+#[cfg(not(dcap))]
+unsafe fn sgx_qe_get_quote_size(_p_quote_size: *mut u32) -> Quote3Error {
+    Quote3Error::ErrorUnexpected
+}
+
+#[cfg(not(dcap))]
+unsafe fn sgx_qe_get_quote(
+    _p_app_report: *const Report,
+    _quote_size: u32,
+    _p_quote: *mut u8,
+) -> Quote3Error {
+    Quote3Error::ErrorUnexpected
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_target_info(p_qe_target_info: *mut TargetInfo) -> Quote3Error {
+    unsafe { sgx_qe_get_target_info(p_qe_target_info) }
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_quote_size(p_quote_size: *mut u32) -> Quote3Error {
+    unsafe { sgx_qe_get_quote_size(p_quote_size) }
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_quote(
+    p_report: *const Report,
+    quote_size: u32,
+    p_quote: *mut u8,
+) -> Quote3Error {
+    unsafe { sgx_qe_get_quote(p_report, quote_size, p_quote) }
+}
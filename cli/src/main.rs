@@ -17,16 +17,27 @@
 
 use anyhow::anyhow;
 use anyhow::bail;
+use anyhow::ensure;
 use anyhow::Result;
 use http::Uri;
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use structopt::StructOpt;
 use teaclave_attestation::report::AttestationReport;
+use teaclave_attestation::verifier::{self, AttestationReportVerifier};
+use teaclave_client_sdk::{
+    AuthenticationService, EnclaveInfo, FrontendService, GetAuditIndexStatusResponse,
+    GetStorageDecommissionStatusResponse, TaskSpec,
+};
 
-use teaclave_crypto::{AesGcm128Key, AesGcm256Key, TeaclaveFile128Key};
+use teaclave_types::MetadataDump;
+
+use teaclave_crypto::{
+    AesGcm128Key, AesGcm256Key, AesGcmSiv128Key, AesGcmSiv256Key, ChaCha20Poly1305Key,
+    TeaclaveFile128Key, DEFAULT_AAD,
+};
 
 const FILE_AUTH_TAG_LENGTH: usize = 16;
 type CMac = [u8; FILE_AUTH_TAG_LENGTH];
@@ -39,6 +50,7 @@ fn decode_hex(src: &str) -> Result<Vec<u8>, hex::FromHexError> {
 #[derive(Debug, StructOpt)]
 struct EncryptDecryptOpt {
     /// Crypto algorithm, supported algorithms are "aes-gcm-128", "aes-gcm-256",
+    /// "aes-gcm-siv-128", "aes-gcm-siv-256", "chacha20-poly1305",
     /// "teaclave-file-128".
     #[structopt(short, long)]
     algorithm: String,
@@ -47,10 +59,15 @@ struct EncryptDecryptOpt {
     #[structopt(short, long, parse(try_from_str = decode_hex))]
     key: KeyVec,
 
-    /// IV for AES keys in the hex format.
+    /// IV for AES and ChaCha20 keys in the hex format.
     #[structopt(long, parse(try_from_str = decode_hex))]
     iv: Option<KeyVec>,
 
+    /// Additional authenticated data in the hex format, e.g., the one of an
+    /// output bound to its task and slot, for all but "teaclave-file-128".
+    #[structopt(long, parse(try_from_str = decode_hex))]
+    aad: Option<KeyVec>,
+
     /// Path of input file.
     #[structopt(short, long = "input-file")]
     input_file: PathBuf,
@@ -90,6 +107,151 @@ struct AttestOpt {
     as_ca_cert: PathBuf,
 }
 
+#[derive(Debug, StructOpt)]
+struct StorageOpt {
+    /// Address of the authentication service
+    #[structopt(long = "authentication-url", default_value = "https://localhost:7776")]
+    authentication_url: String,
+
+    /// Address of the frontend service
+    #[structopt(long = "frontend-url", default_value = "https://localhost:7777")]
+    frontend_url: String,
+
+    /// Path of enclave info
+    #[structopt(short, long = "enclave-info")]
+    enclave_info: PathBuf,
+
+    /// CA cert of attestation service for verifying the attestation report
+    #[structopt(short = "c", long)]
+    as_ca_cert: PathBuf,
+
+    /// Name of a platform admin user
+    #[structopt(short, long)]
+    user: String,
+
+    /// Password of the platform admin user
+    #[structopt(short, long)]
+    password: String,
+
+    #[structopt(subcommand)]
+    action: StorageAction,
+}
+
+#[derive(Debug, StructOpt)]
+enum StorageAction {
+    /// Freeze the storage service and move its state to a replacement node
+    #[structopt(name = "decommission")]
+    Decommission {
+        /// Advertised address of the replacement storage service
+        #[structopt(short, long)]
+        replacement: String,
+
+        /// Wait and print progress until the decommission finishes
+        #[structopt(short, long)]
+        wait: bool,
+    },
+
+    /// Display the progress of the storage decommission
+    #[structopt(name = "status")]
+    Status,
+
+    /// Promote the follower replicating the storage service once the primary
+    /// node is lost, and switch to it
+    #[structopt(name = "promote")]
+    Promote {
+        /// Advertised address of the follower storage service
+        #[structopt(short, long)]
+        follower: String,
+    },
+
+    /// Rebuild the audit index from the saved audit logs, e.g., if it is
+    /// corrupted
+    #[structopt(name = "rebuild-audit-index")]
+    RebuildAuditIndex {
+        /// Wait and print progress until the rebuild finishes
+        #[structopt(short, long)]
+        wait: bool,
+    },
+
+    /// Display the progress of the audit index rebuild
+    #[structopt(name = "audit-index-status")]
+    AuditIndexStatus,
+}
+
+#[derive(Debug, StructOpt)]
+struct MetadataOpt {
+    /// Path of the decrypted metadata dump exported by a platform admin
+    #[structopt(short, long)]
+    dump: PathBuf,
+
+    /// Path of enclave info
+    #[structopt(short, long = "enclave-info")]
+    enclave_info: PathBuf,
+
+    /// CA cert of attestation service for verifying the attestation report
+    #[structopt(short = "c", long)]
+    as_ca_cert: PathBuf,
+
+    /// Directory to import the verified functions, data and tasks into, as
+    /// one JSON file of each
+    #[structopt(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct TaskSubmitOpt {
+    /// Address of the authentication service
+    #[structopt(long = "authentication-url", default_value = "https://localhost:7776")]
+    authentication_url: String,
+
+    /// Address of the frontend service
+    #[structopt(long = "frontend-url", default_value = "https://localhost:7777")]
+    frontend_url: String,
+
+    /// Path of enclave info
+    #[structopt(short, long = "enclave-info")]
+    enclave_info: PathBuf,
+
+    /// CA cert of attestation service for verifying the attestation report
+    #[structopt(short = "c", long)]
+    as_ca_cert: PathBuf,
+
+    /// Name of the user submitting the task
+    #[structopt(short, long)]
+    user: String,
+
+    /// Password of the user
+    #[structopt(short, long)]
+    password: String,
+
+    /// Path of the task specification in YAML
+    #[structopt(short, long)]
+    spec: PathBuf,
+
+    /// Approve the task as the user after assigning the data
+    #[structopt(long)]
+    approve: bool,
+
+    /// Invoke the task after approving it
+    #[structopt(long, requires = "approve")]
+    invoke: bool,
+}
+
+#[derive(Debug, StructOpt)]
+enum TaskAction {
+    /// Validate a task specification without submitting it
+    #[structopt(name = "validate")]
+    Validate {
+        /// Path of the task specification in YAML
+        #[structopt(short, long)]
+        spec: PathBuf,
+    },
+
+    /// Create the task of a specification and assign its data
+    #[structopt(name = "submit")]
+    Submit(TaskSubmitOpt),
+}
+
 #[derive(Debug, StructOpt)]
 enum Command {
     /// Encrypt file
@@ -107,6 +269,18 @@ enum Command {
     /// Display the attestation report of remote Teaclave services
     #[structopt(name = "attest")]
     Attest(AttestOpt),
+
+    /// Manage the storage service as a platform admin
+    #[structopt(name = "storage")]
+    Storage(StorageOpt),
+
+    /// Verify a metadata dump signed by the management service and import it
+    #[structopt(name = "metadata")]
+    Metadata(MetadataOpt),
+
+    /// Validate and submit tasks described in YAML task specifications
+    #[structopt(name = "task")]
+    Task(TaskAction),
 }
 
 #[derive(Debug, StructOpt)]
@@ -118,13 +292,14 @@ struct Opt {
 
 fn decrypt(opt: EncryptDecryptOpt) -> Result<CMac> {
     let key = opt.key;
+    let aad = opt.aad.clone().unwrap_or_else(|| DEFAULT_AAD.to_vec());
     let mut cmac: CMac = [0u8; FILE_AUTH_TAG_LENGTH];
     match opt.algorithm.as_str() {
         AesGcm128Key::SCHEMA => {
             let iv = opt.iv.expect("IV is required.");
             let key = AesGcm128Key::new(&key, &iv)?;
             let mut content = fs::read(opt.input_file)?;
-            let res = key.decrypt(&mut content)?;
+            let res = key.decrypt_with_aad(&mut content, &aad)?;
             cmac.copy_from_slice(&res);
             fs::write(opt.output_file, content)?;
         }
@@ -132,11 +307,39 @@ fn decrypt(opt: EncryptDecryptOpt) -> Result<CMac> {
             let iv = opt.iv.expect("IV is required.");
             let key = AesGcm256Key::new(&key, &iv)?;
             let mut content = fs::read(opt.input_file)?;
-            let res = key.decrypt(&mut content)?;
+            let res = key.decrypt_with_aad(&mut content, &aad)?;
+            cmac.copy_from_slice(&res);
+            fs::write(opt.output_file, content)?;
+        }
+        AesGcmSiv128Key::SCHEMA => {
+            let iv = opt.iv.expect("IV is required.");
+            let key = AesGcmSiv128Key::new(&key, &iv)?;
+            let mut content = fs::read(opt.input_file)?;
+            let res = key.decrypt_with_aad(&mut content, &aad)?;
+            cmac.copy_from_slice(&res);
+            fs::write(opt.output_file, content)?;
+        }
+        AesGcmSiv256Key::SCHEMA => {
+            let iv = opt.iv.expect("IV is required.");
+            let key = AesGcmSiv256Key::new(&key, &iv)?;
+            let mut content = fs::read(opt.input_file)?;
+            let res = key.decrypt_with_aad(&mut content, &aad)?;
+            cmac.copy_from_slice(&res);
+            fs::write(opt.output_file, content)?;
+        }
+        ChaCha20Poly1305Key::SCHEMA => {
+            let iv = opt.iv.expect("IV is required.");
+            let key = ChaCha20Poly1305Key::new(&key, &iv)?;
+            let mut content = fs::read(opt.input_file)?;
+            let res = key.decrypt_with_aad(&mut content, &aad)?;
             cmac.copy_from_slice(&res);
             fs::write(opt.output_file, content)?;
         }
         TeaclaveFile128Key::SCHEMA => {
+            ensure!(
+                opt.aad.is_none(),
+                "AAD is not supported by teaclave-file-128"
+            );
             let key = TeaclaveFile128Key::new(&key)?;
             let mut output_file = fs::File::create(opt.output_file)?;
             let res = key.decrypt(opt.input_file, &mut output_file)?;
@@ -150,13 +353,14 @@ fn decrypt(opt: EncryptDecryptOpt) -> Result<CMac> {
 
 fn encrypt(opt: EncryptDecryptOpt) -> Result<CMac> {
     let key = opt.key;
+    let aad = opt.aad.clone().unwrap_or_else(|| DEFAULT_AAD.to_vec());
     let mut cmac: CMac = [0u8; FILE_AUTH_TAG_LENGTH];
     match opt.algorithm.as_str() {
         AesGcm128Key::SCHEMA => {
             let iv = opt.iv.expect("IV is required.");
             let key = AesGcm128Key::new(&key, &iv)?;
             let mut content = fs::read(opt.input_file)?;
-            let res = key.encrypt(&mut content)?;
+            let res = key.encrypt_with_aad(&mut content, &aad)?;
             cmac.copy_from_slice(&res);
             fs::write(opt.output_file, content)?;
         }
@@ -164,11 +368,39 @@ fn encrypt(opt: EncryptDecryptOpt) -> Result<CMac> {
             let iv = opt.iv.expect("IV is required.");
             let key = AesGcm256Key::new(&key, &iv)?;
             let mut content = fs::read(opt.input_file)?;
-            let res = key.encrypt(&mut content)?;
+            let res = key.encrypt_with_aad(&mut content, &aad)?;
+            cmac.copy_from_slice(&res);
+            fs::write(opt.output_file, content)?;
+        }
+        AesGcmSiv128Key::SCHEMA => {
+            let iv = opt.iv.expect("IV is required.");
+            let key = AesGcmSiv128Key::new(&key, &iv)?;
+            let mut content = fs::read(opt.input_file)?;
+            let res = key.encrypt_with_aad(&mut content, &aad)?;
+            cmac.copy_from_slice(&res);
+            fs::write(opt.output_file, content)?;
+        }
+        AesGcmSiv256Key::SCHEMA => {
+            let iv = opt.iv.expect("IV is required.");
+            let key = AesGcmSiv256Key::new(&key, &iv)?;
+            let mut content = fs::read(opt.input_file)?;
+            let res = key.encrypt_with_aad(&mut content, &aad)?;
+            cmac.copy_from_slice(&res);
+            fs::write(opt.output_file, content)?;
+        }
+        ChaCha20Poly1305Key::SCHEMA => {
+            let iv = opt.iv.expect("IV is required.");
+            let key = ChaCha20Poly1305Key::new(&key, &iv)?;
+            let mut content = fs::read(opt.input_file)?;
+            let res = key.encrypt_with_aad(&mut content, &aad)?;
             cmac.copy_from_slice(&res);
             fs::write(opt.output_file, content)?;
         }
         TeaclaveFile128Key::SCHEMA => {
+            ensure!(
+                opt.aad.is_none(),
+                "AAD is not supported by teaclave-file-128"
+            );
             let key = TeaclaveFile128Key::new(&key)?;
             let content = fs::File::open(opt.input_file)?;
             let res = key.encrypt(opt.output_file, content)?;
@@ -264,6 +496,197 @@ fn attest(opt: AttestOpt) -> Result<()> {
     Ok(())
 }
 
+fn print_decommission_status(status: &GetStorageDecommissionStatusResponse) {
+    print!(
+        "phase: {}, transferred: {}/{}",
+        status.phase, status.transferred_entries, status.total_entries
+    );
+    if !status.replacement_address.is_empty() {
+        print!(", replacement: {}", status.replacement_address);
+    }
+    if !status.error.is_empty() {
+        print!(", error: {}", status.error);
+    }
+    println!();
+}
+
+fn print_audit_index_status(status: &GetAuditIndexStatusResponse) {
+    print!(
+        "phase: {}, indexed: {}/{}, available: {}",
+        status.phase, status.indexed_entries, status.total_entries, status.available
+    );
+    if !status.error.is_empty() {
+        print!(", error: {}", status.error);
+    }
+    println!();
+}
+
+fn storage(opt: StorageOpt) -> Result<()> {
+    let enclave_info = EnclaveInfo::from_file(&opt.enclave_info)?;
+    let content = fs::read(&opt.as_ca_cert)?;
+    let as_root_ca_cert = pem::parse(content)?.contents;
+
+    let mut authentication_client =
+        AuthenticationService::connect(&opt.authentication_url, &enclave_info, &as_root_ca_cert)?;
+    let token = authentication_client.user_login(&opt.user, &opt.password)?;
+    let mut client = FrontendService::connect(&opt.frontend_url, &enclave_info, &as_root_ca_cert)?;
+    client.set_credential(&opt.user, &token);
+
+    match opt.action {
+        StorageAction::Decommission { replacement, wait } => {
+            client.decommission_storage(&replacement)?;
+            println!("Storage decommission started.");
+            if !wait {
+                return Ok(());
+            }
+            loop {
+                let status = client.get_storage_decommission_status()?;
+                print_decommission_status(&status);
+                match status.phase.as_str() {
+                    "completed" => return Ok(()),
+                    "failed" => bail!("Failed to decommission storage."),
+                    _ => std::thread::sleep(std::time::Duration::from_secs(1)),
+                }
+            }
+        }
+        StorageAction::Status => {
+            let status = client.get_storage_decommission_status()?;
+            print_decommission_status(&status);
+        }
+        StorageAction::Promote { follower } => {
+            let applied_index = client.promote_storage_replica(&follower)?;
+            println!(
+                "Storage replica promoted at change log index {}.",
+                applied_index
+            );
+        }
+        StorageAction::RebuildAuditIndex { wait } => {
+            client.rebuild_audit_index()?;
+            println!("Audit index rebuild started.");
+            if !wait {
+                return Ok(());
+            }
+            loop {
+                let status = client.get_audit_index_status()?;
+                print_audit_index_status(&status);
+                match status.phase.as_str() {
+                    "completed" => return Ok(()),
+                    "failed" => bail!("Failed to rebuild audit index."),
+                    _ => std::thread::sleep(std::time::Duration::from_secs(1)),
+                }
+            }
+        }
+        StorageAction::AuditIndexStatus => {
+            let status = client.get_audit_index_status()?;
+            print_audit_index_status(&status);
+        }
+    }
+
+    Ok(())
+}
+
+fn metadata(opt: MetadataOpt) -> Result<()> {
+    let dump = MetadataDump::from_slice(&fs::read(&opt.dump)?)?;
+    let enclave_info = EnclaveInfo::from_file(&opt.enclave_info)?;
+    let attr = enclave_info
+        .get_enclave_attr("teaclave_management_service")
+        .ok_or_else(|| anyhow!("Cannot find the management service in enclave info."))?;
+    let content = fs::read(&opt.as_ca_cert)?;
+    let as_root_ca_cert = pem::parse(content)?.contents;
+    let verifier = AttestationReportVerifier::new(
+        vec![attr],
+        &as_root_ca_cert,
+        verifier::universal_quote_verifier,
+    );
+    verifier.verify_metadata_dump(&dump)?;
+    println!(
+        "Verified metadata dump version {} created at {}: {} functions, {} input files, {} output files, {} tasks.",
+        dump.version,
+        dump.created_at,
+        dump.functions.len(),
+        dump.input_files.len(),
+        dump.output_files.len(),
+        dump.tasks.len()
+    );
+
+    if let Some(output) = opt.output {
+        fs::create_dir_all(&output)?;
+        fs::write(
+            output.join("functions.json"),
+            serde_json::to_vec_pretty(&dump.functions)?,
+        )?;
+        fs::write(
+            output.join("input_files.json"),
+            serde_json::to_vec_pretty(&dump.input_files)?,
+        )?;
+        fs::write(
+            output.join("output_files.json"),
+            serde_json::to_vec_pretty(&dump.output_files)?,
+        )?;
+        fs::write(
+            output.join("tasks.json"),
+            serde_json::to_vec_pretty(&dump.tasks)?,
+        )?;
+        println!("Imported to {}.", output.display());
+    }
+
+    Ok(())
+}
+
+fn read_task_spec(path: &Path) -> Result<TaskSpec> {
+    let content = fs::read_to_string(path)?;
+    TaskSpec::from_yaml(&content)
+        .map_err(|e| anyhow!("Invalid task specification {}: {:#}", path.display(), e))
+}
+
+fn task(action: TaskAction) -> Result<()> {
+    let opt = match action {
+        TaskAction::Validate { spec } => {
+            read_task_spec(&spec)?;
+            println!("Task specification is valid.");
+            return Ok(());
+        }
+        TaskAction::Submit(opt) => opt,
+    };
+    let spec = read_task_spec(&opt.spec)?;
+    let enclave_info = EnclaveInfo::from_file(&opt.enclave_info)?;
+    let content = fs::read(&opt.as_ca_cert)?;
+    let as_root_ca_cert = pem::parse(content)?.contents;
+
+    let mut authentication_client =
+        AuthenticationService::connect(&opt.authentication_url, &enclave_info, &as_root_ca_cert)?;
+    let token = authentication_client.user_login(&opt.user, &opt.password)?;
+    let mut client = FrontendService::connect(&opt.frontend_url, &enclave_info, &as_root_ca_cert)?;
+    client.set_credential(&opt.user, &token);
+
+    let task_id = client.submit_task_spec(&spec)?;
+    println!("Task created: {}", task_id);
+    if opt.approve {
+        client.approve_task(&task_id)?;
+        println!("Task approved by {}.", opt.user);
+    }
+    let pending: Vec<&String> = spec
+        .approvals
+        .iter()
+        .filter(|user| !opt.approve || **user != opt.user)
+        .collect();
+    if !pending.is_empty() {
+        println!("Pending approvals: {:?}", pending);
+    }
+    if opt.invoke {
+        let response = client.invoke_task(&task_id)?;
+        match response.eta_secs {
+            0 => println!("Task invoked, {} tasks ahead.", response.queue_depth),
+            eta => println!(
+                "Task invoked, {} tasks ahead, expected to run in {}s.",
+                response.queue_depth, eta
+            ),
+        }
+    }
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     env_logger::init();
     let args = Opt::from_args();
@@ -292,6 +715,9 @@ fn main() -> Result<()> {
             }
         },
         Command::Attest(opt) => attest(opt)?,
+        Command::Storage(opt) => storage(opt)?,
+        Command::Metadata(opt) => metadata(opt)?,
+        Command::Task(action) => task(action)?,
     };
 
     Ok(())
@@ -68,7 +68,7 @@ new_json="$(jq '.resource_limits.user_space_size = "2GB" |
               .resource_limits.kernel_space_stack_size = "10MB" |
               .process.default_heap_size ="256MB" |
               .process.default_mmap_size = "1GB" |
-              .env.untrusted += ["TEACLAVE_LOG"] ' Occlum.json)" && \
+              .env.untrusted += ["TEACLAVE_LOG", "TEACLAVE_LOG_FORMAT"] ' Occlum.json)" && \
 echo "${new_json}" > Occlum.json
 awk '/hostfs/{for(x=NR-2;x<=NR+2;x++)d[x];}{a[NR]=$0}END{for(i=1;i<=NR;i++)if(!(i in d))print a[i]}' Occlum.json > Occlum.json.tmp 
 mv Occlum.json.tmp Occlum.json
@@ -112,28 +112,7 @@ run_functional_tests() {
   trap cleanup INT TERM ERR
 
   echo_title "functional tests"
-  pushd ${TEACLAVE_SERVICE_INSTALL_DIR}
-  ./teaclave_authentication_service &
-  ./teaclave_storage_service &
-  ./teaclave_access_control_service &
-  wait_port 7776 17776 17778 17779 # wait for access control, authentication, storage service
-  ./teaclave_management_service &
-  ./teaclave_scheduler_service &
-  wait_port 17777 17780 # wait for management service and scheduler_service
-  ./teaclave_frontend_service &
-  wait_port 7777 # wait for other services
-  popd
-
   pushd ${TEACLAVE_TEST_INSTALL_DIR}
-  # Run function tests for all except execution service
-  ./teaclave_functional_tests -t \
-    access_control_service \
-    authentication_service \
-    frontend_service \
-    management_service \
-    scheduler_service \
-    storage_service
-
   ${TEACLAVE_CLI_INSTALL_DIR}/teaclave_cli encrypt \
            --algorithm aes-gcm-128 \
            --input-file ./fixtures/fusion/input1.txt \
@@ -149,21 +128,21 @@ run_functional_tests() {
            --output-file ./fixtures/fusion/input2.enc
 
   start_storage_server
-
-  # Run tests of execution service separately
-  pushd ${TEACLAVE_SERVICE_INSTALL_DIR}
-  ./teaclave_execution_service &
-  popd
-  sleep 3    # wait for execution services
-
-  ./teaclave_functional_tests -t execution_service
-
-  ./teaclave_functional_tests -t end_to_end
-
   generate_python_grpc_stubs
-  
   export PYTHONPATH=${TEACLAVE_PROJECT_ROOT}/sdk/python
-  ./scripts/functional_tests.py -v
+
+  # The test driver starts all services, runs tests of the execution service
+  # after the others, and then the Python tests before stopping the services.
+  ./teaclave_functional_tests --services-dir ${TEACLAVE_SERVICE_INSTALL_DIR} -t \
+    access_control_service \
+    authentication_service \
+    frontend_service \
+    management_service \
+    scheduler_service \
+    storage_service \
+    execution_service \
+    end_to_end \
+    -- ./scripts/functional_tests.py -v
 
   popd
 
@@ -48,6 +48,11 @@ struct Inbound {
 struct GrpcConfig {
     max_encoding_message_size: usize,
     max_decoding_message_size: usize,
+    request_timeout_secs: u64,
+    keepalive_interval_secs: u64,
+    keepalive_timeout_secs: u64,
+    session_freshness_secs: u64,
+    drain_timeout_secs: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
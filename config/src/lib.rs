@@ -28,4 +28,10 @@ pub(crate) enum ConfigSource {
 pub mod build;
 mod runtime;
 
-pub use runtime::RuntimeConfig;
+pub use runtime::{
+    AuditLogConfig, AuthCacheConfig, DataRetentionConfig, ForensicsConfig, FrontendConfig,
+    FunctionPayloadConfig, IdentityMappingConfig, IdentityProvidersConfig, LdapConfig,
+    NotifierConfig, OidcConfig, PasswordPolicyConfig, QuotaConfig, ResourceLimitsConfig,
+    RuntimeConfig, SchedulerConfig, SlackConfig, SloConfig, SloTarget, SmtpConfig,
+    StorageReplicationConfig, TaskLogConfig, VerificationPolicyConfig, WebhookConfig,
+};
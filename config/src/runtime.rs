@@ -23,6 +23,7 @@ use std::untrusted::fs;
 
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::net;
 use std::path::{Path, PathBuf};
@@ -36,6 +37,45 @@ pub struct RuntimeConfig {
     pub audit: AuditConfig,
     pub attestation: AttestationServiceConfig,
     pub mount: MountConfig,
+    #[serde(default)]
+    pub quota: QuotaConfig,
+    #[serde(default)]
+    pub slo: SloConfig,
+    #[serde(default)]
+    pub audit_log: AuditLogConfig,
+    #[serde(default)]
+    pub auth_cache: AuthCacheConfig,
+    #[serde(default)]
+    pub frontend: FrontendConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub task_log: TaskLogConfig,
+    #[serde(default)]
+    pub function_payload: FunctionPayloadConfig,
+    #[serde(default)]
+    pub resource_limits: ResourceLimitsConfig,
+    #[serde(default)]
+    pub rpc: RpcConfig,
+    #[serde(default)]
+    pub verification_policy: VerificationPolicyConfig,
+    /// Digests of ended tasks are not sent without this section.
+    #[serde(default)]
+    pub notifier: Option<NotifierConfig>,
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+    #[serde(default)]
+    pub identity_providers: IdentityProvidersConfig,
+    #[serde(default)]
+    pub password_policy: PasswordPolicyConfig,
+    #[serde(default)]
+    pub data_retention: DataRetentionConfig,
+    /// Forensic bundles of failed tasks are not made without this section.
+    #[serde(default)]
+    pub forensics: Option<ForensicsConfig>,
+    /// The storage service is not replicated without this section.
+    #[serde(default)]
+    pub storage_replication: Option<StorageReplicationConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -83,6 +123,33 @@ pub struct AttestationServiceConfig {
     pub url: String,
     pub key: String,
     pub spid: String,
+    /// Attestation services tried in order when the one at `url` is down.
+    #[serde(default)]
+    pub fallback_urls: Vec<String>,
+    /// Directory to keep the last successful endorsement of each service,
+    /// which is used on start if no attestation service is reachable.
+    #[serde(default)]
+    pub endorsement_cache_dir: Option<PathBuf>,
+    /// Use the cached endorsement on start as long as it is valid, skipping
+    /// the attestation service.
+    #[serde(default)]
+    pub reuse_cached_endorsement: bool,
+}
+
+impl AttestationServiceConfig {
+    /// The primary URL followed by the fallback ones.
+    pub fn urls(&self) -> Vec<String> {
+        std::iter::once(&self.url)
+            .chain(self.fallback_urls.iter())
+            .cloned()
+            .collect()
+    }
+
+    pub fn endorsement_cache_path(&self, service: &str) -> Option<PathBuf> {
+        self.endorsement_cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.endorsement", service)))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -90,6 +157,580 @@ pub struct MountConfig {
     pub fusion_base_dir: PathBuf,
 }
 
+/// Default per-user quotas enforced by the frontend service. A limit of 0
+/// means unlimited.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub max_concurrent_tasks: u32,
+    #[serde(default)]
+    pub max_registered_data: u32,
+    #[serde(default)]
+    pub max_requests_per_minute: u32,
+}
+
+/// Service level objectives of the RPCs forwarded by the frontend service.
+/// Latency and errors are tracked per RPC family (`data`, `function`, `task`
+/// and `admin`) over a sliding window.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SloConfig {
+    #[serde(default = "default_slo_window_secs")]
+    pub window_secs: u64,
+    /// Targets are not checked until a window has this many requests.
+    #[serde(default = "default_slo_min_requests")]
+    pub min_requests: u32,
+    /// Target of the families without their own entry in `families`.
+    #[serde(default)]
+    pub default: SloTarget,
+    #[serde(default)]
+    pub families: HashMap<String, SloTarget>,
+}
+
+/// A target of 0 is not checked.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct SloTarget {
+    /// The 95th percentile latency in milliseconds
+    #[serde(default)]
+    pub p95_latency_ms: u64,
+    /// The ratio of failed requests, between 0 and 1
+    #[serde(default)]
+    pub max_error_rate: f64,
+}
+
+fn default_slo_window_secs() -> u64 {
+    300
+}
+
+fn default_slo_min_requests() -> u32 {
+    10
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: default_slo_window_secs(),
+            min_requests: default_slo_min_requests(),
+            default: SloTarget::default(),
+            families: HashMap::new(),
+        }
+    }
+}
+
+impl SloConfig {
+    pub fn target_of(&self, family: &str) -> SloTarget {
+        self.families.get(family).copied().unwrap_or(self.default)
+    }
+}
+
+/// Audit logs are buffered in the frontend service and sent to the auditor in
+/// the management service every `flush_interval_secs`, or as soon as
+/// `max_buffer_size` logs are buffered.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct AuditLogConfig {
+    #[serde(default = "default_audit_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+    #[serde(default = "default_audit_max_buffer_size")]
+    pub max_buffer_size: usize,
+}
+
+fn default_audit_flush_interval_secs() -> u64 {
+    30
+}
+
+fn default_audit_max_buffer_size() -> usize {
+    1000
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval_secs: default_audit_flush_interval_secs(),
+            max_buffer_size: default_audit_max_buffer_size(),
+        }
+    }
+}
+
+/// The frontend service caches the claims of up to `capacity` tokens for
+/// `ttl_secs` after they are authenticated, 0 for no caching. The claims of a
+/// user are dropped as soon as its credential is changed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct AuthCacheConfig {
+    #[serde(default = "default_auth_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    #[serde(default = "default_auth_cache_capacity")]
+    pub capacity: usize,
+}
+
+fn default_auth_cache_ttl_secs() -> u64 {
+    30
+}
+
+fn default_auth_cache_capacity() -> usize {
+    10000
+}
+
+impl Default for AuthCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_auth_cache_ttl_secs(),
+            capacity: default_auth_cache_capacity(),
+        }
+    }
+}
+
+/// Frontend services behind a load balancer set `shared_state`, so that they
+/// keep the cached claims of tokens and the quotas set for users in the storage
+/// service, and send audit logs as requests are handled instead of buffering
+/// them. Quotas and grants of detailed errors set by other instances are
+/// picked up every `quota_sync_interval_secs`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct FrontendConfig {
+    #[serde(default)]
+    pub shared_state: bool,
+    #[serde(default = "default_quota_sync_interval_secs")]
+    pub quota_sync_interval_secs: u64,
+}
+
+fn default_quota_sync_interval_secs() -> u64 {
+    5
+}
+
+impl Default for FrontendConfig {
+    fn default() -> Self {
+        Self {
+            shared_state: false,
+            quota_sync_interval_secs: default_quota_sync_interval_secs(),
+        }
+    }
+}
+
+/// Tasks invoked while `max_queue_depth` tasks are waiting in the queue are
+/// rejected, and the scheduler service holds at most this many staged tasks
+/// in memory. Executors are asked to attest again once they have been attested
+/// for `reattestation_interval_secs`, 0 for never. With `lease_secs` set,
+/// multiple scheduler instances elect a leader holding a lease of this long
+/// in the storage service, and the others stand by; 0 runs a single instance.
+/// Small tasks of the same function, creator and participants are given to an
+/// executor in batches of up to `max_batch_size`, 1 for no batching.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct SchedulerConfig {
+    #[serde(default = "default_max_queue_depth")]
+    pub max_queue_depth: u32,
+    #[serde(default)]
+    pub reattestation_interval_secs: u64,
+    #[serde(default)]
+    pub lease_secs: u64,
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: u32,
+}
+
+fn default_max_queue_depth() -> u32 {
+    10000
+}
+
+fn default_max_batch_size() -> u32 {
+    1
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_queue_depth: default_max_queue_depth(),
+            reattestation_interval_secs: 0,
+            lease_secs: 0,
+            max_batch_size: default_max_batch_size(),
+        }
+    }
+}
+
+/// Executors upload the encrypted execution log of each run of a task,
+/// keeping the last `max_size_bytes` of it, under `upload_base_url`, which
+/// ends with a slash.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskLogConfig {
+    #[serde(default = "default_task_log_upload_base_url")]
+    pub upload_base_url: String,
+    #[serde(default = "default_task_log_max_size_bytes")]
+    pub max_size_bytes: usize,
+}
+
+fn default_task_log_upload_base_url() -> String {
+    "file:///tmp/teaclave_task_logs/".to_string()
+}
+
+fn default_task_log_max_size_bytes() -> usize {
+    65536
+}
+
+impl Default for TaskLogConfig {
+    fn default() -> Self {
+        Self {
+            upload_base_url: default_task_log_upload_base_url(),
+            max_size_bytes: default_task_log_max_size_bytes(),
+        }
+    }
+}
+
+/// Function payloads are limited to `max_size_bytes`, whether registered in one
+/// request or uploaded in chunks of at most `max_chunk_size_bytes`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct FunctionPayloadConfig {
+    #[serde(default = "default_function_payload_max_size_bytes")]
+    pub max_size_bytes: u64,
+    #[serde(default = "default_function_payload_max_chunk_size_bytes")]
+    pub max_chunk_size_bytes: u64,
+}
+
+fn default_function_payload_max_size_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_function_payload_max_chunk_size_bytes() -> u64 {
+    1024 * 1024
+}
+
+impl Default for FunctionPayloadConfig {
+    fn default() -> Self {
+        Self {
+            max_size_bytes: default_function_payload_max_size_bytes(),
+            max_chunk_size_bytes: default_function_payload_max_chunk_size_bytes(),
+        }
+    }
+}
+
+/// Default resource limits of the Python functions, for the tasks which do
+/// not set their own in `CreateTask`, 0 for no limit.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ResourceLimitsConfig {
+    #[serde(default = "default_resource_limits_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_resource_limits_max_memory_bytes")]
+    pub max_memory_bytes: u64,
+    #[serde(default = "default_resource_limits_max_output_bytes")]
+    pub max_output_bytes: u64,
+}
+
+fn default_resource_limits_timeout_secs() -> u64 {
+    3600
+}
+
+fn default_resource_limits_max_memory_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+fn default_resource_limits_max_output_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+impl Default for ResourceLimitsConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_resource_limits_timeout_secs(),
+            max_memory_bytes: default_resource_limits_max_memory_bytes(),
+            max_output_bytes: default_resource_limits_max_output_bytes(),
+        }
+    }
+}
+
+/// Services refuse clients which cannot speak `min_protocol_version` of the
+/// RPC protocol or a higher one. Raise it once all the clients are upgraded.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct RpcConfig {
+    #[serde(default = "default_min_protocol_version")]
+    pub min_protocol_version: u32,
+}
+
+fn default_min_protocol_version() -> u32 {
+    1
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            min_protocol_version: default_min_protocol_version(),
+        }
+    }
+}
+
+/// Policy on the attestation reports of the other services. Reports are
+/// accepted with any known quote status unless `allowed_quote_statuses` is
+/// set, e.g., to `["OK"]`, and at any age if `max_report_age_secs` is 0.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VerificationPolicyConfig {
+    #[serde(default)]
+    pub allowed_quote_statuses: Option<Vec<String>>,
+    #[serde(default = "default_allow_sw_hardening_needed")]
+    pub allow_sw_hardening_needed: bool,
+    #[serde(default)]
+    pub max_report_age_secs: u64,
+}
+
+fn default_allow_sw_hardening_needed() -> bool {
+    true
+}
+
+impl Default for VerificationPolicyConfig {
+    fn default() -> Self {
+        Self {
+            allowed_quote_statuses: None,
+            allow_sw_hardening_needed: default_allow_sw_hardening_needed(),
+            max_report_age_secs: 0,
+        }
+    }
+}
+
+/// Executors upload a sealed forensic bundle of each failed run of a task,
+/// with the last `log_tail_lines` lines of its execution log, under
+/// `upload_base_url`, which ends with a slash.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ForensicsConfig {
+    pub upload_base_url: String,
+    #[serde(default = "default_forensics_log_tail_lines")]
+    pub log_tail_lines: usize,
+}
+
+fn default_forensics_log_tail_lines() -> usize {
+    200
+}
+
+/// The storage service either ships its change log to the follower at
+/// `follower_address` every `ship_interval_ms`, or is the follower itself,
+/// which only serves reads until it is promoted with
+/// `PromoteStorageReplica`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StorageReplicationConfig {
+    #[serde(default)]
+    pub follower_address: String,
+    #[serde(default)]
+    pub follower: bool,
+    #[serde(default = "default_ship_interval_ms")]
+    pub ship_interval_ms: u64,
+}
+
+fn default_ship_interval_ms() -> u64 {
+    500
+}
+
+/// Backends of the notifier in the frontend service app, which sends task
+/// participants a digest of their ended tasks every `digest_interval_secs`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotifierConfig {
+    #[serde(default = "default_digest_interval_secs")]
+    pub digest_interval_secs: u64,
+    #[serde(default)]
+    pub smtp: Option<SmtpConfig>,
+    #[serde(default)]
+    pub slack: Option<SlackConfig>,
+}
+
+/// An SMTP server accepting implicit TLS, e.g., on port 465.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SmtpConfig {
+    pub server: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub from: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SlackConfig {
+    pub webhook_url: String,
+}
+
+/// Hosts the webhook sinks of each namespace, i.e., the attribute of the data
+/// owner roles, can push notifications to. Webhook sinks cannot be registered
+/// in the namespaces not listed. Notifications are pushed by the notifier,
+/// every `digest_interval_secs`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WebhookConfig {
+    #[serde(default)]
+    pub allowed_hosts: HashMap<String, Vec<String>>,
+}
+
+impl WebhookConfig {
+    pub fn is_allowed(&self, namespace: &str, host: &str) -> bool {
+        self.allowed_hosts
+            .get(namespace)
+            .map_or(false, |hosts| hosts.iter().any(|h| h == host))
+    }
+}
+
+/// Rules the passwords kept by the authentication service follow when they are
+/// set. Passwords expire `max_age_days` after they are set, 0 for never, and
+/// are then changed with `ChangePassword` before the user logs in again.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasswordPolicyConfig {
+    #[serde(default = "default_password_min_length")]
+    pub min_length: usize,
+    #[serde(default)]
+    pub require_uppercase: bool,
+    #[serde(default)]
+    pub require_lowercase: bool,
+    #[serde(default)]
+    pub require_digit: bool,
+    #[serde(default)]
+    pub require_symbol: bool,
+    #[serde(default)]
+    pub max_age_days: u64,
+}
+
+fn default_password_min_length() -> usize {
+    8
+}
+
+impl Default for PasswordPolicyConfig {
+    fn default() -> Self {
+        Self {
+            min_length: default_password_min_length(),
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+            max_age_days: 0,
+        }
+    }
+}
+
+/// The management service looks for outputs past their retention period
+/// every `sweep_interval_secs`, and deletes them with their keys.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct DataRetentionConfig {
+    #[serde(default = "default_retention_sweep_interval_secs")]
+    pub sweep_interval_secs: u64,
+}
+
+fn default_retention_sweep_interval_secs() -> u64 {
+    600
+}
+
+impl Default for DataRetentionConfig {
+    fn default() -> Self {
+        Self {
+            sweep_interval_secs: default_retention_sweep_interval_secs(),
+        }
+    }
+}
+
+/// External identity providers users can log in with, instead of the
+/// passwords kept by the authentication service.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IdentityProvidersConfig {
+    /// Users who only log in with the passwords kept by the authentication
+    /// service, e.g., the platform admin. External identities cannot be
+    /// mapped to them.
+    #[serde(default = "default_local_users")]
+    pub local_users: Vec<String>,
+    /// Users other than `local_users` log in through LDAP by default with
+    /// this section.
+    #[serde(default)]
+    pub ldap: Option<LdapConfig>,
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+}
+
+impl Default for IdentityProvidersConfig {
+    fn default() -> Self {
+        Self {
+            local_users: default_local_users(),
+            ldap: None,
+            oidc: None,
+        }
+    }
+}
+
+/// How the external identities of a provider are mapped to Teaclave users.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct IdentityMappingConfig {
+    /// Prepended to the external ids to make the user ids, e.g., "corp/".
+    #[serde(default)]
+    pub user_id_prefix: String,
+    /// The role users not registered yet are registered with on their first
+    /// login. They have to be registered beforehand without it.
+    #[serde(default)]
+    pub default_role: Option<String>,
+    #[serde(default)]
+    pub default_attribute: String,
+}
+
+/// An LDAP or Active Directory server accepting LDAPS, which the
+/// authentication service verifies the passwords of users with by binding as
+/// them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LdapConfig {
+    pub server: String,
+    #[serde(default = "default_ldaps_port")]
+    pub port: u16,
+    /// The DN to bind as, where "{}" is replaced with the escaped login id,
+    /// e.g., "uid={},ou=people,dc=example,dc=com".
+    pub bind_dn: String,
+    /// The DER-encoded certificate of the CA which issued the certificate of
+    /// the server.
+    pub ca_cert: PathBuf,
+    #[serde(default = "Default::default")]
+    pub ca_cert_bytes: Vec<u8>,
+    #[serde(default = "default_ldap_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(flatten)]
+    pub mapping: IdentityMappingConfig,
+}
+
+/// An OpenID Connect provider, whose ID tokens users log in with.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OidcConfig {
+    /// The issuer and the audience the ID tokens have to name.
+    pub issuer: String,
+    pub audience: String,
+    /// The PEM-encoded public key the ID tokens are signed with.
+    pub public_key: PathBuf,
+    #[serde(default = "Default::default")]
+    pub public_key_bytes: Vec<u8>,
+    #[serde(default = "default_oidc_algorithm")]
+    pub algorithm: String,
+    /// The claim of the external id.
+    #[serde(default = "default_oidc_user_id_claim")]
+    pub user_id_claim: String,
+    /// The claims of the role and its attribute, which take the place of the
+    /// default ones of the mapping if present in the ID tokens.
+    #[serde(default)]
+    pub role_claim: Option<String>,
+    #[serde(default)]
+    pub attribute_claim: Option<String>,
+    #[serde(flatten)]
+    pub mapping: IdentityMappingConfig,
+}
+
+fn default_local_users() -> Vec<String> {
+    vec!["admin".to_string()]
+}
+
+fn default_ldaps_port() -> u16 {
+    636
+}
+
+fn default_ldap_timeout_secs() -> u64 {
+    10
+}
+
+fn default_oidc_algorithm() -> String {
+    "RS256".to_string()
+}
+
+fn default_oidc_user_id_claim() -> String {
+    "sub".to_string()
+}
+
+fn default_digest_interval_secs() -> u64 {
+    3600
+}
+
+fn default_smtp_port() -> u16 {
+    465
+}
+
 impl RuntimeConfig {
     pub fn from_toml<T: AsRef<Path>>(path: T) -> Result<Self> {
         let contents = fs::read_to_string(path.as_ref())
@@ -121,7 +762,13 @@ impl RuntimeConfig {
             && env::var("AS_KEY").is_ok()
         {
             let algorithm = env::var("AS_ALGO").unwrap();
-            let url = env::var("AS_URL").unwrap();
+            // A comma-separated list of URLs in the order of failover
+            let mut urls: Vec<String> = env::var("AS_URL")
+                .unwrap()
+                .split(',')
+                .map(|url| url.trim().to_string())
+                .collect();
+            let url = urls.remove(0);
             let spid = env::var("AS_SPID").unwrap();
             let key = env::var("AS_KEY").unwrap();
             config.attestation = AttestationServiceConfig {
@@ -129,9 +776,29 @@ impl RuntimeConfig {
                 url,
                 key,
                 spid,
+                fallback_urls: urls,
+                endorsement_cache_dir: config.attestation.endorsement_cache_dir,
+                reuse_cached_endorsement: config.attestation.reuse_cached_endorsement,
             };
         }
 
+        if let Some(ldap) = config.identity_providers.ldap.as_mut() {
+            let path = &ldap.ca_cert;
+            ldap.ca_cert_bytes = fs::read(path)
+                .with_context(|| format!("Cannot read the LDAP CA certificate from {:?}", path))?;
+        }
+        if let Some(oidc) = config.identity_providers.oidc.as_mut() {
+            let path = &oidc.public_key;
+            oidc.public_key_bytes = fs::read(path)
+                .with_context(|| format!("Cannot read the OIDC public key from {:?}", path))?;
+        }
+
+        if let Some(smtp) = config.notifier.as_mut().and_then(|n| n.smtp.as_mut()) {
+            if let Ok(password) = env::var("SMTP_PASSWORD") {
+                smtp.password = password;
+            }
+        }
+
         validate_config(&config)?;
 
         log::trace!(
@@ -145,7 +812,7 @@ impl RuntimeConfig {
 
 fn validate_config(config: &RuntimeConfig) -> Result<()> {
     match config.attestation.algorithm.as_str() {
-        "sgx_epid" | "sgx_ecdsa" => (),
+        "sgx_epid" | "sgx_ecdsa" | "sgx_maa" => (),
         _ => bail!(
             "Invalid attestation algorithm {}",
             config.attestation.algorithm
@@ -156,8 +823,86 @@ fn validate_config(config: &RuntimeConfig) -> Result<()> {
         bail!("Cannot find Attestation Service SPID/key or format error");
     }
 
-    if url::Url::parse(&config.attestation.url).is_err() {
-        bail!("Invalid URL of attestation service");
+    for url in config.attestation.urls() {
+        if url::Url::parse(&url).is_err() {
+            bail!("Invalid URL of attestation service: {}", url);
+        }
+    }
+
+    if config.audit_log.flush_interval_secs == 0 || config.audit_log.max_buffer_size == 0 {
+        bail!("The flush interval and buffer size of audit logs should not be 0");
+    }
+
+    if config.frontend.shared_state && config.frontend.quota_sync_interval_secs == 0 {
+        bail!("The sync interval of shared quotas should not be 0");
+    }
+
+    if config.scheduler.max_queue_depth == 0 {
+        bail!("The maximum depth of the task queue should not be 0");
+    }
+
+    if config.scheduler.max_batch_size == 0 {
+        bail!("The maximum size of task batches should not be 0");
+    }
+
+    // The leader renews its lease every few seconds.
+    if config.scheduler.lease_secs != 0 && config.scheduler.lease_secs < 10 {
+        bail!("The lease of the scheduler leader should last 10 seconds at least");
+    }
+
+    if config.password_policy.min_length == 0 {
+        bail!("The minimum length of passwords should not be 0");
+    }
+
+    if config.data_retention.sweep_interval_secs == 0 {
+        bail!("The sweep interval of data retention should not be 0");
+    }
+
+    if config.function_payload.max_size_bytes == 0
+        || config.function_payload.max_chunk_size_bytes == 0
+    {
+        bail!("The size limits of function payloads should not be 0");
+    }
+
+    if let Some(notifier) = &config.notifier {
+        if notifier.digest_interval_secs == 0 {
+            bail!("The digest interval of the notifier should not be 0");
+        }
+        if let Some(slack) = &notifier.slack {
+            if url::Url::parse(&slack.webhook_url).is_err() {
+                bail!("Invalid URL of Slack webhook: {}", slack.webhook_url);
+            }
+        }
+    }
+
+    if let Some(forensics) = &config.forensics {
+        if url::Url::parse(&forensics.upload_base_url).is_err() {
+            bail!(
+                "Invalid base URL of forensic bundles: {}",
+                forensics.upload_base_url
+            );
+        }
+    }
+
+    if let Some(replication) = &config.storage_replication {
+        if replication.follower && !replication.follower_address.is_empty() {
+            bail!("A storage follower should not have a follower of its own");
+        }
+        if !replication.follower && replication.follower_address.is_empty() {
+            bail!("The follower address of storage replication is missing");
+        }
+        if replication.ship_interval_ms == 0 {
+            bail!("The ship interval of storage replication should not be 0");
+        }
+    }
+
+    if let Some(ldap) = &config.identity_providers.ldap {
+        if !ldap.bind_dn.contains("{}") {
+            bail!("The bind DN of LDAP should contain {{}} for the user id");
+        }
+        if ldap.timeout_secs == 0 {
+            bail!("The timeout of LDAP should not be 0");
+        }
     }
 
     Ok(())
@@ -15,25 +15,108 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use aes_gcm_siv::aead::consts::{U12, U16};
+use aes_gcm_siv::aead::generic_array::GenericArray;
+use aes_gcm_siv::aead::{AeadInPlace, KeyInit};
+use aes_gcm_siv::{Aes128GcmSiv, Aes256GcmSiv};
 use anyhow::{anyhow, ensure, Context, Result};
 use rand::prelude::RngCore;
 use ring::aead;
 use serde::{Deserialize, Serialize};
 use sgx_tprotected_fs::SgxFile;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::path::Path;
 
+pub use zeroize::{Zeroize, Zeroizing};
+
 const AES_GCM_128_KEY_LENGTH: usize = 16;
 const AES_GCM_128_IV_LENGTH: usize = 12;
 
 const AES_GCM_256_KEY_LENGTH: usize = 32;
 const AES_GCM_256_IV_LENGTH: usize = 12;
+
+const AES_GCM_SIV_128_KEY_LENGTH: usize = 16;
+const AES_GCM_SIV_256_KEY_LENGTH: usize = 32;
+const AES_GCM_SIV_IV_LENGTH: usize = 12;
+
+const CHACHA20_POLY1305_KEY_LENGTH: usize = 32;
+const CHACHA20_POLY1305_IV_LENGTH: usize = 12;
 const TEACLAVE_FILE_128_ROOT_KEY_LENGTH: usize = 16;
 const CMAC_LENGTH: usize = 16;
 const FILE_CHUNK_SIZE: usize = 1024 * 1024;
 
 type CMac = [u8; CMAC_LENGTH];
 
+/// Additional authenticated data of the AEAD schemes when the content is not
+/// bound to any context.
+pub const DEFAULT_AAD: [u8; 8] = [0; 8];
+
+/// Overwrite sensitive bytes, e.g., key material and plaintext, with zeros
+/// in a way which is not optimized away. Debug builds check that the bytes
+/// are cleared.
+pub fn clear(bytes: &mut [u8]) {
+    bytes.zeroize();
+    debug_assert!(
+        bytes.iter().all(|b| *b == 0),
+        "Sensitive buffer is not cleared"
+    );
+}
+
+/// Fill `bytes` with random bytes from the same generator as the keys.
+pub fn fill_random(bytes: &mut [u8]) {
+    rand::thread_rng().fill_bytes(bytes);
+}
+
+/// Reserve capacity for at least `additional` more bytes in a buffer of
+/// sensitive bytes. Unlike `Vec::reserve`, the old allocation is zeroized
+/// when the buffer is moved, instead of leaving a copy in freed memory.
+pub fn reserve_zeroizing(buffer: &mut Vec<u8>, additional: usize) {
+    if buffer.capacity() - buffer.len() >= additional {
+        return;
+    }
+    let capacity = std::cmp::max(buffer.len() + additional, buffer.capacity() * 2);
+    let mut grown = Vec::with_capacity(capacity);
+    grown.extend_from_slice(buffer);
+    let mut old = std::mem::replace(buffer, grown);
+    clear(&mut old);
+}
+
+/// Read all the plaintext of `reader` into a buffer which is zeroized on
+/// drop, without leaving copies in freed memory while growing it.
+pub fn read_to_end_zeroizing(reader: &mut impl Read) -> io::Result<Zeroizing<Vec<u8>>> {
+    let mut buffer = Zeroizing::new(Vec::new());
+    let mut chunk = Zeroizing::new(std::vec![0; FILE_CHUNK_SIZE]);
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => return Ok(buffer),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        reserve_zeroizing(&mut buffer, n);
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+}
+
+// Keys are `Copy` to be embedded in the file and task types, so they cannot
+// be zeroized on drop. Owners of keys zeroize them when done with them.
+macro_rules! impl_zeroize {
+    ($key:ident, $($field:ident),+) => {
+        impl Zeroize for $key {
+            fn zeroize(&mut self) {
+                $(clear(&mut self.$field);)+
+            }
+        }
+    };
+}
+
+impl_zeroize!(AesGcm128Key, key, iv);
+impl_zeroize!(AesGcm256Key, key, iv);
+impl_zeroize!(AesGcmSiv128Key, key, iv);
+impl_zeroize!(AesGcmSiv256Key, key, iv);
+impl_zeroize!(ChaCha20Poly1305Key, key, iv);
+impl_zeroize!(TeaclaveFile128Key, key);
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct AesGcm256Key {
     pub key: [u8; AES_GCM_256_KEY_LENGTH],
@@ -63,7 +146,8 @@ impl AesGcm256Key {
     }
 
     pub fn from_hex(in_key: impl AsRef<str>, in_iv: impl AsRef<str>) -> Result<Self> {
-        let key = hex::decode(in_key.as_ref()).context("Illegal AesGcm256 key provided")?;
+        let key =
+            Zeroizing::new(hex::decode(in_key.as_ref()).context("Illegal AesGcm256 key provided")?);
         let iv = hex::decode(in_iv.as_ref()).context("Illegal AesGcm256 iv provided")?;
         Self::new(&key, &iv)
     }
@@ -73,7 +157,12 @@ impl AesGcm256Key {
     }
 
     pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
-        let plaintext_len = aead_decrypt(&aead::AES_256_GCM, in_out, &self.key, &self.iv)?.len();
+        self.decrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn decrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        let plaintext_len =
+            aead_decrypt(&aead::AES_256_GCM, in_out, &self.key, &self.iv, aad)?.len();
         let mut cmac: CMac = [0u8; CMAC_LENGTH];
         cmac.copy_from_slice(&in_out[plaintext_len..]);
         in_out.truncate(plaintext_len);
@@ -81,7 +170,11 @@ impl AesGcm256Key {
     }
 
     pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
-        aead_encrypt(&aead::AES_256_GCM, in_out, &self.key, &self.iv)?;
+        self.encrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn encrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        aead_encrypt(&aead::AES_256_GCM, in_out, &self.key, &self.iv, aad)?;
         let mut cmac: CMac = [0u8; CMAC_LENGTH];
         let n = in_out.len();
         let cybertext_len = n - CMAC_LENGTH;
@@ -133,7 +226,8 @@ impl AesGcm128Key {
     }
 
     pub fn from_hex(in_key: impl AsRef<str>, in_iv: impl AsRef<str>) -> Result<Self> {
-        let key = hex::decode(in_key.as_ref()).context("Illegal AesGcm128 key provided")?;
+        let key =
+            Zeroizing::new(hex::decode(in_key.as_ref()).context("Illegal AesGcm128 key provided")?);
         let iv = hex::decode(in_iv.as_ref()).context("Illegal AesGcm128 iv provided")?;
         Self::new(&key, &iv)
     }
@@ -143,7 +237,12 @@ impl AesGcm128Key {
     }
 
     pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
-        let plaintext_len = aead_decrypt(&aead::AES_128_GCM, in_out, &self.key, &self.iv)?.len();
+        self.decrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn decrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        let plaintext_len =
+            aead_decrypt(&aead::AES_128_GCM, in_out, &self.key, &self.iv, aad)?.len();
         let mut cmac: CMac = [0u8; CMAC_LENGTH];
         cmac.copy_from_slice(&in_out[plaintext_len..]);
         in_out.truncate(plaintext_len);
@@ -151,7 +250,11 @@ impl AesGcm128Key {
     }
 
     pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
-        aead_encrypt(&aead::AES_128_GCM, in_out, &self.key, &self.iv)?;
+        self.encrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn encrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        aead_encrypt(&aead::AES_128_GCM, in_out, &self.key, &self.iv, aad)?;
         let mut cmac: CMac = [0u8; CMAC_LENGTH];
         let n = in_out.len();
         let cybertext_len = n - CMAC_LENGTH;
@@ -172,6 +275,227 @@ impl Default for AesGcm128Key {
     }
 }
 
+/// AES-GCM-SIV (RFC 8452) is resistant to nonce misuse: reusing the iv only
+/// reveals whether two plaintexts are equal.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AesGcmSiv128Key {
+    pub key: [u8; AES_GCM_SIV_128_KEY_LENGTH],
+    pub iv: [u8; AES_GCM_SIV_IV_LENGTH],
+}
+
+impl AesGcmSiv128Key {
+    pub const SCHEMA: &'static str = "aes-gcm-siv-128";
+
+    pub fn new(in_key: &[u8], in_iv: &[u8]) -> Result<Self> {
+        ensure!(
+            in_key.len() == AES_GCM_SIV_128_KEY_LENGTH,
+            "Invalid key length for AesGcmSiv128: {}",
+            in_key.len()
+        );
+        ensure!(
+            in_iv.len() == AES_GCM_SIV_IV_LENGTH,
+            "Invalid iv length for AesGcmSiv128: {}",
+            in_iv.len()
+        );
+        let mut key = [0u8; AES_GCM_SIV_128_KEY_LENGTH];
+        let mut iv = [0u8; AES_GCM_SIV_IV_LENGTH];
+        key.copy_from_slice(in_key);
+        iv.copy_from_slice(in_iv);
+
+        Ok(AesGcmSiv128Key { key, iv })
+    }
+
+    pub fn from_hex(in_key: impl AsRef<str>, in_iv: impl AsRef<str>) -> Result<Self> {
+        let key = Zeroizing::new(
+            hex::decode(in_key.as_ref()).context("Illegal AesGcmSiv128 key provided")?,
+        );
+        let iv = hex::decode(in_iv.as_ref()).context("Illegal AesGcmSiv128 iv provided")?;
+        Self::new(&key, &iv)
+    }
+
+    pub fn random() -> Self {
+        Self::default()
+    }
+
+    pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        self.decrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn decrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        siv_decrypt(&Aes128GcmSiv::new(&self.key.into()), in_out, &self.iv, aad)
+    }
+
+    pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        self.encrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn encrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        siv_encrypt(&Aes128GcmSiv::new(&self.key.into()), in_out, &self.iv, aad)
+    }
+}
+
+impl Default for AesGcmSiv128Key {
+    fn default() -> Self {
+        let mut key = [0u8; AES_GCM_SIV_128_KEY_LENGTH];
+        let mut iv = [0u8; AES_GCM_SIV_IV_LENGTH];
+        let mut rng = rand::thread_rng();
+        rng.fill_bytes(&mut key);
+        rng.fill_bytes(&mut iv);
+
+        Self { key, iv }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AesGcmSiv256Key {
+    pub key: [u8; AES_GCM_SIV_256_KEY_LENGTH],
+    pub iv: [u8; AES_GCM_SIV_IV_LENGTH],
+}
+
+impl AesGcmSiv256Key {
+    pub const SCHEMA: &'static str = "aes-gcm-siv-256";
+
+    pub fn new(in_key: &[u8], in_iv: &[u8]) -> Result<Self> {
+        ensure!(
+            in_key.len() == AES_GCM_SIV_256_KEY_LENGTH,
+            "Invalid key length for AesGcmSiv256: {}",
+            in_key.len()
+        );
+        ensure!(
+            in_iv.len() == AES_GCM_SIV_IV_LENGTH,
+            "Invalid iv length for AesGcmSiv256: {}",
+            in_iv.len()
+        );
+        let mut key = [0u8; AES_GCM_SIV_256_KEY_LENGTH];
+        let mut iv = [0u8; AES_GCM_SIV_IV_LENGTH];
+        key.copy_from_slice(in_key);
+        iv.copy_from_slice(in_iv);
+
+        Ok(AesGcmSiv256Key { key, iv })
+    }
+
+    pub fn from_hex(in_key: impl AsRef<str>, in_iv: impl AsRef<str>) -> Result<Self> {
+        let key = Zeroizing::new(
+            hex::decode(in_key.as_ref()).context("Illegal AesGcmSiv256 key provided")?,
+        );
+        let iv = hex::decode(in_iv.as_ref()).context("Illegal AesGcmSiv256 iv provided")?;
+        Self::new(&key, &iv)
+    }
+
+    pub fn random() -> Self {
+        Self::default()
+    }
+
+    pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        self.decrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn decrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        siv_decrypt(&Aes256GcmSiv::new(&self.key.into()), in_out, &self.iv, aad)
+    }
+
+    pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        self.encrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn encrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        siv_encrypt(&Aes256GcmSiv::new(&self.key.into()), in_out, &self.iv, aad)
+    }
+}
+
+impl Default for AesGcmSiv256Key {
+    fn default() -> Self {
+        let mut key = [0u8; AES_GCM_SIV_256_KEY_LENGTH];
+        let mut iv = [0u8; AES_GCM_SIV_IV_LENGTH];
+        let mut rng = rand::thread_rng();
+        rng.fill_bytes(&mut key);
+        rng.fill_bytes(&mut iv);
+
+        Self { key, iv }
+    }
+}
+
+/// ChaCha20-Poly1305 (RFC 8439) is fast in software, for clients on platforms
+/// without AES instructions.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ChaCha20Poly1305Key {
+    pub key: [u8; CHACHA20_POLY1305_KEY_LENGTH],
+    pub iv: [u8; CHACHA20_POLY1305_IV_LENGTH],
+}
+
+impl ChaCha20Poly1305Key {
+    pub const SCHEMA: &'static str = "chacha20-poly1305";
+
+    pub fn new(in_key: &[u8], in_iv: &[u8]) -> Result<Self> {
+        ensure!(
+            in_key.len() == CHACHA20_POLY1305_KEY_LENGTH,
+            "Invalid key length for ChaCha20Poly1305: {}",
+            in_key.len()
+        );
+        ensure!(
+            in_iv.len() == CHACHA20_POLY1305_IV_LENGTH,
+            "Invalid iv length for ChaCha20Poly1305: {}",
+            in_iv.len()
+        );
+        let mut key = [0u8; CHACHA20_POLY1305_KEY_LENGTH];
+        let mut iv = [0u8; CHACHA20_POLY1305_IV_LENGTH];
+        key.copy_from_slice(in_key);
+        iv.copy_from_slice(in_iv);
+
+        Ok(ChaCha20Poly1305Key { key, iv })
+    }
+
+    pub fn from_hex(in_key: impl AsRef<str>, in_iv: impl AsRef<str>) -> Result<Self> {
+        let key = Zeroizing::new(
+            hex::decode(in_key.as_ref()).context("Illegal ChaCha20Poly1305 key provided")?,
+        );
+        let iv = hex::decode(in_iv.as_ref()).context("Illegal ChaCha20Poly1305 iv provided")?;
+        Self::new(&key, &iv)
+    }
+
+    pub fn random() -> Self {
+        Self::default()
+    }
+
+    pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        self.decrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn decrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        let plaintext_len =
+            aead_decrypt(&aead::CHACHA20_POLY1305, in_out, &self.key, &self.iv, aad)?.len();
+        let mut cmac: CMac = [0u8; CMAC_LENGTH];
+        cmac.copy_from_slice(&in_out[plaintext_len..]);
+        in_out.truncate(plaintext_len);
+        Ok(cmac)
+    }
+
+    pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        self.encrypt_with_aad(in_out, &DEFAULT_AAD)
+    }
+
+    pub fn encrypt_with_aad(&self, in_out: &mut Vec<u8>, aad: &[u8]) -> Result<CMac> {
+        aead_encrypt(&aead::CHACHA20_POLY1305, in_out, &self.key, &self.iv, aad)?;
+        let mut cmac: CMac = [0u8; CMAC_LENGTH];
+        let n = in_out.len();
+        let cybertext_len = n - CMAC_LENGTH;
+        cmac.copy_from_slice(&in_out[cybertext_len..]);
+        Ok(cmac)
+    }
+}
+
+impl Default for ChaCha20Poly1305Key {
+    fn default() -> Self {
+        let mut key = [0u8; CHACHA20_POLY1305_KEY_LENGTH];
+        let mut iv = [0u8; CHACHA20_POLY1305_IV_LENGTH];
+        let mut rng = rand::thread_rng();
+        rng.fill_bytes(&mut key);
+        rng.fill_bytes(&mut iv);
+
+        Self { key, iv }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct TeaclaveFile128Key {
     pub key: [u8; TEACLAVE_FILE_128_ROOT_KEY_LENGTH],
@@ -197,7 +521,7 @@ impl TeaclaveFile128Key {
 
     pub fn decrypt<P: AsRef<Path>>(&self, path: P, output: &mut impl Write) -> Result<CMac> {
         let mut file = SgxFile::open_with_key(path.as_ref(), self.key)?;
-        let mut buffer = std::vec![0; FILE_CHUNK_SIZE];
+        let mut buffer = Zeroizing::new(std::vec![0; FILE_CHUNK_SIZE]);
         loop {
             let n = file.read(&mut buffer)?;
             if n > 0 {
@@ -213,7 +537,7 @@ impl TeaclaveFile128Key {
 
     pub fn encrypt<P: AsRef<Path>>(&self, path: P, mut content: impl Read) -> Result<CMac> {
         let mut file = SgxFile::create_with_key(path.as_ref(), self.key)?;
-        let mut buffer = std::vec![0; FILE_CHUNK_SIZE];
+        let mut buffer = Zeroizing::new(std::vec![0; FILE_CHUNK_SIZE]);
         loop {
             let n = content.read(&mut buffer[..])?;
             if n > 0 {
@@ -243,12 +567,13 @@ pub fn aead_decrypt<'a>(
     in_out: &'a mut [u8],
     key: &[u8],
     iv: &[u8],
+    aad: &[u8],
 ) -> Result<&'a mut [u8]> {
     let key =
         aead::UnboundKey::new(alg, key).map_err(|_| anyhow!("Aead unbound key init error"))?;
     let nonce =
         aead::Nonce::try_assume_unique_for_key(iv).map_err(|_| anyhow!("Aead iv init error"))?;
-    let aad = aead::Aad::from([0u8; 8]);
+    let aad = aead::Aad::from(aad);
 
     let dec_key = aead::LessSafeKey::new(key);
     let slice = dec_key
@@ -262,12 +587,14 @@ pub fn aead_encrypt(
     in_out: &mut Vec<u8>,
     key: &[u8],
     iv: &[u8],
+    aad: &[u8],
 ) -> Result<()> {
     let key =
         aead::UnboundKey::new(alg, key).map_err(|_| anyhow!("Aead unbound key init error"))?;
     let nonce =
         aead::Nonce::try_assume_unique_for_key(iv).map_err(|_| anyhow!("Aead iv init error"))?;
-    let aad = aead::Aad::from([0u8; 8]);
+    let aad = aead::Aad::from(aad);
+    reserve_zeroizing(in_out, alg.tag_len());
 
     let enc_key = aead::LessSafeKey::new(key);
     enc_key
@@ -276,13 +603,59 @@ pub fn aead_encrypt(
     Ok(())
 }
 
+// Same layout as the ring ciphers: the tag is appended to the ciphertext.
+fn siv_encrypt(
+    cipher: &impl AeadInPlace<NonceSize = U12, TagSize = U16>,
+    in_out: &mut Vec<u8>,
+    iv: &[u8],
+    aad: &[u8],
+) -> Result<CMac> {
+    reserve_zeroizing(in_out, CMAC_LENGTH);
+    let tag = cipher
+        .encrypt_in_place_detached(GenericArray::from_slice(iv), aad, in_out)
+        .map_err(|_| anyhow!("Aead siv encrypt error"))?;
+    in_out.extend_from_slice(&tag);
+    let mut cmac: CMac = [0u8; CMAC_LENGTH];
+    cmac.copy_from_slice(&tag);
+    Ok(cmac)
+}
+
+fn siv_decrypt(
+    cipher: &impl AeadInPlace<NonceSize = U12, TagSize = U16>,
+    in_out: &mut Vec<u8>,
+    iv: &[u8],
+    aad: &[u8],
+) -> Result<CMac> {
+    ensure!(in_out.len() >= CMAC_LENGTH, "Aead siv ciphertext too short");
+    let plaintext_len = in_out.len() - CMAC_LENGTH;
+    let mut cmac: CMac = [0u8; CMAC_LENGTH];
+    cmac.copy_from_slice(&in_out[plaintext_len..]);
+    cipher
+        .decrypt_in_place_detached(
+            GenericArray::from_slice(iv),
+            aad,
+            &mut in_out[..plaintext_len],
+            GenericArray::from_slice(&cmac),
+        )
+        .map_err(|_| anyhow!("Aead siv decrypt error"))?;
+    in_out.truncate(plaintext_len);
+    Ok(cmac)
+}
+
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
     use teaclave_test_utils::*;
 
     pub fn run_tests() -> bool {
-        run_tests!(test_aead_enc_then_dec, test_crypto_info,)
+        run_tests!(
+            test_aead_enc_then_dec,
+            test_crypto_info,
+            test_chacha20_poly1305,
+            test_aes_gcm_siv,
+            test_zeroize,
+            test_aad,
+        )
     }
 
     fn test_aead_enc_then_dec() {
@@ -291,8 +664,8 @@ pub mod tests {
         let iv = [0x89u8; 12];
 
         let mut buf = plain_text.to_vec();
-        aead_encrypt(&aead::AES_128_GCM, &mut buf, &key, &iv).unwrap();
-        let result = aead_decrypt(&aead::AES_128_GCM, &mut buf, &key, &iv).unwrap();
+        aead_encrypt(&aead::AES_128_GCM, &mut buf, &key, &iv, &DEFAULT_AAD).unwrap();
+        let result = aead_decrypt(&aead::AES_128_GCM, &mut buf, &key, &iv, &DEFAULT_AAD).unwrap();
         assert_eq!(result, plain_text);
     }
 
@@ -310,4 +683,71 @@ pub mod tests {
         crypto_info.decrypt(&mut buf).unwrap();
         assert_eq!(&buf[..], &plain_text[..]);
     }
+
+    fn test_chacha20_poly1305() {
+        let crypto_info = ChaCha20Poly1305Key::random();
+        let plain_text: [u8; 5] = [0xde, 0xff, 0xab, 0xcd, 0x90];
+        let mut buf = plain_text.to_vec();
+
+        let cmac = crypto_info.encrypt(&mut buf).unwrap();
+        assert_eq!(buf.len(), plain_text.len() + CMAC_LENGTH);
+        assert_eq!(crypto_info.decrypt(&mut buf).unwrap(), cmac);
+        assert_eq!(&buf[..], &plain_text[..]);
+    }
+
+    fn test_aes_gcm_siv() {
+        let plain_text: [u8; 5] = [0xde, 0xff, 0xab, 0xcd, 0x90];
+
+        let crypto_info = AesGcmSiv128Key::random();
+        let mut buf = plain_text.to_vec();
+        let cmac = crypto_info.encrypt(&mut buf).unwrap();
+        assert_ne!(&buf[..plain_text.len()], &plain_text[..]);
+        assert_eq!(crypto_info.decrypt(&mut buf).unwrap(), cmac);
+        assert_eq!(&buf[..], &plain_text[..]);
+
+        let crypto_info = AesGcmSiv256Key::random();
+        let mut buf = plain_text.to_vec();
+        crypto_info.encrypt(&mut buf).unwrap();
+        buf[0] ^= 1;
+        assert!(crypto_info.decrypt(&mut buf).is_err());
+        assert!(crypto_info.decrypt(&mut vec![0u8; 4]).is_err());
+    }
+
+    fn test_zeroize() {
+        let mut crypto_info = AesGcm256Key::random();
+        crypto_info.zeroize();
+        assert_eq!(crypto_info.key, [0u8; AES_GCM_256_KEY_LENGTH]);
+        assert_eq!(crypto_info.iv, [0u8; AES_GCM_256_IV_LENGTH]);
+
+        let mut buffer = Vec::with_capacity(5);
+        buffer.extend_from_slice(b"hello");
+        reserve_zeroizing(&mut buffer, CMAC_LENGTH);
+        assert!(buffer.capacity() >= 5 + CMAC_LENGTH);
+        assert_eq!(buffer, b"hello");
+
+        let content = vec![0x5au8; FILE_CHUNK_SIZE + 1];
+        let buffer = read_to_end_zeroizing(&mut content.as_slice()).unwrap();
+        assert_eq!(*buffer, content);
+    }
+
+    fn test_aad() {
+        let plain_text: [u8; 5] = [0xde, 0xff, 0xab, 0xcd, 0x90];
+
+        let crypto_info = AesGcm128Key::random();
+        let mut buf = plain_text.to_vec();
+        crypto_info.encrypt_with_aad(&mut buf, b"task-a").unwrap();
+        assert!(crypto_info.decrypt(&mut buf.clone()).is_err());
+        assert!(crypto_info
+            .decrypt_with_aad(&mut buf.clone(), b"task-b")
+            .is_err());
+        crypto_info.decrypt_with_aad(&mut buf, b"task-a").unwrap();
+        assert_eq!(&buf[..], &plain_text[..]);
+
+        let crypto_info = AesGcmSiv256Key::random();
+        let mut buf = plain_text.to_vec();
+        crypto_info.encrypt_with_aad(&mut buf, b"task-a").unwrap();
+        assert!(crypto_info.decrypt(&mut buf.clone()).is_err());
+        crypto_info.decrypt_with_aad(&mut buf, b"task-a").unwrap();
+        assert_eq!(&buf[..], &plain_text[..]);
+    }
 }
@@ -37,14 +37,17 @@ use sgx_types::error::Quote3Error;
 use sgx_types::types::*;
 
 lazy_static! {
-    static ref SIGNER: signature::RsaKeyPair = {
+    static ref SIGNER: ReportSigner = {
         let figment = Config::figment();
         let key_path = figment
             .extract_inner::<String>("attestation.key")
             .expect("key");
+        let key_type = figment
+            .extract_inner::<String>("attestation.key_type")
+            .unwrap_or_else(|_| "rsa".to_string());
         let key = std::fs::read_to_string(key_path).unwrap();
         let der = pem::parse(key).unwrap().contents;
-        signature::RsaKeyPair::from_pkcs8(&der).unwrap()
+        ReportSigner::from_pkcs8(&key_type, &der)
     };
     static ref REPORT_SIGNING_CERT: String = {
         let figment = Config::figment();
@@ -71,6 +74,100 @@ extern "C" {
         supplemental_data_size: u32,
         p_supplemental_data: *mut u8,
     ) -> Quote3Error;
+
+    fn sgx_qv_get_quote_supplemental_data_size(p_data_size: *mut u32) -> Quote3Error;
+
+    #[allow(improper_ctypes)]
+    fn tdx_qv_verify_quote(
+        p_quote: *const u8,
+        quote_size: u32,
+        p_quote_collateral: *const CQlQveCollateral,
+        expiration_check_date: time_t,
+        p_collateral_expiration_status: *mut u32,
+        p_quote_verification_result: *mut QlQvResult,
+        p_qve_report_info: *mut QlQeReportInfo,
+        supplemental_data_size: u32,
+        p_supplemental_data: *mut u8,
+    ) -> Quote3Error;
+
+    fn tdx_qv_get_quote_supplemental_data_size(p_data_size: *mut u32) -> Quote3Error;
+}
+
+// TEE type in the header of TD quotes, at offset 4
+const TDX_TEE_TYPE: u32 = 0x81;
+// Sizes of the quote header and the report, without the signature data
+const SGX_QUOTE_BODY_SIZE: usize = 48 + 384;
+const TDX_QUOTE_BODY_SIZE: usize = 48 + 584;
+
+const MAX_SA_LIST_SIZE: usize = 320;
+
+/// Leading fields of `sgx_ql_qv_supplemental_t`. The list of security
+/// advisories is only filled in since the major version 3.
+#[repr(C)]
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+struct QvSupplemental {
+    version: u32,
+    earliest_issue_date: time_t,
+    latest_issue_date: time_t,
+    earliest_expiration_date: time_t,
+    tcb_level_date_tag: time_t,
+    pck_crl_num: u32,
+    root_ca_crl_num: u32,
+    tcb_eval_ref_num: u32,
+    root_key_id: [u8; 48],
+    pck_ppid: [u8; 16],
+    tcb_cpusvn: [u8; 16],
+    tcb_pce_isvsvn: u16,
+    pce_id: u16,
+    tee_type: u32,
+    sgx_type: u8,
+    platform_instance_id: [u8; 16],
+    dynamic_platform: i32,
+    cached_keys: i32,
+    smt_enabled: i32,
+    sa_list: [u8; MAX_SA_LIST_SIZE],
+}
+
+/// TCB details taken from the supplemental data of a verified quote.
+struct TcbInfo {
+    /// Security advisories applying to the TCB level of the platform, e.g.,
+    /// INTEL-SA-00334.
+    advisory_ids: Vec<String>,
+    /// Date of the TCB level of the platform.
+    tcb_date: time_t,
+    /// Number of the TCB evaluation data the quote is verified against.
+    tcb_evaluation_data_number: u32,
+}
+
+impl TcbInfo {
+    fn from_supplemental(data: &[u8]) -> Option<Self> {
+        if data.len() < std::mem::size_of::<QvSupplemental>() {
+            return None;
+        }
+        let supplemental =
+            unsafe { std::ptr::read_unaligned(data.as_ptr() as *const QvSupplemental) };
+        let major_version = supplemental.version & 0xffff;
+        let advisory_ids = if major_version >= 3 {
+            let len = supplemental
+                .sa_list
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(MAX_SA_LIST_SIZE);
+            String::from_utf8_lossy(&supplemental.sa_list[..len])
+                .split(',')
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Some(Self {
+            advisory_ids,
+            tcb_date: supplemental.tcb_level_date_tag,
+            tcb_evaluation_data_number: supplemental.tcb_eval_ref_num,
+        })
+    }
 }
 
 enum QuoteVerificationResponse {
@@ -82,13 +179,21 @@ enum QuoteVerificationResponse {
 struct QuoteVerificationResult {
     pub quote_status: QlQvResult,
     pub isv_enclave_quote: String,
+    pub tcb_info: Option<TcbInfo>,
+    pub nonce: Option<String>,
 }
 
 impl QuoteVerificationResponse {
-    fn accept(quote_status: QlQvResult, isv_enclave_quote: String) -> Self {
+    fn accept(
+        quote_status: QlQvResult,
+        isv_enclave_quote: String,
+        tcb_info: Option<TcbInfo>,
+    ) -> Self {
         Self::AcceptedRequest(QuoteVerificationResult {
             quote_status,
             isv_enclave_quote,
+            tcb_info,
+            nonce: None,
         })
     }
 }
@@ -113,15 +218,102 @@ fn to_report(rst: &QlQvResult) -> &str {
 
 impl QuoteVerificationResult {
     pub fn to_json(&self) -> String {
-        serde_json::json!({
+        let mut report = serde_json::json!({
             "id": uuid::Uuid::new_v4().to_simple().to_string(),
             "version": 4,
             "timestamp": Utc::now().format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
             "isvEnclaveQuoteStatus": to_report(&self.quote_status),
             "isvEnclaveQuoteBody": self.isv_enclave_quote,
-        })
-        .to_string()
+        });
+        if let Some(nonce) = &self.nonce {
+            report["nonce"] = nonce.as_str().into();
+        }
+        if let Some(tcb_info) = &self.tcb_info {
+            // Same as the fields of IAS reports, except that IAS has no TCB
+            // evaluation data number
+            if !tcb_info.advisory_ids.is_empty() {
+                report["advisoryURL"] = "https://security-center.intel.com".into();
+                report["advisoryIDs"] = tcb_info.advisory_ids.clone().into();
+            }
+            if let Some(tcb_date) = Utc.timestamp_opt(tcb_info.tcb_date, 0).single() {
+                report["tcbDate"] = tcb_date.format("%Y-%m-%dT%H:%M:%SZ").to_string().into();
+            }
+            report["tcbEvaluationDataNumber"] = tcb_info.tcb_evaluation_data_number.into();
+        }
+        report.to_string()
+    }
+}
+
+/// Report signing key, selected by `key_type` under `[global.attestation]`.
+enum ReportSigner {
+    /// RSA key, signing with PKCS#1 v1.5 and SHA-256 like IAS
+    Rsa(signature::RsaKeyPair),
+    /// ECDSA P-256 key, signing with SHA-256 in ASN.1 DER
+    Ecdsa(signature::EcdsaKeyPair),
+}
+
+impl ReportSigner {
+    fn from_pkcs8(key_type: &str, der: &[u8]) -> Self {
+        match key_type {
+            "rsa" => Self::Rsa(signature::RsaKeyPair::from_pkcs8(der).unwrap()),
+            "ecdsa" => Self::Ecdsa(
+                signature::EcdsaKeyPair::from_pkcs8(
+                    &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+                    der,
+                )
+                .unwrap(),
+            ),
+            _ => panic!("unknown report signing key type: {}", key_type),
+        }
+    }
+}
+
+/// Sign a report with the report signing key, in base64.
+fn sign(payload: &str) -> String {
+    let rng = ring::rand::SystemRandom::new();
+    let signature = match &*SIGNER {
+        ReportSigner::Rsa(key_pair) => {
+            let mut signature = vec![0; key_pair.public_modulus_len()];
+            key_pair
+                .sign(
+                    &signature::RSA_PKCS1_SHA256,
+                    &rng,
+                    payload.as_bytes(),
+                    &mut signature,
+                )
+                .unwrap();
+            signature
+        }
+        ReportSigner::Ecdsa(key_pair) => key_pair
+            .sign(&rng, payload.as_bytes())
+            .unwrap()
+            .as_ref()
+            .to_vec(),
+    };
+    base64::encode(&signature)
+}
+
+fn json_response(payload: String, signature: Option<String>) -> response::Result<'static> {
+    let mut builder = response::Response::build();
+    builder
+        .header(http::ContentType::JSON)
+        .header(http::Header::new(
+            http::hyper::header::CONNECTION.as_str(),
+            "close",
+        ))
+        .raw_header(
+            "X-DCAPReport-Signing-Certificate",
+            percent_encoding::utf8_percent_encode(
+                &REPORT_SIGNING_CERT,
+                percent_encoding::NON_ALPHANUMERIC,
+            ),
+        );
+    if let Some(signature) = signature {
+        builder.raw_header("X-DCAPReport-Signature", signature);
     }
+    builder
+        .sized_body(payload.len(), std::io::Cursor::new(payload))
+        .ok()
 }
 
 impl<'r> response::Responder<'r, 'static> for QuoteVerificationResponse {
@@ -131,129 +323,275 @@ impl<'r> response::Responder<'r, 'static> for QuoteVerificationResponse {
             Self::InternalError => response::Result::Err(http::Status::InternalServerError),
             Self::AcceptedRequest(qvr) => {
                 let payload = qvr.to_json();
-                let mut signature = vec![0; SIGNER.public_modulus_len()];
-                let rng = ring::rand::SystemRandom::new();
-                SIGNER
-                    .sign(
-                        &signature::RSA_PKCS1_SHA256,
-                        &rng,
-                        payload.as_bytes(),
-                        &mut signature,
-                    )
-                    .unwrap();
-                response::Response::build()
-                    .header(http::ContentType::JSON)
-                    .header(http::Header::new(
-                        http::hyper::header::CONNECTION.as_str(),
-                        "close",
-                    ))
-                    .raw_header(
-                        "X-DCAPReport-Signing-Certificate",
-                        percent_encoding::utf8_percent_encode(
-                            &REPORT_SIGNING_CERT,
-                            percent_encoding::NON_ALPHANUMERIC,
-                        ),
-                    )
-                    .raw_header("X-DCAPReport-Signature", base64::encode(&signature))
-                    .sized_body(payload.len(), std::io::Cursor::new(payload))
-                    .ok()
+                let signature = sign(&payload);
+                json_response(payload, Some(signature))
             }
         }
     }
 }
 
+/// Responses to a batch of quotes, in the order of the quotes. Each accepted
+/// quote has its own report and signature, so that a report can be passed on
+/// and verified alone as the one of a single quote.
+enum BatchVerificationResponse {
+    BadRequest,
+    AcceptedRequest(Vec<QuoteVerificationResponse>),
+}
+
+impl<'r> response::Responder<'r, 'static> for BatchVerificationResponse {
+    fn respond_to(self, _: &rocket::Request) -> response::Result<'static> {
+        let responses = match self {
+            Self::BadRequest => return response::Result::Err(http::Status::BadRequest),
+            Self::AcceptedRequest(responses) => responses,
+        };
+        let reports: Vec<serde_json::Value> = responses
+            .into_iter()
+            .map(|response| match response {
+                QuoteVerificationResponse::BadRequest => {
+                    serde_json::json!({ "error": "BAD_REQUEST" })
+                }
+                QuoteVerificationResponse::InternalError => {
+                    serde_json::json!({ "error": "INTERNAL_ERROR" })
+                }
+                QuoteVerificationResponse::AcceptedRequest(qvr) => {
+                    let payload = qvr.to_json();
+                    let signature = sign(&payload);
+                    serde_json::json!({ "report": payload, "signature": signature })
+                }
+            })
+            .collect();
+        json_response(serde_json::Value::Array(reports).to_string(), None)
+    }
+}
+
+const MAX_BATCH_SIZE: usize = 64;
+
+// Same as the limit of IAS
+const MAX_NONCE_LEN: usize = 32;
+
 lazy_static! {
-    static ref GLOBAL_MUTEX: std::sync::Mutex<i32> = std::sync::Mutex::new(0i32);
+    // Quotes are verified in blocking worker threads, at most this many at a
+    // time. Set `attestation.workers` to 1 to verify them one by one.
+    static ref VERIFY_WORKERS: rocket::tokio::sync::Semaphore = {
+        let workers = Config::figment()
+            .extract_inner::<usize>("attestation.workers")
+            .unwrap_or_else(|_| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            });
+        rocket::tokio::sync::Semaphore::new(workers.max(1))
+    };
 }
 
-#[post(
-    "/sgx/dev/attestation/v4/report",
-    format = "application/json",
-    data = "<request>"
-)]
-async fn verify_quote(request: rocket::Data<'_>) -> QuoteVerificationResponse {
-    let bytes = request.open(1.megabytes()).into_bytes().await.unwrap();
+async fn read_json(
+    request: rocket::Data<'_>,
+    limit: rocket::data::ByteUnit,
+) -> Option<serde_json::Value> {
+    let bytes = request.open(limit).into_bytes().await.ok()?;
     if !bytes.is_complete() {
         eprintln!("there are bytes remaining in the stream");
-        return QuoteVerificationResponse::BadRequest;
+        return None;
     }
 
-    let v = match serde_json::from_slice::<serde_json::Value>(&bytes) {
-        Ok(v) => v,
+    match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(v) => Some(v),
         Err(e) => {
             eprintln!("bad request {:?}", e);
-            return QuoteVerificationResponse::BadRequest;
+            None
+        }
+    }
+}
+
+/// The optional nonce of a request, echoed in the reports so that clients can
+/// tell them from replayed ones. Err if the nonce is malformed.
+fn read_nonce(v: &serde_json::Value) -> Result<Option<String>, ()> {
+    match &v["nonce"] {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::String(nonce) if nonce.len() <= MAX_NONCE_LEN => {
+            Ok(Some(nonce.to_string()))
         }
+        _ => Err(()),
+    }
+}
+
+async fn verify_in_worker(base64_quote: &str, nonce: Option<String>) -> QuoteVerificationResponse {
+    let quote = match base64::decode(base64_quote) {
+        Ok(v) => v,
+        Err(_) => return QuoteVerificationResponse::BadRequest,
+    };
+    let _permit = match VERIFY_WORKERS.acquire().await {
+        Ok(permit) => permit,
+        Err(_) => return QuoteVerificationResponse::InternalError,
     };
+    let response = rocket::tokio::task::spawn_blocking(move || verify(&quote))
+        .await
+        .unwrap_or(QuoteVerificationResponse::InternalError);
+    match response {
+        QuoteVerificationResponse::AcceptedRequest(qvr) => {
+            QuoteVerificationResponse::AcceptedRequest(QuoteVerificationResult { nonce, ..qvr })
+        }
+        response => response,
+    }
+}
 
-    if let serde_json::Value::String(base64_quote) = &v["isvEnclaveQuote"] {
-        let quote = match base64::decode(base64_quote) {
-            Ok(v) => v,
-            Err(_) => return QuoteVerificationResponse::BadRequest,
-        };
-        let mut collateral_exp_status = 1u32;
-        let mut quote_verification_result = QlQvResult::Unspecified;
-        let mut qve_report_info = QlQeReportInfo::default();
-
-        let mut nonce = QuoteNonce::default();
-        let mut rng = rand::rngs::StdRng::from_entropy();
-        rng.fill_bytes(&mut nonce.rand);
-        qve_report_info.nonce = nonce;
-        let mut expiration_check_date: time_t = 0;
-
-        let ret = {
-            let _lock = GLOBAL_MUTEX.lock();
-            unsafe {
-                sgx_qv_verify_quote(
-                    quote.as_ptr(),
-                    quote.len() as _,
-                    std::ptr::null() as _,
-                    libc::time(&mut expiration_check_date),
-                    &mut collateral_exp_status as _,
-                    &mut quote_verification_result as _,
-                    &mut qve_report_info as _,
-                    0,
-                    std::ptr::null_mut(),
-                )
-            }
-        };
+fn is_td_quote(quote: &[u8]) -> bool {
+    quote.len() >= 8 && u32::from_le_bytes([quote[4], quote[5], quote[6], quote[7]]) == TDX_TEE_TYPE
+}
 
-        if ret != Quote3Error::Success {
-            eprintln!("sgx_qv_verify_quote failed: {:?}", ret);
-            return QuoteVerificationResponse::BadRequest;
-        };
+fn verify(quote: &[u8]) -> QuoteVerificationResponse {
+    // TD quotes of TDX guests are verified by the TDX flavor of the library,
+    // with the same collateral and results as the quotes of enclaves.
+    let is_td = is_td_quote(quote);
+    let (get_supplemental_data_size, verify_quote, body_size) = if is_td {
+        (
+            tdx_qv_get_quote_supplemental_data_size as unsafe extern "C" fn(_) -> _,
+            tdx_qv_verify_quote as unsafe extern "C" fn(_, _, _, _, _, _, _, _, _) -> _,
+            TDX_QUOTE_BODY_SIZE,
+        )
+    } else {
+        (
+            sgx_qv_get_quote_supplemental_data_size as unsafe extern "C" fn(_) -> _,
+            sgx_qv_verify_quote as unsafe extern "C" fn(_, _, _, _, _, _, _, _, _) -> _,
+            SGX_QUOTE_BODY_SIZE,
+        )
+    };
+    if quote.len() < body_size {
+        return QuoteVerificationResponse::BadRequest;
+    }
 
-        if collateral_exp_status != 0 {
-            eprintln!("collateral_exp_status failed: {:?}", collateral_exp_status);
-            return QuoteVerificationResponse::BadRequest;
-        }
+    let mut collateral_exp_status = 1u32;
+    let mut quote_verification_result = QlQvResult::Unspecified;
+    let mut qve_report_info = QlQeReportInfo::default();
 
-        let mut sha256 = sgx_crypto::sha::Sha256::new().unwrap();
-        sha256.update(&nonce.rand).unwrap();
-        sha256.update(quote.as_slice()).unwrap();
-        sha256.update(&expiration_check_date).unwrap();
-        sha256.update(&collateral_exp_status).unwrap();
-        sha256.update(&(quote_verification_result as u32)).unwrap();
-        let sha256_hash = sha256.finalize().unwrap();
-
-        // This check isn't quote necessary if we are verifying the nonce in
-        // an untrusted environment
-        if sha256_hash != qve_report_info.qe_report.body.report_data.d[..32]
-            || [0u8; 32] != qve_report_info.qe_report.body.report_data.d[32..]
-        {
-            // Something wrong with out SW stack, probably compromised
-            return QuoteVerificationResponse::InternalError;
-        }
+    let mut nonce = QuoteNonce::default();
+    let mut rng = rand::rngs::StdRng::from_entropy();
+    rng.fill_bytes(&mut nonce.rand);
+    qve_report_info.nonce = nonce;
+    let mut expiration_check_date: time_t = 0;
+
+    let mut supplemental_data_size = 0u32;
+    let ret = unsafe { get_supplemental_data_size(&mut supplemental_data_size as _) };
+    if ret != Quote3Error::Success {
+        eprintln!("get_quote_supplemental_data_size failed: {:?}", ret);
+        supplemental_data_size = 0;
+    }
+    let mut supplemental_data = vec![0u8; supplemental_data_size as usize];
+
+    let ret = unsafe {
+        verify_quote(
+            quote.as_ptr(),
+            quote.len() as _,
+            std::ptr::null() as _,
+            libc::time(&mut expiration_check_date),
+            &mut collateral_exp_status as _,
+            &mut quote_verification_result as _,
+            &mut qve_report_info as _,
+            supplemental_data_size,
+            if supplemental_data.is_empty() {
+                std::ptr::null_mut()
+            } else {
+                supplemental_data.as_mut_ptr()
+            },
+        )
+    };
+
+    if ret != Quote3Error::Success {
+        eprintln!("verify_quote failed (TD quote: {}): {:?}", is_td, ret);
+        return QuoteVerificationResponse::BadRequest;
+    };
+
+    if collateral_exp_status != 0 {
+        eprintln!("collateral_exp_status failed: {:?}", collateral_exp_status);
+        return QuoteVerificationResponse::BadRequest;
+    }
+
+    let mut sha256 = sgx_crypto::sha::Sha256::new().unwrap();
+    sha256.update(&nonce.rand).unwrap();
+    sha256.update(quote).unwrap();
+    sha256.update(&expiration_check_date).unwrap();
+    sha256.update(&collateral_exp_status).unwrap();
+    sha256.update(&(quote_verification_result as u32)).unwrap();
+    if !supplemental_data.is_empty() {
+        sha256.update(supplemental_data.as_slice()).unwrap();
+    }
+    let sha256_hash = sha256.finalize().unwrap();
+
+    // This check isn't quote necessary if we are verifying the nonce in
+    // an untrusted environment
+    if sha256_hash != qve_report_info.qe_report.body.report_data.d[..32]
+        || [0u8; 32] != qve_report_info.qe_report.body.report_data.d[32..]
+    {
+        // Something wrong with out SW stack, probably compromised
+        return QuoteVerificationResponse::InternalError;
+    }
+
+    // strip off signature data; client won't need this
+    let quote_body = base64::encode(&quote[..body_size]);
+    let tcb_info = TcbInfo::from_supplemental(&supplemental_data);
+    QuoteVerificationResponse::accept(quote_verification_result, quote_body, tcb_info)
+}
+
+#[post(
+    "/sgx/dev/attestation/v4/report",
+    format = "application/json",
+    data = "<request>"
+)]
+async fn verify_quote(request: rocket::Data<'_>) -> QuoteVerificationResponse {
+    let v = match read_json(request, 1.megabytes()).await {
+        Some(v) => v,
+        None => return QuoteVerificationResponse::BadRequest,
+    };
 
-        // strip off signature data; client won't need this
-        let quote_body = base64::encode(&quote[..432]);
-        QuoteVerificationResponse::accept(quote_verification_result, quote_body)
+    let nonce = match read_nonce(&v) {
+        Ok(nonce) => nonce,
+        Err(_) => return QuoteVerificationResponse::BadRequest,
+    };
+    if let serde_json::Value::String(base64_quote) = &v["isvEnclaveQuote"] {
+        verify_in_worker(base64_quote, nonce).await
     } else {
         QuoteVerificationResponse::BadRequest
     }
 }
 
+/// Verify the quotes in `isvEnclaveQuotes`, up to `MAX_BATCH_SIZE` of them,
+/// in parallel. The `nonce` of the request is echoed in every report.
+#[post(
+    "/sgx/dev/attestation/v4/reports",
+    format = "application/json",
+    data = "<request>"
+)]
+async fn verify_quotes(request: rocket::Data<'_>) -> BatchVerificationResponse {
+    let v = match read_json(request, (MAX_BATCH_SIZE as u64).megabytes()).await {
+        Some(v) => v,
+        None => return BatchVerificationResponse::BadRequest,
+    };
+
+    let quotes = match &v["isvEnclaveQuotes"] {
+        serde_json::Value::Array(quotes) if quotes.len() <= MAX_BATCH_SIZE => quotes,
+        _ => return BatchVerificationResponse::BadRequest,
+    };
+    let nonce = match read_nonce(&v) {
+        Ok(nonce) => nonce,
+        Err(_) => return BatchVerificationResponse::BadRequest,
+    };
+    let verifications = quotes.iter().map(|quote| {
+        let nonce = nonce.clone();
+        async move {
+            match quote {
+                serde_json::Value::String(base64_quote) => {
+                    verify_in_worker(base64_quote, nonce).await
+                }
+                _ => QuoteVerificationResponse::BadRequest,
+            }
+        }
+    });
+    BatchVerificationResponse::AcceptedRequest(
+        rocket::futures::future::join_all(verifications).await,
+    )
+}
+
 #[launch]
 fn rocket() -> _ {
-    rocket::build().mount("/", routes![verify_quote])
+    rocket::build().mount("/", routes![verify_quote, verify_quotes])
 }
@@ -30,6 +30,8 @@ use std::ffi::c_void;
 const FFI_OK: c_uint = 0;
 const FFI_FILE_ERROR: c_uint = 1;
 const FFI_FILE_ERROR_WASM: c_int = -1;
+const FFI_SERVICE_ERROR: c_uint = 2;
+const FFI_SERVICE_ERROR_WASM: c_int = -1;
 
 pub struct Context {
     runtime: Box<dyn TeaclaveRuntime + Send + Sync>,
@@ -82,6 +84,14 @@ impl Context {
         }
         Ok(())
     }
+
+    fn current_time(&self) -> anyhow::Result<u64> {
+        self.runtime.current_time()
+    }
+
+    fn random_bytes(&self, buf: &mut [u8]) -> anyhow::Result<()> {
+        self.runtime.random_bytes(buf)
+    }
 }
 
 trait HandleEncoding {
@@ -231,6 +241,22 @@ pub fn rtc_close_handle(f: FileHandle) -> anyhow::Result<()> {
     })
 }
 
+pub fn rtc_current_time() -> anyhow::Result<u64> {
+    CONTEXT.with(|ctx| {
+        let ctx = ctx.borrow();
+        anyhow::ensure!(ctx.is_some(), "Context not initialized");
+        ctx.as_ref().unwrap().current_time()
+    })
+}
+
+pub fn rtc_random_bytes(buf: &mut [u8]) -> anyhow::Result<()> {
+    CONTEXT.with(|ctx| {
+        let ctx = ctx.borrow();
+        anyhow::ensure!(ctx.is_some(), "Context not initialized");
+        ctx.as_ref().unwrap().random_bytes(buf)
+    })
+}
+
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
@@ -245,7 +271,11 @@ pub mod tests {
     use teaclave_types::StagedFiles;
 
     pub fn run_tests() -> bool {
-        run_tests!(test_file_handle_encoding, test_rtc_api,)
+        run_tests!(
+            test_file_handle_encoding,
+            test_rtc_api,
+            test_rtc_runtime_services,
+        )
     }
 
     fn test_file_handle_encoding() {
@@ -291,6 +321,24 @@ pub mod tests {
         assert!(rtc_close_handle(f).is_err());
         reset_thread_context().unwrap();
     }
+
+    fn test_rtc_runtime_services() {
+        let mut buf = [0u8; 16];
+        assert!(rtc_current_time().is_err());
+        assert!(rtc_random_bytes(&mut buf).is_err());
+
+        let runtime = Box::new(RawIoRuntime::new(
+            StagedFiles::default(),
+            StagedFiles::default(),
+        ));
+        set_thread_context(Context::new(runtime)).unwrap();
+
+        let first = rtc_current_time().unwrap();
+        assert!(rtc_current_time().unwrap() >= first);
+        assert!(rtc_random_bytes(&mut buf).is_ok());
+        assert_ne!(buf, [0u8; 16]);
+        reset_thread_context().unwrap();
+    }
 }
 
 use std::ffi::CStr;
@@ -498,3 +546,76 @@ pub extern "C" fn wasm_close_file(_exec_env: *const c_void, handle: c_int) -> c_
         }
     }
 }
+
+// uint c_current_time(uint64_t* out_time);
+#[allow(unused)]
+#[no_mangle]
+extern "C" fn c_current_time(out_time: *mut u64) -> c_uint {
+    debug!("c_current_time");
+    match rtc_current_time() {
+        Ok(time) => {
+            unsafe {
+                *out_time = time;
+            }
+            FFI_OK
+        }
+        Err(e) => {
+            error!("c_current_time: {:?}", e);
+            FFI_SERVICE_ERROR
+        }
+    }
+}
+
+/// int64_t teaclave_current_time();
+///
+/// # Safety
+/// FFI function and pointer arguments should be valid.
+#[allow(unused)]
+#[no_mangle]
+pub unsafe extern "C" fn wasm_current_time(_exec_env: *const c_void) -> i64 {
+    debug!("wasm_current_time");
+    match rtc_current_time() {
+        Ok(time) => time as i64,
+        Err(e) => {
+            error!("wasm_current_time: {:?}", e);
+            FFI_SERVICE_ERROR_WASM as i64
+        }
+    }
+}
+
+// uint c_random_bytes(void* out_buf, size_t buf_size);
+#[allow(unused)]
+#[no_mangle]
+extern "C" fn c_random_bytes(out_buf: *mut c_uchar, buf_size: size_t) -> c_uint {
+    debug!("c_random_bytes");
+    let out: &mut [u8] = unsafe { slice::from_raw_parts_mut(out_buf, buf_size) };
+    match rtc_random_bytes(out) {
+        Ok(()) => FFI_OK,
+        Err(e) => {
+            error!("c_random_bytes: {:?}", e);
+            FFI_SERVICE_ERROR
+        }
+    }
+}
+
+/// int teaclave_random_bytes(void* out_buf, int buf_size);
+///
+/// # Safety
+/// FFI function and pointer arguments should be valid.
+#[allow(unused)]
+#[no_mangle]
+pub unsafe extern "C" fn wasm_random_bytes(
+    _exec_env: *const c_void,
+    out_buf: *mut c_uchar,
+    buf_size: c_int,
+) -> c_int {
+    debug!("wasm_random_bytes");
+    let out: &mut [u8] = unsafe { slice::from_raw_parts_mut(out_buf, buf_size as usize) };
+    match rtc_random_bytes(out) {
+        Ok(()) => FFI_OK as i32,
+        Err(e) => {
+            error!("wasm_random_bytes: {:?}", e);
+            FFI_SERVICE_ERROR_WASM
+        }
+    }
+}
@@ -16,9 +16,10 @@
 // under the License.
 
 use teaclave_function::{
-    Echo, FaceDetection, GbdtPredict, GbdtTrain, LogisticRegressionPredict,
+    Echo, FaceDetection, GbdtPredict, GbdtTrain, KAnonymityVerify, LogisticRegressionPredict,
     LogisticRegressionTrain, OnlineDecrypt, OrderedSetIntersect, OrderedSetJoin, PasswordCheck,
-    PrincipalComponentsAnalysis, PrivateJoinAndCompute, RsaSign,
+    PiiRedact, PrincipalComponentsAnalysis, PrivateJoinAndCompute, PrivateSetIntersection, RsaSign,
+    SecureAggregation, SqlQuery,
 };
 use teaclave_types::{FunctionArguments, FunctionRuntime, TeaclaveExecutor};
 
@@ -66,6 +67,16 @@ impl TeaclaveExecutor for BuiltinFunctionExecutor {
             FaceDetection::NAME => FaceDetection::new().run(arguments, runtime),
             #[cfg(feature = "builtin_password_check")]
             PasswordCheck::NAME => PasswordCheck::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_pii_redact")]
+            PiiRedact::NAME => PiiRedact::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_k_anonymity_verify")]
+            KAnonymityVerify::NAME => KAnonymityVerify::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_secure_aggregation")]
+            SecureAggregation::NAME => SecureAggregation::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_private_set_intersection")]
+            PrivateSetIntersection::NAME => PrivateSetIntersection::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_sql_query")]
+            SqlQuery::NAME => SqlQuery::new().run(arguments, runtime),
             _ => bail!("Function not found."),
         }
     }
@@ -24,6 +24,33 @@ use std::ffi::CString;
 use teaclave_types::{FunctionArguments, FunctionRuntime, TeaclaveExecutor};
 
 const MAXPYBUFLEN: usize = 20480;
+
+// Appended to payloads for reading typed arguments from `argv`, in which
+// values other than strings are passed as JSON texts.
+const MESAPY_ARGUMENT_HELPER: &str = r#"
+
+def teaclave_argument(argv, key, kind=str):
+    for i in range(0, len(argv) - 1, 2):
+        if argv[i] != key:
+            continue
+        value = argv[i + 1]
+        if kind is str:
+            return value
+        if kind is bool:
+            if value not in ("true", "false"):
+                raise TypeError("argument %s is not a boolean" % key)
+            return value == "true"
+        if kind is int:
+            return int(value)
+        if kind is float:
+            return float(value)
+        import json
+        value = json.loads(value)
+        if not isinstance(value, kind):
+            raise TypeError("argument %s is not %s" % (key, kind.__name__))
+        return value
+    raise KeyError(key)
+"#;
 const MESAPY_ERROR_BUFFER_TOO_SHORT: i64 = -1i64;
 const MESAPY_EXEC_ERROR: i64 = -2i64;
 
@@ -54,6 +81,7 @@ impl TeaclaveExecutor for MesaPy {
             .map(|arg| CString::new(arg.as_str()).unwrap())
             .collect();
 
+        payload.extend_from_slice(MESAPY_ARGUMENT_HELPER.as_bytes());
         payload.push(0u8);
 
         let mut p_argv: Vec<_> = cstr_argv
@@ -99,7 +127,7 @@ pub mod tests {
     use teaclave_types::*;
 
     pub fn run_tests() -> bool {
-        run_tests!(test_mesapy,)
+        run_tests!(test_mesapy, test_mesapy_typed_arguments,)
     }
 
     fn test_mesapy() {
@@ -175,4 +203,42 @@ def entrypoint(argv):
             .unwrap();
         assert_eq!(summary, "");
     }
+
+    fn test_mesapy_typed_arguments() {
+        let py_args = FunctionArguments::from_json(serde_json::json!({
+            "name": "teaclave",
+            "count": 3,
+            "ratio": 0.5,
+            "enabled": true,
+        }))
+        .unwrap();
+        let py_payload = r#"
+def entrypoint(argv):
+    assert teaclave_argument(argv, "name") == "teaclave"
+    assert teaclave_argument(argv, "count", int) == 3
+    assert teaclave_argument(argv, "ratio", float) == 0.5
+    assert teaclave_argument(argv, "enabled", bool) is True
+    try:
+        teaclave_argument(argv, "name", bool)
+    except TypeError:
+        pass
+    return "%d" % teaclave_argument(argv, "count", int)
+"#;
+
+        let runtime = Box::new(RawIoRuntime::new(
+            StagedFiles::default(),
+            StagedFiles::default(),
+        ));
+
+        let function = MesaPy::default();
+        let summary = function
+            .execute(
+                "".to_string(),
+                py_args,
+                py_payload.as_bytes().to_vec(),
+                runtime,
+            )
+            .unwrap();
+        assert_eq!(summary, "3");
+    }
 }
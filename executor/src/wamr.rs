@@ -19,7 +19,8 @@ use teaclave_executor_context::context::reset_thread_context;
 use teaclave_executor_context::context::set_thread_context;
 use teaclave_executor_context::context::Context;
 use teaclave_executor_context::context::{
-    wasm_close_file, wasm_create_output, wasm_open_input, wasm_read_file, wasm_write_file,
+    wasm_close_file, wasm_create_output, wasm_current_time, wasm_open_input, wasm_random_bytes,
+    wasm_read_file, wasm_write_file,
 };
 
 use std::ffi::{c_void, CStr, CString};
@@ -117,7 +118,7 @@ impl TeaclaveExecutor for WAMicroRuntime {
         assert!(ret);
 
         // export native function
-        let export_symbols: [NativeSymbol; 5] = [
+        let export_symbols: [NativeSymbol; 7] = [
             NativeSymbol {
                 symbol: b"teaclave_open_input\0".as_ptr() as _,
                 func_ptr: wasm_open_input as *const c_void,
@@ -148,6 +149,18 @@ impl TeaclaveExecutor for WAMicroRuntime {
                 signature: b"(i)i\0".as_ptr() as _,
                 attachment: std::ptr::null(),
             },
+            NativeSymbol {
+                symbol: b"teaclave_current_time\0".as_ptr() as _,
+                func_ptr: wasm_current_time as *const c_void,
+                signature: b"()I\0".as_ptr() as _,
+                attachment: std::ptr::null(),
+            },
+            NativeSymbol {
+                symbol: b"teaclave_random_bytes\0".as_ptr() as _,
+                func_ptr: wasm_random_bytes as *const c_void,
+                signature: b"(*~)i\0".as_ptr() as _,
+                attachment: std::ptr::null(),
+            },
         ];
 
         let register_succeeded = unsafe {
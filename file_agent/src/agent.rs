@@ -22,7 +22,10 @@ use tokio_util::codec;
 use url::Url;
 
 use std::path::{Component, Path, PathBuf};
-use teaclave_types::{FileAgentRequest, HandleFileCommand, HandleFileInfo};
+use std::time::Duration;
+use teaclave_types::{DownloadOptions, FileAgentRequest, HandleFileCommand, HandleFileInfo};
+
+use crate::download::download_chunked;
 
 async fn download_remote_input_to_file(
     presigned_url: Url,
@@ -83,6 +86,7 @@ async fn upload_output_file_to_remote(
 async fn handle_download(
     info: HandleFileInfo,
     fusion_base: impl AsRef<Path>,
+    options: DownloadOptions,
 ) -> anyhow::Result<()> {
     anyhow::ensure!(
         !info.local.exists(),
@@ -94,7 +98,9 @@ async fn handle_download(
 
     match remote.scheme() {
         "https" | "http" => {
-            download_remote_input_to_file(remote, dst).await?;
+            if !download_chunked(&remote, &dst, &options).await? {
+                download_remote_input_to_file(remote, dst).await?;
+            }
         }
         "file" => {
             // Note: For LibOS, the file path must be inside the LibOS's file system
@@ -192,38 +198,104 @@ async fn handle_upload(info: HandleFileInfo, fusion_base: impl AsRef<Path>) -> a
     Ok(())
 }
 
+async fn delete_remote_file(presigned_url: Url) -> anyhow::Result<()> {
+    reqwest::Client::new()
+        .delete(presigned_url.as_str())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn handle_delete(info: HandleFileInfo, fusion_base: impl AsRef<Path>) -> anyhow::Result<()> {
+    match info.remote.scheme() {
+        "https" | "http" => {
+            delete_remote_file(info.remote).await?;
+        }
+        "file" => {
+            let path = info
+                .remote
+                .to_file_path()
+                .map_err(|e| anyhow::anyhow!("Cannot convert to path: {:?}", e))?;
+            tokio::fs::remove_file(path).await?;
+        }
+        "fusion" => {
+            let path = info
+                .remote
+                .to_file_path()
+                .map_err(|e| anyhow::anyhow!("Cannot convert fusion:// to path: {:?}", e))?;
+            let components = path.components().collect::<Vec<_>>();
+            anyhow::ensure!(
+                (components[0] == Component::RootDir)
+                    && (components[1] == Component::Normal("TEACLAVE_FUSION_BASE".as_ref())),
+                "[Delete] Fusion data format error: {:?}",
+                components
+            );
+
+            let relative_path: PathBuf = components[2..].iter().collect();
+            tokio::fs::remove_file(fusion_base.as_ref().join(relative_path)).await?;
+        }
+        _ => anyhow::bail!("Scheme not supported"),
+    }
+    Ok(())
+}
+
 pub fn handle_file_request(bytes: &[u8]) -> anyhow::Result<()> {
     let req: FileAgentRequest = serde_json::from_slice(bytes)?;
+    let timeout = req.timeout_ms.map(Duration::from_millis);
     let results = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?
         .block_on(async {
             let fusion_base = req.fusion_base.clone();
-            match req.cmd {
-                HandleFileCommand::Download => {
-                    let futures: Vec<_> = req
-                        .info
-                        .into_iter()
-                        .map(|info| {
-                            let fusion_base = fusion_base.clone();
-                            tokio::spawn(async { handle_download(info, fusion_base).await })
-                        })
-                        .collect();
-                    join_all(futures).await
-                }
-                HandleFileCommand::Upload => {
-                    let futures: Vec<_> = req
-                        .info
-                        .into_iter()
-                        .map(|info| {
-                            let fusion_base = fusion_base.clone();
-                            tokio::spawn(async { handle_upload(info, fusion_base).await })
-                        })
-                        .collect();
-                    join_all(futures).await
+            let download_options = req.download_options.clone();
+            let transfers = async move {
+                match req.cmd {
+                    HandleFileCommand::Download => {
+                        let futures: Vec<_> = req
+                            .info
+                            .into_iter()
+                            .map(|info| {
+                                let fusion_base = fusion_base.clone();
+                                let options = download_options.clone();
+                                tokio::spawn(async {
+                                    handle_download(info, fusion_base, options).await
+                                })
+                            })
+                            .collect();
+                        join_all(futures).await
+                    }
+                    HandleFileCommand::Upload => {
+                        let futures: Vec<_> = req
+                            .info
+                            .into_iter()
+                            .map(|info| {
+                                let fusion_base = fusion_base.clone();
+                                tokio::spawn(async { handle_upload(info, fusion_base).await })
+                            })
+                            .collect();
+                        join_all(futures).await
+                    }
+                    HandleFileCommand::Delete => {
+                        let futures: Vec<_> = req
+                            .info
+                            .into_iter()
+                            .map(|info| {
+                                let fusion_base = fusion_base.clone();
+                                tokio::spawn(async { handle_delete(info, fusion_base).await })
+                            })
+                            .collect();
+                        join_all(futures).await
+                    }
                 }
+            };
+            match timeout {
+                Some(limit) => tokio::time::timeout(limit, transfers)
+                    .await
+                    .map_err(|_| anyhow::anyhow!("Timed out after {:?}", limit)),
+                None => Ok(transfers.await),
             }
-        });
+        })?;
 
     let (task_results, errs): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
 
@@ -286,6 +358,28 @@ mod tests {
         std::fs::remove_file(&dest).unwrap();
     }
 
+    #[test]
+    fn test_get_single_file_in_chunks() {
+        let s = "http://localhost:6789/fixtures/functions/gbdt_training/train.txt";
+        let url = Url::parse(s).unwrap();
+        let dest = PathBuf::from("/tmp/input_test_get_single_file_in_chunks.txt");
+
+        let info = HandleFileInfo::new(&dest, &url);
+        let options = DownloadOptions {
+            chunk_size: 1024,
+            parallelism: 2,
+            max_retries: 1,
+        };
+        let req = FileAgentRequest::new(HandleFileCommand::Download, vec![info], "")
+            .download_options(options);
+
+        let bytes = serde_json::to_vec(&req).unwrap();
+        handle_file_request(&bytes).unwrap();
+        assert!(!crate::download::manifest_path(&dest).exists());
+
+        std::fs::remove_file(&dest).unwrap();
+    }
+
     #[test]
     fn test_put_single_file() {
         let src = PathBuf::from("/tmp/output_single_test.txt");
@@ -324,6 +418,14 @@ mod tests {
         let bytes = serde_json::to_vec(&req).unwrap();
         handle_file_request(&bytes).unwrap();
 
+        // test local delete
+        let info = HandleFileInfo::new("", &url);
+        let req = FileAgentRequest::new(HandleFileCommand::Delete, vec![info], "");
+
+        let bytes = serde_json::to_vec(&req).unwrap();
+        handle_file_request(&bytes).unwrap();
+        assert!(!base.join("d1.txt").exists());
+
         std::fs::remove_dir_all(&base).unwrap();
     }
 
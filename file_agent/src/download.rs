@@ -0,0 +1,328 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Chunked and resumable downloads of remote inputs.
+//!
+//! A file is fetched with HTTP range requests into `<dest>.part`. The SHA-256
+//! digest of every completed chunk is recorded in `<dest>.part.json`, so an
+//! interrupted download only refetches chunks that are missing or whose
+//! on-disk content no longer matches the recorded digest.
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{Mutex, Semaphore};
+use url::Url;
+
+use teaclave_types::DownloadOptions;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DownloadManifest {
+    total_len: u64,
+    chunk_size: u64,
+    /// Hex-encoded SHA-256 digest of each finished chunk.
+    chunks: Vec<Option<String>>,
+}
+
+impl DownloadManifest {
+    fn new(total_len: u64, chunk_size: u64) -> Self {
+        let count = ((total_len + chunk_size - 1) / chunk_size) as usize;
+        DownloadManifest {
+            total_len,
+            chunk_size,
+            chunks: vec![None; count],
+        }
+    }
+
+    fn chunk_range(&self, index: usize) -> (u64, u64) {
+        let start = index as u64 * self.chunk_size;
+        let end = std::cmp::min(start + self.chunk_size, self.total_len);
+        (start, end)
+    }
+
+    async fn load(path: &Path) -> Option<Self> {
+        let bytes = tokio::fs::read(path).await.ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let bytes = serde_json::to_vec(self)?;
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+}
+
+struct RemoteInfo {
+    total_len: u64,
+    accept_ranges: bool,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(ring::digest::digest(&ring::digest::SHA256, data))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".");
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+pub(crate) fn part_path(dest: &Path) -> PathBuf {
+    with_suffix(dest, "part")
+}
+
+pub(crate) fn manifest_path(dest: &Path) -> PathBuf {
+    with_suffix(dest, "part.json")
+}
+
+async fn probe_remote(client: &reqwest::Client, url: &Url) -> anyhow::Result<RemoteInfo> {
+    let res = client.head(url.as_str()).send().await?.error_for_status()?;
+    let headers = res.headers();
+    let total_len = headers
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(0);
+    let accept_ranges = headers
+        .get(reqwest::header::ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.eq_ignore_ascii_case("bytes"))
+        .unwrap_or(false);
+    Ok(RemoteInfo {
+        total_len,
+        accept_ranges,
+    })
+}
+
+async fn read_chunk(path: &Path, start: u64, end: u64) -> anyhow::Result<Vec<u8>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    let mut buf = vec![0u8; (end - start) as usize];
+    file.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+async fn fetch_chunk(
+    client: &reqwest::Client,
+    url: &Url,
+    part: &Path,
+    start: u64,
+    end: u64,
+) -> anyhow::Result<String> {
+    let res = client
+        .get(url.as_str())
+        .header(
+            reqwest::header::RANGE,
+            format!("bytes={}-{}", start, end - 1),
+        )
+        .send()
+        .await?
+        .error_for_status()?;
+    anyhow::ensure!(
+        res.status() == http::StatusCode::PARTIAL_CONTENT,
+        "Range request not honored: {}",
+        res.status()
+    );
+    let bytes = res.bytes().await?;
+    anyhow::ensure!(
+        bytes.len() as u64 == end - start,
+        "Chunk length mismatch: expect {}, got {}",
+        end - start,
+        bytes.len()
+    );
+    let digest = sha256_hex(&bytes);
+
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(part).await?;
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    file.write_all(&bytes).await?;
+    file.flush().await?;
+
+    // Read back what actually landed on disk before recording the chunk as done.
+    let written = read_chunk(part, start, end).await?;
+    anyhow::ensure!(
+        sha256_hex(&written) == digest,
+        "Chunk checksum mismatch at bytes {}-{}",
+        start,
+        end - 1
+    );
+    Ok(digest)
+}
+
+async fn fetch_chunk_with_retry(
+    client: &reqwest::Client,
+    url: &Url,
+    part: &Path,
+    range: (u64, u64),
+    max_retries: u32,
+) -> anyhow::Result<String> {
+    let (start, end) = range;
+    let mut attempt = 0;
+    loop {
+        match fetch_chunk(client, url, part, start, end).await {
+            Ok(digest) => return Ok(digest),
+            Err(e) if attempt < max_retries => {
+                attempt += 1;
+                warn!(
+                    "[Download] chunk {}-{} failed (attempt {}/{}): {:?}",
+                    start, end, attempt, max_retries, e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Load a previous manifest for `dest` if it describes the same remote object
+/// and chunking, dropping the digests of chunks that no longer verify.
+async fn resume_manifest(
+    dest: &Path,
+    total_len: u64,
+    chunk_size: u64,
+) -> anyhow::Result<DownloadManifest> {
+    let part = part_path(dest);
+    let manifest = match DownloadManifest::load(&manifest_path(dest)).await {
+        Some(m) if m.total_len == total_len && m.chunk_size == chunk_size && part.exists() => m,
+        _ => {
+            let file = tokio::fs::File::create(&part).await?;
+            file.set_len(total_len).await?;
+            return Ok(DownloadManifest::new(total_len, chunk_size));
+        }
+    };
+
+    let mut manifest = manifest;
+    for index in 0..manifest.chunks.len() {
+        let (start, end) = manifest.chunk_range(index);
+        let valid = match &manifest.chunks[index] {
+            Some(digest) => match read_chunk(&part, start, end).await {
+                Ok(bytes) => &sha256_hex(&bytes) == digest,
+                Err(_) => false,
+            },
+            None => false,
+        };
+        if !valid {
+            manifest.chunks[index] = None;
+        }
+    }
+    Ok(manifest)
+}
+
+/// Download `url` into `dest` with ranged requests. Returns `Ok(false)` when
+/// the remote does not support ranges so the caller can fall back to a plain
+/// streaming download.
+pub(crate) async fn download_chunked(
+    url: &Url,
+    dest: &Path,
+    options: &DownloadOptions,
+) -> anyhow::Result<bool> {
+    anyhow::ensure!(options.chunk_size > 0, "Invalid download chunk size");
+    let client = reqwest::Client::new();
+    let remote = probe_remote(&client, url).await?;
+    if !remote.accept_ranges || remote.total_len == 0 {
+        return Ok(false);
+    }
+
+    let part = part_path(dest);
+    let manifest_file = manifest_path(dest);
+    let manifest = resume_manifest(dest, remote.total_len, options.chunk_size).await?;
+    let pending: Vec<usize> = (0..manifest.chunks.len())
+        .filter(|i| manifest.chunks[*i].is_none())
+        .collect();
+    debug!(
+        "[Download] {}: {} of {} chunks pending",
+        url,
+        pending.len(),
+        manifest.chunks.len()
+    );
+
+    let manifest = Arc::new(Mutex::new(manifest));
+    let semaphore = Arc::new(Semaphore::new(std::cmp::max(options.parallelism, 1)));
+    let futures: Vec<_> = pending
+        .into_iter()
+        .map(|index| {
+            let client = client.clone();
+            let url = url.clone();
+            let part = part.clone();
+            let manifest_file = manifest_file.clone();
+            let manifest = manifest.clone();
+            let semaphore = semaphore.clone();
+            let max_retries = options.max_retries;
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire().await?;
+                let range = manifest.lock().await.chunk_range(index);
+                let digest =
+                    fetch_chunk_with_retry(&client, &url, &part, range, max_retries).await?;
+                let mut manifest = manifest.lock().await;
+                manifest.chunks[index] = Some(digest);
+                manifest.save(&manifest_file).await
+            })
+        })
+        .collect();
+
+    let results = join_all(futures).await;
+    for result in results {
+        result??;
+    }
+
+    let manifest = manifest.lock().await;
+    anyhow::ensure!(
+        manifest.chunks.iter().all(Option::is_some),
+        "[Download] Incomplete chunked download: {:?}",
+        dest
+    );
+    tokio::fs::rename(&part, dest).await?;
+    tokio::fs::remove_file(&manifest_file).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_chunk_range() {
+        let manifest = DownloadManifest::new(10, 4);
+        assert_eq!(manifest.chunks.len(), 3);
+        assert_eq!(manifest.chunk_range(0), (0, 4));
+        assert_eq!(manifest.chunk_range(2), (8, 10));
+    }
+
+    #[test]
+    fn test_resume_drops_corrupted_chunks() {
+        let dest = PathBuf::from("/tmp/file_agent_resume_test.txt");
+        let data = b"0123456789";
+        std::fs::write(part_path(&dest), data).unwrap();
+
+        let mut manifest = DownloadManifest::new(10, 4);
+        manifest.chunks[0] = Some(sha256_hex(&data[0..4]));
+        manifest.chunks[1] = Some(sha256_hex(b"xxxx"));
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let resumed = rt.block_on(async {
+            manifest.save(&manifest_path(&dest)).await.unwrap();
+            resume_manifest(&dest, 10, 4).await.unwrap()
+        });
+        assert!(resumed.chunks[0].is_some());
+        assert!(resumed.chunks[1].is_none());
+        assert!(resumed.chunks[2].is_none());
+
+        std::fs::remove_file(part_path(&dest)).unwrap();
+        std::fs::remove_file(manifest_path(&dest)).unwrap();
+    }
+}
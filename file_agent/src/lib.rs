@@ -19,4 +19,5 @@
 extern crate log;
 
 mod agent;
+mod download;
 pub use agent::{handle_file_request, ocall_handle_file_request};
@@ -0,0 +1,256 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use anyhow::{anyhow, ensure};
+use csv::{ReaderBuilder, StringRecord};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use teaclave_types::{FunctionArguments, FunctionRuntime, ReleaseVerdict};
+
+// The CSV output to verify, registered from a (fusion) output file.
+const IN_DATA: &str = ReleaseVerdict::VERIFIER_INPUT;
+
+#[derive(Default)]
+pub struct KAnonymityVerify;
+
+#[derive(serde::Deserialize)]
+pub struct KAnonymityVerifyArguments {
+    // Columns (start from 0) which together may identify an individual.
+    #[serde(default)]
+    quasi_identifiers: Vec<usize>,
+    // Every combination of the quasi-identifiers should be shared by at least
+    // k records. 0 disables the check.
+    #[serde(default)]
+    k: u64,
+    // Column holding the number of records aggregated into each row, and the
+    // minimal number each row should aggregate. 0 disables the check.
+    #[serde(default)]
+    count_column: Option<usize>,
+    #[serde(default)]
+    min_count: u64,
+    // Minimal number of rows of the whole output.
+    #[serde(default)]
+    min_records: u64,
+    // Only check evenly spaced samples of this size, with group sizes
+    // estimated from the sampling ratio. 0 checks all rows.
+    #[serde(default)]
+    sample_size: usize,
+    #[serde(default)]
+    has_headers: bool,
+}
+
+impl TryFrom<FunctionArguments> for KAnonymityVerifyArguments {
+    type Error = anyhow::Error;
+
+    fn try_from(arguments: FunctionArguments) -> Result<Self, Self::Error> {
+        use anyhow::Context;
+        serde_json::from_str(&arguments.into_string()).context("Cannot deserialize arguments")
+    }
+}
+
+impl KAnonymityVerify {
+    pub const NAME: &'static str = ReleaseVerdict::VERIFIER;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the verdict in JSON. The verdict is recorded on the output the
+    /// input is registered from once the task finishes.
+    pub fn run(
+        &self,
+        arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let args = KAnonymityVerifyArguments::try_from(arguments)?;
+        ensure!(
+            args.k == 0 || !args.quasi_identifiers.is_empty(),
+            "quasi_identifiers are required to check k-anonymity"
+        );
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(args.has_headers)
+            .flexible(true)
+            .from_reader(runtime.open_input(IN_DATA)?);
+        let records = rdr.records().collect::<Result<Vec<_>, _>>()?;
+        let verdict = verify(&args, &records)?;
+        Ok(serde_json::to_string(&verdict)?)
+    }
+}
+
+fn field<'a>(record: &'a StringRecord, index: usize) -> anyhow::Result<&'a str> {
+    record
+        .get(index)
+        .ok_or_else(|| anyhow!("invalid index {}", index))
+}
+
+fn verify(
+    args: &KAnonymityVerifyArguments,
+    records: &[StringRecord],
+) -> anyhow::Result<ReleaseVerdict> {
+    let total = records.len();
+    if (total as u64) < args.min_records {
+        return Ok(ReleaseVerdict::refused(format!(
+            "{} records, less than {}",
+            total, args.min_records
+        )));
+    }
+
+    let step = match args.sample_size {
+        0 => 1,
+        n => std::cmp::max(total / n, 1),
+    };
+    let samples: Vec<&StringRecord> = records.iter().step_by(step).collect();
+    let ratio = total as f64 / std::cmp::max(samples.len(), 1) as f64;
+
+    let mut violations = Vec::new();
+    if args.k > 0 {
+        let mut groups: HashMap<Vec<&str>, u64> = HashMap::new();
+        for record in &samples {
+            let key = args
+                .quasi_identifiers
+                .iter()
+                .map(|i| field(record, *i))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            *groups.entry(key).or_default() += 1;
+        }
+        // Only the size of the smallest group is reported, not its values.
+        if let Some(smallest) = groups.values().min() {
+            let estimated = (*smallest as f64 * ratio).floor() as u64;
+            if estimated < args.k {
+                violations.push(format!(
+                    "smallest group has {} records, less than k = {}",
+                    estimated, args.k
+                ));
+            }
+        }
+    }
+
+    if let (Some(column), true) = (args.count_column, args.min_count > 0) {
+        let mut below = 0;
+        for record in &samples {
+            let count: u64 = field(record, column)?.trim().parse()?;
+            if count < args.min_count {
+                below += 1;
+            }
+        }
+        if below > 0 {
+            violations.push(format!(
+                "{} rows aggregate less than {} records",
+                below, args.min_count
+            ));
+        }
+    }
+
+    let checked = if samples.len() < total {
+        format!("{} of {} records checked", samples.len(), total)
+    } else {
+        format!("{} records checked", total)
+    };
+    if violations.is_empty() {
+        Ok(ReleaseVerdict::releasable(checked))
+    } else {
+        Ok(ReleaseVerdict::refused(format!(
+            "{}; {}",
+            violations.join("; "),
+            checked
+        )))
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::Path;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            test_k_anonymity_verify,
+            test_k_anonymity_violated,
+            test_min_count_violated,
+            test_k_anonymity_sampling,
+        )
+    }
+
+    fn run_verifier(arguments: serde_json::Value) -> ReleaseVerdict {
+        let arguments = FunctionArguments::from_json(arguments).unwrap();
+        let input = Path::new("fixtures/functions/k_anonymity_verify/records.csv");
+        let input_files = StagedFiles::new(hashmap!(
+            IN_DATA =>
+            StagedFileInfo::new(input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+        ));
+        let output_files = StagedFiles::new(hashmap!());
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        let summary = KAnonymityVerify::new().run(arguments, runtime).unwrap();
+        serde_json::from_str(&summary).unwrap()
+    }
+
+    fn test_k_anonymity_verify() {
+        let verdict = run_verifier(json!({
+            "quasi_identifiers": [0],
+            "k": 2,
+            "count_column": 2,
+            "min_count": 5,
+            "min_records": 8,
+            "has_headers": true,
+        }));
+        assert!(verdict.releasable);
+        assert_eq!(verdict.reason, "8 records checked");
+    }
+
+    fn test_k_anonymity_violated() {
+        let verdict = run_verifier(json!({
+            "quasi_identifiers": [0, 1],
+            "k": 2,
+            "has_headers": true,
+        }));
+        assert!(!verdict.releasable);
+        assert!(verdict.reason.contains("smallest group has 1 records"));
+
+        let verdict = run_verifier(json!({
+            "min_records": 10,
+            "has_headers": true,
+        }));
+        assert!(!verdict.releasable);
+    }
+
+    fn test_min_count_violated() {
+        let verdict = run_verifier(json!({
+            "count_column": 2,
+            "min_count": 10,
+            "has_headers": true,
+        }));
+        assert!(!verdict.releasable);
+        assert!(verdict.reason.contains("4 rows"));
+    }
+
+    fn test_k_anonymity_sampling() {
+        let verdict = run_verifier(json!({
+            "quasi_identifiers": [0],
+            "k": 2,
+            "sample_size": 4,
+            "has_headers": true,
+        }));
+        assert!(verdict.releasable);
+        assert_eq!(verdict.reason, "4 of 8 records checked");
+    }
+}
@@ -21,29 +21,39 @@ mod echo;
 mod face_detection;
 mod gbdt_predict;
 mod gbdt_train;
+mod k_anonymity_verify;
 mod logistic_regression_predict;
 mod logistic_regression_train;
 mod online_decrypt;
 mod ordered_set_intersect;
 mod ordered_set_join;
 mod password_check;
+mod pii_redact;
 mod principal_components_analysis;
 mod private_join_and_compute;
+mod private_set_intersection;
 mod rsa_sign;
+mod secure_aggregation;
+mod sql_query;
 
 pub use echo::Echo;
 pub use face_detection::FaceDetection;
 pub use gbdt_predict::GbdtPredict;
 pub use gbdt_train::GbdtTrain;
+pub use k_anonymity_verify::KAnonymityVerify;
 pub use logistic_regression_predict::LogisticRegressionPredict;
 pub use logistic_regression_train::LogisticRegressionTrain;
 pub use online_decrypt::OnlineDecrypt;
 pub use ordered_set_intersect::OrderedSetIntersect;
 pub use ordered_set_join::OrderedSetJoin;
 pub use password_check::PasswordCheck;
+pub use pii_redact::PiiRedact;
 pub use principal_components_analysis::PrincipalComponentsAnalysis;
 pub use private_join_and_compute::PrivateJoinAndCompute;
+pub use private_set_intersection::PrivateSetIntersection;
 pub use rsa_sign::RsaSign;
+pub use secure_aggregation::SecureAggregation;
+pub use sql_query::SqlQuery;
 
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
@@ -56,15 +66,20 @@ pub mod tests {
             face_detection::tests::run_tests(),
             gbdt_predict::tests::run_tests(),
             gbdt_train::tests::run_tests(),
+            k_anonymity_verify::tests::run_tests(),
             logistic_regression_predict::tests::run_tests(),
             logistic_regression_train::tests::run_tests(),
             password_check::tests::run_tests(),
+            pii_redact::tests::run_tests(),
             online_decrypt::tests::run_tests(),
             ordered_set_join::tests::run_tests(),
             ordered_set_intersect::tests::run_tests(),
             principal_components_analysis::tests::run_tests(),
             private_join_and_compute::tests::run_tests(),
+            private_set_intersection::tests::run_tests(),
             rsa_sign::tests::run_tests(),
+            secure_aggregation::tests::run_tests(),
+            sql_query::tests::run_tests(),
         )
     }
 }
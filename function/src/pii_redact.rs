@@ -0,0 +1,521 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use anyhow::ensure;
+use csv::{ReaderBuilder, StringRecord, Writer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Read, Write};
+use teaclave_types::{FunctionArguments, FunctionRuntime};
+
+const IN_DATA: &str = "input_data";
+// The input with the PII replaced by masks
+const OUT_DATA: &str = "output_data";
+// Counts of the redactions in JSON, without any of the redacted values
+const OUT_REPORT: &str = "output_report";
+
+const TERM: &str = "term";
+
+#[derive(Default)]
+pub struct PiiRedact;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PiiKind {
+    Email,
+    Phone,
+    Id,
+}
+
+impl PiiKind {
+    fn name(self) -> &'static str {
+        match self {
+            PiiKind::Email => "email",
+            PiiKind::Phone => "phone",
+            PiiKind::Id => "id",
+        }
+    }
+
+    fn mask(self) -> &'static str {
+        match self {
+            PiiKind::Email => "[EMAIL]",
+            PiiKind::Phone => "[PHONE]",
+            PiiKind::Id => "[ID]",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum InputFormat {
+    Csv,
+    Text,
+}
+
+fn default_patterns() -> Vec<PiiKind> {
+    vec![PiiKind::Email, PiiKind::Phone, PiiKind::Id]
+}
+
+fn default_format() -> InputFormat {
+    InputFormat::Csv
+}
+
+fn default_min_id_digits() -> usize {
+    6
+}
+
+#[derive(Deserialize)]
+struct PiiRedactArguments {
+    // Kinds of PII to redact, where earlier ones take precedence when a value
+    // matches several kinds.
+    #[serde(default = "default_patterns")]
+    patterns: Vec<PiiKind>,
+    // Words redacted wherever they appear, e.g., names, case-insensitively.
+    #[serde(default)]
+    terms: Vec<String>,
+    // Mask replacing all the redacted values instead of the ones of the kinds.
+    #[serde(default)]
+    mask: Option<String>,
+    // Words with at least this many digits are taken as IDs.
+    #[serde(default = "default_min_id_digits")]
+    min_id_digits: usize,
+    #[serde(default = "default_format")]
+    format: InputFormat,
+    // Columns (start from 0) of the CSV input to scan. Empty scans all.
+    #[serde(default)]
+    columns: Vec<usize>,
+    // Headers of the CSV input are kept as they are.
+    #[serde(default)]
+    has_headers: bool,
+}
+
+impl TryFrom<FunctionArguments> for PiiRedactArguments {
+    type Error = anyhow::Error;
+
+    fn try_from(arguments: FunctionArguments) -> Result<Self, Self::Error> {
+        use anyhow::Context;
+        serde_json::from_str(&arguments.into_string()).context("Cannot deserialize arguments")
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct RedactionReport {
+    records: u64,
+    redacted_records: u64,
+    // Redactions by kind, with "term" for the configured terms
+    redactions: BTreeMap<String, u64>,
+    // Redactions by CSV column
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    columns: BTreeMap<usize, u64>,
+}
+
+impl RedactionReport {
+    fn total(&self) -> u64 {
+        self.redactions.values().sum()
+    }
+}
+
+impl PiiRedact {
+    pub const NAME: &'static str = "builtin-pii-redact";
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Writes the redacted input and the report to the outputs, and returns
+    /// the number of redactions.
+    pub fn run(
+        &self,
+        arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let args = PiiRedactArguments::try_from(arguments)?;
+        ensure!(
+            !args.patterns.is_empty() || !args.terms.is_empty(),
+            "patterns or terms are required to redact"
+        );
+        ensure!(args.min_id_digits > 0, "min_id_digits should be positive");
+        let redactor = Redactor::new(&args);
+        let input = runtime.open_input(IN_DATA)?;
+        let output = runtime.create_output(OUT_DATA)?;
+        let report = match args.format {
+            InputFormat::Csv => redact_csv(&redactor, &args, input, output)?,
+            InputFormat::Text => redact_text(&redactor, input, output)?,
+        };
+        serde_json::to_writer(runtime.create_output(OUT_REPORT)?, &report)?;
+
+        Ok(format!(
+            "{} redactions in {} of {} records",
+            report.total(),
+            report.redacted_records,
+            report.records
+        ))
+    }
+}
+
+fn redact_csv(
+    redactor: &Redactor,
+    args: &PiiRedactArguments,
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+) -> anyhow::Result<RedactionReport> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(args.has_headers)
+        .flexible(true)
+        .from_reader(input);
+    let mut wtr = Writer::from_writer(output);
+    if args.has_headers {
+        wtr.write_record(rdr.headers()?)?;
+    }
+
+    let mut report = RedactionReport::default();
+    for record in rdr.records() {
+        let record = record?;
+        let mut redacted = StringRecord::new();
+        let mut count = 0;
+        for (index, field) in record.iter().enumerate() {
+            if !args.columns.is_empty() && !args.columns.contains(&index) {
+                redacted.push_field(field);
+                continue;
+            }
+            let (field, n) = redactor.redact(field, &mut report.redactions);
+            if n > 0 {
+                *report.columns.entry(index).or_default() += n;
+            }
+            count += n;
+            redacted.push_field(&field);
+        }
+        report.records += 1;
+        if count > 0 {
+            report.redacted_records += 1;
+        }
+        wtr.write_record(&redacted)?;
+    }
+    wtr.flush()?;
+    Ok(report)
+}
+
+fn redact_text(
+    redactor: &Redactor,
+    input: Box<dyn Read>,
+    mut output: Box<dyn Write>,
+) -> anyhow::Result<RedactionReport> {
+    let mut report = RedactionReport::default();
+    for line in BufReader::new(input).lines() {
+        let (line, n) = redactor.redact(&line?, &mut report.redactions);
+        report.records += 1;
+        if n > 0 {
+            report.redacted_records += 1;
+        }
+        writeln!(output, "{}", line)?;
+    }
+    output.flush()?;
+    Ok(report)
+}
+
+struct Redactor<'a> {
+    patterns: &'a [PiiKind],
+    terms: Vec<Vec<char>>,
+    mask: Option<&'a str>,
+    min_id_digits: usize,
+}
+
+impl<'a> Redactor<'a> {
+    fn new(args: &'a PiiRedactArguments) -> Self {
+        Redactor {
+            patterns: &args.patterns,
+            terms: args
+                .terms
+                .iter()
+                .filter(|t| !t.is_empty())
+                .map(|t| t.to_lowercase().chars().collect())
+                .collect(),
+            mask: args.mask.as_deref(),
+            min_id_digits: args.min_id_digits,
+        }
+    }
+
+    /// Replaces the PII in `text` with masks, counting the redactions by kind
+    /// in `counts`. Returns the redacted text and the number of redactions.
+    fn redact(&self, text: &str, counts: &mut BTreeMap<String, u64>) -> (String, u64) {
+        let chars: Vec<char> = text.chars().collect();
+        let mut redacted = String::with_capacity(text.len());
+        let mut count = 0;
+        let mut i = 0;
+        while i < chars.len() {
+            match self.match_at(&chars, i) {
+                Some((kind, mask, end)) => {
+                    redacted.push_str(self.mask.unwrap_or(mask));
+                    *counts.entry(kind.to_string()).or_default() += 1;
+                    count += 1;
+                    i = end;
+                }
+                None => {
+                    redacted.push(chars[i]);
+                    i += 1;
+                }
+            }
+        }
+        (redacted, count)
+    }
+
+    /// The kind, the mask and the end of the PII starting at `start`, if any.
+    /// Terms are matched after the patterns, so that e.g. a name in an email
+    /// address does not break the address.
+    fn match_at(
+        &self,
+        chars: &[char],
+        start: usize,
+    ) -> Option<(&'static str, &'static str, usize)> {
+        let pattern = self.patterns.iter().find_map(|kind| {
+            let end = match kind {
+                PiiKind::Email => match_email(chars, start),
+                PiiKind::Phone => match_phone(chars, start),
+                PiiKind::Id => match_id(chars, start, self.min_id_digits),
+            }?;
+            Some((kind.name(), kind.mask(), end))
+        });
+        pattern.or_else(|| {
+            let end = self
+                .terms
+                .iter()
+                .find_map(|t| match_term(chars, start, t))?;
+            Some((TERM, "[REDACTED]", end))
+        })
+    }
+}
+
+// Values only start after a character which cannot be part of a word.
+fn at_boundary(chars: &[char], start: usize, word: fn(char) -> bool) -> bool {
+    start == 0 || !word(chars[start - 1])
+}
+
+fn is_local(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._%+-".contains(c)
+}
+
+fn is_domain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '-'
+}
+
+fn is_phone(c: char) -> bool {
+    c.is_ascii_digit() || " -.()".contains(c)
+}
+
+fn is_id(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-'
+}
+
+fn match_term(chars: &[char], start: usize, term: &[char]) -> Option<usize> {
+    let end = start + term.len();
+    if end > chars.len() || !at_boundary(chars, start, char::is_alphanumeric) {
+        return None;
+    }
+    let matched = chars[start..end]
+        .iter()
+        .zip(term)
+        .all(|(c, t)| c.to_lowercase().eq(std::iter::once(*t)));
+    let bounded = end == chars.len() || !chars[end].is_alphanumeric();
+    (matched && bounded).then_some(end)
+}
+
+// local-part@domain, where the domain ends with a label of at least two
+// letters.
+fn match_email(chars: &[char], start: usize) -> Option<usize> {
+    if !at_boundary(chars, start, is_local) {
+        return None;
+    }
+    let at = start + chars[start..].iter().take_while(|c| is_local(**c)).count();
+    if at == start || chars.get(at) != Some(&'@') {
+        return None;
+    }
+    let mut end = at
+        + 1
+        + chars[at + 1..]
+            .iter()
+            .take_while(|c| is_domain(**c))
+            .count();
+    while end > at + 1 && chars[end - 1] == '.' {
+        end -= 1;
+    }
+    let domain: String = chars[at + 1..end].iter().collect();
+    let tld = domain.rsplit('.').next()?;
+    let valid = !domain.starts_with('.')
+        && domain.contains('.')
+        && tld.len() >= 2
+        && tld.chars().all(|c| c.is_ascii_alphabetic());
+    valid.then_some(end)
+}
+
+// 7 to 15 digits grouped by spaces, dashes, dots or parentheses, optionally
+// after a '+'. Decimal numbers, i.e., digits with a single dot, are not taken
+// as phone numbers.
+fn match_phone(chars: &[char], start: usize) -> Option<usize> {
+    if !at_boundary(chars, start, char::is_alphanumeric) {
+        return None;
+    }
+    let first = chars[start];
+    if !(first == '+' || first == '(' || first.is_ascii_digit()) {
+        return None;
+    }
+    let body = if first == '+' { start + 1 } else { start };
+    let scanned = body + chars[body..].iter().take_while(|c| is_phone(**c)).count();
+    let end = start
+        + chars[start..scanned]
+            .iter()
+            .rposition(|c| c.is_ascii_digit())?
+        + 1;
+    if end < chars.len() && chars[end].is_alphanumeric() {
+        return None;
+    }
+    let value = &chars[start..end];
+    let digits = value.iter().filter(|c| c.is_ascii_digit()).count();
+    let separators: Vec<&char> = value.iter().filter(|c| !c.is_ascii_digit()).collect();
+    let decimal = separators == [&'.'];
+    ((7..=15).contains(&digits) && !decimal).then_some(end)
+}
+
+// Words of letters, digits and dashes with at least `min_digits` digits, e.g.,
+// passport or social security numbers.
+fn match_id(chars: &[char], start: usize, min_digits: usize) -> Option<usize> {
+    if !at_boundary(chars, start, is_id) || !chars[start].is_ascii_alphanumeric() {
+        return None;
+    }
+    let mut end = start + chars[start..].iter().take_while(|c| is_id(**c)).count();
+    while chars[end - 1] == '-' {
+        end -= 1;
+    }
+    let digits = chars[start..end]
+        .iter()
+        .filter(|c| c.is_ascii_digit())
+        .count();
+    (digits >= min_digits).then_some(end)
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::Path;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_pii_redact, test_pii_redact_text, test_pii_patterns)
+    }
+
+    fn run_redact(input: &str, arguments: serde_json::Value) -> (String, String, RedactionReport) {
+        let arguments = FunctionArguments::from_json(arguments).unwrap();
+        let base = Path::new("fixtures/functions/pii_redact");
+        let input = base.join(input);
+        let output = base.join("output_redacted");
+        let report = base.join("output_report.json");
+
+        let input_files = StagedFiles::new(hashmap!(
+            IN_DATA =>
+            StagedFileInfo::new(&input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+        ));
+        let output_files = StagedFiles::new(hashmap!(
+            OUT_DATA =>
+            StagedFileInfo::new(&output, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+            OUT_REPORT =>
+            StagedFileInfo::new(&report, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+        ));
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        let summary = PiiRedact::new().run(arguments, runtime).unwrap();
+        let redacted = fs::read_to_string(&output).unwrap();
+        let report = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+        (summary, redacted, report)
+    }
+
+    fn test_pii_redact() {
+        let (summary, redacted, report) = run_redact(
+            "records.csv",
+            json!({
+                "terms": ["carol"],
+                "has_headers": true,
+            }),
+        );
+        assert_eq!(summary, "8 redactions in 3 of 3 records");
+        let expected = fs::read_to_string("fixtures/functions/pii_redact/redacted.csv").unwrap();
+        assert_eq!(redacted, expected);
+        assert_eq!(
+            report.redactions,
+            btreemap(&[("email", 3), ("id", 1), ("phone", 3), ("term", 1)])
+        );
+        assert_eq!(
+            report.columns,
+            vec![(0, 1), (1, 2), (2, 2), (3, 3)].into_iter().collect()
+        );
+
+        let (summary, redacted, _) = run_redact(
+            "records.csv",
+            json!({
+                "columns": [1],
+                "has_headers": true,
+            }),
+        );
+        assert_eq!(summary, "2 redactions in 2 of 3 records");
+        assert!(redacted.contains("reach me at carol@example.net"));
+    }
+
+    fn test_pii_redact_text() {
+        let (summary, redacted, report) = run_redact(
+            "notes.txt",
+            json!({
+                "patterns": ["email"],
+                "mask": "***",
+                "format": "text",
+            }),
+        );
+        assert_eq!(summary, "1 redactions in 1 of 2 records");
+        assert_eq!(
+            redacted,
+            "Contact *** for access.\n\
+             Her passport number is X1234567, phone +44 20 7946 0958.\n"
+        );
+        assert!(report.columns.is_empty());
+    }
+
+    fn test_pii_patterns() {
+        let args: PiiRedactArguments = serde_json::from_value(json!({})).unwrap();
+        let redactor = Redactor::new(&args);
+        let redact = |text: &str| redactor.redact(text, &mut BTreeMap::new());
+
+        assert_eq!(redact("a.b-c@x.co."), ("[EMAIL].".to_string(), 1));
+        assert_eq!(redact("user@localhost").1, 0);
+        assert_eq!(redact("(555) 123 4567"), ("[PHONE]".to_string(), 1));
+        assert_eq!(redact("123-45-6789"), ("[PHONE]".to_string(), 1));
+        assert_eq!(redact("12345.67").1, 0);
+        assert_eq!(redact("12345"), ("12345".to_string(), 0));
+        assert_eq!(redact("AB123456C"), ("[ID]".to_string(), 1));
+        assert_eq!(redact("4111111111111111"), ("[ID]".to_string(), 1));
+        assert!(serde_json::from_value::<PiiRedactArguments>(json!({
+            "patterns": ["address"],
+        }))
+        .is_err());
+    }
+
+    fn btreemap(counts: &[(&str, u64)]) -> BTreeMap<String, u64> {
+        counts.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+}
@@ -0,0 +1,276 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use anyhow::{bail, ensure};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Write};
+use teaclave_types::{FunctionArguments, FunctionRuntime};
+
+// Inputs input_data0, input_data1, ..., one element per line
+const IN_DATA: &str = "input_data";
+// Outputs output_data0, output_data1, ..., the intersection for each party
+const OUT_RESULT: &str = "output_data";
+const MAX_PARTIES: usize = 256;
+const MAX_PARTITIONS: usize = 4096;
+
+#[derive(Default)]
+pub struct PrivateSetIntersection;
+
+#[derive(serde::Deserialize)]
+struct PrivateSetIntersectionArguments {
+    num_parties: usize,
+    #[serde(default = "default_hash")]
+    hash: String,
+    // Passes over the inputs, each holding the digests of one partition only
+    #[serde(default = "default_partitions")]
+    num_partitions: usize,
+}
+
+fn default_hash() -> String {
+    "sha256".to_string()
+}
+
+fn default_partitions() -> usize {
+    1
+}
+
+impl TryFrom<FunctionArguments> for PrivateSetIntersectionArguments {
+    type Error = anyhow::Error;
+
+    fn try_from(arguments: FunctionArguments) -> Result<Self, Self::Error> {
+        use anyhow::Context;
+        serde_json::from_str(&arguments.into_string()).context("Cannot deserialize arguments")
+    }
+}
+
+fn digest_algorithm(name: &str) -> anyhow::Result<&'static ring::digest::Algorithm> {
+    let algorithm = match name {
+        "sha256" => &ring::digest::SHA256,
+        "sha384" => &ring::digest::SHA384,
+        "sha512" => &ring::digest::SHA512,
+        "sha512_256" => &ring::digest::SHA512_256,
+        _ => bail!("Unsupported hash algorithm: {}", name),
+    };
+    Ok(algorithm)
+}
+
+impl PrivateSetIntersection {
+    pub const NAME: &'static str = "builtin-private-set-intersection";
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Intersect the sets of the parties, writing the common elements
+    /// partition by partition, in the order of the first input within each.
+    /// The inputs are read line by line in every pass, and only the digests
+    /// of the elements falling in the current partition are kept, so that
+    /// the memory taken is about the size of the first set over
+    /// `num_partitions`.
+    pub fn run(
+        &self,
+        arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let args = PrivateSetIntersectionArguments::try_from(arguments)?;
+        let num_parties = args.num_parties;
+        ensure!(
+            (2..=MAX_PARTIES).contains(&num_parties),
+            "num_parties should be between 2 and {}",
+            MAX_PARTIES
+        );
+        ensure!(
+            (1..=MAX_PARTITIONS).contains(&args.num_partitions),
+            "num_partitions should be between 1 and {}",
+            MAX_PARTITIONS
+        );
+        let hasher = Hasher {
+            algorithm: digest_algorithm(&args.hash)?,
+            num_partitions: args.num_partitions,
+        };
+
+        let mut outputs = Vec::with_capacity(num_parties);
+        for i in 0..num_parties {
+            outputs.push(runtime.create_output(&format!("{}{}", OUT_RESULT, i))?);
+        }
+
+        let mut common_items = 0;
+        for partition in 0..args.num_partitions {
+            // Digests of the partition by the last party having the element
+            let mut digests: HashMap<Box<[u8]>, usize> = HashMap::new();
+            for_each_element(&runtime, 0, |element| {
+                if let Some(digest) = hasher.digest(element, partition) {
+                    digests.insert(digest, 0);
+                }
+                Ok(())
+            })?;
+            for party in 1..num_parties {
+                for_each_element(&runtime, party, |element| {
+                    if let Some(digest) = hasher.digest(element, partition) {
+                        if let Some(last) = digests.get_mut(&digest) {
+                            if *last == party - 1 {
+                                *last = party;
+                            }
+                        }
+                    }
+                    Ok(())
+                })?;
+                digests.retain(|_, last| *last == party);
+                if digests.is_empty() {
+                    break;
+                }
+            }
+            if digests.is_empty() {
+                continue;
+            }
+
+            for_each_element(&runtime, 0, |element| {
+                let digest = match hasher.digest(element, partition) {
+                    Some(digest) => digest,
+                    None => return Ok(()),
+                };
+                // Duplicates in the first input are written once.
+                if digests.remove(&digest).is_some() {
+                    for output in outputs.iter_mut() {
+                        writeln!(output, "{}", element)?;
+                    }
+                    common_items += 1;
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(format!(
+            "{} common items among {} parties",
+            common_items, num_parties
+        ))
+    }
+}
+
+struct Hasher {
+    algorithm: &'static ring::digest::Algorithm,
+    num_partitions: usize,
+}
+
+impl Hasher {
+    // The digest of the element, if the element falls in the partition
+    fn digest(&self, element: &str, partition: usize) -> Option<Box<[u8]>> {
+        let digest = ring::digest::digest(self.algorithm, element.as_bytes());
+        let bytes = digest.as_ref();
+        let bucket = u16::from_be_bytes([bytes[0], bytes[1]]) as usize % self.num_partitions;
+        if bucket == partition {
+            Some(bytes.into())
+        } else {
+            None
+        }
+    }
+}
+
+// Stream the elements of the input of the party, skipping empty lines.
+fn for_each_element(
+    runtime: &FunctionRuntime,
+    party: usize,
+    mut f: impl FnMut(&str) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let input = runtime.open_input(&format!("{}{}", IN_DATA, party))?;
+    let mut reader = BufReader::new(input);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let element = line.trim();
+        if !element.is_empty() {
+            f(element)?;
+        }
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    const FIXTURES: &str = "fixtures/functions/private_set_intersection";
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            test_private_set_intersection,
+            test_private_set_intersection_partitions,
+            test_private_set_intersection_invalid,
+        )
+    }
+
+    fn intersect(arguments: serde_json::Value, num_inputs: usize) -> anyhow::Result<Vec<String>> {
+        let arguments = FunctionArguments::from_json(arguments).unwrap();
+        let input_files: StagedFiles = (0..num_inputs)
+            .map(|i| {
+                let path = format!("{}/input{}.txt", FIXTURES, i);
+                let info =
+                    StagedFileInfo::new(path, TeaclaveFile128Key::random(), FileAuthTag::mock());
+                (format!("{}{}", IN_DATA, i), info)
+            })
+            .collect();
+        let output_files: StagedFiles = (0..num_inputs)
+            .map(|i| {
+                let path = format!("{}/output{}.txt", FIXTURES, i);
+                let info =
+                    StagedFileInfo::new(path, TeaclaveFile128Key::random(), FileAuthTag::mock());
+                (format!("{}{}", OUT_RESULT, i), info)
+            })
+            .collect();
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        PrivateSetIntersection::new().run(arguments, runtime)?;
+        let outputs = (0..num_inputs)
+            .map(|i| fs::read_to_string(format!("{}/output{}.txt", FIXTURES, i)).unwrap())
+            .collect();
+        Ok(outputs)
+    }
+
+    fn test_private_set_intersection() {
+        let outputs = intersect(json!({"num_parties": 3}), 3).unwrap();
+        for output in outputs {
+            assert_eq!(output, "bob\ndave\n");
+        }
+
+        let outputs = intersect(json!({"num_parties": 2, "hash": "sha512"}), 2).unwrap();
+        assert_eq!(outputs[1], "bob\ncarol\ndave\n");
+    }
+
+    fn test_private_set_intersection_partitions() {
+        let arguments = json!({"num_parties": 3, "hash": "sha384", "num_partitions": 7});
+        let outputs = intersect(arguments, 3).unwrap();
+        let mut items: Vec<&str> = outputs[0].lines().collect();
+        items.sort_unstable();
+        assert_eq!(items, ["bob", "dave"]);
+    }
+
+    fn test_private_set_intersection_invalid() {
+        assert!(intersect(json!({"num_parties": 1}), 1).is_err());
+        assert!(intersect(json!({"num_parties": 2, "hash": "md5"}), 2).is_err());
+        let arguments = json!({"num_parties": 2, "num_partitions": 0});
+        assert!(intersect(arguments, 2).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use anyhow::{anyhow, ensure};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use teaclave_types::{FunctionArguments, FunctionRuntime};
+
+// Inputs gradient0, gradient1, ..., one per participant
+const IN_GRADIENT: &str = "gradient";
+// A fusion output owned by all the participants
+const OUT_AGGREGATED: &str = "aggregated";
+// Gradients are summed in fixed point with this many fractional bits, so that
+// the masks cancel out exactly.
+const FRACTION_BITS: i32 = 24;
+// Bounds keeping the sum of the fixed-point values within an i64
+const MAX_ABS_VALUE: f64 = 1e6;
+const MAX_PARTICIPANTS: usize = 1024;
+
+#[derive(Default)]
+pub struct SecureAggregation;
+
+#[derive(serde::Deserialize)]
+struct SecureAggregationArguments {
+    num_participants: usize,
+    // Divide the sum by the number of participants, as in federated averaging
+    #[serde(default)]
+    average: bool,
+}
+
+impl TryFrom<FunctionArguments> for SecureAggregationArguments {
+    type Error = anyhow::Error;
+
+    fn try_from(arguments: FunctionArguments) -> Result<Self, Self::Error> {
+        use anyhow::Context;
+        serde_json::from_str(&arguments.into_string()).context("Cannot deserialize arguments")
+    }
+}
+
+impl SecureAggregation {
+    pub const NAME: &'static str = "builtin-secure-aggregation";
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sum the gradients of the participants. Every gradient is masked with
+    /// random values drawn in the enclave before it is added, and the masks
+    /// of all the participants sum to zero, so the running sum reveals no
+    /// partial sum of the gradients until the last one is added.
+    pub fn run(
+        &self,
+        arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let args = SecureAggregationArguments::try_from(arguments)?;
+        let num_participants = args.num_participants;
+        ensure!(
+            (2..=MAX_PARTICIPANTS).contains(&num_participants),
+            "num_participants should be between 2 and {}",
+            MAX_PARTICIPANTS
+        );
+
+        let mut sum: Vec<u64> = Vec::new();
+        let mut mask_sum: Vec<u64> = Vec::new();
+        for i in 0..num_participants {
+            let gradient = read_gradient(i, &runtime)?;
+            if i == 0 {
+                sum = vec![0; gradient.len()];
+                mask_sum = vec![0; gradient.len()];
+            }
+            ensure!(
+                gradient.len() == sum.len(),
+                "{}{} has {} values, expecting {}",
+                IN_GRADIENT,
+                i,
+                gradient.len(),
+                sum.len()
+            );
+            let mask = if i + 1 < num_participants {
+                random_mask(sum.len(), &runtime)?
+            } else {
+                mask_sum.iter().map(|m| m.wrapping_neg()).collect()
+            };
+            for (j, value) in gradient.into_iter().enumerate() {
+                sum[j] = sum[j].wrapping_add(value.wrapping_add(mask[j]));
+                mask_sum[j] = mask_sum[j].wrapping_add(mask[j]);
+            }
+        }
+
+        let scale = (2f64).powi(FRACTION_BITS);
+        let mut output = runtime.create_output(OUT_AGGREGATED)?;
+        for value in &sum {
+            let mut value = *value as i64 as f64 / scale;
+            if args.average {
+                value /= num_participants as f64;
+            }
+            writeln!(output, "{}", value)?;
+        }
+
+        Ok(format!(
+            "Aggregated {} values from {} participants",
+            sum.len(),
+            num_participants
+        ))
+    }
+}
+
+// The gradient in fixed point. The runtime refuses to open an input whose
+// cmac differs from the one registered, which the participants may have
+// pinned when approving the task.
+fn read_gradient(index: usize, runtime: &FunctionRuntime) -> anyhow::Result<Vec<u64>> {
+    let name = format!("{}{}", IN_GRADIENT, index);
+    let mut data = String::new();
+    runtime.open_input(&name)?.read_to_string(&mut data)?;
+
+    let scale = (2f64).powi(FRACTION_BITS);
+    // Values are not quoted in the errors, which are seen by all the
+    // participants.
+    let gradient = data
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .enumerate()
+        .map(|(j, s)| {
+            let value: f64 = s
+                .parse()
+                .map_err(|_| anyhow!("{} has an invalid value at {}", name, j))?;
+            ensure!(
+                value.is_finite() && value.abs() <= MAX_ABS_VALUE,
+                "{} has a value out of range at {}",
+                name,
+                j
+            );
+            Ok((value * scale).round() as i64 as u64)
+        })
+        .collect::<anyhow::Result<Vec<u64>>>()?;
+    ensure!(!gradient.is_empty(), "{} is empty", name);
+    Ok(gradient)
+}
+
+fn random_mask(len: usize, runtime: &FunctionRuntime) -> anyhow::Result<Vec<u64>> {
+    let mut bytes = vec![0u8; len * 8];
+    runtime.random_bytes(&mut bytes)?;
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|chunk| {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(chunk);
+            u64::from_le_bytes(word)
+        })
+        .collect())
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    const FIXTURES: &str = "fixtures/functions/secure_aggregation";
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            test_secure_aggregation,
+            test_secure_aggregation_average,
+            test_secure_aggregation_mismatched,
+        )
+    }
+
+    fn aggregate(arguments: serde_json::Value, inputs: &[&str]) -> anyhow::Result<String> {
+        let arguments = FunctionArguments::from_json(arguments).unwrap();
+        let input_files: StagedFiles = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let path = format!("{}/{}", FIXTURES, input);
+                let info =
+                    StagedFileInfo::new(path, TeaclaveFile128Key::random(), FileAuthTag::mock());
+                (format!("{}{}", IN_GRADIENT, i), info)
+            })
+            .collect();
+        let output = format!("{}/aggregated.txt", FIXTURES);
+        let output_files = StagedFiles::new(hashmap!(
+            OUT_AGGREGATED =>
+            StagedFileInfo::new(&output, TeaclaveFile128Key::random(), FileAuthTag::mock())
+        ));
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        SecureAggregation::new().run(arguments, runtime)?;
+        Ok(fs::read_to_string(output).unwrap())
+    }
+
+    fn test_secure_aggregation() {
+        let inputs = ["gradient0.txt", "gradient1.txt", "gradient2.txt"];
+        let aggregated = aggregate(json!({"num_participants": 3}), &inputs).unwrap();
+        assert_eq!(aggregated, "1.75\n1.375\n2.5\n-0.75\n");
+    }
+
+    fn test_secure_aggregation_average() {
+        let inputs = ["gradient0.txt", "gradient1.txt"];
+        let arguments = json!({"num_participants": 2, "average": true});
+        let aggregated = aggregate(arguments, &inputs).unwrap();
+        assert_eq!(aggregated, "0.375\n0.625\n1\n-0.5\n");
+    }
+
+    fn test_secure_aggregation_mismatched() {
+        let inputs = ["gradient0.txt", "gradient_short.txt"];
+        let error = aggregate(json!({"num_participants": 2}), &inputs).unwrap_err();
+        assert!(error.to_string().contains("expecting 4"));
+
+        let inputs = ["gradient0.txt"];
+        assert!(aggregate(json!({"num_participants": 1}), &inputs).is_err());
+    }
+}
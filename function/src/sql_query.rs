@@ -0,0 +1,896 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A restricted SQL statement over a CSV input with headers:
+//!
+//! ```text
+//! SELECT item, ... FROM input [WHERE condition] [GROUP BY column, ...] [LIMIT n]
+//! ```
+//!
+//! The items are `*`, columns, or the aggregates `COUNT(*)`, `COUNT`, `SUM`,
+//! `AVG`, `MIN` and `MAX` of a column, each optionally renamed with `AS`. The
+//! condition compares columns and literals with `=`, `!=`, `<>`, `<`, `<=`,
+//! `>` and `>=`, tests `IS [NOT] NULL`, and combines them with `AND`, `OR`,
+//! `NOT` and parentheses. Empty fields are NULL, which the aggregates skip
+//! and comparisons are unknown with, like in SQL.
+
+use anyhow::{anyhow, bail, ensure, Context};
+use csv::{ReaderBuilder, StringRecord, Writer};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use teaclave_types::{FunctionArguments, FunctionRuntime};
+
+const OUT_RESULT: &str = "output_data";
+// Groups kept in memory at a time
+const MAX_GROUPS: usize = 1 << 20;
+
+#[derive(Default)]
+pub struct SqlQuery;
+
+#[derive(serde::Deserialize)]
+struct SqlQueryArguments {
+    query: String,
+}
+
+impl TryFrom<FunctionArguments> for SqlQueryArguments {
+    type Error = anyhow::Error;
+
+    fn try_from(arguments: FunctionArguments) -> Result<Self, Self::Error> {
+        serde_json::from_str(&arguments.into_string()).context("Cannot deserialize arguments")
+    }
+}
+
+impl SqlQuery {
+    pub const NAME: &'static str = "builtin-sql-query";
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Run the query over the input named in its `FROM` clause, writing the
+    /// resulting rows with a header row to `output_data`. The input is
+    /// streamed, and only the groups are kept in memory for aggregates.
+    pub fn run(
+        &self,
+        arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let args = SqlQueryArguments::try_from(arguments)?;
+        let mut query = Parser::new(tokenize(&args.query)?).query()?;
+
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true)
+            .from_reader(runtime.open_input(&query.table)?);
+        let headers = rdr.headers()?.clone();
+        query.bind(&headers)?;
+
+        let mut wtr = Writer::from_writer(runtime.create_output(OUT_RESULT)?);
+        wtr.write_record(query.column_names(&headers))?;
+        let limit = query.limit.unwrap_or(usize::MAX);
+        let mut rows = 0;
+        if query.is_aggregate() {
+            let mut groups = Groups::default();
+            for record in rdr.records() {
+                let record = record?;
+                if query.filter(&record) {
+                    groups.update(&query, &record)?;
+                }
+            }
+            for row in groups.finish(&query).into_iter().take(limit) {
+                wtr.write_record(&row)?;
+                rows += 1;
+            }
+        } else {
+            for record in rdr.records() {
+                if rows == limit {
+                    break;
+                }
+                let record = record?;
+                if query.filter(&record) {
+                    wtr.write_record(query.project(&record))?;
+                    rows += 1;
+                }
+            }
+        }
+        wtr.flush()?;
+
+        Ok(format!("{} rows", rows))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    QuotedIdent(String),
+    Number(String),
+    Text(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "<>", "!=", "=", "<", ">", ",", "(", ")", "*", "-", ";",
+];
+
+const KEYWORDS: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "GROUP", "BY", "LIMIT", "AS", "AND", "OR", "NOT", "IS", "NULL",
+];
+
+fn tokenize(query: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut word = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        } else if c.is_ascii_digit() {
+            let mut number = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                number.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Number(number));
+        } else if c == '\'' || c == '"' {
+            chars.next();
+            let mut quoted = String::new();
+            loop {
+                match chars.next() {
+                    // A doubled quote stands for the quote itself.
+                    Some((_, q)) if q == c => match chars.peek() {
+                        Some(&(_, next)) if next == c => {
+                            quoted.push(c);
+                            chars.next();
+                        }
+                        _ => break,
+                    },
+                    Some((_, other)) => quoted.push(other),
+                    None => bail!("Unterminated quote at {}", start),
+                }
+            }
+            tokens.push(if c == '\'' {
+                Token::Text(quoted)
+            } else {
+                Token::QuotedIdent(quoted)
+            });
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|s| query[start..].starts_with(*s))
+                .ok_or_else(|| anyhow!("Unexpected character {:?} at {}", c, start))?;
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+struct Column {
+    name: String,
+    // Index in the records, set when the query is bound to the headers
+    index: usize,
+}
+
+impl Column {
+    fn new(name: String) -> Self {
+        Self { name, index: 0 }
+    }
+
+    fn bind(&mut self, headers: &StringRecord) -> anyhow::Result<()> {
+        self.index = headers
+            .iter()
+            .position(|h| h == self.name)
+            .ok_or_else(|| anyhow!("Unknown column: {}", self.name))?;
+        Ok(())
+    }
+
+    // The field, or None if it is NULL
+    fn get<'a>(&self, record: &'a StringRecord) -> Option<&'a str> {
+        record.get(self.index).filter(|v| !v.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        let function = match name.to_ascii_uppercase().as_str() {
+            "COUNT" => Function::Count,
+            "SUM" => Function::Sum,
+            "AVG" => Function::Avg,
+            "MIN" => Function::Min,
+            "MAX" => Function::Max,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Function::Count => "COUNT",
+            Function::Sum => "SUM",
+            Function::Avg => "AVG",
+            Function::Min => "MIN",
+            Function::Max => "MAX",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Item {
+    Wildcard,
+    Column {
+        column: Column,
+        alias: Option<String>,
+    },
+    // The column is None for COUNT(*)
+    Aggregate {
+        function: Function,
+        column: Option<Column>,
+        alias: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+enum Operand {
+    Column(Column),
+    Literal(String),
+}
+
+impl Operand {
+    fn value<'a>(&'a self, record: &'a StringRecord) -> Option<&'a str> {
+        match self {
+            Operand::Column(column) => column.get(record),
+            Operand::Literal(value) => Some(value),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Condition {
+    Compare(Operand, &'static str, Operand),
+    IsNull(Operand, bool),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+// Fields compare as numbers if both are, or else as strings.
+fn compare(left: &str, right: &str) -> Ordering {
+    match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        (Ok(l), Ok(r)) => l.partial_cmp(&r).unwrap_or(Ordering::Equal),
+        _ => left.cmp(right),
+    }
+}
+
+impl Condition {
+    // Three-valued logic, None being unknown
+    fn eval(&self, record: &StringRecord) -> Option<bool> {
+        match self {
+            Condition::Compare(left, op, right) => {
+                let ordering = compare(left.value(record)?, right.value(record)?);
+                let result = match *op {
+                    "=" => ordering == Ordering::Equal,
+                    "!=" | "<>" => ordering != Ordering::Equal,
+                    "<" => ordering == Ordering::Less,
+                    "<=" => ordering != Ordering::Greater,
+                    ">" => ordering == Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                };
+                Some(result)
+            }
+            Condition::IsNull(operand, negated) => {
+                Some(operand.value(record).is_none() != *negated)
+            }
+            Condition::And(left, right) => match (left.eval(record), right.eval(record)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Condition::Or(left, right) => match (left.eval(record), right.eval(record)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            Condition::Not(condition) => condition.eval(record).map(|b| !b),
+        }
+    }
+
+    fn bind(&mut self, headers: &StringRecord) -> anyhow::Result<()> {
+        let bind_operand = |operand: &mut Operand| match operand {
+            Operand::Column(column) => column.bind(headers),
+            Operand::Literal(_) => Ok(()),
+        };
+        match self {
+            Condition::Compare(left, _, right) => {
+                bind_operand(left)?;
+                bind_operand(right)
+            }
+            Condition::IsNull(operand, _) => bind_operand(operand),
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                left.bind(headers)?;
+                right.bind(headers)
+            }
+            Condition::Not(condition) => condition.bind(headers),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Query {
+    items: Vec<Item>,
+    table: String,
+    condition: Option<Condition>,
+    group_by: Vec<Column>,
+    limit: Option<usize>,
+}
+
+impl Query {
+    fn is_aggregate(&self) -> bool {
+        !self.group_by.is_empty()
+            || self
+                .items
+                .iter()
+                .any(|item| matches!(item, Item::Aggregate { .. }))
+    }
+
+    /// Resolve the columns against the headers of the input, and check the
+    /// items of aggregate queries.
+    fn bind(&mut self, headers: &StringRecord) -> anyhow::Result<()> {
+        for column in &mut self.group_by {
+            column.bind(headers)?;
+        }
+        let is_aggregate = self.is_aggregate();
+        for item in &mut self.items {
+            match item {
+                Item::Wildcard => ensure!(!is_aggregate, "* cannot be selected with aggregates"),
+                Item::Column { column, .. } => {
+                    column.bind(headers)?;
+                    ensure!(
+                        !is_aggregate || self.group_by.iter().any(|c| c.index == column.index),
+                        "Column {} should be grouped by or aggregated",
+                        column.name
+                    );
+                }
+                Item::Aggregate { column, .. } => {
+                    if let Some(column) = column {
+                        column.bind(headers)?;
+                    }
+                }
+            }
+        }
+        if let Some(condition) = &mut self.condition {
+            condition.bind(headers)?;
+        }
+        Ok(())
+    }
+
+    fn column_names(&self, headers: &StringRecord) -> Vec<String> {
+        let mut names = Vec::new();
+        for item in &self.items {
+            match item {
+                Item::Wildcard => names.extend(headers.iter().map(ToString::to_string)),
+                Item::Column { column, alias } => {
+                    names.push(alias.clone().unwrap_or_else(|| column.name.clone()))
+                }
+                Item::Aggregate {
+                    function,
+                    column,
+                    alias,
+                } => names.push(alias.clone().unwrap_or_else(|| {
+                    let argument = column.as_ref().map_or("*", |c| c.name.as_str());
+                    format!("{}({})", function.name(), argument)
+                })),
+            }
+        }
+        names
+    }
+
+    fn filter(&self, record: &StringRecord) -> bool {
+        match &self.condition {
+            Some(condition) => condition.eval(record) == Some(true),
+            None => true,
+        }
+    }
+
+    fn project<'a>(&self, record: &'a StringRecord) -> Vec<&'a str> {
+        let mut row = Vec::new();
+        for item in &self.items {
+            match item {
+                Item::Wildcard => row.extend(record.iter()),
+                Item::Column { column, .. } => row.push(record.get(column.index).unwrap_or("")),
+                Item::Aggregate { .. } => unreachable!("aggregates are not projected"),
+            }
+        }
+        row
+    }
+}
+
+enum Accumulator {
+    Count(u64),
+    Sum(Option<f64>),
+    Avg(f64, u64),
+    Min(Option<String>),
+    Max(Option<String>),
+}
+
+impl Accumulator {
+    fn new(function: Function) -> Self {
+        match function {
+            Function::Count => Accumulator::Count(0),
+            Function::Sum => Accumulator::Sum(None),
+            Function::Avg => Accumulator::Avg(0.0, 0),
+            Function::Min => Accumulator::Min(None),
+            Function::Max => Accumulator::Max(None),
+        }
+    }
+
+    fn update(&mut self, value: &str) -> anyhow::Result<()> {
+        let number = || -> anyhow::Result<f64> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Not a number: {}", value))
+        };
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => *sum = Some(sum.unwrap_or(0.0) + number()?),
+            Accumulator::Avg(sum, count) => {
+                *sum += number()?;
+                *count += 1;
+            }
+            Accumulator::Min(min) => {
+                if min
+                    .as_deref()
+                    .map_or(true, |m| compare(value, m) == Ordering::Less)
+                {
+                    *min = Some(value.to_string());
+                }
+            }
+            Accumulator::Max(max) => {
+                if max
+                    .as_deref()
+                    .map_or(true, |m| compare(value, m) == Ordering::Greater)
+                {
+                    *max = Some(value.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The result, empty for NULL
+    fn finish(&self) -> String {
+        match self {
+            Accumulator::Count(count) => count.to_string(),
+            Accumulator::Sum(sum) => sum.map(|s| s.to_string()).unwrap_or_default(),
+            Accumulator::Avg(_, 0) => String::new(),
+            Accumulator::Avg(sum, count) => (sum / *count as f64).to_string(),
+            Accumulator::Min(value) | Accumulator::Max(value) => value.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Groups in the order they are first seen, keyed by the values of the
+/// columns grouped by.
+#[derive(Default)]
+struct Groups {
+    index: HashMap<Vec<String>, usize>,
+    groups: Vec<(Vec<String>, Vec<Accumulator>)>,
+}
+
+impl Groups {
+    fn update(&mut self, query: &Query, record: &StringRecord) -> anyhow::Result<()> {
+        let key: Vec<String> = query
+            .group_by
+            .iter()
+            .map(|c| record.get(c.index).unwrap_or("").to_string())
+            .collect();
+        let i = match self.index.get(&key) {
+            Some(i) => *i,
+            None => {
+                ensure!(
+                    self.groups.len() < MAX_GROUPS,
+                    "More than {} groups",
+                    MAX_GROUPS
+                );
+                let accumulators = query
+                    .items
+                    .iter()
+                    .filter_map(|item| match item {
+                        Item::Aggregate { function, .. } => Some(Accumulator::new(*function)),
+                        _ => None,
+                    })
+                    .collect();
+                self.index.insert(key.clone(), self.groups.len());
+                self.groups.push((key, accumulators));
+                self.groups.len() - 1
+            }
+        };
+
+        let aggregates = query.items.iter().filter_map(|item| match item {
+            Item::Aggregate { column, .. } => Some(column),
+            _ => None,
+        });
+        for (accumulator, column) in self.groups[i].1.iter_mut().zip(aggregates) {
+            match column {
+                None => accumulator.update("*")?,
+                Some(column) => {
+                    if let Some(value) = column.get(record) {
+                        accumulator
+                            .update(value)
+                            .with_context(|| format!("Column {}", column.name))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, query: &Query) -> Vec<Vec<String>> {
+        // Aggregates over no rows at all still make a row, like in SQL.
+        if self.groups.is_empty() && query.group_by.is_empty() {
+            let accumulators = query
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    Item::Aggregate { function, .. } => Some(Accumulator::new(*function)),
+                    _ => None,
+                })
+                .collect();
+            self.groups.push((Vec::new(), accumulators));
+        }
+
+        let mut rows = Vec::with_capacity(self.groups.len());
+        for (key, accumulators) in self.groups {
+            let mut accumulators = accumulators.iter();
+            let mut row = Vec::with_capacity(query.items.len());
+            for item in &query.items {
+                match item {
+                    Item::Column { column, .. } => {
+                        let i = query
+                            .group_by
+                            .iter()
+                            .position(|c| c.index == column.index)
+                            .unwrap_or_default();
+                        row.push(key[i].clone());
+                    }
+                    Item::Aggregate { .. } => {
+                        row.push(accumulators.next().map(|a| a.finish()).unwrap_or_default())
+                    }
+                    Item::Wildcard => (),
+                }
+            }
+            rows.push(row);
+        }
+        rows
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> anyhow::Result<()> {
+        ensure!(
+            self.keyword(keyword),
+            "Expected {} but found {:?}",
+            keyword,
+            self.peek()
+        );
+        Ok(())
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(Token::Symbol(s)) if *s == symbol => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> anyhow::Result<()> {
+        ensure!(
+            self.symbol(symbol),
+            "Expected {} but found {:?}",
+            symbol,
+            self.peek()
+        );
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> anyhow::Result<String> {
+        let identifier = match self.peek() {
+            Some(Token::Word(word)) if !KEYWORDS.iter().any(|k| word.eq_ignore_ascii_case(k)) => {
+                word.clone()
+            }
+            Some(Token::QuotedIdent(identifier)) => identifier.clone(),
+            other => bail!("Expected an identifier but found {:?}", other),
+        };
+        self.pos += 1;
+        Ok(identifier)
+    }
+
+    fn query(&mut self) -> anyhow::Result<Query> {
+        self.expect_keyword("SELECT")?;
+        let mut items = vec![self.item()?];
+        while self.symbol(",") {
+            items.push(self.item()?);
+        }
+        self.expect_keyword("FROM")?;
+        let table = self.identifier()?;
+        let condition = if self.keyword("WHERE") {
+            Some(self.or()?)
+        } else {
+            None
+        };
+        let mut group_by = Vec::new();
+        if self.keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            group_by.push(Column::new(self.identifier()?));
+            while self.symbol(",") {
+                group_by.push(Column::new(self.identifier()?));
+            }
+        }
+        let limit = if self.keyword("LIMIT") {
+            match self.tokens.get(self.pos) {
+                Some(Token::Number(n)) => {
+                    let limit = n.parse().map_err(|_| anyhow!("Invalid limit: {}", n))?;
+                    self.pos += 1;
+                    Some(limit)
+                }
+                other => bail!("Expected a limit but found {:?}", other),
+            }
+        } else {
+            None
+        };
+        self.symbol(";");
+        if let Some(token) = self.peek() {
+            bail!("Unexpected {:?} after the query", token);
+        }
+
+        Ok(Query {
+            items,
+            table,
+            condition,
+            group_by,
+            limit,
+        })
+    }
+
+    fn item(&mut self) -> anyhow::Result<Item> {
+        if self.symbol("*") {
+            return Ok(Item::Wildcard);
+        }
+        let name = self.identifier()?;
+        let function = Function::from_name(&name);
+        let item = match function {
+            Some(function) if self.symbol("(") => {
+                let column = if function == Function::Count && self.symbol("*") {
+                    None
+                } else {
+                    Some(Column::new(self.identifier()?))
+                };
+                self.expect_symbol(")")?;
+                Item::Aggregate {
+                    function,
+                    column,
+                    alias: self.alias()?,
+                }
+            }
+            _ => Item::Column {
+                column: Column::new(name),
+                alias: self.alias()?,
+            },
+        };
+        Ok(item)
+    }
+
+    fn alias(&mut self) -> anyhow::Result<Option<String>> {
+        if self.keyword("AS") {
+            Ok(Some(self.identifier()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn or(&mut self) -> anyhow::Result<Condition> {
+        let mut condition = self.and()?;
+        while self.keyword("OR") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Condition> {
+        let mut condition = self.not()?;
+        while self.keyword("AND") {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> anyhow::Result<Condition> {
+        if self.keyword("NOT") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        if self.symbol("(") {
+            let condition = self.or()?;
+            self.expect_symbol(")")?;
+            return Ok(condition);
+        }
+        let left = self.operand()?;
+        if self.keyword("IS") {
+            let negated = self.keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Condition::IsNull(left, negated));
+        }
+        let op = match self.peek() {
+            Some(Token::Symbol(op)) if ["=", "!=", "<>", "<", "<=", ">", ">="].contains(op) => *op,
+            other => bail!("Expected a comparison but found {:?}", other),
+        };
+        self.pos += 1;
+        Ok(Condition::Compare(left, op, self.operand()?))
+    }
+
+    fn operand(&mut self) -> anyhow::Result<Operand> {
+        let negative = self.symbol("-");
+        let operand = match self.peek() {
+            Some(Token::Number(n)) => {
+                let n = if negative {
+                    format!("-{}", n)
+                } else {
+                    n.clone()
+                };
+                self.pos += 1;
+                return Ok(Operand::Literal(n));
+            }
+            _ if negative => bail!("Expected a number after -"),
+            Some(Token::Text(text)) => Operand::Literal(text.clone()),
+            _ => return Ok(Operand::Column(Column::new(self.identifier()?))),
+        };
+        self.pos += 1;
+        Ok(operand)
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    const FIXTURES: &str = "fixtures/functions/sql_query";
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            test_sql_query_projection,
+            test_sql_query_group_by,
+            test_sql_query_invalid,
+        )
+    }
+
+    fn run_query(query: &str) -> anyhow::Result<String> {
+        let arguments = FunctionArguments::from_json(json!({ "query": query })).unwrap();
+        let input = format!("{}/sales.csv", FIXTURES);
+        let output = format!("{}/result.csv", FIXTURES);
+        let input_files = StagedFiles::new(hashmap!(
+            "sales" =>
+            StagedFileInfo::new(&input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+        ));
+        let output_files = StagedFiles::new(hashmap!(
+            OUT_RESULT =>
+            StagedFileInfo::new(&output, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+        ));
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        SqlQuery::new().run(arguments, runtime)?;
+        Ok(fs::read_to_string(&output).unwrap())
+    }
+
+    fn test_sql_query_projection() {
+        let result = run_query(
+            "select region, amount as total from sales \
+             where (amount >= 20 and region <> 'west') or product = 'tea' limit 3",
+        )
+        .unwrap();
+        assert_eq!(result, "region,total\neast,10\neast,20\nwest,5\n");
+
+        let result = run_query("SELECT * FROM sales WHERE amount IS NULL").unwrap();
+        assert_eq!(result, "region,product,amount\nnorth,coffee,\n");
+
+        // Comparisons with NULL are unknown, so neither side keeps the row.
+        let result = run_query("SELECT region FROM sales WHERE NOT amount > 100").unwrap();
+        assert_eq!(result, "region\neast\neast\nwest\nwest\nnorth\n");
+    }
+
+    fn test_sql_query_group_by() {
+        let result = run_query(
+            "SELECT region, COUNT(*), COUNT(amount) AS sold, SUM(amount), AVG(amount), \
+             MAX(product) FROM sales GROUP BY region",
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "region,COUNT(*),sold,SUM(amount),AVG(amount),MAX(product)\n\
+             east,2,2,30,15,tea\n\
+             west,2,2,55,27.5,tea\n\
+             north,2,1,30,30,tea\n"
+        );
+
+        let result =
+            run_query("SELECT MIN(amount), SUM(amount) FROM sales WHERE amount > 100").unwrap();
+        assert_eq!(result, "MIN(amount),SUM(amount)\n,\n");
+    }
+
+    fn test_sql_query_invalid() {
+        assert!(run_query("SELECT region, amount FROM sales GROUP BY region").is_err());
+        assert!(run_query("SELECT * FROM sales GROUP BY region").is_err());
+        assert!(run_query("SELECT price FROM sales").is_err());
+        assert!(run_query("SELECT SUM(product) FROM sales").is_err());
+        assert!(run_query("SELECT region FROM other").is_err());
+        assert!(run_query("SELECT region FROM sales; DROP TABLE sales").is_err());
+        assert!(run_query("SELECT region FROM sales WHERE region = 'east").is_err());
+    }
+}
@@ -15,18 +15,22 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::protocol::{self, NegotiatingResolver, LEGACY_PROTOCOL_VERSION};
+use crate::session::{
+    client_sessions, AttestedTicketer, ServerSessions, DEFAULT_SESSION_FRESHNESS,
+};
 use crate::transport::{ClientTlsConfig, ServerTlsConfig};
 use anyhow::{anyhow, bail, Result};
 use log::debug;
 use std::sync::{Arc, RwLock};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 #[cfg(feature = "mesalock_sgx")]
 #[allow(unused_imports)]
 use std::untrusted::time::SystemTimeEx;
 
 use teaclave_attestation::report::AttestationReport;
-use teaclave_attestation::verifier::AttestationReportVerifier;
+use teaclave_attestation::verifier::{AttestationReportVerifier, VerificationPolicy};
 use teaclave_attestation::AttestedTlsConfig;
 use teaclave_types::EnclaveAttr;
 
@@ -39,6 +43,10 @@ pub struct SgxTrustedTlsServerConfig {
     attested_tls_config: Option<Arc<RwLock<AttestedTlsConfig>>>,
     time: std::time::SystemTime,
     validity: std::time::Duration,
+    min_protocol_version: u32,
+    client_verifier: Option<AttestationReportVerifier>,
+    mutual_attestation: bool,
+    session_freshness: Duration,
 }
 
 // Refer to `rustls/src/server/handy.rs` in rustls 0.21.2
@@ -94,6 +102,10 @@ impl Default for SgxTrustedTlsServerConfig {
             attested_tls_config: None,
             time,
             validity,
+            min_protocol_version: LEGACY_PROTOCOL_VERSION,
+            client_verifier: None,
+            mutual_attestation: true,
+            session_freshness: DEFAULT_SESSION_FRESHNESS,
         }
     }
 }
@@ -125,31 +137,90 @@ impl SgxTrustedTlsServerConfig {
     }
 
     // Disable this function for non-SGX targets.
-    #[cfg(any(feature = "mesalock_sgx", feature = "libos"))]
+    #[cfg(any(feature = "mesalock_sgx", feature = "libos", feature = "tdx"))]
     pub fn attestation_report_verifier(
         self,
         accepted_enclave_attrs: Vec<EnclaveAttr>,
         root_ca: &[u8],
         verifier: fn(&AttestationReport) -> bool,
+        policy: VerificationPolicy,
     ) -> Result<Self> {
-        let verifier = Arc::new(AttestationReportVerifier::new(
-            accepted_enclave_attrs,
-            root_ca,
-            verifier,
-        ));
-        let server_config = rustls::ServerConfig::builder()
-            .with_safe_defaults()
-            .with_client_cert_verifier(verifier)
-            .with_cert_resolver(self.server_config.cert_resolver);
+        let verifier = AttestationReportVerifier::new(accepted_enclave_attrs, root_ca, verifier)
+            .policy(policy);
+        let config = Self {
+            client_verifier: Some(verifier),
+            ..self
+        };
+
+        Ok(config.with_client_verifier())
+    }
+
+    /// Whether clients must present an attested certificate, which is
+    /// required by default once the attestation report verifier is set.
+    /// Clients without one are refused in the handshake with the
+    /// `certificate_required` alert. Otherwise, their certificates are only
+    /// verified if presented.
+    pub fn mutual_attestation(self, required: bool) -> Self {
+        Self {
+            mutual_attestation: required,
+            ..self
+        }
+        .with_client_verifier()
+    }
 
-        Ok(Self {
+    // Rebuild the rustls config to verify client certificates with the
+    // current verifier and requirement of mutual attestation.
+    fn with_client_verifier(self) -> Self {
+        let builder = rustls::ServerConfig::builder().with_safe_defaults();
+        let builder = match &self.client_verifier {
+            Some(verifier) => builder.with_client_cert_verifier(Arc::new(
+                verifier.clone().mutual_attestation(self.mutual_attestation),
+            )),
+            None => builder.with_no_client_auth(),
+        };
+        let server_config = builder.with_cert_resolver(self.server_config.cert_resolver.clone());
+
+        Self {
             server_config,
             ..self
-        })
+        }
+    }
+
+    /// Refuse clients which cannot speak `version` of the RPC protocol or a
+    /// higher one.
+    pub fn min_protocol_version(self, version: u32) -> Self {
+        Self {
+            min_protocol_version: version,
+            ..self
+        }
+    }
+
+    /// Resume the sessions of clients for `freshness` after the full
+    /// handshake, without verifying their attestation reports again.
+    pub fn session_freshness(self, freshness: Duration) -> Self {
+        Self {
+            session_freshness: freshness,
+            ..self
+        }
     }
 
     pub fn server_config(&self) -> Arc<rustls::ServerConfig> {
-        Arc::new(self.server_config.clone())
+        Arc::new(self.resumable_server_config())
+    }
+
+    // Sessions can be resumed within the freshness window until the
+    // attestation expires.
+    fn resumable_server_config(&self) -> rustls::ServerConfig {
+        let mut server_config = self.server_config.clone();
+        let expires_at = self.time.checked_add(self.validity);
+        server_config.session_storage = ServerSessions::new(expires_at, self.session_freshness);
+        match AttestedTicketer::new(expires_at, self.session_freshness) {
+            Ok(ticketer) => server_config.ticketer = ticketer,
+            Err(e) => debug!("Session tickets are disabled: {:?}", e),
+        }
+        server_config.cert_resolver =
+            NegotiatingResolver::new(server_config.cert_resolver, self.min_protocol_version);
+        server_config
     }
 
     pub fn need_refresh(&self) -> bool {
@@ -186,7 +257,7 @@ impl SgxTrustedTlsServerConfig {
 
 impl From<SgxTrustedTlsServerConfig> for ServerTlsConfig {
     fn from(config: SgxTrustedTlsServerConfig) -> Self {
-        let mut config_service = config.server_config;
+        let mut config_service = config.resumable_server_config();
         config_service.alpn_protocols = vec![ALPN_H2.as_bytes().to_vec()];
         let mut tls_config = ServerTlsConfig::new();
         let tls_config = tls_config.rustls_server_config(config_service);
@@ -198,6 +269,8 @@ pub struct SgxTrustedTlsClientConfig {
     pub client_config: rustls::ClientConfig,
     pub attested_tls_config: Option<Arc<RwLock<AttestedTlsConfig>>>,
     pub validity: std::time::Duration,
+    pub min_protocol_version: u32,
+    pub session_freshness: Duration,
 }
 
 struct NoServerAuth;
@@ -239,6 +312,8 @@ impl Default for SgxTrustedTlsClientConfig {
             client_config,
             attested_tls_config: None,
             validity: std::time::Duration::default(),
+            min_protocol_version: LEGACY_PROTOCOL_VERSION,
+            session_freshness: DEFAULT_SESSION_FRESHNESS,
         }
     }
 }
@@ -253,12 +328,12 @@ impl SgxTrustedTlsClientConfig {
         accepted_enclave_attrs: Vec<EnclaveAttr>,
         root_ca: &[u8],
         verifier: fn(&AttestationReport) -> bool,
+        policy: VerificationPolicy,
     ) -> Self {
-        let verifier = Arc::new(AttestationReportVerifier::new(
-            accepted_enclave_attrs,
-            root_ca,
-            verifier,
-        ));
+        let verifier = Arc::new(
+            AttestationReportVerifier::new(accepted_enclave_attrs, root_ca, verifier)
+                .policy(policy),
+        );
         self.client_config
             .dangerous()
             .set_certificate_verifier(verifier);
@@ -266,6 +341,42 @@ impl SgxTrustedTlsClientConfig {
         Self { ..self }
     }
 
+    /// Require the client to present an attested certificate to the server,
+    /// i.e., mutual attestation, which fails early if the config has none.
+    /// The server decides whether to verify the certificate otherwise.
+    pub fn mutual_attestation(self, required: bool) -> Result<Self> {
+        if required && !self.client_config.client_auth_cert_resolver.has_certs() {
+            bail!("Mutual attestation requires an attested certificate of the client");
+        }
+
+        Ok(self)
+    }
+
+    /// Only offer `version` of the RPC protocol and the higher ones, so that
+    /// servers speaking none of them refuse the connection.
+    pub fn min_protocol_version(self, version: u32) -> Self {
+        Self {
+            min_protocol_version: version,
+            ..self
+        }
+    }
+
+    /// Resume the sessions to servers for `freshness` after the full
+    /// handshake, without verifying their attestation reports again.
+    pub fn session_freshness(mut self, freshness: Duration) -> Self {
+        if let Some(lock) = &self.attested_tls_config {
+            if let Ok(tls_config) = lock.read() {
+                self.client_config.resumption =
+                    rustls::client::Resumption::store(client_sessions(tls_config.time, freshness));
+            }
+        }
+
+        Self {
+            session_freshness: freshness,
+            ..self
+        }
+    }
+
     pub fn client_cert(mut self, cert: &[u8], key_der: &[u8]) -> Result<Self> {
         let cert_chain = vec![rustls::Certificate(cert.to_vec())];
         let key_der = rustls::PrivateKey(key_der.to_vec());
@@ -281,6 +392,10 @@ impl SgxTrustedTlsClientConfig {
         let lock = attested_tls_config.clone();
         let tls_config = lock.read().map_err(|_| anyhow!("lock error"))?;
         let mut config = Self::new().client_cert(&tls_config.cert, &tls_config.private_key)?;
+        config.client_config.resumption = rustls::client::Resumption::store(client_sessions(
+            tls_config.time,
+            DEFAULT_SESSION_FRESHNESS,
+        ));
         config.attested_tls_config = Some(attested_tls_config);
         Ok(config)
     }
@@ -289,8 +404,9 @@ impl SgxTrustedTlsClientConfig {
 impl From<SgxTrustedTlsClientConfig> for ClientTlsConfig {
     fn from(config: SgxTrustedTlsClientConfig) -> Self {
         let mut client_config = config.client_config;
-        // Yout must set the 'h2' negotiation flag.
-        client_config.alpn_protocols = vec![ALPN_H2.as_bytes().to_vec()];
+        // Yout must set the 'h2' negotiation flag, which is followed by the
+        // offered versions of the RPC protocol.
+        client_config.alpn_protocols = protocol::alpn_protocols(config.min_protocol_version);
         ClientTlsConfig::new().rustls_client_config(client_config)
     }
 }
@@ -0,0 +1,137 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Connections of long-lived channels.
+//!
+//! A channel sends HTTP/2 keepalive pings with the settings of its endpoint,
+//! and closes the connection when a ping is not acknowledged in time, e.g.,
+//! after a network blip, instead of waiting on a stale connection forever.
+//! The next request on the channel then connects again. Channels connected
+//! with `connect_monitored` log these reconnections and count them in
+//! `ConnectionStats`.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::net::TcpStream;
+use tonic::codegen::Service;
+
+use crate::transport::{Channel, Endpoint, Error, Uri};
+
+/// Counters of the connections of a channel.
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    connects: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl ConnectionStats {
+    /// Number of successful connections, including the first one.
+    pub fn connects(&self) -> u64 {
+        self.connects.load(Ordering::Relaxed)
+    }
+
+    pub fn reconnects(&self) -> u64 {
+        self.connects().saturating_sub(1)
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone)]
+struct MonitoredConnector {
+    name: &'static str,
+    connect_timeout: Duration,
+    stats: Arc<ConnectionStats>,
+}
+
+impl MonitoredConnector {
+    async fn connect(self, uri: Uri) -> io::Result<TcpStream> {
+        let host = uri
+            .host()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing host"))?;
+        let port = uri.port_u16().unwrap_or(443);
+        let stream = tokio::time::timeout(
+            self.connect_timeout,
+            TcpStream::connect((host.trim_matches(|c| c == '[' || c == ']'), port)),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timeout"))
+        .and_then(|result| result);
+
+        match stream {
+            Ok(stream) => {
+                stream.set_nodelay(true)?;
+                let connects = self.stats.connects.fetch_add(1, Ordering::Relaxed) + 1;
+                if connects == 1 {
+                    info!("Connected to {} at {}", self.name, uri);
+                } else {
+                    warn!(
+                        "Reconnected to {} at {} ({} reconnects)",
+                        self.name,
+                        uri,
+                        connects - 1
+                    );
+                }
+                Ok(stream)
+            }
+            Err(e) => {
+                self.stats.failures.fetch_add(1, Ordering::Relaxed);
+                warn!("Failed to connect to {} at {}: {}", self.name, uri, e);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Service<Uri> for MonitoredConnector {
+    type Response = TcpStream;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        Box::pin(self.clone().connect(uri))
+    }
+}
+
+/// Connect a channel to the service `name` with `endpoint`, which reconnects
+/// when the connection is lost, and count the connections in `stats`.
+pub async fn connect_monitored(
+    endpoint: &Endpoint,
+    name: &'static str,
+    connect_timeout: Duration,
+    stats: Arc<ConnectionStats>,
+) -> Result<Channel, Error> {
+    let connector = MonitoredConnector {
+        name,
+        connect_timeout,
+        stats,
+    };
+    endpoint.connect_with_connector(connector).await
+}
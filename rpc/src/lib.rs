@@ -16,10 +16,15 @@
 // under the License.
 
 pub mod config;
+pub mod connection;
 pub mod interceptor;
 mod macros;
+pub mod protocol;
+mod session;
+pub mod timeout;
 
 pub use interceptor::{CredentialService, UserCredential};
+pub use session::DEFAULT_SESSION_FRESHNESS;
 
 pub use tonic::{
     async_trait, metadata::MetadataMap, service::interceptor::InterceptedService, Code,
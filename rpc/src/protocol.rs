@@ -0,0 +1,119 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Negotiation of the version of the RPC protocol.
+//!
+//! Clients offer the versions they speak in the ALPN extension of the
+//! attested TLS handshake, as `teaclave/<version>` next to `h2`, from the
+//! highest one down to their minimum version. The server picks the highest
+//! offered version it speaks, and refuses the handshake if that is below its
+//! own minimum version. Clients which offer no version speak version 1.
+//!
+//! The ClientHello is covered by the Finished messages of the handshake, so
+//! the offered versions cannot be stripped to downgrade a connection without
+//! failing the handshake. A resumed session keeps the version negotiated when
+//! it was established.
+
+use std::sync::Arc;
+
+use log::{info, warn};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+
+use crate::config::ALPN_H2;
+
+/// The highest version of the protocol spoken by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The version spoken by clients which offer no version.
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+const ALPN_PREFIX: &str = "teaclave/";
+
+/// ALPN protocols of a client speaking the versions from `min_version` up to
+/// `PROTOCOL_VERSION`, with the highest version first.
+pub(crate) fn alpn_protocols(min_version: u32) -> Vec<Vec<u8>> {
+    let mut protocols = vec![ALPN_H2.as_bytes().to_vec()];
+    protocols.extend(
+        (min_version.max(LEGACY_PROTOCOL_VERSION)..=PROTOCOL_VERSION)
+            .rev()
+            .map(|version| format!("{}{}", ALPN_PREFIX, version).into_bytes()),
+    );
+    protocols
+}
+
+/// Versions offered in the ALPN protocols of a client, or the legacy version
+/// if the client offers none.
+fn offered_versions<'a>(alpn: Option<impl Iterator<Item = &'a [u8]>>) -> Vec<u32> {
+    let versions: Vec<u32> = alpn
+        .into_iter()
+        .flatten()
+        .filter_map(|protocol| std::str::from_utf8(protocol).ok())
+        .filter_map(|protocol| protocol.strip_prefix(ALPN_PREFIX))
+        .filter_map(|version| version.parse().ok())
+        .collect();
+    if versions.is_empty() {
+        vec![LEGACY_PROTOCOL_VERSION]
+    } else {
+        versions
+    }
+}
+
+/// The highest version in `offered` which is spoken by this build and not
+/// below `min_version`.
+pub fn negotiate(offered: &[u32], min_version: u32) -> Option<u32> {
+    offered
+        .iter()
+        .copied()
+        .filter(|version| (min_version..=PROTOCOL_VERSION).contains(version))
+        .max()
+}
+
+/// Resolves the certificate of the server only if the protocol version can be
+/// negotiated with the client, so that the handshake fails otherwise.
+pub(crate) struct NegotiatingResolver {
+    inner: Arc<dyn ResolvesServerCert>,
+    min_version: u32,
+}
+
+impl NegotiatingResolver {
+    pub(crate) fn new(inner: Arc<dyn ResolvesServerCert>, min_version: u32) -> Arc<Self> {
+        Arc::new(Self { inner, min_version })
+    }
+}
+
+impl ResolvesServerCert for NegotiatingResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let offered = offered_versions(client_hello.alpn());
+        match negotiate(&offered, self.min_version) {
+            Some(version) => {
+                info!(
+                    "Negotiated RPC protocol version {} with a client offering {:?}",
+                    version, offered
+                );
+                self.inner.resolve(client_hello)
+            }
+            None => {
+                warn!(
+                    "Refused a client offering RPC protocol versions {:?} without any supported version from {}",
+                    offered, self.min_version
+                );
+                None
+            }
+        }
+    }
+}
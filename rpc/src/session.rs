@@ -0,0 +1,273 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! TLS session resumption of attested channels.
+//!
+//! A resumed session skips the verification of the attestation report of the
+//! peer, so sessions are only resumed within the freshness window after the
+//! full handshake, and while the attestation they were established with is
+//! valid. Servers resume sessions by session IDs or tickets. The tickets are
+//! encrypted with keys generated for the attestation of the server, so they
+//! cannot be resumed once it is refreshed, and carry the time they were
+//! issued at. Clients share the sessions of an attestation until it is
+//! refreshed.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "mesalock_sgx")]
+#[allow(unused_imports)]
+use std::untrusted::time::SystemTimeEx;
+
+use lazy_static::lazy_static;
+use rustls::client::{
+    ClientSessionMemoryCache, ClientSessionStore, Tls12ClientSessionValue, Tls13ClientSessionValue,
+};
+use rustls::server::{ProducesTickets, ServerSessionMemoryCache, StoresServerSessions};
+use rustls::{NamedGroup, ServerName};
+
+/// How long sessions are resumed after the full handshake by default.
+pub const DEFAULT_SESSION_FRESHNESS: Duration = Duration::from_secs(600);
+
+// Number of sessions kept by a server or by the clients of an enclave
+const SESSION_CACHE_SIZE: usize = 256;
+
+// Length of the time prefixed to the stored sessions and tickets
+const TIMESTAMP_LEN: usize = 8;
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The validity of the sessions established with an attestation.
+#[derive(Clone, Copy)]
+struct Freshness {
+    window: Duration,
+    // None if the attestation never expires
+    expires_at: Option<SystemTime>,
+}
+
+impl Freshness {
+    fn is_valid(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => SystemTime::now() < expires_at,
+            None => true,
+        }
+    }
+
+    // Whether a session established at `issued_at`, in seconds since the
+    // epoch, can still be resumed.
+    fn is_fresh(&self, issued_at: u64) -> bool {
+        let now = unix_secs(SystemTime::now());
+        self.is_valid() && now.saturating_sub(issued_at) < self.window.as_secs()
+    }
+
+    // Seconds until the sessions established now are not resumed.
+    fn lifetime(&self) -> u32 {
+        let remaining = match self.expires_at {
+            Some(expires_at) => expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or_default(),
+            None => self.window,
+        };
+        self.window
+            .min(remaining)
+            .as_secs()
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+
+    fn stamp(&self, value: &[u8]) -> Vec<u8> {
+        let mut stamped = unix_secs(SystemTime::now()).to_be_bytes().to_vec();
+        stamped.extend_from_slice(value);
+        stamped
+    }
+
+    // The value of a stamped session if it can still be resumed.
+    fn unstamp(&self, mut stamped: Vec<u8>) -> Option<Vec<u8>> {
+        if stamped.len() < TIMESTAMP_LEN {
+            return None;
+        }
+        let value = stamped.split_off(TIMESTAMP_LEN);
+        let issued_at = u64::from_be_bytes(stamped.try_into().ok()?);
+        self.is_fresh(issued_at).then_some(value)
+    }
+}
+
+/// Sessions of a server which expire with its attestation, or when they are
+/// older than the freshness window.
+pub(crate) struct ServerSessions {
+    cache: Arc<ServerSessionMemoryCache>,
+    freshness: Freshness,
+}
+
+impl ServerSessions {
+    pub(crate) fn new(expires_at: Option<SystemTime>, window: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            cache: ServerSessionMemoryCache::new(SESSION_CACHE_SIZE),
+            freshness: Freshness { window, expires_at },
+        })
+    }
+}
+
+impl StoresServerSessions for ServerSessions {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.freshness.is_valid() && self.cache.put(key, self.freshness.stamp(&value))
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.cache
+            .get(key)
+            .and_then(|stamped| self.freshness.unstamp(stamped))
+    }
+
+    fn take(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.cache
+            .take(key)
+            .and_then(|stamped| self.freshness.unstamp(stamped))
+    }
+
+    fn can_cache(&self) -> bool {
+        self.freshness.is_valid()
+    }
+}
+
+/// Session tickets of a server, encrypted with keys generated for its
+/// attestation. A ticket is only accepted within the freshness window after
+/// it is issued, and before the attestation expires.
+pub(crate) struct AttestedTicketer {
+    inner: Arc<dyn ProducesTickets>,
+    freshness: Freshness,
+}
+
+impl AttestedTicketer {
+    pub(crate) fn new(
+        expires_at: Option<SystemTime>,
+        window: Duration,
+    ) -> Result<Arc<Self>, rustls::Error> {
+        Ok(Arc::new(Self {
+            inner: rustls::Ticketer::new()?,
+            freshness: Freshness { window, expires_at },
+        }))
+    }
+}
+
+impl ProducesTickets for AttestedTicketer {
+    fn enabled(&self) -> bool {
+        self.freshness.is_valid()
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.freshness.lifetime()
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        self.inner.encrypt(&self.freshness.stamp(plain))
+    }
+
+    fn decrypt(&self, cipher: &[u8]) -> Option<Vec<u8>> {
+        self.inner
+            .decrypt(cipher)
+            .and_then(|stamped| self.freshness.unstamp(stamped))
+    }
+}
+
+/// Sessions of the clients of an enclave, which are not resumed when older
+/// than the freshness window.
+pub(crate) struct ClientSessions {
+    cache: ClientSessionMemoryCache,
+    window: Duration,
+    // When the TLS 1.2 session of each server was stored
+    stored_at: Mutex<HashMap<ServerName, SystemTime>>,
+}
+
+impl ClientSessions {
+    fn new(window: Duration) -> Self {
+        Self {
+            cache: ClientSessionMemoryCache::new(SESSION_CACHE_SIZE),
+            window,
+            stored_at: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ClientSessionStore for ClientSessions {
+    fn set_kx_hint(&self, server_name: &ServerName, group: NamedGroup) {
+        self.cache.set_kx_hint(server_name, group)
+    }
+
+    fn kx_hint(&self, server_name: &ServerName) -> Option<NamedGroup> {
+        self.cache.kx_hint(server_name)
+    }
+
+    fn set_tls12_session(&self, server_name: &ServerName, value: Tls12ClientSessionValue) {
+        let mut stored_at = self.stored_at.lock().unwrap();
+        // Forget the servers whose sessions were evicted from the cache
+        if stored_at.len() >= SESSION_CACHE_SIZE {
+            stored_at.retain(|_, time| time.elapsed().unwrap_or_default() < self.window);
+        }
+        stored_at.insert(server_name.clone(), SystemTime::now());
+        self.cache.set_tls12_session(server_name, value)
+    }
+
+    fn tls12_session(&self, server_name: &ServerName) -> Option<Tls12ClientSessionValue> {
+        let stored_at = self.stored_at.lock().unwrap().get(server_name).copied()?;
+        if stored_at.elapsed().unwrap_or_default() >= self.window {
+            self.remove_tls12_session(server_name);
+            return None;
+        }
+        self.cache.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName) {
+        self.stored_at.lock().unwrap().remove(server_name);
+        self.cache.remove_tls12_session(server_name)
+    }
+
+    // TLS 1.3 tickets expire with the lifetime given by the server.
+    fn insert_tls13_ticket(&self, server_name: &ServerName, value: Tls13ClientSessionValue) {
+        self.cache.insert_tls13_ticket(server_name, value)
+    }
+
+    fn take_tls13_ticket(&self, server_name: &ServerName) -> Option<Tls13ClientSessionValue> {
+        self.cache.take_tls13_ticket(server_name)
+    }
+}
+
+// Sessions of the clients of an enclave, and the time of the attestation
+// they were established with
+lazy_static! {
+    static ref CLIENT_SESSIONS: Mutex<Option<(SystemTime, Arc<ClientSessions>)>> = Mutex::new(None);
+}
+
+/// Sessions shared by the clients with the attestation of `time`, so that
+/// new channels to a service resume the sessions of the previous ones. The
+/// sessions are dropped when the freshness window changes.
+pub(crate) fn client_sessions(time: SystemTime, window: Duration) -> Arc<ClientSessions> {
+    let mut sessions = CLIENT_SESSIONS.lock().unwrap();
+    match &*sessions {
+        Some((t, cache)) if *t == time && cache.window == window => cache.clone(),
+        _ => {
+            let cache = Arc::new(ClientSessions::new(window));
+            *sessions = Some((time, cache.clone()));
+            cache
+        }
+    }
+}
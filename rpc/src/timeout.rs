@@ -0,0 +1,39 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Timeouts of requests.
+//!
+//! A channel built from an endpoint with `timeout` fails the requests which
+//! take longer, and a request may have a shorter timeout with
+//! `Request::set_timeout`, which is also sent to the server in the
+//! `grpc-timeout` header so that the server stops handling it as well. Either
+//! way, the request is cancelled, and tonic reports it as `Cancelled`, which
+//! is also what a server cancelling a request returns. Use `is_timeout` to
+//! tell timeouts apart, e.g., to retry the request or degrade gracefully.
+
+use crate::{Code, Status};
+
+// Message of the error of tonic when a request times out
+const TIMEOUT_EXPIRED: &str = "Timeout expired";
+
+pub fn is_timeout(status: &Status) -> bool {
+    match status.code() {
+        Code::DeadlineExceeded => true,
+        Code::Cancelled => status.message() == TIMEOUT_EXPIRED,
+        _ => false,
+    }
+}
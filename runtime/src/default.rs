@@ -17,12 +17,14 @@
 
 use std::io;
 
+use crate::services::RuntimeServices;
 use teaclave_types::StagedFiles;
 use teaclave_types::TeaclaveRuntime;
 
 pub struct DefaultRuntime {
     input_files: StagedFiles,
     output_files: StagedFiles,
+    services: RuntimeServices,
 }
 
 impl DefaultRuntime {
@@ -30,6 +32,7 @@ impl DefaultRuntime {
         DefaultRuntime {
             input_files,
             output_files,
+            services: RuntimeServices::new(),
         }
     }
 }
@@ -56,4 +59,12 @@ impl TeaclaveRuntime for DefaultRuntime {
         let writable = file_info.create_writable_io()?;
         Ok(writable)
     }
+
+    fn current_time(&self) -> anyhow::Result<u64> {
+        self.services.current_time()
+    }
+
+    fn random_bytes(&self, buf: &mut [u8]) -> anyhow::Result<()> {
+        self.services.random_bytes(buf)
+    }
 }
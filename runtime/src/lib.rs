@@ -18,7 +18,10 @@
 extern crate sgx_types;
 
 mod default;
+mod output_limit;
+mod services;
 pub use default::DefaultRuntime;
+pub use output_limit::{OutputLimitedRuntime, OutputUsage};
 
 #[cfg(any(feature = "enclave_unit_test", test_mode))]
 mod raw_io;
@@ -27,7 +30,14 @@ pub use raw_io::RawIoRuntime;
 
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
+
     pub fn run_tests() -> bool {
-        true
+        run_tests!(
+            services::tests::test_current_time,
+            services::tests::test_random_bytes,
+            output_limit::tests::test_output_limit,
+        )
     }
 }
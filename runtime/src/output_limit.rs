@@ -0,0 +1,170 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A limit on the bytes a function writes to all its outputs.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use teaclave_types::{FunctionRuntime, TeaclaveRuntime};
+
+/// Bytes written to the outputs of a run, shared by the writers of the
+/// outputs and the executor.
+pub struct OutputUsage {
+    max_bytes: u64,
+    written: AtomicU64,
+    exceeded: AtomicBool,
+}
+
+impl OutputUsage {
+    /// Whether a write went beyond the limit. Functions may go on with the
+    /// error of the write, so the executor fails the run on this instead.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Acquire)
+    }
+
+    fn add(&self, bytes: usize) -> io::Result<()> {
+        let bytes = bytes as u64;
+        let fits = self
+            .written
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |written| {
+                written
+                    .checked_add(bytes)
+                    .filter(|total| *total <= self.max_bytes)
+            })
+            .is_ok();
+        if !fits {
+            self.exceeded.store(true, Ordering::Release);
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Outputs exceed the limit of {} bytes", self.max_bytes),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The runtime of a function whose outputs are limited to `max_bytes` in
+/// total. A write going beyond the limit is refused as a whole.
+pub struct OutputLimitedRuntime {
+    inner: FunctionRuntime,
+    usage: Arc<OutputUsage>,
+}
+
+impl OutputLimitedRuntime {
+    pub fn new(inner: FunctionRuntime, max_bytes: u64) -> Self {
+        let usage = OutputUsage {
+            max_bytes,
+            written: AtomicU64::new(0),
+            exceeded: AtomicBool::new(false),
+        };
+        Self {
+            inner,
+            usage: Arc::new(usage),
+        }
+    }
+
+    pub fn usage(&self) -> Arc<OutputUsage> {
+        self.usage.clone()
+    }
+}
+
+impl TeaclaveRuntime for OutputLimitedRuntime {
+    fn open_input(&self, identifier: &str) -> anyhow::Result<Box<dyn io::Read>> {
+        self.inner.open_input(identifier)
+    }
+
+    fn create_output(&self, identifier: &str) -> anyhow::Result<Box<dyn io::Write>> {
+        let inner = self.inner.create_output(identifier)?;
+        Ok(Box::new(LimitedWriter {
+            inner,
+            usage: self.usage.clone(),
+        }))
+    }
+
+    fn current_time(&self) -> anyhow::Result<u64> {
+        self.inner.current_time()
+    }
+
+    fn random_bytes(&self, buf: &mut [u8]) -> anyhow::Result<()> {
+        self.inner.random_bytes(buf)
+    }
+}
+
+struct LimitedWriter {
+    inner: Box<dyn io::Write>,
+    usage: Arc<OutputUsage>,
+}
+
+impl io::Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.usage.add(buf.len())?;
+        // Bytes the inner writer does not take are not counted.
+        let written = self.inner.write(buf)?;
+        self.usage
+            .written
+            .fetch_sub((buf.len() - written) as u64, Ordering::AcqRel);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use std::io::Write;
+
+    struct SinkRuntime;
+
+    impl TeaclaveRuntime for SinkRuntime {
+        fn open_input(&self, _identifier: &str) -> anyhow::Result<Box<dyn io::Read>> {
+            Ok(Box::new(io::empty()))
+        }
+
+        fn create_output(&self, _identifier: &str) -> anyhow::Result<Box<dyn io::Write>> {
+            Ok(Box::new(io::sink()))
+        }
+
+        fn current_time(&self) -> anyhow::Result<u64> {
+            Ok(0)
+        }
+
+        fn random_bytes(&self, _buf: &mut [u8]) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    pub fn test_output_limit() {
+        let runtime = OutputLimitedRuntime::new(Box::new(SinkRuntime), 10);
+        let usage = runtime.usage();
+        let mut first = runtime.create_output("first").unwrap();
+        let mut second = runtime.create_output("second").unwrap();
+
+        first.write_all(b"hello").unwrap();
+        second.write_all(b"world").unwrap();
+        assert!(!usage.exceeded());
+
+        // The limit is on all the outputs together.
+        assert!(first.write_all(b"!").is_err());
+        assert!(usage.exceeded());
+        assert!(second.write_all(b"").is_ok());
+    }
+}
@@ -15,6 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::services::RuntimeServices;
 #[cfg(not(feature = "mesalock_sgx"))]
 use std::fs::File;
 use std::io;
@@ -26,6 +27,7 @@ use teaclave_types::TeaclaveRuntime;
 pub struct RawIoRuntime {
     input_files: StagedFiles,
     output_files: StagedFiles,
+    services: RuntimeServices,
 }
 
 impl RawIoRuntime {
@@ -33,6 +35,7 @@ impl RawIoRuntime {
         RawIoRuntime {
             input_files,
             output_files,
+            services: RuntimeServices::new(),
         }
     }
 }
@@ -57,4 +60,12 @@ impl TeaclaveRuntime for RawIoRuntime {
         let f = File::create(&file_info.path)?;
         Ok(Box::new(f))
     }
+
+    fn current_time(&self) -> anyhow::Result<u64> {
+        self.services.current_time()
+    }
+
+    fn random_bytes(&self, buf: &mut [u8]) -> anyhow::Result<()> {
+        self.services.random_bytes(buf)
+    }
 }
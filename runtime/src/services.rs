@@ -0,0 +1,125 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Time and entropy provided to functions by the runtime, so that functions do
+//! not rely on untrusted system calls of their own.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "mesalock_sgx")]
+#[allow(unused_imports)]
+use std::untrusted::time::SystemTimeEx;
+
+use anyhow::{anyhow, ensure, Result};
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+// A host reporting a time before 2023-01-01 is lying about it.
+const MIN_TIME_MS: u64 = 1_672_531_200_000;
+
+/// Time and entropy for a task. The time is read from the host through an
+/// OCall, so it is only returned if it is later than `MIN_TIME_MS` and never
+/// goes backwards during the task. The entropy is drawn from the CPU in the
+/// enclave. How much of them a task used is recorded in its log.
+pub(crate) struct RuntimeServices {
+    // The latest time returned, in milliseconds since the Unix epoch
+    last_time_ms: Mutex<u64>,
+    time_reads: AtomicU64,
+    entropy_bytes: AtomicU64,
+}
+
+impl RuntimeServices {
+    pub(crate) fn new() -> Self {
+        Self {
+            last_time_ms: Mutex::new(MIN_TIME_MS),
+            time_reads: AtomicU64::new(0),
+            entropy_bytes: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn current_time(&self) -> Result<u64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        self.check_time(now)?;
+        self.time_reads.fetch_add(1, Ordering::Relaxed);
+        Ok(now)
+    }
+
+    fn check_time(&self, now: u64) -> Result<()> {
+        let mut last = self
+            .last_time_ms
+            .lock()
+            .map_err(|_| anyhow!("lock poisoned"))?;
+        ensure!(
+            now >= *last,
+            "Untrusted time {} is earlier than {}",
+            now,
+            *last
+        );
+        *last = now;
+        Ok(())
+    }
+
+    pub(crate) fn random_bytes(&self, buf: &mut [u8]) -> Result<()> {
+        OsRng.try_fill_bytes(buf)?;
+        self.entropy_bytes
+            .fetch_add(buf.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Drop for RuntimeServices {
+    fn drop(&mut self) {
+        let time_reads = self.time_reads.load(Ordering::Relaxed);
+        let entropy_bytes = self.entropy_bytes.load(Ordering::Relaxed);
+        if time_reads > 0 || entropy_bytes > 0 {
+            log::info!(
+                "Runtime services used: time read {} times, {} bytes of entropy",
+                time_reads,
+                entropy_bytes
+            );
+        }
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_current_time() {
+        let services = RuntimeServices::new();
+        let first = services.current_time().unwrap();
+        let second = services.current_time().unwrap();
+        assert!(second >= first);
+        assert_eq!(services.time_reads.load(Ordering::Relaxed), 2);
+
+        // Time going backwards or earlier than the minimum is rejected
+        assert!(services.check_time(first - 1).is_err());
+        let services = RuntimeServices::new();
+        assert!(services.check_time(MIN_TIME_MS - 1).is_err());
+    }
+
+    pub fn test_random_bytes() {
+        let services = RuntimeServices::new();
+        let mut first = [0u8; 32];
+        let mut second = [0u8; 32];
+        services.random_bytes(&mut first).unwrap();
+        services.random_bytes(&mut second).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(services.entropy_bytes.load(Ordering::Relaxed), 64);
+    }
+}
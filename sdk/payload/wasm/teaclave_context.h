@@ -24,6 +24,8 @@
  * 2. Run `rustup run nightly cbindgen ./teaclave_context -c cbindgen.toml -o teaclave_context.h`.
  */
 
+#include <stdint.h>
+
 /**
  * Close a file handler
  *
@@ -92,3 +94,26 @@ extern int teaclave_create_output(char *fid);
  * file handler, -1 if error occurs
  */
 extern int teaclave_open_input(char *fid);
+
+/**
+ * Current time from the runtime, which never goes backwards in a task
+ *
+ * # Return
+ *
+ * milliseconds since the Unix epoch, -1 if error occurs
+ */
+extern int64_t teaclave_current_time(void);
+
+/**
+ * Fill a buffer with random bytes from the enclave
+ *
+ * # Arguments
+ *
+ * * `out_buf` - the pointer to output buffer
+ * * `buf_size` - the total size in bytes of the output buffer
+ *
+ * # Return
+ *
+ * 0 if succeed, -1 otherwise
+ */
+extern int teaclave_random_bytes(char *out_buf, int buf_size);
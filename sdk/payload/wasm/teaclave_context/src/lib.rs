@@ -122,6 +122,83 @@ impl TeaclaveContextFile {
     }
 }
 
+/// Function arguments passed to `entrypoint(argc, argv)` in key-value pairs.
+/// Values other than strings are JSON texts, e.g., `10` for an integer and
+/// `true` for a boolean, which can be read with the typed getters.
+pub struct TeaclaveArguments {
+    pairs: Vec<(String, String)>,
+}
+
+impl TeaclaveArguments {
+    /// Collect the arguments from `argv` of the entrypoint
+    ///
+    /// # Safety
+    ///
+    /// `argv` should point to `argc` valid C strings
+    pub unsafe fn from_raw(argc: c_int, argv: *const *const c_char) -> Self {
+        let args: Vec<String> = (0..argc as usize)
+            .map(|i| {
+                std::ffi::CStr::from_ptr(*argv.add(i))
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        Self::from_args(args)
+    }
+
+    pub fn from_args(args: Vec<String>) -> Self {
+        let mut iter = args.into_iter();
+        let mut pairs = Vec::new();
+        while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+            pairs.push((key, value));
+        }
+        Self { pairs }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get_str(key)?.parse().ok()
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get_str(key)?.parse().ok()
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get_str(key)?.parse().ok()
+    }
+}
+
+/// A wrapped version of `teaclave_current_time`
+pub fn current_time() -> Result<u64> {
+    let time = unsafe { teaclave_current_time() };
+    if time == -1 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "teaclave_current_time failed",
+        ));
+    }
+    Ok(time as _)
+}
+
+/// A wrapped version of `teaclave_random_bytes`
+pub fn random_bytes(buf: &mut [u8]) -> Result<()> {
+    let rv = unsafe { teaclave_random_bytes(buf.as_mut_ptr() as _, buf.len() as _) };
+    if rv == -1 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "teaclave_random_bytes failed",
+        ));
+    }
+    Ok(())
+}
+
 impl std::ops::Drop for TeaclaveContextFile {
     fn drop(&mut self) {
         unsafe { teaclave_close_file(self.handle) };
@@ -188,4 +265,23 @@ extern "C" {
     /// file handler, -1 if error occurs
     pub fn teaclave_open_input(fid: *mut c_char) -> c_int;
 
+    /// Current time from the runtime, which never goes backwards in a task
+    ///
+    /// # Return
+    ///
+    /// milliseconds since the Unix epoch, -1 if error occurs
+    pub fn teaclave_current_time() -> i64;
+
+    /// Fill a buffer with random bytes from the enclave
+    ///
+    /// # Arguments
+    ///
+    /// * `out_buf` - the pointer to output buffer
+    /// * `buf_size` - the total size in bytes of the output buffer
+    ///
+    /// # Return
+    ///
+    /// 0 if succeed, -1 otherwise
+    pub fn teaclave_random_bytes(out_buf: *mut c_char, buf_size: c_int) -> c_int;
+
 }
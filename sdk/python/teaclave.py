@@ -25,14 +25,21 @@ authentication service and frontend service) through RPC protocols.
 
 import json
 import base64
+import hashlib
+import logging
 import toml
 import time
 import os
 import ssl
+import threading
+import urllib.request
+from concurrent.futures import ThreadPoolExecutor
 
 import cryptography
 from cryptography import x509
 from cryptography.hazmat.backends import default_backend
+from cryptography.hazmat.primitives.ciphers import aead
+from cryptography.exceptions import InvalidTag
 
 from google.protobuf.json_format import MessageToDict
 from google.protobuf.empty_pb2 import Empty
@@ -49,15 +56,34 @@ from teaclave_authentication_service_grpc import TeaclaveAuthenticationApiStub
 from teaclave_frontend_service_grpc import TeaclaveFrontendStub
 from teaclave_common_pb2 import TaskStatus, FileCryptoInfo
 
-from typing import Tuple, Dict, List, Any
+from typing import Tuple, Dict, List, Any, Callable, Union
 
 __all__ = [
     'FrontendService', 'AuthenticationService', 'FunctionArgument',
-    'FunctionInput', 'FunctionOutput', 'OwnerList', 'DataMap'
+    'FunctionInput', 'FunctionOutput', 'OwnerList', 'DataMap', 'FileTransfer',
+    'InputFile', 'OutputFile', 'UploadedInput', 'TransferProgress',
+    'encryption_context'
 ]
 
 Metadata = Dict[str, str]
 
+# API version of the frontend service the SDK is built for.
+API_VERSION = 1
+
+_logger = logging.getLogger(__name__)
+
+
+def _warn_if_deprecated(method: str, metadata):
+    # Responses to deprecated RPCs tell the replacement and the version
+    # removing them.
+    if not metadata or "deprecation" not in metadata:
+        return
+    sunset = metadata.get("deprecation-sunset", "unknown")
+    replacement = metadata.get("deprecation-replacement", "unknown")
+    _logger.warning(
+        f"{method} is deprecated and will be removed in {sunset}, "
+        f"use {replacement} instead")
+
 
 class Request:
     message = None
@@ -94,9 +120,15 @@ class TeaclaveService:
         self._loop = self._channel._loop
 
     def call_method(self, request):
-        return self._loop.run_until_complete(
-            getattr(self.stub, request.method)(request.message,
-                                               metadata=request.metadata))
+        return self._loop.run_until_complete(self._call(request))
+
+    async def _call(self, request):
+        method = getattr(self.stub, request.method)
+        async with method.open(metadata=request.metadata) as stream:
+            await stream.send_message(request.message, end=True)
+            response = await stream.recv_message()
+            _warn_if_deprecated(request.method, stream.initial_metadata)
+        return response
 
     def __enter__(self):
         return self
@@ -236,12 +268,30 @@ class TeaclaveChannel(Channel):
         except:
             raise TeaclaveException("Failed to verify report signature")
 
-        report = json.loads(report)
-        quote = report['isvEnclaveQuoteBody']
-        quote = base64.b64decode(quote)
+        if ext.get("format") == "azure_jwt":
+            # the report is the encoded header and claims of a token issued by
+            # Microsoft Azure Attestation
+            claims = report.split(b".")[1]
+            claims = base64.urlsafe_b64decode(claims + b"=" *
+                                              (-len(claims) % 4))
+            claims = json.loads(claims)
+            if claims.get("x-ms-attestation-type") != "sgx":
+                raise TeaclaveException(
+                    "Failed to verify the attestation type of the token")
+
+            report_data = bytes.fromhex(claims["x-ms-sgx-report-data"])
+            mr_enclave = claims["x-ms-sgx-mrenclave"].lower()
+            mr_signer = claims["x-ms-sgx-mrsigner"].lower()
+        else:
+            report = json.loads(report)
+            quote = report['isvEnclaveQuoteBody']
+            quote = base64.b64decode(quote)
+
+            # get report_data, mr_enclave and mr_signer from the quote
+            report_data = quote[368:368 + 64]
+            mr_enclave = quote[112:112 + 32].hex()
+            mr_signer = quote[176:176 + 32].hex()
 
-        # get report_data from the quote
-        report_data = quote[368:368 + 64]
         # get EC pub key from the certificate
         pub_key = cert.public_key().public_bytes(
             cryptography.hazmat.primitives.serialization.Encoding.X962,
@@ -255,10 +305,6 @@ class TeaclaveChannel(Channel):
                 "Failed to verify the certificate agaist the report data in the quote"
             )
 
-        # get mr_enclave and mr_signer from the quote
-        mr_enclave = quote[112:112 + 32].hex()
-        mr_signer = quote[176:176 + 32].hex()
-
         # get enclave_info
         try:
             enclave_info = toml.load(enclave_info_path)
@@ -315,15 +361,21 @@ class FunctionArgument:
         allow_overwrite: If allow_overwrite flag is set to be true. The service
                          will allow the task creator to overwrite the arguement
                          value when creating tasks.
+        value_type: Type of the argument value, one of "string", "integer",
+                    "number", "boolean", "array" and "object". The default
+                    value of a typed argument other than "string" is a JSON
+                    text. The default is "" which accepts any value.
     """
 
     def __init__(self,
                  key: str,
                  default_value: str = "",
-                 allow_overwrite=True):
+                 allow_overwrite=True,
+                 value_type: str = ""):
         self.message = fe.FunctionArgument(key=key,
                                            default_value=default_value,
-                                           allow_overwrite=allow_overwrite)
+                                           allow_overwrite=allow_overwrite,
+                                           value_type=value_type)
 
 
 class OwnerList:
@@ -369,6 +421,36 @@ class CryptoInfo:
                                       iv=bytes(iv))
 
 
+class OutputPolicy:
+    """Policy applied to a fusion output before it is encrypted and uploaded.
+    Outputs are CSV records with fields separated by commas.
+    """
+
+    def __init__(self, message):
+        self.message = message
+
+    @staticmethod
+    def min_row_count(min_rows: int, has_headers: bool = False):
+        """Refuse to release outputs with fewer than min_rows records."""
+        return OutputPolicy(
+            fe.OutputPolicy(min_row_count=fe.MinRowCountPolicy(
+                min_rows=min_rows, has_headers=has_headers)))
+
+    @staticmethod
+    def laplace_noise(columns: List[int],
+                      epsilon: float,
+                      sensitivity: float,
+                      has_headers: bool = False):
+        """Add noise drawn from Laplace(0, sensitivity / epsilon) to the
+        numbers in the columns, counted from 0."""
+        return OutputPolicy(
+            fe.OutputPolicy(laplace_noise=fe.LaplaceNoisePolicy(
+                columns=columns,
+                epsilon=epsilon,
+                sensitivity=sensitivity,
+                has_headers=has_headers)))
+
+
 class UserRegisterRequest(Request):
 
     def __init__(self, metadata: Metadata, user_id: str, user_password: str,
@@ -393,10 +475,16 @@ class UserUpdateRequest(Request):
 
 class UserLoginRequest(Request):
 
-    def __init__(self, user_id: str, user_password: str):
+    def __init__(self,
+                 user_id: str,
+                 user_password: str,
+                 provider: str = "",
+                 totp_code: str = ""):
         super().__init__("UserLogin", auth.UserLoginResponse)
         self.message = auth.UserLoginRequest(id=user_id,
-                                             password=user_password)
+                                             password=user_password,
+                                             provider=provider,
+                                             totp_code=totp_code)
 
 
 class UserChangePasswordRequest(Request):
@@ -414,6 +502,27 @@ class ResetUserPasswordRequest(Request):
         self.message = auth.ResetUserPasswordRequest(id=user_id)
 
 
+class ChangePasswordRequest(Request):
+
+    def __init__(self,
+                 user_id: str,
+                 old_password: str,
+                 new_password: str,
+                 totp_code: str = ""):
+        super().__init__("ChangePassword", Empty)
+        self.message = auth.ChangePasswordRequest(id=user_id,
+                                                  old_password=old_password,
+                                                  new_password=new_password,
+                                                  totp_code=totp_code)
+
+
+class ForcePasswordResetRequest(Request):
+
+    def __init__(self, metadata: Metadata, user_id: str):
+        super().__init__("ForcePasswordReset", Empty, metadata)
+        self.message = auth.ForcePasswordResetRequest(id=user_id)
+
+
 class DeleteUserRequest(Request):
 
     def __init__(self, metadata: Metadata, user_id: str):
@@ -428,6 +537,21 @@ class ListUsersRequest(Request):
         self.message = auth.ListUsersRequest(id=user_id)
 
 
+class GenerateTotpSecretRequest(Request):
+
+    def __init__(self, metadata: Metadata, code: str):
+        super().__init__("GenerateTotpSecret",
+                         auth.GenerateTotpSecretResponse, metadata)
+        self.message = auth.GenerateTotpSecretRequest(code=code)
+
+
+class VerifyTotpRequest(Request):
+
+    def __init__(self, metadata: Metadata, code: str):
+        super().__init__("VerifyTotp", auth.VerifyTotpResponse, metadata)
+        self.message = auth.VerifyTotpRequest(code=code)
+
+
 class AuthenticationService(TeaclaveService):
     """
     Establish trusted channel with the authentication service and provide
@@ -499,23 +623,34 @@ class AuthenticationService(TeaclaveService):
         except Exception as e:
             raise TeaclaveException(f"Failed to update user  {str(e)}")
 
-    def user_login(self, user_id: str, user_password: str) -> str:
+    def user_login(self,
+                   user_id: str,
+                   user_password: str,
+                   provider: str = "",
+                   totp_code: str = "") -> str:
         """Login and get a session token.
 
         Args:
 
-            user_id: User ID.
-            user_password: Password.
+            user_id: User ID, optional with an ID token of "oidc".
+            user_password: Password, or the ID token of "oidc".
+            provider: Identity provider, e.g., "ldap" or "oidc".
+            totp_code: TOTP code, or a recovery code, of users enrolled in
+                TOTP.
 
         Returns:
 
             str: User login token.
         """
         self._channel.check_channel()
-        request = UserLoginRequest(user_id, user_password)
+        request = UserLoginRequest(user_id, user_password, provider,
+                                   totp_code)
         try:
             response = self.call_method(request)
-            self.metadata = {"id": user_id, "token": response.token}
+            self.metadata = {
+                "id": response.id or user_id,
+                "token": response.token
+            }
             return response.token
         except Exception as e:
             raise TeaclaveException(f"Failed to login user  {str(e)}")
@@ -557,6 +692,49 @@ class AuthenticationService(TeaclaveService):
             reason = str(e)
             raise TeaclaveException(f"Failed to reset password  {reason}")
 
+    def change_password(self,
+                        user_id: str,
+                        old_password: str,
+                        new_password: str,
+                        totp_code: str = ""):
+        """Change password with the current one, without logging in, e.g.,
+        when the password has expired or a reset is forced.
+
+        Args:
+
+            user_id: User ID.
+            old_password: Current password.
+            new_password: New password.
+            totp_code: TOTP code, or a recovery code, of users enrolled in
+                TOTP.
+        """
+        self._channel.check_channel()
+        request = ChangePasswordRequest(user_id, old_password, new_password,
+                                        totp_code)
+        try:
+            response = self.call_method(request)
+            return response
+        except Exception as e:
+            raise TeaclaveException(f"Failed to change password  {str(e)}")
+
+    def force_password_reset(self, user_id: str):
+        """Require a managed user to change the password before logging in
+        again, which revokes the tokens of the user.
+
+        Args:
+
+            user_id: User ID.
+        """
+        self.check_channel()
+        self.check_metadata()
+        request = ForcePasswordResetRequest(self.metadata, user_id)
+        try:
+            response = self.call_method(request)
+            return response
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to force password reset  {reason}")
+
     def delete_user(self, user_id: str) -> str:
         """Delete a user.
 
@@ -595,6 +773,50 @@ class AuthenticationService(TeaclaveService):
             reason = str(e)
             raise TeaclaveException(f"Failed to list user ({reason})")
 
+    def generate_totp_secret(self, code: str = "") -> Tuple[str, str]:
+        """Generate a TOTP secret, enrolled once a code of it is verified
+        with verify_totp.
+
+        Args:
+
+            code: TOTP code or recovery code, to replace the secret enrolled.
+
+        Returns:
+
+            Tuple[str, str]: The secret in base32 and its otpauth URI.
+        """
+        self.check_channel()
+        self.check_metadata()
+        request = GenerateTotpSecretRequest(self.metadata, code)
+        try:
+            response = self.call_method(request)
+            return (response.secret, response.uri)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to generate TOTP secret ({reason})")
+
+    def verify_totp(self, code: str) -> List[str]:
+        """Verify a TOTP code, enrolling the secret generated if any.
+
+        Args:
+
+            code: TOTP code.
+
+        Returns:
+
+            List[str]: The recovery codes if the secret is enrolled.
+        """
+        self.check_channel()
+        self.check_metadata()
+        request = VerifyTotpRequest(self.metadata, code)
+        try:
+            response = self.call_method(request)
+            return list(response.recovery_codes)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to verify TOTP ({reason})")
+
 
 class RegisterFunctionRequest(Request):
 
@@ -602,7 +824,10 @@ class RegisterFunctionRequest(Request):
                  executor_type: str, public: bool, payload: List[int],
                  arguments: List[FunctionArgument],
                  inputs: List[FunctionInput], outputs: List[FunctionOutput],
-                 user_allowlist: List[str], usage_quota: int):
+                 user_allowlist: List[str], usage_quota: int, version: str,
+                 tags: List[str], profiling: bool,
+                 payload_crypto_info: CryptoInfo = None,
+                 executor_features: Dict[str, str] = {}):
         super().__init__("RegisterFunction", fe.RegisterFunctionResponse,
                          metadata)
         arguments = [x.message for x in arguments]
@@ -619,7 +844,43 @@ class RegisterFunctionRequest(Request):
             inputs=inputs,
             outputs=outputs,
             user_allowlist=user_allowlist,
-            usage_quota=usage_quota)
+            usage_quota=usage_quota,
+            version=version,
+            tags=tags,
+            profiling=profiling,
+            payload_crypto_info=payload_crypto_info.message
+            if payload_crypto_info else None,
+            executor_features=executor_features)
+
+
+class BeginFunctionUploadRequest(Request):
+
+    def __init__(self, metadata: Metadata, total_size: int, sha256: str):
+        super().__init__("BeginFunctionUpload",
+                         fe.BeginFunctionUploadResponse, metadata)
+        self.message = fe.BeginFunctionUploadRequest(total_size=total_size,
+                                                     sha256=sha256)
+
+
+class UploadFunctionChunkRequest(Request):
+
+    def __init__(self, metadata: Metadata, upload_id: str, offset: int,
+                 data: bytes):
+        super().__init__("UploadFunctionChunk",
+                         fe.UploadFunctionChunkResponse, metadata)
+        self.message = fe.UploadFunctionChunkRequest(upload_id=upload_id,
+                                                     offset=offset,
+                                                     data=data)
+
+
+class CommitFunctionRequest(Request):
+
+    def __init__(self, metadata: Metadata, upload_id: str,
+                 function: RegisterFunctionRequest):
+        super().__init__("CommitFunction", fe.RegisterFunctionResponse,
+                         metadata)
+        self.message = fe.CommitFunctionRequest(upload_id=upload_id,
+                                                function=function.message)
 
 
 class UpdateFunctionRequest(Request):
@@ -647,11 +908,28 @@ class ListFunctionsRequest(Request):
         self.message = fe.ListFunctionsRequest(user_id=user_id)
 
 
+class SearchFunctionsRequest(Request):
+
+    def __init__(self, metadata: Metadata, name: str, tag: str, owner: str,
+                 offset: int, limit: int):
+        super().__init__("SearchFunctions", fe.SearchFunctionsResponse,
+                         metadata)
+        self.message = fe.SearchFunctionsRequest(name=name,
+                                                 tag=tag,
+                                                 owner=owner,
+                                                 offset=offset,
+                                                 limit=limit)
+
+
 class DeleteFunctionRequest(Request):
 
-    def __init__(self, metadata: Metadata, function_id: str):
-        super().__init__("ListFunctions", Empty, metadata)
-        self.message = fe.DeleteFunctionRequest(function_id=function_id)
+    def __init__(self,
+                 metadata: Metadata,
+                 function_id: str,
+                 cascade: bool = False):
+        super().__init__("DeleteFunction", Empty, metadata)
+        self.message = fe.DeleteFunctionRequest(function_id=function_id,
+                                                cascade=cascade)
 
 
 class DisableFunctionRequest(Request):
@@ -676,6 +954,19 @@ class GetFunctionUsageStatsRequest(Request):
         self.message = fe.GetFunctionUsageStatsRequest(function_id=function_id)
 
 
+class QueryFunctionUsageRecordsRequest(Request):
+
+    def __init__(self, metadata: Metadata, function_id: str,
+                 start_time: int, end_time: int, limit: int):
+        super().__init__("QueryFunctionUsageRecords",
+                         fe.QueryFunctionUsageRecordsResponse, metadata)
+        self.message = fe.QueryFunctionUsageRecordsRequest(
+            function_id=function_id,
+            start_time=start_time,
+            end_time=end_time,
+            limit=limit)
+
+
 class RegisterInputFileRequest(Request):
 
     def __init__(self, metadata: Metadata, url: str, cmac: List[int],
@@ -688,11 +979,19 @@ class RegisterInputFileRequest(Request):
 
 class RegisterOutputFileRequest(Request):
 
-    def __init__(self, metadata: Metadata, url: str, crypto_info: CryptoInfo):
+    def __init__(self,
+                 metadata: Metadata,
+                 url: str,
+                 crypto_info: CryptoInfo,
+                 bind_context: bool = False,
+                 retention_secs: int = 0):
         super().__init__("RegisterOutputFile", fe.RegisterOutputFileResponse,
                          metadata)
         self.message = fe.RegisterOutputFileRequest(
-            url=url, crypto_info=crypto_info.message)
+            url=url,
+            crypto_info=crypto_info.message,
+            bind_context=bind_context,
+            retention_secs=retention_secs)
 
 
 class RegisterInputFromOutputRequest(Request):
@@ -705,10 +1004,15 @@ class RegisterInputFromOutputRequest(Request):
 
 class RegisterFusionOutputRequest(Request):
 
-    def __init__(self, metadata: Metadata, owner_list: List[str] = []):
+    def __init__(self,
+                 metadata: Metadata,
+                 owner_list: List[str] = [],
+                 output_policies: List[OutputPolicy] = []):
         super().__init__("RegisterFusionOutput",
                          fe.RegisterFusionOutputResponse, metadata)
-        self.message = fe.RegisterFusionOutputRequest(owner_list=owner_list)
+        self.message = fe.RegisterFusionOutputRequest(
+            owner_list=owner_list,
+            output_policies=[p.message for p in output_policies])
 
 
 class UpdateInputFileRequest(Request):
@@ -732,7 +1036,14 @@ class CreateTaskRequest(Request):
     def __init__(self, metadata: Metadata, function_id: str,
                  function_arguments: Dict[str, Any], executor: str,
                  inputs_ownership: List[OwnerList],
-                 outputs_ownership: List[OwnerList]):
+                 outputs_ownership: List[OwnerList], purpose: str,
+                 typed_arguments: Tuple[str, bytes] = None,
+                 approval_expiry_secs: int = 0,
+                 schedule: str = "",
+                 staging_timeout_secs: int = 0,
+                 execution_timeout_secs: int = 0,
+                 upload_timeout_secs: int = 0,
+                 resource_limits: Tuple[int, int, int] = None):
         super().__init__("CreateTask", fe.CreateTaskResponse, metadata)
         inputs_ownership = [x.message for x in inputs_ownership]
         outputs_ownership = [x.message for x in outputs_ownership]
@@ -742,7 +1053,23 @@ class CreateTaskRequest(Request):
             function_arguments=function_arguments,
             executor=executor,
             inputs_ownership=inputs_ownership,
-            outputs_ownership=outputs_ownership)
+            outputs_ownership=outputs_ownership,
+            purpose=purpose,
+            approval_expiry_secs=approval_expiry_secs,
+            schedule=schedule,
+            staging_timeout_secs=staging_timeout_secs,
+            execution_timeout_secs=execution_timeout_secs,
+            upload_timeout_secs=upload_timeout_secs)
+        if resource_limits is not None:
+            timeout_secs, max_memory_bytes, max_output_bytes = resource_limits
+            self.message.resource_limits.CopyFrom(
+                fe.ResourceLimits(timeout_secs=timeout_secs,
+                                  max_memory_bytes=max_memory_bytes,
+                                  max_output_bytes=max_output_bytes))
+        if typed_arguments is not None:
+            content_type, payload = typed_arguments
+            self.message.typed_function_arguments.CopyFrom(
+                fe.TypedArguments(content_type=content_type, payload=payload))
 
 
 class AssignDataRequest(Request):
@@ -759,15 +1086,32 @@ class AssignDataRequest(Request):
 
 class ApproveTaskRequest(Request):
 
-    def __init__(self, metadata: Metadata, task_id: str):
+    def __init__(self,
+                 metadata: Metadata,
+                 task_id: str,
+                 comment: str = "",
+                 input_cmacs: Dict[str, List[int]] = None):
         super().__init__("ApproveTask", Empty, metadata)
-        self.message = fe.ApproveTaskRequest(task_id=task_id)
+        input_cmacs = [
+            fe.InputCmac(data_name=name, cmac=bytes(cmac))
+            for name, cmac in (input_cmacs or {}).items()
+        ]
+        self.message = fe.ApproveTaskRequest(task_id=task_id,
+                                             comment=comment,
+                                             input_cmacs=input_cmacs)
+
+
+class RejectTaskRequest(Request):
+
+    def __init__(self, metadata: Metadata, task_id: str, reason: str = ""):
+        super().__init__("RejectTask", Empty, metadata)
+        self.message = fe.RejectTaskRequest(task_id=task_id, reason=reason)
 
 
 class InvokeTaskRequest(Request):
 
     def __init__(self, metadata: Metadata, task_id: str):
-        super().__init__("InvokeTask", Empty, metadata)
+        super().__init__("InvokeTask", fe.InvokeTaskResponse, metadata)
         self.message = fe.InvokeTaskRequest(task_id=task_id)
 
 
@@ -785,6 +1129,32 @@ class GetTaskRequest(Request):
         self.message = fe.GetTaskRequest(task_id=task_id)
 
 
+class GetConsentRecordsRequest(Request):
+
+    def __init__(self, metadata: Metadata, task_id: str):
+        super().__init__("GetConsentRecords", fe.GetConsentRecordsResponse,
+                         metadata)
+        self.message = fe.GetConsentRecordsRequest(task_id=task_id)
+
+
+class GetTaskLogRequest(Request):
+
+    def __init__(self, metadata: Metadata, task_id: str):
+        super().__init__("GetTaskLog", fe.GetTaskLogResponse, metadata)
+        self.message = fe.GetTaskLogRequest(task_id=task_id)
+
+
+class RegisterWebhookSinkRequest(Request):
+
+    def __init__(self, metadata: Metadata, task_id: str, url: str,
+                 secret: str):
+        super().__init__("RegisterWebhookSink", fe.RegisterWebhookSinkResponse,
+                         metadata)
+        self.message = fe.RegisterWebhookSinkRequest(task_id=task_id,
+                                                     url=url,
+                                                     secret=secret)
+
+
 class QueryAuditLogsRequest(Request):
 
     def __init__(self, metadata: Metadata, message: str, limit: int):
@@ -792,6 +1162,209 @@ class QueryAuditLogsRequest(Request):
         self.message = fe.QueryAuditLogsReqeust(message=message, limit=limit)
 
 
+class ExportAuditLogsRequest(Request):
+
+    def __init__(self, metadata: Metadata, query: str, max_rows: int,
+                 output_id: str, format: str, columns: List[str]):
+        super().__init__("ExportAuditLogs", fe.ExportAuditLogsResponse,
+                         metadata)
+        self.message = fe.ExportAuditLogsRequest(query=query,
+                                                 max_rows=max_rows,
+                                                 output_id=output_id,
+                                                 format=format,
+                                                 columns=columns)
+
+
+class RebuildAuditIndexRequest(Request):
+
+    def __init__(self, metadata: Metadata):
+        super().__init__("RebuildAuditIndex", Empty, metadata)
+        self.message = fe.RebuildAuditIndexRequest()
+
+
+class GetAuditIndexStatusRequest(Request):
+
+    def __init__(self, metadata: Metadata):
+        super().__init__("GetAuditIndexStatus", fe.GetAuditIndexStatusResponse,
+                         metadata)
+        self.message = fe.GetAuditIndexStatusRequest()
+
+
+class SetUserQuotaRequest(Request):
+
+    def __init__(self, metadata: Metadata, user_id: str,
+                 max_concurrent_tasks: int, max_registered_data: int,
+                 max_requests_per_minute: int):
+        super().__init__("SetUserQuota", Empty, metadata)
+        quota = fe.UserQuota(max_concurrent_tasks=max_concurrent_tasks,
+                             max_registered_data=max_registered_data,
+                             max_requests_per_minute=max_requests_per_minute)
+        self.message = fe.SetUserQuotaRequest(user_id=user_id, quota=quota)
+
+
+class GetUserQuotaRequest(Request):
+
+    def __init__(self, metadata: Metadata, user_id: str):
+        super().__init__("GetUserQuota", fe.GetUserQuotaResponse, metadata)
+        self.message = fe.GetUserQuotaRequest(user_id=user_id)
+
+
+class GrantDebugErrorsRequest(Request):
+
+    def __init__(self, metadata: Metadata, user_id: str, duration_secs: int):
+        super().__init__("GrantDebugErrors", fe.GrantDebugErrorsResponse,
+                         metadata)
+        self.message = fe.GrantDebugErrorsRequest(user_id=user_id,
+                                                  duration_secs=duration_secs)
+
+
+class GetMetricsRequest(Request):
+
+    def __init__(self, metadata: Metadata):
+        super().__init__("GetMetrics", fe.GetMetricsResponse, metadata)
+        self.message = fe.GetMetricsRequest()
+
+
+class GetApiVersionRequest(Request):
+
+    def __init__(self, metadata: Metadata):
+        super().__init__("GetApiVersion", fe.GetApiVersionResponse, metadata)
+        self.message = fe.GetApiVersionRequest(client_api_version=API_VERSION)
+
+
+class ManagePolicyRequest(Request):
+
+    def __init__(self, metadata: Metadata, action: int, ptype: str,
+                 values: List[str]):
+        super().__init__("ManagePolicy", fe.ManagePolicyResponse, metadata)
+        rule = fe.PolicyRule(ptype=ptype, values=values)
+        self.message = fe.ManagePolicyRequest(action=action, rule=rule)
+
+
+class SetUserAttributesRequest(Request):
+
+    def __init__(self, metadata: Metadata, user_id: str,
+                 attributes: Dict[str, str]):
+        super().__init__("SetUserAttributes", Empty, metadata)
+        self.message = fe.SetUserAttributesRequest(user_id=user_id,
+                                                   attributes=attributes)
+
+
+class GetUserAttributesRequest(Request):
+
+    def __init__(self, metadata: Metadata, user_id: str):
+        super().__init__("GetUserAttributes", fe.GetUserAttributesResponse,
+                         metadata)
+        self.message = fe.GetUserAttributesRequest(user_id=user_id)
+
+
+class SetDataAttributesRequest(Request):
+
+    def __init__(self, metadata: Metadata, data_id: str,
+                 attributes: Dict[str, str]):
+        super().__init__("SetDataAttributes", Empty, metadata)
+        self.message = fe.SetDataAttributesRequest(data_id=data_id,
+                                                   attributes=attributes)
+
+
+class GetDataAttributesRequest(Request):
+
+    def __init__(self, metadata: Metadata, data_id: str):
+        super().__init__("GetDataAttributes", fe.GetDataAttributesResponse,
+                         metadata)
+        self.message = fe.GetDataAttributesRequest(data_id=data_id)
+
+
+class SetInputAccessPolicyRequest(Request):
+
+    def __init__(self, metadata: Metadata, data_id: str,
+                 function_ids: List[str], function_owners: List[str]):
+        super().__init__("SetInputAccessPolicy", Empty, metadata)
+        self.message = fe.SetInputAccessPolicyRequest(
+            data_id=data_id,
+            function_ids=function_ids,
+            function_owners=function_owners)
+
+
+class DeleteDataRequest(Request):
+
+    def __init__(self, metadata: Metadata, data_id: str, cascade: bool):
+        super().__init__("DeleteData", Empty, metadata)
+        self.message = fe.DeleteDataRequest(data_id=data_id, cascade=cascade)
+
+
+class QueryDataLineageRequest(Request):
+
+    def __init__(self, metadata: Metadata, data_id: str):
+        super().__init__("QueryDataLineage", fe.QueryDataLineageResponse,
+                         metadata)
+        self.message = fe.QueryDataLineageRequest(data_id=data_id)
+
+
+class SetNotificationPreferencesRequest(Request):
+
+    def __init__(self,
+                 metadata: Metadata,
+                 email: str = "",
+                 slack_member_id: str = ""):
+        super().__init__("SetNotificationPreferences", Empty, metadata)
+        self.message = fe.SetNotificationPreferencesRequest(
+            email=email, slack_member_id=slack_member_id)
+
+
+class DecommissionStorageRequest(Request):
+
+    def __init__(self, metadata: Metadata, replacement_address: str):
+        super().__init__("DecommissionStorage", Empty, metadata)
+        self.message = fe.DecommissionStorageRequest(
+            replacement_address=replacement_address)
+
+
+class GetStorageDecommissionStatusRequest(Request):
+
+    def __init__(self, metadata: Metadata):
+        super().__init__("GetStorageDecommissionStatus",
+                         fe.GetStorageDecommissionStatusResponse, metadata)
+        self.message = fe.GetStorageDecommissionStatusRequest()
+
+
+class PromoteStorageReplicaRequest(Request):
+
+    def __init__(self, metadata: Metadata, follower_address: str):
+        super().__init__("PromoteStorageReplica",
+                         fe.PromoteStorageReplicaResponse, metadata)
+        self.message = fe.PromoteStorageReplicaRequest(
+            follower_address=follower_address)
+
+
+class DeclareMaintenanceWindowRequest(Request):
+
+    def __init__(self, metadata: Metadata, starts_at: int, ends_at: int,
+                 mode: str, reason: str):
+        super().__init__("DeclareMaintenanceWindow",
+                         fe.DeclareMaintenanceWindowResponse, metadata)
+        self.message = fe.DeclareMaintenanceWindowRequest(
+            starts_at=starts_at,
+            ends_at=ends_at,
+            mode=fe.MaintenanceMode.Value(mode),
+            reason=reason)
+
+
+class CancelMaintenanceWindowRequest(Request):
+
+    def __init__(self, metadata: Metadata, window_id: str):
+        super().__init__("CancelMaintenanceWindow", Empty, metadata)
+        self.message = fe.CancelMaintenanceWindowRequest(window_id=window_id)
+
+
+class GetServiceInfoRequest(Request):
+
+    def __init__(self, metadata: Metadata):
+        super().__init__("GetServiceInfo", fe.GetServiceInfoResponse,
+                         metadata)
+        self.message = fe.GetServiceInfoRequest()
+
+
 class FrontendService(TeaclaveService):
     """Establish trusted channel with the frontend service and provide
     clients to send request through RPC.
@@ -826,13 +1399,27 @@ class FrontendService(TeaclaveService):
         outputs: List[FunctionOutput] = [],
         user_allowlist: List[str] = [],
         usage_quota: int = -1,
+        version: str = "",
+        tags: List[str] = [],
+        profiling: bool = False,
+        payload_crypto_info: CryptoInfo = None,
+        executor_features: Dict[str, str] = {},
     ):
+        """Register a function.
+
+        The payload may be encrypted with payload_crypto_info of an in-memory
+        schema such as aes-gcm-128, and is only decrypted by the execution
+        service. executor_features lists the features the executors need with
+        the least versions, e.g., {"python": "2.7"}.
+        """
         self.check_metadata()
         self.check_channel()
         request = RegisterFunctionRequest(self.metadata, name, description,
                                           executor_type, public, payload,
                                           arguments, inputs, outputs,
-                                          user_allowlist, usage_quota)
+                                          user_allowlist, usage_quota, version,
+                                          tags, profiling, payload_crypto_info,
+                                          executor_features)
         try:
             response = self.call_method(request)
             return response.function_id
@@ -840,6 +1427,58 @@ class FrontendService(TeaclaveService):
             reason = str(e)
             raise TeaclaveException(f"Failed to register function ({reason})")
 
+    def register_function_in_chunks(
+        self,
+        name: str,
+        description: str,
+        executor_type: str,
+        public: bool = True,
+        payload: List[int] = [],
+        arguments: List[FunctionArgument] = [],
+        inputs: List[FunctionInput] = [],
+        outputs: List[FunctionOutput] = [],
+        user_allowlist: List[str] = [],
+        usage_quota: int = -1,
+        version: str = "",
+        tags: List[str] = [],
+        profiling: bool = False,
+        payload_crypto_info: CryptoInfo = None,
+        executor_features: Dict[str, str] = {},
+    ):
+        """Register a function with a payload too large for one request.
+
+        The payload is uploaded in the chunks allowed by the service and
+        verified against its SHA-256 digest before the function is registered.
+        """
+        self.check_metadata()
+        self.check_channel()
+        payload = bytes(payload)
+        function = RegisterFunctionRequest(self.metadata, name, description,
+                                           executor_type, public, [],
+                                           arguments, inputs, outputs,
+                                           user_allowlist, usage_quota,
+                                           version, tags, profiling,
+                                           payload_crypto_info,
+                                           executor_features)
+        try:
+            request = BeginFunctionUploadRequest(
+                self.metadata, len(payload),
+                hashlib.sha256(payload).hexdigest())
+            response = self.call_method(request)
+            upload_id = response.upload_id
+            offset = 0
+            while offset < len(payload):
+                chunk = payload[offset:offset + response.max_chunk_size]
+                request = UploadFunctionChunkRequest(self.metadata, upload_id,
+                                                     offset, chunk)
+                offset = self.call_method(request).received_size
+            request = CommitFunctionRequest(self.metadata, upload_id,
+                                            function)
+            return self.call_method(request).function_id
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to register function ({reason})")
+
     def update_function(
         self,
         function_id: str,
@@ -879,6 +1518,24 @@ class FrontendService(TeaclaveService):
                              preserving_proto_field_name=True,
                              use_integers_for_enums=True)
 
+    def search_functions(self,
+                         name: str = "",
+                         tag: str = "",
+                         owner: str = "",
+                         offset: int = 0,
+                         limit: int = 0):
+        self.check_metadata()
+        self.check_channel()
+        request = SearchFunctionsRequest(self.metadata, name, tag, owner,
+                                         offset, limit)
+        try:
+            response = self.call_method(request)
+        except Exception as e:
+            raise TeaclaveException(f"Failed to search functions ({str(e)})")
+        return MessageToDict(response,
+                             preserving_proto_field_name=True,
+                             use_integers_for_enums=True)
+
     def get_function(self, function_id: str):
         self.check_metadata()
         self.check_channel()
@@ -902,10 +1559,35 @@ class FrontendService(TeaclaveService):
             raise TeaclaveException(
                 f"Failed to get function usage statistics ({reason})")
 
-    def delete_function(self, function_id: str):
+    def query_function_usage_records(self,
+                                     function_id: str,
+                                     start_time: int = 0,
+                                     end_time: int = 0,
+                                     limit: int = 0):
+        """Usage records of the runs of the function ended in
+        [start_time, end_time), in seconds since the UNIX epoch (unbounded if
+        0), for billing."""
         self.check_metadata()
         self.check_channel()
-        request = DeleteFunctionRequest(self.metadata, function_id)
+        request = QueryFunctionUsageRecordsRequest(self.metadata, function_id,
+                                                   start_time, end_time, limit)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True,
+                                 use_integers_for_enums=True).get(
+                                     "records", [])
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to query function usage records ({reason})")
+
+    def delete_function(self, function_id: str, cascade: bool = False):
+        """Delete the function, which is refused while pending tasks use it,
+        unless cascade cancels the tasks."""
+        self.check_metadata()
+        self.check_channel()
+        request = DeleteFunctionRequest(self.metadata, function_id, cascade)
         try:
             response = self.call_method(request)
             return response
@@ -938,15 +1620,38 @@ class FrontendService(TeaclaveService):
             raise TeaclaveException(
                 f"Failed to register input file ({reason})")
 
-    def register_output_file(self, url: str, schema: str, key: List[int],
-                             iv: List[int]):
-        self.check_metadata()
-        self.check_channel()
-        request = RegisterOutputFileRequest(self.metadata, url,
-                                            CryptoInfo(schema, key, iv))
-        try:
-            response = self.call_method(request)
-            return response.data_id
+    def register_output_file(self,
+                             url: str,
+                             schema: str,
+                             key: List[int],
+                             iv: List[int],
+                             bind_context: bool = False,
+                             retention_secs: int = 0):
+        """Register an output data.
+
+        Args:
+
+            url (str): URL the output is uploaded to.
+            schema (str): Encryption schema of the output.
+            key (List[int]): Key of the output.
+            iv (List[int]): IV of the output.
+            bind_context (bool): Bind the content to the task and slot
+                producing it.
+            retention_secs (int): Seconds the output is kept for before it is
+                deleted with its key, 0 for ever.
+
+        Returns:
+
+            str: ExternalID of output data
+        """
+        self.check_metadata()
+        self.check_channel()
+        request = RegisterOutputFileRequest(self.metadata, url,
+                                            CryptoInfo(schema, key, iv),
+                                            bind_context, retention_secs)
+        try:
+            response = self.call_method(request)
+            return response.data_id
         except Exception as e:
             reason = str(e)
             raise TeaclaveException(
@@ -970,19 +1675,23 @@ class FrontendService(TeaclaveService):
         response = self.call_method(request)
         return response.data_id
 
-    def register_fusion_output(self, owners: List[str] = []):
+    def register_fusion_output(self,
+                               owners: List[str] = [],
+                               output_policies: List[OutputPolicy] = []):
         """Register a fusion output data.
 
         Args:
 
             owners (List[OwnerList], optional): Owners of the output data. Defaults to [].
+            output_policies (List[OutputPolicy], optional): Policies applied in order to the output before it is uploaded. Defaults to [].
         
         Returns:
 
             str: ExternalID of fusion output data
         """
 
-        request = RegisterFusionOutputRequest(self.metadata, owners)
+        request = RegisterFusionOutputRequest(self.metadata, owners,
+                                              output_policies)
         response = self.call_method(request)
         return response.data_id
 
@@ -991,13 +1700,35 @@ class FrontendService(TeaclaveService):
                     function_arguments: Dict[str, Any],
                     executor: str,
                     inputs_ownership: List[OwnerList] = [],
-                    outputs_ownership: List[OwnerList] = []):
+                    outputs_ownership: List[OwnerList] = [],
+                    purpose: str = "",
+                    typed_arguments: Tuple[str, bytes] = None,
+                    approval_expiry_secs: int = 0,
+                    schedule: str = "",
+                    staging_timeout_secs: int = 0,
+                    execution_timeout_secs: int = 0,
+                    upload_timeout_secs: int = 0,
+                    resource_limits: Tuple[int, int, int] = None):
+        # typed_arguments replace function_arguments with a (content type,
+        # payload) pair, e.g., ("application/cbor", cbor2.dumps(arguments)).
+        # A scheduled task, e.g., with schedule "0 2 * * *", runs as a new
+        # task on each match of the cron expression in UTC once invoked.
+        # The timeouts limit the stages of the task in seconds, 0 for no
+        # limit, and the task fails with the stage running out of time.
+        # resource_limits of a Python function are a (timeout_secs,
+        # max_memory_bytes, max_output_bytes) tuple, each 0 for the default
+        # of the platform.
         self.check_metadata()
         self.check_channel()
         function_arguments = json.dumps(function_arguments)
         request = CreateTaskRequest(self.metadata, function_id,
                                     function_arguments, executor,
-                                    inputs_ownership, outputs_ownership)
+                                    inputs_ownership, outputs_ownership,
+                                    purpose, typed_arguments,
+                                    approval_expiry_secs, schedule,
+                                    staging_timeout_secs,
+                                    execution_timeout_secs,
+                                    upload_timeout_secs, resource_limits)
         try:
             response = self.call_method(request)
             return response.task_id
@@ -1017,22 +1748,40 @@ class FrontendService(TeaclaveService):
             raise TeaclaveException(
                 f"Failed to assign data to task ({reason})")
 
-    def approve_task(self, task_id: str):
+    def approve_task(self,
+                     task_id: str,
+                     comment: str = "",
+                     input_cmacs: Dict[str, List[int]] = None):
+        """Approve the task, only if the inputs named in input_cmacs are
+        assigned with files of the cmacs if given."""
         self.check_metadata()
         self.check_channel()
-        request = ApproveTaskRequest(self.metadata, task_id)
+        request = ApproveTaskRequest(self.metadata, task_id, comment,
+                                     input_cmacs)
         try:
             self.call_method(request)
         except Exception as e:
             reason = str(e)
             raise TeaclaveException(f"Failed to approve task ({reason})")
 
+    def reject_task(self, task_id: str, reason: str = ""):
+        self.check_metadata()
+        self.check_channel()
+        request = RejectTaskRequest(self.metadata, task_id, reason)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to reject task ({reason})")
+
     def invoke_task(self, task_id: str):
         self.check_metadata()
         self.check_channel()
         request = InvokeTaskRequest(self.metadata, task_id)
         try:
-            self.call_method(request)
+            response = self.call_method(request)
+            # Staged tasks ahead, and seconds until the task runs (0 if unknown)
+            return response.queue_depth, response.eta_secs
         except Exception as e:
             reason = str(e)
             raise TeaclaveException(f"Failed to invoke task ({reason})")
@@ -1060,6 +1809,42 @@ class FrontendService(TeaclaveService):
             reason = str(e)
             raise TeaclaveException(f"Failed to get task result ({reason})")
 
+    def get_consent_records(self, task_id: str):
+        self.check_metadata()
+        self.check_channel()
+        request = GetConsentRecordsRequest(self.metadata, task_id)
+        try:
+            response = self.call_method(request)
+        except Exception as e:
+            raise TeaclaveException(
+                f"Failed to get consent records ({str(e)})")
+        return MessageToDict(response,
+                             preserving_proto_field_name=True,
+                             use_integers_for_enums=True)
+
+    def get_task_log(self, task_id: str):
+        self.check_metadata()
+        self.check_channel()
+        request = GetTaskLogRequest(self.metadata, task_id)
+        try:
+            response = self.call_method(request)
+        except Exception as e:
+            raise TeaclaveException(f"Failed to get task log ({str(e)})")
+        return MessageToDict(response.log_file,
+                             preserving_proto_field_name=True)
+
+    def register_webhook_sink(self, task_id: str, url: str, secret: str):
+        self.check_metadata()
+        self.check_channel()
+        request = RegisterWebhookSinkRequest(self.metadata, task_id, url,
+                                             secret)
+        try:
+            response = self.call_method(request)
+        except Exception as e:
+            raise TeaclaveException(
+                f"Failed to register webhook sink ({str(e)})")
+        return response.sink_id
+
     def get_task_result(self, task_id: str):
         self.check_metadata()
         self.check_channel()
@@ -1102,6 +1887,34 @@ class FrontendService(TeaclaveService):
                                  use_integers_for_enums=True)
         return base64.b64decode(response["result"]["Ok"]["tags_map"][tag])
 
+    def get_output_artifacts(self, task_id: str):
+        """Get the URL, size, SHA-256 and upload time of each output of a
+        finished task, keyed by the output names."""
+        self.check_metadata()
+        self.check_channel()
+        request = GetTaskRequest(self.metadata, task_id)
+        try:
+            response = self.call_method(request)
+        except Exception as e:
+            raise TeaclaveException(
+                f"Failed to get output artifacts ({str(e)})")
+        if response.status != TaskStatus.Finished:
+            raise TeaclaveException("Task is not finished")
+        response = MessageToDict(response,
+                                 preserving_proto_field_name=True,
+                                 use_integers_for_enums=True)
+        artifacts = response["result"]["Ok"].get("artifacts", {})
+        # 64-bit integers are strings in the JSON mapping of protobuf
+        return {
+            name: {
+                "url": artifact.get("url", ""),
+                "size": int(artifact.get("size", 0)),
+                "sha256": artifact.get("sha256", ""),
+                "uploaded_at": int(artifact.get("uploaded_at", 0)),
+            }
+            for name, artifact in artifacts.items()
+        }
+
     def query_audit_logs(self, message: str, limit: int):
         self.check_metadata()
         self.check_channel()
@@ -1114,3 +1927,626 @@ class FrontendService(TeaclaveService):
         except Exception as e:
             reason = str(e)
             raise TeaclaveException(f"Failed to get audit logs ({reason})")
+
+    def export_audit_logs(self,
+                          query: str,
+                          output_id: str,
+                          max_rows: int = 0,
+                          format: str = "jsonl",
+                          columns: List[str] = []):
+        """Export the logs matching the query to a registered output file
+        as JSON Lines ("jsonl") or CSV ("csv"), with the selected columns or
+        all of them. Returns the number of exported logs."""
+        self.check_metadata()
+        self.check_channel()
+        request = ExportAuditLogsRequest(self.metadata, query, max_rows,
+                                         output_id, format, columns)
+        try:
+            response = self.call_method(request)
+            return response.rows
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to export audit logs ({reason})")
+
+    def rebuild_audit_index(self):
+        """Rebuild the audit index from the saved logs, e.g., if it is
+        corrupted. Check the progress with get_audit_index_status."""
+        self.check_metadata()
+        self.check_channel()
+        request = RebuildAuditIndexRequest(self.metadata)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to rebuild audit index ({reason})")
+
+    def get_audit_index_status(self):
+        self.check_metadata()
+        self.check_channel()
+        request = GetAuditIndexStatusRequest(self.metadata)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True,
+                                 including_default_value_fields=True)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to get audit index status ({reason})")
+
+    def set_user_quota(self,
+                       user_id: str,
+                       max_concurrent_tasks: int = 0,
+                       max_registered_data: int = 0,
+                       max_requests_per_minute: int = 0):
+        """Override the quotas of a user, 0 means unlimited."""
+        self.check_metadata()
+        self.check_channel()
+        request = SetUserQuotaRequest(self.metadata, user_id,
+                                      max_concurrent_tasks,
+                                      max_registered_data,
+                                      max_requests_per_minute)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to set user quota ({reason})")
+
+    def get_user_quota(self, user_id: str):
+        self.check_metadata()
+        self.check_channel()
+        request = GetUserQuotaRequest(self.metadata, user_id)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True,
+                                 including_default_value_fields=True)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to get user quota ({reason})")
+
+    def grant_debug_errors(self, user_id: str, duration_secs: int):
+        """Let a user see detailed internal errors for duration_secs, or
+        revoke the grant if 0. Returns when the grant expires."""
+        self.check_metadata()
+        self.check_channel()
+        request = GrantDebugErrorsRequest(self.metadata, user_id,
+                                          duration_secs)
+        try:
+            response = self.call_method(request)
+            return response.expires_at
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to grant debug errors ({reason})")
+
+    def get_metrics(self):
+        """Get the latency and error rate of RPCs per family and whether
+        they breach their SLO targets."""
+        self.check_metadata()
+        self.check_channel()
+        request = GetMetricsRequest(self.metadata)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True,
+                                 including_default_value_fields=True)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to get metrics ({reason})")
+
+    def get_api_version(self):
+        """Get the API versions served by the frontend service and the
+        features of the deployment. It does not need a login."""
+        self.check_channel()
+        request = GetApiVersionRequest(self.metadata)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True,
+                                 including_default_value_fields=True)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to get API version ({reason})")
+
+    def negotiate_api_version(self):
+        """Check that the frontend service still serves the API version of
+        the SDK, which is best done before other calls, as their messages may
+        have changed.
+
+        Returns:
+
+            dict: API versions and features of the deployment.
+        """
+        info = self.get_api_version()
+        if info["min_api_version"] > API_VERSION:
+            raise TeaclaveException(
+                f"The service requires API version {info['min_api_version']} "
+                f"or later, but the SDK is of {API_VERSION}, upgrade the SDK")
+        if info["api_version"] < API_VERSION:
+            _logger.warning(
+                f"The service is of API version {info['api_version']}, older "
+                f"than {API_VERSION} of the SDK, some calls may be unavailable")
+        return info
+
+    def manage_policy(self,
+                      action: int,
+                      ptype: str = "",
+                      values: List[str] = []):
+        self.check_metadata()
+        self.check_channel()
+        request = ManagePolicyRequest(self.metadata, action, ptype, values)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True).get(
+                                     "rules", [])
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to manage policy ({reason})")
+
+    def list_policy_rules(self):
+        return self.manage_policy(fe.ListRules)
+
+    def add_policy_rule(self, ptype: str, values: List[str]):
+        """Add a rule to the access control policy. For example,
+        ("p", ["rule_auditor", "query_audit_logs"]) allows the role
+        rule_auditor to query audit logs, and ("g", ["Auditor",
+        "rule_auditor"]) grants that role to the Auditor users."""
+        return self.manage_policy(fe.AddRule, ptype, values)
+
+    def remove_policy_rule(self, ptype: str, values: List[str]):
+        return self.manage_policy(fe.RemoveRule, ptype, values)
+
+    def set_user_attributes(self, user_id: str, attributes: Dict[str, str]):
+        self.check_metadata()
+        self.check_channel()
+        request = SetUserAttributesRequest(self.metadata, user_id, attributes)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to set user attributes ({reason})")
+
+    def get_user_attributes(self, user_id: str):
+        self.check_metadata()
+        self.check_channel()
+        request = GetUserAttributesRequest(self.metadata, user_id)
+        try:
+            response = self.call_method(request)
+            return dict(response.attributes)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to get user attributes ({reason})")
+
+    def set_data_attributes(self, data_id: str, attributes: Dict[str, str]):
+        self.check_metadata()
+        self.check_channel()
+        request = SetDataAttributesRequest(self.metadata, data_id, attributes)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to set data attributes ({reason})")
+
+    def get_data_attributes(self, data_id: str):
+        self.check_metadata()
+        self.check_channel()
+        request = GetDataAttributesRequest(self.metadata, data_id)
+        try:
+            response = self.call_method(request)
+            return dict(response.attributes)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to get data attributes ({reason})")
+
+    def set_input_access_policy(self,
+                                data_id: str,
+                                function_ids: List[str] = [],
+                                function_owners: List[str] = []):
+        """Only the functions with the IDs or of the owners can use the input
+        file, and assigning the file to their tasks approves the tasks. Empty
+        lists allow all the functions."""
+        self.check_metadata()
+        self.check_channel()
+        request = SetInputAccessPolicyRequest(self.metadata, data_id,
+                                              function_ids, function_owners)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to set input access policy ({reason})")
+
+    def delete_data(self, data_id: str, cascade: bool = False):
+        """Delete the input or output file, which is refused while pending
+        tasks are assigned it, unless cascade cancels the tasks."""
+        self.check_metadata()
+        self.check_channel()
+        request = DeleteDataRequest(self.metadata, data_id, cascade)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to delete data ({reason})")
+
+    def query_data_lineage(self, data_id: str):
+        """Lineage of the data and all its upstream data, e.g., the tasks
+        which produced them and their inputs."""
+        self.check_metadata()
+        self.check_channel()
+        request = QueryDataLineageRequest(self.metadata, data_id)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True).get(
+                                     "lineage", [])
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to query data lineage ({reason})")
+
+    def set_notification_preferences(self,
+                                     email: str = "",
+                                     slack_member_id: str = ""):
+        """Opt in to the digests of ended tasks, or opt out of a channel
+        with an empty value."""
+        self.check_metadata()
+        self.check_channel()
+        request = SetNotificationPreferencesRequest(self.metadata, email,
+                                                    slack_member_id)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to set notification preferences ({reason})")
+
+    def decommission_storage(self, replacement_address: str):
+        self.check_metadata()
+        self.check_channel()
+        request = DecommissionStorageRequest(self.metadata, replacement_address)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to decommission storage ({reason})")
+
+    def get_storage_decommission_status(self):
+        self.check_metadata()
+        self.check_channel()
+        request = GetStorageDecommissionStatusRequest(self.metadata)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True,
+                                 including_default_value_fields=True)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to get storage decommission status ({reason})")
+
+    def promote_storage_replica(self, follower_address: str):
+        self.check_metadata()
+        self.check_channel()
+        request = PromoteStorageReplicaRequest(self.metadata, follower_address)
+        try:
+            response = self.call_method(request)
+            return response.applied_index
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to promote storage replica ({reason})")
+
+    def declare_maintenance_window(self,
+                                   starts_at: int,
+                                   ends_at: int,
+                                   mode: str = "QueueTasks",
+                                   reason: str = ""):
+        """Declare a maintenance window, in seconds since the UNIX epoch.
+        Tasks invoked in the window are held until it ends with the mode
+        "QueueTasks", or rejected with "RejectTasks"."""
+        self.check_metadata()
+        self.check_channel()
+        request = DeclareMaintenanceWindowRequest(self.metadata, starts_at,
+                                                  ends_at, mode, reason)
+        try:
+            response = self.call_method(request)
+            return response.window_id
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to declare maintenance window ({reason})")
+
+    def cancel_maintenance_window(self, window_id: str):
+        self.check_metadata()
+        self.check_channel()
+        request = CancelMaintenanceWindowRequest(self.metadata, window_id)
+        try:
+            self.call_method(request)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(
+                f"Failed to cancel maintenance window ({reason})")
+
+    def get_service_info(self):
+        """Get the maintenance windows of the service, which users are best
+        told about before they invoke tasks. It does not need a login."""
+        self.check_channel()
+        request = GetServiceInfoRequest(self.metadata)
+        try:
+            response = self.call_method(request)
+            return MessageToDict(response,
+                                 preserving_proto_field_name=True,
+                                 including_default_value_fields=True)
+        except Exception as e:
+            reason = str(e)
+            raise TeaclaveException(f"Failed to get service info ({reason})")
+
+    def register_input_files(self, files: List["UploadedInput"]) -> List[str]:
+        """Register input files uploaded by FileTransfer.upload_inputs.
+
+        Args:
+
+            files: The uploaded input files.
+
+        Returns:
+
+            List[str]: IDs of the input data, in the order of the files.
+        """
+        return [
+            self.register_input_file(f.url, f.schema, f.key, f.iv, f.cmac)
+            for f in files
+        ]
+
+
+_AEAD_SCHEMAS = {
+    "aes-gcm-128": (aead.AESGCM, 16),
+    "aes-gcm-256": (aead.AESGCM, 32),
+    "chacha20-poly1305": (aead.ChaCha20Poly1305, 32),
+}
+if hasattr(aead, "AESGCMSIV"):
+    _AEAD_SCHEMAS["aes-gcm-siv-128"] = (aead.AESGCMSIV, 16)
+    _AEAD_SCHEMAS["aes-gcm-siv-256"] = (aead.AESGCMSIV, 32)
+
+_IV_LEN = 12
+_CMAC_LEN = 16
+# Files not bound to a task are encrypted with this additional data
+_DEFAULT_AAD = bytes(8)
+
+
+def _cipher(schema: str, key: bytes):
+    if schema not in _AEAD_SCHEMAS:
+        raise TeaclaveException(f"Unsupported crypto schema {schema}")
+    cipher, key_len = _AEAD_SCHEMAS[schema]
+    if len(key) != key_len:
+        raise TeaclaveException(f"Key of {schema} should be {key_len} bytes")
+    return cipher(key)
+
+
+def encryption_context(task_id: str, slot: str, data_id: str) -> bytes:
+    """The additional data of outputs registered with bind_context, which are
+    bound to the task, the slot and the output data producing them.
+
+    Args:
+
+        task_id: ID of the task, e.g., "task-<uuid>".
+        slot: Name of the output in the function.
+        data_id: ID of the output data, e.g., "output-<uuid>".
+    """
+
+    def uuid_of(external_id):
+        return external_id.split("-", 1)[1] if external_id.startswith(
+            ("task-", "output-")) else external_id
+
+    return f"teaclave:{uuid_of(task_id)}:{slot}:{uuid_of(data_id)}".encode()
+
+
+class InputFile:
+    """An input file to encrypt and upload.
+
+    Args:
+
+        url: Presigned URL to upload the encrypted file to.
+        content: Content of the file, or the path to it.
+        schema: Crypto schema, e.g., "aes-gcm-128".
+        key: Key, random if not given.
+        iv: IV, random if not given.
+    """
+
+    def __init__(self,
+                 url: str,
+                 content: Union[bytes, str],
+                 schema: str = "aes-gcm-128",
+                 key: bytes = None,
+                 iv: bytes = None):
+        self.url = url
+        self.content = content
+        self.schema = schema
+        self.key = key if key is not None else os.urandom(
+            _AEAD_SCHEMAS.get(schema, (None, 16))[1])
+        self.iv = iv if iv is not None else os.urandom(_IV_LEN)
+
+
+class UploadedInput:
+    """An uploaded input file, with what it is registered with."""
+
+    def __init__(self, url: str, schema: str, key: bytes, iv: bytes,
+                 cmac: bytes, sha256: str):
+        self.url = url
+        self.schema = schema
+        self.key = list(key)
+        self.iv = list(iv)
+        self.cmac = list(cmac)
+        # Digest of the plaintext, to tell the files apart
+        self.sha256 = sha256
+
+
+class OutputFile:
+    """An output file to download and decrypt.
+
+    Args:
+
+        url: Presigned URL to download the encrypted file from.
+        schema: Crypto schema the output is registered with.
+        key: Key the output is registered with.
+        iv: IV the output is registered with.
+        cmac: CMAC of the output in the task result, checked if given.
+        path: Path to save the content to, returned if not given.
+        context: Additional data of outputs bound to their task, see
+            encryption_context.
+    """
+
+    def __init__(self,
+                 url: str,
+                 schema: str,
+                 key: bytes,
+                 iv: bytes,
+                 cmac: bytes = None,
+                 path: str = None,
+                 context: bytes = None):
+        self.url = url
+        self.schema = schema
+        self.key = bytes(key)
+        self.iv = bytes(iv)
+        self.cmac = bytes(cmac) if cmac is not None else None
+        self.path = path
+        self.context = context
+
+
+class TransferProgress:
+    """Aggregate progress of the files transferred by FileTransfer."""
+
+    def __init__(self, total_files: int):
+        self.total_files = total_files
+        self.done_files = 0
+        self.failed_files = 0
+        # Bytes of the encrypted files transferred
+        self.transferred_bytes = 0
+
+    def __repr__(self):
+        return (f"TransferProgress({self.done_files}/{self.total_files} "
+                f"files, {self.failed_files} failed, "
+                f"{self.transferred_bytes} bytes)")
+
+
+class FileTransfer:
+    """Encrypt and upload input files, and download and decrypt output files,
+    many at a time.
+
+    Args:
+
+        parallelism: The maximum number of files transferred at a time.
+        timeout: Timeout of transferring a file in seconds.
+        progress: Called with the TransferProgress each time a file is done
+            or failed.
+    """
+
+    def __init__(self,
+                 parallelism: int = 4,
+                 timeout: float = 60,
+                 progress: Callable[[TransferProgress], None] = None):
+        if parallelism < 1:
+            raise TeaclaveException("Parallelism should be at least 1")
+        self.parallelism = parallelism
+        self.timeout = timeout
+        self.progress = progress
+
+    def upload_inputs(self, files: List[InputFile]) -> List[UploadedInput]:
+        """Encrypt and upload the input files.
+
+        Returns:
+
+            List[UploadedInput]: The uploaded files in the order of the files,
+            to be registered with FrontendService.register_input_files.
+        """
+        return self._run(files, self._upload)
+
+    def download_outputs(self,
+                         files: List[OutputFile]) -> List[Union[bytes, str]]:
+        """Download and decrypt the output files, whose integrity is verified
+        with their tags and the CMACs given.
+
+        Returns:
+
+            List[Union[bytes, str]]: The content of each file, or the path it
+            is saved to, in the order of the files.
+        """
+        return self._run(files, self._download)
+
+    def _run(self, files, transfer):
+        progress = TransferProgress(len(files))
+        lock = threading.Lock()
+
+        def run(file):
+            try:
+                result, size = transfer(file)
+            except Exception:
+                with lock:
+                    progress.failed_files += 1
+                    self._report(progress)
+                raise
+            with lock:
+                progress.done_files += 1
+                progress.transferred_bytes += size
+                self._report(progress)
+            return result
+
+        with ThreadPoolExecutor(max_workers=self.parallelism) as executor:
+            futures = [executor.submit(run, file) for file in files]
+            results, failures = [], []
+            for file, future in zip(files, futures):
+                try:
+                    results.append(future.result())
+                except Exception as e:
+                    failures.append(f"{file.url}: {e}")
+        if failures:
+            raise TeaclaveException(
+                f"Failed to transfer {len(failures)} of {len(files)} files "
+                f"({'; '.join(failures)})")
+        return results
+
+    def _report(self, progress):
+        if self.progress:
+            self.progress(progress)
+
+    def _upload(self, file: InputFile):
+        content = file.content
+        if isinstance(content, str):
+            with open(content, "rb") as f:
+                content = f.read()
+        sha256 = hashlib.sha256(content).hexdigest()
+        cipher = _cipher(file.schema, file.key)
+        encrypted = cipher.encrypt(file.iv, content, _DEFAULT_AAD)
+        request = urllib.request.Request(file.url,
+                                         data=encrypted,
+                                         method="PUT")
+        with urllib.request.urlopen(request, timeout=self.timeout):
+            pass
+        cmac = encrypted[-_CMAC_LEN:]
+        uploaded = UploadedInput(file.url, file.schema, file.key, file.iv,
+                                 cmac, sha256)
+        return uploaded, len(encrypted)
+
+    def _download(self, file: OutputFile):
+        with urllib.request.urlopen(file.url, timeout=self.timeout) as r:
+            encrypted = r.read()
+        if file.cmac is not None and encrypted[-_CMAC_LEN:] != file.cmac:
+            raise TeaclaveException("CMAC mismatch")
+        cipher = _cipher(file.schema, file.key)
+        aad = file.context if file.context is not None else _DEFAULT_AAD
+        try:
+            content = cipher.decrypt(file.iv, encrypted, aad)
+        except InvalidTag:
+            raise TeaclaveException("Failed to decrypt, the file is corrupted")
+        if file.path is None:
+            return content, len(encrypted)
+        with open(file.path, "wb") as f:
+            f.write(content)
+        return file.path, len(encrypted)
@@ -581,6 +581,11 @@ generate_function_serialized!(
     teaclave_approve_task_serialized,
     approve_task_serialized
 );
+generate_function_serialized!(
+    FrontendClient,
+    teaclave_reject_task_serialized,
+    reject_task_serialized
+);
 generate_function_serialized!(
     FrontendClient,
     teaclave_invoke_task_serialized,
@@ -601,3 +606,18 @@ generate_function_serialized!(
     teaclave_query_audit_logs_serialized,
     query_audit_logs_serialized
 );
+generate_function_serialized!(
+    FrontendClient,
+    teaclave_export_audit_logs_serialized,
+    export_audit_logs_serialized
+);
+generate_function_serialized!(
+    FrontendClient,
+    teaclave_query_data_lineage_serialized,
+    query_data_lineage_serialized
+);
+generate_function_serialized!(
+    FrontendClient,
+    teaclave_set_notification_preferences_serialized,
+    set_notification_preferences_serialized
+);
@@ -18,44 +18,95 @@
 use anyhow::{bail, Result};
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
+use std::path::Path;
 use teaclave_attestation::verifier;
 use teaclave_proto::teaclave_authentication_service::TeaclaveAuthenticationApiClient;
-use teaclave_proto::teaclave_frontend_service::TeaclaveFrontendClient;
+use teaclave_proto::teaclave_frontend_service::{TeaclaveFrontendClient, API_VERSION};
 use teaclave_rpc::transport::{Channel, Uri};
-use teaclave_rpc::{config::SgxTrustedTlsClientConfig, CredentialService, UserCredential};
-use teaclave_types::FileAuthTag;
+use teaclave_rpc::{
+    config::SgxTrustedTlsClientConfig, CredentialService, MetadataMap, UserCredential,
+};
+use teaclave_types::{ExternalID, FileAuthTag, FunctionUpload};
 use tokio::runtime::Runtime;
 use url::Url;
 
+pub use teaclave_proto::teaclave_authentication_service_proto::GenerateTotpSecretResponse;
 use teaclave_proto::teaclave_authentication_service_proto::{
-    UserLoginRequest, UserLoginResponse, UserRegisterRequest,
+    ChangePasswordRequest, ForcePasswordResetRequest, GenerateTotpSecretRequest, UserLoginRequest,
+    UserLoginResponse, UserRegisterRequest, VerifyTotpRequest, VerifyTotpResponse,
 };
 pub use teaclave_proto::teaclave_frontend_service::GetFunctionResponse as Function;
 pub use teaclave_proto::teaclave_frontend_service::{
-    ApproveTaskRequest, AssignDataRequest, CancelTaskRequest, CreateTaskRequest,
-    CreateTaskResponse, GetFunctionRequest, GetFunctionResponse, GetFunctionUsageStatsRequest,
-    GetFunctionUsageStatsResponse, GetTaskRequest, GetTaskResponse, InvokeTaskRequest,
-    QueryAuditLogsRequest, QueryAuditLogsResponse, RegisterFunctionRequest,
-    RegisterFunctionRequestBuilder, RegisterFunctionResponse, RegisterFusionOutputRequest,
-    RegisterFusionOutputResponse, RegisterInputFileRequest, RegisterInputFileResponse,
-    RegisterInputFromOutputRequest, RegisterInputFromOutputResponse, RegisterOutputFileRequest,
-    RegisterOutputFileResponse,
+    ApproveTaskRequest, AssignDataRequest, BeginFunctionUploadRequest, BeginFunctionUploadResponse,
+    CancelMaintenanceWindowRequest, CancelTaskRequest, CommitFunctionRequest,
+    CreateStorageSnapshotRequest, CreateStorageSnapshotResponse, CreateTaskRequest,
+    CreateTaskResponse, DataLineage, DeclareMaintenanceWindowRequest,
+    DeclareMaintenanceWindowResponse, DecommissionStorageRequest, DeleteDataRequest,
+    ExportAuditLogsRequest, ExportAuditLogsResponse, ExportMetadataRequest, ExportMetadataResponse,
+    FunctionUsageRecord, GetApiVersionRequest, GetApiVersionResponse, GetAuditIndexStatusRequest,
+    GetAuditIndexStatusResponse, GetDataAttributesRequest, GetDataAttributesResponse,
+    GetFunctionRequest, GetFunctionResponse, GetFunctionUsageStatsRequest,
+    GetFunctionUsageStatsResponse, GetMetricsRequest, GetMetricsResponse, GetPlatformStatsRequest,
+    GetPlatformStatsResponse, GetServiceInfoRequest, GetServiceInfoResponse,
+    GetStorageDecommissionStatusRequest, GetStorageDecommissionStatusResponse, GetTaskLogRequest,
+    GetTaskLogResponse, GetTaskRequest, GetTaskResponse, GetUserAttributesRequest,
+    GetUserAttributesResponse, GetUserQuotaRequest, GetUserQuotaResponse, GrantDebugErrorsRequest,
+    GrantDebugErrorsResponse, InvokeTaskRequest, InvokeTaskResponse, ListTasksRequest,
+    ListTasksResponse, MaintenanceWindow, ManagePolicyRequest, ManagePolicyResponse,
+    ParticipantApproval, PolicyRule, PromoteStorageReplicaRequest, PromoteStorageReplicaResponse,
+    QueryAuditLogsRequest, QueryAuditLogsResponse, QueryDataLineageRequest,
+    QueryDataLineageResponse, QueryFunctionUsageRecordsRequest, QueryFunctionUsageRecordsResponse,
+    RebuildAuditIndexRequest, RegisterFunctionRequest, RegisterFunctionRequestBuilder,
+    RegisterFunctionResponse, RegisterFusionOutputRequest, RegisterFusionOutputResponse,
+    RegisterInputFileRequest, RegisterInputFileResponse, RegisterInputFromOutputRequest,
+    RegisterInputFromOutputResponse, RegisterOutputFileRequest, RegisterOutputFileResponse,
+    RegisterWebhookSinkRequest, RegisterWebhookSinkResponse, RejectTaskRequest,
+    RestoreStorageSnapshotRequest, RestoreStorageSnapshotResponse, RpcFamilyMetrics,
+    SetDataAttributesRequest, SetInputAccessPolicyRequest, SetNotificationPreferencesRequest,
+    SetUserAttributesRequest, SetUserQuotaRequest, UploadFunctionChunkRequest,
+    UploadFunctionChunkResponse, UserQuota,
 };
 pub use teaclave_types::{
-    EnclaveInfo, Entry, Executor, FileCrypto, FunctionArgument, FunctionInput, FunctionOutput,
-    FunctionUsage, TaskResult,
+    ArgumentType, ArgumentsFormat, EnclaveInfo, EncryptionContext, Entry, ExecutionReceipt,
+    Executor, ExecutorFeatures, FileCrypto, FunctionArgument, FunctionArguments, FunctionInput,
+    FunctionOutput, FunctionUsage, MaintenanceMode, NotificationPreferences, OutputPolicy,
+    TaskLogFile, TaskResult,
 };
 
 pub mod bindings;
+pub mod task_spec;
+
+pub use task_spec::TaskSpec;
 
 // This macro is intended for use cases where you are invoking from synchronous code to asynchronous code.
 macro_rules! do_request_with_credential {
     ($client:ident,$fun:ident,$request:ident) => {{
         let response = $client.rt.block_on($client.client.$fun($request))?;
+        warn_if_deprecated(stringify!($fun), response.metadata());
         Ok(response.into_inner())
     }};
 }
 
+// Responses to deprecated RPCs tell the replacement and the version removing
+// them.
+fn warn_if_deprecated(rpc: &str, metadata: &MetadataMap) {
+    if metadata.get("deprecation").is_none() {
+        return;
+    }
+    let get = |key| {
+        metadata
+            .get(key)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("unknown")
+    };
+    log::warn!(
+        "{} is deprecated and will be removed in {}, use {} instead",
+        rpc,
+        get("deprecation-sunset"),
+        get("deprecation-replacement")
+    );
+}
+
 pub struct AuthenticationClient {
     client: TeaclaveAuthenticationApiClient<CredentialService>,
     rt: Runtime,
@@ -124,6 +175,94 @@ impl AuthenticationClient {
 
         Ok(response.token)
     }
+
+    /// Log in with the credential of an identity provider, e.g., an ID token
+    /// of "oidc", returning the token and the user it is issued to.
+    pub fn user_login_with_provider(
+        &mut self,
+        provider: &str,
+        user_id: &str,
+        credential: &str,
+    ) -> Result<(String, String)> {
+        let request = UserLoginRequest::new(user_id, credential).provider(provider);
+        let response = self.user_login_with_request(request)?;
+
+        Ok((response.token, response.id))
+    }
+
+    /// Log in as a user enrolled in TOTP with a code of the authenticator, or
+    /// one of the recovery codes.
+    pub fn user_login_with_totp(
+        &mut self,
+        user_id: &str,
+        user_password: &str,
+        totp_code: &str,
+    ) -> Result<String> {
+        let request = UserLoginRequest::new(user_id, user_password).totp_code(totp_code);
+        let response = self.user_login_with_request(request)?;
+
+        Ok(response.token)
+    }
+
+    pub fn change_password_with_request(&mut self, request: ChangePasswordRequest) -> Result<()> {
+        self.rt.block_on(self.client.change_password(request))?;
+        Ok(())
+    }
+
+    /// Change the password with the current one, which also works once the
+    /// password has expired or a reset is forced, when logins are refused.
+    pub fn change_password(
+        &mut self,
+        user_id: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<()> {
+        let request = ChangePasswordRequest::new(user_id, old_password, new_password);
+        self.change_password_with_request(request)
+    }
+
+    pub fn force_password_reset_with_request(
+        &mut self,
+        request: ForcePasswordResetRequest,
+    ) -> Result<()> {
+        do_request_with_credential!(self, force_password_reset, request)
+    }
+
+    /// Require the user to change the password before logging in again.
+    pub fn force_password_reset(&mut self, user_id: &str) -> Result<()> {
+        let request = ForcePasswordResetRequest::new(user_id);
+        self.force_password_reset_with_request(request)
+    }
+
+    pub fn generate_totp_secret_with_request(
+        &mut self,
+        request: GenerateTotpSecretRequest,
+    ) -> Result<GenerateTotpSecretResponse> {
+        do_request_with_credential!(self, generate_totp_secret, request)
+    }
+
+    /// Generate a TOTP secret to be verified with `verify_totp`. The code is
+    /// required if enrolled already, to replace the enrolled secret.
+    pub fn generate_totp_secret(&mut self, code: &str) -> Result<GenerateTotpSecretResponse> {
+        let request = GenerateTotpSecretRequest::new(code);
+        self.generate_totp_secret_with_request(request)
+    }
+
+    pub fn verify_totp_with_request(
+        &mut self,
+        request: VerifyTotpRequest,
+    ) -> Result<VerifyTotpResponse> {
+        do_request_with_credential!(self, verify_totp, request)
+    }
+
+    /// Verify a TOTP code, returning the recovery codes if it enrolls the
+    /// secret generated.
+    pub fn verify_totp(&mut self, code: &str) -> Result<Vec<String>> {
+        let request = VerifyTotpRequest::new(code);
+        let response = self.verify_totp_with_request(request)?;
+
+        Ok(response.recovery_codes)
+    }
 }
 
 impl AuthenticationService {
@@ -140,6 +279,7 @@ impl AuthenticationService {
                 vec![enclave_attr],
                 as_root_ca_cert,
                 verifier::universal_quote_verifier,
+                verifier::VerificationPolicy::default(),
             )
             .into();
         let rt = tokio::runtime::Builder::new_current_thread()
@@ -163,6 +303,80 @@ impl AuthenticationService {
     }
 }
 
+/// Verify that a task result was signed by an attested execution service
+/// listed in `enclave_info`. Checking the hashes and CMACs in the receipt
+/// against the function and data is up to the caller, or see
+/// `verify_task_output`.
+pub fn verify_execution_receipt(
+    receipt: &ExecutionReceipt,
+    enclave_info: &EnclaveInfo,
+    as_root_ca_cert: &[u8],
+) -> Result<()> {
+    let enclave_attr = enclave_info
+        .get_enclave_attr("teaclave_execution_service")
+        .ok_or_else(|| anyhow::anyhow!("no enclave info of execution service"))?;
+    let verifier = verifier::AttestationReportVerifier::new(
+        vec![enclave_attr],
+        as_root_ca_cert,
+        verifier::universal_quote_verifier,
+    );
+    verifier.verify_receipt(receipt)?;
+    Ok(())
+}
+
+/// A task output downloaded by the client, with the key and the tag it was
+/// given for the output.
+pub struct DownloadedOutput<'a> {
+    /// Name of the output in the function
+    pub name: &'a str,
+    pub path: &'a Path,
+    pub crypto: &'a FileCrypto,
+    /// Context the output was encrypted with, for the in-memory schemas
+    pub context: Option<&'a EncryptionContext>,
+    pub tag: &'a FileAuthTag,
+}
+
+/// Compute the tag of an encrypted file by decrypting it, which fails if the
+/// file has been tampered with.
+pub fn file_auth_tag(
+    path: &Path,
+    crypto: &FileCrypto,
+    context: Option<&EncryptionContext>,
+) -> Result<FileAuthTag> {
+    let tag = match crypto {
+        FileCrypto::TeaclaveFile128(key) => {
+            let cmac = key.decrypt(path, &mut std::io::sink())?;
+            FileAuthTag::from(cmac)
+        }
+        FileCrypto::Raw => bail!("Raw files carry no tag"),
+        _ => {
+            let mut content = std::fs::read(path)?;
+            crypto.decrypt_in_memory(&mut content, context)?
+        }
+    };
+    Ok(tag)
+}
+
+/// Verify that a downloaded output is the result of the function with
+/// `function_payload`: the file is authenticated by its tag, the tag is the
+/// one recorded in the receipt, the receipt is about the function, and it was
+/// signed by an attested execution service listed in `enclave_info`.
+pub fn verify_task_output(
+    receipt: &ExecutionReceipt,
+    enclave_info: &EnclaveInfo,
+    as_root_ca_cert: &[u8],
+    function_payload: &[u8],
+    output: &DownloadedOutput,
+) -> Result<()> {
+    let tag = file_auth_tag(output.path, output.crypto, output.context)?;
+    if &tag != output.tag {
+        bail!("The tag of the file {} differs", output.path.display());
+    }
+    receipt.check_output(output.name, output.tag)?;
+    receipt.check_function(function_payload)?;
+    verify_execution_receipt(receipt, enclave_info, as_root_ca_cert)
+}
+
 #[repr(C)]
 pub struct FrontendService;
 
@@ -180,6 +394,7 @@ impl FrontendService {
                 vec![enclave_attr],
                 as_root_ca_cert,
                 verifier::universal_quote_verifier,
+                verifier::VerificationPolicy::default(),
             )
             .into();
 
@@ -287,6 +502,68 @@ impl FrontendClient {
         Ok(response.function_id)
     }
 
+    pub fn begin_function_upload_with_request(
+        &mut self,
+        request: BeginFunctionUploadRequest,
+    ) -> Result<BeginFunctionUploadResponse> {
+        do_request_with_credential!(self, begin_function_upload, request)
+    }
+
+    pub fn upload_function_chunk_with_request(
+        &mut self,
+        request: UploadFunctionChunkRequest,
+    ) -> Result<UploadFunctionChunkResponse> {
+        do_request_with_credential!(self, upload_function_chunk, request)
+    }
+
+    pub fn commit_function_with_request(
+        &mut self,
+        request: CommitFunctionRequest,
+    ) -> Result<RegisterFunctionResponse> {
+        do_request_with_credential!(self, commit_function, request)
+    }
+
+    /// Register a function whose payload is too large for a single request,
+    /// uploading the payload in the chunks allowed by the service.
+    pub fn register_function_in_chunks(
+        &mut self,
+        mut request: RegisterFunctionRequest,
+    ) -> Result<String> {
+        let payload = std::mem::take(&mut request.payload);
+        let digest = FunctionUpload::digest(&payload);
+        let begin = BeginFunctionUploadRequest::new(payload.len() as u64, digest);
+        let response = self.begin_function_upload_with_request(begin)?;
+        let upload_id: ExternalID = response.upload_id.try_into()?;
+        if response.max_chunk_size == 0 {
+            bail!("Invalid chunk size");
+        }
+
+        let mut offset = 0;
+        for chunk in payload.chunks(response.max_chunk_size as usize) {
+            let request = UploadFunctionChunkRequest::new(upload_id.clone(), offset, chunk);
+            offset = self
+                .upload_function_chunk_with_request(request)?
+                .received_size;
+        }
+
+        let request = CommitFunctionRequest::new(upload_id, request);
+        let response = self.commit_function_with_request(request)?;
+
+        Ok(response.function_id)
+    }
+
+    pub fn register_function_in_chunks_serialized(
+        &mut self,
+        serialized_request: &str,
+    ) -> Result<String> {
+        let request = serde_json::from_str(serialized_request)?;
+        let function_id = self.register_function_in_chunks(request)?;
+        let response = RegisterFunctionResponse { function_id };
+        let serialized_response = serde_json::to_string(&response)?;
+
+        Ok(serialized_response)
+    }
+
     pub fn get_function_with_request(
         &mut self,
         request: GetFunctionRequest,
@@ -336,6 +613,40 @@ impl FrontendClient {
         do_request_with_credential!(self, get_function_usage_stats, request)
     }
 
+    pub fn query_function_usage_records_with_request(
+        &mut self,
+        request: QueryFunctionUsageRecordsRequest,
+    ) -> Result<QueryFunctionUsageRecordsResponse> {
+        do_request_with_credential!(self, query_function_usage_records, request)
+    }
+
+    /// Usage records of the runs of the function ended in
+    /// `[start_time, end_time)`, in seconds since the UNIX epoch (unbounded if
+    /// 0).
+    pub fn query_function_usage_records(
+        &mut self,
+        function_id: &str,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<Vec<FunctionUsageRecord>> {
+        let function_id = function_id.try_into()?;
+        let request =
+            QueryFunctionUsageRecordsRequest::new(function_id).time_range(start_time, end_time);
+        let response = self.query_function_usage_records_with_request(request)?;
+        Ok(response.records)
+    }
+
+    pub fn query_function_usage_records_serialized(
+        &mut self,
+        serialized_request: &str,
+    ) -> Result<String> {
+        let request = serde_json::from_str(serialized_request)?;
+        let response = self.query_function_usage_records_with_request(request)?;
+        let serialized_response = serde_json::to_string(&response)?;
+
+        Ok(serialized_response)
+    }
+
     pub fn register_input_file_with_request(
         &mut self,
         request: RegisterInputFileRequest,
@@ -442,6 +753,19 @@ impl FrontendClient {
         Ok(response.data_id)
     }
 
+    /// Register a fusion output with policies applied to its content before
+    /// it is uploaded, e.g., adding noise or a minimal number of rows.
+    pub fn register_fusion_output_with_policies(
+        &mut self,
+        owners: impl Into<teaclave_types::OwnerList>,
+        policies: Vec<OutputPolicy>,
+    ) -> Result<String> {
+        let request = RegisterFusionOutputRequest::new(owners).output_policies(policies);
+        let response = self.register_fusion_output_with_request(request)?;
+
+        Ok(response.data_id)
+    }
+
     pub fn create_task_serialized(&mut self, serialized_request: &str) -> Result<String> {
         let request = serde_json::from_str(serialized_request)?;
         let response = self.create_task_with_request(request)?;
@@ -540,23 +864,59 @@ impl FrontendClient {
         self.approve_task_with_request(request)
     }
 
+    /// Approve the task only if the inputs are assigned with files of the
+    /// cmacs, e.g., the gradients uploaded for an aggregation.
+    pub fn approve_task_with_cmacs(
+        &mut self,
+        task_id: &str,
+        input_cmacs: HashMap<String, Vec<u8>>,
+    ) -> Result<()> {
+        let mut request = ApproveTaskRequest::new(task_id.try_into()?);
+        for (data_name, cmac) in input_cmacs {
+            request = request.input_cmac(data_name, FileAuthTag::from_bytes(&cmac)?);
+        }
+        self.approve_task_with_request(request)
+    }
+
     pub fn approve_task_serialized(&mut self, serialized_request: &str) -> Result<String> {
         let request = serde_json::from_str(serialized_request)?;
         self.approve_task_with_request(request)?;
         Ok(String::new())
     }
 
-    pub fn invoke_task_with_request(&mut self, request: InvokeTaskRequest) -> Result<()> {
+    pub fn reject_task_with_request(&mut self, request: RejectTaskRequest) -> Result<()> {
+        do_request_with_credential!(self, reject_task, request)
+    }
+
+    pub fn reject_task(&mut self, task_id: &str, reason: &str) -> Result<()> {
+        let request = RejectTaskRequest::new(task_id.try_into()?, reason);
+        self.reject_task_with_request(request)
+    }
+
+    pub fn reject_task_serialized(&mut self, serialized_request: &str) -> Result<String> {
+        let request = serde_json::from_str(serialized_request)?;
+        self.reject_task_with_request(request)?;
+        Ok(String::new())
+    }
+
+    pub fn invoke_task_with_request(
+        &mut self,
+        request: InvokeTaskRequest,
+    ) -> Result<InvokeTaskResponse> {
         do_request_with_credential!(self, invoke_task, request)
     }
 
     pub fn invoke_task_serialized(&mut self, serialized_request: &str) -> Result<String> {
         let request = serde_json::from_str(serialized_request)?;
-        self.invoke_task_with_request(request)?;
-        Ok(String::new())
+        let response = self.invoke_task_with_request(request)?;
+        let serialized_response = serde_json::to_string(&response)?;
+
+        Ok(serialized_response)
     }
 
-    pub fn invoke_task(&mut self, task_id: &str) -> Result<()> {
+    /// Invoke the task, returning the number of staged tasks ahead of it and
+    /// the seconds it may wait before it runs, 0 if unknown.
+    pub fn invoke_task(&mut self, task_id: &str) -> Result<InvokeTaskResponse> {
         let request = InvokeTaskRequest::new(task_id.try_into()?);
         self.invoke_task_with_request(request)
     }
@@ -592,6 +952,61 @@ impl FrontendClient {
         }
     }
 
+    /// Get the execution receipt of a finished task, which can be verified
+    /// offline with `verify_execution_receipt`.
+    pub fn get_task_receipt(&mut self, task_id: &str) -> Result<ExecutionReceipt> {
+        let request = GetTaskRequest::new(task_id.try_into()?);
+        let response = self.get_task_with_request(request)?;
+        match teaclave_types::TaskResult::try_from(response.result)? {
+            TaskResult::Ok(task_outputs) => task_outputs
+                .receipt
+                .ok_or_else(|| anyhow::anyhow!("no receipt for the task")),
+            _ => bail!("task is not finished"),
+        }
+    }
+
+    pub fn get_task_log_with_request(
+        &mut self,
+        request: GetTaskLogRequest,
+    ) -> Result<GetTaskLogResponse> {
+        do_request_with_credential!(self, get_task_log, request)
+    }
+
+    /// Get the location and key of the execution log of a task created by
+    /// the user. The downloaded log is decrypted with
+    /// `FileCrypto::decrypt_in_memory` without a context.
+    pub fn get_task_log(&mut self, task_id: &str) -> Result<TaskLogFile> {
+        let request = GetTaskLogRequest::new(task_id.try_into()?);
+        let response = self.get_task_log_with_request(request)?;
+        let log_file = response
+            .log_file
+            .ok_or_else(|| anyhow::anyhow!("no log for the task"))?;
+        log_file.try_into()
+    }
+
+    pub fn register_webhook_sink_with_request(
+        &mut self,
+        request: RegisterWebhookSinkRequest,
+    ) -> Result<RegisterWebhookSinkResponse> {
+        do_request_with_credential!(self, register_webhook_sink, request)
+    }
+
+    /// Push the result notification of a task created by the user to `url`
+    /// once the task ends. Returns the sink id. Receivers verify the
+    /// `X-Teaclave-Signature` header, i.e., `sha256=` followed by the
+    /// hex-encoded HMAC-SHA256 of the body using `secret`.
+    pub fn register_webhook_sink(
+        &mut self,
+        task_id: &str,
+        url: &str,
+        secret: &str,
+    ) -> Result<String> {
+        let request =
+            RegisterWebhookSinkRequest::new(task_id.try_into()?, Url::parse(url)?, secret);
+        let response = self.register_webhook_sink_with_request(request)?;
+        Ok(response.sink_id)
+    }
+
     pub fn cancel_task_with_request(&mut self, request: CancelTaskRequest) -> Result<()> {
         do_request_with_credential!(self, cancel_task, request)
     }
@@ -628,6 +1043,502 @@ impl FrontendClient {
     ) -> Result<QueryAuditLogsResponse> {
         do_request_with_credential!(self, query_audit_logs, request)
     }
+
+    /// Export the logs matching `query` to the registered output `output_id`
+    /// as JSON Lines or CSV, with the selected columns, or all if empty.
+    /// Returns the number of exported logs.
+    pub fn export_audit_logs(
+        &mut self,
+        query: String,
+        max_rows: usize,
+        output_id: &str,
+        format: &str,
+        columns: Vec<String>,
+    ) -> Result<u64> {
+        let output_id = teaclave_types::ExternalID::try_from(output_id)?;
+        let request = ExportAuditLogsRequest::new(query, max_rows, output_id)
+            .format(format)
+            .columns(columns);
+        let response = self.export_audit_logs_with_request(request)?;
+
+        Ok(response.rows)
+    }
+
+    pub fn export_audit_logs_serialized(&mut self, serialized_request: &str) -> Result<String> {
+        let request = serde_json::from_str(serialized_request)?;
+        let response = self.export_audit_logs_with_request(request)?;
+        let serialized_response = serde_json::to_string(&response)?;
+
+        Ok(serialized_response)
+    }
+
+    pub fn export_audit_logs_with_request(
+        &mut self,
+        request: ExportAuditLogsRequest,
+    ) -> Result<ExportAuditLogsResponse> {
+        do_request_with_credential!(self, export_audit_logs, request)
+    }
+
+    /// Export the signed metadata dump of all the functions, data and tasks
+    /// to the registered output `output_id`, for the CLI to verify.
+    pub fn rebuild_audit_index_with_request(
+        &mut self,
+        request: RebuildAuditIndexRequest,
+    ) -> Result<()> {
+        do_request_with_credential!(self, rebuild_audit_index, request)
+    }
+
+    pub fn rebuild_audit_index(&mut self) -> Result<()> {
+        self.rebuild_audit_index_with_request(RebuildAuditIndexRequest::default())
+    }
+
+    pub fn get_audit_index_status_with_request(
+        &mut self,
+        request: GetAuditIndexStatusRequest,
+    ) -> Result<GetAuditIndexStatusResponse> {
+        do_request_with_credential!(self, get_audit_index_status, request)
+    }
+
+    pub fn get_audit_index_status(&mut self) -> Result<GetAuditIndexStatusResponse> {
+        self.get_audit_index_status_with_request(GetAuditIndexStatusRequest::default())
+    }
+
+    pub fn export_metadata(&mut self, output_id: &str) -> Result<ExportMetadataResponse> {
+        let output_id = teaclave_types::ExternalID::try_from(output_id)?;
+        let request = ExportMetadataRequest::new(output_id);
+        self.export_metadata_with_request(request)
+    }
+
+    pub fn export_metadata_with_request(
+        &mut self,
+        request: ExportMetadataRequest,
+    ) -> Result<ExportMetadataResponse> {
+        do_request_with_credential!(self, export_metadata, request)
+    }
+
+    pub fn set_user_quota_with_request(&mut self, request: SetUserQuotaRequest) -> Result<()> {
+        do_request_with_credential!(self, set_user_quota, request)
+    }
+
+    pub fn set_user_quota(&mut self, user_id: &str, quota: UserQuota) -> Result<()> {
+        let request = SetUserQuotaRequest::new(user_id, quota);
+        self.set_user_quota_with_request(request)
+    }
+
+    pub fn get_user_quota_with_request(
+        &mut self,
+        request: GetUserQuotaRequest,
+    ) -> Result<GetUserQuotaResponse> {
+        do_request_with_credential!(self, get_user_quota, request)
+    }
+
+    pub fn get_user_quota(&mut self, user_id: &str) -> Result<GetUserQuotaResponse> {
+        let request = GetUserQuotaRequest::new(user_id);
+        self.get_user_quota_with_request(request)
+    }
+
+    pub fn grant_debug_errors_with_request(
+        &mut self,
+        request: GrantDebugErrorsRequest,
+    ) -> Result<GrantDebugErrorsResponse> {
+        do_request_with_credential!(self, grant_debug_errors, request)
+    }
+
+    /// Let the user see detailed internal errors for `duration_secs`, or
+    /// revoke the grant if 0. Returns when the grant expires.
+    pub fn grant_debug_errors(&mut self, user_id: &str, duration_secs: u64) -> Result<u64> {
+        let request = GrantDebugErrorsRequest::new(user_id, duration_secs);
+        let response = self.grant_debug_errors_with_request(request)?;
+        Ok(response.expires_at)
+    }
+
+    pub fn get_metrics_with_request(
+        &mut self,
+        request: GetMetricsRequest,
+    ) -> Result<GetMetricsResponse> {
+        do_request_with_credential!(self, get_metrics, request)
+    }
+
+    pub fn get_metrics(&mut self) -> Result<GetMetricsResponse> {
+        let request = GetMetricsRequest::default();
+        self.get_metrics_with_request(request)
+    }
+
+    pub fn get_api_version_with_request(
+        &mut self,
+        request: GetApiVersionRequest,
+    ) -> Result<GetApiVersionResponse> {
+        do_request_with_credential!(self, get_api_version, request)
+    }
+
+    pub fn get_api_version(&mut self) -> Result<GetApiVersionResponse> {
+        let request = GetApiVersionRequest::new();
+        self.get_api_version_with_request(request)
+    }
+
+    /// Checks that the service still serves the API version of the SDK, which
+    /// is best done before other calls, as their messages may have changed.
+    pub fn negotiate_api_version(&mut self) -> Result<GetApiVersionResponse> {
+        let response = self.get_api_version()?;
+        if !response.is_compatible() {
+            bail!(
+                "The service requires API version {} or later, but the SDK is of {}, upgrade the SDK",
+                response.min_api_version,
+                API_VERSION
+            );
+        }
+        if response.api_version < API_VERSION {
+            log::warn!(
+                "The service is of API version {}, older than {} of the SDK, some calls may be unavailable",
+                response.api_version,
+                API_VERSION
+            );
+        }
+        Ok(response)
+    }
+
+    pub fn get_platform_stats_with_request(
+        &mut self,
+        request: GetPlatformStatsRequest,
+    ) -> Result<GetPlatformStatsResponse> {
+        do_request_with_credential!(self, get_platform_stats, request)
+    }
+
+    pub fn get_platform_stats(&mut self) -> Result<GetPlatformStatsResponse> {
+        let request = GetPlatformStatsRequest::default();
+        self.get_platform_stats_with_request(request)
+    }
+
+    pub fn list_tasks_with_request(
+        &mut self,
+        request: ListTasksRequest,
+    ) -> Result<ListTasksResponse> {
+        do_request_with_credential!(self, list_tasks, request)
+    }
+
+    /// List the tasks from `offset`, all of them if `limit` is 0.
+    pub fn list_tasks(&mut self, offset: usize, limit: usize) -> Result<ListTasksResponse> {
+        let request = ListTasksRequest::new().page(offset, limit);
+        self.list_tasks_with_request(request)
+    }
+
+    /// List `limit` tasks after the task `after` in the order of IDs, from
+    /// the first one if `after` is empty. Pass the `after` of `next_cursor`
+    /// in the response for the next page.
+    pub fn list_tasks_after(&mut self, after: &str, limit: usize) -> Result<ListTasksResponse> {
+        let request = ListTasksRequest::new().cursor(after, limit);
+        self.list_tasks_with_request(request)
+    }
+
+    pub fn manage_policy_with_request(
+        &mut self,
+        request: ManagePolicyRequest,
+    ) -> Result<ManagePolicyResponse> {
+        do_request_with_credential!(self, manage_policy, request)
+    }
+
+    pub fn list_policy_rules(&mut self) -> Result<Vec<PolicyRule>> {
+        let request = ManagePolicyRequest::list_rules();
+        let response = self.manage_policy_with_request(request)?;
+        Ok(response.rules)
+    }
+
+    pub fn add_policy_rule(&mut self, rule: PolicyRule) -> Result<Vec<PolicyRule>> {
+        let request = ManagePolicyRequest::add_rule(rule);
+        let response = self.manage_policy_with_request(request)?;
+        Ok(response.rules)
+    }
+
+    pub fn remove_policy_rule(&mut self, rule: PolicyRule) -> Result<Vec<PolicyRule>> {
+        let request = ManagePolicyRequest::remove_rule(rule);
+        let response = self.manage_policy_with_request(request)?;
+        Ok(response.rules)
+    }
+
+    pub fn set_user_attributes_with_request(
+        &mut self,
+        request: SetUserAttributesRequest,
+    ) -> Result<()> {
+        do_request_with_credential!(self, set_user_attributes, request)
+    }
+
+    pub fn set_user_attributes(
+        &mut self,
+        user_id: &str,
+        attributes: HashMap<String, String>,
+    ) -> Result<()> {
+        let request = SetUserAttributesRequest::new(user_id, attributes);
+        self.set_user_attributes_with_request(request)
+    }
+
+    pub fn get_user_attributes_with_request(
+        &mut self,
+        request: GetUserAttributesRequest,
+    ) -> Result<GetUserAttributesResponse> {
+        do_request_with_credential!(self, get_user_attributes, request)
+    }
+
+    pub fn get_user_attributes(&mut self, user_id: &str) -> Result<HashMap<String, String>> {
+        let request = GetUserAttributesRequest::new(user_id);
+        let response = self.get_user_attributes_with_request(request)?;
+        Ok(response.attributes)
+    }
+
+    pub fn set_data_attributes_with_request(
+        &mut self,
+        request: SetDataAttributesRequest,
+    ) -> Result<()> {
+        do_request_with_credential!(self, set_data_attributes, request)
+    }
+
+    pub fn set_data_attributes(
+        &mut self,
+        data_id: &str,
+        attributes: HashMap<String, String>,
+    ) -> Result<()> {
+        let data_id = teaclave_types::ExternalID::try_from(data_id)?;
+        let request = SetDataAttributesRequest::new(data_id, attributes);
+        self.set_data_attributes_with_request(request)
+    }
+
+    pub fn get_data_attributes_with_request(
+        &mut self,
+        request: GetDataAttributesRequest,
+    ) -> Result<GetDataAttributesResponse> {
+        do_request_with_credential!(self, get_data_attributes, request)
+    }
+
+    pub fn get_data_attributes(&mut self, data_id: &str) -> Result<HashMap<String, String>> {
+        let data_id = teaclave_types::ExternalID::try_from(data_id)?;
+        let request = GetDataAttributesRequest::new(data_id);
+        let response = self.get_data_attributes_with_request(request)?;
+        Ok(response.attributes)
+    }
+
+    pub fn set_input_access_policy_with_request(
+        &mut self,
+        request: SetInputAccessPolicyRequest,
+    ) -> Result<()> {
+        do_request_with_credential!(self, set_input_access_policy, request)
+    }
+
+    /// Only the functions with the IDs or of the owners can use the input
+    /// file, and assigning the file to their tasks approves the tasks. Empty
+    /// lists allow all the functions.
+    pub fn set_input_access_policy(
+        &mut self,
+        data_id: &str,
+        function_ids: &[&str],
+        function_owners: &[&str],
+    ) -> Result<()> {
+        let data_id = teaclave_types::ExternalID::try_from(data_id)?;
+        let functions = function_ids
+            .iter()
+            .map(|id| teaclave_types::ExternalID::try_from(*id))
+            .collect::<Result<_>>()?;
+        let function_owners = function_owners.iter().map(|o| (*o).into()).collect();
+        let policy = teaclave_types::InputAccessPolicy::new(functions, function_owners);
+        let request = SetInputAccessPolicyRequest::new(data_id, policy);
+        self.set_input_access_policy_with_request(request)
+    }
+
+    pub fn delete_data_with_request(&mut self, request: DeleteDataRequest) -> Result<()> {
+        do_request_with_credential!(self, delete_data, request)
+    }
+
+    /// Delete the input or output file, which is refused while pending tasks
+    /// are assigned it, unless `cascade` cancels the tasks.
+    pub fn delete_data(&mut self, data_id: &str, cascade: bool) -> Result<()> {
+        let mut request = DeleteDataRequest::new(data_id.try_into()?);
+        if cascade {
+            request = request.cascade();
+        }
+        self.delete_data_with_request(request)
+    }
+
+    pub fn query_data_lineage_with_request(
+        &mut self,
+        request: QueryDataLineageRequest,
+    ) -> Result<QueryDataLineageResponse> {
+        do_request_with_credential!(self, query_data_lineage, request)
+    }
+
+    /// Lineage of the data and all its upstream data, e.g., the tasks which
+    /// produced them and their inputs.
+    pub fn query_data_lineage(&mut self, data_id: &str) -> Result<Vec<DataLineage>> {
+        let data_id = teaclave_types::ExternalID::try_from(data_id)?;
+        let request = QueryDataLineageRequest::new(data_id);
+        let response = self.query_data_lineage_with_request(request)?;
+        Ok(response.lineage)
+    }
+
+    pub fn query_data_lineage_serialized(&mut self, serialized_request: &str) -> Result<String> {
+        let request = serde_json::from_str(serialized_request)?;
+        let response = self.query_data_lineage_with_request(request)?;
+        let serialized_response = serde_json::to_string(&response)?;
+
+        Ok(serialized_response)
+    }
+
+    pub fn set_notification_preferences_with_request(
+        &mut self,
+        request: SetNotificationPreferencesRequest,
+    ) -> Result<()> {
+        do_request_with_credential!(self, set_notification_preferences, request)
+    }
+
+    /// Opt in to the digests of ended tasks through the given channels, or
+    /// opt out with none.
+    pub fn set_notification_preferences(
+        &mut self,
+        email: Option<&str>,
+        slack_member_id: Option<&str>,
+    ) -> Result<()> {
+        let preferences = NotificationPreferences {
+            email: email.map(|s| s.to_string()),
+            slack_member_id: slack_member_id.map(|s| s.to_string()),
+        };
+        let request = SetNotificationPreferencesRequest::new(preferences);
+        self.set_notification_preferences_with_request(request)
+    }
+
+    pub fn set_notification_preferences_serialized(
+        &mut self,
+        serialized_request: &str,
+    ) -> Result<String> {
+        let request = serde_json::from_str(serialized_request)?;
+        self.set_notification_preferences_with_request(request)?;
+        Ok(String::new())
+    }
+
+    pub fn decommission_storage_with_request(
+        &mut self,
+        request: DecommissionStorageRequest,
+    ) -> Result<()> {
+        do_request_with_credential!(self, decommission_storage, request)
+    }
+
+    pub fn decommission_storage(&mut self, replacement_address: &str) -> Result<()> {
+        let request = DecommissionStorageRequest::new(replacement_address);
+        self.decommission_storage_with_request(request)
+    }
+
+    pub fn get_storage_decommission_status_with_request(
+        &mut self,
+        request: GetStorageDecommissionStatusRequest,
+    ) -> Result<GetStorageDecommissionStatusResponse> {
+        do_request_with_credential!(self, get_storage_decommission_status, request)
+    }
+
+    pub fn get_storage_decommission_status(
+        &mut self,
+    ) -> Result<GetStorageDecommissionStatusResponse> {
+        let request = GetStorageDecommissionStatusRequest::default();
+        self.get_storage_decommission_status_with_request(request)
+    }
+
+    pub fn create_storage_snapshot_with_request(
+        &mut self,
+        request: CreateStorageSnapshotRequest,
+    ) -> Result<CreateStorageSnapshotResponse> {
+        do_request_with_credential!(self, create_storage_snapshot, request)
+    }
+
+    /// Back up the storage to `url`, encrypted with `file_crypto`. Returns the
+    /// tag needed to restore the snapshot.
+    pub fn create_storage_snapshot(
+        &mut self,
+        url: &str,
+        file_crypto: FileCrypto,
+    ) -> Result<Vec<u8>> {
+        let url = Url::parse(url)?;
+        let request = CreateStorageSnapshotRequest::new(url, file_crypto);
+        let response = self.create_storage_snapshot_with_request(request)?;
+        Ok(response.cmac)
+    }
+
+    pub fn restore_storage_snapshot_with_request(
+        &mut self,
+        request: RestoreStorageSnapshotRequest,
+    ) -> Result<RestoreStorageSnapshotResponse> {
+        do_request_with_credential!(self, restore_storage_snapshot, request)
+    }
+
+    /// Restore the snapshot created at `url`, returning the number of
+    /// restored entries.
+    pub fn restore_storage_snapshot(
+        &mut self,
+        url: &str,
+        file_crypto: FileCrypto,
+        cmac: &[u8],
+    ) -> Result<u64> {
+        let url = Url::parse(url)?;
+        let cmac = FileAuthTag::from_bytes(cmac)?;
+        let request = RestoreStorageSnapshotRequest::new(url, file_crypto, cmac);
+        let response = self.restore_storage_snapshot_with_request(request)?;
+        Ok(response.entries)
+    }
+
+    pub fn promote_storage_replica_with_request(
+        &mut self,
+        request: PromoteStorageReplicaRequest,
+    ) -> Result<PromoteStorageReplicaResponse> {
+        do_request_with_credential!(self, promote_storage_replica, request)
+    }
+
+    /// Promote the follower replicating the lost storage node, returning the
+    /// last index of the change log it had applied.
+    pub fn promote_storage_replica(&mut self, follower_address: &str) -> Result<u64> {
+        let request = PromoteStorageReplicaRequest::new(follower_address);
+        let response = self.promote_storage_replica_with_request(request)?;
+        Ok(response.applied_index)
+    }
+
+    pub fn declare_maintenance_window_with_request(
+        &mut self,
+        request: DeclareMaintenanceWindowRequest,
+    ) -> Result<DeclareMaintenanceWindowResponse> {
+        do_request_with_credential!(self, declare_maintenance_window, request)
+    }
+
+    /// Declare a maintenance window from `starts_at` to `ends_at`, in seconds
+    /// since the UNIX epoch. Returns the id of the window.
+    pub fn declare_maintenance_window(
+        &mut self,
+        starts_at: i64,
+        ends_at: i64,
+        mode: MaintenanceMode,
+        reason: &str,
+    ) -> Result<String> {
+        let request = DeclareMaintenanceWindowRequest::new(starts_at, ends_at, mode, reason);
+        let response = self.declare_maintenance_window_with_request(request)?;
+        Ok(response.window_id)
+    }
+
+    pub fn cancel_maintenance_window_with_request(
+        &mut self,
+        request: CancelMaintenanceWindowRequest,
+    ) -> Result<()> {
+        do_request_with_credential!(self, cancel_maintenance_window, request)
+    }
+
+    pub fn cancel_maintenance_window(&mut self, window_id: &str) -> Result<()> {
+        let request = CancelMaintenanceWindowRequest::new(window_id.try_into()?);
+        self.cancel_maintenance_window_with_request(request)
+    }
+
+    pub fn get_service_info_with_request(
+        &mut self,
+        request: GetServiceInfoRequest,
+    ) -> Result<GetServiceInfoResponse> {
+        do_request_with_credential!(self, get_service_info, request)
+    }
+
+    /// The maintenance windows of the service, which users are best told
+    /// about before they invoke tasks.
+    pub fn get_service_info(&mut self) -> Result<GetServiceInfoResponse> {
+        self.get_service_info_with_request(GetServiceInfoRequest::default())
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,348 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Declarative task specifications in YAML.
+//!
+//! A task specification describes a task as a whole: the function and its
+//! arguments, the owners of each input and output slot, the files to assign
+//! to the slots and the participants who have to approve the task. It can be
+//! reviewed and kept under version control, then submitted with
+//! `FrontendClient::submit_task_spec` or the `task` subcommand of the command
+//! line tool.
+//!
+//! ```yaml
+//! function_id: function-00000000-0000-0000-0000-000000000001
+//! executor: builtin
+//! arguments:
+//!   message: Hello, Teaclave!
+//! inputs:
+//!   input:
+//!     owners: [alice]
+//!     file:
+//!       url: https://storage.example.com/input?token
+//!       cmac: 00000000000000000000000000000000
+//!       crypto:
+//!         schema: teaclave-file-128
+//!         key: 00000000000000000000000000000000
+//! outputs:
+//!   output:
+//!     owners: [alice, bob]
+//!     data_id: output-00000000-0000-0000-0000-000000000002
+//! approvals: [alice, bob]
+//! ```
+//!
+//! Slots without `file` or `data_id` are left for their owners to assign.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use teaclave_types::{ExternalID, FileAuthTag, FileCrypto};
+use url::Url;
+
+use crate::{Executor, FrontendClient};
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TaskSpec {
+    pub function_id: String,
+    #[serde(default = "default_executor")]
+    pub executor: String,
+    #[serde(default)]
+    pub arguments: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, SlotSpec>,
+    #[serde(default)]
+    pub outputs: BTreeMap<String, SlotSpec>,
+    /// Participants who have to approve the task before it can be invoked,
+    /// i.e., all the owners of its inputs and outputs.
+    #[serde(default)]
+    pub approvals: Vec<String>,
+}
+
+fn default_executor() -> String {
+    "builtin".to_string()
+}
+
+/// An input or output slot of the task.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SlotSpec {
+    pub owners: Vec<String>,
+    /// A file to register and assign to the slot
+    #[serde(default)]
+    pub file: Option<FileSpec>,
+    /// Registered data to assign to the slot
+    #[serde(default)]
+    pub data_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FileSpec {
+    pub url: String,
+    /// CMAC of the file in the hex format, required for inputs
+    #[serde(default)]
+    pub cmac: Option<String>,
+    pub crypto: CryptoSpec,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CryptoSpec {
+    pub schema: String,
+    /// Key in the hex format
+    pub key: String,
+    /// IV in the hex format, empty for "teaclave-file-128"
+    #[serde(default)]
+    pub iv: String,
+}
+
+impl CryptoSpec {
+    pub fn file_crypto(&self) -> Result<FileCrypto> {
+        let key = hex::decode(&self.key).context("Invalid key")?;
+        let iv = hex::decode(&self.iv).context("Invalid IV")?;
+        FileCrypto::new(&self.schema, &key, &iv)
+    }
+}
+
+impl TaskSpec {
+    /// Parse and validate a task specification.
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let spec: TaskSpec = serde_yaml::from_str(content)?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Check everything that can be checked before submitting the task, so
+    /// that an invalid specification does not leave registered files or
+    /// half-assigned tasks behind.
+    pub fn validate(&self) -> Result<()> {
+        ExternalID::try_from(self.function_id.as_str())
+            .with_context(|| format!("Invalid function id: {}", self.function_id))?;
+        Executor::try_from(self.executor.as_str())?;
+
+        for (slot, spec) in &self.inputs {
+            spec.validate(true)
+                .with_context(|| format!("Invalid input {}", slot))?;
+        }
+        for (slot, spec) in &self.outputs {
+            spec.validate(false)
+                .with_context(|| format!("Invalid output {}", slot))?;
+        }
+        for slot in self.inputs.keys() {
+            ensure!(
+                !self.outputs.contains_key(slot),
+                "Slot {} is both an input and an output",
+                slot
+            );
+        }
+
+        if !self.approvals.is_empty() {
+            let approvals: BTreeSet<&String> = self.approvals.iter().collect();
+            ensure!(
+                approvals.len() == self.approvals.len(),
+                "Duplicated approvals"
+            );
+            let participants = self.participants();
+            if let Some(user) = approvals.difference(&participants).next() {
+                bail!("{} approves the task without owning any of its data", user);
+            }
+            if let Some(user) = participants.difference(&approvals).next() {
+                bail!(
+                    "{} owns data of the task but is not listed in approvals",
+                    user
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Owners of the inputs and outputs of the task.
+    pub fn participants(&self) -> BTreeSet<&String> {
+        self.inputs
+            .values()
+            .chain(self.outputs.values())
+            .flat_map(|spec| spec.owners.iter())
+            .collect()
+    }
+}
+
+impl SlotSpec {
+    fn validate(&self, is_input: bool) -> Result<()> {
+        ensure!(!self.owners.is_empty(), "No owners");
+        match (&self.file, &self.data_id) {
+            (Some(_), Some(_)) => bail!("Both file and data_id are given"),
+            (Some(file), None) => {
+                Url::parse(&file.url)?;
+                match (&file.cmac, is_input) {
+                    (Some(cmac), true) => {
+                        FileAuthTag::from_hex(cmac)?;
+                    }
+                    (None, true) => bail!("CMAC is required for input files"),
+                    (Some(_), false) => bail!("CMAC is not needed for output files"),
+                    (None, false) => (),
+                }
+                file.crypto.file_crypto()?;
+            }
+            (None, Some(data_id)) => {
+                ExternalID::try_from(data_id.as_str())?;
+            }
+            (None, None) => (),
+        }
+        Ok(())
+    }
+}
+
+impl FrontendClient {
+    /// Create the task of `spec`, register its files and assign them along
+    /// with the given data to the task. Returns the task id.
+    pub fn submit_task_spec(&mut self, spec: &TaskSpec) -> Result<String> {
+        spec.validate()?;
+
+        let ownership = |slots: &BTreeMap<String, SlotSpec>| {
+            let ownership: HashMap<String, Vec<String>> = slots
+                .iter()
+                .map(|(slot, spec)| (slot.to_owned(), spec.owners.clone()))
+                .collect();
+            Some(ownership).filter(|o| !o.is_empty())
+        };
+        let arguments: HashMap<String, serde_json::Value> = spec
+            .arguments
+            .iter()
+            .map(|(k, v)| (k.to_owned(), v.clone()))
+            .collect();
+        let task_id = self.create_task(
+            &spec.function_id,
+            Some(arguments).filter(|a| !a.is_empty()),
+            &spec.executor,
+            ownership(&spec.inputs),
+            ownership(&spec.outputs),
+        )?;
+
+        let mut inputs = HashMap::new();
+        for (slot, slot_spec) in &spec.inputs {
+            let data_id = match (&slot_spec.file, &slot_spec.data_id) {
+                (Some(file), _) => {
+                    // Checked by validate
+                    let cmac = FileAuthTag::from_hex(file.cmac.as_deref().unwrap_or_default())?;
+                    self.register_input_file(
+                        &file.url,
+                        &cmac.to_bytes(),
+                        file.crypto.file_crypto()?,
+                    )?
+                }
+                (None, Some(data_id)) => data_id.to_owned(),
+                (None, None) => continue,
+            };
+            inputs.insert(slot.to_owned(), data_id);
+        }
+        let mut outputs = HashMap::new();
+        for (slot, slot_spec) in &spec.outputs {
+            let data_id = match (&slot_spec.file, &slot_spec.data_id) {
+                (Some(file), _) => {
+                    self.register_output_file(&file.url, file.crypto.file_crypto()?)?
+                }
+                (None, Some(data_id)) => data_id.to_owned(),
+                (None, None) => continue,
+            };
+            outputs.insert(slot.to_owned(), data_id);
+        }
+        if !inputs.is_empty() || !outputs.is_empty() {
+            self.assign_data(&task_id, Some(inputs), Some(outputs))?;
+        }
+
+        Ok(task_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"
+function_id: function-00000000-0000-0000-0000-000000000001
+executor: builtin
+arguments:
+  message: Hello, Teaclave!
+  times: 3
+inputs:
+  input:
+    owners: [alice]
+    file:
+      url: https://storage.example.com/input?token
+      cmac: 00000000000000000000000000000000
+      crypto:
+        schema: teaclave-file-128
+        key: 00000000000000000000000000000000
+outputs:
+  output:
+    owners: [alice, bob]
+    data_id: output-00000000-0000-0000-0000-000000000002
+approvals: [alice, bob]
+"#;
+
+    #[test]
+    fn test_task_spec_from_yaml() {
+        let spec = TaskSpec::from_yaml(SPEC).unwrap();
+        assert_eq!(spec.executor, "builtin");
+        assert_eq!(spec.arguments["times"], serde_json::json!(3));
+        assert_eq!(spec.inputs["input"].owners, vec!["alice"]);
+        assert_eq!(spec.participants().len(), 2);
+        assert_eq!(TaskSpec::from_yaml(&spec.to_yaml().unwrap()).unwrap(), spec);
+    }
+
+    #[test]
+    fn test_task_spec_validate() {
+        let spec = TaskSpec::from_yaml(SPEC).unwrap();
+
+        let mut invalid = spec.clone();
+        invalid.approvals.pop();
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = spec.clone();
+        invalid.approvals.push("mallory".to_string());
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = spec.clone();
+        invalid
+            .inputs
+            .get_mut("input")
+            .unwrap()
+            .file
+            .as_mut()
+            .unwrap()
+            .cmac = None;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = spec.clone();
+        invalid.executor = "unknown".to_string();
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = spec;
+        invalid.outputs.get_mut("output").unwrap().owners.clear();
+        assert!(invalid.validate().is_err());
+
+        assert!(TaskSpec::from_yaml(&SPEC.replace("owners", "owner")).is_err());
+    }
+}
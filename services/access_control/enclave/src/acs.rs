@@ -19,15 +19,23 @@ use anyhow::{bail, Result};
 use casbin::prelude::*;
 use csv::{ReaderBuilder, StringRecord};
 
+const MODEL_TEXT: &str = include_str!("../../model.conf");
+/// The role accepted by the matcher of the model for every API
+const PLATFORM_ADMIN: &str = "PlatformAdmin";
+/// The built-in policy, used until platform admins change it.
+pub(crate) const POLICY_TEXT: &str = include_str!("../../policy.csv");
+
 pub async fn init_memory_enforcer() -> Result<Enforcer> {
-    const MODEL_TEXT: &str = include_str!("../../model.conf");
-    const POLICY_TEXT: &str = include_str!("../../policy.csv");
+    init_enforcer_with_policy(POLICY_TEXT).await
+}
 
+/// Build an enforcer for policies in the format of `policy.csv`.
+pub(crate) async fn init_enforcer_with_policy(policy: &str) -> Result<Enforcer> {
     let model = DefaultModel::from_str(MODEL_TEXT).await?;
     let adapter = MemoryAdapter::default();
     let mut enforcer = Enforcer::new(model, adapter).await?;
 
-    let (general, grouping) = parse_policy_str(POLICY_TEXT)?;
+    let (general, grouping) = parse_policy_str(policy)?;
     enforcer.add_policies(general).await?;
     enforcer.add_grouping_policies(grouping).await?;
 
@@ -36,8 +44,35 @@ pub async fn init_memory_enforcer() -> Result<Enforcer> {
 
 type Policy = Vec<String>;
 
+/// The rule in the policy of `enforcer` accepting `role` to call `api`, in
+/// the format of `policy.csv`, or None if the call is denied.
+pub(crate) fn matched_api_rule(enforcer: &Enforcer, role: &str, api: &str) -> Option<String> {
+    if role == PLATFORM_ADMIN {
+        return Some(format!("r.sub == \"{}\"", PLATFORM_ADMIN));
+    }
+
+    // Roles inherited by `role` through the grouping policies
+    let grouping = enforcer.get_grouping_policy();
+    let mut roles = vec![role.to_owned()];
+    let mut i = 0;
+    while i < roles.len() {
+        for rule in &grouping {
+            if rule[0] == roles[i] && !roles.contains(&rule[1]) {
+                roles.push(rule[1].clone());
+            }
+        }
+        i += 1;
+    }
+
+    enforcer
+        .get_filtered_policy(1, vec![api.to_owned()])
+        .into_iter()
+        .find(|rule| roles.contains(&rule[0]))
+        .map(|rule| format!("p,{}", rule.join(",")))
+}
+
 /// Parse casbin polices in bytes to general and grouping policies
-fn parse_policy_str(polices: &str) -> Result<(Vec<Policy>, Vec<Policy>)> {
+pub(crate) fn parse_policy_str(polices: &str) -> Result<(Vec<Policy>, Vec<Policy>)> {
     let mut general = Vec::new();
     let mut grouping = Vec::new();
 
@@ -81,6 +116,24 @@ pub mod tests {
 
         assert!(e.enforce(("PlatformAdmin", "arbitrary_api")).unwrap());
         assert!(e.enforce(("PlatformAdmin", "query_audit_logs")).unwrap());
+        assert!(e.enforce(("PlatformAdmin", "export_audit_logs")).unwrap());
+        assert!(e.enforce(("PlatformAdmin", "rebuild_audit_index")).unwrap());
+        assert!(e.enforce(("PlatformAdmin", "export_metadata")).unwrap());
+        assert!(e
+            .enforce(("PlatformAdmin", "decommission_storage"))
+            .unwrap());
+        assert!(e
+            .enforce(("PlatformAdmin", "restore_storage_snapshot"))
+            .unwrap());
+        assert!(e
+            .enforce(("PlatformAdmin", "promote_storage_replica"))
+            .unwrap());
+        assert!(e.enforce(("PlatformAdmin", "set_user_quota")).unwrap());
+        assert!(e.enforce(("PlatformAdmin", "get_metrics")).unwrap());
+        assert!(e.enforce(("PlatformAdmin", "manage_policy")).unwrap());
+        assert!(e.enforce(("PlatformAdmin", "set_user_attributes")).unwrap());
+        assert!(e.enforce(("PlatformAdmin", "list_tasks")).unwrap());
+        assert!(e.enforce(("PlatformAdmin", "get_platform_stats")).unwrap());
 
         assert!(!e.enforce(("Invalid", "register_function")).unwrap());
         assert!(!e.enforce(("Invalid", "register_input_file")).unwrap());
@@ -89,15 +142,54 @@ pub mod tests {
 
         assert!(e.enforce(("FunctionOwner", "register_function")).unwrap());
         assert!(e.enforce(("FunctionOwner", "update_function")).unwrap());
+        assert!(e
+            .enforce(("FunctionOwner", "begin_function_upload"))
+            .unwrap());
+        assert!(e
+            .enforce(("FunctionOwner", "upload_function_chunk"))
+            .unwrap());
+        assert!(e.enforce(("FunctionOwner", "commit_function")).unwrap());
         assert!(e.enforce(("FunctionOwner", "delete_function")).unwrap());
         assert!(e.enforce(("FunctionOwner", "disable_function")).unwrap());
         assert!(e.enforce(("FunctionOwner", "get_function")).unwrap());
         assert!(e.enforce(("FunctionOwner", "list_functions")).unwrap());
+        assert!(e.enforce(("FunctionOwner", "search_functions")).unwrap());
         assert!(e
             .enforce(("FunctionOwner", "get_function_usage_stats"))
             .unwrap());
+        assert!(e
+            .enforce(("FunctionOwner", "query_function_usage_records"))
+            .unwrap());
         assert!(!e.enforce(("FunctionOwner", "get_task")).unwrap());
         assert!(!e.enforce(("FunctionOwner", "query_audit_logs")).unwrap());
+        assert!(!e.enforce(("FunctionOwner", "export_audit_logs")).unwrap());
+        assert!(!e.enforce(("DataOwner", "rebuild_audit_index")).unwrap());
+        assert!(!e
+            .enforce(("DataOwnerManager", "get_audit_index_status"))
+            .unwrap());
+        assert!(!e.enforce(("FunctionOwner", "export_metadata")).unwrap());
+        assert!(!e
+            .enforce(("FunctionOwner", "decommission_storage"))
+            .unwrap());
+        assert!(!e
+            .enforce(("FunctionOwner", "create_storage_snapshot"))
+            .unwrap());
+        assert!(!e.enforce(("DataOwner", "promote_storage_replica")).unwrap());
+        assert!(!e.enforce(("FunctionOwner", "set_user_quota")).unwrap());
+        assert!(!e.enforce(("DataOwner", "grant_debug_errors")).unwrap());
+        assert!(!e.enforce(("DataOwner", "get_user_quota")).unwrap());
+        assert!(!e.enforce(("DataOwner", "get_metrics")).unwrap());
+        assert!(!e.enforce(("DataOwner", "list_tasks")).unwrap());
+        assert!(!e.enforce(("FunctionOwner", "get_platform_stats")).unwrap());
+        assert!(!e.enforce(("DataOwner", "manage_policy")).unwrap());
+        assert!(!e.enforce(("FunctionOwner", "manage_policy")).unwrap());
+        assert!(!e.enforce(("DataOwner", "set_user_attributes")).unwrap());
+        assert!(!e.enforce(("DataOwner", "get_user_attributes")).unwrap());
+        assert!(e.enforce(("DataOwner", "set_data_attributes")).unwrap());
+        assert!(e.enforce(("DataOwner", "get_data_attributes")).unwrap());
+        assert!(e.enforce(("DataOwner", "set_input_access_policy")).unwrap());
+        assert!(e.enforce(("DataOwner", "delete_data")).unwrap());
+        assert!(e.enforce(("DataOwner", "query_data_lineage")).unwrap());
 
         assert!(e.enforce(("DataOwner", "register_input_file")).unwrap());
         assert!(e.enforce(("DataOwner", "register_output_file")).unwrap());
@@ -113,14 +205,52 @@ pub mod tests {
         assert!(e.enforce(("DataOwnerManager", "get_task")).unwrap());
         assert!(e.enforce(("DataOwnerManager", "assign_data")).unwrap());
         assert!(e.enforce(("DataOwnerManager", "approve_task")).unwrap());
+        assert!(e.enforce(("DataOwnerManager", "reject_task")).unwrap());
         assert!(e.enforce(("DataOwnerManager", "invoke_task")).unwrap());
         assert!(e.enforce(("DataOwnerManager", "cancel_task")).unwrap());
+        assert!(e
+            .enforce(("DataOwnerManager", "get_consent_records"))
+            .unwrap());
+        assert!(e.enforce(("DataOwnerManager", "get_task_log")).unwrap());
+        assert!(e
+            .enforce(("DataOwnerManager", "register_webhook_sink"))
+            .unwrap());
+        assert!(e
+            .enforce(("DataOwnerManager", "set_notification_preferences"))
+            .unwrap());
         assert!(e.enforce(("DataOwnerManager", "get_function")).unwrap());
         assert!(e.enforce(("DataOwnerManager", "list_functions")).unwrap());
+        assert!(e.enforce(("DataOwnerManager", "search_functions")).unwrap());
         assert!(e
             .enforce(("DataOwnerManager", "get_function_usage_stats"))
             .unwrap());
+        assert!(!e
+            .enforce(("DataOwnerManager", "query_function_usage_records"))
+            .unwrap());
         assert!(!e.enforce(("DataOwner", "register_function")).unwrap());
+        assert!(!e.enforce(("DataOwner", "commit_function")).unwrap());
         assert!(!e.enforce(("DataOwnerManager", "query_audit_logs")).unwrap());
+        assert!(!e
+            .enforce(("DataOwnerManager", "get_storage_decommission_status"))
+            .unwrap());
+    }
+
+    pub async fn test_matched_api_rule() {
+        let e = init_memory_enforcer().await.unwrap();
+
+        assert_eq!(
+            matched_api_rule(&e, "PlatformAdmin", "arbitrary_api").as_deref(),
+            Some("r.sub == \"PlatformAdmin\"")
+        );
+        assert_eq!(
+            matched_api_rule(&e, "DataOwner", "create_task").as_deref(),
+            Some("p,rule_data_owner,create_task")
+        );
+        assert_eq!(
+            matched_api_rule(&e, "FunctionOwner", "get_function").as_deref(),
+            Some("p,rule_function_owner,get_function")
+        );
+        assert_eq!(matched_api_rule(&e, "DataOwner", "register_function"), None);
+        assert_eq!(matched_api_rule(&e, "Invalid", "get_function"), None);
     }
 }
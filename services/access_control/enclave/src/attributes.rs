@@ -0,0 +1,221 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Attributes of users and data, and the attribute-based policy for data
+//! access.
+//!
+//! A user may access a data object if:
+//! - the `clearance` of the user is not lower than the `classification` of
+//!   the data, where both are levels in `LEVELS` and default to `public`;
+//! - for any other attribute of the data, e.g., `department`, the user has
+//!   the same value.
+//!
+//! Data attributes are keyed by the uuid of the data, so that an input
+//! registered from an output shares the attributes of the output.
+
+use crate::error::TeaclavAccessControlError;
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use teaclave_proto::teaclave_access_control_service::{Denial, DenialCode};
+use teaclave_proto::teaclave_storage_service::{GetRequest, PutRequest, TeaclaveStorageClient};
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Code;
+use teaclave_types::ExternalID;
+use tokio::sync::Mutex;
+
+pub(crate) type Attributes = HashMap<String, String>;
+
+pub(crate) const CLEARANCE: &str = "clearance";
+pub(crate) const CLASSIFICATION: &str = "classification";
+/// Levels of clearance and classification from the lowest.
+pub(crate) const LEVELS: [&str; 4] = ["public", "internal", "confidential", "secret"];
+
+const USER_ATTRIBUTES_PREFIX: &str = "user_attributes";
+const DATA_ATTRIBUTES_PREFIX: &str = "data_attributes";
+
+fn level_of(attributes: &Attributes, key: &str) -> usize {
+    attributes
+        .get(key)
+        .and_then(|value| LEVELS.iter().position(|level| level == value))
+        .unwrap_or(0)
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+pub(crate) fn validate_attributes(
+    attributes: &Attributes,
+) -> Result<(), TeaclavAccessControlError> {
+    for (key, value) in attributes {
+        if !is_identifier(key) || !is_identifier(value) {
+            return Err(TeaclavAccessControlError::InvalidAttribute(format!(
+                "{}={}",
+                key, value
+            )));
+        }
+        if (key == CLEARANCE || key == CLASSIFICATION) && !LEVELS.contains(&value.as_str()) {
+            return Err(TeaclavAccessControlError::InvalidAttribute(format!(
+                "{} should be one of {:?}",
+                key, LEVELS
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the failed constraint if the user may not access the data.
+pub(crate) fn check_data_access(user: &Attributes, data: &Attributes) -> Option<Denial> {
+    let clearance = level_of(user, CLEARANCE);
+    let classification = level_of(data, CLASSIFICATION);
+    if clearance < classification {
+        return Some(Denial::new(
+            DenialCode::ClearanceTooLow,
+            format!("{} >= {}", CLEARANCE, LEVELS[classification]),
+        ));
+    }
+    data.iter()
+        .filter(|(key, _)| key.as_str() != CLASSIFICATION)
+        .find(|(key, value)| user.get(key.as_str()) != Some(value))
+        .map(|(key, value)| {
+            Denial::new(
+                DenialCode::AttributeRequired,
+                format!("{} = {}", key, value),
+            )
+        })
+}
+
+fn data_key(data_id: &str) -> Result<String, TeaclavAccessControlError> {
+    let data_id = ExternalID::try_from(data_id)
+        .map_err(|_| TeaclavAccessControlError::InvalidAttribute("invalid data id".into()))?;
+    Ok(format!("{}-{}", DATA_ATTRIBUTES_PREFIX, data_id.uuid))
+}
+
+fn user_key(user_id: &str) -> String {
+    format!("{}-{}", USER_ATTRIBUTES_PREFIX, user_id)
+}
+
+#[derive(Clone)]
+pub(crate) struct AttributeStore {
+    storage_client: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+}
+
+impl AttributeStore {
+    pub(crate) fn new(storage_client: Arc<Mutex<TeaclaveStorageClient<Channel>>>) -> Self {
+        Self { storage_client }
+    }
+
+    pub(crate) async fn user_attributes(&self, user_id: &str) -> Result<Attributes> {
+        self.read(&user_key(user_id)).await
+    }
+
+    pub(crate) async fn set_user_attributes(
+        &self,
+        user_id: &str,
+        attributes: &Attributes,
+    ) -> Result<()> {
+        self.write(&user_key(user_id), attributes).await
+    }
+
+    pub(crate) async fn data_attributes(
+        &self,
+        data_id: &str,
+    ) -> Result<Attributes, TeaclavAccessControlError> {
+        let key = data_key(data_id)?;
+        Ok(self.read(&key).await?)
+    }
+
+    pub(crate) async fn set_data_attributes(
+        &self,
+        data_id: &str,
+        attributes: &Attributes,
+    ) -> Result<(), TeaclavAccessControlError> {
+        let key = data_key(data_id)?;
+        Ok(self.write(&key, attributes).await?)
+    }
+
+    // Users and data without attributes have none stored.
+    async fn read(&self, key: &str) -> Result<Attributes> {
+        let request = GetRequest::new(key.as_bytes());
+        let response = self.storage_client.lock().await.get(request).await;
+        match response {
+            Ok(response) => Ok(serde_json::from_slice(&response.into_inner().value)?),
+            Err(status) if status.code() == Code::NotFound => Ok(Attributes::new()),
+            Err(status) => Err(anyhow!("Failed to read attributes: {:?}", status)),
+        }
+    }
+
+    async fn write(&self, key: &str, attributes: &Attributes) -> Result<()> {
+        let value = serde_json::to_vec(attributes)?;
+        let request = PutRequest::new(key.as_bytes(), value);
+        self.storage_client.lock().await.put(request).await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    fn attributes(pairs: &[(&str, &str)]) -> Attributes {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    pub fn test_check_data_access() {
+        let analyst = attributes(&[("department", "finance"), ("clearance", "confidential")]);
+        let intern = attributes(&[("department", "finance")]);
+
+        let report = attributes(&[("department", "finance"), ("classification", "internal")]);
+        assert!(check_data_access(&analyst, &report).is_none());
+        assert!(check_data_access(&intern, &report).is_some());
+
+        let secret = attributes(&[("classification", "secret")]);
+        let denial = check_data_access(&analyst, &secret).unwrap();
+        assert_eq!(denial.code(), DenialCode::ClearanceTooLow);
+        assert_eq!(denial.constraint, "clearance >= secret");
+
+        let hr_data = attributes(&[("department", "hr")]);
+        let denial = check_data_access(&analyst, &hr_data).unwrap();
+        assert_eq!(denial.code(), DenialCode::AttributeRequired);
+        assert_eq!(denial.constraint, "department = hr");
+
+        // Data without attributes is public
+        assert!(check_data_access(&Attributes::new(), &Attributes::new()).is_none());
+    }
+
+    pub fn test_validate_attributes() {
+        assert!(validate_attributes(&attributes(&[("clearance", "secret")])).is_ok());
+        assert!(validate_attributes(&attributes(&[("clearance", "top")])).is_err());
+        assert!(validate_attributes(&attributes(&[("department", "")])).is_err());
+        assert!(validate_attributes(&attributes(&[("a,b", "c")])).is_err());
+    }
+
+    pub fn test_data_key() {
+        let output = "output-00000000-0000-0000-0000-000000000001";
+        let input = "input-00000000-0000-0000-0000-000000000001";
+        assert_eq!(data_key(output).unwrap(), data_key(input).unwrap());
+        assert!(data_key("invalid").is_err());
+    }
+}
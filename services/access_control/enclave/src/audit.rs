@@ -0,0 +1,274 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Audit of the access control decisions, with the subject, the object, the
+//! decision and the rule it is made by. Decisions are sent to the auditor in
+//! the management service in the background, batching those made while the
+//! previous batch is being sent, and share the trace id of the request they
+//! are made for, e.g., with the entry of the API call in the frontend service.
+
+use std::fmt;
+
+use teaclave_proto::teaclave_access_control_service::Denial;
+use teaclave_proto::teaclave_management_service::{SaveLogsRequest, TeaclaveManagementClient};
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Request;
+use teaclave_types::{Entry, EntryBuilder};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// Most decisions sent in one request to the auditor
+const MAX_BATCH_SIZE: usize = 100;
+/// The rule of data decisions accepted by the attributes.
+const ATTRIBUTE_RULE: &str = "data attributes";
+
+pub(crate) enum AccessDecision<'a> {
+    /// The role `subject` calling `api`, accepted by `rule` if any
+    Api {
+        subject: &'a str,
+        api: &'a str,
+        rule: Option<String>,
+    },
+    Data {
+        subject: &'a str,
+        data_id: &'a str,
+        denial: Option<&'a Denial>,
+    },
+    /// The participants of a staged task accessing its data
+    StagedTask {
+        subjects: &'a [String],
+        task_id: &'a str,
+        denial: Option<&'a Denial>,
+    },
+    /// The participants of a staged task accessing `objects` of it in a batch
+    Batch {
+        subjects: &'a [String],
+        task_id: &'a str,
+        objects: usize,
+        denials: &'a [&'a Denial],
+    },
+}
+
+impl AccessDecision<'_> {
+    fn subject(&self) -> String {
+        match self {
+            Self::Api { subject, .. } | Self::Data { subject, .. } => subject.to_string(),
+            Self::StagedTask { subjects, .. } | Self::Batch { subjects, .. } => subjects.join(","),
+        }
+    }
+
+    fn accepted(&self) -> bool {
+        match self {
+            Self::Api { rule, .. } => rule.is_some(),
+            Self::Data { denial, .. } | Self::StagedTask { denial, .. } => denial.is_none(),
+            Self::Batch { denials, .. } => denials.is_empty(),
+        }
+    }
+}
+
+impl fmt::Display for AccessDecision<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decision = if self.accepted() { "accept" } else { "deny" };
+        match self {
+            Self::Api { subject, api, rule } => write!(
+                f,
+                "access authorize_api: {} to {}: {} by {}",
+                subject,
+                api,
+                decision,
+                rule.as_deref().unwrap_or("no rule")
+            ),
+            Self::Data {
+                subject,
+                data_id,
+                denial,
+            } => write!(
+                f,
+                "access authorize_data: {} to {}: {} by {}",
+                subject,
+                data_id,
+                decision,
+                denial.map_or_else(|| ATTRIBUTE_RULE.to_string(), Denial::to_string)
+            ),
+            Self::StagedTask {
+                subjects,
+                task_id,
+                denial,
+            } => write!(
+                f,
+                "access authorize_staged_task: {} to {}: {} by {}",
+                subjects.join(","),
+                task_id,
+                decision,
+                denial.map_or_else(|| ATTRIBUTE_RULE.to_string(), Denial::to_string)
+            ),
+            Self::Batch {
+                subjects,
+                task_id,
+                objects,
+                denials,
+            } => write!(
+                f,
+                "access authorize_batch: {} to {} ({} objects): {} by {}",
+                subjects.join(","),
+                task_id,
+                objects,
+                decision,
+                if denials.is_empty() {
+                    ATTRIBUTE_RULE.to_string()
+                } else {
+                    denials
+                        .iter()
+                        .map(|d| d.to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                }
+            ),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct DecisionAuditor {
+    sender: UnboundedSender<Entry>,
+}
+
+impl DecisionAuditor {
+    /// Start sending the decisions to the auditor in the background. The
+    /// returned task ends after the decisions recorded before all clones of
+    /// the auditor are dropped are sent.
+    pub(crate) fn new(
+        management_client: TeaclaveManagementClient<Channel>,
+    ) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = unbounded_channel();
+        let handle = tokio::spawn(send_decisions(management_client, receiver));
+        (Self { sender }, handle)
+    }
+
+    pub(crate) fn record<T>(&self, request: &Request<T>, decision: AccessDecision) {
+        let entry = decision_entry(request, &decision);
+        // Fails only if the sending task is gone with the runtime
+        let _ = self.sender.send(entry);
+    }
+}
+
+async fn send_decisions(
+    mut client: TeaclaveManagementClient<Channel>,
+    mut receiver: UnboundedReceiver<Entry>,
+) {
+    while let Some(entry) = receiver.recv().await {
+        let mut logs = vec![entry];
+        while logs.len() < MAX_BATCH_SIZE {
+            match receiver.try_recv() {
+                Ok(entry) => logs.push(entry),
+                Err(_) => break,
+            }
+        }
+        let count = logs.len();
+        if let Err(e) = client.save_logs(SaveLogsRequest::new(logs)).await {
+            log::warn!(
+                "Failed to send the audit logs of {} access decisions: {:?}",
+                count,
+                e
+            );
+        }
+    }
+}
+
+fn decision_entry<T>(request: &Request<T>, decision: &AccessDecision) -> Entry {
+    let trace_id = request
+        .metadata()
+        .get("trace_id")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default();
+
+    EntryBuilder::new()
+        .user(decision.subject())
+        .message(decision.to_string())
+        .result(decision.accepted())
+        .trace_id(trace_id.to_owned())
+        .build()
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_proto::teaclave_access_control_service::DenialCode;
+
+    pub fn test_decision_entry() {
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("trace_id", "trace".parse().unwrap());
+        let decision = AccessDecision::Api {
+            subject: "DataOwner",
+            api: "create_task",
+            rule: Some("p,rule_data_owner,create_task".to_owned()),
+        };
+        let entry = decision_entry(&request, &decision);
+        assert_eq!(entry.user(), "DataOwner");
+        assert_eq!(
+            entry.message(),
+            "access authorize_api: DataOwner to create_task: accept by p,rule_data_owner,create_task"
+        );
+        assert!(entry.result());
+        assert_eq!(entry.trace_id(), "trace");
+
+        let denial = Denial::new(DenialCode::ClearanceTooLow, "clearance >= secret")
+            .user_id("alice")
+            .data_id("input-1");
+        let decision = AccessDecision::Data {
+            subject: "alice",
+            data_id: "input-1",
+            denial: Some(&denial),
+        };
+        let entry = decision_entry(&Request::new(()), &decision);
+        assert_eq!(
+            entry.message(),
+            "access authorize_data: alice to input-1: deny by alice to input-1: \
+             clearance >= secret is required"
+        );
+        assert!(!entry.result());
+
+        let subjects = vec!["alice".to_owned(), "bob".to_owned()];
+        let decision = AccessDecision::StagedTask {
+            subjects: &subjects,
+            task_id: "task-1",
+            denial: None,
+        };
+        let entry = decision_entry(&Request::new(()), &decision);
+        assert_eq!(entry.user(), "alice,bob");
+        assert_eq!(
+            entry.message(),
+            "access authorize_staged_task: alice,bob to task-1: accept by data attributes"
+        );
+
+        let decision = AccessDecision::Batch {
+            subjects: &subjects,
+            task_id: "task-1",
+            objects: 3,
+            denials: &[&denial],
+        };
+        let entry = decision_entry(&Request::new(()), &decision);
+        assert_eq!(
+            entry.message(),
+            "access authorize_batch: alice,bob to task-1 (3 objects): deny by alice to \
+             input-1: clearance >= secret is required"
+        );
+        assert!(!entry.result());
+    }
+}
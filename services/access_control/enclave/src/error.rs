@@ -21,10 +21,28 @@ use thiserror::Error;
 pub(crate) enum TeaclavAccessControlError {
     #[error("access control error")]
     AccessControlError,
+    #[error("permission denied")]
+    PermissionDenied,
+    #[error("invalid policy rule: {0}")]
+    InvalidPolicyRule(String),
+    #[error("invalid attribute: {0}")]
+    InvalidAttribute(String),
+    #[error("service internal error")]
+    Service(#[from] anyhow::Error),
 }
 
 impl From<TeaclavAccessControlError> for teaclave_rpc::Status {
     fn from(error: TeaclavAccessControlError) -> Self {
-        teaclave_rpc::Status::permission_denied(error.to_string())
+        log::debug!("TeaclavAccessControlError: {:?}", error);
+        match error {
+            TeaclavAccessControlError::InvalidPolicyRule(_)
+            | TeaclavAccessControlError::InvalidAttribute(_) => {
+                teaclave_rpc::Status::invalid_argument(error.to_string())
+            }
+            TeaclavAccessControlError::Service(_) => {
+                teaclave_rpc::Status::internal(error.to_string())
+            }
+            _ => teaclave_rpc::Status::permission_denied(error.to_string()),
+        }
     }
 }
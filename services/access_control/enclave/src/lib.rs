@@ -31,12 +31,19 @@ use teaclave_config::build::{
 };
 use teaclave_config::RuntimeConfig;
 use teaclave_proto::teaclave_access_control_service::TeaclaveAccessControlServer;
+use teaclave_proto::teaclave_management_service::TeaclaveManagementClient;
 use teaclave_rpc::{config::SgxTrustedTlsServerConfig, transport::Server};
-use teaclave_service_enclave_utils::ServiceEnclave;
+use teaclave_service_enclave_utils::{
+    create_trusted_management_endpoint, create_trusted_storage_endpoint, drain_signal, drained,
+    ServiceEnclave,
+};
 use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
 
 mod acs;
+mod attributes;
+mod audit;
 mod error;
+mod policy;
 mod service;
 
 // Sets the number of worker threads the Runtime will use.
@@ -47,7 +54,10 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
 
     let listen_address = config.internal_endpoints.access_control.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(config)?;
+    let verification_policy = verifier::VerificationPolicy::from_teaclave_config(config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
+        .endorsement_cache(config.attestation.endorsement_cache_path("access_control"))
+        .reuse_cached_endorsement(config.attestation.reuse_cached_endorsement)
         .generate_and_endorse()?
         .attested_tls_config()
         .ok_or_else(|| anyhow!("cannot get attested TLS config"))?;
@@ -66,26 +76,64 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         })
         .collect::<Result<_>>()?;
 
-    let server_config = SgxTrustedTlsServerConfig::from_attested_tls_config(attested_tls_config)?
-        .attestation_report_verifier(
-            accepted_enclave_attrs,
-            AS_ROOT_CA_CERT,
-            verifier::universal_quote_verifier,
-        )?
-        .into();
+    let server_config =
+        SgxTrustedTlsServerConfig::from_attested_tls_config(attested_tls_config.clone())?
+            .attestation_report_verifier(
+                accepted_enclave_attrs,
+                AS_ROOT_CA_CERT,
+                verifier::universal_quote_verifier,
+                verification_policy.clone(),
+            )?
+            .mutual_attestation(true)
+            .min_protocol_version(config.rpc.min_protocol_version)
+            .session_freshness(std::time::Duration::from_secs(
+                teaclave_config::build::GRPC_CONFIG.session_freshness_secs,
+            ))
+            .into();
     info!(" Starting Access control: Server config setup finished ...");
 
-    let service = service::TeaclaveAccessControlService::new().await;
+    let storage_service_endpoint = create_trusted_storage_endpoint(
+        &config.internal_endpoints.storage.advertised_address,
+        &enclave_info,
+        AS_ROOT_CA_CERT,
+        verifier::universal_quote_verifier,
+        verification_policy.clone(),
+        attested_tls_config.clone(),
+    )?;
+    info!(" Starting Access control: setup storage endpoint finished ...");
+
+    // The management service connects to this service when it starts, so it
+    // is connected to when the first decision is sent to the auditor.
+    let management_service_endpoint = create_trusted_management_endpoint(
+        &config.internal_endpoints.management.advertised_address,
+        &enclave_info,
+        AS_ROOT_CA_CERT,
+        verifier::universal_quote_verifier,
+        verification_policy,
+        attested_tls_config,
+    )?;
+    let management_client = TeaclaveManagementClient::new_with_builtin_config(
+        management_service_endpoint.connect_lazy(),
+    );
+    let (auditor, auditor_handle) = audit::DecisionAuditor::new(management_client);
+
+    let service =
+        service::TeaclaveAccessControlService::new(storage_service_endpoint, auditor).await?;
 
     info!("Starting Access control: start listening ...");
-    Server::builder()
+    let served = Server::builder()
         .tls_config(server_config)
         .map_err(|_| anyhow::anyhow!("TeaclaveFrontendServer tls config error"))?
         .add_service(TeaclaveAccessControlServer::new_with_builtin_config(
             service,
         ))
-        .serve(listen_address)
-        .await?;
+        .serve_with_shutdown(listen_address, drain_signal())
+        .await;
+    // The decisions still queued are sent once the service, and the auditor
+    // with it, is dropped by the server.
+    auditor_handle.await?;
+    drained();
+    served?;
     Ok(())
 }
 
@@ -115,6 +163,7 @@ fn handle_init_enclave(_: &InitEnclaveInput) -> TeeServiceResult<InitEnclaveOutp
 
 #[handle_ecall]
 fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<FinalizeEnclaveOutput> {
+    ServiceEnclave::drain();
     ServiceEnclave::finalize()?;
     Ok(FinalizeEnclaveOutput)
 }
@@ -132,6 +181,20 @@ pub mod tests {
     use teaclave_test_utils::*;
 
     pub fn run_tests() -> bool {
-        run_async_tests!(acs::tests::test_access_api,)
+        check_all_passed!(
+            run_tests!(
+                attributes::tests::test_check_data_access,
+                attributes::tests::test_validate_attributes,
+                attributes::tests::test_data_key,
+                audit::tests::test_decision_entry,
+                policy::tests::test_policy_rules_round_trip,
+                policy::tests::test_validate_rule,
+            ),
+            run_async_tests!(
+                acs::tests::test_access_api,
+                acs::tests::test_matched_api_rule,
+                policy::tests::test_enforcer_with_added_rule,
+            ),
+        )
     }
 }
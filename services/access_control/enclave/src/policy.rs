@@ -0,0 +1,194 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Access control policy persisted in the storage service.
+//!
+//! The policy is stored in the format of `policy.csv`, which is also used to
+//! seed the storage on the first start. Platform admins can add and remove
+//! rules at runtime, and the enforcer is rebuilt from the stored policy after
+//! every change.
+
+use crate::acs::{init_enforcer_with_policy, parse_policy_str, POLICY_TEXT};
+use crate::error::TeaclavAccessControlError;
+
+use anyhow::{anyhow, Result};
+use casbin::Enforcer;
+use std::sync::{Arc, RwLock};
+use teaclave_proto::teaclave_access_control_service::PolicyRule;
+use teaclave_proto::teaclave_storage_service::{GetRequest, PutRequest, TeaclaveStorageClient};
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Code;
+use tokio::sync::Mutex;
+
+const POLICY_KEY: &[u8] = b"access_control_policy";
+
+pub(crate) fn rules_from_str(policy: &str) -> Result<Vec<PolicyRule>> {
+    let (general, grouping) = parse_policy_str(policy)?;
+    let general = general
+        .into_iter()
+        .map(|values| PolicyRule::new("p", values));
+    let grouping = grouping
+        .into_iter()
+        .map(|values| PolicyRule::new("g", values));
+    Ok(general.chain(grouping).collect())
+}
+
+pub(crate) fn rules_to_string(rules: &[PolicyRule]) -> String {
+    rules
+        .iter()
+        .map(|rule| format!("{},{}\n", rule.ptype, rule.values.join(",")))
+        .collect()
+}
+
+/// Both kinds of rules of the model have two fields: `p, <role>, <api>` and
+/// `g, <user role>, <role>`.
+pub(crate) fn validate_rule(rule: &PolicyRule) -> Result<(), TeaclavAccessControlError> {
+    let invalid = |reason: &str| Err(TeaclavAccessControlError::InvalidPolicyRule(reason.into()));
+    if rule.ptype != "p" && rule.ptype != "g" {
+        return invalid("rule type should be p or g");
+    }
+    if rule.values.len() != 2 {
+        return invalid("rule should have two fields");
+    }
+    let is_valid_field = |v: &String| {
+        !v.is_empty()
+            && v.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+    if !rule.values.iter().all(is_valid_field) {
+        return invalid("rule fields should be non-empty identifiers");
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub(crate) struct PolicyStore {
+    storage_client: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+    enforcer: Arc<RwLock<Enforcer>>,
+    // Serializes changes so that concurrent updates are not lost
+    lock: Arc<Mutex<()>>,
+}
+
+impl PolicyStore {
+    pub(crate) fn new(
+        storage_client: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+        enforcer: Arc<RwLock<Enforcer>>,
+    ) -> Self {
+        Self {
+            storage_client,
+            enforcer,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Load the stored policy into the enforcer, seeding the storage with the
+    /// built-in policy if there is none yet.
+    pub(crate) async fn reload(&self) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let policy = self.read_policy().await?;
+        self.apply(&policy).await
+    }
+
+    pub(crate) async fn list(&self) -> Result<Vec<PolicyRule>> {
+        let policy = self.read_policy().await?;
+        rules_from_str(&policy)
+    }
+
+    /// Returns the rules after the change.
+    pub(crate) async fn add(&self, rule: PolicyRule) -> Result<Vec<PolicyRule>> {
+        self.update(|rules| {
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        })
+        .await
+    }
+
+    /// Returns the rules after the change.
+    pub(crate) async fn remove(&self, rule: &PolicyRule) -> Result<Vec<PolicyRule>> {
+        self.update(|rules| rules.retain(|r| r != rule)).await
+    }
+
+    async fn update(&self, f: impl FnOnce(&mut Vec<PolicyRule>)) -> Result<Vec<PolicyRule>> {
+        let _guard = self.lock.lock().await;
+        let mut rules = rules_from_str(&self.read_policy().await?)?;
+        f(&mut rules);
+        let policy = rules_to_string(&rules);
+        // Check the new policy builds before persisting it
+        let enforcer = init_enforcer_with_policy(&policy).await?;
+        self.write_policy(&policy).await?;
+        *self.enforcer.write().unwrap() = enforcer;
+        info!("Access control policy reloaded with {} rules", rules.len());
+        Ok(rules)
+    }
+
+    async fn apply(&self, policy: &str) -> Result<()> {
+        let enforcer = init_enforcer_with_policy(policy).await?;
+        *self.enforcer.write().unwrap() = enforcer;
+        Ok(())
+    }
+
+    async fn read_policy(&self) -> Result<String> {
+        let request = GetRequest::new(POLICY_KEY);
+        let response = self.storage_client.lock().await.get(request).await;
+        match response {
+            Ok(response) => Ok(String::from_utf8(response.into_inner().value)?),
+            Err(status) if status.code() == Code::NotFound => {
+                self.write_policy(POLICY_TEXT).await?;
+                Ok(POLICY_TEXT.to_string())
+            }
+            Err(status) => Err(anyhow!("Failed to read policy: {:?}", status)),
+        }
+    }
+
+    async fn write_policy(&self, policy: &str) -> Result<()> {
+        let request = PutRequest::new(POLICY_KEY, policy.as_bytes());
+        self.storage_client.lock().await.put(request).await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_policy_rules_round_trip() {
+        let rules = rules_from_str(POLICY_TEXT).unwrap();
+        assert!(rules.contains(&PolicyRule::new("p", ["rule_data_owner", "create_task"])));
+        assert!(rules.contains(&PolicyRule::new("g", ["DataOwner", "rule_data_owner"])));
+        assert_eq!(rules_from_str(&rules_to_string(&rules)).unwrap(), rules);
+    }
+
+    pub fn test_validate_rule() {
+        assert!(validate_rule(&PolicyRule::new("p", ["rule_auditor", "query_audit_logs"])).is_ok());
+        assert!(validate_rule(&PolicyRule::new("x", ["a", "b"])).is_err());
+        assert!(validate_rule(&PolicyRule::new("p", ["a"])).is_err());
+        assert!(validate_rule(&PolicyRule::new("p", ["a", "b,c"])).is_err());
+        assert!(validate_rule(&PolicyRule::new("g", ["a", ""])).is_err());
+    }
+
+    pub async fn test_enforcer_with_added_rule() {
+        let mut rules = rules_from_str(POLICY_TEXT).unwrap();
+        rules.push(PolicyRule::new("p", ["rule_auditor", "query_audit_logs"]));
+        rules.push(PolicyRule::new("g", ["Auditor", "rule_auditor"]));
+        let e = init_enforcer_with_policy(&rules_to_string(&rules))
+            .await
+            .unwrap();
+        assert!(casbin::CoreApi::enforce(&e, ("Auditor", "query_audit_logs")).unwrap());
+        assert!(!casbin::CoreApi::enforce(&e, ("Auditor", "create_task")).unwrap());
+    }
+}
@@ -15,28 +15,119 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::acs::init_memory_enforcer;
+use crate::acs::{init_memory_enforcer, matched_api_rule};
+use crate::attributes::{check_data_access, validate_attributes, AttributeStore};
+use crate::audit::{AccessDecision, DecisionAuditor};
 use crate::error::TeaclavAccessControlError;
+use crate::policy::{validate_rule, PolicyStore};
 use teaclave_proto::teaclave_access_control_service::*;
+use teaclave_proto::teaclave_storage_service::TeaclaveStorageClient;
+use teaclave_rpc::transport::{channel::Endpoint, Channel};
 use teaclave_rpc::{Request, Response};
-use teaclave_types::TeaclaveServiceResponseResult;
+use teaclave_types::{TeaclaveServiceResponseResult, UserRole};
 
 use std::sync::{Arc, RwLock};
 
+use anyhow::{anyhow, Result};
 use casbin::{CoreApi, Enforcer};
+use tokio::sync::Mutex;
 
 #[derive(Clone)]
 pub(crate) struct TeaclaveAccessControlService {
     api_enforcer: Arc<RwLock<Enforcer>>,
+    policy: PolicyStore,
+    attributes: AttributeStore,
+    auditor: DecisionAuditor,
 }
 
 impl TeaclaveAccessControlService {
-    pub(crate) async fn new() -> Self {
-        let api_enforcer = Arc::new(RwLock::new(init_memory_enforcer().await.unwrap()));
-        TeaclaveAccessControlService { api_enforcer }
+    pub(crate) async fn new(
+        storage_service_endpoint: Endpoint,
+        auditor: DecisionAuditor,
+    ) -> Result<Self> {
+        let channel = storage_service_endpoint
+            .connect()
+            .await
+            .map_err(|e| anyhow!("Failed to connect to storage service, {:?}", e))?;
+        let storage_client = Arc::new(Mutex::new(TeaclaveStorageClient::new_with_builtin_config(
+            channel,
+        )));
+        let api_enforcer = Arc::new(RwLock::new(init_memory_enforcer().await?));
+        let policy = PolicyStore::new(storage_client.clone(), api_enforcer.clone());
+        policy.reload().await?;
+        let attributes = AttributeStore::new(storage_client);
+        Ok(TeaclaveAccessControlService {
+            api_enforcer,
+            policy,
+            attributes,
+            auditor,
+        })
+    }
+
+    /// Returns the denial if any of the users may not access any of the data.
+    async fn check_data_access(
+        &self,
+        user_ids: &[String],
+        data_ids: &[String],
+    ) -> Result<Option<Denial>, TeaclavAccessControlError> {
+        let mut data_attributes = Vec::with_capacity(data_ids.len());
+        for data_id in data_ids {
+            data_attributes.push((data_id, self.attributes.data_attributes(data_id).await?));
+        }
+        for user_id in user_ids {
+            let user_attributes = self.attributes.user_attributes(user_id).await?;
+            for (data_id, attributes) in &data_attributes {
+                if let Some(denial) = check_data_access(&user_attributes, attributes) {
+                    return Ok(Some(denial.user_id(user_id).data_id(*data_id)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the decision on each of the objects, with the denial of the
+    /// first user who may not access it.
+    async fn check_batch_access(
+        &self,
+        user_ids: &[String],
+        object_ids: &[String],
+    ) -> Result<Vec<AuthorizeBatchDecision>, TeaclavAccessControlError> {
+        let mut user_attributes = Vec::with_capacity(user_ids.len());
+        for user_id in user_ids {
+            user_attributes.push((user_id, self.attributes.user_attributes(user_id).await?));
+        }
+        let mut decisions = Vec::with_capacity(object_ids.len());
+        for object_id in object_ids {
+            let attributes = self.attributes.data_attributes(object_id).await?;
+            let denial = user_attributes.iter().find_map(|(user_id, user)| {
+                check_data_access(user, &attributes)
+                    .map(|denial| denial.user_id(*user_id).data_id(object_id))
+            });
+            decisions.push(AuthorizeBatchDecision {
+                object_id: object_id.to_owned(),
+                accept: denial.is_none(),
+                denial,
+            });
+        }
+        Ok(decisions)
     }
 }
 
+// The frontend service has authorized the request, double check the role it
+// forwarded.
+fn ensure_platform_admin<T>(request: &Request<T>) -> Result<(), TeaclavAccessControlError> {
+    let role = request
+        .metadata()
+        .get("role")
+        .and_then(|x| x.to_str().ok())
+        .map(UserRole::from_str)
+        .ok_or(TeaclavAccessControlError::PermissionDenied)?;
+    if !role.is_platform_admin() {
+        return Err(TeaclavAccessControlError::PermissionDenied);
+    }
+    Ok(())
+}
+
 #[teaclave_rpc::async_trait]
 impl TeaclaveAccessControl for TeaclaveAccessControlService {
     async fn authorize_api(
@@ -44,12 +135,213 @@ impl TeaclaveAccessControl for TeaclaveAccessControlService {
         request: Request<AuthorizeApiRequest>,
     ) -> TeaclaveServiceResponseResult<AuthorizeApiResponse> {
         let e = self.api_enforcer.read().unwrap();
-        let request = request.into_inner();
+        let trace_id = request
+            .metadata()
+            .get("trace_id")
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let message = request.get_ref();
 
         let accept = e
-            .enforce((request.user_role, request.api))
+            .enforce((message.user_role.as_str(), message.api.as_str()))
             .map_err(|_| TeaclavAccessControlError::AccessControlError)?;
+        log::debug!(
+            trace_id = trace_id.as_str();
+            "AuthorizeApi: {} to {}: {}",
+            message.user_role,
+            message.api,
+            accept
+        );
+        let rule = if accept {
+            // Accepted by the model itself if no rule is found
+            Some(
+                matched_api_rule(&e, &message.user_role, &message.api)
+                    .unwrap_or_else(|| "model".to_owned()),
+            )
+        } else {
+            None
+        };
+        self.auditor.record(
+            &request,
+            AccessDecision::Api {
+                subject: &message.user_role,
+                api: &message.api,
+                rule,
+            },
+        );
+        let request = request.into_inner();
+        let denial = (!accept).then(|| {
+            Denial::new(
+                DenialCode::ApiNotPermitted,
+                format!("permission to call {}", request.api),
+            )
+            .user_id(request.user_role)
+        });
 
-        Ok(Response::new(AuthorizeApiResponse { accept }))
+        Ok(Response::new(AuthorizeApiResponse { accept, denial }))
+    }
+
+    async fn manage_policy(
+        &self,
+        request: Request<ManagePolicyRequest>,
+    ) -> TeaclaveServiceResponseResult<ManagePolicyResponse> {
+        ensure_platform_admin(&request)?;
+
+        let request = request.into_inner();
+        let action = PolicyAction::from_i32(request.action)
+            .ok_or_else(|| TeaclavAccessControlError::InvalidPolicyRule("unknown action".into()))?;
+        let rules = match action {
+            PolicyAction::ListRules => self.policy.list().await,
+            PolicyAction::AddRule | PolicyAction::RemoveRule => {
+                let rule = request.rule.ok_or_else(|| {
+                    TeaclavAccessControlError::InvalidPolicyRule("missing rule".into())
+                })?;
+                validate_rule(&rule)?;
+                if action == PolicyAction::AddRule {
+                    self.policy.add(rule).await
+                } else {
+                    self.policy.remove(&rule).await
+                }
+            }
+        }
+        .map_err(TeaclavAccessControlError::Service)?;
+
+        Ok(Response::new(ManagePolicyResponse { rules }))
+    }
+
+    async fn authorize_data(
+        &self,
+        request: Request<AuthorizeDataRequest>,
+    ) -> TeaclaveServiceResponseResult<AuthorizeDataResponse> {
+        let message = request.get_ref();
+        let denial = self
+            .check_data_access(
+                &[message.subject_user_id.clone()],
+                &[message.object_data_id.clone()],
+            )
+            .await?;
+        log::debug!("AuthorizeData: {:?}", denial);
+        self.auditor.record(
+            &request,
+            AccessDecision::Data {
+                subject: &message.subject_user_id,
+                data_id: &message.object_data_id,
+                denial: denial.as_ref(),
+            },
+        );
+
+        Ok(Response::new(AuthorizeDataResponse {
+            accept: denial.is_none(),
+            reason: denial.as_ref().map(Denial::to_string).unwrap_or_default(),
+            denial,
+        }))
+    }
+
+    async fn authorize_staged_task(
+        &self,
+        request: Request<AuthorizeStagedTaskRequest>,
+    ) -> TeaclaveServiceResponseResult<AuthorizeStagedTaskResponse> {
+        let message = request.get_ref();
+        let denial = self
+            .check_data_access(&message.subject_user_id_list, &message.object_data_id_list)
+            .await?;
+        log::debug!(
+            "AuthorizeStagedTask: {}: {:?}",
+            message.subject_task_id,
+            denial
+        );
+        self.auditor.record(
+            &request,
+            AccessDecision::StagedTask {
+                subjects: &message.subject_user_id_list,
+                task_id: &message.subject_task_id,
+                denial: denial.as_ref(),
+            },
+        );
+
+        Ok(Response::new(AuthorizeStagedTaskResponse {
+            accept: denial.is_none(),
+            reason: denial.as_ref().map(Denial::to_string).unwrap_or_default(),
+            denial,
+        }))
+    }
+
+    async fn authorize_batch(
+        &self,
+        request: Request<AuthorizeBatchRequest>,
+    ) -> TeaclaveServiceResponseResult<AuthorizeBatchResponse> {
+        let message = request.get_ref();
+        let decisions = self
+            .check_batch_access(&message.subject_user_id_list, &message.object_id_list)
+            .await?;
+        let response = AuthorizeBatchResponse {
+            accept: decisions.iter().all(|d| d.accept),
+            decisions,
+        };
+        let denials: Vec<_> = response.denials().collect();
+        log::debug!("AuthorizeBatch: {}: {:?}", message.subject_task_id, denials);
+        self.auditor.record(
+            &request,
+            AccessDecision::Batch {
+                subjects: &message.subject_user_id_list,
+                task_id: &message.subject_task_id,
+                objects: message.object_id_list.len(),
+                denials: &denials,
+            },
+        );
+
+        Ok(Response::new(response))
+    }
+
+    async fn set_user_attributes(
+        &self,
+        request: Request<SetUserAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        ensure_platform_admin(&request)?;
+        let request = request.into_inner();
+        validate_attributes(&request.attributes)?;
+        self.attributes
+            .set_user_attributes(&request.user_id, &request.attributes)
+            .await
+            .map_err(TeaclavAccessControlError::Service)?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_user_attributes(
+        &self,
+        request: Request<GetUserAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<GetUserAttributesResponse> {
+        ensure_platform_admin(&request)?;
+        let attributes = self
+            .attributes
+            .user_attributes(&request.into_inner().user_id)
+            .await
+            .map_err(TeaclavAccessControlError::Service)?;
+        Ok(Response::new(GetUserAttributesResponse { attributes }))
+    }
+
+    // The management service has checked that the user owns the data.
+    async fn set_data_attributes(
+        &self,
+        request: Request<SetDataAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let request = request.into_inner();
+        validate_attributes(&request.attributes)?;
+        self.attributes
+            .set_data_attributes(&request.data_id, &request.attributes)
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_data_attributes(
+        &self,
+        request: Request<GetDataAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<GetDataAttributesResponse> {
+        let attributes = self
+            .attributes
+            .data_attributes(&request.into_inner().data_id)
+            .await?;
+        Ok(Response::new(GetDataAttributesResponse { attributes }))
     }
 }
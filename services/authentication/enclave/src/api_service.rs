@@ -15,8 +15,13 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::audit::{CredentialAuditor, CredentialEvent};
 use crate::error::AuthenticationError;
 use crate::error::AuthenticationServiceError;
+use crate::identity::{IdentityProvider, IdentityProviders};
+use crate::password::PasswordPolicy;
+use crate::revocation::RevocationLog;
+use crate::totp::{self, RecoveryCodeStore, TotpInfo};
 use crate::user_db::DbClient;
 use crate::user_info::UserInfo;
 
@@ -24,6 +29,7 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 #[allow(unused_imports)]
 use std::untrusted::time::SystemTimeEx;
+use teaclave_crypto::{clear, Zeroizing};
 use teaclave_proto::teaclave_authentication_service::*;
 use teaclave_rpc::{Request, Response};
 use teaclave_service_enclave_utils::{bail, ensure};
@@ -32,13 +38,153 @@ use teaclave_types::{TeaclaveServiceResponseResult, UserRole};
 pub(crate) struct TeaclaveAuthenticationApiService {
     db_client: Arc<Mutex<DbClient>>,
     jwt_secret: Vec<u8>,
+    auditor: CredentialAuditor,
+    revocations: Arc<RevocationLog>,
+    identity_providers: Arc<IdentityProviders>,
+    recovery_codes: RecoveryCodeStore,
+    password_policy: PasswordPolicy,
 }
 
 impl TeaclaveAuthenticationApiService {
-    pub(crate) fn new(db_client: DbClient, jwt_secret: Vec<u8>) -> Self {
+    pub(crate) fn new(
+        db_client: DbClient,
+        jwt_secret: Vec<u8>,
+        auditor: CredentialAuditor,
+        revocations: Arc<RevocationLog>,
+        identity_providers: Arc<IdentityProviders>,
+        recovery_codes: RecoveryCodeStore,
+        password_policy: PasswordPolicy,
+    ) -> Self {
         Self {
             db_client: Arc::new(Mutex::new(db_client)),
             jwt_secret,
+            auditor,
+            revocations,
+            identity_providers,
+            recovery_codes,
+            password_policy,
+        }
+    }
+
+    // Verify the credential of the login with the identity provider, which
+    // blocks, and find the user of the identity. Users not registered yet are
+    // registered with the role the provider assigns, and registered users are
+    // updated to it.
+    async fn login_external(
+        &self,
+        request: &Request<UserLoginRequest>,
+        provider: Arc<dyn IdentityProvider>,
+        credential: &Zeroizing<String>,
+    ) -> Result<UserInfo, AuthenticationServiceError> {
+        let name = provider.name();
+        let id = request.get_ref().id.clone();
+        let credential = credential.clone();
+        let identity = tokio::task::spawn_blocking(move || provider.authenticate(&id, &credential))
+            .await
+            .map_err(|e| AuthenticationServiceError::Service(e.into()))?
+            .map_err(|e| {
+                warn!("Identity provider {} failed: {:?}", name, e);
+                AuthenticationServiceError::Service(e)
+            })?
+            .ok_or(AuthenticationError::IncorrectPassword)?;
+        // External identities cannot take over the local users, e.g., the
+        // platform admin.
+        ensure!(
+            !self.identity_providers.is_local_user(&identity.user_id),
+            AuthenticationError::InvalidUserId
+        );
+
+        let id = &identity.user_id;
+        let db_client = self.db_client.lock().unwrap();
+        let (user, registered) = match (db_client.get_user(id), identity.role) {
+            (Ok(user), Some(role)) if user.role != role => (UserInfo { role, ..user }, true),
+            (Ok(user), _) => return Ok(user),
+            (Err(_), Some(role)) => (UserInfo::new(id, &random_password(), role), false),
+            (Err(_), None) => bail!(AuthenticationError::UserIdNotFound),
+        };
+        let role = &user.role;
+        let (result, event) = if registered {
+            let event = CredentialEvent::Update { id, role };
+            (db_client.update_user(&user), event)
+        } else {
+            let event = CredentialEvent::Register { id, role };
+            (db_client.create_user(&user), event)
+        };
+        self.auditor.record(request, id, event, result.is_ok());
+        result.map_err(|e| AuthenticationServiceError::Service(e.into()))?;
+        if registered {
+            self.revocations.revoke(id);
+        }
+        Ok(user)
+    }
+
+    // Accept a TOTP code of the enrolled secret, keeping its step so that the
+    // code is not accepted again.
+    fn accept_totp_code(
+        &self,
+        user: &mut UserInfo,
+        code: &str,
+    ) -> Result<bool, AuthenticationServiceError> {
+        let secret = match &user.totp.secret {
+            Some(secret) => secret,
+            None => bail!(AuthenticationServiceError::TotpNotEnrolled),
+        };
+        let now = unix_time()?.as_secs();
+        let step = match totp::verify_code(secret, code, now, user.totp.last_step) {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+        user.totp.last_step = step;
+        self.db_client
+            .lock()
+            .unwrap()
+            .update_user(user)
+            .map_err(|e| AuthenticationServiceError::Service(e.into()))?;
+        Ok(true)
+    }
+
+    // Users enrolled in TOTP present a code, or one of their recovery codes.
+    async fn verify_second_factor(
+        &self,
+        user: &mut UserInfo,
+        code: &str,
+    ) -> Result<(), AuthenticationServiceError> {
+        if !user.totp.enrolled() {
+            return Ok(());
+        }
+        ensure!(!code.is_empty(), AuthenticationServiceError::TotpRequired);
+        if self.accept_totp_code(user, code)? {
+            return Ok(());
+        }
+        let redeemed = self
+            .recovery_codes
+            .redeem(&user.id, &user.totp.recovery_key, code)
+            .await
+            .map_err(AuthenticationServiceError::Service)?;
+        ensure!(redeemed, AuthenticationError::IncorrectTotpCode);
+        Ok(())
+    }
+
+    fn check_password(&self, password: &str) -> Result<(), AuthenticationServiceError> {
+        match self.password_policy.check(password) {
+            Some(rule) => bail!(AuthenticationServiceError::WeakPassword(rule)),
+            None => Ok(()),
+        }
+    }
+
+    fn get_requester(&self, id: &str) -> Result<UserInfo, AuthenticationServiceError> {
+        self.db_client
+            .lock()
+            .unwrap()
+            .get_user(id)
+            .map_err(|_| AuthenticationServiceError::InvalidUserId)
+    }
+
+    // The claims of the user cached elsewhere no longer hold once its
+    // credential is changed.
+    fn revoke_on_success<T>(&self, id: &str, result: &TeaclaveServiceResponseResult<T>) {
+        if result.is_ok() {
+            self.revocations.revoke(id);
         }
     }
 
@@ -83,195 +229,413 @@ impl TeaclaveAuthenticationApiService {
     }
 }
 
+// Passwords are moved out of the requests to be cleared on drop, whichever
+// way the requests are handled.
+fn take_password(password: &mut String) -> Zeroizing<String> {
+    Zeroizing::new(std::mem::take(password))
+}
+
+fn unix_time() -> Result<Duration, AuthenticationServiceError> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| AuthenticationServiceError::Service(e.into()))
+}
+
+// A password nobody knows, for the users registered by identity providers.
+fn random_password() -> Zeroizing<String> {
+    let mut encode_buffer = uuid::Uuid::encode_buffer();
+    let password = Zeroizing::new(
+        uuid::Uuid::new_v4()
+            .to_simple()
+            .encode_lower(&mut encode_buffer)
+            .to_string(),
+    );
+    clear(&mut encode_buffer);
+    password
+}
+
+// The requester claimed in the metadata, which is audited even if the request
+// is not authenticated.
+fn requester_id<T>(request: &Request<T>) -> String {
+    request
+        .metadata()
+        .get("id")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default()
+        .to_owned()
+}
+
 #[teaclave_rpc::async_trait]
 impl TeaclaveAuthenticationApi for TeaclaveAuthenticationApiService {
     async fn user_register(
         &self,
-        request: Request<UserRegisterRequest>,
+        mut request: Request<UserRegisterRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
-        let requester_role = self.validate_credential_in_request(&request)?;
-
-        let request = request.get_ref();
-        ensure!(
-            !request.id.is_empty(),
-            AuthenticationServiceError::InvalidUserId
-        );
-        if self.db_client.lock().unwrap().get_user(&request.id).is_ok() {
-            bail!(AuthenticationServiceError::UserIdExist);
+        let password = take_password(&mut request.get_mut().password);
+        let role = UserRole::new(&request.get_ref().role, &request.get_ref().attribute);
+        let result: TeaclaveServiceResponseResult<()> = async {
+            let requester_role = self.validate_credential_in_request(&request)?;
+
+            let request = request.get_ref();
+            ensure!(
+                !request.id.is_empty(),
+                AuthenticationServiceError::InvalidUserId
+            );
+            if self.db_client.lock().unwrap().get_user(&request.id).is_ok() {
+                bail!(AuthenticationServiceError::UserIdExist);
+            }
+            ensure!(
+                role != UserRole::Invalid,
+                AuthenticationServiceError::InvalidRole
+            );
+
+            ensure!(
+                authorize_user_register(&requester_role, request),
+                AuthenticationServiceError::PermissionDenied
+            );
+            self.check_password(&password)?;
+
+            let new_user = UserInfo::new(&request.id, &password, role.clone());
+            match self.db_client.lock().unwrap().create_user(&new_user) {
+                Ok(_) => Ok(Response::new(())),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
         }
-        let role = UserRole::new(&request.role, &request.attribute);
-        ensure!(
-            role != UserRole::Invalid,
-            AuthenticationServiceError::InvalidRole
-        );
-
-        ensure!(
-            authorize_user_register(&requester_role, request),
-            AuthenticationServiceError::PermissionDenied
-        );
+        .await;
 
-        let new_user = UserInfo::new(&request.id, &request.password, role);
-        match self.db_client.lock().unwrap().create_user(&new_user) {
-            Ok(_) => Ok(Response::new(())),
-            Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
-        }
+        let event = CredentialEvent::Register {
+            id: &request.get_ref().id,
+            role: &role,
+        };
+        self.auditor
+            .record(&request, &requester_id(&request), event, result.is_ok());
+        result
     }
 
     async fn user_update(
         &self,
-        request: Request<UserUpdateRequest>,
+        mut request: Request<UserUpdateRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
-        let requester_role = self.validate_credential_in_request(&request)?;
-
-        let request = request.get_ref();
-        ensure!(
-            !request.id.is_empty(),
-            AuthenticationServiceError::InvalidUserId
-        );
-        if self
-            .db_client
-            .lock()
-            .unwrap()
-            .get_user(&request.id)
-            .is_err()
-        {
-            bail!(AuthenticationServiceError::InvalidUserId);
+        let password = take_password(&mut request.get_mut().password);
+        let role = UserRole::new(&request.get_ref().role, &request.get_ref().attribute);
+        let result: TeaclaveServiceResponseResult<()> = async {
+            let requester_role = self.validate_credential_in_request(&request)?;
+
+            let request = request.get_ref();
+            ensure!(
+                !request.id.is_empty(),
+                AuthenticationServiceError::InvalidUserId
+            );
+            let user = self
+                .db_client
+                .lock()
+                .unwrap()
+                .get_user(&request.id)
+                .map_err(|_| AuthenticationServiceError::InvalidUserId)?;
+            ensure!(
+                role != UserRole::Invalid,
+                AuthenticationServiceError::InvalidRole
+            );
+
+            ensure!(
+                authorize_user_update(&requester_role, request),
+                AuthenticationServiceError::PermissionDenied
+            );
+            self.check_password(&password)?;
+
+            // The user stays enrolled in TOTP
+            let updated_user = UserInfo {
+                totp: user.totp,
+                ..UserInfo::new(&request.id, &password, role.clone())
+            };
+            match self.db_client.lock().unwrap().update_user(&updated_user) {
+                Ok(_) => Ok(Response::new(())),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
         }
-        let role = UserRole::new(&request.role, &request.attribute);
-        ensure!(
-            role != UserRole::Invalid,
-            AuthenticationServiceError::InvalidRole
-        );
-
-        ensure!(
-            authorize_user_update(&requester_role, request),
-            AuthenticationServiceError::PermissionDenied
-        );
+        .await;
 
-        let updated_user = UserInfo::new(&request.id, &request.password, role);
-        match self.db_client.lock().unwrap().update_user(&updated_user) {
-            Ok(_) => Ok(Response::new(())),
-            Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
-        }
+        self.revoke_on_success(&request.get_ref().id, &result);
+        let event = CredentialEvent::Update {
+            id: &request.get_ref().id,
+            role: &role,
+        };
+        self.auditor
+            .record(&request, &requester_id(&request), event, result.is_ok());
+        result
     }
 
     async fn user_login(
         &self,
-        request: Request<UserLoginRequest>,
+        mut request: Request<UserLoginRequest>,
     ) -> TeaclaveServiceResponseResult<UserLoginResponse> {
-        let request = request.get_ref();
-        ensure!(!request.id.is_empty(), AuthenticationError::InvalidUserId);
-        ensure!(
-            !request.password.is_empty(),
-            AuthenticationError::InvalidPassword
-        );
-        let user = self
-            .db_client
-            .lock()
-            .unwrap()
-            .get_user(&request.id)
-            .map_err(|_| AuthenticationError::UserIdNotFound)?;
-        if !user.verify_password(&request.password) {
-            bail!(AuthenticationError::IncorrectPassword)
-        } else {
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map_err(|e| AuthenticationServiceError::Service(e.into()))?;
-            let exp = (now + Duration::from_secs(24 * 60 * 60)).as_secs();
+        let password = take_password(&mut request.get_mut().password);
+        let totp_code = take_password(&mut request.get_mut().totp_code);
+        let result: TeaclaveServiceResponseResult<UserLoginResponse> = async {
+            ensure!(!password.is_empty(), AuthenticationError::InvalidPassword);
+            let login = request.get_ref();
+            let provider = self
+                .identity_providers
+                .provider_of(&login.provider, &login.id)
+                .map_err(|e| {
+                    debug!("{:?}", e);
+                    AuthenticationError::InvalidProvider
+                })?;
+            let mut user = match provider {
+                Some(provider) => self.login_external(&request, provider, &password).await?,
+                None => {
+                    ensure!(!login.id.is_empty(), AuthenticationError::InvalidUserId);
+                    let user = self
+                        .db_client
+                        .lock()
+                        .unwrap()
+                        .get_user(&login.id)
+                        .map_err(|_| AuthenticationError::UserIdNotFound)?;
+                    ensure!(
+                        user.verify_password(&password),
+                        AuthenticationError::IncorrectPassword
+                    );
+                    ensure!(
+                        !self
+                            .password_policy
+                            .needs_change(&user, unix_time()?.as_secs()),
+                        AuthenticationError::PasswordChangeRequired
+                    );
+                    user
+                }
+            };
+            self.verify_second_factor(&mut user, &totp_code).await?;
+            let exp = (unix_time()? + Duration::from_secs(24 * 60 * 60)).as_secs();
             match user.get_token(exp, &self.jwt_secret) {
-                Ok(token) => Ok(Response::new(UserLoginResponse { token })),
+                Ok(token) => Ok(Response::new(UserLoginResponse::new(token, user.id))),
                 Err(e) => bail!(AuthenticationServiceError::Service(e)),
             }
         }
+        .await;
+
+        // Logins with external identities are of the users they are mapped to
+        let id = match &result {
+            Ok(response) => response.get_ref().id.clone(),
+            Err(_) => request.get_ref().id.clone(),
+        };
+        let event = CredentialEvent::Login { id: &id };
+        self.auditor.record(&request, &id, event, result.is_ok());
+        result
     }
 
     async fn user_change_password(
         &self,
-        request: Request<UserChangePasswordRequest>,
+        mut request: Request<UserChangePasswordRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
-        let requester_role = self.validate_credential_in_request(&request)?;
-
-        let id: String = request
-            .metadata()
-            .get("id")
-            .and_then(|x| x.to_str().ok())
-            .unwrap()
-            .into();
-        let request = request.get_ref();
-        ensure!(
-            !request.password.is_empty(),
-            AuthenticationError::InvalidPassword
-        );
-        let updated_user = UserInfo::new(&id, &request.password, requester_role);
-
-        match self.db_client.lock().unwrap().update_user(&updated_user) {
-            Ok(_) => Ok(Response::new(())),
-            Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+        let password = take_password(&mut request.get_mut().password);
+        let id = requester_id(&request);
+        let result: TeaclaveServiceResponseResult<()> = async {
+            let requester_role = self.validate_credential_in_request(&request)?;
+
+            ensure!(!password.is_empty(), AuthenticationError::InvalidPassword);
+            self.check_password(&password)?;
+            let user = self.get_requester(&id)?;
+            let updated_user = UserInfo {
+                totp: user.totp,
+                ..UserInfo::new(&id, &password, requester_role)
+            };
+
+            match self.db_client.lock().unwrap().update_user(&updated_user) {
+                Ok(_) => Ok(Response::new(())),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
         }
+        .await;
+
+        self.revoke_on_success(&id, &result);
+        let event = CredentialEvent::ChangePassword { id: &id };
+        self.auditor.record(&request, &id, event, result.is_ok());
+        result
     }
 
     async fn reset_user_password(
         &self,
         request: Request<ResetUserPasswordRequest>,
     ) -> TeaclaveServiceResponseResult<ResetUserPasswordResponse> {
-        let requester_role = self.validate_credential_in_request(&request)?;
+        let result: TeaclaveServiceResponseResult<ResetUserPasswordResponse> = async {
+            let requester_role = self.validate_credential_in_request(&request)?;
+
+            let request = request.get_ref();
+            ensure!(
+                !request.id.is_empty(),
+                AuthenticationServiceError::InvalidUserId
+            );
+            let user = self
+                .db_client
+                .lock()
+                .unwrap()
+                .get_user(&request.id)
+                .map_err(|_| AuthenticationServiceError::PermissionDenied)?;
+
+            ensure!(
+                authorize_reset_user_password(&requester_role, &user),
+                AuthenticationServiceError::PermissionDenied
+            );
+
+            let mut encode_buffer = uuid::Uuid::encode_buffer();
+            let new_password = Zeroizing::new(
+                uuid::Uuid::new_v4()
+                    .to_simple()
+                    .encode_lower(&mut encode_buffer)
+                    .to_string(),
+            );
+            clear(&mut encode_buffer);
+            let updated_user = UserInfo {
+                totp: user.totp,
+                ..UserInfo::new(&request.id, &new_password, user.role)
+            };
+            match self.db_client.lock().unwrap().update_user(&updated_user) {
+                Ok(_) => Ok(Response::new(ResetUserPasswordResponse {
+                    password: new_password.to_string(),
+                })),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
+        }
+        .await;
 
-        let request = request.get_ref();
-        ensure!(
-            !request.id.is_empty(),
-            AuthenticationServiceError::InvalidUserId
-        );
-        let user = self
-            .db_client
-            .lock()
-            .unwrap()
-            .get_user(&request.id)
-            .map_err(|_| AuthenticationServiceError::PermissionDenied)?;
+        self.revoke_on_success(&request.get_ref().id, &result);
+        let event = CredentialEvent::ResetPassword {
+            id: &request.get_ref().id,
+        };
+        self.auditor
+            .record(&request, &requester_id(&request), event, result.is_ok());
+        result
+    }
 
-        ensure!(
-            authorize_reset_user_password(&requester_role, &user),
-            AuthenticationServiceError::PermissionDenied
-        );
+    async fn change_password(
+        &self,
+        mut request: Request<ChangePasswordRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let old_password = take_password(&mut request.get_mut().old_password);
+        let new_password = take_password(&mut request.get_mut().new_password);
+        let totp_code = take_password(&mut request.get_mut().totp_code);
+        let id = request.get_ref().id.clone();
+        let result: TeaclaveServiceResponseResult<()> = async {
+            ensure!(!id.is_empty(), AuthenticationError::InvalidUserId);
+            ensure!(
+                !old_password.is_empty(),
+                AuthenticationError::InvalidPassword
+            );
+            let mut user = self
+                .db_client
+                .lock()
+                .unwrap()
+                .get_user(&id)
+                .map_err(|_| AuthenticationError::UserIdNotFound)?;
+            ensure!(
+                user.verify_password(&old_password),
+                AuthenticationError::IncorrectPassword
+            );
+            self.verify_second_factor(&mut user, &totp_code).await?;
+            self.check_password(&new_password)?;
+            ensure!(
+                *new_password != *old_password,
+                AuthenticationServiceError::WeakPassword("same as the old one".to_string())
+            );
+
+            let updated_user = UserInfo {
+                totp: user.totp,
+                ..UserInfo::new(&id, &new_password, user.role)
+            };
+            match self.db_client.lock().unwrap().update_user(&updated_user) {
+                Ok(_) => Ok(Response::new(())),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
+        }
+        .await;
 
-        let mut encode_buffer = uuid::Uuid::encode_buffer();
-        let new_password = uuid::Uuid::new_v4()
-            .to_simple()
-            .encode_lower(&mut encode_buffer);
-        let updated_user = UserInfo::new(&request.id, new_password, user.role);
-        match self.db_client.lock().unwrap().update_user(&updated_user) {
-            Ok(_) => Ok(Response::new(ResetUserPasswordResponse {
-                password: new_password.to_string(),
-            })),
-            Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+        self.revoke_on_success(&id, &result);
+        let event = CredentialEvent::ChangePassword { id: &id };
+        self.auditor.record(&request, &id, event, result.is_ok());
+        result
+    }
+
+    async fn force_password_reset(
+        &self,
+        request: Request<ForcePasswordResetRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let result: TeaclaveServiceResponseResult<()> = async {
+            let requester_role = self.validate_credential_in_request(&request)?;
+
+            let request = request.get_ref();
+            ensure!(
+                !request.id.is_empty(),
+                AuthenticationServiceError::InvalidUserId
+            );
+            let user = self
+                .db_client
+                .lock()
+                .unwrap()
+                .get_user(&request.id)
+                .map_err(|_| AuthenticationServiceError::PermissionDenied)?;
+
+            ensure!(
+                authorize_reset_user_password(&requester_role, &user),
+                AuthenticationServiceError::PermissionDenied
+            );
+
+            let updated_user = UserInfo {
+                password_reset_required: true,
+                ..user
+            };
+            match self.db_client.lock().unwrap().update_user(&updated_user) {
+                Ok(_) => Ok(Response::new(())),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
         }
+        .await;
+
+        self.revoke_on_success(&request.get_ref().id, &result);
+        let event = CredentialEvent::ForcePasswordReset {
+            id: &request.get_ref().id,
+        };
+        self.auditor
+            .record(&request, &requester_id(&request), event, result.is_ok());
+        result
     }
 
     async fn delete_user(
         &self,
         request: Request<DeleteUserRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
-        let requester_role = self.validate_credential_in_request(&request)?;
-
-        let request = request.get_ref();
-        ensure!(
-            !request.id.is_empty(),
-            AuthenticationServiceError::InvalidUserId
-        );
-        let user = self
-            .db_client
-            .lock()
-            .unwrap()
-            .get_user(&request.id)
-            .map_err(|_| AuthenticationServiceError::PermissionDenied)?;
-
-        ensure!(
-            authorize_delete_user(&requester_role, &user),
-            AuthenticationServiceError::PermissionDenied
-        );
-        match self.db_client.lock().unwrap().delete_user(&request.id) {
-            Ok(_) => Ok(Response::new(())),
-            Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+        let result: TeaclaveServiceResponseResult<()> = async {
+            let requester_role = self.validate_credential_in_request(&request)?;
+
+            let request = request.get_ref();
+            ensure!(
+                !request.id.is_empty(),
+                AuthenticationServiceError::InvalidUserId
+            );
+            let user = self
+                .db_client
+                .lock()
+                .unwrap()
+                .get_user(&request.id)
+                .map_err(|_| AuthenticationServiceError::PermissionDenied)?;
+
+            ensure!(
+                authorize_delete_user(&requester_role, &user),
+                AuthenticationServiceError::PermissionDenied
+            );
+            match self.db_client.lock().unwrap().delete_user(&request.id) {
+                Ok(_) => Ok(Response::new(())),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
         }
+        .await;
+
+        self.revoke_on_success(&request.get_ref().id, &result);
+        let event = CredentialEvent::Delete {
+            id: &request.get_ref().id,
+        };
+        self.auditor
+            .record(&request, &requester_id(&request), event, result.is_ok());
+        result
     }
 
     async fn list_users(
@@ -300,10 +664,106 @@ impl TeaclaveAuthenticationApi for TeaclaveAuthenticationApiService {
                 .list_users_by_attribute(&request.id),
         };
 
-        match users {
-            Ok(ids) => Ok(Response::new(ListUsersResponse { ids })),
+        let mut ids = match users {
+            Ok(ids) => ids,
             Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+        };
+        ids.sort();
+
+        let total = ids.len();
+        let limit = match request.limit as usize {
+            0 => total,
+            limit => limit,
+        };
+        let ids = ids
+            .into_iter()
+            .skip(request.offset as usize)
+            .take(limit)
+            .collect();
+        Ok(Response::new(ListUsersResponse::new(ids, total)))
+    }
+
+    async fn generate_totp_secret(
+        &self,
+        mut request: Request<GenerateTotpSecretRequest>,
+    ) -> TeaclaveServiceResponseResult<GenerateTotpSecretResponse> {
+        let code = take_password(&mut request.get_mut().code);
+        let id = requester_id(&request);
+        let result: TeaclaveServiceResponseResult<GenerateTotpSecretResponse> = async {
+            self.validate_credential_in_request(&request)?;
+            let mut user = self.get_requester(&id)?;
+            // The secret of an enrolled user is replaced with the second
+            // factor only, e.g., a recovery code if the authenticator is lost.
+            self.verify_second_factor(&mut user, &code).await?;
+
+            let secret = totp::generate_secret();
+            let response = GenerateTotpSecretResponse::new(
+                totp::encode_secret(&secret),
+                totp::provisioning_uri(&id, &secret),
+            );
+            user.totp.pending_secret = Some(secret);
+            match self.db_client.lock().unwrap().update_user(&user) {
+                Ok(_) => Ok(Response::new(response)),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
+        }
+        .await;
+
+        let event = CredentialEvent::GenerateTotpSecret { id: &id };
+        self.auditor.record(&request, &id, event, result.is_ok());
+        result
+    }
+
+    async fn verify_totp(
+        &self,
+        mut request: Request<VerifyTotpRequest>,
+    ) -> TeaclaveServiceResponseResult<VerifyTotpResponse> {
+        let code = take_password(&mut request.get_mut().code);
+        let id = requester_id(&request);
+        let result: TeaclaveServiceResponseResult<VerifyTotpResponse> = async {
+            self.validate_credential_in_request(&request)?;
+            let mut user = self.get_requester(&id)?;
+            let secret = match user.totp.pending_secret.take() {
+                Some(secret) => secret,
+                // Codes of the enrolled secret are only checked
+                None => {
+                    ensure!(
+                        self.accept_totp_code(&mut user, &code)?,
+                        AuthenticationError::IncorrectTotpCode
+                    );
+                    return Ok(Response::new(VerifyTotpResponse::new(Vec::new())));
+                }
+            };
+
+            let now = unix_time()?.as_secs();
+            let step = totp::verify_code(&secret, &code, now, 0)
+                .ok_or(AuthenticationError::IncorrectTotpCode)?;
+            // The codes sealed with a new key replace the previous ones, which
+            // cannot be unsealed unless the user is updated to the new key.
+            let recovery_codes = totp::generate_recovery_codes();
+            let recovery_key = totp::generate_recovery_key();
+            self.recovery_codes
+                .store(&id, &recovery_key, &recovery_codes)
+                .await
+                .map_err(AuthenticationServiceError::Service)?;
+            user.totp = TotpInfo {
+                secret: Some(secret),
+                pending_secret: None,
+                last_step: step,
+                recovery_key,
+            };
+            match self.db_client.lock().unwrap().update_user(&user) {
+                Ok(_) => Ok(Response::new(VerifyTotpResponse::new(
+                    recovery_codes.iter().map(|code| code.to_string()).collect(),
+                ))),
+                Err(e) => bail!(AuthenticationServiceError::Service(e.into())),
+            }
         }
+        .await;
+
+        let event = CredentialEvent::VerifyTotp { id: &id };
+        self.auditor.record(&request, &id, event, result.is_ok());
+        result
     }
 }
 
@@ -384,6 +844,11 @@ pub mod tests {
         TeaclaveAuthenticationApiService {
             db_client: Arc::new(Mutex::new(database.get_client())),
             jwt_secret,
+            auditor: CredentialAuditor::disabled(),
+            revocations: Arc::new(RevocationLog::new()),
+            identity_providers: Arc::new(IdentityProviders::default()),
+            recovery_codes: RecoveryCodeStore::disabled(),
+            password_policy: PasswordPolicy::default(),
         }
     }
 
@@ -472,6 +937,43 @@ pub mod tests {
         assert!(service.user_login(request).await.is_err());
     }
 
+    pub async fn test_totp_login() {
+        let service = get_mock_service();
+        let secret = totp::generate_secret();
+        let user = UserInfo {
+            totp: TotpInfo {
+                secret: Some(secret.clone()),
+                ..Default::default()
+            },
+            ..UserInfo::new("test_totp_id", "test_password", UserRole::FunctionOwner)
+        };
+        service
+            .db_client
+            .lock()
+            .unwrap()
+            .create_user(&user)
+            .unwrap();
+
+        let request = UserLoginRequest::new("test_totp_id", "test_password").into_request();
+        assert!(service.user_login(request).await.is_err());
+
+        let step = unix_time().unwrap().as_secs() / totp::STEP_SECS;
+        let code = format!("{:06}", totp::code_at(&secret, step));
+        let request = UserLoginRequest::new("test_totp_id", "test_password")
+            .totp_code(&code)
+            .into_request();
+        assert!(service.user_login(request).await.is_ok());
+        // A code is accepted once
+        let request = UserLoginRequest::new("test_totp_id", "test_password")
+            .totp_code(&code)
+            .into_request();
+        assert!(service.user_login(request).await.is_err());
+        let request = UserLoginRequest::new("test_totp_id", "wrong_password")
+            .totp_code(&code)
+            .into_request();
+        assert!(service.user_login(request).await.is_err());
+    }
+
     pub async fn test_user_change_password() {
         let service = get_mock_service();
         let request = UserLoginRequest::new("admin", "teaclave").into_request();
@@ -545,6 +1047,117 @@ pub mod tests {
         assert!(response.is_ok());
     }
 
+    pub async fn test_change_password() {
+        let service = get_mock_service();
+        let user = UserInfo::new(
+            "test_change_password_id",
+            "test_password",
+            UserRole::DataOwner("".to_string()),
+        );
+        service
+            .db_client
+            .lock()
+            .unwrap()
+            .create_user(&user)
+            .unwrap();
+
+        let request =
+            ChangePasswordRequest::new("test_change_password_id", "wrong_password", "new_password")
+                .into_request();
+        assert!(service.change_password(request).await.is_err());
+        // Passwords are checked against the policy
+        let request =
+            ChangePasswordRequest::new("test_change_password_id", "test_password", "short")
+                .into_request();
+        assert!(service.change_password(request).await.is_err());
+        let request =
+            ChangePasswordRequest::new("test_change_password_id", "test_password", "test_password")
+                .into_request();
+        assert!(service.change_password(request).await.is_err());
+        let request =
+            ChangePasswordRequest::new("test_change_password_id", "test_password", "new_password")
+                .into_request();
+        service.change_password(request).await.unwrap();
+
+        let request =
+            UserLoginRequest::new("test_change_password_id", "new_password").into_request();
+        assert!(service.user_login(request).await.is_ok());
+    }
+
+    pub async fn test_force_password_reset() {
+        let service = get_mock_service();
+        let request = UserLoginRequest::new("admin", "teaclave").into_request();
+        let response = service.user_login(request).await.unwrap().into_inner();
+
+        let mut metadata = MetadataMap::new();
+        metadata.insert("id", "admin".parse().unwrap());
+        metadata.insert("token", response.token.parse().unwrap());
+        let mut request =
+            UserRegisterRequest::new("test_force_reset_id", "test_password", "FunctionOwner", "")
+                .into_request();
+        *request.metadata_mut() = metadata.clone();
+        assert!(service.user_register(request).await.is_ok());
+
+        let request = UserLoginRequest::new("test_force_reset_id", "test_password").into_request();
+        let token = service
+            .user_login(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .token;
+
+        let mut request = ForcePasswordResetRequest::new("test_force_reset_id").into_request();
+        *request.metadata_mut() = metadata;
+        service.force_password_reset(request).await.unwrap();
+
+        // The issued tokens are revoked, and logins refused until the password
+        // is changed.
+        let user = service
+            .db_client
+            .lock()
+            .unwrap()
+            .get_user("test_force_reset_id")
+            .unwrap();
+        assert!(user.validate_token(&service.jwt_secret, &token).is_err());
+        let request = UserLoginRequest::new("test_force_reset_id", "test_password").into_request();
+        assert!(service.user_login(request).await.is_err());
+
+        let request =
+            ChangePasswordRequest::new("test_force_reset_id", "test_password", "new_password")
+                .into_request();
+        service.change_password(request).await.unwrap();
+        let request = UserLoginRequest::new("test_force_reset_id", "new_password").into_request();
+        assert!(service.user_login(request).await.is_ok());
+    }
+
+    pub async fn test_list_users() {
+        let service = get_mock_service();
+        let request = UserLoginRequest::new("admin", "teaclave").into_request();
+        let response = service.user_login(request).await.unwrap().into_inner();
+
+        let mut metadata = MetadataMap::new();
+        metadata.insert("id", "admin".parse().unwrap());
+        metadata.insert("token", response.token.parse().unwrap());
+        for id in ["test_list_users_b", "test_list_users_a"] {
+            let mut request =
+                UserRegisterRequest::new(id, "test_password", "FunctionOwner", "").into_request();
+            *request.metadata_mut() = metadata.clone();
+            assert!(service.user_register(request).await.is_ok());
+        }
+
+        let mut request = ListUsersRequest::new("admin").into_request();
+        *request.metadata_mut() = metadata.clone();
+        let all = service.list_users(request).await.unwrap().into_inner();
+        assert_eq!(all.total as usize, all.ids.len());
+        assert!(all.ids.windows(2).all(|ids| ids[0] <= ids[1]));
+
+        let mut request = ListUsersRequest::new("admin").page(1, 1).into_request();
+        *request.metadata_mut() = metadata;
+        let page = service.list_users(request).await.unwrap().into_inner();
+        assert_eq!(page.total, all.total);
+        assert_eq!(page.ids, all.ids[1..2].to_vec());
+    }
+
     pub async fn test_delete_user() {
         let service = get_mock_service();
 
@@ -0,0 +1,186 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Audit of the changes to the credentials of users, e.g., registrations,
+//! role and password changes, and tokens issued on login. Each change is sent
+//! to the auditor in the management service as soon as it is handled, failed
+//! attempts included, so that it can be investigated later.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr};
+
+use teaclave_proto::teaclave_management_service::{SaveLogsRequest, TeaclaveManagementClient};
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Request;
+use teaclave_types::{Entry, EntryBuilder, UserRole};
+
+/// A change to the credentials of the user `id`.
+pub(crate) enum CredentialEvent<'a> {
+    Register {
+        id: &'a str,
+        role: &'a UserRole,
+    },
+    /// Grants `role` to the user, revoking the previous one
+    Update {
+        id: &'a str,
+        role: &'a UserRole,
+    },
+    /// Issues a token to the user
+    Login {
+        id: &'a str,
+    },
+    ChangePassword {
+        id: &'a str,
+    },
+    ResetPassword {
+        id: &'a str,
+    },
+    /// Requires the user to change the password before the next login
+    ForcePasswordReset {
+        id: &'a str,
+    },
+    Delete {
+        id: &'a str,
+    },
+    /// Generates a TOTP secret pending verification
+    GenerateTotpSecret {
+        id: &'a str,
+    },
+    /// Verifies a TOTP code, enrolling the pending secret if any
+    VerifyTotp {
+        id: &'a str,
+    },
+}
+
+impl fmt::Display for CredentialEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register { id, role } => write!(f, "credential register: {} as {}", id, role),
+            Self::Update { id, role } => write!(f, "credential update: {} as {}", id, role),
+            Self::Login { id } => write!(f, "credential issue_token: {}", id),
+            Self::ChangePassword { id } => write!(f, "credential change_password: {}", id),
+            Self::ResetPassword { id } => write!(f, "credential reset_password: {}", id),
+            Self::ForcePasswordReset { id } => {
+                write!(f, "credential force_password_reset: {}", id)
+            }
+            Self::Delete { id } => write!(f, "credential delete: {}", id),
+            Self::GenerateTotpSecret { id } => {
+                write!(f, "credential generate_totp_secret: {}", id)
+            }
+            Self::VerifyTotp { id } => write!(f, "credential verify_totp: {}", id),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CredentialAuditor {
+    // None if there is no auditor to send the entries to, e.g., in unit tests
+    management_client: Option<TeaclaveManagementClient<Channel>>,
+}
+
+impl CredentialAuditor {
+    pub(crate) fn new(management_client: TeaclaveManagementClient<Channel>) -> Self {
+        Self {
+            management_client: Some(management_client),
+        }
+    }
+
+    pub(crate) fn disabled() -> Self {
+        Self {
+            management_client: None,
+        }
+    }
+
+    /// Record `event` requested by the user `requester` with `request`, and
+    /// whether it succeeded.
+    pub(crate) fn record<T>(
+        &self,
+        request: &Request<T>,
+        requester: &str,
+        event: CredentialEvent,
+        result: bool,
+    ) {
+        let entry = credential_entry(request, requester, &event, result);
+        log::info!("{} by {}: {}", entry.message(), requester, result);
+
+        let mut client = match &self.management_client {
+            Some(client) => client.clone(),
+            None => return,
+        };
+        tokio::spawn(async move {
+            let request = SaveLogsRequest::new(vec![entry]);
+            if let Err(e) = client.save_logs(request).await {
+                log::warn!(
+                    "Failed to send the audit log of a credential change: {:?}",
+                    e
+                );
+            }
+        });
+    }
+}
+
+fn credential_entry<T>(
+    request: &Request<T>,
+    requester: &str,
+    event: &CredentialEvent,
+    result: bool,
+) -> Entry {
+    let ip = match request.remote_addr().map(|s| s.ip()) {
+        Some(IpAddr::V4(ip_v4)) => ip_v4.to_ipv6_compatible(),
+        Some(IpAddr::V6(ip_v6)) => ip_v6,
+        None => Ipv6Addr::UNSPECIFIED,
+    };
+    let trace_id = request
+        .metadata()
+        .get("trace_id")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default();
+
+    EntryBuilder::new()
+        .ip(ip)
+        .user(requester.to_owned())
+        .message(event.to_string())
+        .result(result)
+        .trace_id(trace_id.to_owned())
+        .build()
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub async fn test_credential_entry() {
+        let request = Request::new(());
+        let role = UserRole::DataOwner("org".to_owned());
+        let event = CredentialEvent::Register {
+            id: "alice",
+            role: &role,
+        };
+        let entry = credential_entry(&request, "admin", &event, true);
+        assert_eq!(entry.user(), "admin");
+        assert_eq!(
+            entry.message(),
+            format!("credential register: alice as {}", role)
+        );
+        assert!(entry.result());
+
+        let event = CredentialEvent::Login { id: "alice" };
+        let entry = credential_entry(&request, "alice", &event, false);
+        assert_eq!(entry.message(), "credential issue_token: alice");
+        assert!(!entry.result());
+    }
+}
@@ -31,6 +31,12 @@ pub(crate) enum AuthenticationError {
     IncorrectPassword,
     #[error("incorrect token")]
     IncorrectToken,
+    #[error("invalid identity provider")]
+    InvalidProvider,
+    #[error("incorrect totp code")]
+    IncorrectTotpCode,
+    #[error("password change required")]
+    PasswordChangeRequired,
 }
 
 impl From<AuthenticationError> for AuthenticationServiceError {
@@ -63,6 +69,12 @@ pub(crate) enum AuthenticationServiceError {
     MissingUserId,
     #[error("missing token")]
     MissingToken,
+    #[error("totp code required")]
+    TotpRequired,
+    #[error("totp not enrolled")]
+    TotpNotEnrolled,
+    #[error("weak password: {0}")]
+    WeakPassword(String),
 }
 
 impl From<AuthenticationServiceError> for teaclave_rpc::Status {
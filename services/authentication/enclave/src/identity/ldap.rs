@@ -0,0 +1,278 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Verification of passwords by binding to an LDAP server as the users. The
+//! TLS session to the server ends in the enclave, so the untrusted app only
+//! relays encrypted bytes, and the bind result cannot be forged by it.
+
+use super::{ExternalIdentity, IdentityMapping, IdentityProvider};
+use anyhow::{anyhow, bail, ensure, Result};
+use rand::Rng;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+use teaclave_config::LdapConfig;
+use teaclave_crypto::Zeroizing;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_ENUMERATED: u8 = 0x0a;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_BIND_REQUEST: u8 = 0x60;
+const TAG_BIND_RESPONSE: u8 = 0x61;
+const TAG_UNBIND_REQUEST: u8 = 0x42;
+const TAG_SIMPLE_AUTH: u8 = 0x80;
+
+const LDAP_VERSION: u32 = 3;
+const RESULT_SUCCESS: u32 = 0;
+const RESULT_INVALID_CREDENTIALS: u32 = 49;
+// Bind responses are small, anything larger is not one.
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+pub(crate) struct LdapProvider {
+    config: LdapConfig,
+    mapping: IdentityMapping,
+    tls_config: Arc<rustls::ClientConfig>,
+}
+
+impl LdapProvider {
+    pub(crate) fn new(config: &LdapConfig) -> Result<Self> {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&rustls::Certificate(config.ca_cert_bytes.clone()))?;
+        let tls_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(Self {
+            config: config.clone(),
+            mapping: IdentityMapping::new(&config.mapping)?,
+            tls_config: Arc::new(tls_config),
+        })
+    }
+
+    // Bind as the user with the password, returning whether the server
+    // accepted the credential.
+    fn bind(&self, id: &str, password: &str) -> Result<bool> {
+        // A simple bind without a password is an anonymous bind, which
+        // servers accept whoever the DN names.
+        ensure!(!password.is_empty(), "Empty password");
+        let bind_dn = self.config.bind_dn.replace("{}", &escape_dn_value(id));
+        let message_id = rand::thread_rng().gen_range(1..i32::MAX as u32);
+        let request = bind_request(message_id, &bind_dn, password);
+
+        let config = &self.config;
+        let server_name = rustls::ServerName::try_from(config.server.as_str())?;
+        let connection = rustls::ClientConnection::new(self.tls_config.clone(), server_name)?;
+        let tcp = TcpStream::connect((config.server.as_str(), config.port))?;
+        let timeout = Duration::from_secs(config.timeout_secs);
+        tcp.set_read_timeout(Some(timeout))?;
+        tcp.set_write_timeout(Some(timeout))?;
+        let mut stream = rustls::StreamOwned::new(connection, tcp);
+
+        stream.write_all(&request)?;
+        stream.flush()?;
+        let response = read_message(&mut stream)?;
+        let result_code = parse_bind_response(message_id, &response)?;
+        let _ = stream.write_all(&unbind_request(message_id + 1));
+
+        match result_code {
+            RESULT_SUCCESS => Ok(true),
+            RESULT_INVALID_CREDENTIALS => Ok(false),
+            code => bail!("LDAP bind failed with result code {}", code),
+        }
+    }
+}
+
+impl IdentityProvider for LdapProvider {
+    fn name(&self) -> &'static str {
+        "ldap"
+    }
+
+    fn authenticate(&self, id: &str, password: &str) -> Result<Option<ExternalIdentity>> {
+        ensure!(!id.is_empty(), "Empty login id");
+        if !self.bind(id, password)? {
+            return Ok(None);
+        }
+        Ok(Some(self.mapping.identity(id, None)))
+    }
+}
+
+// Escape the special characters of an attribute value in a DN (RFC 4514).
+fn escape_dn_value(value: &str) -> String {
+    let last = value.chars().count().saturating_sub(1);
+    let mut escaped = String::with_capacity(value.len());
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            ',' | '+' | '"' | '\\' | '<' | '>' | ';' | '=' => escaped.push('\\'),
+            '#' | ' ' if i == 0 => escaped.push('\\'),
+            ' ' if i == last => escaped.push('\\'),
+            '\0' => {
+                escaped.push_str("\\00");
+                continue;
+            }
+            _ => (),
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        encoded.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        encoded.push(0x80 | (bytes.len() - skip) as u8);
+        encoded.extend_from_slice(&bytes[skip..]);
+    }
+    encoded.extend_from_slice(content);
+    encoded
+}
+
+// The shortest two's complement encoding of a non-negative integer.
+fn encode_integer(tag: u8, value: u32) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 3 && bytes[start] == 0 && bytes[start + 1] < 0x80 {
+        start += 1;
+    }
+    encode(tag, &bytes[start..])
+}
+
+// The request and the intermediate encodings holding the password are cleared
+// once dropped.
+fn bind_request(message_id: u32, bind_dn: &str, password: &str) -> Zeroizing<Vec<u8>> {
+    let mut bind = Zeroizing::new(encode_integer(TAG_INTEGER, LDAP_VERSION));
+    bind.extend(encode(TAG_OCTET_STRING, bind_dn.as_bytes()));
+    bind.extend(Zeroizing::new(encode(TAG_SIMPLE_AUTH, password.as_bytes())).iter());
+
+    let mut message = Zeroizing::new(encode_integer(TAG_INTEGER, message_id));
+    message.extend(Zeroizing::new(encode(TAG_BIND_REQUEST, &bind)).iter());
+    Zeroizing::new(encode(TAG_SEQUENCE, &message))
+}
+
+fn unbind_request(message_id: u32) -> Vec<u8> {
+    let mut message = encode_integer(TAG_INTEGER, message_id);
+    message.extend(encode(TAG_UNBIND_REQUEST, &[]));
+    encode(TAG_SEQUENCE, &message)
+}
+
+// Read one LDAP message, i.e., a BER-encoded sequence, from the stream.
+fn read_message(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+    ensure!(header[0] == TAG_SEQUENCE, "Not an LDAP message");
+    let len = match header[1] {
+        len if len < 0x80 => len as usize,
+        octets if (0x81..=0x84).contains(&octets) => {
+            let mut bytes = [0u8; 4];
+            let octets = (octets & 0x7f) as usize;
+            reader.read_exact(&mut bytes[4 - octets..])?;
+            u32::from_be_bytes(bytes) as usize
+        }
+        _ => bail!("Unsupported length of LDAP message"),
+    };
+    ensure!(len <= MAX_MESSAGE_LEN, "LDAP message too large");
+    let mut message = vec![0u8; len];
+    reader.read_exact(&mut message)?;
+    Ok(message)
+}
+
+// Take the next element of the tag off the encoded elements.
+fn next_element<'a>(encoded: &mut &'a [u8], tag: u8) -> Result<&'a [u8]> {
+    let truncated = || anyhow!("Truncated BER element");
+    ensure!(encoded.first() == Some(&tag), "Unexpected BER tag");
+    let first = *encoded.get(1).ok_or_else(truncated)?;
+    let (len, header_len) = match first {
+        len if len < 0x80 => (len as usize, 2),
+        octets if (0x81..=0x84).contains(&octets) => {
+            let octets = (octets & 0x7f) as usize;
+            let bytes = encoded.get(2..2 + octets).ok_or_else(truncated)?;
+            let len = bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+            (len, 2 + octets)
+        }
+        _ => bail!("Unsupported BER length"),
+    };
+    let end = header_len.checked_add(len).ok_or_else(truncated)?;
+    let element = encoded.get(header_len..end).ok_or_else(truncated)?;
+    *encoded = &encoded[end..];
+    Ok(element)
+}
+
+fn decode_integer(bytes: &[u8]) -> Result<u32> {
+    ensure!(
+        !bytes.is_empty() && bytes.len() <= 4 && bytes[0] < 0x80,
+        "Unsupported BER integer"
+    );
+    Ok(bytes.iter().fold(0, |value, b| (value << 8) | *b as u32))
+}
+
+// The result code of the bind response to the request of the message id.
+fn parse_bind_response(message_id: u32, message: &[u8]) -> Result<u32> {
+    let mut elements = message;
+    let id = decode_integer(next_element(&mut elements, TAG_INTEGER)?)?;
+    ensure!(id == message_id, "Unexpected LDAP message id {}", id);
+    let mut response = next_element(&mut elements, TAG_BIND_RESPONSE)?;
+    decode_integer(next_element(&mut response, TAG_ENUMERATED)?)
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub async fn test_ldap_bind_messages() {
+        assert_eq!(escape_dn_value("alice"), "alice");
+        assert_eq!(escape_dn_value("a,b=c+d"), "a\\,b\\=c\\+d");
+        assert_eq!(escape_dn_value(" #x "), "\\ #x\\ ");
+        assert_eq!(escape_dn_value("#x"), "\\#x");
+
+        assert_eq!(encode_integer(TAG_INTEGER, 0), vec![0x02, 0x01, 0x00]);
+        let integer = encode_integer(TAG_INTEGER, 0x80);
+        assert_eq!(integer, vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(encode(TAG_OCTET_STRING, &[0; 200])[..3], [0x04, 0x81, 200]);
+
+        let request = bind_request(1, "uid=alice", "secret");
+        let expected = [
+            &[0x30, 0x1b, 0x02, 0x01, 0x01, 0x60, 0x16, 0x02, 0x01, 0x03][..],
+            &[0x04, 0x09],
+            b"uid=alice",
+            &[0x80, 0x06],
+            b"secret",
+        ]
+        .concat();
+        assert_eq!(*request, expected);
+
+        // The request parses as a message
+        let message = read_message(&mut request.as_slice()).unwrap();
+        assert_eq!(message, request[2..]);
+
+        let response = [
+            0x02, 0x01, 0x07, 0x61, 0x07, 0x0a, 0x01, 0x31, 0x04, 0x00, 0x04, 0x00,
+        ];
+        assert_eq!(parse_bind_response(7, &response).unwrap(), 49);
+        assert!(parse_bind_response(8, &response).is_err());
+        assert!(parse_bind_response(7, &response[..6]).is_err());
+        // A notice of disconnection is not a bind response
+        let notice = [0x02, 0x01, 0x00, 0x78, 0x03, 0x0a, 0x01, 0x34];
+        assert!(parse_bind_response(0, &notice).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! External identity providers configured in the `identity_providers`
+//! section of the runtime config. A provider verifies the credential of a
+//! login, and maps the identity it proves to a Teaclave user, and to the role
+//! the user is registered with if not registered yet.
+
+pub(crate) mod ldap;
+pub(crate) mod oidc;
+
+use anyhow::{bail, ensure, Result};
+use std::sync::Arc;
+use teaclave_config::{IdentityMappingConfig, IdentityProvidersConfig};
+use teaclave_types::UserRole;
+
+pub(crate) use ldap::LdapProvider;
+pub(crate) use oidc::OidcProvider;
+
+/// The Teaclave user an external identity is mapped to.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ExternalIdentity {
+    pub(crate) user_id: String,
+    /// The role of the user, if the provider assigns one
+    pub(crate) role: Option<UserRole>,
+}
+
+pub(crate) trait IdentityProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Verify the credential presented with the login id, returning the
+    /// identity it proves, or None if the provider rejects the credential.
+    /// This may block on the provider.
+    fn authenticate(&self, id: &str, credential: &str) -> Result<Option<ExternalIdentity>>;
+}
+
+pub(crate) struct IdentityMapping {
+    user_id_prefix: String,
+    default_role: Option<UserRole>,
+}
+
+impl IdentityMapping {
+    pub(crate) fn new(config: &IdentityMappingConfig) -> Result<Self> {
+        let default_role = match &config.default_role {
+            Some(role) => Some(parse_role(role, &config.default_attribute)?),
+            None => None,
+        };
+        Ok(Self {
+            user_id_prefix: config.user_id_prefix.clone(),
+            default_role,
+        })
+    }
+
+    /// Map the external id, with the role the provider assigns if any.
+    pub(crate) fn identity(&self, external_id: &str, role: Option<UserRole>) -> ExternalIdentity {
+        ExternalIdentity {
+            user_id: format!("{}{}", self.user_id_prefix, external_id),
+            role: role.or_else(|| self.default_role.clone()),
+        }
+    }
+}
+
+pub(crate) fn parse_role(role: &str, attribute: &str) -> Result<UserRole> {
+    let parsed = UserRole::new(role, attribute);
+    ensure!(parsed != UserRole::Invalid, "Invalid role {}", role);
+    Ok(parsed)
+}
+
+#[derive(Default)]
+pub(crate) struct IdentityProviders {
+    local_users: Vec<String>,
+    ldap: Option<Arc<dyn IdentityProvider>>,
+    oidc: Option<Arc<dyn IdentityProvider>>,
+}
+
+impl IdentityProviders {
+    pub(crate) fn new(config: &IdentityProvidersConfig) -> Result<Self> {
+        let ldap = match &config.ldap {
+            Some(ldap) => Some(Arc::new(LdapProvider::new(ldap)?) as Arc<dyn IdentityProvider>),
+            None => None,
+        };
+        let oidc = match &config.oidc {
+            Some(oidc) => Some(Arc::new(OidcProvider::new(oidc)?) as Arc<dyn IdentityProvider>),
+            None => None,
+        };
+        Ok(Self {
+            local_users: config.local_users.clone(),
+            ldap,
+            oidc,
+        })
+    }
+
+    pub(crate) fn is_local_user(&self, user_id: &str) -> bool {
+        self.local_users.iter().any(|user| user == user_id)
+    }
+
+    /// The provider named in the login, or None if the password kept by the
+    /// service is to be verified. Logins naming no provider go to LDAP if
+    /// configured, except for the local users.
+    pub(crate) fn provider_of(
+        &self,
+        name: &str,
+        id: &str,
+    ) -> Result<Option<Arc<dyn IdentityProvider>>> {
+        let provider = match name {
+            "" if self.is_local_user(id) => return Ok(None),
+            "" => return Ok(self.ldap.clone()),
+            "ldap" => &self.ldap,
+            "oidc" => &self.oidc,
+            _ => bail!("Unknown identity provider {}", name),
+        };
+        match provider {
+            Some(provider) => Ok(Some(provider.clone())),
+            None => bail!("Identity provider {} is not configured", name),
+        }
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub async fn test_identity_mapping() {
+        let config = IdentityMappingConfig {
+            user_id_prefix: "corp/".to_string(),
+            default_role: Some("DataOwner".to_string()),
+            default_attribute: "corp".to_string(),
+        };
+        let mapping = IdentityMapping::new(&config).unwrap();
+        let identity = mapping.identity("alice", None);
+        assert_eq!(identity.user_id, "corp/alice");
+        assert_eq!(identity.role, Some(UserRole::DataOwner("corp".to_string())));
+        let identity = mapping.identity("bob", Some(UserRole::FunctionOwner));
+        assert_eq!(identity.role, Some(UserRole::FunctionOwner));
+
+        let mapping = IdentityMapping::new(&IdentityMappingConfig::default()).unwrap();
+        assert_eq!(mapping.identity("alice", None).role, None);
+
+        let config = IdentityMappingConfig {
+            default_role: Some("Superuser".to_string()),
+            ..Default::default()
+        };
+        assert!(IdentityMapping::new(&config).is_err());
+
+        let providers = IdentityProviders {
+            local_users: vec!["admin".to_string()],
+            ..Default::default()
+        };
+        assert!(providers.provider_of("", "admin").unwrap().is_none());
+        assert!(providers.provider_of("", "alice").unwrap().is_none());
+        assert!(providers.provider_of("ldap", "alice").is_err());
+        assert!(providers.provider_of("saml", "alice").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Logins with the ID tokens of an OpenID Connect provider, which are
+//! validated against the configured issuer, audience and public key in the
+//! enclave, without connecting to the provider.
+
+use super::{parse_role, ExternalIdentity, IdentityMapping, IdentityProvider};
+use anyhow::{anyhow, bail, Result};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use teaclave_config::OidcConfig;
+
+// Clock skew tolerated in the expiration of ID tokens
+const LEEWAY_SECS: u64 = 60;
+
+type Claims = HashMap<String, Value>;
+
+pub(crate) struct OidcProvider {
+    config: OidcConfig,
+    mapping: IdentityMapping,
+    key: DecodingKey<'static>,
+    validation: Validation,
+}
+
+impl OidcProvider {
+    pub(crate) fn new(config: &OidcConfig) -> Result<Self> {
+        let algorithm = Algorithm::from_str(&config.algorithm)?;
+        let key = decoding_key(algorithm, &config.public_key_bytes)?;
+        let mut validation = Validation {
+            iss: Some(config.issuer.clone()),
+            algorithms: vec![algorithm],
+            leeway: LEEWAY_SECS,
+            ..Default::default()
+        };
+        validation.set_audience(&[&config.audience]);
+        Ok(Self {
+            config: config.clone(),
+            mapping: IdentityMapping::new(&config.mapping)?,
+            key,
+            validation,
+        })
+    }
+
+    fn identity_of(&self, claims: &Claims) -> Result<ExternalIdentity> {
+        let config = &self.config;
+        let external_id = string_claim(claims, &config.user_id_claim)
+            .ok_or_else(|| anyhow!("Missing claim {}", config.user_id_claim))?;
+        let claim = |name: &Option<String>| name.as_ref().and_then(|n| string_claim(claims, n));
+        let role = match claim(&config.role_claim) {
+            Some(role) => {
+                let attribute = claim(&config.attribute_claim).unwrap_or_default();
+                Some(parse_role(role, attribute)?)
+            }
+            None => None,
+        };
+        Ok(self.mapping.identity(external_id, role))
+    }
+}
+
+impl IdentityProvider for OidcProvider {
+    fn name(&self) -> &'static str {
+        "oidc"
+    }
+
+    fn authenticate(&self, id: &str, token: &str) -> Result<Option<ExternalIdentity>> {
+        let claims = match jsonwebtoken::decode::<Claims>(token, &self.key, &self.validation) {
+            Ok(data) => data.claims,
+            Err(e) => {
+                debug!("Rejected ID token: {:?}", e);
+                return Ok(None);
+            }
+        };
+        let identity = self.identity_of(&claims)?;
+        // The login id is optional, but has to be the user of the token if
+        // given.
+        if !id.is_empty() && id != identity.user_id {
+            return Ok(None);
+        }
+        Ok(Some(identity))
+    }
+}
+
+// ID tokens are signed with the private keys of the providers, never with a
+// shared secret.
+fn decoding_key(algorithm: Algorithm, pem: &[u8]) -> Result<DecodingKey<'static>> {
+    let key = match algorithm {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => DecodingKey::from_rsa_pem(pem)?,
+        Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(pem)?,
+        _ => bail!("Unsupported algorithm of ID tokens {:?}", algorithm),
+    };
+    Ok(key.into_static())
+}
+
+fn string_claim<'a>(claims: &'a Claims, name: &str) -> Option<&'a str> {
+    claims.get(name).and_then(Value::as_str)
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_config::IdentityMappingConfig;
+    use teaclave_types::UserRole;
+
+    pub async fn test_oidc_identity() {
+        let config = OidcConfig {
+            issuer: "https://login.example.com".to_string(),
+            audience: "teaclave".to_string(),
+            public_key: Default::default(),
+            public_key_bytes: Vec::new(),
+            algorithm: "RS256".to_string(),
+            user_id_claim: "email".to_string(),
+            role_claim: Some("role".to_string()),
+            attribute_claim: Some("org".to_string()),
+            mapping: IdentityMappingConfig {
+                user_id_prefix: "oidc/".to_string(),
+                ..Default::default()
+            },
+        };
+        // Shared secrets are not accepted
+        assert!(decoding_key(Algorithm::HS256, b"secret").is_err());
+        assert!(decoding_key(Algorithm::RS256, b"not a key").is_err());
+
+        let provider = OidcProvider {
+            mapping: IdentityMapping::new(&config.mapping).unwrap(),
+            config,
+            key: DecodingKey::from_secret(b"secret").into_static(),
+            validation: Validation::default(),
+        };
+        let claims: Claims = serde_json::from_str(
+            r#"{"sub": "1234", "email": "alice@example.com", "role": "DataOwner", "org": "org1"}"#,
+        )
+        .unwrap();
+        let identity = provider.identity_of(&claims).unwrap();
+        assert_eq!(identity.user_id, "oidc/alice@example.com");
+        assert_eq!(identity.role, Some(UserRole::DataOwner("org1".to_string())));
+
+        let claims: Claims = serde_json::from_str(r#"{"email": "bob@example.com"}"#).unwrap();
+        assert_eq!(provider.identity_of(&claims).unwrap().role, None);
+        let claims: Claims =
+            serde_json::from_str(r#"{"email": "bob@example.com", "role": "Root"}"#).unwrap();
+        assert!(provider.identity_of(&claims).is_err());
+        let claims: Claims = serde_json::from_str(r#"{"sub": "1234"}"#).unwrap();
+        assert!(provider.identity_of(&claims).is_err());
+
+        // The signature of the token is checked before anything else
+        assert!(provider.authenticate("", "a.b.c").unwrap().is_none());
+    }
+}
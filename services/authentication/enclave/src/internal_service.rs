@@ -16,11 +16,13 @@
 // under the License.
 
 use crate::error::AuthenticationError;
+use crate::revocation::RevocationLog;
 use crate::user_db::DbClient;
 use crate::user_info::UserInfo;
 use std::sync::{Arc, Mutex};
 use teaclave_proto::teaclave_authentication_service::{
-    TeaclaveAuthenticationInternal, UserAuthenticateRequest, UserAuthenticateResponse,
+    ListRevocationsRequest, ListRevocationsResponse, TeaclaveAuthenticationInternal,
+    UserAuthenticateRequest, UserAuthenticateResponse,
 };
 use teaclave_rpc::{ensure, Request, Response};
 use teaclave_service_enclave_utils::bail;
@@ -29,13 +31,19 @@ use teaclave_types::TeaclaveServiceResponseResult;
 pub(crate) struct TeaclaveAuthenticationInternalService {
     db_client: Arc<Mutex<DbClient>>,
     jwt_secret: Vec<u8>,
+    revocations: Arc<RevocationLog>,
 }
 
 impl TeaclaveAuthenticationInternalService {
-    pub(crate) fn new(db_client: DbClient, jwt_secret: Vec<u8>) -> Self {
+    pub(crate) fn new(
+        db_client: DbClient,
+        jwt_secret: Vec<u8>,
+        revocations: Arc<RevocationLog>,
+    ) -> Self {
         Self {
             db_client: Arc::new(Mutex::new(db_client)),
             jwt_secret,
+            revocations,
         }
     }
 }
@@ -63,6 +71,17 @@ impl TeaclaveAuthenticationInternal for TeaclaveAuthenticationInternalService {
             .map_err(|_| AuthenticationError::IncorrectToken)?;
         Ok(Response::new(UserAuthenticateResponse::new(claims)))
     }
+
+    async fn list_revocations(
+        &self,
+        request: Request<ListRevocationsRequest>,
+    ) -> TeaclaveServiceResponseResult<ListRevocationsResponse> {
+        let request = request.into_inner();
+        let (user_ids, latest, complete) = self.revocations.since(&request.epoch, request.after);
+        let response =
+            ListRevocationsResponse::new(self.revocations.epoch(), latest, user_ids, complete);
+        Ok(Response::new(response))
+    }
 }
 
 #[cfg(feature = "enclave_unit_test")]
@@ -93,6 +112,7 @@ pub mod tests {
         TeaclaveAuthenticationInternalService {
             db_client: Arc::new(Mutex::new(database.get_client())),
             jwt_secret,
+            revocations: Arc::new(RevocationLog::new()),
         }
     }
 
@@ -194,6 +214,33 @@ pub mod tests {
         }
     }
 
+    pub async fn test_list_revocations() {
+        let service = get_mock_service();
+        let list = |epoch: &str, after| {
+            let request = ListRevocationsRequest::new(epoch, after).into_request();
+            service.list_revocations(request)
+        };
+
+        // A follower of another epoch starts over from the latest one
+        let response = list("", 0).await.unwrap().into_inner();
+        assert!(!response.complete);
+        assert_eq!(response.latest, 0);
+        let epoch = response.epoch;
+
+        service.revocations.revoke("alice");
+        service.revocations.revoke("bob");
+        let response = list(&epoch, 0).await.unwrap().into_inner();
+        assert!(response.complete);
+        assert_eq!(response.latest, 2);
+        assert_eq!(response.user_ids, vec!["alice", "bob"]);
+        let response = list(&epoch, 1).await.unwrap().into_inner();
+        assert_eq!(response.user_ids, vec!["bob"]);
+        let response = list(&epoch, 2).await.unwrap().into_inner();
+        assert!(response.complete);
+        assert!(response.user_ids.is_empty());
+        assert!(!list(&epoch, 3).await.unwrap().into_inner().complete);
+    }
+
     fn get_correct_claim(id: &str) -> UserAuthClaims {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -36,13 +36,23 @@ use teaclave_config::RuntimeConfig;
 use teaclave_proto::teaclave_authentication_service::{
     TeaclaveAuthenticationApiServer, TeaclaveAuthenticationInternalServer,
 };
+use teaclave_proto::teaclave_management_service::TeaclaveManagementClient;
+use teaclave_proto::teaclave_storage_service::TeaclaveStorageClient;
 use teaclave_rpc::{config::SgxTrustedTlsServerConfig, transport::Server};
-use teaclave_service_enclave_utils::{base_dir_for_db, ServiceEnclave};
+use teaclave_service_enclave_utils::{
+    base_dir_for_db, create_trusted_management_endpoint, create_trusted_storage_endpoint,
+    drain_signal, drained, ServiceEnclave,
+};
 use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult, UserRole};
 
 mod api_service;
+mod audit;
 mod error;
+mod identity;
 mod internal_service;
+mod password;
+mod revocation;
+mod totp;
 mod user_db;
 mod user_info;
 
@@ -50,24 +60,38 @@ async fn start_internal_endpoint(
     addr: std::net::SocketAddr,
     db_client: user_db::DbClient,
     jwt_secret: Vec<u8>,
+    revocations: Arc<revocation::RevocationLog>,
     attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
     accepted_enclave_attrs: Vec<teaclave_types::EnclaveAttr>,
+    verification_policy: verifier::VerificationPolicy,
+    min_protocol_version: u32,
 ) -> Result<()> {
     let server_config = SgxTrustedTlsServerConfig::from_attested_tls_config(attested_tls_config)?
         .attestation_report_verifier(
             accepted_enclave_attrs,
             AS_ROOT_CA_CERT,
             verifier::universal_quote_verifier,
+            verification_policy,
         )?
+        .mutual_attestation(true)
+        .min_protocol_version(min_protocol_version)
+        .session_freshness(std::time::Duration::from_secs(
+            teaclave_config::build::GRPC_CONFIG.session_freshness_secs,
+        ))
         .into();
-    let service =
-        internal_service::TeaclaveAuthenticationInternalService::new(db_client, jwt_secret);
-    Server::builder()
+    let service = internal_service::TeaclaveAuthenticationInternalService::new(
+        db_client,
+        jwt_secret,
+        revocations,
+    );
+    let served = Server::builder()
         .tls_config(server_config)
         .map_err(|_| anyhow!("TeaclaveFrontendServer tls config error"))?
         .add_service(TeaclaveAuthenticationInternalServer::new_with_builtin_config(service))
-        .serve(addr)
-        .await?;
+        .serve_with_shutdown(addr, drain_signal())
+        .await;
+    drained();
+    served?;
     Ok(())
 }
 
@@ -76,19 +100,39 @@ async fn start_api_endpoint(
     db_client: user_db::DbClient,
     jwt_secret: Vec<u8>,
     attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
+    min_protocol_version: u32,
+    auditor: audit::CredentialAuditor,
+    revocations: Arc<revocation::RevocationLog>,
+    identity_providers: Arc<identity::IdentityProviders>,
+    recovery_codes: totp::RecoveryCodeStore,
+    password_policy: password::PasswordPolicy,
 ) -> Result<()> {
-    let tls_config =
-        SgxTrustedTlsServerConfig::from_attested_tls_config(attested_tls_config)?.into();
+    let tls_config = SgxTrustedTlsServerConfig::from_attested_tls_config(attested_tls_config)?
+        .min_protocol_version(min_protocol_version)
+        .session_freshness(std::time::Duration::from_secs(
+            teaclave_config::build::GRPC_CONFIG.session_freshness_secs,
+        ))
+        .into();
 
-    let service = api_service::TeaclaveAuthenticationApiService::new(db_client, jwt_secret);
-    Server::builder()
+    let service = api_service::TeaclaveAuthenticationApiService::new(
+        db_client,
+        jwt_secret,
+        auditor,
+        revocations,
+        identity_providers,
+        recovery_codes,
+        password_policy,
+    );
+    let served = Server::builder()
         .tls_config(tls_config)
         .map_err(|_| anyhow!("TeaclaveAuthenticationApiServer tls config error"))?
         .add_service(TeaclaveAuthenticationApiServer::new_with_builtin_config(
             service,
         ))
-        .serve(addr)
-        .await?;
+        .serve_with_shutdown(addr, drain_signal())
+        .await;
+    drained();
+    served?;
     Ok(())
 }
 
@@ -110,7 +154,10 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
     let api_listen_address = config.api_endpoints.authentication.listen_address;
     let internal_listen_address = config.internal_endpoints.authentication.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(config)?;
+    let verification_policy = verifier::VerificationPolicy::from_teaclave_config(config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
+        .endorsement_cache(config.attestation.endorsement_cache_path("authentication"))
+        .reuse_cached_endorsement(config.attestation.reuse_cached_endorsement)
         .generate_and_endorse()?
         .attested_tls_config()
         .ok_or_else(|| anyhow!("cannot get attested TLS config"))?;
@@ -124,6 +171,39 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
     let mut rng = rand::thread_rng();
     rng.fill_bytes(&mut api_jwt_secret);
     let internal_jwt_secret = api_jwt_secret.to_owned();
+    let revocations = Arc::new(revocation::RevocationLog::new());
+    let identity_providers = Arc::new(identity::IdentityProviders::new(
+        &config.identity_providers,
+    )?);
+
+    // The management service may start later, so it is connected to when the
+    // first credential change is sent to the auditor.
+    let management_service_endpoint = create_trusted_management_endpoint(
+        &config.internal_endpoints.management.advertised_address,
+        &enclave_info,
+        AS_ROOT_CA_CERT,
+        verifier::universal_quote_verifier,
+        verification_policy.clone(),
+        attested_tls_config.clone(),
+    )?;
+    let management_client = TeaclaveManagementClient::new_with_builtin_config(
+        management_service_endpoint.connect_lazy(),
+    );
+    let auditor = audit::CredentialAuditor::new(management_client);
+
+    // Recovery codes of TOTP are kept in the storage service, which is
+    // connected to when they are first stored or redeemed.
+    let storage_service_endpoint = create_trusted_storage_endpoint(
+        &config.internal_endpoints.storage.advertised_address,
+        &enclave_info,
+        AS_ROOT_CA_CERT,
+        verifier::universal_quote_verifier,
+        verification_policy.clone(),
+        attested_tls_config.clone(),
+    )?;
+    let storage_client =
+        TeaclaveStorageClient::new_with_builtin_config(storage_service_endpoint.connect_lazy());
+    let recovery_codes = totp::RecoveryCodeStore::new(storage_client);
 
     let attested_tls_config_ref = attested_tls_config.clone();
     {
@@ -139,6 +219,12 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         client,
         api_jwt_secret,
         attested_tls_config_ref,
+        config.rpc.min_protocol_version,
+        auditor,
+        revocations.clone(),
+        identity_providers,
+        recovery_codes,
+        password::PasswordPolicy::new(&config.password_policy),
     ));
 
     info!(" Starting Authentication: setup API endpoint finished ...");
@@ -148,8 +234,11 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         internal_listen_address,
         client,
         internal_jwt_secret,
+        revocations,
         attested_tls_config,
         accepted_enclave_attrs,
+        verification_policy,
+        config.rpc.min_protocol_version,
     ));
     info!(" Starting Authentication: setup Internal endpoint finished ...");
 
@@ -200,6 +289,7 @@ fn handle_init_enclave(_: &InitEnclaveInput) -> TeeServiceResult<InitEnclaveOutp
 
 #[handle_ecall]
 fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<FinalizeEnclaveOutput> {
+    ServiceEnclave::drain();
     ServiceEnclave::finalize()?;
     Ok(FinalizeEnclaveOutput)
 }
@@ -219,17 +309,29 @@ pub mod tests {
     pub fn run_tests() -> bool {
         run_async_tests!(
             api_service::tests::test_user_login,
+            api_service::tests::test_totp_login,
             api_service::tests::test_user_register,
             api_service::tests::test_user_update,
             api_service::tests::test_user_change_password,
             api_service::tests::test_reset_user_password,
+            api_service::tests::test_change_password,
+            api_service::tests::test_force_password_reset,
+            api_service::tests::test_list_users,
             api_service::tests::test_delete_user,
+            audit::tests::test_credential_entry,
             internal_service::tests::test_user_authenticate,
             internal_service::tests::test_invalid_algorithm,
             internal_service::tests::test_invalid_issuer,
             internal_service::tests::test_expired_token,
             internal_service::tests::test_invalid_user,
             internal_service::tests::test_wrong_secret,
+            internal_service::tests::test_list_revocations,
+            identity::tests::test_identity_mapping,
+            identity::ldap::tests::test_ldap_bind_messages,
+            identity::oidc::tests::test_oidc_identity,
+            password::tests::test_password_policy,
+            totp::tests::test_totp_codes,
+            totp::tests::test_recovery_codes,
         )
     }
 }
@@ -0,0 +1,119 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The policy of the passwords kept by the service, i.e., the complexity they
+//! are checked for when they are set, and how long they are valid for.
+
+use crate::user_info::UserInfo;
+use teaclave_config::PasswordPolicyConfig;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Default)]
+pub(crate) struct PasswordPolicy {
+    config: PasswordPolicyConfig,
+}
+
+impl PasswordPolicy {
+    pub(crate) fn new(config: &PasswordPolicyConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// The first rule the password breaks, if any.
+    pub(crate) fn check(&self, password: &str) -> Option<String> {
+        let config = &self.config;
+        if password.chars().count() < config.min_length {
+            return Some(format!("shorter than {} characters", config.min_length));
+        }
+        let rules: [(bool, fn(char) -> bool, &str); 4] = [
+            (
+                config.require_uppercase,
+                char::is_uppercase,
+                "uppercase letter",
+            ),
+            (
+                config.require_lowercase,
+                char::is_lowercase,
+                "lowercase letter",
+            ),
+            (config.require_digit, |c| c.is_ascii_digit(), "digit"),
+            (config.require_symbol, is_symbol, "symbol"),
+        ];
+        rules
+            .iter()
+            .find(|(required, matches, _)| *required && !password.chars().any(*matches))
+            .map(|(_, _, name)| format!("no {}", name))
+    }
+
+    /// Whether the password of the user has to be changed before logging in,
+    /// i.e., a reset is forced or it has expired. Passwords set before their
+    /// time was kept are expired once they have a maximum age.
+    pub(crate) fn needs_change(&self, user: &UserInfo, now: u64) -> bool {
+        if user.password_reset_required {
+            return true;
+        }
+        match self.config.max_age_days {
+            0 => false,
+            days => {
+                user.password_set_at
+                    .saturating_add(days.saturating_mul(SECS_PER_DAY))
+                    <= now
+            }
+        }
+    }
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_alphanumeric() && !c.is_whitespace()
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_types::UserRole;
+
+    pub async fn test_password_policy() {
+        let policy = PasswordPolicy::default();
+        assert!(policy.check("password").is_none());
+        assert!(policy.check("passwd").is_some());
+
+        let policy = PasswordPolicy::new(&PasswordPolicyConfig {
+            min_length: 10,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: true,
+            max_age_days: 90,
+        });
+        assert!(policy.check("Passw0rd!").is_some());
+        assert!(policy.check("password10!").is_some());
+        assert!(policy.check("PASSWORD10!").is_some());
+        assert!(policy.check("Password!!").is_some());
+        assert!(policy.check("Password10").is_some());
+        assert!(policy.check("Password10!").is_none());
+
+        let mut user = UserInfo::new("test_policy_id", "Password10!", UserRole::FunctionOwner);
+        let set_at = user.password_set_at;
+        assert!(!policy.needs_change(&user, set_at + SECS_PER_DAY));
+        assert!(policy.needs_change(&user, set_at + 90 * SECS_PER_DAY));
+        assert!(!PasswordPolicy::default().needs_change(&user, u64::MAX / 2));
+        user.password_reset_required = true;
+        assert!(PasswordPolicy::default().needs_change(&user, set_at));
+    }
+}
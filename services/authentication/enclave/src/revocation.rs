@@ -0,0 +1,84 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Revocations of the credentials of users, i.e., role and password changes
+//! and deletions. They are numbered in the epoch of the service, which starts
+//! over with a new JWT secret, and followed by the frontend service to drop
+//! the claims it caches for the users.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use uuid::Uuid;
+
+/// Most revocations kept, so that followers further behind start over.
+const MAX_REVOCATIONS: usize = 4096;
+
+#[derive(Default)]
+struct Revocations {
+    // The number of the latest revocation, 0 if there is none
+    latest: u64,
+    // The users of the latest revocations, the last one numbered `latest`
+    user_ids: VecDeque<String>,
+}
+
+pub(crate) struct RevocationLog {
+    epoch: String,
+    revocations: Mutex<Revocations>,
+}
+
+impl RevocationLog {
+    pub(crate) fn new() -> Self {
+        Self {
+            epoch: Uuid::new_v4().to_simple().to_string(),
+            revocations: Mutex::new(Revocations::default()),
+        }
+    }
+
+    pub(crate) fn epoch(&self) -> &str {
+        &self.epoch
+    }
+
+    pub(crate) fn revoke(&self, user_id: &str) {
+        let mut revocations = self.revocations.lock().unwrap();
+        revocations.latest += 1;
+        revocations.user_ids.push_back(user_id.to_owned());
+        if revocations.user_ids.len() > MAX_REVOCATIONS {
+            revocations.user_ids.pop_front();
+        }
+    }
+
+    /// The users revoked after the revocation numbered `after` in `epoch`,
+    /// the number of the latest revocation, and whether the users are
+    /// complete.
+    pub(crate) fn since(&self, epoch: &str, after: u64) -> (Vec<String>, u64, bool) {
+        let revocations = self.revocations.lock().unwrap();
+        let latest = revocations.latest;
+        // The number of the revocation before the oldest one kept
+        let oldest_after = latest - revocations.user_ids.len() as u64;
+        if epoch != self.epoch || after < oldest_after || after > latest {
+            return (Vec::new(), latest, false);
+        }
+        let user_ids = revocations
+            .user_ids
+            .iter()
+            .skip((after - oldest_after) as usize)
+            .cloned()
+            .collect();
+        (user_ids, latest, true)
+    }
+}
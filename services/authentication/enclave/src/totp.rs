@@ -0,0 +1,330 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Time-based one-time passwords (RFC 6238) as the second factor of logins,
+//! and the recovery codes replacing them when the authenticator is lost.
+//!
+//! A user enrolls with `GenerateTotpSecret`, which keeps a pending secret, and
+//! completes it by verifying a code of the secret with `VerifyTotp`, which
+//! returns the recovery codes. Logins of enrolled users need a code, or one of
+//! the recovery codes, each of which is accepted once. The codes are kept as
+//! digests in the storage service, sealed with a key of the user kept by this
+//! service, so that the storage service can neither read nor swap them, and
+//! they are gone with the user once deleted.
+
+use anyhow::{anyhow, ensure, Result};
+use rand::{Rng, RngCore};
+use ring::{constant_time, digest, hmac};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use teaclave_crypto::{AesGcm256Key, Zeroizing};
+use teaclave_proto::teaclave_storage_service::{GetRequest, PutRequest, TeaclaveStorageClient};
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Code;
+use tokio::sync::Mutex;
+
+use crate::user_info::ISSUER_NAME;
+
+const SECRET_LEN: usize = 20;
+pub(crate) const STEP_SECS: u64 = 30;
+const DIGITS: u32 = 6;
+// Codes of the adjacent steps are accepted for the clock skew of the
+// authenticators.
+const SKEW_STEPS: u64 = 1;
+
+const RECOVERY_CODES: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+const RECOVERY_CODES_PREFIX: &str = "totp_recovery_codes";
+// IV of AES-GCM sealing the recovery codes
+const SEAL_IV_LEN: usize = 12;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// TOTP enrollment of a user.
+#[derive(Default, Clone, Serialize, Deserialize, Debug)]
+pub(crate) struct TotpInfo {
+    /// Secret of the codes verified on login, none until enrolled
+    pub(crate) secret: Option<Vec<u8>>,
+    /// Secret generated but not verified yet
+    pub(crate) pending_secret: Option<Vec<u8>>,
+    /// The last step a code is accepted for, so that a code is used once
+    pub(crate) last_step: u64,
+    /// Key sealing the recovery codes in the storage service
+    pub(crate) recovery_key: Vec<u8>,
+}
+
+impl TotpInfo {
+    pub(crate) fn enrolled(&self) -> bool {
+        self.secret.is_some()
+    }
+}
+
+pub(crate) fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// The secret in base32 without padding, as entered into authenticators.
+pub(crate) fn encode_secret(secret: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in secret.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = buf.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        let chars = (chunk.len() * 8 + 4) / 5;
+        for i in 0..chars {
+            let index = (bits >> (35 - i * 5)) & 0x1f;
+            encoded.push(BASE32_ALPHABET[index as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// The otpauth URI of the secret, e.g., to be shown as a QR code.
+pub(crate) fn provisioning_uri(user_id: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={}&issuer={issuer}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(user_id),
+        encode_secret(secret),
+        DIGITS,
+        STEP_SECS,
+        issuer = ISSUER_NAME,
+    )
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub(crate) fn code_at(secret: &[u8], step: u64) -> u32 {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let tag = hmac::sign(&key, &step.to_be_bytes());
+    let digest = tag.as_ref();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let mut truncated = [0u8; 4];
+    truncated.copy_from_slice(&digest[offset..offset + 4]);
+    (u32::from_be_bytes(truncated) & 0x7fff_ffff) % 10u32.pow(DIGITS)
+}
+
+/// The step of the secret `code` is valid for at `now`, if any step after
+/// `last_step`.
+pub(crate) fn verify_code(secret: &[u8], code: &str, now: u64, last_step: u64) -> Option<u64> {
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let current = now / STEP_SECS;
+    (current.saturating_sub(SKEW_STEPS)..=current + SKEW_STEPS)
+        .filter(|step| *step > last_step)
+        .find(|step| {
+            let expected = format!(
+                "{:0width$}",
+                code_at(secret, *step),
+                width = DIGITS as usize
+            );
+            constant_time::verify_slices_are_equal(expected.as_bytes(), code.as_bytes()).is_ok()
+        })
+}
+
+pub(crate) fn generate_recovery_codes() -> Vec<Zeroizing<String>> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let mut code = Zeroizing::new(String::new());
+            for i in 0..RECOVERY_CODE_LEN {
+                if i == RECOVERY_CODE_LEN / 2 {
+                    code.push('-');
+                }
+                let c = BASE32_ALPHABET[rng.gen_range(0..BASE32_ALPHABET.len())];
+                code.push(c.to_ascii_lowercase() as char);
+            }
+            code
+        })
+        .collect()
+}
+
+// Recovery codes may be entered in either case, with or without the dash.
+fn recovery_code_digest(code: &str) -> Vec<u8> {
+    let normalized: Zeroizing<String> = Zeroizing::new(
+        code.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect(),
+    );
+    digest::digest(&digest::SHA256, normalized.as_bytes())
+        .as_ref()
+        .to_vec()
+}
+
+pub(crate) fn generate_recovery_key() -> Vec<u8> {
+    AesGcm256Key::random().key.to_vec()
+}
+
+// The digests are sealed with a random IV prepended, and bound to the user.
+fn seal(key: &[u8], user_id: &str, digests: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let cipher = AesGcm256Key::new(key, &AesGcm256Key::random().iv)?;
+    let mut sealed = serde_json::to_vec(digests)?;
+    cipher.encrypt_with_aad(&mut sealed, user_id.as_bytes())?;
+    Ok([cipher.iv.to_vec(), sealed].concat())
+}
+
+fn unseal(key: &[u8], user_id: &str, sealed: &[u8]) -> Result<Vec<Vec<u8>>> {
+    ensure!(
+        sealed.len() > SEAL_IV_LEN,
+        "Sealed recovery codes are truncated"
+    );
+    let (iv, ciphertext) = sealed.split_at(SEAL_IV_LEN);
+    let cipher = AesGcm256Key::new(key, iv)?;
+    let mut digests = ciphertext.to_vec();
+    cipher.decrypt_with_aad(&mut digests, user_id.as_bytes())?;
+    Ok(serde_json::from_slice(&digests)?)
+}
+
+#[derive(Clone)]
+pub(crate) struct RecoveryCodeStore {
+    // None if there is no storage service, e.g., in unit tests
+    storage_client: Option<Arc<Mutex<TeaclaveStorageClient<Channel>>>>,
+}
+
+impl RecoveryCodeStore {
+    pub(crate) fn new(storage_client: TeaclaveStorageClient<Channel>) -> Self {
+        Self {
+            storage_client: Some(Arc::new(Mutex::new(storage_client))),
+        }
+    }
+
+    pub(crate) fn disabled() -> Self {
+        Self {
+            storage_client: None,
+        }
+    }
+
+    fn client(&self) -> Result<&Arc<Mutex<TeaclaveStorageClient<Channel>>>> {
+        self.storage_client
+            .as_ref()
+            .ok_or_else(|| anyhow!("No storage service for recovery codes"))
+    }
+
+    /// Replace the recovery codes of the user with `codes`.
+    pub(crate) async fn store(
+        &self,
+        user_id: &str,
+        key: &[u8],
+        codes: &[Zeroizing<String>],
+    ) -> Result<()> {
+        let digests: Vec<Vec<u8>> = codes.iter().map(|c| recovery_code_digest(c)).collect();
+        let request = PutRequest::new(
+            storage_key(user_id).as_bytes(),
+            seal(key, user_id, &digests)?,
+        );
+        self.client()?.lock().await.put(request).await?;
+        Ok(())
+    }
+
+    /// Whether `code` is one of the recovery codes of the user, which is
+    /// removed once accepted.
+    pub(crate) async fn redeem(&self, user_id: &str, key: &[u8], code: &str) -> Result<bool> {
+        // The client is kept locked until the code is removed, so that it is
+        // accepted once even by concurrent logins.
+        let mut client = self.client()?.lock().await;
+        let request = GetRequest::new(storage_key(user_id).as_bytes());
+        let sealed = match client.get(request).await {
+            Ok(response) => response.into_inner().value,
+            Err(status) if status.code() == Code::NotFound => return Ok(false),
+            Err(status) => return Err(anyhow!("Failed to read recovery codes: {:?}", status)),
+        };
+        let mut digests = unseal(key, user_id, &sealed)?;
+        let digest = recovery_code_digest(code);
+        let position = digests
+            .iter()
+            .position(|d| constant_time::verify_slices_are_equal(d, &digest).is_ok());
+        let position = match position {
+            Some(position) => position,
+            None => return Ok(false),
+        };
+        digests.remove(position);
+        let request = PutRequest::new(
+            storage_key(user_id).as_bytes(),
+            seal(key, user_id, &digests)?,
+        );
+        client.put(request).await?;
+        Ok(true)
+    }
+}
+
+fn storage_key(user_id: &str) -> String {
+    format!("{}-{}", RECOVERY_CODES_PREFIX, user_id)
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub async fn test_totp_codes() {
+        // Test vectors of RFC 6238 with SHA-1
+        let secret = b"12345678901234567890";
+        assert_eq!(code_at(secret, 59 / STEP_SECS), 287082);
+        assert_eq!(code_at(secret, 1111111109 / STEP_SECS), 81804);
+        assert_eq!(code_at(secret, 1234567890 / STEP_SECS), 5924);
+        assert_eq!(code_at(secret, 2000000000 / STEP_SECS), 279037);
+
+        let now = 1111111109;
+        let step = now / STEP_SECS;
+        assert_eq!(verify_code(secret, "081804", now, 0), Some(step));
+        assert_eq!(
+            verify_code(secret, "081804", now + STEP_SECS, 0),
+            Some(step)
+        );
+        assert_eq!(verify_code(secret, "081804", now + 2 * STEP_SECS, 0), None);
+        // Codes are accepted once
+        assert_eq!(verify_code(secret, "081804", now, step), None);
+        assert_eq!(verify_code(secret, "81804", now, 0), None);
+
+        assert_eq!(encode_secret(secret), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(encode_secret(b"foob"), "MZXW6YQ");
+        let uri = provisioning_uri("alice@example.com", secret);
+        assert!(uri.starts_with("otpauth://totp/Teaclave:alice%40example.com?secret=GEZD"));
+    }
+
+    pub async fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODES);
+        assert_eq!(codes[0].len(), RECOVERY_CODE_LEN + 1);
+        assert_eq!(
+            recovery_code_digest(&codes[0]),
+            recovery_code_digest(&codes[0].replace('-', "").to_uppercase())
+        );
+
+        let key = generate_recovery_key();
+        let digests: Vec<Vec<u8>> = codes.iter().map(|c| recovery_code_digest(c)).collect();
+        let sealed = seal(&key, "alice", &digests).unwrap();
+        assert_eq!(unseal(&key, "alice", &sealed).unwrap(), digests);
+        // Sealed codes are bound to the user and the key
+        assert!(unseal(&key, "bob", &sealed).is_err());
+        assert!(unseal(&generate_recovery_key(), "alice", &sealed).is_err());
+
+        let store = RecoveryCodeStore::disabled();
+        assert!(store.redeem("alice", &key, &codes[0]).await.is_err());
+    }
+}
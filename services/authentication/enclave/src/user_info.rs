@@ -15,16 +15,21 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use jsonwebtoken as jwt;
 use rand::prelude::RngCore;
 use ring::{digest, pbkdf2};
 use serde::{Deserialize, Serialize};
 use std::num;
+use std::time::{SystemTime, UNIX_EPOCH};
+#[allow(unused_imports)]
+use std::untrusted::time::SystemTimeEx;
 use std::vec;
 
 use teaclave_types::{UserAuthClaims, UserRole};
 
+use crate::totp::TotpInfo;
+
 const SALT_LEN: usize = 16;
 const PASSWORD_DIGEST_LEN: usize = digest::SHA512_OUTPUT_LEN;
 const PBKDF2_ITERATIONS: u32 = 100_000;
@@ -40,6 +45,14 @@ pub(crate) struct UserInfo {
     pub role: UserRole,
     pub salt: Vec<u8>,
     pub salted_password_hash: Vec<u8>,
+    #[serde(default)]
+    pub totp: TotpInfo,
+    /// When the password was set, in seconds since the epoch, 0 if unknown
+    #[serde(default)]
+    pub password_set_at: u64,
+    /// Set by an admin to have the password changed before the next login
+    #[serde(default)]
+    pub password_reset_required: bool,
 }
 
 impl UserInfo {
@@ -61,6 +74,12 @@ impl UserInfo {
             role,
             salt,
             salted_password_hash,
+            totp: TotpInfo::default(),
+            password_set_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            password_reset_required: false,
         }
     }
 
@@ -94,6 +113,8 @@ impl UserInfo {
     }
 
     pub(crate) fn validate_token(&self, secret: &[u8], token: &str) -> Result<UserAuthClaims> {
+        // Tokens are revoked once a password reset is forced
+        ensure!(!self.password_reset_required, "Password reset required");
         let iss = ISSUER_NAME.to_string();
         let mut validation = jwt::Validation::new(JWT_ALG);
         validation.iss = Some(iss);
@@ -48,6 +48,7 @@ fn handle_init_enclave(_: &InitEnclaveInput) -> TeeServiceResult<InitEnclaveOutp
 
 #[handle_ecall]
 fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<FinalizeEnclaveOutput> {
+    crate::service::handoff_before_finalize();
     ServiceEnclave::finalize()?;
     Ok(FinalizeEnclaveOutput)
 }
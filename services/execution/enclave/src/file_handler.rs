@@ -18,7 +18,14 @@
 use anyhow::Result;
 #[cfg(feature = "mesalock_sgx")]
 use sgx_types::error::SgxStatus;
-use teaclave_types::FileAgentRequest;
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::fs;
+use teaclave_types::{FileAgentRequest, FileTransferRecord, StagedTask, TaskUsage};
+use uuid::Uuid;
 
 #[cfg(feature = "mesalock_sgx")]
 extern "C" {
@@ -43,6 +50,84 @@ pub(crate) fn handle_file_request(request: FileAgentRequest) -> Result<()> {
     teaclave_file_agent::handle_file_request(&bytes)
 }
 
+/// Records the files of a task moved through the file agent, which are
+/// reported with the task result for the audit log.
+#[derive(Clone)]
+pub(crate) struct FileTransferRecorder {
+    task_id: Uuid,
+    user_id: String,
+    trace_id: String,
+    records: Arc<Mutex<Vec<FileTransferRecord>>>,
+    // The end of the budget of the stage at hand, if it is limited
+    deadline: Arc<Mutex<Option<Instant>>>,
+}
+
+impl FileTransferRecorder {
+    pub(crate) fn new(task: &StagedTask) -> Self {
+        Self {
+            task_id: task.task_id,
+            user_id: task.user_id.clone(),
+            trace_id: task.trace_id.clone(),
+            records: Arc::new(Mutex::new(Vec::new())),
+            deadline: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Cut off the file agent requests at `deadline` until it is reset.
+    pub(crate) fn set_deadline(&self, deadline: Option<Instant>) {
+        if let Ok(mut current) = self.deadline.lock() {
+            *current = deadline;
+        }
+    }
+
+    pub(crate) fn handle_file_request(&self, request: FileAgentRequest) -> Result<()> {
+        let deadline = self.deadline.lock().ok().and_then(|deadline| *deadline);
+        let request = match deadline {
+            Some(deadline) => request.timeout(deadline.saturating_duration_since(Instant::now())),
+            None => request,
+        };
+        let cmd = request.cmd;
+        let info = request.info.clone();
+        let started = Instant::now();
+        let result = handle_file_request(request);
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        let mut records = self
+            .records
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock poisoned"))?;
+        for file in info {
+            let bytes = fs::metadata(&file.local).map(|m| m.len()).unwrap_or(0);
+            let record = FileTransferRecord::new(
+                self.task_id,
+                &self.user_id,
+                &self.trace_id,
+                cmd,
+                &file.remote,
+            );
+            records.push(record.outcome(bytes, duration_ms, result.is_ok()));
+        }
+        result
+    }
+
+    /// Usage of a task run taking `duration`, counting the files moved so
+    /// far.
+    pub(crate) fn usage(&self, duration: Duration) -> TaskUsage {
+        let duration_ms = duration.as_millis() as u64;
+        self.records
+            .lock()
+            .map(|records| TaskUsage::from_transfers(duration_ms, &records))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn take(&self) -> Vec<FileTransferRecord> {
+        self.records
+            .lock()
+            .map(|mut records| std::mem::take(&mut *records))
+            .unwrap_or_default()
+    }
+}
+
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
@@ -20,10 +20,11 @@
 extern crate sgx_types;
 use anyhow::{anyhow, ensure, Result};
 use log::info;
-use teaclave_attestation::{verifier, AttestationConfig, RemoteAttestation};
-use teaclave_config::build::{AS_ROOT_CA_CERT, AUDITOR_PUBLIC_KEYS};
+use std::sync::Arc;
+use teaclave_attestation::verifier::VerificationPolicy;
+use teaclave_attestation::{AttestationConfig, RemoteAttestation};
+use teaclave_config::build::AUDITOR_PUBLIC_KEYS;
 use teaclave_config::RuntimeConfig;
-use teaclave_service_enclave_utils::create_trusted_scheduler_endpoint;
 use teaclave_types::EnclaveInfo;
 
 #[cfg(feature = "mesalock_sgx")]
@@ -32,6 +33,7 @@ use std::untrusted::path::PathEx;
 #[cfg(feature = "mesalock_sgx")]
 mod ecall;
 mod file_handler;
+mod output_policy;
 mod service;
 mod task_file_manager;
 
@@ -39,8 +41,12 @@ pub async fn start_service(config: &RuntimeConfig) -> Result<()> {
     info!("Starting Execution...");
 
     let attestation_config = AttestationConfig::from_teaclave_config(config)?;
-    let attested_tls_config = RemoteAttestation::new(attestation_config)
-        .generate_and_endorse()?
+    let verification_policy = VerificationPolicy::from_teaclave_config(config)?;
+    let attestation = RemoteAttestation::new(attestation_config)
+        .endorsement_cache(config.attestation.endorsement_cache_path("execution"))
+        .reuse_cached_endorsement(config.attestation.reuse_cached_endorsement)
+        .generate_and_endorse()?;
+    let attested_tls_config = attestation
         .attested_tls_config()
         .ok_or_else(|| anyhow!("cannot get attested TLS config"))?;
     info!(" Starting Execution: Self attestation finished ...");
@@ -50,14 +56,19 @@ pub async fn start_service(config: &RuntimeConfig) -> Result<()> {
         AUDITOR_PUBLIC_KEYS,
         &config.audit.auditor_signatures_bytes,
     )?;
-    let scheduler_service_address = &config.internal_endpoints.scheduler.advertised_address;
-    let scheduler_service_endpoint = create_trusted_scheduler_endpoint(
-        scheduler_service_address,
-        &enclave_info,
-        AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
-        attested_tls_config,
-    )?;
+    // Results are signed with the attested key and claim the audited
+    // measurement, which verifiers check against the attestation report.
+    let measurement = enclave_info
+        .get_enclave_attr("teaclave_execution_service")
+        .ok_or_else(|| anyhow!("cannot get measurement of execution service"))?
+        .measurement;
+    let receipt_signer = service::ReceiptSigner::new(attested_tls_config, measurement);
+    let scheduler_connector = service::SchedulerConnector::new(
+        Arc::new(attestation),
+        &config.internal_endpoints.scheduler.advertised_address,
+        enclave_info,
+        verification_policy,
+    );
 
     let fusion_base = config.mount.fusion_base_dir.clone();
 
@@ -73,14 +84,40 @@ pub async fn start_service(config: &RuntimeConfig) -> Result<()> {
         "Fusion base directory is not mounted: {}",
         fusion_base.display()
     );
+    #[cfg(test_mode)]
+    create_task_log_dir(&config.task_log)?;
 
     info!(" Starting Execution: start ...");
-    let mut service =
-        service::TeaclaveExecutionService::new(scheduler_service_endpoint, fusion_base).await?;
+    let mut service = service::TeaclaveExecutionService::new(
+        scheduler_connector,
+        fusion_base,
+        receipt_signer,
+        config.task_log.clone(),
+        config.forensics.clone(),
+    )
+    .await?;
 
     service.start().await
 }
 
+/// Task logs uploaded to a local directory need the directory to exist, which
+/// is only created in test_mode like the fusion base directory.
+#[cfg(test_mode)]
+fn create_task_log_dir(config: &teaclave_config::TaskLogConfig) -> Result<()> {
+    let url = url::Url::parse(&config.upload_base_url)?;
+    if url.scheme() != "file" {
+        return Ok(());
+    }
+    let dir = url
+        .to_file_path()
+        .map_err(|_| anyhow!("Invalid task log URL: {}", url))?;
+    #[cfg(feature = "mesalock_sgx")]
+    std::untrusted::fs::create_dir_all(dir)?;
+    #[cfg(not(feature = "mesalock_sgx"))]
+    std::fs::create_dir_all(dir)?;
+    Ok(())
+}
+
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
@@ -89,7 +126,12 @@ pub mod tests {
     pub fn run_tests() -> bool {
         run_tests!(
             file_handler::tests::test_handle_file_request,
+            output_policy::tests::test_output_policies,
             service::tests::test_invoke_echo,
+            service::tests::test_stage_timeouts,
+            service::tests::test_resource_limits,
+            service::tests::test_function_panic,
+            service::tests::test_truncate_log,
             service::tests::test_invoke_gbdt_train,
             task_file_manager::tests::test_input,
         )
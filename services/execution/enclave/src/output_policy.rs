@@ -0,0 +1,208 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use anyhow::{anyhow, ensure, Context, Result};
+use teaclave_crypto::{fill_random, reserve_zeroizing, Zeroizing};
+use teaclave_types::OutputPolicy;
+
+/// A hook post-processing the plaintext of an output before it is encrypted
+/// and uploaded. A failing hook fails the task, so that outputs violating
+/// their policies are never released.
+pub(crate) trait OutputHook {
+    fn apply(&self, content: Zeroizing<Vec<u8>>) -> Result<Zeroizing<Vec<u8>>>;
+}
+
+struct MinRowCount {
+    min_rows: u64,
+    has_headers: bool,
+}
+
+struct LaplaceNoise {
+    columns: Vec<usize>,
+    scale: f64,
+    has_headers: bool,
+}
+
+fn hook_for(policy: &OutputPolicy) -> Box<dyn OutputHook> {
+    match policy {
+        OutputPolicy::MinRowCount {
+            min_rows,
+            has_headers,
+        } => Box::new(MinRowCount {
+            min_rows: *min_rows,
+            has_headers: *has_headers,
+        }),
+        OutputPolicy::LaplaceNoise {
+            columns,
+            epsilon,
+            sensitivity,
+            has_headers,
+        } => Box::new(LaplaceNoise {
+            columns: columns.clone(),
+            scale: sensitivity / epsilon,
+            has_headers: *has_headers,
+        }),
+    }
+}
+
+/// Apply the policies of an output in order to its content.
+pub(crate) fn apply_output_policies(
+    policies: &[OutputPolicy],
+    content: Zeroizing<Vec<u8>>,
+) -> Result<Zeroizing<Vec<u8>>> {
+    policies.iter().try_fold(content, |content, policy| {
+        policy.validate()?;
+        hook_for(policy)
+            .apply(content)
+            .with_context(|| format!("Output policy {} is not satisfied", policy.name()))
+    })
+}
+
+impl OutputHook for MinRowCount {
+    fn apply(&self, content: Zeroizing<Vec<u8>>) -> Result<Zeroizing<Vec<u8>>> {
+        let lines = content
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .count();
+        let records = lines.saturating_sub(self.has_headers as usize) as u64;
+        ensure!(
+            records >= self.min_rows,
+            "{} records are fewer than {}",
+            records,
+            self.min_rows
+        );
+        Ok(content)
+    }
+}
+
+impl OutputHook for LaplaceNoise {
+    fn apply(&self, content: Zeroizing<Vec<u8>>) -> Result<Zeroizing<Vec<u8>>> {
+        let text = std::str::from_utf8(&content).context("Output is not UTF-8")?;
+        let mut output = Zeroizing::new(Vec::with_capacity(content.len()));
+        let mut headers_skipped = !self.has_headers;
+        for (n, line) in text.split('\n').enumerate() {
+            if n > 0 {
+                output.push(b'\n');
+            }
+            let (record, cr) = match line.strip_suffix('\r') {
+                Some(record) => (record, "\r"),
+                None => (line, ""),
+            };
+            if record.trim().is_empty() || !headers_skipped {
+                headers_skipped |= !record.trim().is_empty();
+                reserve_zeroizing(&mut output, line.len());
+                output.extend_from_slice(line.as_bytes());
+                continue;
+            }
+            let noisy = self
+                .add_noise(record)
+                .with_context(|| format!("Cannot add noise to line {}", n + 1))?;
+            let mut noisy = Zeroizing::new(noisy);
+            noisy.push_str(cr);
+            reserve_zeroizing(&mut output, noisy.len());
+            output.extend_from_slice(noisy.as_bytes());
+        }
+        Ok(output)
+    }
+}
+
+impl LaplaceNoise {
+    fn add_noise(&self, record: &str) -> Result<String> {
+        ensure!(!record.contains('"'), "quoted fields are not supported");
+        let mut fields: Vec<Zeroizing<String>> = record
+            .split(',')
+            .map(|field| Zeroizing::new(field.to_string()))
+            .collect();
+        for &column in &self.columns {
+            let field = fields
+                .get_mut(column)
+                .ok_or_else(|| anyhow!("no column {}", column))?;
+            let value = field.trim();
+            let noisy = if let Ok(n) = value.parse::<i64>() {
+                // Rounding is post-processing, which keeps the privacy of
+                // the noisy value, and keeps counts integral.
+                format!("{}", (n as f64 + laplace(self.scale)).round() as i64)
+            } else {
+                let x = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|x| x.is_finite())
+                    .ok_or_else(|| anyhow!("column {} is not a number", column))?;
+                format!("{}", x + laplace(self.scale))
+            };
+            *field = Zeroizing::new(noisy);
+        }
+        let fields: Vec<&str> = fields.iter().map(|field| field.as_str()).collect();
+        Ok(fields.join(","))
+    }
+}
+
+/// A sample of Laplace(0, scale) by inverse transform sampling.
+fn laplace(scale: f64) -> f64 {
+    let mut bytes = [0u8; 8];
+    fill_random(&mut bytes);
+    // Uniform in (-0.5, 0.5) from 53 random bits, so the log below is finite
+    let bits = u64::from_le_bytes(bytes) >> 11;
+    let u = (bits as f64 + 0.5) / (1u64 << 53) as f64 - 0.5;
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    fn apply(policies: &[OutputPolicy], content: &str) -> Result<String> {
+        let content = Zeroizing::new(content.as_bytes().to_vec());
+        let output = apply_output_policies(policies, content)?;
+        Ok(String::from_utf8(output.to_vec()).unwrap())
+    }
+
+    pub fn test_output_policies() {
+        let content = "zip,count,mean\r\n10001,12,3.5\r\n10002,7,1.25\r\n\r\n";
+
+        let policy = OutputPolicy::min_row_count(2, true);
+        assert_eq!(apply(&[policy], content).unwrap(), content);
+        let policy = OutputPolicy::min_row_count(3, true);
+        assert!(apply(&[policy], content).is_err());
+        let policy = OutputPolicy::min_row_count(3, false);
+        assert!(apply(&[policy], content).is_ok());
+        let policy = OutputPolicy::min_row_count(0, false);
+        assert!(apply(&[policy], content).is_err());
+
+        // Noise of a tiny scale is rounded away from the counts.
+        let policy = OutputPolicy::laplace_noise([1, 2], 1.0, 1e-12, true);
+        let output = apply(&[policy], content).unwrap();
+        let lines: Vec<&str> = output.split("\r\n").collect();
+        assert_eq!(lines[0], "zip,count,mean");
+        assert!(lines[1].starts_with("10001,12,"));
+        assert!(lines[2].starts_with("10002,7,"));
+        let mean: f64 = lines[1].rsplit(',').next().unwrap().parse().unwrap();
+        assert!((mean - 3.5).abs() < 1e-6);
+        assert_eq!(&lines[3..], ["", ""]);
+
+        let policy = OutputPolicy::laplace_noise([1], 1.0, 1e-12, false);
+        assert!(apply(&[policy], content).is_err());
+        let policy = OutputPolicy::laplace_noise([3], 1.0, 1e-12, true);
+        assert!(apply(&[policy], content).is_err());
+        let policy = OutputPolicy::laplace_noise([1], 1.0, 1e-12, true);
+        assert!(apply(&[policy], "count\n\"1\"\n").is_err());
+
+        let samples: Vec<f64> = (0..1000).map(|_| laplace(2.0)).collect();
+        assert!(samples.iter().all(|x| x.is_finite()));
+        assert!(samples.iter().any(|x| *x > 0.0) && samples.iter().any(|x| *x < 0.0));
+    }
+}
@@ -15,78 +15,308 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::file_handler::FileTransferRecorder;
 use crate::task_file_manager::TaskFileManager;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fs;
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::fs;
+use teaclave_attestation::{verifier, AttestedTlsConfig, RemoteAttestation};
+use teaclave_config::build::AS_ROOT_CA_CERT;
+use teaclave_config::{ForensicsConfig, TaskLogConfig};
+use teaclave_crypto::AesGcm128Key;
 use teaclave_proto::teaclave_common::{ExecutorCommand, ExecutorStatus};
 use teaclave_proto::teaclave_scheduler_service::*;
-use teaclave_rpc::transport::{channel::Endpoint, Channel};
+use teaclave_rpc::transport::Channel;
+use teaclave_service_enclave_utils::create_trusted_scheduler_endpoint;
 use teaclave_types::*;
 use teaclave_worker::Worker;
+use url::Url;
 use uuid::Uuid;
 
 static WORKER_BASE_DIR: &str = "/tmp/teaclave_agent/";
 
+/// How long the finalization of the enclave waits for the task at hand to be
+/// handed back, which covers an iteration of the executor loop
+const SHUTDOWN_HANDOFF_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The enclave is finalized in another ECall, e.g., when the process receives
+// SIGTERM on a deploy or scale-in, so the executor is told through these
+// flags.
+static EXECUTOR_RUNNING: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_HANDED_OFF: AtomicBool = AtomicBool::new(false);
+// Set once a function going beyond its resource limits is left running, which
+// the executor stops by restarting after reporting the task.
+static FUNCTION_ABANDONED: AtomicBool = AtomicBool::new(false);
+
+/// How often the resources of a running function are checked
+const RESOURCE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Signs execution receipts with the private key of the current attested TLS
+/// certificate, which is renewed by the freshness keeper.
+#[derive(Clone)]
+pub(crate) struct ReceiptSigner {
+    attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
+    measurement: EnclaveMeasurement,
+}
+
+impl ReceiptSigner {
+    pub(crate) fn new(
+        attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
+        measurement: EnclaveMeasurement,
+    ) -> Self {
+        Self {
+            attested_tls_config,
+            measurement,
+        }
+    }
+
+    fn sign(&self, task: &StagedTask, outputs: TaskOutputs) -> Result<TaskOutputs> {
+        let tls_config = self
+            .attested_tls_config
+            .read()
+            .map_err(|_| anyhow!("lock poisoned"))?;
+        let receipt = ExecutionReceipt::new(
+            &task.task_id,
+            &task.function_payload,
+            &task.input_data,
+            &outputs.tags_map,
+            &outputs.return_value,
+        )
+        .sign(&self.measurement, &tls_config.cert, &tls_config.private_key)?;
+        Ok(outputs.receipt(receipt))
+    }
+}
+
+/// Times the phases of a task whose function is registered with profiling
+/// enabled, and does nothing otherwise.
+#[derive(Default)]
+pub(crate) struct TaskProfiler {
+    profile: Option<TaskProfile>,
+}
+
+impl TaskProfiler {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            profile: enabled.then(TaskProfile::default),
+        }
+    }
+
+    fn time<T>(
+        &mut self,
+        phase: impl FnOnce(&mut TaskProfile) -> &mut u64,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let profile = match self.profile.as_mut() {
+            Some(profile) => profile,
+            None => return f(),
+        };
+        let started = Instant::now();
+        let ret = f();
+        *phase(profile) += started.elapsed().as_millis() as u64;
+        ret
+    }
+
+    fn finish(self) -> Option<TaskProfile> {
+        self.profile
+    }
+}
+
+/// Connects to the scheduler with the current attested TLS certificate, and
+/// attests the executor again when the scheduler asks for it.
+#[derive(Clone)]
+pub(crate) struct SchedulerConnector {
+    attestation: Arc<RemoteAttestation>,
+    address: String,
+    enclave_info: Arc<EnclaveInfo>,
+    verification_policy: verifier::VerificationPolicy,
+}
+
+impl SchedulerConnector {
+    pub(crate) fn new(
+        attestation: Arc<RemoteAttestation>,
+        address: &str,
+        enclave_info: EnclaveInfo,
+        verification_policy: verifier::VerificationPolicy,
+    ) -> Self {
+        Self {
+            attestation,
+            address: address.to_owned(),
+            enclave_info: Arc::new(enclave_info),
+            verification_policy,
+        }
+    }
+
+    async fn connect(&self) -> Result<TeaclaveSchedulerClient<Channel>> {
+        let attested_tls_config = self
+            .attestation
+            .attested_tls_config()
+            .ok_or_else(|| anyhow!("cannot get attested TLS config"))?;
+        let channel = create_trusted_scheduler_endpoint(
+            &self.address,
+            &self.enclave_info,
+            AS_ROOT_CA_CERT,
+            verifier::universal_quote_verifier,
+            self.verification_policy.clone(),
+            attested_tls_config,
+        )?
+        .connect()
+        .await?;
+        Ok(TeaclaveSchedulerClient::new_with_builtin_config(channel))
+    }
+
+    /// Gets a new quote and reconnects, so that the scheduler sees the new
+    /// certificate on the connection.
+    async fn reattest(&self) -> Result<TeaclaveSchedulerClient<Channel>> {
+        self.attestation.refresh()?;
+        self.connect().await
+    }
+}
+
+/// The result of a task, where its execution log is uploaded, the files
+/// moved for it and the resources it used
+pub(crate) struct TaskRun {
+    result: Result<TaskOutputs>,
+    log_file: Option<TaskLogFile>,
+    file_transfers: Vec<FileTransferRecord>,
+    usage: TaskUsage,
+    /// Where the forensic bundle of a failed run is uploaded
+    forensic_bundle: Option<ForensicBundleFile>,
+}
+
+/// A function panicking, which is caught so that the task fails instead of
+/// the executor, and the panic is kept in the forensic bundle of the run.
+#[derive(thiserror::Error, Debug)]
+#[error("The function panicked: {0}")]
+struct FunctionPanic(String);
+
+impl FunctionPanic {
+    fn new(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload
+                .downcast_ref::<&str>()
+                .map_or_else(|| "unknown panic".to_string(), |s| s.to_string()),
+        };
+        Self(message)
+    }
+}
+
+/// Reports the phase of the task at hand to the main loop of the executor,
+/// which forwards the latest one to the scheduler. Functions cannot tell how
+/// far they are, so the percentages only mark the phases.
+struct ProgressReporter {
+    task_id: Uuid,
+    sender: mpsc::Sender<ReportTaskProgressRequest>,
+}
+
+impl ProgressReporter {
+    fn report(&self, percentage: u32, state: &str) {
+        let request = ReportTaskProgressRequest::new(self.task_id, percentage, state);
+        // The receiver is gone only if the executor is shutting down.
+        let _ = self.sender.send(request);
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct TeaclaveExecutionService {
     #[allow(dead_code)]
     worker: Arc<Worker>,
+    scheduler_connector: SchedulerConnector,
     scheduler_client: TeaclaveSchedulerClient<Channel>,
     fusion_base: PathBuf,
+    receipt_signer: ReceiptSigner,
+    task_log: TaskLogConfig,
+    forensics: Option<ForensicsConfig>,
     id: Uuid,
     status: ExecutorStatus,
+    // Tasks of the batch at hand not started yet
+    batch: VecDeque<StagedTask>,
 }
 
 impl TeaclaveExecutionService {
     pub(crate) async fn new(
-        scheduler_service_endpoint: Endpoint,
+        scheduler_connector: SchedulerConnector,
         fusion_base: impl AsRef<Path>,
+        receipt_signer: ReceiptSigner,
+        task_log: TaskLogConfig,
+        forensics: Option<ForensicsConfig>,
     ) -> Result<Self> {
-        let channel = scheduler_service_endpoint.connect().await?;
-        let scheduler_client = TeaclaveSchedulerClient::new_with_builtin_config(channel);
+        let scheduler_client = scheduler_connector.connect().await?;
 
         Ok(TeaclaveExecutionService {
             worker: Arc::new(Worker::default()),
+            scheduler_connector,
             scheduler_client,
             fusion_base: fusion_base.as_ref().to_owned(),
+            receipt_signer,
+            task_log,
+            forensics,
             id: Uuid::new_v4(),
             status: ExecutorStatus::Idle,
+            batch: VecDeque::new(),
         })
     }
 
     pub(crate) async fn start(&mut self) -> Result<()> {
         let (tx, rx) = mpsc::channel();
+        let (progress_tx, progress_rx) = mpsc::channel();
         let mut current_task: Arc<Option<StagedTask>> = Arc::new(None);
         let mut task_handle: Option<thread::JoinHandle<()>> = None;
+        let mut throttled_until: Option<Instant> = None;
+        EXECUTOR_RUNNING.store(true, Ordering::Release);
 
         loop {
             std::thread::sleep(std::time::Duration::from_secs(3));
 
+            if SHUTDOWN_REQUESTED.load(Ordering::Acquire) {
+                if let Some(task) = current_task.as_ref() {
+                    self.handoff(task, rx.try_recv().ok()).await;
+                }
+                let batch: Vec<StagedTask> = self.batch.drain(..).collect();
+                for task in batch {
+                    self.handoff(&task, None).await;
+                }
+                SHUTDOWN_HANDED_OFF.store(true, Ordering::Release);
+                log::info!("Executor {} is shut down", self.id);
+                return Ok(());
+            }
+
+            // An idle executor throttled by the scheduler has nothing to do
+            // until the throttle ends.
+            if throttled_until.map_or(false, |until| Instant::now() < until) {
+                continue;
+            }
+
             match self.heartbeat().await {
-                Ok(ExecutorCommand::Stop) => {
+                Ok((ExecutorCommand::Throttle, throttle))
+                    if self.status == ExecutorStatus::Idle =>
+                {
+                    log::info!("Executor {} throttled for {:?}", self.id, throttle);
+                    throttled_until = Some(Instant::now() + throttle);
+                }
+                Ok((ExecutorCommand::Stop, _)) => {
                     log::info!("Executor {} is stopped", self.id);
                     return Err(anyhow::anyhow!("EnclaveForceTermination"));
                 }
-                Ok(ExecutorCommand::NewTask) if self.status == ExecutorStatus::Idle => {
+                Ok((ExecutorCommand::NewTask, _)) if self.status == ExecutorStatus::Idle => {
                     match self.pull_task().await {
                         Ok(task) => {
                             self.status = ExecutorStatus::Executing;
-                            self.update_task_status(&task.task_id, TaskStatus::Running)
-                                .await?;
-                            let tx_task = tx.clone();
-                            let fusion_base = self.fusion_base.clone();
-                            current_task = Arc::new(Some(task));
-                            let task_copy = current_task.clone();
-                            let handle = thread::spawn(move || {
-                                let result =
-                                    invoke_task(task_copy.as_ref().as_ref().unwrap(), &fusion_base);
-                                tx_task.send(result).unwrap();
-                            });
+                            let (task, handle) = self.launch_task(task, &tx, &progress_tx).await?;
+                            current_task = task;
                             task_handle = Some(handle);
                         }
                         Err(e) => {
@@ -94,6 +324,20 @@ impl TeaclaveExecutionService {
                         }
                     };
                 }
+                Ok((ExecutorCommand::Reattest, _)) if self.status == ExecutorStatus::Idle => {
+                    // Exiting on failure leaves the scheduler to drain this
+                    // executor once the re-attestation times out.
+                    match self.scheduler_connector.reattest().await {
+                        Ok(client) => {
+                            self.scheduler_client = client;
+                            log::info!("Executor {} re-attested", self.id);
+                        }
+                        Err(e) => {
+                            log::error!("Executor {} failed to re-attest: {}", self.id, e);
+                            return Err(e);
+                        }
+                    }
+                }
                 Err(e) => {
                     log::error!("Executor {} failed to heartbeat: {}", self.id, e);
                     return Err(e);
@@ -101,8 +345,21 @@ impl TeaclaveExecutionService {
                 _ => {}
             }
 
+            // Only the latest progress matters.
+            if let Some(request) = progress_rx.try_iter().last() {
+                if let Err(e) = self.scheduler_client.report_task_progress(request).await {
+                    log::warn!("Executor {} failed to report progress: {}", self.id, e);
+                }
+            }
+
             match rx.try_recv() {
-                Ok(result) => {
+                Ok(TaskRun {
+                    result,
+                    log_file,
+                    file_transfers,
+                    usage,
+                    forensic_bundle,
+                }) => {
                     let task_unwrapped = current_task.as_ref().as_ref().unwrap();
                     match result {
                         Ok(_) => log::debug!(
@@ -117,9 +374,18 @@ impl TeaclaveExecutionService {
                         ),
                     }
                     log::debug!("InvokeTask result: {:?}", result);
+                    let result = result
+                        .and_then(|outputs| self.receipt_signer.sign(task_unwrapped, outputs));
                     let task_copy = current_task.clone();
                     match self
-                        .update_task_result(&task_copy.as_ref().as_ref().unwrap().task_id, result)
+                        .update_task_result(
+                            &task_copy.as_ref().as_ref().unwrap().task_id,
+                            result,
+                            log_file,
+                            file_transfers,
+                            usage,
+                            forensic_bundle,
+                        )
                         .await
                     {
                         Ok(_) => (),
@@ -131,7 +397,26 @@ impl TeaclaveExecutionService {
                     current_task = Arc::new(None);
                     task_handle.unwrap().join().unwrap();
                     task_handle = None;
-                    self.status = ExecutorStatus::Idle;
+                    if FUNCTION_ABANDONED.load(Ordering::Acquire) {
+                        let batch: Vec<StagedTask> = self.batch.drain(..).collect();
+                        for task in batch {
+                            self.handoff(&task, None).await;
+                        }
+                        log::warn!(
+                            "Executor {} restarts to stop a function beyond its limits",
+                            self.id
+                        );
+                        return Err(anyhow!("EnclaveForceTermination"));
+                    }
+                    // The tasks of a batch run one after another.
+                    match self.batch.pop_front() {
+                        Some(task) => {
+                            let (task, handle) = self.launch_task(task, &tx, &progress_tx).await?;
+                            current_task = task;
+                            task_handle = Some(handle);
+                        }
+                        None => self.status = ExecutorStatus::Idle,
+                    }
                 }
                 Err(mpsc::TryRecvError::Disconnected) => {
                     log::error!(
@@ -145,31 +430,131 @@ impl TeaclaveExecutionService {
         }
     }
 
-    async fn pull_task(&mut self) -> Result<StagedTask> {
-        let request = PullTaskRequest {
-            executor_id: self.id.to_string(),
+    /// Report the task at hand before shutting down: the result if it is
+    /// ready, otherwise hand the task back to the scheduler, which queues it
+    /// again right away instead of failing it once the heartbeat times out.
+    /// None of the executors can checkpoint a function, so the task runs
+    /// again from the beginning.
+    async fn handoff(&mut self, task: &StagedTask, run: Option<TaskRun>) {
+        let reported = match run {
+            Some(TaskRun {
+                result,
+                log_file,
+                file_transfers,
+                usage,
+                forensic_bundle,
+            }) => {
+                let result = result.and_then(|outputs| self.receipt_signer.sign(task, outputs));
+                self.update_task_result(
+                    &task.task_id,
+                    result,
+                    log_file,
+                    file_transfers,
+                    usage,
+                    forensic_bundle,
+                )
+                .await
+            }
+            None => {
+                let request =
+                    HandoffTaskRequest::new(self.id, task.task_id, HandoffReason::ExecutorShutdown);
+                self.scheduler_client
+                    .handoff_task(request)
+                    .await
+                    .map(|_| ())
+                    .map_err(Into::into)
+            }
         };
+        match reported {
+            Ok(_) => log::info!(
+                "Executor {} handed off task {} on shutdown",
+                self.id,
+                task.task_id
+            ),
+            Err(e) => log::error!(
+                "Executor {} failed to hand off task {}: {:?}",
+                self.id,
+                task.task_id,
+                e
+            ),
+        }
+    }
+
+    /// Run the task in a thread of its own, which sends the run back to the
+    /// main loop once the task ends.
+    async fn launch_task(
+        &mut self,
+        task: StagedTask,
+        tx: &mpsc::Sender<TaskRun>,
+        progress_tx: &mpsc::Sender<ReportTaskProgressRequest>,
+    ) -> Result<(Arc<Option<StagedTask>>, thread::JoinHandle<()>)> {
+        self.update_task_status(&task.task_id, TaskStatus::Running)
+            .await?;
+        let tx_task = tx.clone();
+        let fusion_base = self.fusion_base.clone();
+        let task_log = self.task_log.clone();
+        let forensics = self.forensics.clone();
+        let progress = ProgressReporter {
+            task_id: task.task_id,
+            sender: progress_tx.clone(),
+        };
+        let current_task = Arc::new(Some(task));
+        let task_copy = current_task.clone();
+        let handle = thread::spawn(move || {
+            let run = invoke_task(
+                task_copy.as_ref().as_ref().unwrap(),
+                &fusion_base,
+                &task_log,
+                forensics.as_ref(),
+                &progress,
+            );
+            tx_task.send(run).unwrap();
+        });
+        Ok((current_task, handle))
+    }
+
+    /// Pull a task, keeping the other tasks of its batch, if any, to run next.
+    async fn pull_task(&mut self) -> Result<StagedTask> {
+        let request =
+            PullTaskRequest::new(self.id, self.worker.features().clone()).accepts_batch(true);
         let response = self.scheduler_client.pull_task(request).await?.into_inner();
 
         log::debug!("pull_stask response: {:?}", response);
         let staged_task = StagedTask::from_slice(&response.staged_task)?;
+        self.batch = response
+            .batched_tasks
+            .iter()
+            .map(|task| StagedTask::from_slice(task))
+            .collect::<Result<_>>()?;
         Ok(staged_task)
     }
 
-    async fn heartbeat(&mut self) -> Result<ExecutorCommand> {
+    /// The command of the scheduler, along with how long to hold off if
+    /// throttled.
+    async fn heartbeat(&mut self) -> Result<(ExecutorCommand, Duration)> {
         let request = HeartbeatRequest::new(self.id, self.status);
         let response = self.scheduler_client.heartbeat(request).await?.into_inner();
 
         log::debug!("heartbeat_with_result response: {:?}", response);
-        response.command.try_into()
+        let command = response.command.try_into()?;
+        Ok((command, Duration::from_secs(response.throttle_secs)))
     }
 
     async fn update_task_result(
         &mut self,
         task_id: &Uuid,
         task_result: Result<TaskOutputs>,
+        log_file: Option<TaskLogFile>,
+        file_transfers: Vec<FileTransferRecord>,
+        usage: TaskUsage,
+        forensic_bundle: Option<ForensicBundleFile>,
     ) -> Result<()> {
-        let request = UpdateTaskResultRequest::new(*task_id, task_result);
+        let task_result =
+            task_result.map_err(|e| TaskFailure::new(e).forensic_bundle(forensic_bundle));
+        let request = UpdateTaskResultRequest::with_failure(*task_id, task_result)
+            .log_file(log_file)
+            .file_transfers(file_transfers)
+            .usage(usage);
 
         let _response = self.scheduler_client.update_task_result(request).await?;
 
@@ -184,7 +569,35 @@ impl TeaclaveExecutionService {
     }
 }
 
-fn invoke_task(task: &StagedTask, fusion_base: &PathBuf) -> Result<TaskOutputs> {
+/// Let the executor hand off the task at hand and wait for it before the
+/// enclave is finalized.
+pub(crate) fn handoff_before_finalize() {
+    if !EXECUTOR_RUNNING.load(Ordering::Acquire) {
+        return;
+    }
+    SHUTDOWN_REQUESTED.store(true, Ordering::Release);
+
+    let started = Instant::now();
+    while !SHUTDOWN_HANDED_OFF.load(Ordering::Acquire) {
+        if started.elapsed() > SHUTDOWN_HANDOFF_TIMEOUT {
+            log::warn!("Timed out handing off the task before finalizing the enclave");
+            return;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Run the task with everything logged in the enclave captured as its
+/// execution log, which is uploaded encrypted for the task creator, and also
+/// returned in the outputs if the `save_log` argument is set. A forensic
+/// bundle is uploaded for a failed run if it is configured.
+fn invoke_task(
+    task: &StagedTask,
+    fusion_base: &Path,
+    log_config: &TaskLogConfig,
+    forensics: Option<&ForensicsConfig>,
+    progress: &ProgressReporter,
+) -> TaskRun {
     let save_log = task
         .function_arguments
         .get("save_log")
@@ -192,40 +605,332 @@ fn invoke_task(task: &StagedTask, fusion_base: &PathBuf) -> Result<TaskOutputs>
         .and_then(|v| v.as_str().and_then(|s| s.parse().ok()))
         .unwrap_or(false);
     let log_arc = Arc::new(Mutex::new(Vec::<String>::new()));
+    let recorder = FileTransferRecorder::new(task);
+
+    let log_ptr = Arc::into_raw(log_arc.clone());
+    log::info!(buffer = log_ptr.expose_addr(); "");
+    let started = Instant::now();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        execute_task(task, fusion_base, &recorder, progress)
+    }))
+    .unwrap_or_else(|payload| Err(FunctionPanic::new(payload).into()));
+    // The log upload is not part of the run.
+    let usage = recorder.usage(started.elapsed());
+    // The logger must be reset whether or not the task succeeds, otherwise
+    // the next task cannot be logged.
+    log::info!(buffer = 0; "");
 
-    if save_log {
-        let log_arc = Arc::into_raw(log_arc.clone());
-        log::info!(buffer = log_arc.expose_addr(); "");
+    let mut log = log_arc
+        .lock()
+        .map(|mut log| std::mem::take(&mut *log))
+        .unwrap_or_default();
+    if let Err(e) = &result {
+        log.push(format!("[ERROR] Task failed: {}", e));
     }
+    let truncated = truncate_log(&mut log, log_config.max_size_bytes);
+    let forensic_bundle = match (&result, forensics) {
+        (Err(e), Some(config)) => {
+            upload_forensic_bundle(task, fusion_base, config, e, &log, usage, &recorder)
+                .map_err(|e| {
+                    log::error!(
+                        "Failed to upload the forensic bundle of task {}: {:?}",
+                        task.task_id,
+                        e
+                    )
+                })
+                .ok()
+        }
+        _ => None,
+    };
+    let log_file = upload_task_log(
+        &task.task_id,
+        fusion_base,
+        log_config,
+        &log,
+        truncated,
+        &recorder,
+    )
+    .map_err(|e| log::error!("Failed to upload the log of task {}: {:?}", task.task_id, e))
+    .ok();
 
+    let result = result.map(|outputs| {
+        if save_log {
+            TaskOutputs { log, ..outputs }
+        } else {
+            outputs
+        }
+    });
+    TaskRun {
+        result,
+        log_file,
+        file_transfers: recorder.take(),
+        usage,
+        forensic_bundle,
+    }
+}
+
+fn execute_task(
+    task: &StagedTask,
+    fusion_base: &Path,
+    recorder: &FileTransferRecorder,
+    progress: &ProgressReporter,
+) -> Result<TaskOutputs> {
+    progress.report(0, "staging inputs");
     let file_mgr = TaskFileManager::new(
         WORKER_BASE_DIR,
         fusion_base,
         &task.task_id,
         &task.input_data,
         &task.output_data,
+        recorder.clone(),
     )?;
-    let invocation = prepare_task(task, &file_mgr)?;
+    let mut profiler = TaskProfiler::new(task.profiling);
+    let invocation = run_stage(task, TaskStage::Staging, recorder, || {
+        prepare_task(task, &file_mgr, &mut profiler)
+    })?;
 
     log::debug!("Invoke function: {:?}", invocation);
-    let worker = Worker::default();
-    let summary = worker.invoke_function(invocation)?;
+    progress.report(20, "executing");
+    let limit = task.timeouts.limit(TaskStage::Execution);
+    let summary = profiler.time(
+        |p| &mut p.execution_ms,
+        || invoke_function(invocation, limit, &task.resource_limits),
+    )?;
 
-    let outputs_tag = finalize_task(&file_mgr)?;
-    if save_log {
-        log::info!(buffer = 0; "");
+    progress.report(80, "uploading outputs");
+    let (outputs_tag, artifacts) = run_stage(task, TaskStage::Upload, recorder, || {
+        finalize_task(&file_mgr, &mut profiler)
+    })?;
+    let task_outputs = TaskOutputs::new(summary.as_bytes(), outputs_tag, Vec::new())
+        .profile(profiler.finish())
+        .artifacts(artifacts);
+
+    Ok(task_outputs)
+}
+
+/// Run `stage` of the task within its budget, if any. The file agent
+/// requests of the stage are cut off at the end of the budget, and the stage
+/// fails once the budget is over, whatever it returns.
+fn run_stage<T>(
+    task: &StagedTask,
+    stage: TaskStage,
+    recorder: &FileTransferRecorder,
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let limit = match task.timeouts.limit(stage) {
+        Some(limit) => limit,
+        None => return f(),
+    };
+    let deadline = Instant::now() + limit;
+    recorder.set_deadline(Some(deadline));
+    let result = f();
+    recorder.set_deadline(None);
+    if Instant::now() > deadline {
+        bail!("{} timed out after {}s", stage, limit.as_secs());
     }
+    result
+}
 
-    let log = Arc::try_unwrap(log_arc)
-        .map_err(|_| anyhow::anyhow!("log buffer is referenced more than once"))?
-        .into_inner()?;
-    let task_outputs = TaskOutputs::new(summary.as_bytes(), outputs_tag, log);
+/// The time and memory limits of a running function, checked from outside of
+/// it. The memory is taken as the growth of the peak heap of the enclave, as
+/// the executor runs one function at a time, so only the memory beyond the
+/// peak of the earlier functions counts.
+struct ResourceWatch {
+    timeout: Option<Duration>,
+    max_memory_bytes: u64,
+    peak_heap_before: u64,
+}
 
-    Ok(task_outputs)
+impl ResourceWatch {
+    fn new(limits: &ResourceLimits) -> Self {
+        Self {
+            timeout: (limits.timeout_secs != 0).then(|| Duration::from_secs(limits.timeout_secs)),
+            max_memory_bytes: limits.max_memory_bytes,
+            peak_heap_before: peak_heap_used(),
+        }
+    }
+
+    fn is_needed(&self) -> bool {
+        self.timeout.is_some() || self.max_memory_bytes != 0
+    }
+
+    fn check_memory(&self) -> std::result::Result<(), ResourceLimitExceeded> {
+        let grown = peak_heap_used().saturating_sub(self.peak_heap_before);
+        if self.max_memory_bytes != 0 && grown > self.max_memory_bytes {
+            return Err(ResourceLimitExceeded::Memory(self.max_memory_bytes));
+        }
+        Ok(())
+    }
+
+    fn check(&self, elapsed: Duration) -> std::result::Result<(), ResourceLimitExceeded> {
+        match self.timeout {
+            Some(timeout) if elapsed >= timeout => {
+                Err(ResourceLimitExceeded::Timeout(timeout.as_secs()))
+            }
+            _ => self.check_memory(),
+        }
+    }
+}
+
+// Read the counter of the enclave heap, which is only ever written by the
+// trusted runtime.
+fn peak_heap_used() -> u64 {
+    let peak_heap_used = unsafe { teaclave_service_enclave_utils::g_peak_heap_used };
+    peak_heap_used.max(0) as u64
 }
 
-fn prepare_task(task: &StagedTask, file_mgr: &TaskFileManager) -> Result<StagedFunction> {
-    let input_files = file_mgr.prepare_staged_inputs()?;
+/// Invoke the function on another thread if its execution is limited. The
+/// executors cannot interrupt a function, so one running out of time is
+/// left to return on its own, and its result is dropped. One going beyond its
+/// resource limits is left the same way, and stopped by restarting the
+/// executor once the task is reported.
+fn invoke_function(
+    invocation: StagedFunction,
+    limit: Option<Duration>,
+    resource_limits: &ResourceLimits,
+) -> Result<String> {
+    let watch = ResourceWatch::new(resource_limits);
+    if limit.is_none() && !watch.is_needed() {
+        return Worker::default().invoke_function(invocation);
+    }
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            Worker::default().invoke_function(invocation)
+        }))
+        .unwrap_or_else(|payload| Err(FunctionPanic::new(payload).into()));
+        let _ = tx.send(result);
+    });
+    let started = Instant::now();
+    loop {
+        match rx.recv_timeout(RESOURCE_POLL_INTERVAL) {
+            Ok(result) => {
+                // The function may have taken the memory since the last check.
+                watch.check_memory()?;
+                return result;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => bail!("The function panicked"),
+        }
+        let elapsed = started.elapsed();
+        if let Err(e) = watch.check(elapsed) {
+            FUNCTION_ABANDONED.store(true, Ordering::Release);
+            return Err(e.into());
+        }
+        if let Some(limit) = limit.filter(|limit| elapsed >= *limit) {
+            bail!(
+                "{} timed out after {}s",
+                TaskStage::Execution,
+                limit.as_secs()
+            );
+        }
+    }
+}
+
+/// Drop the head of the log to keep it within `max_size` bytes, returning
+/// whether any line is dropped.
+fn truncate_log(log: &mut Vec<String>, max_size: usize) -> bool {
+    let mut size = 0;
+    let kept = log
+        .iter()
+        .rev()
+        .take_while(|line| {
+            size += line.len() + 1;
+            size <= max_size
+        })
+        .count();
+    let dropped = log.len() - kept;
+    log.drain(..dropped);
+    dropped > 0
+}
+
+/// Encrypt the log with a new key and upload it under the configured base
+/// URL. Every run of a task is uploaded to a new file, since a task handed
+/// off on shutdown runs again.
+fn upload_task_log(
+    task_id: &Uuid,
+    fusion_base: &Path,
+    config: &TaskLogConfig,
+    log: &[String],
+    truncated: bool,
+    recorder: &FileTransferRecorder,
+) -> Result<TaskLogFile> {
+    let file_name = format!("{}-{}.log", task_id, Uuid::new_v4());
+    let url = Url::parse(&config.upload_base_url)?.join(&file_name)?;
+
+    let crypto = FileCrypto::AesGcm128(AesGcm128Key::random());
+    let mut content = log.join("\n").into_bytes();
+    let cmac = crypto.encrypt_in_memory(&mut content, None)?;
+
+    let local_dir = Path::new(WORKER_BASE_DIR).join(task_id.to_string());
+    fs::create_dir_all(&local_dir)?;
+    let local_path = local_dir.join(&file_name);
+    fs::write(&local_path, &content)?;
+
+    let request = FileAgentRequest::new(
+        HandleFileCommand::Upload,
+        vec![HandleFileInfo::new(&local_path, &url)],
+        fusion_base,
+    );
+    let uploaded = recorder.handle_file_request(request);
+    fs::remove_file(&local_path)?;
+    uploaded?;
+
+    Ok(TaskLogFile::new(url, crypto, cmac, truncated))
+}
+
+/// Seal the forensic bundle of a failed run with a new key, like the
+/// execution log, and upload it under the configured base URL.
+fn upload_forensic_bundle(
+    task: &StagedTask,
+    fusion_base: &Path,
+    config: &ForensicsConfig,
+    error: &anyhow::Error,
+    log: &[String],
+    usage: TaskUsage,
+    recorder: &FileTransferRecorder,
+) -> Result<ForensicBundleFile> {
+    let panic = error.downcast_ref::<FunctionPanic>().map(|p| p.0.clone());
+    let bundle = ForensicBundle::new(task, error)?
+        .panic(panic)
+        .log_tail(log, config.log_tail_lines)
+        .usage(usage)
+        .peak_heap_bytes(peak_heap_used());
+
+    let file_name = format!("{}-{}.forensics", task.task_id, Uuid::new_v4());
+    let url = Url::parse(&config.upload_base_url)?.join(&file_name)?;
+
+    let crypto = FileCrypto::AesGcm128(AesGcm128Key::random());
+    let mut content = bundle.to_vec()?;
+    let cmac = crypto.encrypt_in_memory(&mut content, None)?;
+
+    let local_dir = Path::new(WORKER_BASE_DIR).join(task.task_id.to_string());
+    fs::create_dir_all(&local_dir)?;
+    let local_path = local_dir.join(&file_name);
+    fs::write(&local_path, &content)?;
+
+    let request = FileAgentRequest::new(
+        HandleFileCommand::Upload,
+        vec![HandleFileInfo::new(&local_path, &url)],
+        fusion_base,
+    );
+    let uploaded = recorder.handle_file_request(request);
+    fs::remove_file(&local_path)?;
+    uploaded?;
+
+    Ok(ForensicBundleFile::new(url, crypto, cmac))
+}
+
+fn prepare_task(
+    task: &StagedTask,
+    file_mgr: &TaskFileManager,
+    profiler: &mut TaskProfiler,
+) -> Result<StagedFunction> {
+    profiler.time(|p| &mut p.staging_ms, || file_mgr.download_inputs())?;
+    let input_files = profiler.time(
+        |p| &mut p.decryption_ms,
+        || file_mgr.prepare_staged_inputs(),
+    )?;
     let output_files = file_mgr.prepare_staged_outputs()?;
 
     let staged_function = StagedFunctionBuilder::new()
@@ -233,16 +938,27 @@ fn prepare_task(task: &StagedTask, file_mgr: &TaskFileManager) -> Result<StagedF
         .executor(task.executor)
         .name(&task.function_name)
         .arguments(task.function_arguments.clone())
-        .payload(task.function_payload.clone())
+        .payload(task.decrypt_function_payload()?)
         .input_files(input_files)
         .output_files(output_files)
         .runtime_name("default")
+        .max_output_bytes(task.resource_limits.max_output_bytes)
         .build();
     Ok(staged_function)
 }
 
-fn finalize_task(file_mgr: &TaskFileManager) -> Result<HashMap<String, FileAuthTag>> {
-    file_mgr.upload_outputs()
+type UploadedOutputs = (
+    HashMap<String, FileAuthTag>,
+    HashMap<String, OutputArtifact>,
+);
+
+fn finalize_task(
+    file_mgr: &TaskFileManager,
+    profiler: &mut TaskProfiler,
+) -> Result<UploadedOutputs> {
+    let auth_tags = profiler.time(|p| &mut p.conversion_ms, || file_mgr.convert_outputs())?;
+    let artifacts = profiler.time(|p| &mut p.upload_ms, || file_mgr.upload_outputs())?;
+    Ok((auth_tags, artifacts))
 }
 
 #[cfg(feature = "enclave_unit_test")]
@@ -271,18 +987,90 @@ pub mod tests {
             &staged_task.task_id,
             &staged_task.input_data,
             &staged_task.output_data,
+            FileTransferRecorder::new(&staged_task),
         )
         .unwrap();
-        let invocation = prepare_task(&staged_task, &file_mgr).unwrap();
+        let mut profiler = TaskProfiler::default();
+        let invocation = prepare_task(&staged_task, &file_mgr, &mut profiler).unwrap();
 
         let worker = Worker::default();
         let result = worker.invoke_function(invocation);
         if result.is_ok() {
-            finalize_task(&file_mgr).unwrap();
+            finalize_task(&file_mgr, &mut profiler).unwrap();
         }
+        assert!(profiler.finish().is_none());
         assert_eq!(result.unwrap(), "Hello, Teaclave!");
     }
 
+    pub fn test_stage_timeouts() {
+        let staged_task = StagedTaskBuilder::new()
+            .task_id(Uuid::new_v4())
+            .timeouts(TaskTimeouts::new(1, 0, 0))
+            .build();
+        let recorder = FileTransferRecorder::new(&staged_task);
+        let result = run_stage(&staged_task, TaskStage::Staging, &recorder, || {
+            thread::sleep(Duration::from_millis(1100));
+            Ok(())
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "staging timed out after 1s"
+        );
+        assert!(run_stage(&staged_task, TaskStage::Upload, &recorder, || Ok(())).is_ok());
+
+        let function_arguments =
+            FunctionArguments::from_json(json!({"message": "Hello, Teaclave!"})).unwrap();
+        let invocation = StagedFunctionBuilder::new()
+            .executor(Executor::Builtin)
+            .name("builtin-echo")
+            .arguments(function_arguments)
+            .runtime_name("default")
+            .build();
+        let limits = ResourceLimits::default();
+        let result = invoke_function(invocation, Some(Duration::from_secs(10)), &limits);
+        assert_eq!(result.unwrap(), "Hello, Teaclave!");
+    }
+
+    pub fn test_resource_limits() {
+        let invocation = || {
+            let function_arguments =
+                FunctionArguments::from_json(json!({"message": "Hello, Teaclave!"})).unwrap();
+            StagedFunctionBuilder::new()
+                .executor(Executor::Builtin)
+                .name("builtin-echo")
+                .arguments(function_arguments)
+                .runtime_name("default")
+                .build()
+        };
+        let limits = ResourceLimits::new(10, 1 << 30, 0);
+        let result = invoke_function(invocation(), None, &limits);
+        assert_eq!(result.unwrap(), "Hello, Teaclave!");
+        assert!(!FUNCTION_ABANDONED.load(Ordering::Acquire));
+
+        let watch = ResourceWatch::new(&ResourceLimits::new(1, 0, 0));
+        assert!(watch.check(Duration::from_millis(500)).is_ok());
+        let error = watch.check(Duration::from_secs(1)).unwrap_err();
+        assert_eq!(error, ResourceLimitExceeded::Timeout(1));
+        assert!(!ResourceWatch::new(&ResourceLimits::default()).is_needed());
+    }
+
+    pub fn test_function_panic() {
+        let payload = panic::catch_unwind(|| panic!("out of {}", "memory")).unwrap_err();
+        assert_eq!(FunctionPanic::new(payload).0, "out of memory");
+        let payload = panic::catch_unwind(|| panic!("aborted")).unwrap_err();
+        let error: anyhow::Error = FunctionPanic::new(payload).into();
+        assert_eq!(error.to_string(), "The function panicked: aborted");
+        assert!(error.downcast_ref::<FunctionPanic>().is_some());
+    }
+
+    pub fn test_truncate_log() {
+        let mut log = vec!["a".repeat(10), "b".repeat(10), "c".repeat(10)];
+        assert!(!truncate_log(&mut log, 33));
+        assert_eq!(log.len(), 3);
+        assert!(truncate_log(&mut log, 25));
+        assert_eq!(log, vec!["b".repeat(10), "c".repeat(10)]);
+    }
+
     pub fn test_invoke_gbdt_train() {
         let task_id = Uuid::new_v4();
         let function_arguments = FunctionArguments::from_json(json!({
@@ -318,6 +1106,7 @@ pub mod tests {
             .function_arguments(function_arguments)
             .input_data(input_data)
             .output_data(output_data)
+            .profiling(true)
             .build();
 
         let file_mgr = TaskFileManager::new(
@@ -326,16 +1115,22 @@ pub mod tests {
             &staged_task.task_id,
             &staged_task.input_data,
             &staged_task.output_data,
+            FileTransferRecorder::new(&staged_task),
         )
         .unwrap();
-        let invocation = prepare_task(&staged_task, &file_mgr).unwrap();
+        let mut profiler = TaskProfiler::new(staged_task.profiling);
+        let invocation = prepare_task(&staged_task, &file_mgr, &mut profiler).unwrap();
 
         let worker = Worker::default();
-        let result = worker.invoke_function(invocation);
+        let result = profiler.time(
+            |p| &mut p.execution_ms,
+            || worker.invoke_function(invocation),
+        );
         if result.is_ok() {
-            finalize_task(&file_mgr).unwrap();
+            finalize_task(&file_mgr, &mut profiler).unwrap();
         }
         log::debug!("summary: {:?}", result);
         assert!(result.is_ok());
+        assert!(profiler.finish().is_some());
     }
 }
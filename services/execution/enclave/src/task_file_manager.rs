@@ -15,16 +15,18 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::file_handler::handle_file_request;
+use crate::file_handler::FileTransferRecorder;
+use crate::output_policy::apply_output_policies;
 use anyhow::Result;
 use std::collections::HashMap;
 #[cfg(not(feature = "mesalock_sgx"))]
 use std::fs;
+use std::io::{Read, Write};
 use std::path::Path;
 use std::path::PathBuf;
 #[cfg(feature = "mesalock_sgx")]
 use std::untrusted::{fs, path::PathEx};
-use teaclave_crypto::TeaclaveFile128Key;
+use teaclave_crypto::{TeaclaveFile128Key, Zeroizing};
 use teaclave_types::*;
 use url::Url;
 use uuid::Uuid;
@@ -33,6 +35,7 @@ pub(crate) struct TaskFileManager {
     inter_inputs: InterInputs,
     inter_outputs: InterOutputs,
     fusion_base: PathBuf,
+    recorder: FileTransferRecorder,
 }
 
 struct InterInputs {
@@ -64,6 +67,7 @@ impl TaskFileManager {
         task_id: &Uuid,
         inputs: &FunctionInputFiles,
         outputs: &FunctionOutputFiles,
+        recorder: FileTransferRecorder,
     ) -> Result<Self> {
         let cwd = Path::new(inter_base.as_ref()).join(task_id.to_string());
         let inputs_base = cwd.join("inputs");
@@ -76,13 +80,18 @@ impl TaskFileManager {
             inter_inputs,
             inter_outputs,
             fusion_base: fusion_base.as_ref().to_owned(),
+            recorder,
         };
 
         Ok(tfmgr)
     }
 
+    pub(crate) fn download_inputs(&self) -> Result<()> {
+        self.inter_inputs
+            .download(&self.fusion_base, &self.recorder)
+    }
+
     pub(crate) fn prepare_staged_inputs(&self) -> Result<StagedFiles> {
-        self.inter_inputs.download(&self.fusion_base)?;
         self.inter_inputs.convert_to_staged_files()
     }
 
@@ -91,10 +100,13 @@ impl TaskFileManager {
         Ok(staged_outputs)
     }
 
-    pub(crate) fn upload_outputs(&self) -> Result<HashMap<String, FileAuthTag>> {
-        let auth_tags = self.inter_outputs.convert_staged_files_for_upload()?;
-        self.inter_outputs.upload(&self.fusion_base)?;
-        Ok(auth_tags)
+    pub(crate) fn convert_outputs(&self) -> Result<HashMap<String, FileAuthTag>> {
+        self.inter_outputs.convert_staged_files_for_upload()
+    }
+
+    /// Upload the converted outputs, returning what was uploaded for each.
+    pub(crate) fn upload_outputs(&self) -> Result<HashMap<String, OutputArtifact>> {
+        self.inter_outputs.upload(&self.fusion_base, &self.recorder)
     }
 }
 
@@ -123,40 +135,28 @@ impl InterInput {
                 std::os::unix::fs::symlink(src, dst)?;
                 StagedFileInfo::new(src, crypto, self.file.cmac)
             }
-            FileCrypto::AesGcm128(crypto) => {
-                let mut bytes = read_all_bytes(src)?;
-                let n = bytes.len();
-                anyhow::ensure!(
-                    n > FILE_AUTH_TAG_LENGTH,
-                    "AesGcm128 File, invalid length: {:?}",
-                    src
-                );
-                anyhow::ensure!(
-                    self.file.cmac == bytes[n - FILE_AUTH_TAG_LENGTH..],
-                    "AesGcm128 File, invalid tag: {:?}",
-                    src
-                );
-                crypto.decrypt(&mut bytes)?;
+            FileCrypto::Raw => {
+                let bytes = read_all_bytes(src)?;
                 StagedFileInfo::create_with_bytes(dst, &bytes)?
             }
-            FileCrypto::AesGcm256(crypto) => {
-                let mut bytes = read_all_bytes(src)?;
+            crypto => {
+                // Decrypted in place, so the plaintext is cleared on drop.
+                let mut bytes = Zeroizing::new(read_all_bytes(src)?);
                 let n = bytes.len();
                 anyhow::ensure!(
                     n > FILE_AUTH_TAG_LENGTH,
-                    "AesGcm256 File, invalid length: {:?}",
+                    "{} File, invalid length: {:?}",
+                    crypto.schema(),
                     src
                 );
                 anyhow::ensure!(
                     self.file.cmac == bytes[n - FILE_AUTH_TAG_LENGTH..],
-                    "AesGcm256 File, invalid tag: {:?}",
+                    "{} File, invalid tag: {:?}",
+                    crypto.schema(),
                     src
                 );
-                crypto.decrypt(&mut bytes)?;
-                StagedFileInfo::create_with_bytes(dst, &bytes)?
-            }
-            FileCrypto::Raw => {
-                let bytes = read_all_bytes(src)?;
+                // Fails if the file is bound to another task or slot.
+                crypto.decrypt_in_memory(&mut bytes, self.file.context.as_ref())?;
                 StagedFileInfo::create_with_bytes(dst, &bytes)?
             }
         };
@@ -180,14 +180,18 @@ impl InterInputs {
             .collect()
     }
 
-    pub(crate) fn download(&self, fusion_base: impl AsRef<Path>) -> Result<()> {
+    pub(crate) fn download(
+        &self,
+        fusion_base: impl AsRef<Path>,
+        recorder: &FileTransferRecorder,
+    ) -> Result<()> {
         let req_info = self.inner.iter().map(|inter_input| {
             HandleFileInfo::new(&inter_input.download_path, &inter_input.file.url)
         });
         let request =
             FileAgentRequest::new(HandleFileCommand::Download, req_info, fusion_base.as_ref());
         log::debug!("Ocall file download request: {:?}", request);
-        handle_file_request(request)?;
+        recorder.handle_file_request(request)?;
         Ok(())
     }
 
@@ -227,12 +231,32 @@ impl InterOutput {
     }
 
     fn convert_to_upload_file(&self) -> Result<FileAuthTag> {
+        if !self.file.output_policies.is_empty() {
+            let content = self.staged_info.read_plaintext()?;
+            let content = apply_output_policies(&self.file.output_policies, content)?;
+            let mut staged_file = self.staged_info.create_writable_io()?;
+            staged_file.write_all(&content)?;
+            staged_file.flush()?;
+        }
         let dest = &self.upload_path;
-        let cmac = self
-            .staged_info
-            .convert_for_uploading(dest, self.file.crypto_info.to_owned())?;
+        let cmac = self.staged_info.convert_for_uploading(
+            dest,
+            self.file.crypto_info.to_owned(),
+            self.file.context.as_ref(),
+        )?;
         Ok(cmac)
     }
+
+    // Size and digest of the file to upload, which is what ends up at the URL.
+    fn to_artifact(&self, uploaded_at: i64) -> Result<OutputArtifact> {
+        let (size, sha256) = digest_file(&self.upload_path)?;
+        Ok(OutputArtifact::new(
+            self.file.url.clone(),
+            size,
+            sha256,
+            uploaded_at,
+        ))
+    }
 }
 
 impl InterOutputs {
@@ -269,16 +293,45 @@ impl InterOutputs {
             .collect()
     }
 
-    pub(crate) fn upload(&self, fusion_base: impl AsRef<Path>) -> Result<()> {
+    pub(crate) fn upload(
+        &self,
+        fusion_base: impl AsRef<Path>,
+        recorder: &FileTransferRecorder,
+    ) -> Result<HashMap<String, OutputArtifact>> {
         let req_info = self.inner.iter().map(|inter_output| {
             HandleFileInfo::new(&inter_output.upload_path, &inter_output.file.url)
         });
         let request =
             FileAgentRequest::new(HandleFileCommand::Upload, req_info, fusion_base.as_ref());
         log::debug!("Ocall file upload request: {:?}", request);
-        handle_file_request(request)?;
-        Ok(())
+        recorder.handle_file_request(request)?;
+        let uploaded_at = now_secs();
+        self.inner
+            .iter()
+            .map(|inter_output| {
+                inter_output
+                    .to_artifact(uploaded_at)
+                    .map(|artifact| (inter_output.funiq_key.clone(), artifact))
+            })
+            .collect()
+    }
+}
+
+// Byte size and hex SHA-256 of the file, read in chunks.
+fn digest_file(path: impl AsRef<Path>) -> Result<(u64, String)> {
+    let mut file = fs::File::open(path)?;
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        context.update(&buf[..n]);
+        size += n as u64;
     }
+    Ok((size, hex::encode(context.finish().as_ref())))
 }
 
 // Staged file is put in $base_dir/${funiq_key}-staged/$original_name
@@ -331,11 +384,14 @@ pub mod tests {
         let output_url =
             Url::parse("http://localhost:6789/fixtures/functions/gbdt_training/result.aes_gcm_128")
                 .unwrap();
+        let output_file_url = output_url.clone();
         let output_file = FunctionOutputFile::new(output_url, crypto);
 
         let inputs = hashmap!("training_data" => input_file);
         let outputs = hashmap!("result" => output_file);
         let task_id = Uuid::new_v4();
+        let staged_task = StagedTaskBuilder::new().task_id(task_id).build();
+        let recorder = FileTransferRecorder::new(&staged_task);
 
         let file_mgr = TaskFileManager::new(
             "/tmp",
@@ -343,9 +399,11 @@ pub mod tests {
             &task_id,
             &inputs.into(),
             &outputs.into(),
+            recorder.clone(),
         )
         .unwrap();
 
+        file_mgr.download_inputs().unwrap();
         let input_files = file_mgr.prepare_staged_inputs().unwrap();
         let output_files = file_mgr.prepare_staged_outputs().unwrap();
         // sin_file has random key1
@@ -356,6 +414,16 @@ pub mod tests {
         sin_file
             .convert_to_teaclave_file(&sout_file.path, sout_file.crypto_info)
             .unwrap();
-        file_mgr.upload_outputs().unwrap();
+        file_mgr.convert_outputs().unwrap();
+        let artifacts = file_mgr.upload_outputs().unwrap();
+        let artifact = artifacts.get("result").unwrap();
+        assert_eq!(artifact.url, output_file_url);
+        assert!(artifact.size > 0);
+        assert_eq!(artifact.sha256.len(), 64);
+
+        let records = recorder.take();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.success && r.bytes > 0));
+        assert_eq!(records[0].remote, "http://localhost");
     }
 }
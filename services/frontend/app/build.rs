@@ -29,6 +29,11 @@ fn choose_sgx_dylib(is_sim: bool) {
 }
 
 fn main() {
+    // Services in TDX guests run without enclaves, so there is nothing to link.
+    if env::var_os("CARGO_FEATURE_TDX").is_some() {
+        return;
+    }
+
     let sdk_dir = env::var("SGX_SDK").unwrap_or("/opt/intel/sgxsdk".into());
     println!("cargo:rustc-link-search=native={}/lib64", sdk_dir);
 
@@ -37,9 +42,9 @@ fn main() {
 
     println!("cargo:rustc-link-search=native={}", out_dir.display());
     if let Ok(edl_dir) = env::var("TEACLAVE_EDL_DIR") {
-        println!("cargo:rerun-if-changed={}/Enclave_common.edl", edl_dir);
+        println!("cargo:rerun-if-changed={}/Enclave_notify.edl", edl_dir);
     }
-    println!("cargo:rustc-link-lib=static:+whole-archive=Enclave_common_u");
+    println!("cargo:rustc-link-lib=static:+whole-archive=Enclave_notify_u");
 
     let is_sim = match env::var("SGX_MODE") {
         Ok(ref v) if v == "SW" => true,
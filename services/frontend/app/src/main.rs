@@ -16,10 +16,31 @@
 // under the License.
 
 use anyhow::Result;
-use teaclave_service_app_utils::launch_teaclave_service;
+use teaclave_config::RuntimeConfig;
+use teaclave_service_app_utils::{launch_teaclave_service, validate_config_requested};
+
+mod notifier;
 
 const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
 
+#[cfg(not(feature = "tdx"))]
 fn main() -> Result<()> {
+    // The backends of the notifier run in the app, so that their credentials
+    // and connections stay out of the enclave.
+    if !validate_config_requested() {
+        let config = RuntimeConfig::from_toml("runtime.config.toml")?;
+        notifier::init(config.notifier.as_ref());
+    }
     launch_teaclave_service(PACKAGE_NAME)
 }
+
+#[cfg(feature = "tdx")]
+fn main() -> Result<()> {
+    if validate_config_requested() {
+        return launch_teaclave_service(PACKAGE_NAME);
+    }
+    // The service runs in this process, next to the notifier.
+    let config = RuntimeConfig::from_toml("runtime.config.toml")?;
+    notifier::init(config.notifier.as_ref());
+    teaclave_frontend_service_enclave::run_td_guest()
+}
@@ -0,0 +1,86 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Delivery of the digests of ended tasks, which the frontend service enclave
+//! hands over through `ocall_send_notification`. Each backend configured in
+//! the `notifier` section of the runtime config sends a digest through its own
+//! channel if the user opted in to the channel. Result notifications of tasks
+//! are posted to their webhook sinks through `ocall_send_webhook`.
+
+mod slack;
+mod smtp;
+mod webhook;
+
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use teaclave_config::NotifierConfig;
+use teaclave_types::NotificationDigest;
+
+pub(crate) use slack::SlackNotifier;
+pub(crate) use smtp::SmtpNotifier;
+
+pub(crate) trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Send the digest, or do nothing if the user did not opt in to the
+    /// channel of this notifier.
+    fn notify(&self, digest: &NotificationDigest) -> Result<()>;
+}
+
+static NOTIFIERS: OnceCell<Vec<Box<dyn Notifier>>> = OnceCell::new();
+
+pub(crate) fn init(config: Option<&NotifierConfig>) {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    if let Some(config) = config {
+        if let Some(smtp) = &config.smtp {
+            notifiers.push(Box::new(SmtpNotifier::new(smtp.clone())));
+        }
+        if let Some(slack) = &config.slack {
+            notifiers.push(Box::new(SlackNotifier::new(slack.clone())));
+        }
+    }
+    let _ = NOTIFIERS.set(notifiers);
+}
+
+fn send_notification(bytes: &[u8]) -> Result<()> {
+    let digest: NotificationDigest = serde_json::from_slice(bytes)?;
+    let notifiers = NOTIFIERS.get().map(|n| n.as_slice()).unwrap_or_default();
+    let mut failed = Vec::new();
+    for notifier in notifiers {
+        if let Err(e) = notifier.notify(&digest) {
+            log::error!("{} notifier failed: {:?}", notifier.name(), e);
+            failed.push(notifier.name());
+        }
+    }
+    if !failed.is_empty() {
+        bail!("Failed to notify {} with {:?}", digest.user_id, failed);
+    }
+    Ok(())
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ocall_send_notification(in_buf: *const u8, in_len: u32) -> u32 {
+    let input_buf: &[u8] = unsafe { std::slice::from_raw_parts(in_buf, in_len as usize) };
+    match send_notification(input_buf) {
+        Ok(_) => 0,
+        Err(e) => {
+            log::error!("error: {:?}", e);
+            1
+        }
+    }
+}
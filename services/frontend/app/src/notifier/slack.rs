@@ -0,0 +1,61 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use super::Notifier;
+use anyhow::Result;
+use teaclave_config::SlackConfig;
+use teaclave_types::NotificationDigest;
+
+/// Posts digests to the channel of an incoming webhook, mentioning the
+/// member ID of the user.
+pub(crate) struct SlackNotifier {
+    config: SlackConfig,
+}
+
+impl SlackNotifier {
+    pub(crate) fn new(config: SlackConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &'static str {
+        "Slack"
+    }
+
+    fn notify(&self, digest: &NotificationDigest) -> Result<()> {
+        let member_id = match &digest.preferences.slack_member_id {
+            Some(member_id) => member_id,
+            None => return Ok(()),
+        };
+        let text = format!("<@{}> {}\n{}", member_id, digest.subject(), digest.body());
+        let payload = serde_json::json!({ "text": text });
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(async {
+                reqwest::Client::new()
+                    .post(&self.config.webhook_url)
+                    .json(&payload)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            })
+    }
+}
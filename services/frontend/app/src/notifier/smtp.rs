@@ -0,0 +1,155 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use super::Notifier;
+use anyhow::{ensure, Context, Result};
+use native_tls::{TlsConnector, TlsStream};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use teaclave_config::SmtpConfig;
+use teaclave_types::NotificationDigest;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sends digests by email through an SMTP server with implicit TLS.
+pub(crate) struct SmtpNotifier {
+    config: SmtpConfig,
+}
+
+impl SmtpNotifier {
+    pub(crate) fn new(config: SmtpConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &'static str {
+        "SMTP"
+    }
+
+    fn notify(&self, digest: &NotificationDigest) -> Result<()> {
+        let to = match &digest.preferences.email {
+            Some(email) => email,
+            None => return Ok(()),
+        };
+        let config = &self.config;
+        let tcp = TcpStream::connect((config.server.as_str(), config.port))
+            .with_context(|| format!("Cannot connect to {}:{}", config.server, config.port))?;
+        tcp.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        tcp.set_write_timeout(Some(SMTP_TIMEOUT))?;
+        let tls = TlsConnector::new()?.connect(&config.server, tcp)?;
+
+        let mut session = SmtpSession::new(tls)?;
+        session.command("EHLO teaclave", 250)?;
+        if !config.username.is_empty() {
+            let credential = format!("\0{}\0{}", config.username, config.password);
+            session.command(&format!("AUTH PLAIN {}", base64::encode(credential)), 235)?;
+        }
+        session.command(&format!("MAIL FROM:<{}>", config.from), 250)?;
+        session.command(&format!("RCPT TO:<{}>", to), 250)?;
+        session.command("DATA", 354)?;
+        session.command(&format_message(&config.from, to, digest), 250)?;
+        session.command("QUIT", 221)
+    }
+}
+
+// Lines starting with "." are escaped, and the message ends with a line with
+// a single ".".
+fn format_message(from: &str, to: &str, digest: &NotificationDigest) -> String {
+    let body = digest
+        .body()
+        .lines()
+        .map(|line| match line.starts_with('.') {
+            true => format!(".{}", line),
+            false => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n.",
+        from,
+        to,
+        digest.subject(),
+        body
+    )
+}
+
+struct SmtpSession {
+    stream: BufReader<TlsStream<TcpStream>>,
+}
+
+impl SmtpSession {
+    fn new(stream: TlsStream<TcpStream>) -> Result<Self> {
+        let mut session = Self {
+            stream: BufReader::new(stream),
+        };
+        session.expect(220)?;
+        Ok(session)
+    }
+
+    fn command(&mut self, command: &str, code: u16) -> Result<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(command.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+        self.expect(code)
+    }
+
+    // A reply may span multiple lines, where all but the last line have a
+    // "-" after the code.
+    fn expect(&mut self, code: u16) -> Result<()> {
+        loop {
+            let mut line = String::new();
+            self.stream.read_line(&mut line)?;
+            ensure!(line.len() >= 3, "Unexpected SMTP reply: {:?}", line);
+            let reply: u16 = line[..3].parse()?;
+            ensure!(reply == code, "Unexpected SMTP reply: {}", line.trim_end());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teaclave_types::{NotificationPreferences, TaskEvent, TaskStatus};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_format_message() {
+        let task_id = Uuid::new_v4();
+        let digest = NotificationDigest {
+            user_id: "alice".into(),
+            preferences: NotificationPreferences::default(),
+            events: vec![TaskEvent {
+                task_id,
+                status: TaskStatus::Failed,
+                reason: ".".to_string(),
+                participants: Vec::new(),
+                timestamp: 0,
+                webhook_sinks: Vec::new(),
+            }],
+        };
+        let message = format_message("teaclave@example.com", "alice@example.com", &digest);
+        assert!(message.starts_with("From: teaclave@example.com\r\nTo: alice@example.com\r\n"));
+        assert!(message.contains(&format!("- task {}: Failed (.)", task_id)));
+        assert!(message.ends_with("\r\n."));
+    }
+}
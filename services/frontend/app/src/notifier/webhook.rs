@@ -0,0 +1,59 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Delivery of the result notifications to the webhook sinks of tasks. They
+//! are signed in the enclaves, so the app never sees the signing secrets.
+
+use anyhow::Result;
+use teaclave_types::WebhookNotification;
+
+const SIGNATURE_HEADER: &str = "X-Teaclave-Signature";
+
+fn send_webhook(bytes: &[u8]) -> Result<()> {
+    let notification: WebhookNotification = serde_json::from_slice(bytes)?;
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            reqwest::Client::new()
+                .post(&notification.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(
+                    SIGNATURE_HEADER,
+                    format!("sha256={}", notification.signature),
+                )
+                .body(notification.body)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ocall_send_webhook(in_buf: *const u8, in_len: u32) -> u32 {
+    let input_buf: &[u8] = unsafe { std::slice::from_raw_parts(in_buf, in_len as usize) };
+    match send_webhook(input_buf) {
+        Ok(_) => 0,
+        Err(e) => {
+            log::error!("error: {:?}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Versions of the API and features of the deployment, which the SDKs ask for
+//! before other calls. An SDK too old for the service then fails with a clear
+//! message, instead of errors decoding messages it does not know.
+
+use crate::error::FrontendServiceError;
+use teaclave_config::RuntimeConfig;
+use teaclave_proto::teaclave_frontend_service::{
+    GetApiVersionResponse, API_VERSION, MIN_API_VERSION,
+};
+use teaclave_types::Executor;
+
+const EXECUTORS: &[Executor] = &[
+    Executor::Builtin,
+    Executor::MesaPy,
+    Executor::WAMicroRuntime,
+];
+
+pub(crate) fn api_version_info(config: &RuntimeConfig) -> GetApiVersionResponse {
+    let optional = [
+        ("notifier", config.notifier.is_some()),
+        ("webhook_sinks", !config.webhook.allowed_hosts.is_empty()),
+        ("ldap", config.identity_providers.ldap.is_some()),
+        ("oidc", config.identity_providers.oidc.is_some()),
+        ("password_expiry", config.password_policy.max_age_days > 0),
+    ];
+    GetApiVersionResponse {
+        api_version: API_VERSION,
+        min_api_version: MIN_API_VERSION,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        executors: EXECUTORS.iter().map(ToString::to_string).collect(),
+        attestation: config.attestation.algorithm.clone(),
+        features: optional
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(feature, _)| feature.to_string())
+            .collect(),
+    }
+}
+
+// SDKs not telling their versions send 0, and are let through.
+pub(crate) fn check_client_version(version: u32) -> Result<(), FrontendServiceError> {
+    if version != 0 && version < MIN_API_VERSION {
+        return Err(FrontendServiceError::UnsupportedApiVersion(version));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_check_client_version() {
+        assert!(check_client_version(0).is_ok());
+        assert!(check_client_version(MIN_API_VERSION).is_ok());
+        assert!(check_client_version(API_VERSION + 1).is_ok());
+        if MIN_API_VERSION > 1 {
+            assert!(check_client_version(MIN_API_VERSION - 1).is_err());
+        }
+    }
+}
@@ -15,47 +15,199 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use tokio::sync::Mutex;
-use tokio::time::{sleep, Duration};
+use tokio::sync::{Mutex, Notify};
+use tokio::time::{sleep, timeout, Duration, Instant};
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+use teaclave_config::AuditLogConfig;
 use teaclave_proto::teaclave_management_service::{SaveLogsRequest, TeaclaveManagementClient};
 use teaclave_rpc::transport::Channel;
 use teaclave_types::Entry;
 
+/// How often the agent checks whether the enclave is being finalized
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the finalization of the enclave waits for the last flush
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The enclave is finalized in another ECall, outside of the runtime of the
+// service, so the agent is told through these flags.
+static AGENT_RUNNING: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_FLUSHED: AtomicBool = AtomicBool::new(false);
+
+/// Audit logs waiting to be sent. Pushing to a full buffer wakes up the agent.
+pub struct AuditLogBuffer {
+    entries: Mutex<Vec<Entry>>,
+    max_size: usize,
+    full: Notify,
+}
+
+impl AuditLogBuffer {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+            max_size,
+            full: Notify::new(),
+        }
+    }
+
+    pub async fn push(&self, entry: Entry) {
+        let mut entries = self.entries.lock().await;
+        entries.push(entry);
+        if entries.len() >= self.max_size {
+            self.full.notify_one();
+        }
+    }
+
+    async fn is_full(&self) -> bool {
+        self.entries.lock().await.len() >= self.max_size
+    }
+
+    async fn take(&self) -> Vec<Entry> {
+        self.entries.lock().await.drain(..).collect()
+    }
+
+    // Logs which failed to be sent are kept for the next flush before the
+    // ones pushed in the meantime. The oldest are dropped if there are too
+    // many, so that the buffer stays bounded when the auditor is down.
+    async fn put_back(&self, mut logs: Vec<Entry>) {
+        let mut entries = self.entries.lock().await;
+        logs.append(&mut entries);
+        let excess = logs.len().saturating_sub(self.max_size);
+        if excess > 0 {
+            log::warn!("Dropped {} audit logs which failed to be sent", excess);
+            logs.drain(..excess);
+        }
+        *entries = logs;
+    }
+}
+
 /// Agent to send audit information to the auditor in the management service.
-/// To reduce the network activity, buffer and then send the information every 30 seconds.
+/// To reduce the network activity, the information is buffered and sent
+/// periodically, when the buffer is full, and before the enclave is finalized.
 pub struct AuditAgent {
-    management_client: Arc<Mutex<TeaclaveManagementClient<Channel>>>,
-    buffer: Arc<Mutex<Vec<Entry>>>,
+    management_client: TeaclaveManagementClient<Channel>,
+    buffer: Arc<AuditLogBuffer>,
+    flush_interval: Duration,
 }
 
 impl AuditAgent {
     pub fn new(
-        management_client: Arc<Mutex<TeaclaveManagementClient<Channel>>>,
-        buffer: Arc<Mutex<Vec<Entry>>>,
+        management_client: TeaclaveManagementClient<Channel>,
+        buffer: Arc<AuditLogBuffer>,
+        config: &AuditLogConfig,
     ) -> Self {
         Self {
             management_client,
             buffer,
+            flush_interval: Duration::from_secs(config.flush_interval_secs),
         }
     }
 
     pub async fn run(&self) {
+        AGENT_RUNNING.store(true, Ordering::Release);
+        let mut last_flush = Instant::now();
         loop {
-            let mut mutex = self.buffer.lock().await;
-            let logs: Vec<Entry> = mutex.drain(..).collect();
-            drop(mutex);
-
-            if !logs.is_empty() {
-                let request = SaveLogsRequest::new(logs);
+            tokio::select! {
+                _ = self.buffer.full.notified() => {}
+                _ = sleep(SHUTDOWN_POLL_INTERVAL) => {}
+            }
 
-                let mut client = self.management_client.lock().await;
-                let _ = client.save_logs(request).await;
+            let shutdown = SHUTDOWN_REQUESTED.load(Ordering::Acquire);
+            if shutdown
+                || last_flush.elapsed() >= self.flush_interval
+                || self.buffer.is_full().await
+            {
+                self.flush().await;
+                last_flush = Instant::now();
             }
+            if shutdown {
+                SHUTDOWN_FLUSHED.store(true, Ordering::Release);
+                return;
+            }
+        }
+    }
+
+    async fn flush(&self) {
+        let logs = self.buffer.take().await;
+        if logs.is_empty() {
+            return;
+        }
+
+        let request = SaveLogsRequest::new(logs.clone());
+        let mut client = self.management_client.clone();
+        if let Err(e) = client.save_logs(request).await {
+            log::warn!("Failed to send audit logs: {:?}", e);
+            self.buffer.put_back(logs).await;
+        }
+    }
+}
+
+/// Let the agent send the buffered logs and wait for it before the enclave is
+/// finalized.
+pub fn flush_before_finalize() {
+    if !AGENT_RUNNING.load(Ordering::Acquire) {
+        return;
+    }
+    SHUTDOWN_REQUESTED.store(true, Ordering::Release);
 
-            sleep(Duration::from_secs(30)).await;
+    let flushed = async {
+        while !SHUTDOWN_FLUSHED.load(Ordering::Acquire) {
+            sleep(Duration::from_millis(100)).await;
         }
+    };
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build();
+    match runtime {
+        Ok(runtime) => {
+            if runtime
+                .block_on(async { timeout(SHUTDOWN_FLUSH_TIMEOUT, flushed).await })
+                .is_err()
+            {
+                log::warn!("Timed out flushing audit logs before finalizing the enclave");
+            }
+        }
+        Err(e) => log::warn!("Cannot wait for audit logs to be flushed: {:?}", e),
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_types::EntryBuilder;
+
+    fn entry(message: &str) -> Entry {
+        EntryBuilder::new().message(message.to_owned()).build()
+    }
+
+    pub fn test_audit_log_buffer() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let buffer = AuditLogBuffer::new(2);
+            buffer.push(entry("a")).await;
+            assert!(!buffer.is_full().await);
+            buffer.push(entry("b")).await;
+            assert!(buffer.is_full().await);
+            // The agent is woken up by a full buffer
+            assert!(timeout(Duration::from_millis(10), buffer.full.notified())
+                .await
+                .is_ok());
+
+            let logs = buffer.take().await;
+            assert_eq!(logs.len(), 2);
+            assert!(!buffer.is_full().await);
+
+            // Unsent logs go before the new ones, and the oldest are dropped
+            buffer.push(entry("c")).await;
+            buffer.put_back(logs).await;
+            let messages: Vec<String> = buffer.take().await.iter().map(Entry::message).collect();
+            assert_eq!(messages, vec!["b", "c"]);
+        });
     }
 }
@@ -0,0 +1,353 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cache of the claims of authenticated tokens, which saves a call to the
+//! authentication service on each request of a client. Claims expire after
+//! the configured TTL or with the token, whichever comes first, and those of
+//! a user are dropped once the authentication service revokes its credential.
+//! Revocations are followed in the background, and everything is dropped if
+//! they cannot be, e.g., when the authentication service restarts with a new
+//! JWT secret.
+//!
+//! Instances of the service behind a load balancer also share the claims in
+//! the storage service. Shared claims carry the revocations followed by the
+//! instance which authenticated them, and are only taken while no credential
+//! of the user has been revoked since.
+
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use teaclave_config::AuthCacheConfig;
+use teaclave_proto::teaclave_authentication_service::{
+    ListRevocationsRequest, ListRevocationsResponse, TeaclaveAuthenticationInternalClient,
+};
+use teaclave_rpc::transport::Channel;
+use teaclave_types::UserAuthClaims;
+
+/// How often revocations are fetched, i.e., how long the claims of a revoked
+/// credential may still be used
+const REVOCATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+struct CachedClaims {
+    claims: UserAuthClaims,
+    expires_at: Instant,
+}
+
+pub(crate) struct AuthCache {
+    ttl: Duration,
+    capacity: usize,
+    // Claims by the user id and the token
+    entries: HashMap<(String, String), CachedClaims>,
+    // The revocations followed so far
+    epoch: String,
+    latest: u64,
+    // Whether the revocations are being followed, i.e., none has been missed
+    synced: bool,
+    // Shared claims authenticated before this are not taken, since the
+    // revocations up to it may have been missed.
+    floor: u64,
+    // The revocations the credentials of users were last revoked by
+    revoked_at: HashMap<String, u64>,
+    // Changed whenever claims are dropped, so that claims authenticated
+    // before are not cached after.
+    revision: u64,
+}
+
+impl AuthCache {
+    pub(crate) fn new(config: &AuthCacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            capacity: config.capacity,
+            entries: HashMap::new(),
+            epoch: String::new(),
+            latest: 0,
+            synced: false,
+            floor: 0,
+            revoked_at: HashMap::new(),
+            revision: 0,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    pub(crate) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub(crate) fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub(crate) fn get(&self, id: &str, token: &str, now: Instant) -> Option<UserAuthClaims> {
+        let key = (id.to_owned(), token.to_owned());
+        self.entries
+            .get(&key)
+            .filter(|cached| cached.expires_at > now)
+            .map(|cached| cached.claims.clone())
+    }
+
+    /// Cache the claims authenticated since `revision`, unless some claims
+    /// have been dropped in the meantime.
+    pub(crate) fn insert(
+        &mut self,
+        id: &str,
+        token: &str,
+        claims: &UserAuthClaims,
+        revision: u64,
+        now: Instant,
+    ) {
+        if !self.is_enabled() || revision != self.revision {
+            return;
+        }
+        let unix_now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(u64::MAX);
+        let token_ttl = Duration::from_secs(claims.exp.saturating_sub(unix_now));
+        if token_ttl.is_zero() {
+            return;
+        }
+        let key = (id.to_owned(), token.to_owned());
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            self.entries.retain(|_, cached| cached.expires_at > now);
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let first_expiring = self
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.expires_at)
+                .map(|(key, _)| key.clone());
+            if let Some(first_expiring) = first_expiring {
+                self.entries.remove(&first_expiring);
+            }
+        }
+        let cached = CachedClaims {
+            claims: claims.clone(),
+            expires_at: now + self.ttl.min(token_ttl),
+        };
+        self.entries.insert(key, cached);
+    }
+
+    /// The revocations followed so far, to be shared with the claims
+    /// authenticated from now on, if none has been missed.
+    pub(crate) fn cursor(&self) -> Option<(String, u64)> {
+        if self.synced {
+            Some((self.epoch.clone(), self.latest))
+        } else {
+            None
+        }
+    }
+
+    /// Whether claims shared by another instance, which had followed the
+    /// revocations up to `latest` of `epoch` when it authenticated them, can
+    /// still be taken.
+    pub(crate) fn accepts_shared(&self, user_id: &str, epoch: &str, latest: u64) -> bool {
+        self.synced
+            && epoch == self.epoch
+            && latest >= self.floor
+            && self
+                .revoked_at
+                .get(user_id)
+                .map_or(true, |revoked| *revoked <= latest)
+    }
+
+    pub(crate) fn revoke(&mut self, user_id: &str) {
+        self.entries.retain(|(id, _), _| id != user_id);
+        self.revision += 1;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.revision += 1;
+    }
+
+    fn apply_revocations(&mut self, response: ListRevocationsResponse) {
+        if response.complete {
+            for user_id in &response.user_ids {
+                self.revoke(user_id);
+                self.revoked_at.insert(user_id.clone(), response.latest);
+            }
+        } else {
+            self.clear();
+            self.revoked_at.clear();
+            self.floor = response.latest;
+        }
+        self.epoch = response.epoch;
+        self.latest = response.latest;
+        self.synced = true;
+    }
+
+    fn lose_track(&mut self) {
+        self.clear();
+        self.synced = false;
+    }
+}
+
+/// Follows the revocations of the authentication service for the cache.
+pub(crate) struct RevocationFollower {
+    authentication_client: TeaclaveAuthenticationInternalClient<Channel>,
+    cache: Arc<Mutex<AuthCache>>,
+}
+
+impl RevocationFollower {
+    pub(crate) fn new(
+        authentication_client: TeaclaveAuthenticationInternalClient<Channel>,
+        cache: Arc<Mutex<AuthCache>>,
+    ) -> Self {
+        Self {
+            authentication_client,
+            cache,
+        }
+    }
+
+    pub(crate) async fn run(mut self) {
+        loop {
+            sleep(REVOCATION_POLL_INTERVAL).await;
+            self.follow().await;
+        }
+    }
+
+    async fn follow(&mut self) {
+        let request = {
+            let cache = self.cache.lock().await;
+            ListRevocationsRequest::new(cache.epoch.clone(), cache.latest)
+        };
+        match self.authentication_client.list_revocations(request).await {
+            Ok(response) => self
+                .cache
+                .lock()
+                .await
+                .apply_revocations(response.into_inner()),
+            Err(e) => {
+                // Revocations may be missed in the meantime.
+                log::warn!("Failed to fetch revocations: {:?}", e);
+                self.cache.lock().await.lose_track();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    fn claims(id: &str, exp: u64) -> UserAuthClaims {
+        UserAuthClaims {
+            sub: id.to_owned(),
+            role: "PlatformAdmin".to_owned(),
+            iss: "Teaclave".to_owned(),
+            exp,
+        }
+    }
+
+    fn far_future() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600
+    }
+
+    fn new_cache(ttl_secs: u64, capacity: usize) -> AuthCache {
+        AuthCache::new(&AuthCacheConfig { ttl_secs, capacity })
+    }
+
+    pub fn test_auth_cache_expiry() {
+        let mut cache = new_cache(30, 2);
+        let now = Instant::now();
+        let expired = now + Duration::from_secs(30);
+        cache.insert("alice", "t1", &claims("alice", far_future()), 0, now);
+        assert_eq!(cache.get("alice", "t1", now).unwrap().sub, "alice");
+        assert!(cache.get("alice", "t2", now).is_none());
+        assert!(cache.get("alice", "t1", expired).is_none());
+
+        // Expired tokens are not cached
+        cache.insert("bob", "t1", &claims("bob", 0), 0, now);
+        assert!(cache.get("bob", "t1", now).is_none());
+
+        // The claims expiring first are evicted
+        let later = now + Duration::from_secs(1);
+        cache.insert("bob", "t2", &claims("bob", far_future()), 0, later);
+        let latest = now + Duration::from_secs(2);
+        cache.insert("carol", "t1", &claims("carol", far_future()), 0, latest);
+        assert!(cache.get("alice", "t1", latest).is_none());
+        assert!(cache.get("bob", "t2", latest).is_some());
+        assert!(cache.get("carol", "t1", latest).is_some());
+
+        let mut disabled = new_cache(0, 2);
+        disabled.insert("alice", "t1", &claims("alice", far_future()), 0, now);
+        assert!(disabled.get("alice", "t1", now).is_none());
+    }
+
+    pub fn test_auth_cache_revocations() {
+        let mut cache = new_cache(30, 10);
+        let now = Instant::now();
+        cache.insert("alice", "t1", &claims("alice", far_future()), 0, now);
+        cache.insert("bob", "t1", &claims("bob", far_future()), 0, now);
+
+        let revision = cache.revision();
+        cache.apply_revocations(ListRevocationsResponse::new(
+            "epoch",
+            1,
+            vec!["alice".to_owned()],
+            true,
+        ));
+        assert!(cache.get("alice", "t1", now).is_none());
+        assert!(cache.get("bob", "t1", now).is_some());
+        assert_eq!((cache.epoch.as_str(), cache.latest), ("epoch", 1));
+
+        // Claims authenticated before a revocation are not cached
+        cache.insert("alice", "t2", &claims("alice", far_future()), revision, now);
+        assert!(cache.get("alice", "t2", now).is_none());
+
+        cache.apply_revocations(ListRevocationsResponse::new("other", 0, vec![], false));
+        assert!(cache.get("bob", "t1", now).is_none());
+    }
+
+    pub fn test_auth_cache_shared_claims() {
+        let mut cache = new_cache(30, 10);
+        assert!(cache.cursor().is_none());
+        assert!(!cache.accepts_shared("alice", "", 0));
+
+        cache.apply_revocations(ListRevocationsResponse::new("epoch", 3, vec![], false));
+        assert_eq!(cache.cursor(), Some(("epoch".to_owned(), 3)));
+        assert!(cache.accepts_shared("alice", "epoch", 3));
+        // Revocations before the cache started following them are unknown
+        assert!(!cache.accepts_shared("alice", "epoch", 2));
+        assert!(!cache.accepts_shared("alice", "other", 3));
+
+        cache.apply_revocations(ListRevocationsResponse::new(
+            "epoch",
+            5,
+            vec!["alice".to_owned()],
+            true,
+        ));
+        assert!(!cache.accepts_shared("alice", "epoch", 4));
+        assert!(cache.accepts_shared("alice", "epoch", 5));
+        assert!(cache.accepts_shared("bob", "epoch", 4));
+
+        cache.lose_track();
+        assert!(cache.cursor().is_none());
+        assert!(!cache.accepts_shared("bob", "epoch", 5));
+    }
+}
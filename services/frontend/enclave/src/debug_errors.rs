@@ -0,0 +1,107 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Grants of platform admins letting users see the detailed chains of internal
+//! errors, which are otherwise hidden since they may tell about the internals
+//! of the platform. Grants are time-limited, and the detailed errors shown to
+//! a user are rate-limited, so that a grant cannot be used to probe the
+//! services at length.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// The longest a grant can last
+pub(crate) const MAX_GRANT_SECS: u64 = 24 * 60 * 60;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const MAX_DETAILED_PER_MINUTE: usize = 30;
+
+#[derive(Default)]
+pub(crate) struct DebugErrorGrants {
+    // Seconds since the UNIX epoch the grant of each user expires at
+    grants: HashMap<String, u64>,
+    // Times the detailed errors were shown to each user in the rate window
+    shown: HashMap<String, VecDeque<Instant>>,
+}
+
+impl DebugErrorGrants {
+    pub(crate) fn grant(&mut self, user_id: &str, expires_at: u64) {
+        self.grants.insert(user_id.to_string(), expires_at);
+    }
+
+    pub(crate) fn revoke(&mut self, user_id: &str) {
+        self.grants.remove(user_id);
+        self.shown.remove(user_id);
+    }
+
+    /// Replace the grants with the ones shared by all instances.
+    pub(crate) fn set_grants(&mut self, grants: HashMap<String, u64>) {
+        self.shown.retain(|user_id, _| grants.contains_key(user_id));
+        self.grants = grants;
+    }
+
+    /// Whether a detailed error can be shown to the user, counting it against
+    /// the rate limit if so.
+    pub(crate) fn take(&mut self, user_id: &str, unix_now: u64, now: Instant) -> bool {
+        match self.grants.get(user_id) {
+            Some(expires_at) if *expires_at > unix_now => (),
+            Some(_) => {
+                self.revoke(user_id);
+                return false;
+            }
+            None => return false,
+        }
+        let shown = self.shown.entry(user_id.to_string()).or_default();
+        while let Some(t) = shown.front() {
+            if now.duration_since(*t) < RATE_WINDOW {
+                break;
+            }
+            shown.pop_front();
+        }
+        if shown.len() >= MAX_DETAILED_PER_MINUTE {
+            return false;
+        }
+        shown.push_back(now);
+        true
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_debug_error_grants() {
+        let mut grants = DebugErrorGrants::default();
+        let now = Instant::now();
+        assert!(!grants.take("user", 100, now));
+
+        grants.grant("user", 200);
+        for _ in 0..MAX_DETAILED_PER_MINUTE {
+            assert!(grants.take("user", 100, now));
+        }
+        assert!(!grants.take("user", 100, now));
+        assert!(!grants.take("other_user", 100, now));
+        assert!(grants.take("user", 110, now + RATE_WINDOW));
+
+        // Expired grants are dropped.
+        assert!(!grants.take("user", 200, now + RATE_WINDOW));
+        assert!(!grants.take("user", 100, now + RATE_WINDOW));
+
+        grants.grant("user", 200);
+        grants.revoke("user");
+        assert!(!grants.take("user", 100, now));
+    }
+}
@@ -0,0 +1,102 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Registry of deprecated RPCs. Responses to them carry the deprecation
+//! metadata so that the SDKs can warn users, and their calls are counted in
+//! the metrics so that maintainers know when it is safe to remove them.
+
+use std::collections::BTreeMap;
+use teaclave_proto::teaclave_frontend_service::DeprecatedRpcMetrics;
+use teaclave_rpc::MetadataMap;
+
+pub(crate) struct Deprecation {
+    pub(crate) api: &'static str,
+    pub(crate) replacement: &'static str,
+    pub(crate) sunset_version: &'static str,
+}
+
+pub(crate) const DEPRECATED_APIS: &[Deprecation] = &[Deprecation {
+    api: "list_functions",
+    replacement: "search_functions",
+    sunset_version: "0.7.0",
+}];
+
+pub(crate) fn find_deprecation(api: &str) -> Option<&'static Deprecation> {
+    DEPRECATED_APIS.iter().find(|d| d.api == api)
+}
+
+impl Deprecation {
+    pub(crate) fn annotate(&self, metadata: &mut MetadataMap) {
+        // The registry only has valid ASCII values.
+        metadata.insert("deprecation", "true".parse().unwrap());
+        metadata.insert("deprecation-replacement", self.replacement.parse().unwrap());
+        metadata.insert("deprecation-sunset", self.sunset_version.parse().unwrap());
+    }
+}
+
+/// Calls of the deprecated RPCs since the service started.
+#[derive(Default)]
+pub(crate) struct DeprecationTracker {
+    calls: BTreeMap<&'static str, u64>,
+}
+
+impl DeprecationTracker {
+    pub(crate) fn record(&mut self, deprecation: &'static Deprecation) -> u64 {
+        let calls = self.calls.entry(deprecation.api).or_default();
+        *calls += 1;
+        *calls
+    }
+
+    pub(crate) fn metrics(&self) -> Vec<DeprecatedRpcMetrics> {
+        DEPRECATED_APIS
+            .iter()
+            .map(|d| DeprecatedRpcMetrics {
+                rpc: d.api.to_string(),
+                replacement: d.replacement.to_string(),
+                sunset_version: d.sunset_version.to_string(),
+                calls: self.calls.get(d.api).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_deprecation_registry() {
+        assert!(find_deprecation("search_functions").is_none());
+        let deprecation = find_deprecation("list_functions").unwrap();
+
+        let mut metadata = MetadataMap::new();
+        deprecation.annotate(&mut metadata);
+        assert_eq!(metadata.get("deprecation").unwrap(), "true");
+        assert_eq!(
+            metadata.get("deprecation-replacement").unwrap(),
+            "search_functions"
+        );
+
+        let mut tracker = DeprecationTracker::default();
+        assert_eq!(tracker.metrics()[0].calls, 0);
+        tracker.record(deprecation);
+        assert_eq!(tracker.record(deprecation), 2);
+        let metrics = tracker.metrics();
+        assert_eq!(metrics.len(), DEPRECATED_APIS.len());
+        assert_eq!(metrics[0].rpc, "list_functions");
+        assert_eq!(metrics[0].calls, 2);
+    }
+}
@@ -15,6 +15,8 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use teaclave_proto::teaclave_access_control_service::Denial;
+use teaclave_proto::teaclave_frontend_service::MIN_API_VERSION;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -35,25 +37,41 @@ impl From<AuthenticationError> for FrontendServiceError {
 
 #[derive(Error, Debug)]
 pub(crate) enum FrontendServiceError {
-    #[error("permission denied")]
-    PermissionDenied,
+    #[error("permission denied: {0}")]
+    Denied(Denial),
     #[error("service internal error")]
     Service(#[from] anyhow::Error),
     #[error("authentication failed")]
     Authentication(AuthenticationError),
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(&'static str),
+    #[error("timeout: {0}")]
+    Timeout(String),
+    #[error(
+        "API version {0} of the SDK is no longer supported, upgrade to API version {} or later",
+        MIN_API_VERSION
+    )]
+    UnsupportedApiVersion(u32),
 }
 
 impl From<FrontendServiceError> for teaclave_rpc::Status {
     fn from(error: FrontendServiceError) -> Self {
         log::debug!("FrontendServiceError: {:?}", error);
         match error {
-            FrontendServiceError::PermissionDenied => {
-                teaclave_rpc::Status::permission_denied("permission denied")
+            FrontendServiceError::Denied(denial) => {
+                teaclave_rpc::Status::permission_denied(format!("permission denied: {}", denial))
             }
             FrontendServiceError::Service(e) => teaclave_rpc::Status::internal(e.to_string()),
             FrontendServiceError::Authentication(e) => {
                 teaclave_rpc::Status::unauthenticated(e.to_string())
             }
+            FrontendServiceError::QuotaExceeded(quota) => {
+                teaclave_rpc::Status::resource_exhausted(format!("quota exceeded: {}", quota))
+            }
+            FrontendServiceError::Timeout(e) => teaclave_rpc::Status::deadline_exceeded(e),
+            FrontendServiceError::UnsupportedApiVersion(_) => {
+                teaclave_rpc::Status::failed_precondition(error.to_string())
+            }
         }
     }
 }
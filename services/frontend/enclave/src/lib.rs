@@ -19,43 +19,52 @@
 extern crate log;
 extern crate sgx_types;
 use anyhow::{anyhow, Result};
-use tokio::sync::Mutex;
 
 use std::sync::Arc;
 
 use teaclave_attestation::verifier;
 use teaclave_attestation::{AttestationConfig, RemoteAttestation};
-use teaclave_binder::proto::{
-    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, InitEnclaveInput, InitEnclaveOutput,
-    StartServiceInput, StartServiceOutput,
-};
-use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::AS_ROOT_CA_CERT;
 use teaclave_config::RuntimeConfig;
 use teaclave_proto::teaclave_access_control_service::TeaclaveAccessControlClient;
 use teaclave_proto::teaclave_authentication_service::TeaclaveAuthenticationInternalClient;
 use teaclave_proto::teaclave_frontend_service::TeaclaveFrontendServer;
 use teaclave_proto::teaclave_management_service::TeaclaveManagementClient;
+use teaclave_proto::teaclave_storage_service::TeaclaveStorageClient;
+use teaclave_rpc::connection::{connect_monitored, ConnectionStats};
 use teaclave_rpc::{config::SgxTrustedTlsServerConfig, transport::Server};
 use teaclave_service_enclave_utils::{
     create_trusted_access_control_endpoint, create_trusted_authentication_endpoint,
-    create_trusted_management_endpoint, ServiceEnclave,
+    create_trusted_management_endpoint, create_trusted_storage_endpoint, drain_signal, drained,
+    CONNECT_TIMEOUT,
 };
-use teaclave_types::{TeeServiceError, TeeServiceResult};
 
+mod api_version;
 mod audit;
+mod auth_cache;
+mod debug_errors;
+mod deprecation;
+#[cfg(feature = "mesalock_sgx")]
+mod ecall;
 mod error;
+mod notifier;
+mod quota;
 mod service;
+mod shared_state;
+mod slo;
 
 // Sets the number of worker threads the Runtime will use.
 const N_WORKERS: usize = 8;
 
-async fn start_service(config: &RuntimeConfig) -> Result<()> {
+pub async fn start_service(config: &RuntimeConfig) -> Result<()> {
     info!("Starting FrontEnd ...");
 
     let listen_address = config.api_endpoints.frontend.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(config)?;
+    let verification_policy = verifier::VerificationPolicy::from_teaclave_config(config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
+        .endorsement_cache(config.attestation.endorsement_cache_path("frontend"))
+        .reuse_cached_endorsement(config.attestation.reuse_cached_endorsement)
         .generate_and_endorse()?
         .attested_tls_config()
         .ok_or_else(|| anyhow!("cannot get attested TLS config"))?;
@@ -63,7 +72,12 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
     info!(" Starting FrontEnd: Self attestation finished ...");
 
     let server_config =
-        SgxTrustedTlsServerConfig::from_attested_tls_config(attested_tls_config.clone())?.into();
+        SgxTrustedTlsServerConfig::from_attested_tls_config(attested_tls_config.clone())?
+            .min_protocol_version(config.rpc.min_protocol_version)
+            .session_freshness(std::time::Duration::from_secs(
+                teaclave_config::build::GRPC_CONFIG.session_freshness_secs,
+            ))
+            .into();
     info!(" Starting FrontEnd: Server config setup finished ...");
 
     let enclave_info = teaclave_types::EnclaveInfo::from_bytes(&config.audit.enclave_info_bytes);
@@ -72,16 +86,30 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         &enclave_info,
         AS_ROOT_CA_CERT,
         verifier::universal_quote_verifier,
+        verification_policy.clone(),
         attested_tls_config.clone(),
     )?;
 
-    let authentication_channel = authentication_service_endpoint
-        .connect()
-        .await
-        .map_err(|e| anyhow!("Failed to connect to authentication service, retry {:?}", e))?;
-    let authentication_client = Arc::new(Mutex::new(
-        TeaclaveAuthenticationInternalClient::new_with_builtin_config(authentication_channel),
-    ));
+    let authentication_stats = Arc::new(ConnectionStats::default());
+    let authentication_channel = connect_monitored(
+        &authentication_service_endpoint,
+        "authentication",
+        CONNECT_TIMEOUT,
+        authentication_stats.clone(),
+    )
+    .await
+    .map_err(|e| anyhow!("Failed to connect to authentication service, retry {:?}", e))?;
+    let authentication_client =
+        TeaclaveAuthenticationInternalClient::new_with_builtin_config(authentication_channel);
+
+    let auth_cache = auth_cache::AuthCache::new(&config.auth_cache);
+    let auth_cache_enabled = auth_cache.is_enabled();
+    let auth_cache = Arc::new(tokio::sync::Mutex::new(auth_cache));
+    if auth_cache_enabled {
+        let follower =
+            auth_cache::RevocationFollower::new(authentication_client.clone(), auth_cache.clone());
+        tokio::spawn(follower.run());
+    }
 
     info!(" Starting FrontEnd: setup authentication client finished ...");
 
@@ -90,104 +118,165 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         &enclave_info,
         AS_ROOT_CA_CERT,
         verifier::universal_quote_verifier,
+        verification_policy.clone(),
         attested_tls_config.clone(),
     )?;
 
-    let management_channel = management_service_endpoint
-        .connect()
-        .await
-        .map_err(|e| anyhow!("Failed to connect to management service, {:?}", e))?;
-    let management_client = Arc::new(Mutex::new(
-        TeaclaveManagementClient::new_with_builtin_config(management_channel),
-    ));
+    let management_stats = Arc::new(ConnectionStats::default());
+    let management_channel = connect_monitored(
+        &management_service_endpoint,
+        "management",
+        CONNECT_TIMEOUT,
+        management_stats.clone(),
+    )
+    .await
+    .map_err(|e| anyhow!("Failed to connect to management service, {:?}", e))?;
+    let management_client = TeaclaveManagementClient::new_with_builtin_config(management_channel);
 
     info!(" Starting FrontEnd: setup management client finished ...");
 
+    // Instances behind a load balancer share their state in the storage
+    // service, which is connected to when it is first used.
+    let shared = if config.frontend.shared_state {
+        let storage_service_endpoint = create_trusted_storage_endpoint(
+            &config.internal_endpoints.storage.advertised_address,
+            &enclave_info,
+            AS_ROOT_CA_CERT,
+            verifier::universal_quote_verifier,
+            verification_policy.clone(),
+            attested_tls_config.clone(),
+        )?;
+        let storage_client =
+            TeaclaveStorageClient::new_with_builtin_config(storage_service_endpoint.connect_lazy());
+        Some(shared_state::SharedState::new(storage_client))
+    } else {
+        None
+    };
+
     let access_control_service_endpoint = create_trusted_access_control_endpoint(
         &config.internal_endpoints.access_control.advertised_address,
         &enclave_info,
         AS_ROOT_CA_CERT,
         verifier::universal_quote_verifier,
+        verification_policy,
         attested_tls_config.clone(),
     )?;
 
-    let access_control_channel = access_control_service_endpoint
-        .connect()
-        .await
-        .map_err(|e| anyhow!("Failed to connect to access_control service, retry {:?}", e))?;
-    let access_control_client = Arc::new(Mutex::new(TeaclaveAccessControlClient::new(
-        access_control_channel,
-    )));
+    let access_control_stats = Arc::new(ConnectionStats::default());
+    let access_control_channel = connect_monitored(
+        &access_control_service_endpoint,
+        "access_control",
+        CONNECT_TIMEOUT,
+        access_control_stats.clone(),
+    )
+    .await
+    .map_err(|e| anyhow!("Failed to connect to access_control service, retry {:?}", e))?;
+    let access_control_client = TeaclaveAccessControlClient::new(access_control_channel);
 
     info!(" Starting FrontEnd: setup access_control client finished ...");
 
-    let log_buffer = Arc::new(Mutex::new(Vec::new()));
-    let audit_agent = audit::AuditAgent::new(management_client.clone(), log_buffer.clone());
+    let log_buffer = Arc::new(audit::AuditLogBuffer::new(config.audit_log.max_buffer_size));
+    let audit_agent = audit::AuditAgent::new(
+        management_client.clone(),
+        log_buffer.clone(),
+        &config.audit_log,
+    );
     let agent_handle = tokio::spawn(async move {
         audit_agent.run().await;
     });
 
+    if let Some(notifier_config) = &config.notifier {
+        let interval = std::time::Duration::from_secs(notifier_config.digest_interval_secs);
+        let notification_agent =
+            notifier::NotificationAgent::new(management_client.clone(), interval);
+        tokio::spawn(async move {
+            notification_agent.run().await;
+        });
+    }
+
+    let quota = Arc::new(tokio::sync::Mutex::new(quota::QuotaManager::new(
+        config.quota,
+    )));
+    let debug_errors = Arc::new(tokio::sync::Mutex::new(
+        debug_errors::DebugErrorGrants::default(),
+    ));
+    if let Some(shared) = shared.clone() {
+        let interval = std::time::Duration::from_secs(config.frontend.quota_sync_interval_secs);
+        tokio::spawn(shared.sync(quota.clone(), debug_errors.clone(), interval));
+    }
+
     let service = service::TeaclaveFrontendService::new(
         authentication_client,
         management_client,
         access_control_client,
         log_buffer,
+        auth_cache,
+        quota,
+        debug_errors,
+        slo::SloTracker::new(config.slo.clone()),
+        vec![
+            ("authentication", authentication_stats),
+            ("management", management_stats),
+            ("access_control", access_control_stats),
+        ],
+        api_version::api_version_info(config),
+        shared,
     )
     .await?;
 
     info!(" Starting FrontEnd: start listening ...");
-    Server::builder()
+    let served = Server::builder()
         .tls_config(server_config)
         .map_err(|_| anyhow!("TeaclaveFrontendServer tls config error"))?
         .add_service(TeaclaveFrontendServer::new_with_builtin_config(service))
-        .serve(listen_address)
-        .await?;
+        .serve_with_shutdown(listen_address, drain_signal())
+        .await;
+    // The audit logs of the drained requests are flushed by the agent after
+    // this, before the enclave is finalized.
+    drained();
+    served?;
 
     agent_handle.await?;
 
     Ok(())
 }
 
-#[handle_ecall]
-fn handle_start_service(input: &StartServiceInput) -> TeeServiceResult<StartServiceOutput> {
-    let result = tokio::runtime::Builder::new_multi_thread()
-        .worker_threads(N_WORKERS)
-        .enable_all()
-        .build()
-        .map_err(|_| TeeServiceError::SgxError)?
-        .block_on(start_service(&input.config));
-
-    match result {
-        Ok(_) => Ok(StartServiceOutput),
-        Err(e) => {
-            error!("Failed to run service: {}", e);
-            Err(TeeServiceError::ServiceError)
-        }
-    }
-}
-
-#[handle_ecall]
-fn handle_init_enclave(_: &InitEnclaveInput) -> TeeServiceResult<InitEnclaveOutput> {
-    ServiceEnclave::init(env!("CARGO_PKG_NAME"))?;
-    Ok(InitEnclaveOutput)
-}
-
-#[handle_ecall]
-fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<FinalizeEnclaveOutput> {
-    ServiceEnclave::finalize()?;
-    Ok(FinalizeEnclaveOutput)
+/// Runs the service as a process of a TDX guest, until it is terminated.
+#[cfg(feature = "tdx")]
+pub fn run_td_guest() -> Result<()> {
+    teaclave_service_enclave_utils::td_guest::launch(
+        env!("CARGO_PKG_NAME"),
+        |config| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(N_WORKERS)
+                .enable_all()
+                .build()?
+                .block_on(start_service(config))
+        },
+        crate::audit::flush_before_finalize,
+    )
 }
 
-register_ecall_handler!(
-    type ECallCommand,
-    (ECallCommand::StartService, StartServiceInput, StartServiceOutput),
-    (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
-    (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
-);
-
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
+
     pub fn run_tests() -> bool {
-        true
+        run_tests!(
+            api_version::tests::test_check_client_version,
+            quota::tests::test_rate_limit,
+            quota::tests::test_task_and_data_quota,
+            slo::tests::test_rpc_family,
+            slo::tests::test_latency_breach_and_recovery,
+            slo::tests::test_error_rate_breach,
+            slo::tests::test_rejections,
+            audit::tests::test_audit_log_buffer,
+            auth_cache::tests::test_auth_cache_expiry,
+            auth_cache::tests::test_auth_cache_revocations,
+            auth_cache::tests::test_auth_cache_shared_claims,
+            deprecation::tests::test_deprecation_registry,
+            debug_errors::tests::test_debug_error_grants,
+        )
     }
 }
@@ -0,0 +1,122 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use anyhow::Result;
+#[cfg(feature = "mesalock_sgx")]
+use sgx_types::error::SgxStatus;
+use tokio::time::{sleep, Duration};
+
+use std::convert::TryFrom;
+
+use teaclave_proto::teaclave_management_service::TeaclaveManagementClient;
+use teaclave_rpc::transport::Channel;
+use teaclave_types::{NotificationDigest, WebhookNotification};
+
+#[cfg(feature = "mesalock_sgx")]
+extern "C" {
+    fn ocall_send_notification(p_retval: *mut u32, in_buf: *const u8, in_len: u32) -> SgxStatus;
+    fn ocall_send_webhook(p_retval: *mut u32, in_buf: *const u8, in_len: u32) -> SgxStatus;
+}
+
+// In a TDX guest, the notifier of the app runs in the same process and its
+// handlers of the ocalls are called directly.
+#[cfg(feature = "tdx")]
+extern "C" {
+    fn ocall_send_notification(in_buf: *const u8, in_len: u32) -> u32;
+    fn ocall_send_webhook(in_buf: *const u8, in_len: u32) -> u32;
+}
+
+/// Agent to pull digests and webhook notifications of ended tasks from the
+/// management service, and hand them over to the notifier in the untrusted
+/// app for delivery.
+pub struct NotificationAgent {
+    management_client: TeaclaveManagementClient<Channel>,
+    interval: Duration,
+}
+
+impl NotificationAgent {
+    pub fn new(management_client: TeaclaveManagementClient<Channel>, interval: Duration) -> Self {
+        Self {
+            management_client,
+            interval,
+        }
+    }
+
+    pub async fn run(&self) {
+        loop {
+            sleep(self.interval).await;
+
+            let response = self
+                .management_client
+                .clone()
+                .pull_notification_digests(())
+                .await;
+            let (digests, webhooks) = match response {
+                Ok(response) => {
+                    let response = response.into_inner();
+                    (response.digests, response.webhooks)
+                }
+                Err(e) => {
+                    warn!("Failed to pull notification digests: {:?}", e);
+                    continue;
+                }
+            };
+
+            for digest in digests {
+                let result = NotificationDigest::try_from(digest).and_then(send_notification);
+                if let Err(e) = result {
+                    warn!("Failed to send notification digest: {:?}", e);
+                }
+            }
+
+            for webhook in webhooks {
+                if let Err(e) = send_webhook(WebhookNotification::from(webhook)) {
+                    warn!("Failed to send webhook notification: {:?}", e);
+                }
+            }
+        }
+    }
+}
+
+fn send_notification(digest: NotificationDigest) -> Result<()> {
+    let bytes = serde_json::to_vec(&digest)?;
+    hand_over(ocall_send_notification, &bytes)
+}
+
+fn send_webhook(notification: WebhookNotification) -> Result<()> {
+    let bytes = serde_json::to_vec(&notification)?;
+    hand_over(ocall_send_webhook, &bytes)
+}
+
+#[cfg(feature = "mesalock_sgx")]
+fn hand_over(
+    ocall: unsafe extern "C" fn(*mut u32, *const u8, u32) -> SgxStatus,
+    bytes: &[u8],
+) -> Result<()> {
+    let mut rt: u32 = 2;
+    let res = unsafe { ocall(&mut rt as _, bytes.as_ptr() as _, bytes.len() as u32) };
+    anyhow::ensure!(res == SgxStatus::Success, "ocall sgx_error = {:?}", res);
+    anyhow::ensure!(rt == 0, "ocall error = {:?}", rt);
+    Ok(())
+}
+
+#[cfg(feature = "tdx")]
+fn hand_over(ocall: unsafe extern "C" fn(*const u8, u32) -> u32, bytes: &[u8]) -> Result<()> {
+    let rt = unsafe { ocall(bytes.as_ptr() as _, bytes.len() as u32) };
+    anyhow::ensure!(rt == 0, "ocall error = {:?}", rt);
+    Ok(())
+}
@@ -0,0 +1,205 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::error::FrontendServiceError;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use teaclave_config::QuotaConfig;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct UserUsage {
+    // Arrival time of the requests in the current rate window
+    requests: VecDeque<Instant>,
+    // Tasks created through this frontend which have not been seen ended
+    active_tasks: HashSet<String>,
+    registered_data: u32,
+}
+
+/// Per-user quotas and usage. Quotas default to the runtime config and can be
+/// overridden per user by platform admins. Both overrides and usage are kept
+/// in memory and start over when the frontend service restarts, unless the
+/// overrides are shared with other instances in the storage service. Usage is
+/// always counted by each instance.
+pub(crate) struct QuotaManager {
+    default_quota: QuotaConfig,
+    overrides: HashMap<String, QuotaConfig>,
+    usage: HashMap<String, UserUsage>,
+}
+
+fn exceeds(used: usize, limit: u32) -> bool {
+    limit != 0 && used >= limit as usize
+}
+
+impl QuotaManager {
+    pub(crate) fn new(default_quota: QuotaConfig) -> Self {
+        Self {
+            default_quota,
+            overrides: HashMap::new(),
+            usage: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn quota_of(&self, user_id: &str) -> QuotaConfig {
+        self.overrides
+            .get(user_id)
+            .copied()
+            .unwrap_or(self.default_quota)
+    }
+
+    pub(crate) fn set_quota(&mut self, user_id: &str, quota: QuotaConfig) {
+        self.overrides.insert(user_id.to_string(), quota);
+    }
+
+    /// Replace the overrides with the ones shared by all instances.
+    pub(crate) fn set_overrides(&mut self, overrides: HashMap<String, QuotaConfig>) {
+        self.overrides = overrides;
+    }
+
+    /// Count a request against the rate limit of the user.
+    pub(crate) fn check_rate(
+        &mut self,
+        user_id: &str,
+        now: Instant,
+    ) -> Result<(), FrontendServiceError> {
+        let limit = self.quota_of(user_id).max_requests_per_minute;
+        let usage = self.usage.entry(user_id.to_string()).or_default();
+        while let Some(t) = usage.requests.front() {
+            if now.duration_since(*t) < RATE_WINDOW {
+                break;
+            }
+            usage.requests.pop_front();
+        }
+        if exceeds(usage.requests.len(), limit) {
+            return Err(FrontendServiceError::QuotaExceeded("requests per minute"));
+        }
+        usage.requests.push_back(now);
+        Ok(())
+    }
+
+    pub(crate) fn check_tasks(&self, user_id: &str) -> Result<(), FrontendServiceError> {
+        let limit = self.quota_of(user_id).max_concurrent_tasks;
+        let active = self.usage.get(user_id).map_or(0, |u| u.active_tasks.len());
+        if exceeds(active, limit) {
+            return Err(FrontendServiceError::QuotaExceeded("concurrent tasks"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_data(&self, user_id: &str) -> Result<(), FrontendServiceError> {
+        let limit = self.quota_of(user_id).max_registered_data;
+        let registered = self.usage.get(user_id).map_or(0, |u| u.registered_data);
+        if exceeds(registered as usize, limit) {
+            return Err(FrontendServiceError::QuotaExceeded("registered data"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_task(&mut self, user_id: &str, task_id: &str) {
+        let usage = self.usage.entry(user_id.to_string()).or_default();
+        usage.active_tasks.insert(task_id.to_string());
+    }
+
+    pub(crate) fn finish_task(&mut self, user_id: &str, task_id: &str) {
+        if let Some(usage) = self.usage.get_mut(user_id) {
+            usage.active_tasks.remove(task_id);
+        }
+    }
+
+    pub(crate) fn active_tasks(&self, user_id: &str) -> Vec<String> {
+        self.usage
+            .get(user_id)
+            .map(|u| u.active_tasks.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn add_data(&mut self, user_id: &str) {
+        let usage = self.usage.entry(user_id.to_string()).or_default();
+        usage.registered_data += 1;
+    }
+
+    pub(crate) fn remove_data(&mut self, user_id: &str) {
+        if let Some(usage) = self.usage.get_mut(user_id) {
+            usage.registered_data = usage.registered_data.saturating_sub(1);
+        }
+    }
+
+    /// Returns the number of active tasks, registered data and requests in
+    /// the last minute of the user.
+    pub(crate) fn usage_of(&self, user_id: &str, now: Instant) -> (u32, u32, u32) {
+        match self.usage.get(user_id) {
+            Some(u) => {
+                let requests = u
+                    .requests
+                    .iter()
+                    .filter(|t| now.duration_since(**t) < RATE_WINDOW)
+                    .count();
+                (
+                    u.active_tasks.len() as u32,
+                    u.registered_data,
+                    requests as u32,
+                )
+            }
+            None => (0, 0, 0),
+        }
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    fn quota(tasks: u32, data: u32, requests: u32) -> QuotaConfig {
+        QuotaConfig {
+            max_concurrent_tasks: tasks,
+            max_registered_data: data,
+            max_requests_per_minute: requests,
+        }
+    }
+
+    pub fn test_rate_limit() {
+        let mut manager = QuotaManager::new(quota(0, 0, 2));
+        let now = Instant::now();
+        assert!(manager.check_rate("user", now).is_ok());
+        assert!(manager.check_rate("user", now).is_ok());
+        assert!(manager.check_rate("user", now).is_err());
+        assert!(manager.check_rate("other_user", now).is_ok());
+        assert_eq!(manager.usage_of("user", now), (0, 0, 2));
+
+        let later = now + RATE_WINDOW;
+        assert!(manager.check_rate("user", later).is_ok());
+    }
+
+    pub fn test_task_and_data_quota() {
+        let mut manager = QuotaManager::new(quota(1, 1, 0));
+        assert!(manager.check_tasks("user").is_ok());
+        manager.add_task("user", "task-1");
+        assert!(manager.check_tasks("user").is_err());
+        manager.finish_task("user", "task-1");
+        assert!(manager.check_tasks("user").is_ok());
+
+        manager.add_data("user");
+        assert!(manager.check_data("user").is_err());
+        manager.remove_data("user");
+        assert!(manager.check_data("user").is_ok());
+        manager.add_data("user");
+        manager.set_quota("user", quota(1, 0, 0));
+        assert!(manager.check_data("user").is_ok());
+    }
+}
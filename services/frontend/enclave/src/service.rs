@@ -15,42 +15,78 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::api_version::check_client_version;
+use crate::audit::AuditLogBuffer;
+use crate::auth_cache::AuthCache;
+use crate::debug_errors::{DebugErrorGrants, MAX_GRANT_SECS};
+use crate::deprecation::{find_deprecation, DeprecationTracker};
 use crate::error::AuthenticationError;
 use crate::error::FrontendServiceError;
+use crate::quota::QuotaManager;
+use crate::shared_state::SharedState;
+use crate::slo::{is_backend_error, SloTracker};
 
 use anyhow::Result;
 use std::net::{IpAddr, Ipv6Addr};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use teaclave_proto::teaclave_access_control_service::{
-    AuthorizeApiRequest, TeaclaveAccessControlClient,
+    AuthorizeApiRequest, Denial, DenialCode, TeaclaveAccessControlClient,
 };
 use teaclave_proto::teaclave_authentication_service::{
     TeaclaveAuthenticationInternalClient, UserAuthenticateRequest,
 };
-use teaclave_proto::teaclave_common::UserCredential;
+use teaclave_proto::teaclave_common::{i32_to_task_status, take_error_detail, UserCredential};
 use teaclave_proto::teaclave_frontend_service::{
-    ApproveTaskRequest, AssignDataRequest, CancelTaskRequest, CreateTaskRequest,
-    CreateTaskResponse, DeleteFunctionRequest, DisableFunctionRequest, GetFunctionRequest,
-    GetFunctionResponse, GetFunctionUsageStatsRequest, GetFunctionUsageStatsResponse,
-    GetInputFileRequest, GetInputFileResponse, GetOutputFileRequest, GetOutputFileResponse,
-    GetTaskRequest, GetTaskResponse, InvokeTaskRequest, ListFunctionsRequest,
-    ListFunctionsResponse, QueryAuditLogsRequest, QueryAuditLogsResponse, RegisterFunctionRequest,
-    RegisterFunctionResponse, RegisterFusionOutputRequest, RegisterFusionOutputResponse,
-    RegisterInputFileRequest, RegisterInputFileResponse, RegisterInputFromOutputRequest,
-    RegisterInputFromOutputResponse, RegisterOutputFileRequest, RegisterOutputFileResponse,
-    TeaclaveFrontend, UpdateFunctionRequest, UpdateFunctionResponse, UpdateInputFileRequest,
-    UpdateInputFileResponse, UpdateOutputFileRequest, UpdateOutputFileResponse,
+    ApproveTaskRequest, AssignDataRequest, BeginFunctionUploadRequest, BeginFunctionUploadResponse,
+    CancelMaintenanceWindowRequest, CancelTaskRequest, ChannelMetrics, CommitFunctionRequest,
+    CreateStorageSnapshotRequest, CreateStorageSnapshotResponse, CreateTaskRequest,
+    CreateTaskResponse, DeclareMaintenanceWindowRequest, DeclareMaintenanceWindowResponse,
+    DecommissionStorageRequest, DeleteDataRequest, DeleteFunctionRequest, DisableFunctionRequest,
+    ExportAuditLogsRequest, ExportAuditLogsResponse, ExportMetadataRequest, ExportMetadataResponse,
+    GetApiVersionRequest, GetApiVersionResponse, GetAuditIndexStatusRequest,
+    GetAuditIndexStatusResponse, GetConsentRecordsRequest, GetConsentRecordsResponse,
+    GetDataAttributesRequest, GetDataAttributesResponse, GetFunctionRequest, GetFunctionResponse,
+    GetFunctionUsageStatsRequest, GetFunctionUsageStatsResponse, GetInputFileRequest,
+    GetInputFileResponse, GetMetricsRequest, GetMetricsResponse, GetOutputFileRequest,
+    GetOutputFileResponse, GetPlatformStatsRequest, GetPlatformStatsResponse,
+    GetServiceInfoRequest, GetServiceInfoResponse, GetStorageDecommissionStatusRequest,
+    GetStorageDecommissionStatusResponse, GetTaskRequest, GetTaskResponse,
+    GetUserAttributesRequest, GetUserAttributesResponse, GetUserQuotaRequest, GetUserQuotaResponse,
+    GrantDebugErrorsRequest, GrantDebugErrorsResponse, InvokeTaskRequest, InvokeTaskResponse,
+    ListFunctionsRequest, ListFunctionsResponse, ListTasksRequest, ListTasksResponse,
+    ManagePolicyRequest, ManagePolicyResponse, PolicyAction, PromoteStorageReplicaRequest,
+    PromoteStorageReplicaResponse, QueryAuditLogsRequest, QueryAuditLogsResponse,
+    QueryDataLineageRequest, QueryDataLineageResponse, RebuildAuditIndexRequest,
+    RegisterFunctionRequest, RegisterFunctionResponse, RegisterFusionOutputRequest,
+    RegisterFusionOutputResponse, RegisterInputFileRequest, RegisterInputFileResponse,
+    RegisterInputFromOutputRequest, RegisterInputFromOutputResponse, RegisterOutputFileRequest,
+    RegisterOutputFileResponse, RejectTaskRequest, RestoreStorageSnapshotRequest,
+    RestoreStorageSnapshotResponse, SearchFunctionsRequest, SearchFunctionsResponse,
+    SetDataAttributesRequest, SetInputAccessPolicyRequest, SetNotificationPreferencesRequest,
+    SetUserAttributesRequest, SetUserQuotaRequest, TeaclaveFrontend, UpdateFunctionRequest,
+    UpdateFunctionResponse, UpdateInputFileRequest, UpdateInputFileResponse,
+    UpdateOutputFileRequest, UpdateOutputFileResponse, UploadFunctionChunkRequest,
+    UploadFunctionChunkResponse,
 };
-use teaclave_proto::teaclave_management_service::TeaclaveManagementClient;
+use teaclave_proto::teaclave_management_service::{SaveLogsRequest, TeaclaveManagementClient};
+use teaclave_rpc::connection::ConnectionStats;
+use teaclave_rpc::timeout::is_timeout;
 use teaclave_rpc::transport::Channel;
-use teaclave_rpc::Request;
+use teaclave_rpc::{Code, MetadataMap, Request, Response, Status};
 use teaclave_service_enclave_utils::bail;
-use teaclave_types::{Entry, EntryBuilder, TeaclaveServiceResponseResult, UserAuthClaims};
+use teaclave_types::{
+    now_secs, Entry, EntryBuilder, TaskStatus, TeaclaveServiceResponseResult, UserAuthClaims,
+};
 use tokio::sync::Mutex;
+use uuid::Uuid;
 
 macro_rules! authentication_and_forward_to_management {
     ($service: ident, $request: ident, $func: ident) => {{
         let function_name = stringify!($func).to_owned();
+        // Propagated to the services handling the request and returned to the
+        // client, so that a user action can be followed across service logs.
+        let trace_id = new_trace_id();
         let ip_option = $request.remote_addr().map(|s| s.ip());
         let ip = match ip_option {
             Some(IpAddr::V4(ip_v4)) => ip_v4.to_ipv6_compatible(),
@@ -58,35 +94,45 @@ macro_rules! authentication_and_forward_to_management {
             None => Ipv6Addr::UNSPECIFIED,
         };
 
-        let builder = EntryBuilder::new().ip(ip);
+        let builder = EntryBuilder::new().ip(ip).trace_id(trace_id.clone());
+        $service.record_deprecated_call(stringify!($func)).await;
 
         let claims = match $service.authenticate(&$request).await {
             Ok(claims) => {
-                if $service
+                match $service
                     .check_api_privilege(
                         claims.get_role().to_string().split('-').next().unwrap(),
                         stringify!($func),
+                        &trace_id,
                     )
                     .await
                 {
-                    claims
-                } else {
-                    log::debug!(
-                        "User is not authorized to access func: {}",
-                        stringify!($func)
-                    );
-
-                    let entry = builder
-                        .message(String::from("authenticate to ") + &function_name)
-                        .result(false)
-                        .build();
-                    $service.push_log(entry).await;
-
-                    bail!(FrontendServiceError::PermissionDenied);
+                    Ok(()) => claims,
+                    Err(denial) => {
+                        log::debug!(
+                            trace_id = trace_id.as_str();
+                            "User is not authorized to access func: {}",
+                            stringify!($func)
+                        );
+
+                        let entry = builder
+                            .message(
+                                String::from("authenticate to ")
+                                    + &function_name
+                                    + ": "
+                                    + &denial.to_string(),
+                            )
+                            .result(false)
+                            .build();
+                        $service.push_log(entry).await;
+
+                        bail!(FrontendServiceError::Denied(denial));
+                    }
                 }
             }
             Err(e) => {
                 log::debug!(
+                    trace_id = trace_id.as_str();
                     "User is not authenticated to access func: {}",
                     stringify!($func)
                 );
@@ -106,8 +152,22 @@ macro_rules! authentication_and_forward_to_management {
         let user = claims.to_string();
         let builder = builder.user(user);
 
-        let client = $service.management_client.clone();
-        let mut client = client.lock().await;
+        if let Err(e) = $service
+            .check_quota(&claims, $request.metadata(), stringify!($func))
+            .await
+        {
+            let entry = builder
+                .message(function_name + ": " + &e.to_string())
+                .result(false)
+                .build();
+            $service.push_log(entry).await;
+
+            bail!(e);
+        }
+
+        // Clients are cheap to clone and share the channel, so that calls of
+        // concurrent requests are multiplexed instead of waiting for each other.
+        let mut client = $service.management_client.clone();
         let meta = $request.metadata().clone();
         let message = $request.get_ref().to_owned();
 
@@ -115,20 +175,49 @@ macro_rules! authentication_and_forward_to_management {
         let metadata = request.metadata_mut();
         *metadata = meta;
         metadata.insert("role", claims.role.parse().unwrap());
+        metadata.insert("trace_id", trace_id.parse().unwrap());
+
+        let started = Instant::now();
+        let response = client.$func(request).await;
+        $service
+            .record_slo(stringify!($func), started.elapsed(), &response)
+            .await;
 
-        let response = match client.$func(request).await {
+        let mut response = match response {
             Err(e) => {
-                let entry = builder
-                    .clone()
-                    .message(function_name.clone() + ":" + &e.to_string())
-                    .result(false)
-                    .build();
+                // Denials of data access are always logged in full, while the
+                // client only sees what the management service shows it.
+                let denial = Denial::from_status(&e);
+                let mut message = function_name.clone() + ":" + &e.to_string();
+                if let Some(ref denial) = denial {
+                    message = message + " (" + &denial.to_string() + ")";
+                }
+                let entry = builder.clone().message(message).result(false).build();
                 $service.push_log(entry).await;
+                let mut e = e;
+                let detail = take_error_detail(&mut e);
+                let e = match denial {
+                    Some(_) => Status::new(e.code(), e.message()),
+                    None => e,
+                };
+                let e = $service.reveal_error(&claims.sub, e, detail).await;
+                // A hung management service is reported as such, so that
+                // clients can retry later.
+                if is_timeout(&e) {
+                    let message = format!("management service timed out on {}", function_name);
+                    bail!(FrontendServiceError::Timeout(message));
+                }
                 return Err(e);
             }
             Ok(r) => r,
         };
 
+        response
+            .metadata_mut()
+            .insert("trace_id", trace_id.parse().unwrap());
+        if let Some(deprecation) = find_deprecation(stringify!($func)) {
+            deprecation.annotate(response.metadata_mut());
+        }
         let entry = builder.message(function_name).result(true).build();
         $service.push_log(entry).await;
         Ok(response)
@@ -137,41 +226,226 @@ macro_rules! authentication_and_forward_to_management {
 
 #[derive(Clone)]
 pub(crate) struct TeaclaveFrontendService {
-    authentication_client: Arc<Mutex<TeaclaveAuthenticationInternalClient<Channel>>>,
-    management_client: Arc<Mutex<TeaclaveManagementClient<Channel>>>,
-    access_control_client: Arc<Mutex<TeaclaveAccessControlClient<Channel>>>,
-    audit_log_buffer: Arc<Mutex<Vec<Entry>>>,
+    authentication_client: TeaclaveAuthenticationInternalClient<Channel>,
+    management_client: TeaclaveManagementClient<Channel>,
+    access_control_client: TeaclaveAccessControlClient<Channel>,
+    audit_log_buffer: Arc<AuditLogBuffer>,
+    auth_cache: Arc<Mutex<AuthCache>>,
+    quota: Arc<Mutex<QuotaManager>>,
+    debug_errors: Arc<Mutex<DebugErrorGrants>>,
+    slo: Arc<Mutex<SloTracker>>,
+    deprecations: Arc<Mutex<DeprecationTracker>>,
+    channels: Vec<(&'static str, Arc<ConnectionStats>)>,
+    api_version: GetApiVersionResponse,
+    // State shared with the other instances behind the load balancer
+    shared: Option<SharedState>,
 }
 
 impl TeaclaveFrontendService {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn new(
-        authentication_client: Arc<Mutex<TeaclaveAuthenticationInternalClient<Channel>>>,
-        management_client: Arc<Mutex<TeaclaveManagementClient<Channel>>>,
-        access_control_client: Arc<Mutex<TeaclaveAccessControlClient<Channel>>>,
-        audit_log_buffer: Arc<Mutex<Vec<Entry>>>,
+        authentication_client: TeaclaveAuthenticationInternalClient<Channel>,
+        management_client: TeaclaveManagementClient<Channel>,
+        access_control_client: TeaclaveAccessControlClient<Channel>,
+        audit_log_buffer: Arc<AuditLogBuffer>,
+        auth_cache: Arc<Mutex<AuthCache>>,
+        quota: Arc<Mutex<QuotaManager>>,
+        debug_errors: Arc<Mutex<DebugErrorGrants>>,
+        slo: SloTracker,
+        channels: Vec<(&'static str, Arc<ConnectionStats>)>,
+        api_version: GetApiVersionResponse,
+        shared: Option<SharedState>,
     ) -> Result<Self> {
         Ok(Self {
             authentication_client,
             management_client,
             access_control_client,
             audit_log_buffer,
+            auth_cache,
+            quota,
+            debug_errors,
+            slo: Arc::new(Mutex::new(slo)),
+            deprecations: Arc::new(Mutex::new(DeprecationTracker::default())),
+            channels,
+            api_version,
+            shared,
         })
     }
 
     pub async fn push_log(&self, entry: Entry) {
-        let mut buffer_lock = self.audit_log_buffer.lock().await;
-        buffer_lock.push(entry);
+        // Instances sharing state send each log right away, so that none is
+        // lost with an instance taken out of the load balancer. The buffer is
+        // only used while the auditor cannot be reached.
+        if self.shared.is_some() {
+            let request = SaveLogsRequest::new(vec![entry.clone()]);
+            match self.management_client.clone().save_logs(request).await {
+                Ok(_) => return,
+                Err(e) => log::warn!("Failed to send the audit log: {:?}", e),
+            }
+        }
+        self.audit_log_buffer.push(entry).await;
     }
 
-    async fn check_api_privilege(&self, user_role: &str, api: &str) -> bool {
-        let request = AuthorizeApiRequest {
+    async fn check_api_privilege(
+        &self,
+        user_role: &str,
+        api: &str,
+        trace_id: &str,
+    ) -> Result<(), Denial> {
+        let mut request = Request::new(AuthorizeApiRequest {
             user_role: user_role.to_owned(),
             api: api.to_owned(),
-        };
+        });
+        request
+            .metadata_mut()
+            .insert("trace_id", trace_id.parse().unwrap());
 
-        let mut acs_client = self.access_control_client.lock().await;
-        let result = acs_client.authorize_api(request).await;
-        result.map(|r| r.into_inner().accept).unwrap_or(false)
+        let result = self
+            .access_control_client
+            .clone()
+            .authorize_api(request)
+            .await;
+        match result.map(|r| r.into_inner()) {
+            Ok(response) if response.accept => Ok(()),
+            Ok(response) => Err(response.denial.unwrap_or_else(|| {
+                Denial::new(
+                    DenialCode::ApiNotPermitted,
+                    format!("permission to call {}", api),
+                )
+                .user_id(user_role)
+            })),
+            // Denied without a verdict of the access control service
+            Err(_) => Err(Denial::new(
+                DenialCode::Unspecified,
+                format!("permission to call {}", api),
+            )
+            .user_id(user_role)),
+        }
+    }
+
+    // Platform admins are not limited by quotas.
+    async fn check_quota(
+        &self,
+        claims: &UserAuthClaims,
+        metadata: &MetadataMap,
+        api: &str,
+    ) -> Result<(), FrontendServiceError> {
+        if claims.get_role().is_platform_admin() {
+            return Ok(());
+        }
+
+        let user_id = &claims.sub;
+        self.quota
+            .lock()
+            .await
+            .check_rate(user_id, Instant::now())?;
+        match api {
+            "create_task" => {
+                if self.quota.lock().await.check_tasks(user_id).is_err() {
+                    self.release_ended_tasks(claims, metadata).await;
+                }
+                self.quota.lock().await.check_tasks(user_id)
+            }
+            "register_input_file"
+            | "register_output_file"
+            | "register_fusion_output"
+            | "register_input_from_output" => self.quota.lock().await.check_data(user_id),
+            _ => Ok(()),
+        }
+    }
+
+    // The frontend does not see tasks end, so ask the management service
+    // about the tracked ones when the user reaches the limit.
+    async fn release_ended_tasks(&self, claims: &UserAuthClaims, metadata: &MetadataMap) {
+        let user_id = &claims.sub;
+        let task_ids = self.quota.lock().await.active_tasks(user_id);
+        for task_id in task_ids {
+            let mut request = Request::new(GetTaskRequest {
+                task_id: task_id.clone(),
+            });
+            *request.metadata_mut() = metadata.clone();
+            request
+                .metadata_mut()
+                .insert("role", claims.role.parse().unwrap());
+
+            let response = self.management_client.clone().get_task(request).await;
+            let ended = match response {
+                Ok(r) => matches!(
+                    i32_to_task_status(r.into_inner().status),
+                    Ok(TaskStatus::Finished | TaskStatus::Failed | TaskStatus::Canceled)
+                ),
+                Err(_) => false,
+            };
+            if ended {
+                self.quota.lock().await.finish_task(user_id, &task_id);
+            }
+        }
+    }
+
+    // Breaches and recoveries are recorded in the audit log so that they can
+    // be correlated with the failed requests around them.
+    async fn record_deprecated_call(&self, api: &str) {
+        if let Some(deprecation) = find_deprecation(api) {
+            let calls = self.deprecations.lock().await.record(deprecation);
+            log::debug!(
+                "Deprecated {} called ({} in total), to be removed in {}",
+                api,
+                calls,
+                deprecation.sunset_version
+            );
+        }
+    }
+
+    async fn record_slo<T>(&self, api: &str, latency: Duration, response: &Result<T, Status>) {
+        // Rejections by backpressure, e.g., of a full task queue, are counted
+        // apart, and each of them is in the audit log with the failed request.
+        if let Err(status) = response {
+            if status.code() == Code::ResourceExhausted {
+                let rejected = self.slo.lock().await.record_rejection(api);
+                log::warn!(
+                    "{} rejected by backpressure ({} in total): {}",
+                    api,
+                    rejected,
+                    status.message()
+                );
+                return;
+            }
+        }
+        let failed = matches!(
+            response,
+            Err(status) if is_backend_error(status.code()) || is_timeout(status)
+        );
+        let event = self
+            .slo
+            .lock()
+            .await
+            .record(api, latency, failed, Instant::now());
+        if let Some(event) = event {
+            if event.breached {
+                log::warn!("{}", event.message);
+            } else {
+                log::info!("{}", event.message);
+            }
+            let entry = EntryBuilder::new()
+                .message(event.message)
+                .result(!event.breached)
+                .build();
+            self.push_log(entry).await;
+        }
+    }
+
+    // Only for APIs served by the frontend itself
+    async fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        api: &str,
+    ) -> Result<UserAuthClaims, FrontendServiceError> {
+        let claims = self.authenticate(request).await?;
+        let role = claims.get_role().to_string();
+        self.check_api_privilege(role.split('-').next().unwrap(), api, &new_trace_id())
+            .await
+            .map_err(FrontendServiceError::Denied)?;
+        Ok(claims)
     }
 }
 
@@ -181,7 +455,12 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         &self,
         request: Request<RegisterInputFileRequest>,
     ) -> TeaclaveServiceResponseResult<RegisterInputFileResponse> {
-        authentication_and_forward_to_management!(self, request, register_input_file)
+        let response =
+            authentication_and_forward_to_management!(self, request, register_input_file);
+        if response.is_ok() {
+            self.record_data(&request).await;
+        }
+        response
     }
 
     async fn update_input_file(
@@ -195,7 +474,12 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         &self,
         request: Request<RegisterOutputFileRequest>,
     ) -> TeaclaveServiceResponseResult<RegisterOutputFileResponse> {
-        authentication_and_forward_to_management!(self, request, register_output_file)
+        let response =
+            authentication_and_forward_to_management!(self, request, register_output_file);
+        if response.is_ok() {
+            self.record_data(&request).await;
+        }
+        response
     }
 
     async fn update_output_file(
@@ -209,14 +493,24 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         &self,
         request: Request<RegisterFusionOutputRequest>,
     ) -> TeaclaveServiceResponseResult<RegisterFusionOutputResponse> {
-        authentication_and_forward_to_management!(self, request, register_fusion_output)
+        let response =
+            authentication_and_forward_to_management!(self, request, register_fusion_output);
+        if response.is_ok() {
+            self.record_data(&request).await;
+        }
+        response
     }
 
     async fn register_input_from_output(
         &self,
         request: Request<RegisterInputFromOutputRequest>,
     ) -> TeaclaveServiceResponseResult<RegisterInputFromOutputResponse> {
-        authentication_and_forward_to_management!(self, request, register_input_from_output)
+        let response =
+            authentication_and_forward_to_management!(self, request, register_input_from_output);
+        if response.is_ok() {
+            self.record_data(&request).await;
+        }
+        response
     }
 
     async fn get_output_file(
@@ -240,6 +534,27 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         authentication_and_forward_to_management!(self, request, register_function)
     }
 
+    async fn begin_function_upload(
+        &self,
+        request: Request<BeginFunctionUploadRequest>,
+    ) -> TeaclaveServiceResponseResult<BeginFunctionUploadResponse> {
+        authentication_and_forward_to_management!(self, request, begin_function_upload)
+    }
+
+    async fn upload_function_chunk(
+        &self,
+        request: Request<UploadFunctionChunkRequest>,
+    ) -> TeaclaveServiceResponseResult<UploadFunctionChunkResponse> {
+        authentication_and_forward_to_management!(self, request, upload_function_chunk)
+    }
+
+    async fn commit_function(
+        &self,
+        request: Request<CommitFunctionRequest>,
+    ) -> TeaclaveServiceResponseResult<RegisterFunctionResponse> {
+        authentication_and_forward_to_management!(self, request, commit_function)
+    }
+
     async fn update_function(
         &self,
         request: Request<UpdateFunctionRequest>,
@@ -261,6 +576,13 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         authentication_and_forward_to_management!(self, request, get_function_usage_stats)
     }
 
+    async fn query_function_usage_records(
+        &self,
+        request: Request<QueryFunctionUsageRecordsRequest>,
+    ) -> TeaclaveServiceResponseResult<QueryFunctionUsageRecordsResponse> {
+        authentication_and_forward_to_management!(self, request, query_function_usage_records)
+    }
+
     async fn delete_function(
         &self,
         request: Request<DeleteFunctionRequest>,
@@ -282,11 +604,23 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         authentication_and_forward_to_management!(self, request, list_functions)
     }
 
+    async fn search_functions(
+        &self,
+        request: Request<SearchFunctionsRequest>,
+    ) -> TeaclaveServiceResponseResult<SearchFunctionsResponse> {
+        authentication_and_forward_to_management!(self, request, search_functions)
+    }
+
     async fn create_task(
         &self,
         request: Request<CreateTaskRequest>,
     ) -> TeaclaveServiceResponseResult<CreateTaskResponse> {
-        authentication_and_forward_to_management!(self, request, create_task)
+        let response = authentication_and_forward_to_management!(self, request, create_task);
+        if let (Ok(r), Some(user_id)) = (&response, request_user_id(&request)) {
+            let task_id = &r.get_ref().task_id;
+            self.quota.lock().await.add_task(user_id, task_id);
+        }
+        response
     }
 
     async fn get_task(
@@ -296,6 +630,13 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         authentication_and_forward_to_management!(self, request, get_task)
     }
 
+    async fn list_tasks(
+        &self,
+        request: Request<ListTasksRequest>,
+    ) -> TeaclaveServiceResponseResult<ListTasksResponse> {
+        authentication_and_forward_to_management!(self, request, list_tasks)
+    }
+
     async fn assign_data(
         &self,
         request: Request<AssignDataRequest>,
@@ -310,10 +651,17 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         authentication_and_forward_to_management!(self, request, approve_task)
     }
 
+    async fn reject_task(
+        &self,
+        request: Request<RejectTaskRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        authentication_and_forward_to_management!(self, request, reject_task)
+    }
+
     async fn invoke_task(
         &self,
         request: Request<InvokeTaskRequest>,
-    ) -> TeaclaveServiceResponseResult<()> {
+    ) -> TeaclaveServiceResponseResult<InvokeTaskResponse> {
         authentication_and_forward_to_management!(self, request, invoke_task)
     }
 
@@ -324,15 +672,424 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         authentication_and_forward_to_management!(self, request, cancel_task)
     }
 
+    async fn get_consent_records(
+        &self,
+        request: Request<GetConsentRecordsRequest>,
+    ) -> TeaclaveServiceResponseResult<GetConsentRecordsResponse> {
+        authentication_and_forward_to_management!(self, request, get_consent_records)
+    }
+
+    async fn get_task_log(
+        &self,
+        request: Request<GetTaskLogRequest>,
+    ) -> TeaclaveServiceResponseResult<GetTaskLogResponse> {
+        authentication_and_forward_to_management!(self, request, get_task_log)
+    }
+
+    async fn register_webhook_sink(
+        &self,
+        request: Request<RegisterWebhookSinkRequest>,
+    ) -> TeaclaveServiceResponseResult<RegisterWebhookSinkResponse> {
+        authentication_and_forward_to_management!(self, request, register_webhook_sink)
+    }
+
     async fn query_audit_logs(
         &self,
         request: Request<QueryAuditLogsRequest>,
     ) -> TeaclaveServiceResponseResult<QueryAuditLogsResponse> {
         authentication_and_forward_to_management!(self, request, query_audit_logs)
     }
+
+    async fn get_platform_stats(
+        &self,
+        request: Request<GetPlatformStatsRequest>,
+    ) -> TeaclaveServiceResponseResult<GetPlatformStatsResponse> {
+        authentication_and_forward_to_management!(self, request, get_platform_stats)
+    }
+
+    async fn export_audit_logs(
+        &self,
+        request: Request<ExportAuditLogsRequest>,
+    ) -> TeaclaveServiceResponseResult<ExportAuditLogsResponse> {
+        authentication_and_forward_to_management!(self, request, export_audit_logs)
+    }
+
+    async fn rebuild_audit_index(
+        &self,
+        request: Request<RebuildAuditIndexRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        authentication_and_forward_to_management!(self, request, rebuild_audit_index)
+    }
+
+    async fn get_audit_index_status(
+        &self,
+        request: Request<GetAuditIndexStatusRequest>,
+    ) -> TeaclaveServiceResponseResult<GetAuditIndexStatusResponse> {
+        authentication_and_forward_to_management!(self, request, get_audit_index_status)
+    }
+
+    async fn export_metadata(
+        &self,
+        request: Request<ExportMetadataRequest>,
+    ) -> TeaclaveServiceResponseResult<ExportMetadataResponse> {
+        authentication_and_forward_to_management!(self, request, export_metadata)
+    }
+
+    async fn decommission_storage(
+        &self,
+        request: Request<DecommissionStorageRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        authentication_and_forward_to_management!(self, request, decommission_storage)
+    }
+
+    async fn get_storage_decommission_status(
+        &self,
+        request: Request<GetStorageDecommissionStatusRequest>,
+    ) -> TeaclaveServiceResponseResult<GetStorageDecommissionStatusResponse> {
+        authentication_and_forward_to_management!(self, request, get_storage_decommission_status)
+    }
+
+    async fn create_storage_snapshot(
+        &self,
+        request: Request<CreateStorageSnapshotRequest>,
+    ) -> TeaclaveServiceResponseResult<CreateStorageSnapshotResponse> {
+        authentication_and_forward_to_management!(self, request, create_storage_snapshot)
+    }
+
+    async fn restore_storage_snapshot(
+        &self,
+        request: Request<RestoreStorageSnapshotRequest>,
+    ) -> TeaclaveServiceResponseResult<RestoreStorageSnapshotResponse> {
+        authentication_and_forward_to_management!(self, request, restore_storage_snapshot)
+    }
+
+    async fn promote_storage_replica(
+        &self,
+        request: Request<PromoteStorageReplicaRequest>,
+    ) -> TeaclaveServiceResponseResult<PromoteStorageReplicaResponse> {
+        authentication_and_forward_to_management!(self, request, promote_storage_replica)
+    }
+
+    async fn set_user_quota(
+        &self,
+        request: Request<SetUserQuotaRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let claims = self.authorize(&request, "set_user_quota").await?;
+        let request = request.into_inner();
+        let quota = request
+            .quota
+            .ok_or_else(|| teaclave_rpc::Status::invalid_argument("missing quota"))?;
+        let quota = quota.into();
+        if let Some(shared) = self.shared.as_ref() {
+            shared
+                .put_quota(&request.user_id, &quota)
+                .await
+                .map_err(|e| {
+                    log::warn!("Failed to share the quota: {:?}", e);
+                    Status::unavailable("failed to share the quota")
+                })?;
+        }
+        self.quota.lock().await.set_quota(&request.user_id, quota);
+
+        let entry = EntryBuilder::new()
+            .user(claims.to_string())
+            .message(format!("set_user_quota: {}", request.user_id))
+            .result(true)
+            .build();
+        self.push_log(entry).await;
+        Ok(Response::new(()))
+    }
+
+    async fn get_user_quota(
+        &self,
+        request: Request<GetUserQuotaRequest>,
+    ) -> TeaclaveServiceResponseResult<GetUserQuotaResponse> {
+        self.authorize(&request, "get_user_quota").await?;
+        let user_id = request.into_inner().user_id;
+        let quota = self.quota.lock().await;
+        let (active_tasks, registered_data, requests_in_last_minute) =
+            quota.usage_of(&user_id, Instant::now());
+        let response = GetUserQuotaResponse {
+            quota: Some(quota.quota_of(&user_id).into()),
+            active_tasks,
+            registered_data,
+            requests_in_last_minute,
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn grant_debug_errors(
+        &self,
+        request: Request<GrantDebugErrorsRequest>,
+    ) -> TeaclaveServiceResponseResult<GrantDebugErrorsResponse> {
+        let claims = self.authorize(&request, "grant_debug_errors").await?;
+        let request = request.into_inner();
+        if request.duration_secs > MAX_GRANT_SECS {
+            return Err(Status::invalid_argument(format!(
+                "grants last {} seconds at most",
+                MAX_GRANT_SECS
+            )));
+        }
+        let expires_at = match request.duration_secs {
+            0 => None,
+            duration_secs => Some(now_secs() as u64 + duration_secs),
+        };
+        if let Some(shared) = self.shared.as_ref() {
+            shared
+                .put_debug_grant(&request.user_id, expires_at)
+                .await
+                .map_err(|e| {
+                    log::warn!("Failed to share the debug error grant: {:?}", e);
+                    Status::unavailable("failed to share the grant")
+                })?;
+        }
+        let message = {
+            let mut grants = self.debug_errors.lock().await;
+            match expires_at {
+                Some(expires_at) => {
+                    grants.grant(&request.user_id, expires_at);
+                    format!(
+                        "grant_debug_errors: {} for {} seconds",
+                        request.user_id, request.duration_secs
+                    )
+                }
+                None => {
+                    grants.revoke(&request.user_id);
+                    format!("grant_debug_errors: {} revoked", request.user_id)
+                }
+            }
+        };
+
+        let entry = EntryBuilder::new()
+            .user(claims.to_string())
+            .message(message)
+            .result(true)
+            .build();
+        self.push_log(entry).await;
+        Ok(Response::new(GrantDebugErrorsResponse {
+            expires_at: expires_at.unwrap_or(0),
+        }))
+    }
+
+    async fn get_metrics(
+        &self,
+        request: Request<GetMetricsRequest>,
+    ) -> TeaclaveServiceResponseResult<GetMetricsResponse> {
+        self.authorize(&request, "get_metrics").await?;
+        let mut slo = self.slo.lock().await;
+        let response = GetMetricsResponse {
+            window_secs: slo.window_secs(),
+            families: slo.metrics(Instant::now()),
+            deprecated_rpcs: self.deprecations.lock().await.metrics(),
+            channels: self
+                .channels
+                .iter()
+                .map(|(service, stats)| ChannelMetrics {
+                    service: service.to_string(),
+                    reconnects: stats.reconnects(),
+                    failures: stats.failures(),
+                })
+                .collect(),
+        };
+        Ok(Response::new(response))
+    }
+
+    // Not authenticated, so that the SDKs can check the versions before
+    // logging in.
+    async fn get_api_version(
+        &self,
+        request: Request<GetApiVersionRequest>,
+    ) -> TeaclaveServiceResponseResult<GetApiVersionResponse> {
+        check_client_version(request.get_ref().client_api_version)?;
+        Ok(Response::new(self.api_version.clone()))
+    }
+
+    async fn manage_policy(
+        &self,
+        request: Request<ManagePolicyRequest>,
+    ) -> TeaclaveServiceResponseResult<ManagePolicyResponse> {
+        let claims = self.authorize(&request, "manage_policy").await?;
+        let trace_id = new_trace_id();
+        let message = request.into_inner();
+        let change = match PolicyAction::from_i32(message.action) {
+            Some(PolicyAction::ListRules) | None => None,
+            Some(action) => Some(format!(
+                "manage_policy: {:?} {:?}",
+                action,
+                message.rule.clone().unwrap_or_default()
+            )),
+        };
+
+        let mut request = Request::new(message);
+        let metadata = request.metadata_mut();
+        metadata.insert("role", claims.role.parse().unwrap());
+        metadata.insert("trace_id", trace_id.parse().unwrap());
+        let response = self
+            .access_control_client
+            .clone()
+            .manage_policy(request)
+            .await;
+
+        if let Some(change) = change {
+            let entry = EntryBuilder::new()
+                .user(claims.to_string())
+                .trace_id(trace_id)
+                .message(change)
+                .result(response.is_ok())
+                .build();
+            self.push_log(entry).await;
+        }
+        response
+    }
+
+    async fn set_user_attributes(
+        &self,
+        request: Request<SetUserAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let claims = self.authorize(&request, "set_user_attributes").await?;
+        let trace_id = new_trace_id();
+        let message = request.into_inner();
+        let change = format!(
+            "set_user_attributes: {} {:?}",
+            message.user_id, message.attributes
+        );
+
+        let mut request = Request::new(message);
+        let metadata = request.metadata_mut();
+        metadata.insert("role", claims.role.parse().unwrap());
+        metadata.insert("trace_id", trace_id.parse().unwrap());
+        let response = self
+            .access_control_client
+            .clone()
+            .set_user_attributes(request)
+            .await;
+
+        let entry = EntryBuilder::new()
+            .user(claims.to_string())
+            .trace_id(trace_id)
+            .message(change)
+            .result(response.is_ok())
+            .build();
+        self.push_log(entry).await;
+        response
+    }
+
+    async fn get_user_attributes(
+        &self,
+        request: Request<GetUserAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<GetUserAttributesResponse> {
+        let claims = self.authorize(&request, "get_user_attributes").await?;
+        let mut request = Request::new(request.into_inner());
+        request
+            .metadata_mut()
+            .insert("role", claims.role.parse().unwrap());
+        self.access_control_client
+            .clone()
+            .get_user_attributes(request)
+            .await
+    }
+
+    async fn set_data_attributes(
+        &self,
+        request: Request<SetDataAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        authentication_and_forward_to_management!(self, request, set_data_attributes)
+    }
+
+    async fn get_data_attributes(
+        &self,
+        request: Request<GetDataAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<GetDataAttributesResponse> {
+        authentication_and_forward_to_management!(self, request, get_data_attributes)
+    }
+
+    async fn set_input_access_policy(
+        &self,
+        request: Request<SetInputAccessPolicyRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        authentication_and_forward_to_management!(self, request, set_input_access_policy)
+    }
+
+    async fn delete_data(
+        &self,
+        request: Request<DeleteDataRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let response = authentication_and_forward_to_management!(self, request, delete_data);
+        if response.is_ok() {
+            if let Some(user_id) = request_user_id(&request) {
+                self.quota.lock().await.remove_data(user_id);
+            }
+        }
+        response
+    }
+
+    async fn query_data_lineage(
+        &self,
+        request: Request<QueryDataLineageRequest>,
+    ) -> TeaclaveServiceResponseResult<QueryDataLineageResponse> {
+        authentication_and_forward_to_management!(self, request, query_data_lineage)
+    }
+
+    async fn set_notification_preferences(
+        &self,
+        request: Request<SetNotificationPreferencesRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        authentication_and_forward_to_management!(self, request, set_notification_preferences)
+    }
+
+    async fn declare_maintenance_window(
+        &self,
+        request: Request<DeclareMaintenanceWindowRequest>,
+    ) -> TeaclaveServiceResponseResult<DeclareMaintenanceWindowResponse> {
+        authentication_and_forward_to_management!(self, request, declare_maintenance_window)
+    }
+
+    async fn cancel_maintenance_window(
+        &self,
+        request: Request<CancelMaintenanceWindowRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        authentication_and_forward_to_management!(self, request, cancel_maintenance_window)
+    }
+
+    // Not authenticated, so that the SDKs can tell users about maintenance
+    // before they log in.
+    async fn get_service_info(
+        &self,
+        request: Request<GetServiceInfoRequest>,
+    ) -> TeaclaveServiceResponseResult<GetServiceInfoResponse> {
+        let mut client = self.management_client.clone();
+        client
+            .get_service_info(Request::new(request.into_inner()))
+            .await
+    }
 }
 
 impl TeaclaveFrontendService {
+    // The detailed error chain is appended to the message of internal errors
+    // for users granted to see it.
+    async fn reveal_error(&self, user_id: &str, status: Status, detail: Option<String>) -> Status {
+        let detail = match detail {
+            Some(detail) => detail,
+            None => return status,
+        };
+        let granted =
+            self.debug_errors
+                .lock()
+                .await
+                .take(user_id, now_secs() as u64, Instant::now());
+        if !granted {
+            return status;
+        }
+        let message = format!("{}: {}", status.message(), detail);
+        Status::with_metadata(status.code(), message, status.metadata().clone())
+    }
+
+    async fn record_data<T>(&self, request: &Request<T>) {
+        if let Some(user_id) = request_user_id(request) {
+            self.quota.lock().await.add_data(user_id);
+        }
+    }
+
     async fn authenticate<T>(
         &self,
         request: &Request<T>,
@@ -347,13 +1104,27 @@ impl TeaclaveFrontendService {
             .get("token")
             .and_then(|x| x.to_str().ok())
             .ok_or(AuthenticationError::MissingToken)?;
+        let (revision, cursor) = {
+            let cache = self.auth_cache.lock().await;
+            if let Some(claims) = cache.get(id, token, Instant::now()) {
+                return Ok(claims);
+            }
+            (cache.revision(), cache.cursor())
+        };
+        if let Some(shared) = self.shared.as_ref() {
+            if let Some(found) = shared.get_claims(id, token).await {
+                let mut cache = self.auth_cache.lock().await;
+                if cache.accepts_shared(id, &found.epoch, found.latest) {
+                    cache.insert(id, token, &found.claims, revision, Instant::now());
+                    return Ok(found.claims);
+                }
+            }
+        }
         let credential = Some(UserCredential::new(id, token));
         let auth_request = UserAuthenticateRequest { credential };
         let claims = self
             .authentication_client
             .clone()
-            .lock()
-            .await
             .user_authenticate(auth_request)
             .await
             .map_err(|_| AuthenticationError::IncorrectCredential)?
@@ -361,7 +1132,28 @@ impl TeaclaveFrontendService {
             .claims
             .and_then(|x| x.try_into().ok())
             .ok_or(AuthenticationError::IncorrectCredential)?;
+        let ttl = {
+            let mut cache = self.auth_cache.lock().await;
+            cache.insert(id, token, &claims, revision, Instant::now());
+            cache.ttl()
+        };
+        // Shared only if the revocations are being followed, since other
+        // instances check them against the ones followed by this one.
+        if let (Some(shared), Some(cursor)) = (self.shared.as_ref(), cursor) {
+            if !ttl.is_zero() {
+                shared.put_claims(id, token, &claims, cursor, ttl).await;
+            }
+        }
 
         Ok(claims)
     }
 }
+
+fn new_trace_id() -> String {
+    Uuid::new_v4().to_simple().to_string()
+}
+
+// The id has been authenticated once the request is forwarded.
+fn request_user_id<T>(request: &Request<T>) -> Option<&str> {
+    request.metadata().get("id").and_then(|x| x.to_str().ok())
+}
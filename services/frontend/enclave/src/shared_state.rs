@@ -0,0 +1,211 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! State of the frontend service kept in the storage service, so that the
+//! instances behind a load balancer serve a client alike whichever the client
+//! reaches: the claims of authenticated tokens, and the quotas and grants of
+//! detailed errors set for users.
+//! Tokens never leave the enclave, the claims are keyed by a digest of the
+//! user id and the token.
+
+use crate::debug_errors::DebugErrorGrants;
+use crate::quota::QuotaManager;
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use teaclave_config::QuotaConfig;
+use teaclave_proto::teaclave_storage_service::{
+    DeleteRequest, GetRequest, ListEntriesRequest, PutRequest, TeaclaveStorageClient,
+};
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Code;
+use teaclave_types::UserAuthClaims;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+const CLAIMS_PREFIX: &str = "frontend_claims/";
+const QUOTA_PREFIX: &str = "frontend_quota/";
+const DEBUG_ERRORS_PREFIX: &str = "frontend_debug_errors/";
+// Quotas and grants are listed from the storage in pages of about this size.
+const PAGE_BYTES: u64 = 64 * 1024;
+
+/// Claims authenticated by an instance, along with the revocations it had
+/// followed then.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SharedClaims {
+    pub(crate) claims: UserAuthClaims,
+    pub(crate) epoch: String,
+    pub(crate) latest: u64,
+}
+
+#[derive(Clone)]
+pub(crate) struct SharedState {
+    storage_client: TeaclaveStorageClient<Channel>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(u64::MAX)
+}
+
+fn claims_key(id: &str, token: &str) -> String {
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    context.update(id.as_bytes());
+    context.update(&[0]);
+    context.update(token.as_bytes());
+    let digest: String = context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("{}{}", CLAIMS_PREFIX, digest)
+}
+
+impl SharedState {
+    pub(crate) fn new(storage_client: TeaclaveStorageClient<Channel>) -> Self {
+        Self { storage_client }
+    }
+
+    pub(crate) async fn get_claims(&self, id: &str, token: &str) -> Option<SharedClaims> {
+        let request = GetRequest::new(claims_key(id, token).as_bytes());
+        let value = match self.storage_client.clone().get(request).await {
+            Ok(response) => response.into_inner().value,
+            Err(status) if status.code() == Code::NotFound => return None,
+            Err(status) => {
+                log::warn!("Failed to read shared claims: {:?}", status);
+                return None;
+            }
+        };
+        let shared: SharedClaims = serde_json::from_slice(&value).ok()?;
+        // The claims are of the token, but the id is checked all the same.
+        if shared.claims.sub != id || shared.claims.exp <= unix_now() {
+            return None;
+        }
+        Some(shared)
+    }
+
+    /// Share the claims for `ttl` at most, or until the token expires.
+    pub(crate) async fn put_claims(
+        &self,
+        id: &str,
+        token: &str,
+        claims: &UserAuthClaims,
+        cursor: (String, u64),
+        ttl: Duration,
+    ) {
+        let expires_at = unix_now().saturating_add(ttl.as_secs()).min(claims.exp);
+        let shared = SharedClaims {
+            claims: claims.clone(),
+            epoch: cursor.0,
+            latest: cursor.1,
+        };
+        let value = match serde_json::to_vec(&shared) {
+            Ok(value) => value,
+            Err(e) => {
+                log::warn!("Failed to serialize shared claims: {:?}", e);
+                return;
+            }
+        };
+        let request =
+            PutRequest::new(claims_key(id, token).as_bytes(), value).expires_at(expires_at);
+        if let Err(e) = self.storage_client.clone().put(request).await {
+            log::warn!("Failed to share claims: {:?}", e);
+        }
+    }
+
+    pub(crate) async fn put_quota(&self, user_id: &str, quota: &QuotaConfig) -> Result<()> {
+        let key = format!("{}{}", QUOTA_PREFIX, user_id);
+        let request = PutRequest::new(key.as_bytes(), serde_json::to_vec(quota)?);
+        self.storage_client.clone().put(request).await?;
+        Ok(())
+    }
+
+    /// Share the grant, which the storage service drops once it expires, or
+    /// revoke it if `expires_at` is None.
+    pub(crate) async fn put_debug_grant(
+        &self,
+        user_id: &str,
+        expires_at: Option<u64>,
+    ) -> Result<()> {
+        let key = format!("{}{}", DEBUG_ERRORS_PREFIX, user_id);
+        let mut client = self.storage_client.clone();
+        match expires_at {
+            Some(expires_at) => {
+                let value = serde_json::to_vec(&expires_at)?;
+                let request = PutRequest::new(key.as_bytes(), value).expires_at(expires_at);
+                client.put(request).await?;
+            }
+            None => {
+                client.delete(DeleteRequest::new(key.as_bytes())).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // The values under `prefix` by the rest of their keys
+    async fn read_entries<T: DeserializeOwned>(&self, prefix: &str) -> Result<HashMap<String, T>> {
+        let mut values = HashMap::new();
+        let mut start_after = Vec::new();
+        loop {
+            let request = ListEntriesRequest::new(prefix.as_bytes())
+                .page(start_after.clone(), 0)
+                .with_values(PAGE_BYTES);
+            let entries = self
+                .storage_client
+                .clone()
+                .list_entries(request)
+                .await?
+                .into_inner()
+                .entries;
+            start_after = match entries.last() {
+                Some(entry) => entry.key.clone(),
+                None => return Ok(values),
+            };
+            for entry in entries {
+                let name = String::from_utf8_lossy(&entry.key[prefix.len()..]);
+                values.insert(name.into_owned(), serde_json::from_slice(&entry.value)?);
+            }
+        }
+    }
+
+    /// Pick up the quotas and grants set by other instances every `interval`.
+    pub(crate) async fn sync(
+        self,
+        quota: Arc<Mutex<QuotaManager>>,
+        debug_errors: Arc<Mutex<DebugErrorGrants>>,
+        interval: Duration,
+    ) {
+        loop {
+            match self.read_entries(QUOTA_PREFIX).await {
+                Ok(overrides) => quota.lock().await.set_overrides(overrides),
+                Err(e) => log::warn!("Failed to read shared quotas: {:?}", e),
+            }
+            match self.read_entries(DEBUG_ERRORS_PREFIX).await {
+                Ok(grants) => debug_errors.lock().await.set_grants(grants),
+                Err(e) => log::warn!("Failed to read shared debug error grants: {:?}", e),
+            }
+            sleep(interval).await;
+        }
+    }
+}
@@ -0,0 +1,315 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+use teaclave_config::{SloConfig, SloTarget};
+use teaclave_proto::teaclave_frontend_service::RpcFamilyMetrics;
+use teaclave_rpc::Code;
+
+pub(crate) const RPC_FAMILIES: [&str; 4] = ["data", "function", "task", "admin"];
+
+pub(crate) fn rpc_family(api: &str) -> &'static str {
+    if api.contains("function") {
+        "function"
+    } else if api.ends_with("_file")
+        || api.ends_with("_output")
+        || api == "register_webhook_sink"
+        || api == "set_input_access_policy"
+        || api == "delete_data"
+    {
+        "data"
+    } else if api.ends_with("_task")
+        || api == "assign_data"
+        || api == "get_consent_records"
+        || api == "get_task_log"
+    {
+        "task"
+    } else {
+        "admin"
+    }
+}
+
+/// Errors telling that a backend service is degraded, as opposed to errors
+/// caused by the request itself such as `NotFound` or `PermissionDenied`.
+pub(crate) fn is_backend_error(code: Code) -> bool {
+    matches!(
+        code,
+        Code::Internal
+            | Code::Unavailable
+            | Code::Unknown
+            | Code::DeadlineExceeded
+            | Code::DataLoss
+    )
+}
+
+struct Sample {
+    at: Instant,
+    latency: Duration,
+    failed: bool,
+}
+
+#[derive(Default)]
+struct FamilyWindow {
+    samples: VecDeque<Sample>,
+    breached: bool,
+    breaches: u64,
+    rejected: u64,
+}
+
+/// A family entering or leaving the breached state.
+pub(crate) struct SloEvent {
+    pub breached: bool,
+    pub message: String,
+}
+
+/// Latency and error rate of forwarded RPCs per family over a sliding window.
+/// Breaches are reported once when a family starts violating its target and
+/// once when it recovers.
+pub(crate) struct SloTracker {
+    config: SloConfig,
+    windows: BTreeMap<&'static str, FamilyWindow>,
+}
+
+impl FamilyWindow {
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while let Some(sample) = self.samples.front() {
+            if now.duration_since(sample.at) < window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    fn errors(&self) -> usize {
+        self.samples.iter().filter(|s| s.failed).count()
+    }
+
+    fn error_rate(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.errors() as f64 / self.samples.len() as f64
+    }
+
+    fn p95_latency(&self) -> Duration {
+        let mut latencies: Vec<Duration> = self.samples.iter().map(|s| s.latency).collect();
+        if latencies.is_empty() {
+            return Duration::default();
+        }
+        latencies.sort();
+        let rank = (latencies.len() * 95 + 99) / 100;
+        latencies[rank - 1]
+    }
+
+    // None if there are too few requests to tell.
+    fn violations(&self, target: &SloTarget, min_requests: u32) -> Option<Vec<String>> {
+        if self.samples.len() < min_requests as usize {
+            return None;
+        }
+        let mut violations = Vec::new();
+        let p95 = self.p95_latency().as_millis() as u64;
+        if target.p95_latency_ms != 0 && p95 > target.p95_latency_ms {
+            violations.push(format!(
+                "p95 latency {}ms exceeds {}ms",
+                p95, target.p95_latency_ms
+            ));
+        }
+        let error_rate = self.error_rate();
+        if target.max_error_rate > 0.0 && error_rate > target.max_error_rate {
+            violations.push(format!(
+                "error rate {:.3} exceeds {:.3}",
+                error_rate, target.max_error_rate
+            ));
+        }
+        Some(violations)
+    }
+}
+
+impl SloTracker {
+    pub(crate) fn new(config: SloConfig) -> Self {
+        Self {
+            config,
+            windows: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn window_secs(&self) -> u64 {
+        self.config.window_secs
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.config.window_secs)
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        api: &str,
+        latency: Duration,
+        failed: bool,
+        now: Instant,
+    ) -> Option<SloEvent> {
+        let family = rpc_family(api);
+        let target = self.config.target_of(family);
+        let min_requests = self.config.min_requests;
+        let window = self.window();
+
+        let family_window = self.windows.entry(family).or_default();
+        family_window.samples.push_back(Sample {
+            at: now,
+            latency,
+            failed,
+        });
+        family_window.prune(now, window);
+
+        let violations = family_window.violations(&target, min_requests)?;
+        let breached = !violations.is_empty();
+        if breached == family_window.breached {
+            return None;
+        }
+        family_window.breached = breached;
+        let message = if breached {
+            family_window.breaches += 1;
+            format!("SLO breached: {}: {}", family, violations.join(", "))
+        } else {
+            format!("SLO recovered: {}", family)
+        };
+        Some(SloEvent { breached, message })
+    }
+
+    /// Count a request rejected by backpressure of the backend. It is not
+    /// sampled, as the rejection tells nothing about the latency.
+    pub(crate) fn record_rejection(&mut self, api: &str) -> u64 {
+        let family_window = self.windows.entry(rpc_family(api)).or_default();
+        family_window.rejected += 1;
+        family_window.rejected
+    }
+
+    pub(crate) fn metrics(&mut self, now: Instant) -> Vec<RpcFamilyMetrics> {
+        let window = self.window();
+        RPC_FAMILIES
+            .iter()
+            .map(|family| {
+                let target = self.config.target_of(family);
+                let family_window = self.windows.entry(*family).or_default();
+                family_window.prune(now, window);
+                RpcFamilyMetrics {
+                    family: family.to_string(),
+                    requests: family_window.samples.len() as u64,
+                    errors: family_window.errors() as u64,
+                    error_rate: family_window.error_rate(),
+                    p95_latency_ms: family_window.p95_latency().as_millis() as u64,
+                    target_p95_latency_ms: target.p95_latency_ms,
+                    target_error_rate: target.max_error_rate,
+                    breached: family_window.breached,
+                    breaches: family_window.breaches,
+                    rejected: family_window.rejected,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    fn config(p95_latency_ms: u64, max_error_rate: f64) -> SloConfig {
+        SloConfig {
+            window_secs: 60,
+            min_requests: 2,
+            default: SloTarget {
+                p95_latency_ms,
+                max_error_rate,
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn test_rpc_family() {
+        assert_eq!(rpc_family("register_input_file"), "data");
+        assert_eq!(rpc_family("register_fusion_output"), "data");
+        assert_eq!(rpc_family("register_input_from_output"), "data");
+        assert_eq!(rpc_family("register_webhook_sink"), "data");
+        assert_eq!(rpc_family("set_input_access_policy"), "data");
+        assert_eq!(rpc_family("delete_data"), "data");
+        assert_eq!(rpc_family("get_function_usage_stats"), "function");
+        assert_eq!(rpc_family("query_function_usage_records"), "function");
+        assert_eq!(rpc_family("upload_function_chunk"), "function");
+        assert_eq!(rpc_family("assign_data"), "task");
+        assert_eq!(rpc_family("invoke_task"), "task");
+        assert_eq!(rpc_family("query_audit_logs"), "admin");
+    }
+
+    pub fn test_latency_breach_and_recovery() {
+        let mut tracker = SloTracker::new(config(100, 0.0));
+        let now = Instant::now();
+        let slow = Duration::from_millis(500);
+        let fast = Duration::from_millis(10);
+
+        // Not checked below the minimal number of requests
+        assert!(tracker.record("get_task", slow, false, now).is_none());
+        let event = tracker.record("get_task", slow, false, now).unwrap();
+        assert!(event.breached);
+        assert!(event.message.contains("task"));
+        // Reported once per breach
+        assert!(tracker.record("get_task", slow, false, now).is_none());
+
+        let later = now + Duration::from_secs(60);
+        assert!(tracker.record("get_task", fast, false, later).is_none());
+        let event = tracker.record("get_task", fast, false, later).unwrap();
+        assert!(!event.breached);
+
+        let metrics = tracker.metrics(later);
+        let task = metrics.iter().find(|m| m.family == "task").unwrap();
+        assert_eq!(task.requests, 2);
+        assert_eq!(task.breaches, 1);
+        assert!(!task.breached);
+    }
+
+    pub fn test_error_rate_breach() {
+        let mut tracker = SloTracker::new(config(0, 0.5));
+        let now = Instant::now();
+        let latency = Duration::from_millis(10);
+
+        assert!(tracker.record("get_function", latency, true, now).is_none());
+        let event = tracker.record("get_function", latency, true, now).unwrap();
+        assert!(event.breached);
+        assert!(tracker
+            .record("register_input_file", latency, false, now)
+            .is_none());
+
+        let metrics = tracker.metrics(now);
+        let function = metrics.iter().find(|m| m.family == "function").unwrap();
+        assert_eq!(function.errors, 2);
+        assert!(function.breached);
+        let data = metrics.iter().find(|m| m.family == "data").unwrap();
+        assert!(!data.breached);
+    }
+
+    pub fn test_rejections() {
+        let mut tracker = SloTracker::new(config(100, 0.0));
+        assert_eq!(tracker.record_rejection("invoke_task"), 1);
+        assert_eq!(tracker.record_rejection("invoke_task"), 2);
+
+        let metrics = tracker.metrics(Instant::now());
+        let task = metrics.iter().find(|m| m.family == "task").unwrap();
+        assert_eq!(task.rejected, 2);
+        assert_eq!(task.requests, 0);
+        assert!(!task.breached);
+    }
+}
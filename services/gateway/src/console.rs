@@ -0,0 +1,263 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Endpoints for web consoles.
+//!
+//! `POST /v1/session` with `{"id": ..., "password": ...}` logs in and keeps
+//! the credential in `HttpOnly` cookies, which are then accepted in place of
+//! the `id` and `token` headers; `DELETE /v1/session` drops them.
+//!
+//! `GET /v1/console/<view>` aggregates the RPCs needed by a page of the
+//! console, with the parameters in the query string:
+//! - `dashboard`: platform stats, RPC metrics and the number of users;
+//! - `users?offset=&limit=`;
+//! - `tasks?creator=&offset=&limit=`;
+//! - `functions?name=&tag=&owner=&offset=&limit=`;
+//! - `audit_logs?query=&offset=&limit=`.
+//!
+//! `tasks` and `functions` also take `cursor=` instead of `offset=`, which
+//! starts at the first page if empty, and the `after` of `next_cursor` in the
+//! response continues with the next page.
+//!
+//! The views are authorized by the services behind, and all of them except
+//! `functions` require the platform admin role.
+
+use crate::rest::{credential, from_json, json_response, read_body, to_json};
+use crate::Upstreams;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Method, Request, Response};
+use std::collections::HashMap;
+use teaclave_proto::teaclave_authentication_service::{
+    ListUsersRequest, TeaclaveAuthenticationApiClient, UserLoginRequest,
+};
+use teaclave_proto::teaclave_frontend_service::{
+    GetMetricsRequest, GetPlatformStatsRequest, ListTasksRequest, QueryAuditLogsRequest,
+    SearchFunctionsRequest, TeaclaveFrontendClient,
+};
+use teaclave_rpc::{CredentialService, InterceptedService, Status, UserCredential};
+
+pub(crate) const SESSION_PATH: &str = "/v1/session";
+pub(crate) const CONSOLE_PATH_PREFIX: &str = "/v1/console/";
+
+const ID_COOKIE: &str = "teaclave_id";
+const TOKEN_COOKIE: &str = "teaclave_token";
+// Scripts cannot read the cookies, and browsers do not send them along with
+// cross-site requests.
+const COOKIE_ATTRIBUTES: &str = "HttpOnly; Secure; SameSite=Strict; Path=/v1";
+const DEFAULT_PAGE_SIZE: u64 = 50;
+
+/// The credential kept in the session cookies, if any.
+pub(crate) fn session_credential(headers: &HeaderMap) -> Option<UserCredential> {
+    let mut cookies = HashMap::new();
+    for value in headers.get_all(header::COOKIE) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        for cookie in value.split(';') {
+            if let Some((name, value)) = cookie.trim().split_once('=') {
+                cookies.insert(name, value);
+            }
+        }
+    }
+    let id = cookies.get(ID_COOKIE)?;
+    let token = cookies.get(TOKEN_COOKIE)?;
+    Some(UserCredential::new(*id, *token))
+}
+
+// Characters allowed in a cookie value by RFC 6265
+fn is_cookie_value(s: &str) -> bool {
+    s.bytes()
+        .all(|b| b.is_ascii_graphic() && !matches!(b, b'"' | b',' | b';' | b'\\'))
+}
+
+fn set_cookies(response: &mut Response<Body>, cookies: &[(&str, &str)], attributes: &str) {
+    for (name, value) in cookies {
+        let cookie = format!("{}={}; {}", name, value, attributes);
+        // Values are checked with is_cookie_value.
+        let cookie = HeaderValue::from_str(&cookie).unwrap();
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+}
+
+async fn login(upstreams: &Upstreams, request: Request<Body>) -> Result<(String, String), Status> {
+    let body = read_body(request).await?;
+    let request: UserLoginRequest = from_json(&body)?;
+    if !is_cookie_value(&request.id) {
+        return Err(Status::invalid_argument("Invalid user id"));
+    }
+    let id = request.id.clone();
+    let mut client =
+        TeaclaveAuthenticationApiClient::new_with_builtin_config(upstreams.authentication.clone());
+    let token = client.user_login(request).await?.into_inner().token;
+    if !is_cookie_value(&token) {
+        return Err(Status::internal("Invalid token"));
+    }
+    Ok((id, token))
+}
+
+pub(crate) async fn session(upstreams: &Upstreams, request: Request<Body>) -> Response<Body> {
+    match *request.method() {
+        Method::POST => match login(upstreams, request).await {
+            Ok((id, token)) => {
+                let mut response = json_response(Ok(serde_json::json!({ "id": id })));
+                let cookies = [(ID_COOKIE, id.as_str()), (TOKEN_COOKIE, token.as_str())];
+                set_cookies(&mut response, &cookies, COOKIE_ATTRIBUTES);
+                response
+            }
+            Err(status) => json_response(Err(status)),
+        },
+        Method::DELETE => {
+            let mut response = json_response(Ok(serde_json::json!({})));
+            let attributes = format!("{}; Max-Age=0", COOKIE_ATTRIBUTES);
+            set_cookies(
+                &mut response,
+                &[(ID_COOKIE, ""), (TOKEN_COOKIE, "")],
+                &attributes,
+            );
+            response
+        }
+        _ => json_response(Err(Status::unimplemented(
+            "Only POST and DELETE are supported",
+        ))),
+    }
+}
+
+struct Params(HashMap<String, String>);
+
+impl Params {
+    fn new(query: Option<&str>) -> Self {
+        let query = query.unwrap_or_default().as_bytes();
+        Self(url::form_urlencoded::parse(query).into_owned().collect())
+    }
+
+    fn string(&self, name: &str) -> String {
+        self.0.get(name).cloned().unwrap_or_default()
+    }
+
+    fn number(&self, name: &str, default: u64) -> Result<u64, Status> {
+        match self.0.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| Status::invalid_argument(format!("Invalid {}: {}", name, value))),
+            None => Ok(default),
+        }
+    }
+
+    fn cursor(&self) -> Option<&String> {
+        self.0.get("cursor")
+    }
+
+    fn page(&self) -> Result<(usize, usize), Status> {
+        let offset = self.number("offset", 0)?;
+        let limit = self.number("limit", DEFAULT_PAGE_SIZE)?;
+        Ok((offset as usize, limit as usize))
+    }
+}
+
+fn frontend_client(
+    upstreams: &Upstreams,
+    credential: &UserCredential,
+) -> TeaclaveFrontendClient<CredentialService> {
+    let service = InterceptedService::new(upstreams.frontend.clone(), credential.clone());
+    TeaclaveFrontendClient::new_with_builtin_config(service)
+}
+
+fn authentication_client(
+    upstreams: &Upstreams,
+    credential: &UserCredential,
+) -> TeaclaveAuthenticationApiClient<CredentialService> {
+    let service = InterceptedService::new(upstreams.authentication.clone(), credential.clone());
+    TeaclaveAuthenticationApiClient::new_with_builtin_config(service)
+}
+
+async fn dashboard(
+    upstreams: &Upstreams,
+    credential: &UserCredential,
+) -> Result<serde_json::Value, Status> {
+    let mut stats_client = frontend_client(upstreams, credential);
+    let mut metrics_client = stats_client.clone();
+    let mut users_client = authentication_client(upstreams, credential);
+    let (stats, metrics, users) = tokio::join!(
+        stats_client.get_platform_stats(GetPlatformStatsRequest::default()),
+        metrics_client.get_metrics(GetMetricsRequest::default()),
+        users_client.list_users(ListUsersRequest::new(&credential.id).page(0, 1)),
+    );
+    Ok(serde_json::json!({
+        "platform": to_json(&stats?.into_inner())?,
+        "metrics": to_json(&metrics?.into_inner())?,
+        "users": users?.into_inner().total,
+    }))
+}
+
+pub(crate) async fn call(
+    upstreams: &Upstreams,
+    request: Request<Body>,
+) -> Result<serde_json::Value, Status> {
+    if request.method() != Method::GET {
+        return Err(Status::unimplemented("Only GET is supported"));
+    }
+    let credential = credential(request.headers());
+    let params = Params::new(request.uri().query());
+    let path = request.uri().path();
+    let view = path.strip_prefix(CONSOLE_PATH_PREFIX).unwrap_or_default();
+    match view {
+        "dashboard" => dashboard(upstreams, &credential).await,
+        "users" => {
+            let (offset, limit) = params.page()?;
+            let request = ListUsersRequest::new(&credential.id).page(offset, limit);
+            let mut client = authentication_client(upstreams, &credential);
+            to_json(&client.list_users(request).await?.into_inner())
+        }
+        "tasks" => {
+            let (offset, limit) = params.page()?;
+            let request = ListTasksRequest::new().creator(params.string("creator"));
+            let request = match params.cursor() {
+                Some(cursor) => request.cursor(cursor, limit),
+                None => request.page(offset, limit),
+            };
+            let mut client = frontend_client(upstreams, &credential);
+            to_json(&client.list_tasks(request).await?.into_inner())
+        }
+        "functions" => {
+            let (offset, limit) = params.page()?;
+            let request = SearchFunctionsRequest::new()
+                .name(params.string("name"))
+                .tag(params.string("tag"))
+                .owner(params.string("owner"));
+            let request = match params.cursor() {
+                Some(cursor) => request.cursor(cursor, limit),
+                None => request.page(offset, limit),
+            };
+            let mut client = frontend_client(upstreams, &credential);
+            to_json(&client.search_functions(request).await?.into_inner())
+        }
+        "audit_logs" => {
+            let (offset, limit) = params.page()?;
+            // Searching for no logs is not supported by the index.
+            let limit = if limit == 0 {
+                DEFAULT_PAGE_SIZE as usize
+            } else {
+                limit
+            };
+            let request = QueryAuditLogsRequest::new(params.string("query"), limit).offset(offset);
+            let mut client = frontend_client(upstreams, &credential);
+            to_json(&client.query_audit_logs(request).await?.into_inner())
+        }
+        _ => Err(Status::not_found(format!("Unknown path {}", path))),
+    }
+}
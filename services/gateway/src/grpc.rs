@@ -0,0 +1,63 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Standard gRPC: requests are forwarded as they are, including the `id` and
+//! `token` metadata, since the messages are the same on both sides.
+
+use crate::Upstreams;
+use hyper::{header, Body, Request, Response};
+use tonic::Status;
+use tower::ServiceExt;
+
+// Only the API services are exposed, e.g., not the internal authentication
+// service.
+const FRONTEND_PATH_PREFIX: &str = "/teaclave_frontend_service_proto.TeaclaveFrontend/";
+const AUTHENTICATION_PATH_PREFIX: &str =
+    "/teaclave_authentication_service_proto.TeaclaveAuthenticationApi/";
+
+pub(crate) fn is_grpc(request: &Request<Body>) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.starts_with("application/grpc"))
+}
+
+fn status_response(status: Status) -> Response<Body> {
+    status.to_http().map(|_| Body::empty())
+}
+
+pub(crate) async fn forward(upstreams: &Upstreams, request: Request<Body>) -> Response<Body> {
+    let path = request.uri().path();
+    let channel = if path.starts_with(FRONTEND_PATH_PREFIX) {
+        upstreams.frontend.clone()
+    } else if path.starts_with(AUTHENTICATION_PATH_PREFIX) {
+        upstreams.authentication.clone()
+    } else {
+        return status_response(Status::unimplemented(format!("Unknown method {}", path)));
+    };
+
+    // The scheme and authority are replaced with the ones of the channel.
+    let request = request.map(tonic::body::boxed);
+    match channel.oneshot(request).await {
+        Ok(response) => response,
+        Err(e) => status_response(Status::unavailable(format!(
+            "Cannot reach the service: {}",
+            e
+        ))),
+    }
+}
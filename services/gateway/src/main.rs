@@ -0,0 +1,162 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! An untrusted gateway in front of the API endpoints for clients which
+//! cannot speak the attested TLS protocol, e.g., web clients and SDKs on
+//! non-SGX platforms. It serves standard gRPC and REST+JSON in plaintext, and
+//! forwards requests to the frontend and authentication services over
+//! attested TLS. It is supposed to be deployed behind a TLS-terminating
+//! reverse proxy.
+
+use anyhow::{anyhow, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use log::info;
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use teaclave_attestation::verifier;
+use teaclave_rpc::config::SgxTrustedTlsClientConfig;
+use teaclave_rpc::transport::{Channel, Uri};
+use teaclave_types::EnclaveInfo;
+
+mod console;
+mod grpc;
+mod rest;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "teaclave_gateway",
+    about = "Teaclave gateway for standard gRPC and REST+JSON clients."
+)]
+struct Opt {
+    /// Address to serve gRPC and REST+JSON requests
+    #[structopt(short, long, default_value = "127.0.0.1:8080")]
+    listen_address: SocketAddr,
+
+    /// Address of the authentication service
+    #[structopt(long = "authentication-url", default_value = "https://localhost:7776")]
+    authentication_url: String,
+
+    /// Address of the frontend service
+    #[structopt(long = "frontend-url", default_value = "https://localhost:7777")]
+    frontend_url: String,
+
+    /// Path of enclave info
+    #[structopt(short, long = "enclave-info")]
+    enclave_info: PathBuf,
+
+    /// CA cert of attestation service for verifying the attestation report
+    #[structopt(short = "c", long)]
+    as_ca_cert: PathBuf,
+}
+
+/// Attested channels to the API endpoints, shared by all the connections.
+#[derive(Clone)]
+pub(crate) struct Upstreams {
+    pub(crate) frontend: Channel,
+    pub(crate) authentication: Channel,
+}
+
+impl Upstreams {
+    fn new(opt: &Opt) -> Result<Self> {
+        let enclave_info = EnclaveInfo::from_file(&opt.enclave_info)?;
+        let content = fs::read(&opt.as_ca_cert)?;
+        let as_root_ca_cert = pem::parse(content)?.contents;
+
+        let frontend = connect(
+            &opt.frontend_url,
+            &enclave_info,
+            "teaclave_frontend_service",
+            &as_root_ca_cert,
+        )?;
+        let authentication = connect(
+            &opt.authentication_url,
+            &enclave_info,
+            "teaclave_authentication_service",
+            &as_root_ca_cert,
+        )?;
+
+        Ok(Self {
+            frontend,
+            authentication,
+        })
+    }
+}
+
+// The channel connects on the first request, so that the gateway can be
+// started before the services.
+fn connect(
+    url: &str,
+    enclave_info: &EnclaveInfo,
+    service_name: &str,
+    as_root_ca_cert: &[u8],
+) -> Result<Channel> {
+    let enclave_attr = enclave_info
+        .get_enclave_attr(service_name)
+        .ok_or_else(|| anyhow!("Cannot find enclave info of {}", service_name))?;
+    let tls_config = SgxTrustedTlsClientConfig::new()
+        .attestation_report_verifier(
+            vec![enclave_attr],
+            as_root_ca_cert,
+            verifier::universal_quote_verifier,
+            verifier::VerificationPolicy::default(),
+        )
+        .into();
+    let channel = Channel::builder(url.parse::<Uri>()?)
+        .tls_config(tls_config)?
+        .connect_lazy();
+    Ok(channel)
+}
+
+async fn handle(
+    upstreams: Upstreams,
+    request: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    let response = if grpc::is_grpc(&request) {
+        grpc::forward(&upstreams, request).await
+    } else {
+        rest::handle(&upstreams, request).await
+    };
+    Ok(response)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let opt = Opt::from_args();
+    let upstreams = Upstreams::new(&opt)?;
+
+    // Both HTTP/1.1 and HTTP/2 with prior knowledge are served on the same
+    // address, the latter for gRPC.
+    let make_service = make_service_fn(move |_| {
+        let upstreams = upstreams.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle(upstreams.clone(), request)
+            }))
+        }
+    });
+    info!("Gateway listening on {}", opt.listen_address);
+    Server::bind(&opt.listen_address)
+        .serve(make_service)
+        .await?;
+
+    Ok(())
+}
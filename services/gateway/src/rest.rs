@@ -0,0 +1,263 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! REST+JSON: `POST /v1/frontend/<method>` and
+//! `POST /v1/authentication/<method>`, where `<method>` is the RPC name in
+//! snake case, e.g., `/v1/frontend/get_task`. The body is the request message
+//! in JSON with the field names in the proto files, and so is the response.
+//! Credentials are taken from the `id` and `token` headers, like the metadata
+//! of gRPC requests, or else from the session cookies set by the console
+//! endpoints, in which case the body must be sent as `application/json`.
+
+use crate::console;
+use crate::Upstreams;
+use http_body::Limited;
+use hyper::{header, Body, HeaderMap, Method, Request, Response, StatusCode};
+use teaclave_config::build::GRPC_CONFIG;
+use teaclave_proto::teaclave_authentication_service::TeaclaveAuthenticationApiClient;
+use teaclave_proto::teaclave_authentication_service_proto as authentication;
+use teaclave_proto::teaclave_frontend_service::TeaclaveFrontendClient;
+use teaclave_proto::teaclave_frontend_service_proto as frontend;
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::{Code, InterceptedService, Status, UserCredential};
+
+const PATH_PREFIX: &str = "/v1/";
+
+// Each method is called with the request type deserialized from JSON.
+macro_rules! rest_api {
+    ($name:ident, $client:ident, $proto:ident, { $($method:ident: $request:ident,)* }) => {
+        async fn $name(
+            channel: Channel,
+            credential: UserCredential,
+            method: &str,
+            body: &[u8],
+        ) -> Result<serde_json::Value, Status> {
+            let service = InterceptedService::new(channel, credential);
+            let mut client = $client::new_with_builtin_config(service);
+            match method {
+                $(stringify!($method) => {
+                    let request: $proto::$request = from_json(body)?;
+                    let response = client.$method(request).await?.into_inner();
+                    to_json(&response)
+                })*
+                _ => Err(Status::not_found(format!("Unknown method {}", method))),
+            }
+        }
+    };
+}
+
+rest_api!(call_frontend, TeaclaveFrontendClient, frontend, {
+    register_input_file: RegisterInputFileRequest,
+    register_output_file: RegisterOutputFileRequest,
+    update_input_file: UpdateInputFileRequest,
+    update_output_file: UpdateOutputFileRequest,
+    register_fusion_output: RegisterFusionOutputRequest,
+    register_input_from_output: RegisterInputFromOutputRequest,
+    get_output_file: GetOutputFileRequest,
+    get_input_file: GetInputFileRequest,
+    register_function: RegisterFunctionRequest,
+    begin_function_upload: BeginFunctionUploadRequest,
+    upload_function_chunk: UploadFunctionChunkRequest,
+    commit_function: CommitFunctionRequest,
+    get_function: GetFunctionRequest,
+    get_function_usage_stats: GetFunctionUsageStatsRequest,
+    query_function_usage_records: QueryFunctionUsageRecordsRequest,
+    update_function: UpdateFunctionRequest,
+    list_functions: ListFunctionsRequest,
+    search_functions: SearchFunctionsRequest,
+    delete_function: DeleteFunctionRequest,
+    disable_function: DisableFunctionRequest,
+    create_task: CreateTaskRequest,
+    get_task: GetTaskRequest,
+    list_tasks: ListTasksRequest,
+    assign_data: AssignDataRequest,
+    approve_task: ApproveTaskRequest,
+    reject_task: RejectTaskRequest,
+    invoke_task: InvokeTaskRequest,
+    cancel_task: CancelTaskRequest,
+    get_consent_records: GetConsentRecordsRequest,
+    get_task_log: GetTaskLogRequest,
+    register_webhook_sink: RegisterWebhookSinkRequest,
+    query_audit_logs: QueryAuditLogsRequest,
+    export_audit_logs: ExportAuditLogsRequest,
+    rebuild_audit_index: RebuildAuditIndexRequest,
+    get_audit_index_status: GetAuditIndexStatusRequest,
+    export_metadata: ExportMetadataRequest,
+    set_user_quota: SetUserQuotaRequest,
+    get_user_quota: GetUserQuotaRequest,
+    grant_debug_errors: GrantDebugErrorsRequest,
+    get_metrics: GetMetricsRequest,
+    get_api_version: GetApiVersionRequest,
+    get_platform_stats: GetPlatformStatsRequest,
+    manage_policy: ManagePolicyRequest,
+    set_user_attributes: SetUserAttributesRequest,
+    get_user_attributes: GetUserAttributesRequest,
+    set_data_attributes: SetDataAttributesRequest,
+    get_data_attributes: GetDataAttributesRequest,
+    set_input_access_policy: SetInputAccessPolicyRequest,
+    delete_data: DeleteDataRequest,
+    query_data_lineage: QueryDataLineageRequest,
+    set_notification_preferences: SetNotificationPreferencesRequest,
+    decommission_storage: DecommissionStorageRequest,
+    get_storage_decommission_status: GetStorageDecommissionStatusRequest,
+    create_storage_snapshot: CreateStorageSnapshotRequest,
+    restore_storage_snapshot: RestoreStorageSnapshotRequest,
+    promote_storage_replica: PromoteStorageReplicaRequest,
+    declare_maintenance_window: DeclareMaintenanceWindowRequest,
+    cancel_maintenance_window: CancelMaintenanceWindowRequest,
+    get_service_info: GetServiceInfoRequest,
+});
+
+rest_api!(call_authentication, TeaclaveAuthenticationApiClient, authentication, {
+    user_register: UserRegisterRequest,
+    user_update: UserUpdateRequest,
+    user_login: UserLoginRequest,
+    user_change_password: UserChangePasswordRequest,
+    reset_user_password: ResetUserPasswordRequest,
+    change_password: ChangePasswordRequest,
+    force_password_reset: ForcePasswordResetRequest,
+    delete_user: DeleteUserRequest,
+    list_users: ListUsersRequest,
+    generate_totp_secret: GenerateTotpSecretRequest,
+    verify_totp: VerifyTotpRequest,
+});
+
+pub(crate) fn from_json<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, Status> {
+    serde_json::from_slice(body)
+        .map_err(|e| Status::invalid_argument(format!("Invalid request: {}", e)))
+}
+
+// RPCs returning nothing are answered with an empty object.
+pub(crate) fn to_json<T: serde::Serialize>(response: &T) -> Result<serde_json::Value, Status> {
+    match serde_json::to_value(response) {
+        Ok(serde_json::Value::Null) => Ok(serde_json::json!({})),
+        Ok(value) => Ok(value),
+        Err(e) => Err(Status::internal(format!("Invalid response: {}", e))),
+    }
+}
+
+pub(crate) fn credential(headers: &HeaderMap) -> UserCredential {
+    if !headers.contains_key("token") {
+        if let Some(credential) = console::session_credential(headers) {
+            return credential;
+        }
+    }
+    let get = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    UserCredential::new(get("id"), get("token"))
+}
+
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+// Cross-site forms can post cookies along with a body of any other type.
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.starts_with("application/json"))
+}
+
+pub(crate) async fn read_body(request: Request<Body>) -> Result<hyper::body::Bytes, Status> {
+    let body = Limited::new(request.into_body(), GRPC_CONFIG.max_encoding_message_size);
+    hyper::body::to_bytes(body)
+        .await
+        .map_err(|e| Status::invalid_argument(format!("Cannot read the body: {}", e)))
+}
+
+async fn call(upstreams: &Upstreams, request: Request<Body>) -> Result<serde_json::Value, Status> {
+    if request.method() != Method::POST {
+        return Err(Status::unimplemented("Only POST is supported"));
+    }
+    let headers = request.headers();
+    if !headers.contains_key("token")
+        && console::session_credential(headers).is_some()
+        && !is_json(headers)
+    {
+        return Err(Status::invalid_argument(
+            "Content-Type must be application/json with session cookies",
+        ));
+    }
+    let path = request.uri().path().to_string();
+    let credential = credential(headers);
+    let body = read_body(request).await?;
+
+    let route = path
+        .strip_prefix(PATH_PREFIX)
+        .and_then(|path| path.split_once('/'));
+    match route {
+        Some(("frontend", method)) => {
+            call_frontend(upstreams.frontend.clone(), credential, method, &body).await
+        }
+        Some(("authentication", method)) => {
+            call_authentication(upstreams.authentication.clone(), credential, method, &body).await
+        }
+        _ => Err(Status::not_found(format!("Unknown path {}", path))),
+    }
+}
+
+pub(crate) async fn handle(upstreams: &Upstreams, request: Request<Body>) -> Response<Body> {
+    let path = request.uri().path();
+    if path == console::SESSION_PATH {
+        console::session(upstreams, request).await
+    } else if path.starts_with(console::CONSOLE_PATH_PREFIX) {
+        json_response(console::call(upstreams, request).await)
+    } else {
+        json_response(call(upstreams, request).await)
+    }
+}
+
+pub(crate) fn json_response(result: Result<serde_json::Value, Status>) -> Response<Body> {
+    let (status, value) = match result {
+        Ok(value) => (StatusCode::OK, value),
+        Err(status) => (
+            http_status(status.code()),
+            serde_json::json!({
+                "code": status.code() as i32,
+                "message": status.message(),
+            }),
+        ),
+    };
+    // Serializing a JSON value never fails.
+    let body = serde_json::to_vec(&value).unwrap();
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
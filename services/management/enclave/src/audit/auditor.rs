@@ -15,34 +15,51 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use super::entry_store::EntryStore;
+use super::rebuild::RebuildState;
 use super::*;
 
 use teaclave_proto::teaclave_storage_service::TeaclaveStorageClient;
 use teaclave_rpc::transport::Channel;
 use teaclave_types::{Entry, EntryBuilder};
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 
 use anyhow::{anyhow, Result};
 use tantivy::{
-    collector::TopDocs, query::QueryParser, schema::*, DateTime, Index, IndexReader, IndexSettings,
-    IndexSortByField, IndexWriter, Order, ReloadPolicy,
+    collector::{Count, TopDocs},
+    query::QueryParser,
+    schema::*,
+    DateTime, Index, IndexReader, IndexSettings, IndexSortByField, IndexWriter, Order,
+    ReloadPolicy,
 };
 
-#[derive(Clone)]
-pub struct Auditor {
-    index: Arc<Mutex<Index>>,
-    reader: Arc<Mutex<IndexReader>>,
-    writer: Arc<Mutex<IndexWriter>>,
+// 8 is the max thread number of tantivy writer
+const WRITER_MEMORY_BYTES: usize = 8 * 3_000_000;
+
+/// The prefix of the files of the index of `generation`. The first index is
+/// kept where it has always been.
+pub(crate) fn index_prefix(generation: u64) -> String {
+    match generation {
+        0 => "tantivy/".to_string(),
+        _ => format!("tantivy-{}/", generation),
+    }
 }
 
-impl Auditor {
-    pub fn try_new(
+pub(crate) struct AuditIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+}
+
+impl AuditIndex {
+    pub(crate) fn open(
         storage: Arc<tokio::sync::Mutex<TeaclaveStorageClient<Channel>>>,
+        generation: u64,
     ) -> Result<Self> {
-        let directory = db_directory::DbDirectory::new(storage);
+        let directory = db_directory::DbDirectory::new(storage, &index_prefix(generation));
 
-        let schema = Self::log_schema();
+        let schema = Auditor::log_schema();
 
         let settings = IndexSettings {
             sort_by_field: Some(IndexSortByField {
@@ -60,13 +77,7 @@ impl Auditor {
             .reader_builder()
             .reload_policy(ReloadPolicy::OnCommit)
             .try_into()?;
-
-        // 8 is the max thread number of tantivy writer
-        let writer = index.writer(8 * 3_000_000)?;
-
-        let index = Arc::new(Mutex::new(index));
-        let reader = Arc::new(Mutex::new(reader));
-        let writer = Arc::new(Mutex::new(writer));
+        let writer = Mutex::new(index.writer(WRITER_MEMORY_BYTES)?);
 
         Ok(Self {
             index,
@@ -75,11 +86,11 @@ impl Auditor {
         })
     }
 
-    pub fn add_logs(&self, logs: Vec<Entry>) -> Result<()> {
+    pub(crate) fn add(&self, logs: impl IntoIterator<Item = Entry>) -> Result<()> {
         let mut writer = self.writer.lock().unwrap();
 
         for log in logs {
-            let document = Self::convert_to_doc(log);
+            let document = Auditor::convert_to_doc(log);
             writer.add_document(document)?;
         }
 
@@ -87,27 +98,103 @@ impl Auditor {
 
         Ok(())
     }
+}
+
+/// The audit logs, saved as they are and indexed for queries. If the index is
+/// corrupted, logs are still saved, and queries fail until it is rebuilt from
+/// the saved logs. While it is rebuilt, queries are served by the current
+/// index, if any, without the logs saved in the meantime, which are added to
+/// the new index before it is switched to.
+#[derive(Clone)]
+pub struct Auditor {
+    pub(super) storage: Arc<tokio::sync::Mutex<TeaclaveStorageClient<Channel>>>,
+    pub(super) entries: EntryStore,
+    // None if the index cannot be opened
+    pub(super) index: Arc<RwLock<Option<Arc<AuditIndex>>>>,
+    // Also serializes the saving of logs with the rebuild
+    pub(super) rebuild: Arc<Mutex<RebuildState>>,
+}
+
+impl Auditor {
+    pub fn try_new(
+        storage: Arc<tokio::sync::Mutex<TeaclaveStorageClient<Channel>>>,
+    ) -> Result<Self> {
+        let entries = EntryStore::new(storage.clone());
+        let generation = entries.index_generation()?;
+        let index = match AuditIndex::open(storage.clone(), generation) {
+            Ok(index) => Some(Arc::new(index)),
+            Err(e) => {
+                log::error!("Cannot open the audit index, rebuild it: {:?}", e);
+                None
+            }
+        };
+
+        Ok(Self {
+            storage,
+            entries,
+            index: Arc::new(RwLock::new(index)),
+            rebuild: Arc::default(),
+        })
+    }
+
+    /// Whether audit logs can be queried
+    pub fn is_available(&self) -> bool {
+        self.index.read().unwrap().is_some()
+    }
+
+    pub fn add_logs(&self, logs: Vec<Entry>) -> Result<()> {
+        let logs: Vec<(String, Entry)> = logs
+            .into_iter()
+            .map(|log| (EntryStore::entry_key(&log), log))
+            .collect();
+
+        let mut rebuild = self.rebuild.lock().unwrap();
+        self.entries.save(&logs)?;
+        if let Some(pending) = rebuild.pending.as_mut() {
+            pending.extend(logs);
+            return Ok(());
+        }
+        match self.index.read().unwrap().as_ref() {
+            Some(index) => index.add(logs.into_iter().map(|(_, log)| log)),
+            // Indexed once the index is rebuilt
+            None => Ok(()),
+        }
+    }
 
     /// query: the query for tantivy
     /// limit: maximum number of the returned logs
     pub fn query_logs(&self, query: &str, limit: usize) -> Result<Vec<Entry>> {
-        let reader = self.reader.lock().unwrap();
-        let searcher = reader.searcher();
-        drop(reader);
+        Ok(self.search_logs(query, 0, limit)?.0)
+    }
+
+    /// Like `query_logs`, but skip the latest `offset` logs, and also return
+    /// the number of all the matching logs for pagination.
+    pub fn search_logs(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Entry>, usize)> {
+        let index = self
+            .index
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("audit index is unavailable until it is rebuilt"))?;
+        let searcher = index.reader.searcher();
 
-        let index = self.index.lock().unwrap();
         let schema = Self::log_schema();
 
         let message = schema.get_field("message").unwrap();
         let date = schema.get_field("date").unwrap();
 
-        let query_parser = QueryParser::for_index(&index, vec![message]);
+        let query_parser = QueryParser::for_index(&index.index, vec![message]);
         let query = query_parser.parse_query(query)?;
 
-        let top_docs = searcher.search(
-            &query,
-            &TopDocs::with_limit(limit).order_by_fast_field::<DateTime>(date),
-        )?;
+        let top_docs = TopDocs::with_limit(limit)
+            .and_offset(offset)
+            .order_by_fast_field::<DateTime>(date);
+        let (top_docs, total) = searcher.search(&query, &(top_docs, Count))?;
 
         let mut entries = Vec::new();
 
@@ -117,7 +204,7 @@ impl Auditor {
             entries.push(entry);
         }
 
-        Ok(entries)
+        Ok((entries, total))
     }
 
     pub(crate) fn try_convert_to_entry(doc: Document) -> Result<Entry> {
@@ -127,6 +214,7 @@ impl Auditor {
         let user = schema.get_field("user").unwrap();
         let message = schema.get_field("message").unwrap();
         let result = schema.get_field("result").unwrap();
+        let trace_id = schema.get_field("trace_id").unwrap();
 
         let date = doc
             .get_first(date)
@@ -148,6 +236,11 @@ impl Auditor {
             .get_first(result)
             .and_then(|r| r.as_bool())
             .ok_or_else(|| anyhow!("failed to get result"))?;
+        // Logs saved before trace ids were introduced have none
+        let trace_id = doc
+            .get_first(trace_id)
+            .and_then(|t| t.as_text())
+            .unwrap_or_default();
 
         let microsecond = date.into_timestamp_micros();
 
@@ -157,6 +250,7 @@ impl Auditor {
             .user(user.to_owned())
             .message(message.to_owned())
             .result(result)
+            .trace_id(trace_id.to_owned())
             .build();
 
         Ok(entry)
@@ -169,6 +263,7 @@ impl Auditor {
         let user = schema.get_field("user").unwrap();
         let message = schema.get_field("message").unwrap();
         let result = schema.get_field("result").unwrap();
+        let trace_id = schema.get_field("trace_id").unwrap();
 
         let date_v = DateTime::from_timestamp_micros(entry.datetime().timestamp_micros());
 
@@ -178,6 +273,7 @@ impl Auditor {
         doc.add_text(user, &entry.user());
         doc.add_text(message, &entry.message());
         doc.add_bool(result, entry.result());
+        doc.add_text(trace_id, &entry.trace_id());
 
         doc
     }
@@ -189,6 +285,9 @@ impl Auditor {
         builder.add_text_field("user", TEXT | STORED);
         builder.add_text_field("message", TEXT | STORED);
         builder.add_bool_field("result", INDEXED | STORED);
+        // Not tokenized so that logs of a request can be queried by
+        // `trace_id:<id>`
+        builder.add_text_field("trace_id", STRING | STORED);
 
         builder.build()
     }
@@ -19,13 +19,14 @@
 // https://github.com/quickwit-oss/tantivy/blob/main/src/directory/ram_directory.rs
 
 use teaclave_proto::teaclave_storage_service::{
-    DeleteRequest, GetRequest, PutRequest, TeaclaveStorageClient,
+    DeleteRequest, GetRequest, ListEntriesRequest, PutRequest, TeaclaveStorageClient,
 };
 use teaclave_rpc::transport::Channel;
 
+use std::collections::HashMap;
 use std::io::{self, BufWriter, Cursor, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, LazyLock};
+use std::sync::{Arc, LazyLock, RwLock};
 use std::{fmt, result};
 
 use tantivy::directory::error::{DeleteError, OpenReadError, OpenWriteError};
@@ -37,9 +38,10 @@ use tokio::runtime::{Builder, Runtime};
 use tokio::sync::Mutex;
 
 pub static META_FILEPATH: LazyLock<&'static Path> = LazyLock::new(|| Path::new("meta.json"));
-pub static DB_PREFIX: LazyLock<String> = LazyLock::new(|| String::from("tantivy/"));
 static INDEX_WRITER_LOCK: LazyLock<&'static Path> =
     LazyLock::new(|| Path::new(".tantivy-writer.lock"));
+// Bytes of the files listed in a page, well below the message size limit
+const LIST_PAGE_BYTES: u64 = 1024 * 1024;
 
 struct Cache {
     path: PathBuf,
@@ -96,11 +98,21 @@ impl TerminatingWrite for Cache {
 }
 
 /// A Directory storing everything in the storage service.
+///
+/// All the files are listed from the storage service in a few batched calls
+/// once the index is opened, and kept in memory, so that reloading the index
+/// reads no segment files from the storage service. The directory is the only
+/// writer of the files, and keeps the listing up to date as it writes. If the
+/// listing fails, the files are read one by one instead until it is listed
+/// again on the next reload. The files are stored under `prefix`, so that an
+/// index can be rebuilt next to the current one.
 #[derive(Clone)]
 pub struct DbDirectory {
     db: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+    prefix: String,
     watch_router: Arc<WatchCallbackList>,
     rt: Arc<Runtime>,
+    files: Arc<RwLock<Option<HashMap<PathBuf, FileSlice>>>>,
 }
 
 impl fmt::Debug for DbDirectory {
@@ -110,29 +122,76 @@ impl fmt::Debug for DbDirectory {
 }
 
 impl DbDirectory {
-    pub fn new(db: Arc<Mutex<TeaclaveStorageClient<Channel>>>) -> Self {
+    pub fn new(db: Arc<Mutex<TeaclaveStorageClient<Channel>>>, prefix: &str) -> Self {
         let rt = Arc::new(Builder::new_current_thread().enable_all().build().unwrap());
         let dir = Self {
             db,
+            prefix: prefix.to_owned(),
             watch_router: Arc::default(),
             rt,
+            files: Arc::default(),
         };
 
         // remove the lockfile if it exists
         let _ = dir.delete(&INDEX_WRITER_LOCK);
+        dir.load_files();
 
         dir
     }
 
+    /// List all the files with their content, page by page.
+    fn load_files(&self) {
+        let mut files = HashMap::new();
+        let mut start_after = Vec::new();
+        loop {
+            let request = ListEntriesRequest::new(self.prefix.as_bytes())
+                .page(start_after, 0)
+                .with_values(LIST_PAGE_BYTES);
+            let entries = match self
+                .rt
+                .block_on(self.db.blocking_lock().list_entries(request))
+            {
+                Ok(response) => response.into_inner().entries,
+                Err(e) => {
+                    log::warn!("Failed to list the audit index files: {:?}", e);
+                    return;
+                }
+            };
+            let last = match entries.last() {
+                Some(entry) => entry.key.clone(),
+                None => break,
+            };
+            for entry in entries {
+                let key = String::from_utf8_lossy(&entry.key);
+                let path = PathBuf::from(&key[self.prefix.len()..]);
+                files.insert(path, FileSlice::from(entry.value));
+            }
+            start_after = last;
+        }
+        *self.files.write().unwrap() = Some(files);
+    }
+
     fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
-        let key = DB_PREFIX.clone() + &path.to_string_lossy();
+        let key = self.key(path);
         let request = PutRequest::new(key.as_bytes(), data);
 
         self.rt
             .block_on(self.db.blocking_lock().put(request))
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        if let Some(files) = self.files.write().unwrap().as_mut() {
+            files.insert(path.to_owned(), FileSlice::from(data.to_vec()));
+        }
         Ok(())
     }
+
+    fn key(&self, path: &Path) -> String {
+        self.prefix.clone() + &path.to_string_lossy()
+    }
+
+    fn cached_file(&self, path: &Path) -> Option<Option<FileSlice>> {
+        let files = self.files.read().unwrap();
+        files.as_ref().map(|files| files.get(path).cloned())
+    }
 }
 
 impl Directory for DbDirectory {
@@ -142,7 +201,10 @@ impl Directory for DbDirectory {
     }
 
     fn open_read(&self, path: &Path) -> result::Result<FileSlice, OpenReadError> {
-        let key = DB_PREFIX.clone() + &path.to_string_lossy();
+        if let Some(file) = self.cached_file(path) {
+            return file.ok_or_else(|| OpenReadError::FileDoesNotExist(PathBuf::from(path)));
+        }
+        let key = self.key(path);
         let request = GetRequest::new(key.as_bytes());
 
         self.rt
@@ -152,17 +214,23 @@ impl Directory for DbDirectory {
     }
 
     fn delete(&self, path: &Path) -> result::Result<(), DeleteError> {
-        let key = DB_PREFIX.clone() + &path.to_string_lossy();
+        let key = self.key(path);
         let request = DeleteRequest::new(key.as_bytes());
 
         self.rt
             .block_on(self.db.blocking_lock().delete(request))
             .map_err(|_| DeleteError::FileDoesNotExist(PathBuf::from(path)))?;
+        if let Some(files) = self.files.write().unwrap().as_mut() {
+            files.remove(path);
+        }
         Ok(())
     }
 
     fn exists(&self, path: &Path) -> Result<bool, OpenReadError> {
-        let key = DB_PREFIX.clone() + &path.to_string_lossy();
+        if let Some(file) = self.cached_file(path) {
+            return Ok(file.is_some());
+        }
+        let key = self.key(path);
         let request = GetRequest::new(key.as_bytes());
 
         let get = self.rt.block_on(self.db.blocking_lock().get(request));
@@ -187,6 +255,10 @@ impl Directory for DbDirectory {
     }
 
     fn atomic_read(&self, path: &Path) -> Result<Vec<u8>, OpenReadError> {
+        // The index is reloaded from the meta file.
+        if path == *META_FILEPATH && self.files.read().unwrap().is_none() {
+            self.load_files();
+        }
         let bytes =
             self.open_read(path)?
                 .read_bytes()
@@ -0,0 +1,189 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use teaclave_proto::teaclave_storage_service::{
+    GetRequest, ListEntriesRequest, PutRequest, TeaclaveStorageClient, WriteBatchRequest,
+};
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Code;
+use teaclave_types::{Entry, EntryBuilder};
+
+use std::net::Ipv6Addr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::Mutex;
+
+const ENTRY_PREFIX: &str = "audit_entry/";
+/// The generation of the index in use, 0 if it has never been rebuilt
+const GENERATION_KEY: &str = "audit_index_generation";
+// Bytes of the entries listed in a page, well below the message size limit
+const LIST_PAGE_BYTES: u64 = 1024 * 1024;
+// Keys listed or deleted in a page
+const KEY_PAGE_SIZE: u32 = 1024;
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredEntry {
+    microsecond: i64,
+    ip: Ipv6Addr,
+    user: String,
+    message: String,
+    result: bool,
+    trace_id: String,
+}
+
+impl From<&Entry> for StoredEntry {
+    fn from(entry: &Entry) -> Self {
+        Self {
+            microsecond: entry.datetime().timestamp_micros(),
+            ip: entry.ip(),
+            user: entry.user(),
+            message: entry.message(),
+            result: entry.result(),
+            trace_id: entry.trace_id(),
+        }
+    }
+}
+
+impl From<StoredEntry> for Entry {
+    fn from(stored: StoredEntry) -> Self {
+        EntryBuilder::new()
+            .microsecond(stored.microsecond)
+            .ip(stored.ip)
+            .user(stored.user)
+            .message(stored.message)
+            .result(stored.result)
+            .trace_id(stored.trace_id)
+            .build()
+    }
+}
+
+/// The audit logs as they are saved, alongside the index, so that the index
+/// can be rebuilt from them if it is corrupted. The keys are ordered by the
+/// time of the logs.
+#[derive(Clone)]
+pub(crate) struct EntryStore {
+    db: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+    rt: Arc<Runtime>,
+}
+
+impl EntryStore {
+    pub(crate) fn new(db: Arc<Mutex<TeaclaveStorageClient<Channel>>>) -> Self {
+        let rt = Arc::new(Builder::new_current_thread().enable_all().build().unwrap());
+        Self { db, rt }
+    }
+
+    pub(crate) fn entry_key(entry: &Entry) -> String {
+        format!(
+            "{}{:020}-{}",
+            ENTRY_PREFIX,
+            entry.datetime().timestamp_micros(),
+            uuid::Uuid::new_v4()
+        )
+    }
+
+    /// Save the logs under their keys in one batch.
+    pub(crate) fn save(&self, logs: &[(String, Entry)]) -> Result<()> {
+        let mut request = WriteBatchRequest::new();
+        for (key, entry) in logs {
+            let value = serde_json::to_vec(&StoredEntry::from(entry))?;
+            request = request.put(key.as_bytes(), value);
+        }
+        self.rt
+            .block_on(self.db.blocking_lock().write_batch(request))?;
+        Ok(())
+    }
+
+    /// The number of the saved logs.
+    pub(crate) fn count(&self) -> Result<u64> {
+        let mut count = 0;
+        let mut start_after = Vec::new();
+        loop {
+            let keys = self.key_page(ENTRY_PREFIX, start_after)?;
+            start_after = match keys.last() {
+                Some(key) => key.clone(),
+                None => return Ok(count),
+            };
+            count += keys.len() as u64;
+        }
+    }
+
+    /// Load the logs saved after the one of `start_after`, page by page. An
+    /// empty page is the end.
+    pub(crate) fn load_page(&self, start_after: &str) -> Result<Vec<(String, Entry)>> {
+        let request = ListEntriesRequest::new(ENTRY_PREFIX.as_bytes())
+            .page(start_after.as_bytes(), 0)
+            .with_values(LIST_PAGE_BYTES);
+        let entries = self
+            .rt
+            .block_on(self.db.blocking_lock().list_entries(request))?
+            .into_inner()
+            .entries;
+
+        let mut logs = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let key = String::from_utf8_lossy(&entry.key).into_owned();
+            let stored: StoredEntry = serde_json::from_slice(&entry.value)?;
+            logs.push((key, stored.into()));
+        }
+        Ok(logs)
+    }
+
+    pub(crate) fn index_generation(&self) -> Result<u64> {
+        let request = GetRequest::new(GENERATION_KEY.as_bytes());
+        match self.rt.block_on(self.db.blocking_lock().get(request)) {
+            Ok(response) => Ok(String::from_utf8(response.into_inner().value)?.parse()?),
+            Err(status) if status.code() == Code::NotFound => Ok(0),
+            Err(status) => Err(status.into()),
+        }
+    }
+
+    pub(crate) fn set_index_generation(&self, generation: u64) -> Result<()> {
+        let request = PutRequest::new(GENERATION_KEY.as_bytes(), generation.to_string());
+        self.rt.block_on(self.db.blocking_lock().put(request))?;
+        Ok(())
+    }
+
+    /// Delete everything under `prefix`, e.g., the files of an index.
+    pub(crate) fn delete_prefix(&self, prefix: &str) -> Result<()> {
+        let mut start_after = Vec::new();
+        loop {
+            let keys = self.key_page(prefix, start_after)?;
+            start_after = match keys.last() {
+                Some(key) => key.clone(),
+                None => return Ok(()),
+            };
+            let request = keys
+                .into_iter()
+                .fold(WriteBatchRequest::new(), WriteBatchRequest::delete);
+            self.rt
+                .block_on(self.db.blocking_lock().write_batch(request))?;
+        }
+    }
+
+    fn key_page(&self, prefix: &str, start_after: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        let request = ListEntriesRequest::new(prefix.as_bytes()).page(start_after, KEY_PAGE_SIZE);
+        let entries = self
+            .rt
+            .block_on(self.db.blocking_lock().list_entries(request))?
+            .into_inner()
+            .entries;
+        Ok(entries.into_iter().map(|entry| entry.key).collect())
+    }
+}
@@ -0,0 +1,182 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Rendering of audit logs for export. Logs are exported as JSON Lines by
+//! default, or as CSV with a header row. Both formats contain the selected
+//! columns only, in the selected order. The rendered logs are encrypted in
+//! the enclave with the key of a registered output file and uploaded to its
+//! URL through the file agent.
+
+use crate::file_handler::handle_file_request;
+
+use anyhow::{bail, ensure, Result};
+use std::path::Path;
+use std::untrusted::fs;
+use teaclave_types::{
+    Entry, FileAgentRequest, FileAuthTag, HandleFileCommand, HandleFileInfo, StagedFileInfo,
+    TeaclaveOutputFile,
+};
+use uuid::Uuid;
+
+static EXPORT_BASE_DIR: &str = "/tmp/teaclave_audit_export/";
+
+/// Columns of the exported logs, named after the fields of the log schema.
+pub(crate) const COLUMNS: [&str; 6] = ["date", "ip", "user", "message", "result", "trace_id"];
+
+/// The maximum number of exported logs, which is also used if the caller
+/// asks for none.
+pub(crate) const MAX_EXPORT_ROWS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportFormat {
+    JsonLines,
+    Csv,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "" | "jsonl" => Ok(ExportFormat::JsonLines),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => bail!("unsupported format {}, expect jsonl or csv", s),
+        }
+    }
+}
+
+pub(crate) fn row_cap(max_rows: u64) -> usize {
+    match max_rows as usize {
+        0 => MAX_EXPORT_ROWS,
+        n => n.min(MAX_EXPORT_ROWS),
+    }
+}
+
+/// All the columns if none is selected.
+pub(crate) fn select_columns(columns: &[String]) -> Result<Vec<&'static str>> {
+    if columns.is_empty() {
+        return Ok(COLUMNS.to_vec());
+    }
+    columns
+        .iter()
+        .map(|column| match COLUMNS.iter().find(|c| *c == column) {
+            Some(c) => Ok(*c),
+            None => bail!("unknown column {}, expect one of {:?}", column, COLUMNS),
+        })
+        .collect()
+}
+
+fn value_of(entry: &Entry, column: &str) -> String {
+    match column {
+        "date" => {
+            let datetime = entry.datetime();
+            format!("{}T{}Z", datetime.date(), datetime.time())
+        }
+        "ip" => entry.ip().to_string(),
+        "user" => entry.user(),
+        "message" => entry.message(),
+        "result" => entry.result().to_string(),
+        "trace_id" => entry.trace_id(),
+        _ => unreachable!("column is selected from COLUMNS"),
+    }
+}
+
+// Quote the fields as in RFC 4180. Fields which spreadsheets would take as
+// formulas are prefixed with a single quote, since users and messages come
+// from requests.
+fn csv_field(value: &str) -> String {
+    let value = match value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        true => format!("'{}", value),
+        false => value.to_string(),
+    };
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+pub(crate) fn render(
+    entries: &[Entry],
+    format: ExportFormat,
+    columns: &[&'static str],
+) -> Result<Vec<u8>> {
+    ensure!(!columns.is_empty(), "no column to export");
+    let mut lines = Vec::with_capacity(entries.len() + 1);
+    match format {
+        ExportFormat::JsonLines => {
+            for entry in entries {
+                let row: serde_json::Map<String, serde_json::Value> = columns
+                    .iter()
+                    .map(|c| (c.to_string(), value_of(entry, c).into()))
+                    .collect();
+                lines.push(serde_json::to_string(&row)?);
+            }
+        }
+        ExportFormat::Csv => {
+            lines.push(columns.join(","));
+            for entry in entries {
+                let row: Vec<String> = columns
+                    .iter()
+                    .map(|c| csv_field(&value_of(entry, c)))
+                    .collect();
+                lines.push(row.join(","));
+            }
+        }
+    }
+    // RFC 4180 ends each CSV record with CRLF
+    let separator = match format {
+        ExportFormat::JsonLines => "\n",
+        ExportFormat::Csv => "\r\n",
+    };
+    Ok(lines
+        .iter()
+        .flat_map(|line| [line.as_str(), separator])
+        .collect::<String>()
+        .into_bytes())
+}
+
+/// Encrypt `bytes` for the output file and upload them to its URL. Returns
+/// the tag of the uploaded file.
+pub(crate) fn upload(
+    bytes: &[u8],
+    output: &TeaclaveOutputFile,
+    fusion_base: impl AsRef<Path>,
+) -> Result<FileAuthTag> {
+    let cwd = Path::new(EXPORT_BASE_DIR).join(Uuid::new_v4().to_string());
+    fs::create_dir_all(&cwd)?;
+    let result = stage_and_upload(&cwd, bytes, output, fusion_base.as_ref());
+    // Nothing is left behind whether the upload succeeds or not.
+    let _ = fs::remove_dir_all(&cwd);
+    result
+}
+
+fn stage_and_upload(
+    cwd: &Path,
+    bytes: &[u8],
+    output: &TeaclaveOutputFile,
+    fusion_base: &Path,
+) -> Result<FileAuthTag> {
+    let staged = StagedFileInfo::create_with_bytes(cwd.join("staged"), bytes)?;
+    let upload_path = cwd.join("upload");
+    let cmac = staged.convert_for_uploading(&upload_path, output.crypto_info, None)?;
+    let info = HandleFileInfo::new(&upload_path, &output.url);
+    let request = FileAgentRequest::new(HandleFileCommand::Upload, vec![info], fusion_base);
+    log::debug!("Ocall file upload request: {:?}", request);
+    handle_file_request(request)?;
+    Ok(cmac)
+}
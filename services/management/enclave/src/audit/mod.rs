@@ -17,6 +17,9 @@
 
 mod auditor;
 mod db_directory;
+mod entry_store;
+pub(crate) mod export;
+mod rebuild;
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests;
 
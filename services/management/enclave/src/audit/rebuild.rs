@@ -0,0 +1,197 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Rebuilding of the audit index from the saved logs.
+//!
+//! The new index is built under the prefix of the next generation, next to
+//! the current one, which keeps serving queries. Logs saved in the meantime
+//! are held back and added to the new index before the generation in use is
+//! switched to it, after which the files of the previous index are deleted.
+
+use super::auditor::{index_prefix, AuditIndex, Auditor};
+
+use teaclave_types::Entry;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RebuildPhase {
+    Idle,
+    Counting,
+    Indexing,
+    Switching,
+    Completed,
+    Failed,
+}
+
+impl fmt::Display for RebuildPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            RebuildPhase::Idle => "idle",
+            RebuildPhase::Counting => "counting",
+            RebuildPhase::Indexing => "indexing",
+            RebuildPhase::Switching => "switching",
+            RebuildPhase::Completed => "completed",
+            RebuildPhase::Failed => "failed",
+        };
+        write!(f, "{}", phase)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RebuildStatus {
+    pub phase: RebuildPhase,
+    pub total_entries: u64,
+    pub indexed_entries: u64,
+    pub error: String,
+}
+
+impl Default for RebuildStatus {
+    fn default() -> Self {
+        Self {
+            phase: RebuildPhase::Idle,
+            total_entries: 0,
+            indexed_entries: 0,
+            error: String::new(),
+        }
+    }
+}
+
+impl RebuildStatus {
+    pub(crate) fn is_running(&self) -> bool {
+        !matches!(
+            self.phase,
+            RebuildPhase::Idle | RebuildPhase::Completed | RebuildPhase::Failed
+        )
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct RebuildState {
+    pub status: RebuildStatus,
+    // Logs saved during the rebuild by their keys, None if not rebuilding
+    pub pending: Option<BTreeMap<String, Entry>>,
+}
+
+impl Auditor {
+    pub(crate) fn rebuild_status(&self) -> RebuildStatus {
+        self.rebuild.lock().unwrap().status.clone()
+    }
+
+    /// Reset the status for a new rebuild. Returns false if another rebuild
+    /// is still in progress.
+    pub(crate) fn try_begin_rebuild(&self) -> bool {
+        let mut state = self.rebuild.lock().unwrap();
+        if state.status.is_running() {
+            return false;
+        }
+        state.status = RebuildStatus {
+            phase: RebuildPhase::Counting,
+            ..Default::default()
+        };
+        state.pending = Some(BTreeMap::new());
+        true
+    }
+
+    /// Rebuild the index begun with `try_begin_rebuild`, blocking until it
+    /// is done.
+    pub(crate) fn rebuild(&self) {
+        match self.rebuild_index() {
+            Ok(generation) => info!("Audit index rebuilt as generation {}", generation),
+            Err(e) => {
+                error!("Failed to rebuild the audit index: {:?}", e);
+                let mut state = self.rebuild.lock().unwrap();
+                // The logs saved in the meantime go to the current index
+                let pending = state.pending.take().unwrap_or_default();
+                if let Some(index) = self.index.read().unwrap().as_ref() {
+                    if let Err(e) = index.add(pending.into_values()) {
+                        warn!("Failed to index the logs saved during the rebuild: {:?}", e);
+                    }
+                }
+                state.status.phase = RebuildPhase::Failed;
+                state.status.error = e.to_string();
+            }
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut RebuildStatus)) {
+        f(&mut self.rebuild.lock().unwrap().status);
+    }
+
+    fn rebuild_index(&self) -> Result<u64> {
+        let total = self.entries.count()?;
+        self.update(|s| {
+            s.phase = RebuildPhase::Indexing;
+            s.total_entries = total;
+        });
+
+        let generation = self.entries.index_generation()? + 1;
+        // Files left by a rebuild which failed
+        self.entries.delete_prefix(&index_prefix(generation))?;
+        let index = AuditIndex::open(self.storage.clone(), generation)?;
+
+        let mut start_after = String::new();
+        loop {
+            // Listed along with the logs held back, so that a log is either
+            // in the page or held back when it is skipped here.
+            let (logs, count, last) = {
+                let state = self.rebuild.lock().unwrap();
+                let page = self.entries.load_page(&start_after)?;
+                let last = match page.last() {
+                    Some((key, _)) => key.clone(),
+                    None => break,
+                };
+                let count = page.len() as u64;
+                let held_back = |key: &String| {
+                    state
+                        .pending
+                        .as_ref()
+                        .map_or(false, |pending| pending.contains_key(key))
+                };
+                let logs: Vec<Entry> = page
+                    .into_iter()
+                    .filter(|(key, _)| !held_back(key))
+                    .map(|(_, log)| log)
+                    .collect();
+                (logs, count, last)
+            };
+            index.add(logs)?;
+            self.update(|s| s.indexed_entries += count);
+            start_after = last;
+        }
+
+        let mut state = self.rebuild.lock().unwrap();
+        state.status.phase = RebuildPhase::Switching;
+        if let Some(pending) = state.pending.as_ref() {
+            index.add(pending.values().cloned())?;
+        }
+        self.entries.set_index_generation(generation)?;
+        *self.index.write().unwrap() = Some(Arc::new(index));
+        state.pending = None;
+        state.status.phase = RebuildPhase::Completed;
+        drop(state);
+
+        if let Err(e) = self.entries.delete_prefix(&index_prefix(generation - 1)) {
+            warn!("Failed to delete the previous audit index: {:?}", e);
+        }
+        Ok(generation)
+    }
+}
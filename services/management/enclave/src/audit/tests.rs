@@ -15,9 +15,13 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use super::auditor::index_prefix;
+use super::entry_store::{EntryStore, StoredEntry};
+use super::export::*;
+use super::rebuild::{RebuildPhase, RebuildStatus};
 use super::*;
 
-use teaclave_types::EntryBuilder;
+use teaclave_types::{Entry, EntryBuilder};
 
 pub fn test_entry_doc_conversion() {
     let schema = Auditor::log_schema();
@@ -30,11 +34,83 @@ pub fn test_entry_doc_conversion() {
             "ip": "0000:0000:0000:0000:0000:0000:0000:0000",
             "user": "",
             "message": "",
-            "result": false
+            "result": false,
+            "trace_id": ""
         }"#,
         )
         .unwrap();
 
     assert_eq!(entry, Auditor::try_convert_to_entry(doc.clone()).unwrap());
     assert_eq!(Auditor::convert_to_doc(entry), doc);
+
+    let entry = EntryBuilder::new()
+        .microsecond(0)
+        .trace_id("00000000000000000000000000000001".to_owned())
+        .build();
+    let doc = Auditor::convert_to_doc(entry.clone());
+    assert_eq!(entry, Auditor::try_convert_to_entry(doc).unwrap());
+}
+
+pub fn test_export_logs() {
+    let entries = vec![
+        EntryBuilder::new()
+            .microsecond(1_500_000)
+            .user("alice".to_owned())
+            .message("get_task, \"quoted\"".to_owned())
+            .result(true)
+            .build(),
+        EntryBuilder::new()
+            .microsecond(0)
+            .user("=cmd()".to_owned())
+            .message("cancel_task".to_owned())
+            .build(),
+    ];
+
+    let columns = select_columns(&["user".to_owned(), "message".to_owned()]).unwrap();
+    let csv = render(&entries, ExportFormat::Csv, &columns).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "user,message\r\nalice,\"get_task, \"\"quoted\"\"\"\r\n'=cmd(),cancel_task\r\n"
+    );
+
+    let columns = select_columns(&["date".to_owned(), "result".to_owned()]).unwrap();
+    let jsonl = render(&entries[..1], ExportFormat::JsonLines, &columns).unwrap();
+    assert_eq!(
+        String::from_utf8(jsonl).unwrap(),
+        "{\"date\":\"1970-01-01T00:00:01.500Z\",\"result\":\"true\"}\n"
+    );
+
+    assert_eq!(select_columns(&[]).unwrap(), COLUMNS.to_vec());
+    assert!(select_columns(&["password".to_owned()]).is_err());
+    assert!("xml".parse::<ExportFormat>().is_err());
+    assert_eq!(row_cap(0), MAX_EXPORT_ROWS);
+    assert_eq!(row_cap(10), 10);
+}
+
+pub fn test_rebuild_audit_index() {
+    let entry = EntryBuilder::new()
+        .microsecond(1_500_000)
+        .user("alice".to_owned())
+        .message("get_task".to_owned())
+        .result(true)
+        .trace_id("00000000000000000000000000000001".to_owned())
+        .build();
+    let stored = serde_json::to_vec(&StoredEntry::from(&entry)).unwrap();
+    let stored: StoredEntry = serde_json::from_slice(&stored).unwrap();
+    assert_eq!(Entry::from(stored), entry);
+
+    // Keys are ordered by the time of the logs
+    let earlier = EntryBuilder::new().microsecond(999_999).build();
+    assert!(EntryStore::entry_key(&earlier) < EntryStore::entry_key(&entry));
+
+    assert_eq!(index_prefix(0), "tantivy/");
+    assert_eq!(index_prefix(2), "tantivy-2/");
+
+    let mut status = RebuildStatus::default();
+    assert!(!status.is_running());
+    status.phase = RebuildPhase::Indexing;
+    assert!(status.is_running());
+    assert_eq!(status.phase.to_string(), "indexing");
+    status.phase = RebuildPhase::Failed;
+    assert!(!status.is_running());
 }
@@ -0,0 +1,230 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Decommissioning of the storage node behind the management service.
+//!
+//! The current node is frozen so it stops accepting writes, its final
+//! snapshot is exported and verified, and the entries are imported into the
+//! replacement node over an attested TLS channel. The replacement seals the
+//! data with its own key. Once a fresh export of the replacement matches the
+//! original digest, the management service switches its storage client to
+//! the replacement.
+//!
+//! A follower replicating the storage node is promoted instead once the node
+//! is lost, so it has no final snapshot to transfer. The follower accepts
+//! writes from then on, and the management service switches to it.
+
+use anyhow::{anyhow, ensure, Result};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use teaclave_proto::teaclave_storage_service::{
+    ExportSnapshotRequest, FreezeRequest, ImportSnapshotRequest, PromoteReplicaRequest,
+    TeaclaveStorageClient,
+};
+use teaclave_rpc::transport::{channel::Endpoint, Channel};
+use tokio::sync::Mutex;
+
+// Number of entries sent to the replacement in one ImportSnapshot call.
+const IMPORT_BATCH_SIZE: usize = 512;
+
+pub(crate) type StorageEndpointFactory = Arc<dyn Fn(&str) -> Result<Endpoint> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DecommissionPhase {
+    Idle,
+    Freezing,
+    Snapshotting,
+    Transferring,
+    Verifying,
+    Switching,
+    Completed,
+    Failed,
+}
+
+impl fmt::Display for DecommissionPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            DecommissionPhase::Idle => "idle",
+            DecommissionPhase::Freezing => "freezing",
+            DecommissionPhase::Snapshotting => "snapshotting",
+            DecommissionPhase::Transferring => "transferring",
+            DecommissionPhase::Verifying => "verifying",
+            DecommissionPhase::Switching => "switching",
+            DecommissionPhase::Completed => "completed",
+            DecommissionPhase::Failed => "failed",
+        };
+        write!(f, "{}", phase)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct DecommissionStatus {
+    pub phase: DecommissionPhase,
+    pub replacement_address: String,
+    pub total_entries: u64,
+    pub transferred_entries: u64,
+    pub error: String,
+}
+
+impl Default for DecommissionStatus {
+    fn default() -> Self {
+        Self {
+            phase: DecommissionPhase::Idle,
+            replacement_address: String::new(),
+            total_entries: 0,
+            transferred_entries: 0,
+            error: String::new(),
+        }
+    }
+}
+
+impl DecommissionStatus {
+    pub(crate) fn is_running(&self) -> bool {
+        !matches!(
+            self.phase,
+            DecommissionPhase::Idle | DecommissionPhase::Completed | DecommissionPhase::Failed
+        )
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct StorageDecommission {
+    storage_client: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+    endpoint_factory: StorageEndpointFactory,
+    status: Arc<RwLock<DecommissionStatus>>,
+}
+
+impl StorageDecommission {
+    pub(crate) fn new(
+        storage_client: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+        endpoint_factory: StorageEndpointFactory,
+    ) -> Self {
+        Self {
+            storage_client,
+            endpoint_factory,
+            status: Arc::new(RwLock::new(DecommissionStatus::default())),
+        }
+    }
+
+    pub(crate) fn status(&self) -> DecommissionStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    /// Reset the status for a new run. Returns false if another run is still
+    /// in progress.
+    pub(crate) fn try_begin(&self, replacement_address: &str) -> bool {
+        let mut status = self.status.write().unwrap();
+        if status.is_running() {
+            return false;
+        }
+        *status = DecommissionStatus {
+            phase: DecommissionPhase::Freezing,
+            replacement_address: replacement_address.to_string(),
+            ..Default::default()
+        };
+        true
+    }
+
+    pub(crate) async fn run(self) {
+        let address = self.status().replacement_address;
+        match self.transfer_to(&address).await {
+            Ok(_) => {
+                info!("Storage decommissioned, switched to {}", address);
+                self.update(|s| s.phase = DecommissionPhase::Completed);
+            }
+            Err(e) => {
+                error!("Failed to decommission storage: {:?}", e);
+                self.update(|s| {
+                    s.phase = DecommissionPhase::Failed;
+                    s.error = e.to_string();
+                });
+            }
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut DecommissionStatus)) {
+        f(&mut self.status.write().unwrap());
+    }
+
+    async fn transfer_to(&self, address: &str) -> Result<()> {
+        // Writes are rejected from here on; reads keep being served by the
+        // current node until the switch.
+        let mut current = self.storage_client.lock().await.clone();
+        current.freeze(FreezeRequest::default()).await?;
+
+        self.update(|s| s.phase = DecommissionPhase::Snapshotting);
+        let snapshot = current
+            .export_snapshot(ExportSnapshotRequest::default())
+            .await?
+            .into_inner();
+        ensure!(snapshot.verify(), "final snapshot failed verification");
+        let total = snapshot.entries.len() as u64;
+        self.update(|s| {
+            s.phase = DecommissionPhase::Transferring;
+            s.total_entries = total;
+        });
+
+        let channel = (self.endpoint_factory)(address)?
+            .connect()
+            .await
+            .map_err(|e| anyhow!("Failed to connect to replacement storage, {:?}", e))?;
+        let mut replacement = TeaclaveStorageClient::new_with_builtin_config(channel);
+        for batch in snapshot.entries.chunks(IMPORT_BATCH_SIZE) {
+            replacement
+                .import_snapshot(ImportSnapshotRequest::new(batch.to_vec()))
+                .await?;
+            self.update(|s| s.transferred_entries += batch.len() as u64);
+        }
+
+        self.update(|s| s.phase = DecommissionPhase::Verifying);
+        let imported = replacement
+            .export_snapshot(ExportSnapshotRequest::default())
+            .await?
+            .into_inner();
+        ensure!(
+            imported.digest == snapshot.digest,
+            "replacement storage does not match the final snapshot"
+        );
+
+        self.update(|s| s.phase = DecommissionPhase::Switching);
+        *self.storage_client.lock().await = replacement;
+        Ok(())
+    }
+
+    /// Promote the follower at `address` and switch to it, returning the last
+    /// index of the change log it had applied. Writes acknowledged by the lost
+    /// node after that index are lost with it.
+    pub(crate) async fn promote(&self, address: &str) -> Result<u64> {
+        ensure!(!self.status().is_running(), "decommission in progress");
+        let channel = (self.endpoint_factory)(address)?
+            .connect()
+            .await
+            .map_err(|e| anyhow!("Failed to connect to follower storage, {:?}", e))?;
+        let mut follower = TeaclaveStorageClient::new_with_builtin_config(channel);
+        let applied_index = follower
+            .promote_replica(PromoteReplicaRequest::default())
+            .await?
+            .into_inner()
+            .applied_index;
+        *self.storage_client.lock().await = follower;
+        info!(
+            "Storage replica {} promoted at change log index {}",
+            address, applied_index
+        );
+        Ok(applied_index)
+    }
+}
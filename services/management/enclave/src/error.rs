@@ -15,7 +15,10 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use teaclave_proto::teaclave_access_control_service::Denial;
+use teaclave_proto::teaclave_common::attach_error_detail;
 use teaclave_rpc::{Code, Status};
+use teaclave_types::UserID;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -32,40 +35,162 @@ pub(crate) enum ManagementServiceError {
     InvalidDataId,
     #[error("invalid output file")]
     InvalidOutputFile,
+    #[error("output has expired")]
+    OutputExpired,
     #[error("invalid function id")]
     InvalidFunctionId,
     #[error("invalid task id")]
     InvalidTaskId,
     #[error("invalid task")]
     InvalidTask,
+    #[error("invalid task status")]
+    InvalidTaskStatus,
+    #[error("invalid function arguments, reason: {0}")]
+    InvalidFunctionArguments(String),
     #[error("failed to assign data to task")]
     TaskAssignDataError,
     #[error("failed to approve task")]
     TaskApproveError,
+    #[error("failed to reject task, reason: {0}")]
+    TaskRejectError(String),
+    #[error("approval of the task has expired")]
+    ApprovalExpired,
+    #[error("input {0} is not assigned with the expected file")]
+    InputCmacMismatch(String),
+    #[error("referenced by {0} pending tasks: {1}")]
+    PendingDependents(usize, String),
     #[error("failed to invoke task")]
     TaskInvokeError,
     #[error("failed to cancel task, reason: {0}")]
     TaskCancelError(String),
     #[error("function quota has been used up")]
     FunctionQuotaError,
+    #[error("task queue is full with {0} tasks, try again later")]
+    Backpressure(u32),
+    #[error("function version already exists")]
+    FunctionVersionExists,
     #[error("audit log error, reason: {0}")]
     AuditError(String),
+    #[error("invalid audit log export, reason: {0}")]
+    InvalidAuditExport(String),
+    #[error("failed to export metadata, reason: {0}")]
+    MetadataExportError(String),
+    #[error("failed to rebuild audit index, reason: {0}")]
+    AuditIndexRebuildError(String),
+    #[error("audit index is unavailable until it is rebuilt")]
+    AuditIndexUnavailable,
+    #[error("failed to decommission storage, reason: {0}")]
+    DecommissionError(String),
+    #[error("storage snapshot error, reason: {0}")]
+    SnapshotError(String),
+    #[error("failed to promote storage replica, reason: {0}")]
+    PromotionError(String),
+    #[error("denied by data attributes: {0}")]
+    AttributeDenied(String, Denial),
+    #[error("invalid notification preferences, reason: {0}")]
+    InvalidNotificationPreferences(String),
+    #[error("task log is not available")]
+    TaskLogNotFound,
+    #[error("invalid webhook sink, reason: {0}")]
+    InvalidWebhookSink(String),
+    #[error("webhooks to {0} are not allowed in the namespace")]
+    WebhookNotAllowed(String),
+    #[error("invalid task schedule, reason: {0}")]
+    InvalidSchedule(String),
+    #[error("invalid function upload, reason: {0}")]
+    InvalidFunctionUpload(String),
+    #[error("function payload exceeds the limit of {0} bytes")]
+    FunctionPayloadTooLarge(u64),
+    #[error("function upload not found or expired")]
+    FunctionUploadNotFound,
+    #[error("invalid access policy, reason: {0}")]
+    InvalidAccessPolicy(String),
+    #[error("invalid output policy, reason: {0}")]
+    InvalidOutputPolicy(String),
+    #[error("resource limits are only supported for Python functions")]
+    InvalidResourceLimits,
+    #[error("invalid maintenance window, reason: {0}")]
+    InvalidMaintenanceWindow(String),
+    #[error("service is under maintenance, retry after {0} seconds")]
+    UnderMaintenance(u64),
+}
+
+impl ManagementServiceError {
+    // Users are told the failed constraint of their own access only, as the
+    // constraints failed by other participants reveal their attributes.
+    pub(crate) fn attribute_denied(denial: Denial, caller: &UserID) -> Self {
+        let shown = if denial.user_id == caller.to_string() {
+            denial.to_string()
+        } else {
+            denial.redacted()
+        };
+        ManagementServiceError::AttributeDenied(shown, denial)
+    }
 }
 
 impl From<ManagementServiceError> for Status {
     fn from(error: ManagementServiceError) -> Self {
         log::debug!("ManagementServiceError: {:?}", error);
         let msg = error.to_string();
+        // The whole denial goes to the frontend service for the audit log.
+        if let ManagementServiceError::AttributeDenied(_, ref denial) = error {
+            return denial.attach(Status::new(Code::PermissionDenied, msg));
+        }
+        // Clients can back off without parsing the message.
+        if let ManagementServiceError::UnderMaintenance(retry_after) = error {
+            let mut status = Status::new(Code::Unavailable, msg);
+            if let Ok(value) = retry_after.to_string().parse() {
+                status.metadata_mut().insert("retry-after", value);
+            }
+            return status;
+        }
+        // The chain is hidden from the users not granted to see it by the
+        // frontend service.
+        let detail = match error {
+            ManagementServiceError::Service(ref e) => Some(format!("{:#}", e)),
+            _ => None,
+        };
         let code = match error {
-            ManagementServiceError::PermissionDenied => Code::PermissionDenied,
+            ManagementServiceError::PermissionDenied
+            | ManagementServiceError::WebhookNotAllowed(_) => Code::PermissionDenied,
             ManagementServiceError::Service(_) => Code::Internal,
             ManagementServiceError::InvalidDataId
             | ManagementServiceError::InvalidOutputFile
             | ManagementServiceError::InvalidFunctionId
             | ManagementServiceError::InvalidTaskId
-            | ManagementServiceError::InvalidTask => Code::InvalidArgument,
+            | ManagementServiceError::InvalidTask
+            | ManagementServiceError::InvalidTaskStatus
+            | ManagementServiceError::InvalidFunctionArguments(_)
+            | ManagementServiceError::InvalidNotificationPreferences(_)
+            | ManagementServiceError::InvalidWebhookSink(_)
+            | ManagementServiceError::InvalidSchedule(_)
+            | ManagementServiceError::InvalidFunctionUpload(_)
+            | ManagementServiceError::InvalidAccessPolicy(_)
+            | ManagementServiceError::InvalidOutputPolicy(_)
+            | ManagementServiceError::InvalidResourceLimits
+            | ManagementServiceError::InvalidMaintenanceWindow(_)
+            | ManagementServiceError::InvalidAuditExport(_) => Code::InvalidArgument,
+            ManagementServiceError::FunctionVersionExists => Code::AlreadyExists,
+            ManagementServiceError::DecommissionError(_)
+            | ManagementServiceError::AuditIndexRebuildError(_)
+            | ManagementServiceError::SnapshotError(_)
+            | ManagementServiceError::PromotionError(_)
+            | ManagementServiceError::TaskRejectError(_)
+            | ManagementServiceError::ApprovalExpired
+            | ManagementServiceError::InputCmacMismatch(_)
+            | ManagementServiceError::PendingDependents(..) => Code::FailedPrecondition,
+            ManagementServiceError::Backpressure(_)
+            | ManagementServiceError::FunctionPayloadTooLarge(_) => Code::ResourceExhausted,
+            ManagementServiceError::TaskLogNotFound
+            | ManagementServiceError::OutputExpired
+            | ManagementServiceError::FunctionUploadNotFound => Code::NotFound,
+            ManagementServiceError::AuditIndexUnavailable => Code::Unavailable,
             _ => Code::Unknown,
         };
-        Status::new(code, msg)
+        let status = Status::new(code, msg);
+        match detail {
+            Some(detail) => attach_error_detail(status, &detail),
+            None => status,
+        }
     }
 }
@@ -21,6 +21,7 @@
 extern crate log;
 extern crate sgx_types;
 use anyhow::{anyhow, Result};
+use std::sync::Arc;
 
 use teaclave_attestation::{verifier, AttestationConfig, RemoteAttestation};
 use teaclave_binder::proto::{
@@ -31,22 +32,31 @@ use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::{AS_ROOT_CA_CERT, AUDITOR_PUBLIC_KEYS, MANAGEMENT_INBOUND_SERVICES};
 use teaclave_config::RuntimeConfig;
 use teaclave_proto::teaclave_management_service::TeaclaveManagementServer;
-use teaclave_service_enclave_utils::{create_trusted_storage_endpoint, ServiceEnclave};
+use teaclave_service_enclave_utils::{
+    create_trusted_access_control_endpoint, create_trusted_storage_endpoint, drain_signal, drained,
+    ServiceEnclave,
+};
 use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
 
 mod audit;
+mod decommission;
 mod error;
+mod file_handler;
 mod service;
 
 // Sets the number of worker threads the Runtime will use.
 const N_WORKERS: usize = 16;
+const MAINTENANCE_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
 
 async fn start_service(config: &RuntimeConfig) -> Result<()> {
     info!("Starting Management...");
 
     let listen_address = config.internal_endpoints.management.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(config)?;
+    let verification_policy = verifier::VerificationPolicy::from_teaclave_config(config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
+        .endorsement_cache(config.attestation.endorsement_cache_path("management"))
+        .reuse_cached_endorsement(config.attestation.reuse_cached_endorsement)
         .generate_and_endorse()?
         .attested_tls_config()
         .ok_or_else(|| anyhow!("cannot get attested TLS config"))?;
@@ -72,7 +82,13 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         accepted_enclave_attrs,
         AS_ROOT_CA_CERT,
         verifier::universal_quote_verifier,
+        verification_policy.clone(),
     )?
+    .mutual_attestation(true)
+    .min_protocol_version(config.rpc.min_protocol_version)
+    .session_freshness(std::time::Duration::from_secs(
+        teaclave_config::build::GRPC_CONFIG.session_freshness_secs,
+    ))
     .into();
     info!(" Starting Management: Server config setup finished ...");
 
@@ -81,20 +97,85 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         &enclave_info,
         AS_ROOT_CA_CERT,
         verifier::universal_quote_verifier,
-        attested_tls_config,
+        verification_policy.clone(),
+        attested_tls_config.clone(),
     )?;
 
+    let access_control_service_endpoint = create_trusted_access_control_endpoint(
+        &config.internal_endpoints.access_control.advertised_address,
+        &enclave_info,
+        AS_ROOT_CA_CERT,
+        verifier::universal_quote_verifier,
+        verification_policy.clone(),
+        attested_tls_config.clone(),
+    )?;
+
+    // Metadata dumps are signed with the attested key and claim the audited
+    // measurement, which verifiers check against the attestation report.
+    let measurement = enclave_info
+        .get_enclave_attr("teaclave_management_service")
+        .ok_or_else(|| anyhow!("cannot get measurement of management service"))?
+        .measurement;
+    let metadata_signer = service::MetadataSigner::new(attested_tls_config.clone(), measurement);
+
+    // Endpoints of replacement storage nodes are attested the same way as the
+    // current one when the storage is decommissioned.
+    let storage_endpoint_factory: decommission::StorageEndpointFactory =
+        Arc::new(move |address: &str| {
+            create_trusted_storage_endpoint(
+                address,
+                &enclave_info,
+                AS_ROOT_CA_CERT,
+                verifier::universal_quote_verifier,
+                verification_policy.clone(),
+                attested_tls_config.clone(),
+            )
+        });
+
     info!(" Starting Management: setup storage endpoint finished ...");
 
-    let service = service::TeaclaveManagementService::new(storage_service_endpoint).await?;
+    let service = service::TeaclaveManagementService::new(
+        storage_service_endpoint,
+        access_control_service_endpoint,
+        storage_endpoint_factory,
+        metadata_signer,
+        config.mount.fusion_base_dir.clone(),
+        config.scheduler.max_queue_depth,
+        config.webhook.clone(),
+        config.function_payload,
+        config.resource_limits,
+    )
+    .await?;
+
+    let retention = service.clone();
+    let sweep_interval = std::time::Duration::from_secs(config.data_retention.sweep_interval_secs);
+    tokio::spawn(async move {
+        let mut sweep = tokio::time::interval(sweep_interval);
+        loop {
+            sweep.tick().await;
+            retention.expire_outputs().await;
+        }
+    });
+
+    // Held tasks are staged within a few seconds after a window ends.
+    let maintenance = service.clone();
+    tokio::spawn(async move {
+        let mut sweep = tokio::time::interval(MAINTENANCE_SWEEP_INTERVAL);
+        loop {
+            sweep.tick().await;
+            maintenance.end_maintenance_windows().await;
+        }
+    });
 
     info!(" Starting Management: start listening ...");
-    teaclave_rpc::transport::Server::builder()
+    let served = teaclave_rpc::transport::Server::builder()
         .tls_config(server_config)
         .map_err(|_| anyhow::anyhow!("TeaclaveFrontendServer tls config error"))?
         .add_service(TeaclaveManagementServer::new_with_builtin_config(service))
-        .serve(listen_address)
-        .await?;
+        .serve_with_shutdown(listen_address, drain_signal())
+        .await;
+    drained();
+    served?;
     Ok(())
 }
 
@@ -124,6 +205,7 @@ fn handle_init_enclave(_: &InitEnclaveInput) -> TeeServiceResult<InitEnclaveOutp
 
 #[handle_ecall]
 fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<FinalizeEnclaveOutput> {
+    ServiceEnclave::drain();
     ServiceEnclave::finalize()?;
     Ok(FinalizeEnclaveOutput)
 }
@@ -144,12 +226,18 @@ pub mod tests {
         run_tests!(
             service::tests::handle_input_file,
             service::tests::handle_output_file,
+            service::tests::expire_output_file,
             service::tests::handle_function,
             service::tests::check_function_quota,
             service::tests::deserialize_function_arguments,
+            service::tests::typed_function_arguments,
             service::tests::handle_task,
+            service::tests::approve_and_reject_task,
+            service::tests::assign_data_by_access_policy,
             service::tests::handle_staged_task,
             audit::tests::test_entry_doc_conversion,
+            audit::tests::test_export_logs,
+            audit::tests::test_rebuild_audit_index,
         )
     }
 }
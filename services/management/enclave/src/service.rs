@@ -17,35 +17,121 @@
 
 use super::*;
 
-use audit::Auditor;
+use audit::{export, Auditor};
+use decommission::{StorageDecommission, StorageEndpointFactory};
 use error::ManagementServiceError;
 
 use anyhow::anyhow;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryInto;
-use std::sync::Arc;
-use teaclave_proto::teaclave_common::i32_from_task_status;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use teaclave_attestation::AttestedTlsConfig;
+use teaclave_config::{FunctionPayloadConfig, ResourceLimitsConfig, WebhookConfig};
+use teaclave_proto::teaclave_access_control_service::{
+    AuthorizeBatchRequest, AuthorizeDataRequest, TeaclaveAccessControlClient,
+};
+use teaclave_proto::teaclave_common::{i32_from_task_status, i32_to_task_status};
 use teaclave_proto::teaclave_frontend_service::*;
 use teaclave_proto::teaclave_frontend_service::{
     from_proto_file_ids, from_proto_ownership, to_proto_file_ids, to_proto_ownership,
 };
-use teaclave_proto::teaclave_management_service::{SaveLogsRequest, TeaclaveManagement};
+use teaclave_proto::teaclave_management_service::{
+    PullNotificationDigestsResponse, SaveLogsRequest, TeaclaveManagement,
+};
 use teaclave_proto::teaclave_storage_service::{
-    DeleteRequest, EnqueueRequest, GetKeysByPrefixRequest, GetRequest, PutRequest,
-    TeaclaveStorageClient,
+    ConsistencyToken, CreateSnapshotRequest, DeleteRequest, DequeueRequest, EnqueueRequest,
+    GetKeysByPrefixRequest, GetQueueLengthRequest, GetRequest, PutRequest, RestoreSnapshotRequest,
+    TeaclaveStorageClient, WriteBatchRequest,
 };
 use teaclave_rpc::transport::{channel::Endpoint, Channel};
+use teaclave_rpc::Code;
 use teaclave_rpc::{Request, Response};
 use teaclave_service_enclave_utils::ensure;
+use teaclave_types::ResourceLimits;
 use teaclave_types::*;
 use tokio::sync::Mutex;
 use tokio::task;
 use url::Url;
 use uuid::Uuid;
 
+const NOTIFICATION_PREFERENCES_PREFIX: &str = "notification_preferences";
+// Events left in the queue are delivered in the next digests.
+const MAX_EVENTS_PER_DIGEST: usize = 1000;
+// Bounds the lineage graph returned for a piece of data.
+const MAX_LINEAGE_RECORDS: usize = 1000;
+// Keys read from the storage at a time when reading a page of items.
+const SCAN_BATCH_SIZE: u32 = 256;
+const MAX_WEBHOOK_SINKS_PER_TASK: usize = 8;
+// Pending tasks listed in the error refusing to delete what they reference
+const MAX_LISTED_DEPENDENTS: usize = 16;
+
+/// Signs metadata dumps with the key of the current attested TLS
+/// certificate, like the receipts of the execution service.
+#[derive(Clone)]
+pub(crate) struct MetadataSigner {
+    attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
+    measurement: EnclaveMeasurement,
+}
+
+impl MetadataSigner {
+    pub(crate) fn new(
+        attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
+        measurement: EnclaveMeasurement,
+    ) -> Self {
+        Self {
+            attested_tls_config,
+            measurement,
+        }
+    }
+
+    fn sign(&self, dump: MetadataDump) -> anyhow::Result<MetadataDump> {
+        let tls_config = self
+            .attested_tls_config
+            .read()
+            .map_err(|_| anyhow!("lock poisoned"))?;
+        dump.sign(&self.measurement, &tls_config.cert, &tls_config.private_key)
+    }
+}
+
+/// Token of the latest write of the service to the storage. Reads present it
+/// so that they never act on a state older than the writes before them, e.g.,
+/// in the transitions of a task.
+#[derive(Clone, Default)]
+struct LatestWrite(Arc<std::sync::Mutex<Option<ConsistencyToken>>>);
+
+impl LatestWrite {
+    fn observe(&self, token: Option<ConsistencyToken>) {
+        if let Some(token) = token {
+            let mut latest = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            *latest = Some(ConsistencyToken::latest(latest.take(), token));
+        }
+    }
+
+    fn token(&self) -> Option<ConsistencyToken> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // The writes after the snapshot restored are gone.
+    fn reset(&self) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct TeaclaveManagementService {
     storage_client: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+    latest_write: LatestWrite,
+    access_control_client: Arc<Mutex<TeaclaveAccessControlClient<Channel>>>,
     auditor: audit::Auditor,
+    decommission: StorageDecommission,
+    metadata_signer: MetadataSigner,
+    fusion_base: PathBuf,
+    max_queue_depth: u32,
+    webhook: WebhookConfig,
+    function_payload: FunctionPayloadConfig,
+    // Defaults of the tasks running Python functions
+    resource_limits: ResourceLimits,
 }
 
 #[teaclave_rpc::async_trait]
@@ -98,7 +184,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
             old_input_file.cmac,
             old_input_file.crypto_info,
             old_input_file.owner,
-        );
+        )
+        .access_policy(old_input_file.access_policy.unwrap_or_default());
 
         self.write_to_db(&input_file).await?;
 
@@ -121,7 +208,9 @@ impl TeaclaveManagement for TeaclaveManagementService {
                 .try_into()
                 .map_err(tonic_error)?,
             vec![user_id],
-        );
+        )
+        .bind_context(request.bind_context)
+        .retention(request.retention_secs);
 
         self.write_to_db(&output_file).await?;
 
@@ -149,11 +238,16 @@ impl TeaclaveManagement for TeaclaveManagementService {
             ManagementServiceError::PermissionDenied
         );
 
-        let output_file = TeaclaveOutputFile::new(
-            Url::parse(&request.url).map_err(tonic_error)?,
-            old_output_file.crypto_info,
-            old_output_file.owner,
-        );
+        let output_file = TeaclaveOutputFile {
+            expires_at: old_output_file.expires_at,
+            ..TeaclaveOutputFile::new(
+                Url::parse(&request.url).map_err(tonic_error)?,
+                old_output_file.crypto_info,
+                old_output_file.owner,
+            )
+            .bind_context(old_output_file.bind_context)
+            .output_policies(old_output_file.output_policies)
+        };
 
         self.write_to_db(&output_file).await?;
 
@@ -166,14 +260,20 @@ impl TeaclaveManagement for TeaclaveManagementService {
         request: Request<RegisterFusionOutputRequest>,
     ) -> TeaclaveServiceResponseResult<RegisterFusionOutputResponse> {
         let user_id = get_request_user_id(&request)?.to_string();
+        let request = request.into_inner();
 
-        let owner_list = request.into_inner().owner_list;
+        let output_policies = request
+            .policies()
+            .map_err(|e| ManagementServiceError::InvalidOutputPolicy(e.to_string()))?;
+        let owner_list = request.owner_list;
         ensure!(
             owner_list.len() > 1 && owner_list.contains(&user_id),
             ManagementServiceError::PermissionDenied
         );
 
-        let output_file = create_fusion_data(owner_list).map_err(tonic_error)?;
+        let output_file = create_fusion_data(owner_list)
+            .map_err(tonic_error)?
+            .output_policies(output_policies);
 
         self.write_to_db(&output_file).await?;
 
@@ -203,11 +303,15 @@ impl TeaclaveManagement for TeaclaveManagementService {
             output.owner.contains(&user_id),
             ManagementServiceError::PermissionDenied
         );
+        ensure!(!output.is_expired(), ManagementServiceError::OutputExpired);
+        self.authorize_data(&user_id, &data_id).await?;
 
         let input = TeaclaveInputFile::from_output(output)
             .map_err(|_| ManagementServiceError::InvalidOutputFile)?;
 
         self.write_to_db(&input).await?;
+        let lineage = LineageRecord::from_output(input.external_id(), data_id);
+        self.write_lineage(&lineage).await?;
 
         let response = RegisterInputFromOutputResponse::new(input.external_id());
         Ok(Response::new(response))
@@ -232,8 +336,16 @@ impl TeaclaveManagement for TeaclaveManagementService {
             output_file.owner.contains(&user_id),
             ManagementServiceError::PermissionDenied
         );
+        ensure!(
+            !output_file.is_expired(),
+            ManagementServiceError::OutputExpired
+        );
 
-        let response = GetOutputFileResponse::new(output_file.owner, output_file.cmac);
+        let response = GetOutputFileResponse::new(output_file.owner, output_file.cmac)
+            .release_verdict(output_file.release_verdict)
+            .context(output_file.context)
+            .output_policies(output_file.output_policies)
+            .expires_at(output_file.expires_at);
         Ok(Response::new(response))
     }
 
@@ -257,7 +369,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
             ManagementServiceError::PermissionDenied
         );
 
-        let response = GetInputFileResponse::new(input_file.owner, input_file.cmac);
+        let response = GetInputFileResponse::new(input_file.owner, input_file.cmac)
+            .access_policy(input_file.access_policy);
         Ok(Response::new(response))
     }
 
@@ -271,57 +384,131 @@ impl TeaclaveManagement for TeaclaveManagementService {
         let function = FunctionBuilder::try_from(request.into_inner())
             .map_err(tonic_error)?
             .id(Uuid::new_v4())
-            .owner(user_id.clone())
+            .owner(user_id)
             .build();
+        ensure!(
+            function.payload.len() as u64 <= self.function_payload.max_size_bytes,
+            ManagementServiceError::FunctionPayloadTooLarge(self.function_payload.max_size_bytes)
+        );
+        self.add_function(&function).await?;
 
-        self.write_to_db(&function).await?;
+        let response = RegisterFunctionResponse::new(function.external_id());
+        Ok(Response::new(response))
+    }
 
-        let mut u = User {
-            id: user_id,
-            ..Default::default()
+    // access control: none
+    async fn begin_function_upload(
+        &self,
+        request: Request<BeginFunctionUploadRequest>,
+    ) -> TeaclaveServiceResponseResult<BeginFunctionUploadResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let request = request.into_inner();
+        let max_size = self.function_payload.max_size_bytes;
+        ensure!(
+            request.total_size <= max_size,
+            ManagementServiceError::FunctionPayloadTooLarge(max_size)
+        );
+        let upload = FunctionUpload::new(user_id, request.total_size, request.sha256, max_size)
+            .map_err(|e| ManagementServiceError::InvalidFunctionUpload(e.to_string()))?;
+        self.write_upload_to_db(upload.key(), upload.to_vec()?, &upload)
+            .await?;
+
+        let response = BeginFunctionUploadResponse::new(
+            upload.external_id(),
+            self.function_payload.max_chunk_size_bytes,
+        );
+        Ok(Response::new(response))
+    }
+
+    // access control: upload.owner == user_id
+    async fn upload_function_chunk(
+        &self,
+        request: Request<UploadFunctionChunkRequest>,
+    ) -> TeaclaveServiceResponseResult<UploadFunctionChunkResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let request = request.into_inner();
+        let mut upload = self
+            .read_function_upload(&request.upload_id, &user_id)
+            .await?;
+        let index = upload
+            .accept_chunk(
+                request.offset,
+                request.data.len() as u64,
+                self.function_payload.max_chunk_size_bytes,
+            )
+            .map_err(|e| ManagementServiceError::InvalidFunctionUpload(e.to_string()))?;
+
+        // A chunk written without the upload updated is overwritten when the
+        // chunk is uploaded again.
+        self.write_upload_to_db(upload.chunk_key(index), request.data, &upload)
+            .await?;
+        self.write_upload_to_db(upload.key(), upload.to_vec()?, &upload)
+            .await?;
+
+        let response = UploadFunctionChunkResponse {
+            received_size: upload.received_size,
         };
-        let external_id = u.external_id();
+        Ok(Response::new(response))
+    }
 
-        let user = self.read_from_db::<User>(&external_id).await;
-        match user {
-            Ok(mut us) => {
-                us.registered_functions
-                    .push(function.external_id().to_string());
-                self.write_to_db(&us).await?;
-            }
-            Err(_) => {
-                u.registered_functions
-                    .push(function.external_id().to_string());
-                self.write_to_db(&u).await?;
-            }
-        }
+    // access control: upload.owner == user_id
+    async fn commit_function(
+        &self,
+        request: Request<CommitFunctionRequest>,
+    ) -> TeaclaveServiceResponseResult<RegisterFunctionResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let request = request.into_inner();
+        let upload = self
+            .read_function_upload(&request.upload_id, &user_id)
+            .await?;
+        let function = request.function.ok_or_else(|| {
+            ManagementServiceError::InvalidFunctionUpload("missing function".into())
+        })?;
+        ensure!(
+            function.payload.is_empty(),
+            ManagementServiceError::InvalidFunctionUpload("payload is not empty".into())
+        );
 
-        // Update allowed function list for users
-        for user_id in &function.user_allowlist {
-            let mut u = User {
-                id: user_id.into(),
-                ..Default::default()
-            };
-            let external_id = u.external_id();
-            let user = self.read_from_db::<User>(&external_id).await;
-            match user {
-                Ok(mut us) => {
-                    us.allowed_functions
-                        .push(function.external_id().to_string());
-                    self.write_to_db(&us).await?;
-                }
-                Err(_) => {
-                    u.allowed_functions.push(function.external_id().to_string());
-                    self.write_to_db(&u).await?;
-                }
-            }
+        let mut payload = Vec::with_capacity(upload.received_size as usize);
+        for index in 0..upload.chunks {
+            let request = GetRequest::new(upload.chunk_key(index)).after(self.latest_write.token());
+            let chunk = self
+                .storage_client
+                .clone()
+                .lock()
+                .await
+                .get(request)
+                .await
+                .map_err(|_| ManagementServiceError::FunctionUploadNotFound)?
+                .into_inner()
+                .value;
+            payload.extend_from_slice(&chunk);
         }
+        upload
+            .verify(&payload)
+            .map_err(|e| ManagementServiceError::InvalidFunctionUpload(e.to_string()))?;
 
-        let usage = FunctionUsage {
-            function_id: function.id,
-            ..Default::default()
-        };
-        self.write_to_db(&usage).await?;
+        let function = FunctionBuilder::try_from(function)
+            .map_err(tonic_error)?
+            .id(Uuid::new_v4())
+            .owner(user_id)
+            .payload(payload)
+            .build();
+        self.add_function(&function).await?;
+
+        let mut batch = WriteBatchRequest::new().delete(upload.key());
+        for index in 0..upload.chunks {
+            batch = batch.delete(upload.chunk_key(index));
+        }
+        let response = self
+            .storage_client
+            .clone()
+            .lock()
+            .await
+            .write_batch(batch)
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        self.latest_write.observe(response.into_inner().token);
 
         let response = RegisterFunctionResponse::new(function.external_id());
         Ok(Response::new(response))
@@ -353,6 +540,10 @@ impl TeaclaveManagement for TeaclaveManagementService {
             .map_err(tonic_error)?
             .owner(user_id)
             .build();
+        ensure!(
+            function.payload.len() as u64 <= self.function_payload.max_size_bytes,
+            ManagementServiceError::FunctionPayloadTooLarge(self.function_payload.max_size_bytes)
+        );
 
         self.write_to_db(&function).await?;
 
@@ -424,9 +615,59 @@ impl TeaclaveManagement for TeaclaveManagementService {
             .await
             .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
         let function_quota = function.usage_quota.unwrap_or(-1);
-        let response = GetFunctionUsageStatsResponse {
+        let mut response = GetFunctionUsageStatsResponse {
             function_quota,
             current_usage: function_usage.use_numbers,
+            ..Default::default()
+        };
+
+        // Phase timings may reveal properties of the function's logic, so
+        // they are only shown to its owner.
+        if role == UserRole::PlatformAdmin || function.owner == user_id {
+            let profile = FunctionProfile::new(function.id);
+            if let Ok(profile) = self
+                .read_from_db::<FunctionProfile>(&profile.external_id())
+                .await
+            {
+                response.profiled_tasks = profile.profiled_tasks;
+                response.average_profile = profile.average().map(|p| p.into());
+            }
+        }
+        Ok(Response::new(response))
+    }
+
+    // access control: function_owner
+    async fn query_function_usage_records(
+        &self,
+        request: Request<QueryFunctionUsageRecordsRequest>,
+    ) -> TeaclaveServiceResponseResult<QueryFunctionUsageRecordsResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let role = get_request_role(&request)?;
+        let request = request.into_inner();
+        let function_id: ExternalID = request
+            .function_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
+        let function: Function = self
+            .read_from_db(&function_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
+
+        ensure!(
+            role == UserRole::PlatformAdmin || function.owner == user_id,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let records = self
+            .read_usage_records_from_db(
+                &function.id,
+                request.start_time,
+                request.end_time,
+                request.limit as usize,
+            )
+            .await?;
+        let response = QueryFunctionUsageRecordsResponse {
+            records: records.into_iter().map(FunctionUsageRecord::from).collect(),
         };
         Ok(Response::new(response))
     }
@@ -436,8 +677,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
         request: Request<DeleteFunctionRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
         let user_id = get_request_user_id(&request)?;
-        let function_id = request
-            .into_inner()
+        let request = request.into_inner();
+        let function_id: ExternalID = request
             .function_id
             .try_into()
             .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
@@ -450,6 +691,12 @@ impl TeaclaveManagement for TeaclaveManagementService {
             function.owner == user_id,
             ManagementServiceError::PermissionDenied
         );
+        let dependents = self
+            .find_pending_tasks(|ts| ts.function_id == function_id)
+            .await?;
+        let reason = format!("Task canceled: function {} deleted", function_id);
+        self.resolve_dependents(dependents, request.cascade, &reason)
+            .await?;
         self.delete_from_db(&function_id)
             .await
             .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
@@ -562,6 +809,64 @@ impl TeaclaveManagement for TeaclaveManagementService {
         }
     }
 
+    // Only functions visible to the requester are returned: public ones,
+    // those owned by the requester, and those the requester is allowed to use.
+    async fn search_functions(
+        &self,
+        request: Request<SearchFunctionsRequest>,
+    ) -> TeaclaveServiceResponseResult<SearchFunctionsResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let role = get_request_role(&request)?;
+        let request = request.into_inner();
+
+        let is_match = |f: &Function| {
+            (f.public
+                || role == UserRole::PlatformAdmin
+                || f.owner == user_id
+                || f.user_allowlist.contains(&user_id.to_string()))
+                && (request.name.is_empty() || f.name.contains(&request.name))
+                && (request.tag.is_empty() || f.tags.contains(&request.tag))
+                && (request.owner.is_empty() || f.owner.to_string() == request.owner)
+        };
+
+        if let Some(cursor) = &request.cursor {
+            let (functions, next_cursor) = self
+                .read_page_from_db(&cursor.after, request.limit as usize, is_match)
+                .await?;
+            let response = SearchFunctionsResponse {
+                functions: functions.iter().map(FunctionSummary::from).collect(),
+                total: 0,
+                next_cursor,
+            };
+            return Ok(Response::new(response));
+        }
+
+        let mut functions: Vec<Function> = self
+            .read_all_from_db::<Function>()
+            .await?
+            .into_iter()
+            .filter(is_match)
+            .collect();
+        functions.sort_by(|a, b| (&a.name, &a.version, a.id).cmp(&(&b.name, &b.version, b.id)));
+
+        let total = functions.len();
+        let limit = match request.limit as usize {
+            0 => total,
+            limit => limit,
+        };
+        let response = SearchFunctionsResponse {
+            functions: functions
+                .iter()
+                .skip(request.offset as usize)
+                .take(limit)
+                .map(FunctionSummary::from)
+                .collect(),
+            total: total as u64,
+            next_cursor: None,
+        };
+        Ok(Response::new(response))
+    }
+
     // access control: none
     // when a task is created, following rules will be verified:
     // 1) arugments match function definition
@@ -598,19 +903,50 @@ impl TeaclaveManagement for TeaclaveManagementService {
                 return Err(ManagementServiceError::PermissionDenied.into());
             }
         }
+        let function_arguments = match request.typed_function_arguments {
+            Some(typed_arguments) => typed_arguments.try_into().map_err(|e: anyhow::Error| {
+                ManagementServiceError::InvalidFunctionArguments(e.to_string())
+            })?,
+            None => request.function_arguments.try_into().map_err(tonic_error)?,
+        };
+        let executor_type = function.executor_type;
         let task = Task::<Create>::new(
             user_id,
             request.executor.try_into().map_err(tonic_error)?,
-            request.function_arguments.try_into().map_err(tonic_error)?,
+            function_arguments,
             from_proto_ownership(request.inputs_ownership),
             from_proto_ownership(request.outputs_ownership),
             function,
         )
-        .map_err(|_| ManagementServiceError::InvalidTask)?;
+        .map_err(|_| ManagementServiceError::InvalidTask)?
+        .purpose(request.purpose);
+        let task = match request.approval_expiry_secs {
+            0 => task,
+            secs => task.approval_expiry(std::time::Duration::from_secs(secs)),
+        };
+        let task = match request.schedule.as_str() {
+            "" => task,
+            expression => {
+                let cron = CronSchedule::parse(expression)
+                    .map_err(|e| ManagementServiceError::InvalidSchedule(e.to_string()))?;
+                task.schedule(cron)
+            }
+        };
+        let task = task.timeouts(TaskTimeouts::new(
+            request.staging_timeout_secs,
+            request.execution_timeout_secs,
+            request.upload_timeout_secs,
+        ));
+        let limits: ResourceLimits = request.resource_limits.map(Into::into).unwrap_or_default();
+        let task = match executor_type {
+            ExecutorType::Python => task.resource_limits(limits.or(self.resource_limits)),
+            _ if limits.is_unlimited() => task,
+            _ => return Err(ManagementServiceError::InvalidResourceLimits.into()),
+        };
 
         log::debug!("CreateTask: {:?}", task);
         let ts: TaskState = task.into();
-        self.write_to_db(&ts).await?;
+        self.write_task_to_db(&ts).await?;
 
         let response = CreateTaskResponse::new(ts.external_id());
         Ok(Response::new(response))
@@ -651,7 +987,117 @@ impl TeaclaveManagement for TeaclaveManagementService {
             assigned_inputs: to_proto_file_ids(ts.assigned_inputs.external_ids()),
             assigned_outputs: to_proto_file_ids(ts.assigned_outputs.external_ids()),
             result: Some(ts.result.into()),
+            approvals: ts
+                .participants
+                .clone()
+                .into_iter()
+                .map(|user_id| ParticipantApproval::from_task_state(&ts, &user_id))
+                .collect(),
+            approval_deadline: ts.approval_deadline,
             status: i32_from_task_status(ts.status),
+            progress: ts.progress.map(Into::into),
+            schedule: ts
+                .schedule
+                .as_ref()
+                .map(|s| s.cron.expression().to_string())
+                .unwrap_or_default(),
+            next_run_at: ts
+                .schedule
+                .as_ref()
+                .map(|s| s.next_run_at)
+                .unwrap_or_default(),
+            scheduled_runs: ts
+                .schedule
+                .iter()
+                .flat_map(|s| s.runs.iter().map(Into::into))
+                .collect(),
+            scheduled_from: ts
+                .scheduled_from
+                .map(|id| ExternalID::new(TaskState::key_prefix(), id).to_string())
+                .unwrap_or_default(),
+            purpose: ts.purpose,
+        };
+        Ok(Response::new(response))
+    }
+
+    // access control: platform admins see all the tasks, and other users see
+    // the ones they participate in
+    async fn list_tasks(
+        &self,
+        request: Request<ListTasksRequest>,
+    ) -> TeaclaveServiceResponseResult<ListTasksResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let role = get_request_role(&request)?;
+        let request = request.into_inner();
+
+        let statuses = request
+            .statuses
+            .iter()
+            .map(|status| i32_to_task_status(*status))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|_| ManagementServiceError::InvalidTaskStatus)?;
+        let is_visible = |t: &TaskState| {
+            (role == UserRole::PlatformAdmin || t.has_participant(&user_id))
+                && (request.creator.is_empty() || t.creator.to_string() == request.creator)
+                && (statuses.is_empty() || statuses.contains(&t.status))
+                && (request.created_after == 0 || t.created_at >= request.created_after)
+                && (request.created_before == 0 || t.created_at < request.created_before)
+        };
+        let indexed = self.find_tasks_by_index(&request, &statuses).await?;
+
+        if let Some(cursor) = &request.cursor {
+            let (tasks, next_cursor) = match indexed {
+                Some(task_ids) => {
+                    self.read_task_page_from_db(
+                        task_ids,
+                        &cursor.after,
+                        request.limit as usize,
+                        is_visible,
+                    )
+                    .await?
+                }
+                None => {
+                    self.read_page_from_db(&cursor.after, request.limit as usize, is_visible)
+                        .await?
+                }
+            };
+            let response = ListTasksResponse {
+                tasks: tasks.iter().map(TaskSummary::from).collect(),
+                total: 0,
+                next_cursor,
+            };
+            return Ok(Response::new(response));
+        }
+
+        let mut tasks: Vec<TaskState> = match indexed {
+            Some(task_ids) => {
+                self.read_task_page_from_db(task_ids, "", 0, is_visible)
+                    .await?
+                    .0
+            }
+            None => self
+                .read_all_from_db::<TaskState>()
+                .await?
+                .into_iter()
+                .filter(is_visible)
+                .collect(),
+        };
+        tasks.sort_by_key(|t| t.task_id);
+
+        let total = tasks.len();
+        let limit = match request.limit as usize {
+            0 => total,
+            limit => limit,
+        };
+        let response = ListTasksResponse {
+            tasks: tasks
+                .iter()
+                .skip(request.offset as usize)
+                .take(limit)
+                .map(TaskSummary::from)
+                .collect(),
+            total: total as u64,
+            next_cursor: None,
         };
         Ok(Response::new(response))
     }
@@ -666,6 +1112,9 @@ impl TeaclaveManagement for TeaclaveManagementService {
     //    * inputs_ownership or outputs_ownership contains the data name
     //    * input file: OwnerList match input_file.owner
     //    * output file: OwnerList match output_file.owner
+    //    * input file: the access policy of the file allows the function
+    // The task is approved for the user once all the inputs of the user are
+    // assigned with files having access policies.
     async fn assign_data(
         &self,
         request: Request<AssignDataRequest>,
@@ -706,14 +1155,34 @@ impl TeaclaveManagement for TeaclaveManagementService {
                 .read_from_db(data_id)
                 .await
                 .map_err(|_| ManagementServiceError::InvalidDataId)?;
+            ensure!(!file.is_expired(), ManagementServiceError::OutputExpired);
             task.assign_output(&user_id, data_name, file)
                 .map_err(|_| ManagementServiceError::PermissionDenied)?;
         }
+        let approved = task
+            .approve_by_access_policy(&user_id)
+            .map_err(|_| ManagementServiceError::ApprovalExpired)?;
 
         log::debug!("AssignData: {:?}", task);
 
-        let ts: TaskState = task.into();
-        self.write_to_db(&ts).await?;
+        let mut ts: TaskState = task.into();
+        if approved {
+            let function: Function = self
+                .read_from_db(&ts.function_id)
+                .await
+                .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
+            let consent = ConsentRecord::new(&ts, &user_id, &function);
+            self.write_to_db(&consent).await?;
+            // The approval may be the last one the task waits for.
+            if ts.status == TaskStatus::DataAssigned {
+                let task: Task<Approve> = ts.try_into().map_err(|e| {
+                    log::warn!("Approve state error: {:?}", e);
+                    ManagementServiceError::TaskApproveError
+                })?;
+                ts = task.into();
+            }
+        }
+        self.write_task_to_db(&ts).await?;
 
         Ok(Response::new(()))
     }
@@ -726,9 +1195,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
         request: Request<ApproveTaskRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
         let user_id = get_request_user_id(&request)?;
-
+        let request = request.into_inner();
         let task_id = request
-            .into_inner()
             .task_id
             .try_into()
             .map_err(|_| ManagementServiceError::InvalidTaskId)?;
@@ -737,91 +1205,323 @@ impl TeaclaveManagement for TeaclaveManagementService {
             .read_from_db(&task_id)
             .await
             .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+        ensure!(
+            !ts.approval_expired(),
+            ManagementServiceError::ApprovalExpired
+        );
+        // The approval holds for the inputs of the pinned content only.
+        for pinned in &request.input_cmacs {
+            let assigned = ts.assigned_inputs.get(&pinned.data_name);
+            ensure!(
+                matches!(assigned, Some(file) if file.cmac.to_bytes() == pinned.cmac),
+                ManagementServiceError::InputCmacMismatch(pinned.data_name.clone())
+            );
+        }
 
         let mut task: Task<Approve> = ts.try_into().map_err(|e| {
             log::warn!("Approve state error: {:?}", e);
             ManagementServiceError::TaskApproveError
         })?;
 
-        task.approve(&user_id)
+        task.approve(&user_id, request.comment)
             .map_err(|_| ManagementServiceError::PermissionDenied)?;
 
         log::debug!("ApproveTask: approve:{:?}", task);
 
         let ts: TaskState = task.into();
-        self.write_to_db(&ts).await?;
+        let function: Function = self
+            .read_from_db(&ts.function_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
+
+        // The consent record is written before the approval so that an
+        // approved task always has the matching evidence.
+        let consent = ConsentRecord::new(&ts, &user_id, &function);
+        self.write_to_db(&consent).await?;
+        self.write_task_to_db(&ts).await?;
 
         Ok(Response::new(()))
     }
 
     // prerequisite:
-    // 1) task status == Approved
-    // 2) user_id == task.creator
-    async fn invoke_task(
+    // 1) task status == Created or DataAssigned
+    // 2) user_id in task.participants
+    async fn reject_task(
         &self,
-        request: Request<InvokeTaskRequest>,
+        request: Request<RejectTaskRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
         let user_id = get_request_user_id(&request)?;
+        let request = request.into_inner();
         let task_id = request
-            .into_inner()
             .task_id
             .try_into()
             .map_err(|_| ManagementServiceError::InvalidTaskId)?;
 
-        let ts: TaskState = self
+        let mut ts: TaskState = self
             .read_from_db(&task_id)
             .await
             .map_err(|_| ManagementServiceError::InvalidTaskId)?;
-
-        // Early validation
         ensure!(
-            ts.has_creator(&user_id),
+            ts.has_participant(&user_id),
             ManagementServiceError::PermissionDenied
         );
+        ensure!(
+            !ts.approval_expired(),
+            ManagementServiceError::ApprovalExpired
+        );
 
-        let function: Function = self
-            .read_from_db(&ts.function_id)
-            .await
-            .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
-
-        log::debug!("InvokeTask: get function: {:?}", function);
+        ts.reject(&user_id, request.reason)
+            .map_err(|e| ManagementServiceError::TaskRejectError(e.to_string()))?;
 
-        let usage = FunctionUsage {
-            function_id: function.id,
-            ..Default::default()
-        };
-        let external_id = usage.external_id();
-        let mut function_usage = self
-            .read_from_db::<FunctionUsage>(&external_id)
-            .await
-            .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
-        let function_current_use_numbers = function_usage.use_numbers;
+        log::debug!("RejectTask: reject:{:?}", ts);
+        self.write_task_to_db(&ts).await?;
 
-        if let Some(quota) = function.usage_quota {
-            if quota <= function_current_use_numbers {
-                return Err(ManagementServiceError::FunctionQuotaError.into());
-            }
-        }
+        Ok(Response::new(()))
+    }
 
-        let mut task: Task<Stage> = ts.try_into().map_err(|e| {
-            log::warn!("Stage state error: {:?}", e);
-            ManagementServiceError::TaskInvokeError
+    // access control:
+    // 1) PlatformAdmin can read the records of all participants
+    // 2) other participants can only read their own record
+    async fn get_consent_records(
+        &self,
+        request: Request<GetConsentRecordsRequest>,
+    ) -> TeaclaveServiceResponseResult<GetConsentRecordsResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let role = get_request_role(&request)?;
+        let task_id = request
+            .into_inner()
+            .task_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+        let ts: TaskState = self
+            .read_from_db(&task_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+
+        let owners: Vec<UserID> = if role == UserRole::PlatformAdmin {
+            ts.participants.clone().into_iter().collect()
+        } else {
+            ensure!(
+                ts.has_participant(&user_id),
+                ManagementServiceError::PermissionDenied
+            );
+            vec![user_id]
+        };
+
+        let mut records = Vec::new();
+        for owner in owners {
+            let external_id = ConsentRecord::external_id_of(&ts.task_id, &owner);
+            if let Ok(record) = self.read_from_db::<ConsentRecord>(&external_id).await {
+                records.push(record.into());
+            }
+        }
+
+        let response = GetConsentRecordsResponse { records };
+        Ok(Response::new(response))
+    }
+
+    // access control: user_id == task.creator
+    async fn get_task_log(
+        &self,
+        request: Request<GetTaskLogRequest>,
+    ) -> TeaclaveServiceResponseResult<GetTaskLogResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let task_id = request
+            .into_inner()
+            .task_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+        let ts: TaskState = self
+            .read_from_db(&task_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+        ensure!(
+            ts.creator == user_id,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let log_file = ts.log_file.ok_or(ManagementServiceError::TaskLogNotFound)?;
+        Ok(Response::new(GetTaskLogResponse::new(log_file)))
+    }
+
+    // access control:
+    // 1) user_id == task.creator
+    // 2) the host of the url is allowed in the namespace of the user role
+    // 3) task.status < Staged
+    async fn register_webhook_sink(
+        &self,
+        request: Request<RegisterWebhookSinkRequest>,
+    ) -> TeaclaveServiceResponseResult<RegisterWebhookSinkResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let role = get_request_role(&request)?;
+        let request = request.into_inner();
+        let task_id = request
+            .task_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+        let url = Url::parse(&request.url)
+            .map_err(|e| ManagementServiceError::InvalidWebhookSink(e.to_string()))?;
+        let sink = WebhookSink::new(url, request.secret, user_id.clone())
+            .map_err(|e| ManagementServiceError::InvalidWebhookSink(e.to_string()))?;
+
+        let namespace = match &role {
+            UserRole::DataOwnerManager(namespace) | UserRole::DataOwner(namespace) => namespace,
+            _ => return Err(ManagementServiceError::PermissionDenied.into()),
+        };
+        let host = sink.url.host_str().unwrap_or_default();
+        ensure!(
+            self.webhook.is_allowed(namespace, host),
+            ManagementServiceError::WebhookNotAllowed(host.to_string())
+        );
+
+        let mut ts: TaskState = self
+            .read_from_db(&task_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+        ensure!(
+            ts.creator == user_id,
+            ManagementServiceError::PermissionDenied
+        );
+        ensure!(
+            matches!(
+                ts.status,
+                TaskStatus::Created | TaskStatus::DataAssigned | TaskStatus::Approved
+            ),
+            ManagementServiceError::InvalidTaskStatus
+        );
+        ensure!(
+            ts.webhook_sinks.len() < MAX_WEBHOOK_SINKS_PER_TASK,
+            ManagementServiceError::InvalidWebhookSink("too many webhook sinks".to_string())
+        );
+
+        self.write_to_db(&sink).await?;
+        ts.webhook_sinks.push(sink.external_id());
+        self.write_task_to_db(&ts).await?;
+
+        let response = RegisterWebhookSinkResponse::new(sink.external_id());
+        Ok(Response::new(response))
+    }
+
+    // prerequisite:
+    // 1) task status == Approved
+    // 2) user_id == task.creator
+    async fn invoke_task(
+        &self,
+        request: Request<InvokeTaskRequest>,
+    ) -> TeaclaveServiceResponseResult<InvokeTaskResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let trace_id = get_request_trace_id(&request);
+        let task_id = request
+            .into_inner()
+            .task_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+
+        let ts: TaskState = self
+            .read_from_db(&task_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidTaskId)?;
+
+        // Early validation
+        ensure!(
+            ts.has_creator(&user_id),
+            ManagementServiceError::PermissionDenied
+        );
+
+        let function: Function = self
+            .read_from_db(&ts.function_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
+
+        log::debug!("InvokeTask: get function: {:?}", function);
+
+        let usage = FunctionUsage {
+            function_id: function.id,
+            ..Default::default()
+        };
+        let external_id = usage.external_id();
+        let mut function_usage = self
+            .read_from_db::<FunctionUsage>(&external_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidFunctionId)?;
+        let function_current_use_numbers = function_usage.use_numbers;
+
+        if let Some(quota) = function.usage_quota {
+            if quota <= function_current_use_numbers {
+                return Err(ManagementServiceError::FunctionQuotaError.into());
+            }
+        }
+
+        self.authorize_staged_task(&ts, &user_id).await?;
+
+        let mut task: Task<Stage> = ts.try_into().map_err(|e| {
+            log::warn!("Stage state error: {:?}", e);
+            ManagementServiceError::TaskInvokeError
         })?;
 
         log::debug!("InvokeTask: get task: {:?}", task);
-        let staged_task = task
+        let mut staged_task = task
             .stage_for_running(&user_id, function)
             .map_err(|_| ManagementServiceError::PermissionDenied)?;
+        // The scheduler picks the trace up from the staged task
+        staged_task.trace_id = trace_id.clone();
         log::debug!("InvokeTask: staged task: {:?}", staged_task);
-        self.enqueue_to_db(StagedTask::get_queue_key().as_bytes(), &staged_task)
-            .await?;
 
-        let ts: TaskState = task.into();
-        self.write_to_db(&ts).await?;
+        let mut ts: TaskState = task.into();
+        let response = match ts.schedule.as_mut() {
+            // The scheduler service keeps the staged task, and runs it as new
+            // tasks on schedule.
+            Some(schedule) => {
+                schedule
+                    .activate()
+                    .map_err(|e| ManagementServiceError::InvalidSchedule(e.to_string()))?;
+                log::info!(
+                    trace_id = trace_id.as_str();
+                    "InvokeTask: task {} scheduled at {}",
+                    task_id,
+                    schedule.next_run_at
+                );
+                self.enqueue_to_db(
+                    SCHEDULE_QUEUE_KEY.as_bytes(),
+                    &ScheduledTask::new(staged_task),
+                )
+                .await?;
+                InvokeTaskResponse::default()
+            }
+            None => match self.active_maintenance_window().await? {
+                Some(window) if window.mode == teaclave_types::MaintenanceMode::RejectTasks => {
+                    let retry_after = window.retry_after(now_secs());
+                    return Err(ManagementServiceError::UnderMaintenance(retry_after).into());
+                }
+                // Held until the window ends, so that executors are drained
+                // without losing the task.
+                Some(window) => {
+                    log::info!(
+                        trace_id = trace_id.as_str();
+                        "InvokeTask: task {} held until {}",
+                        task_id,
+                        window.ends_at
+                    );
+                    self.enqueue_to_db(MAINTENANCE_QUEUE_KEY.as_bytes(), &staged_task)
+                        .await?;
+                    let mut response = self.queue_position(self.get_queue_length().await?).await;
+                    response.eta_secs += window.retry_after(now_secs());
+                    response
+                }
+                None => {
+                    let depth = self.check_queue_depth(&trace_id).await?;
+                    log::info!(trace_id = trace_id.as_str(); "InvokeTask: task {} staged", task_id);
+                    self.enqueue_to_db(StagedTask::get_queue_key().as_bytes(), &staged_task)
+                        .await?;
+                    self.queue_position(depth).await
+                }
+            },
+        };
+        self.write_task_to_db(&ts).await?;
 
         function_usage.use_numbers = function_current_use_numbers + 1;
         self.write_to_db(&function_usage).await?;
-        Ok(Response::new(()))
+        Ok(Response::new(response))
     }
 
     async fn cancel_task(
@@ -845,35 +1545,7 @@ impl TeaclaveManagement for TeaclaveManagementService {
             ManagementServiceError::PermissionDenied
         );
 
-        match ts.status {
-            // need scheduler to cancel the task
-            TaskStatus::Staged | TaskStatus::Running => {
-                self.enqueue_to_db(CANCEL_QUEUE_KEY.as_bytes(), &ts).await?;
-            }
-            _ => {
-                // early cancelation
-                // race will not affect correctness/privacy
-                let mut task: Task<Cancel> = ts.try_into().map_err(|e| {
-                    log::warn!("Cancel state error: {:?}", e);
-                    ManagementServiceError::TaskCancelError(
-                        "task has already been canceled".to_string(),
-                    )
-                })?;
-
-                log::debug!("Canceled Task: {:?}", task);
-
-                task.update_result(TaskResult::Err(TaskFailure {
-                    reason: "Task canceled".to_string(),
-                }))
-                .map_err(|_| {
-                    ManagementServiceError::TaskCancelError("cannot update result".to_string())
-                })?;
-                let ts: TaskState = task.into();
-                self.write_to_db(&ts).await?;
-
-                log::warn!("Canceled Task: writtenback");
-            }
-        }
+        self.cancel_task_state(ts, "Task canceled").await?;
 
         Ok(Response::new(()))
     }
@@ -914,10 +1586,19 @@ impl TeaclaveManagement for TeaclaveManagementService {
             ManagementServiceError::PermissionDenied
         );
 
+        ensure!(
+            self.auditor.is_available(),
+            ManagementServiceError::AuditIndexUnavailable
+        );
+        self.flush_file_transfers().await;
         let request = request.into_inner();
         let auditor = self.auditor.clone();
-        let logs = task::spawn_blocking(move || {
-            auditor.query_logs(&request.query, request.limit as usize)
+        let (logs, total) = task::spawn_blocking(move || {
+            auditor.search_logs(
+                &request.query,
+                request.offset as usize,
+                request.limit as usize,
+            )
         })
         .await
         .map_err(|e| anyhow!("{}", e.to_string()))
@@ -927,101 +1608,1273 @@ impl TeaclaveManagement for TeaclaveManagementService {
             ManagementServiceError::AuditError(err_msg)
         })?;
 
-        let response = QueryAuditLogsResponse::new(logs);
+        let response = QueryAuditLogsResponse::new(logs, total);
         Ok(Response::new(response))
     }
-}
 
-impl TeaclaveManagementService {
-    pub(crate) async fn new(storage_service_endpoint: Endpoint) -> anyhow::Result<Self> {
-        let channel = storage_service_endpoint
-            .connect()
-            .await
-            .map_err(|e| anyhow!("Failed to connect to storage service, {:?}", e))?;
-        let storage_client = Arc::new(Mutex::new(TeaclaveStorageClient::new_with_builtin_config(
-            channel,
-        )));
-        let client_clone = storage_client.clone();
-        let auditor = task::spawn_blocking(move || Auditor::try_new(client_clone)).await??;
-        let service = Self {
-            storage_client,
-            auditor,
+    // access control: role == PlatformAdmin
+    async fn get_platform_stats(
+        &self,
+        request: Request<GetPlatformStatsRequest>,
+    ) -> TeaclaveServiceResponseResult<GetPlatformStatsResponse> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let functions = self.count_in_db::<Function>().await?;
+        let input_files = self.count_in_db::<TeaclaveInputFile>().await?;
+        let output_files = self.count_in_db::<TeaclaveOutputFile>().await?;
+        let tasks = self.read_all_from_db::<TaskState>().await?;
+        let mut counts: HashMap<i32, u64> = HashMap::new();
+        for task in &tasks {
+            *counts
+                .entry(i32_from_task_status(task.status.clone()))
+                .or_default() += 1;
+        }
+        let mut tasks_by_status: Vec<TaskStatusCount> = counts
+            .into_iter()
+            .map(|(status, count)| TaskStatusCount { status, count })
+            .collect();
+        tasks_by_status.sort_by_key(|c| c.status);
+
+        let response = GetPlatformStatsResponse {
+            functions,
+            input_files,
+            output_files,
+            tasks: tasks.len() as u64,
+            tasks_by_status,
+            queue_length: self.get_queue_length().await?,
+            max_queue_depth: self.max_queue_depth,
+        };
+        Ok(Response::new(response))
+    }
+
+    // access control:
+    // 1) role == PlatformAdmin
+    // 2) user_id in output.owner
+    async fn export_audit_logs(
+        &self,
+        request: Request<ExportAuditLogsRequest>,
+    ) -> TeaclaveServiceResponseResult<ExportAuditLogsResponse> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+        let user_id = get_request_user_id(&request)?;
+        let request = request.into_inner();
+
+        let format: export::ExportFormat = request
+            .format
+            .parse()
+            .map_err(|e| ManagementServiceError::InvalidAuditExport(e.to_string()))?;
+        let columns = export::select_columns(&request.columns)
+            .map_err(|e| ManagementServiceError::InvalidAuditExport(e.to_string()))?;
+
+        let output_id: ExternalID = request
+            .output_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        let mut output_file: TeaclaveOutputFile = self
+            .read_from_db(&output_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        ensure!(
+            output_file.owner.contains(&user_id),
+            ManagementServiceError::PermissionDenied
+        );
+        ensure!(
+            output_file.cmac.is_none(),
+            ManagementServiceError::InvalidOutputFile
+        );
+
+        ensure!(
+            self.auditor.is_available(),
+            ManagementServiceError::AuditIndexUnavailable
+        );
+        self.flush_file_transfers().await;
+        let auditor = self.auditor.clone();
+        let fusion_base = self.fusion_base.clone();
+        let output = output_file.clone();
+        let (rows, cmac) = task::spawn_blocking(move || {
+            let logs = auditor.query_logs(&request.query, export::row_cap(request.max_rows))?;
+            let bytes = export::render(&logs, format, &columns)?;
+            let cmac = export::upload(&bytes, &output, fusion_base)?;
+            Ok::<_, anyhow::Error>((logs.len(), cmac))
+        })
+        .await
+        .map_err(|e| anyhow!("{}", e.to_string()))
+        .flatten()
+        .map_err(|e| {
+            let err_msg = format!("failed to export logs {:?}", e);
+            ManagementServiceError::AuditError(err_msg)
+        })?;
+
+        output_file
+            .assign_cmac(&cmac)
+            .map_err(ManagementServiceError::Service)?;
+        self.write_to_db(&output_file).await?;
+
+        let response = ExportAuditLogsResponse::new(rows);
+        Ok(Response::new(response))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn rebuild_audit_index(
+        &self,
+        request: Request<RebuildAuditIndexRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+        ensure!(
+            self.auditor.try_begin_rebuild(),
+            ManagementServiceError::AuditIndexRebuildError("already in progress".to_string())
+        );
+
+        // The rebuild can take a while; progress is reported through
+        // get_audit_index_status.
+        let auditor = self.auditor.clone();
+        task::spawn_blocking(move || auditor.rebuild());
+
+        Ok(Response::new(()))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn get_audit_index_status(
+        &self,
+        request: Request<GetAuditIndexStatusRequest>,
+    ) -> TeaclaveServiceResponseResult<GetAuditIndexStatusResponse> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let status = self.auditor.rebuild_status();
+        let response = GetAuditIndexStatusResponse {
+            phase: status.phase.to_string(),
+            total_entries: status.total_entries,
+            indexed_entries: status.indexed_entries,
+            error: status.error,
+            available: self.auditor.is_available(),
+        };
+        Ok(Response::new(response))
+    }
+
+    // access control:
+    // 1) role == PlatformAdmin
+    // 2) user_id in output.owner
+    async fn export_metadata(
+        &self,
+        request: Request<ExportMetadataRequest>,
+    ) -> TeaclaveServiceResponseResult<ExportMetadataResponse> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+        let user_id = get_request_user_id(&request)?;
+        let request = request.into_inner();
+
+        let output_id: ExternalID = request
+            .output_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        let mut output_file: TeaclaveOutputFile = self
+            .read_from_db(&output_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        ensure!(
+            output_file.owner.contains(&user_id),
+            ManagementServiceError::PermissionDenied
+        );
+        ensure!(
+            output_file.cmac.is_none(),
+            ManagementServiceError::InvalidOutputFile
+        );
+
+        let functions = self.read_all_from_db::<Function>().await?;
+        let input_files = self.read_all_from_db::<TeaclaveInputFile>().await?;
+        let output_files = self.read_all_from_db::<TeaclaveOutputFile>().await?;
+        let tasks = self.read_all_from_db::<TaskState>().await?;
+        let dump = MetadataDump::new(functions, &input_files, &output_files, &tasks);
+        let dump = self
+            .metadata_signer
+            .sign(dump)
+            .map_err(|e| ManagementServiceError::MetadataExportError(e.to_string()))?;
+        let response = ExportMetadataResponse::from(&dump);
+
+        let fusion_base = self.fusion_base.clone();
+        let output = output_file.clone();
+        let cmac = task::spawn_blocking(move || {
+            let bytes = dump.to_vec()?;
+            export::upload(&bytes, &output, fusion_base)
+        })
+        .await
+        .map_err(|e| anyhow!("{}", e.to_string()))
+        .flatten()
+        .map_err(|e| ManagementServiceError::MetadataExportError(format!("{:?}", e)))?;
+
+        output_file
+            .assign_cmac(&cmac)
+            .map_err(ManagementServiceError::Service)?;
+        self.write_to_db(&output_file).await?;
+
+        Ok(Response::new(response))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn decommission_storage(
+        &self,
+        request: Request<DecommissionStorageRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let request = request.into_inner();
+        ensure!(
+            !request.replacement_address.is_empty(),
+            ManagementServiceError::DecommissionError("missing replacement address".to_string())
+        );
+        ensure!(
+            self.decommission.try_begin(&request.replacement_address),
+            ManagementServiceError::DecommissionError("already in progress".to_string())
+        );
+
+        // The transfer can take a while; progress is reported through
+        // get_storage_decommission_status.
+        task::spawn(self.decommission.clone().run());
+
+        Ok(Response::new(()))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn get_storage_decommission_status(
+        &self,
+        request: Request<GetStorageDecommissionStatusRequest>,
+    ) -> TeaclaveServiceResponseResult<GetStorageDecommissionStatusResponse> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let status = self.decommission.status();
+        let response = GetStorageDecommissionStatusResponse {
+            phase: status.phase.to_string(),
+            replacement_address: status.replacement_address,
+            total_entries: status.total_entries,
+            transferred_entries: status.transferred_entries,
+            error: status.error,
+        };
+        Ok(Response::new(response))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn create_storage_snapshot(
+        &self,
+        request: Request<CreateStorageSnapshotRequest>,
+    ) -> TeaclaveServiceResponseResult<CreateStorageSnapshotResponse> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let request = request.into_inner();
+        let request = CreateSnapshotRequest {
+            url: request.url,
+            crypto_info: request.crypto_info,
+        };
+        // The storage service encrypts the snapshot and uploads it.
+        let response = self
+            .storage_client
+            .lock()
+            .await
+            .create_snapshot(request)
+            .await
+            .map_err(|e| ManagementServiceError::SnapshotError(e.message().to_string()))?
+            .into_inner();
+
+        let response = CreateStorageSnapshotResponse {
+            entries: response.entries,
+            cmac: response.cmac,
+        };
+        Ok(Response::new(response))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn restore_storage_snapshot(
+        &self,
+        request: Request<RestoreStorageSnapshotRequest>,
+    ) -> TeaclaveServiceResponseResult<RestoreStorageSnapshotResponse> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let request = request.into_inner();
+        let request = RestoreSnapshotRequest {
+            url: request.url,
+            crypto_info: request.crypto_info,
+            cmac: request.cmac,
+        };
+        let response = self
+            .storage_client
+            .lock()
+            .await
+            .restore_snapshot(request)
+            .await
+            .map_err(|e| ManagementServiceError::SnapshotError(e.message().to_string()))?
+            .into_inner();
+        self.latest_write.reset();
+
+        let response = RestoreStorageSnapshotResponse {
+            entries: response.entries,
+        };
+        Ok(Response::new(response))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn promote_storage_replica(
+        &self,
+        request: Request<PromoteStorageReplicaRequest>,
+    ) -> TeaclaveServiceResponseResult<PromoteStorageReplicaResponse> {
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let request = request.into_inner();
+        ensure!(
+            !request.follower_address.is_empty(),
+            ManagementServiceError::PromotionError("missing follower address".to_string())
+        );
+        let applied_index = self
+            .decommission
+            .promote(&request.follower_address)
+            .await
+            .map_err(|e| ManagementServiceError::PromotionError(e.to_string()))?;
+        // The follower may not have applied the latest writes of the lost node.
+        self.latest_write.reset();
+
+        let response = PromoteStorageReplicaResponse { applied_index };
+        Ok(Response::new(response))
+    }
+
+    // access control:
+    // 1) user_id in data.owner or the user is a platform admin
+    async fn set_data_attributes(
+        &self,
+        request: Request<SetDataAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        self.check_data_owner(&request, &request.get_ref().data_id)
+            .await?;
+        self.access_control_client
+            .lock()
+            .await
+            .set_data_attributes(request.into_inner())
+            .await
+    }
+
+    // access control:
+    // 1) user_id in data.owner or the user is a platform admin
+    async fn get_data_attributes(
+        &self,
+        request: Request<GetDataAttributesRequest>,
+    ) -> TeaclaveServiceResponseResult<GetDataAttributesResponse> {
+        self.check_data_owner(&request, &request.get_ref().data_id)
+            .await?;
+        self.access_control_client
+            .lock()
+            .await
+            .get_data_attributes(request.into_inner())
+            .await
+    }
+
+    // access control:
+    // 1) exisiting_file.owner_list.len() == 1
+    // 2) user_id in existing_file.owner_list
+    // access control:
+    // 1) the user is the only owner of the data
+    async fn delete_data(
+        &self,
+        request: Request<DeleteDataRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let user_id = get_request_user_id(&request)?;
+        let request = request.into_inner();
+        let data_id: ExternalID = request
+            .data_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        let owner = if TeaclaveInputFile::match_prefix(&data_id.prefix) {
+            self.read_from_db::<TeaclaveInputFile>(&data_id)
+                .await
+                .map(|file| file.owner)
+        } else {
+            self.read_from_db::<TeaclaveOutputFile>(&data_id)
+                .await
+                .map(|file| file.owner)
+        }
+        .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        // Co-owners of a fusion output may still need it.
+        ensure!(
+            owner == OwnerList::from(vec![user_id]),
+            ManagementServiceError::PermissionDenied
+        );
+
+        let dependents = self
+            .find_pending_tasks(|ts| {
+                let inputs = ts.assigned_inputs.external_ids();
+                let outputs = ts.assigned_outputs.external_ids();
+                inputs
+                    .values()
+                    .chain(outputs.values())
+                    .any(|id| *id == data_id)
+            })
+            .await?;
+        let reason = format!("Task canceled: data {} deleted", data_id);
+        self.resolve_dependents(dependents, request.cascade, &reason)
+            .await?;
+        self.delete_from_db(&data_id)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+
+        Ok(Response::new(()))
+    }
+
+    async fn set_input_access_policy(
+        &self,
+        request: Request<SetInputAccessPolicyRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let user_id = get_request_user_id(&request)?;
+        let request = request.into_inner();
+        let policy = request
+            .access_policy()
+            .map_err(|e| ManagementServiceError::InvalidAccessPolicy(e.to_string()))?;
+
+        let input_file: TeaclaveInputFile = self
+            .read_from_db(&request.data_id.try_into().map_err(tonic_error)?)
+            .await
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        ensure!(
+            input_file.owner == OwnerList::from(vec![user_id]),
+            ManagementServiceError::PermissionDenied
+        );
+
+        // Tasks assigned the file already are not affected.
+        let input_file = input_file.access_policy(policy);
+        self.write_to_db(&input_file).await?;
+
+        Ok(Response::new(()))
+    }
+
+    // access control:
+    // 1) user_id in data.owner or the user is a platform admin
+    async fn query_data_lineage(
+        &self,
+        request: Request<QueryDataLineageRequest>,
+    ) -> TeaclaveServiceResponseResult<QueryDataLineageResponse> {
+        self.check_data_owner(&request, &request.get_ref().data_id)
+            .await?;
+        let data_id: ExternalID = request
+            .into_inner()
+            .data_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+
+        let lineage = self.collect_lineage(data_id).await?;
+        Ok(Response::new(QueryDataLineageResponse::new(lineage)))
+    }
+
+    // access control: none, users can only set their own preferences
+    async fn set_notification_preferences(
+        &self,
+        request: Request<SetNotificationPreferencesRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let user_id = get_request_user_id(&request)?;
+        let preferences = NotificationPreferences::from(request.into_inner());
+        preferences
+            .validate()
+            .map_err(|e| ManagementServiceError::InvalidNotificationPreferences(e.to_string()))?;
+        let key = notification_preferences_key(&user_id);
+        let value = serde_json::to_vec(&preferences)
+            .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        let response = self
+            .storage_client
+            .lock()
+            .await
+            .put(PutRequest::new(key.as_bytes(), value))
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        self.latest_write.observe(response.into_inner().token);
+
+        Ok(Response::new(()))
+    }
+
+    // access control: none
+    async fn pull_notification_digests(
+        &self,
+        _request: Request<()>,
+    ) -> TeaclaveServiceResponseResult<PullNotificationDigestsResponse> {
+        let events = self.dequeue_task_events().await?;
+        let mut preferences = HashMap::new();
+        for user_id in events.iter().flat_map(|e| e.participants.iter()) {
+            if !preferences.contains_key(user_id) {
+                let p = self.read_notification_preferences(user_id).await?;
+                preferences.insert(user_id.clone(), p);
+            }
+        }
+
+        let digests = NotificationDigest::collect(&events, &preferences);
+        let webhooks = self.collect_webhook_notifications(&events).await;
+        Ok(Response::new(PullNotificationDigestsResponse::new(
+            digests, webhooks,
+        )))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn declare_maintenance_window(
+        &self,
+        request: Request<DeclareMaintenanceWindowRequest>,
+    ) -> TeaclaveServiceResponseResult<DeclareMaintenanceWindowResponse> {
+        let user_id = get_request_user_id(&request)?;
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let request = request.into_inner();
+        let mode = teaclave_types::MaintenanceMode::try_from(request.mode)
+            .map_err(|e| ManagementServiceError::InvalidMaintenanceWindow(e.to_string()))?;
+        let window = teaclave_types::MaintenanceWindow::new(
+            request.starts_at,
+            request.ends_at,
+            mode,
+            request.reason,
+            &user_id,
+            now_secs(),
+        )
+        .map_err(|e| ManagementServiceError::InvalidMaintenanceWindow(e.to_string()))?;
+        self.write_to_db(&window).await?;
+        log::info!(
+            "Maintenance window {} declared by {}: {} to {}, {:?}",
+            window.external_id(),
+            user_id,
+            window.starts_at,
+            window.ends_at,
+            window.mode
+        );
+
+        Ok(Response::new(DeclareMaintenanceWindowResponse {
+            window_id: window.external_id().to_string(),
+        }))
+    }
+
+    // access control: role == PlatformAdmin
+    async fn cancel_maintenance_window(
+        &self,
+        request: Request<CancelMaintenanceWindowRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let user_id = get_request_user_id(&request)?;
+        let role = get_request_role(&request)?;
+        ensure!(
+            role == UserRole::PlatformAdmin,
+            ManagementServiceError::PermissionDenied
+        );
+
+        let window_id: ExternalID = request.into_inner().window_id.try_into().map_err(|_| {
+            ManagementServiceError::InvalidMaintenanceWindow("invalid window id".to_string())
+        })?;
+        let window: teaclave_types::MaintenanceWindow =
+            self.read_from_db(&window_id).await.map_err(|_| {
+                ManagementServiceError::InvalidMaintenanceWindow("window not found".to_string())
+            })?;
+        // Tasks held by the window are staged in the next sweep.
+        self.delete_from_db(&window.external_id()).await?;
+        log::info!("Maintenance window {} canceled by {}", window_id, user_id);
+
+        Ok(Response::new(()))
+    }
+
+    // access control: none
+    async fn get_service_info(
+        &self,
+        _request: Request<GetServiceInfoRequest>,
+    ) -> TeaclaveServiceResponseResult<GetServiceInfoResponse> {
+        let windows = self.read_maintenance_windows().await?;
+        Ok(Response::new(GetServiceInfoResponse::new(
+            &windows,
+            now_secs(),
+        )))
+    }
+}
+
+impl TeaclaveManagementService {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn new(
+        storage_service_endpoint: Endpoint,
+        access_control_service_endpoint: Endpoint,
+        storage_endpoint_factory: StorageEndpointFactory,
+        metadata_signer: MetadataSigner,
+        fusion_base: PathBuf,
+        max_queue_depth: u32,
+        webhook: WebhookConfig,
+        function_payload: FunctionPayloadConfig,
+        resource_limits: ResourceLimitsConfig,
+    ) -> anyhow::Result<Self> {
+        let channel = storage_service_endpoint
+            .connect()
+            .await
+            .map_err(|e| anyhow!("Failed to connect to storage service, {:?}", e))?;
+        let storage_client = Arc::new(Mutex::new(TeaclaveStorageClient::new_with_builtin_config(
+            channel,
+        )));
+        let channel = access_control_service_endpoint
+            .connect()
+            .await
+            .map_err(|e| anyhow!("Failed to connect to access_control service, {:?}", e))?;
+        let access_control_client = Arc::new(Mutex::new(
+            TeaclaveAccessControlClient::new_with_builtin_config(channel),
+        ));
+        let client_clone = storage_client.clone();
+        let auditor = task::spawn_blocking(move || Auditor::try_new(client_clone)).await??;
+        let decommission =
+            StorageDecommission::new(storage_client.clone(), storage_endpoint_factory);
+        let service = Self {
+            storage_client,
+            latest_write: LatestWrite::default(),
+            access_control_client,
+            auditor,
+            decommission,
+            metadata_signer,
+            fusion_base,
+            max_queue_depth,
+            webhook,
+            function_payload,
+            resource_limits: ResourceLimits::new(
+                resource_limits.timeout_secs,
+                resource_limits.max_memory_bytes,
+                resource_limits.max_output_bytes,
+            ),
+        };
+
+        service.index_tasks().await?;
+
+        #[cfg(test_mode)]
+        service.add_mock_data().await?;
+
+        Ok(service)
+    }
+
+    async fn check_data_owner<T>(
+        &self,
+        request: &Request<T>,
+        data_id: &str,
+    ) -> Result<(), ManagementServiceError> {
+        if get_request_role(request)? == UserRole::PlatformAdmin {
+            return Ok(());
+        }
+        let user_id = get_request_user_id(request)?;
+        let data_id: ExternalID = data_id
+            .try_into()
+            .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        let owner = if TeaclaveInputFile::match_prefix(&data_id.prefix) {
+            self.read_from_db::<TeaclaveInputFile>(&data_id)
+                .await
+                .map(|file| file.owner)
+        } else {
+            self.read_from_db::<TeaclaveOutputFile>(&data_id)
+                .await
+                .map(|file| file.owner)
+        }
+        .map_err(|_| ManagementServiceError::InvalidDataId)?;
+        ensure!(
+            owner.contains(&user_id),
+            ManagementServiceError::PermissionDenied
+        );
+        Ok(())
+    }
+
+    // Consults the attributes of the user and the data in the access control
+    // service, on top of the ownership checked by the caller.
+    async fn authorize_data(
+        &self,
+        user_id: &UserID,
+        data_id: &ExternalID,
+    ) -> Result<(), ManagementServiceError> {
+        let request = AuthorizeDataRequest {
+            subject_user_id: user_id.to_string(),
+            object_data_id: data_id.to_string(),
+        };
+        let response = self
+            .access_control_client
+            .lock()
+            .await
+            .authorize_data(request)
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?
+            .into_inner();
+        if !response.accept {
+            let denial = response.denial.unwrap_or_default();
+            return Err(ManagementServiceError::attribute_denied(denial, user_id));
+        }
+        Ok(())
+    }
+
+    // The function and all the data of the task are checked in one round
+    // trip, and the caller is told the first denial.
+    async fn authorize_staged_task(
+        &self,
+        ts: &TaskState,
+        caller: &UserID,
+    ) -> Result<(), ManagementServiceError> {
+        let object_id_list = std::iter::once(ts.function_id.clone())
+            .chain(ts.assigned_inputs.external_ids().into_values())
+            .chain(ts.assigned_outputs.external_ids().into_values())
+            .map(|id| id.to_string())
+            .collect();
+        let request = AuthorizeBatchRequest {
+            subject_task_id: ts.external_id().to_string(),
+            subject_user_id_list: ts.participants.clone().into(),
+            object_id_list,
+        };
+        let response = self
+            .access_control_client
+            .lock()
+            .await
+            .authorize_batch(request)
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?
+            .into_inner();
+        if !response.accept {
+            log::debug!(
+                "AuthorizeBatch of {}: {:?}",
+                ts.external_id(),
+                response.denials().collect::<Vec<_>>()
+            );
+            let denial = response.denials().next().cloned().unwrap_or_default();
+            return Err(ManagementServiceError::attribute_denied(denial, caller));
+        }
+        Ok(())
+    }
+
+    async fn write_to_db(&self, item: &impl Storable) -> Result<(), ManagementServiceError> {
+        let k = item.key();
+        let v = item.to_vec()?;
+        let put_request = PutRequest::new(k.as_slice(), v.as_slice());
+        let put_response = self
+            .storage_client
+            .clone()
+            .lock()
+            .await
+            .put(put_request)
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        self.latest_write.observe(put_response.into_inner().token);
+        Ok(())
+    }
+
+    async fn read_from_db<T: Storable>(
+        &self,
+        key: &ExternalID,
+    ) -> Result<T, ManagementServiceError> {
+        ensure!(
+            T::match_prefix(&key.prefix),
+            anyhow!("key prefix doesn't match")
+        );
+
+        let request = GetRequest::new(key.to_bytes()).after(self.latest_write.token());
+        let response = self
+            .storage_client
+            .clone()
+            .lock()
+            .await
+            .get(request)
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?
+            .into_inner();
+        T::from_slice(response.value.as_slice()).map_err(ManagementServiceError::Service)
+    }
+
+    async fn get_keys_by_prefix_from_db(
+        &self,
+        prefix: impl Into<Vec<u8>>,
+    ) -> Result<Vec<String>, ManagementServiceError> {
+        self.get_key_page_from_db(GetKeysByPrefixRequest::new(prefix.into()))
+            .await
+    }
+
+    async fn get_key_page_from_db(
+        &self,
+        request: GetKeysByPrefixRequest,
+    ) -> Result<Vec<String>, ManagementServiceError> {
+        let request = request.after(self.latest_write.token());
+        let response = self
+            .storage_client
+            .clone()
+            .lock()
+            .await
+            .get_keys_by_prefix(request)
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        Ok(response
+            .into_inner()
+            .keys
+            .into_iter()
+            .map(String::from_utf8)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow!("cannot convert keys"))?)
+    }
+
+    async fn read_all_from_db<T: Storable>(&self) -> Result<Vec<T>, ManagementServiceError> {
+        let keys = self.get_keys_by_prefix_from_db(T::key_prefix()).await?;
+        let mut items = Vec::with_capacity(keys.len());
+        for key in keys {
+            let external_id = ExternalID::try_from(key).map_err(ManagementServiceError::Service)?;
+            items.push(self.read_from_db(&external_id).await?);
+        }
+        Ok(items)
+    }
+
+    async fn count_in_db<T: Storable>(&self) -> Result<u64, ManagementServiceError> {
+        let keys = self.get_keys_by_prefix_from_db(T::key_prefix()).await?;
+        Ok(keys.len() as u64)
+    }
+
+    /// Read the items after the ID `after` in the order of IDs, until `limit`
+    /// of them (all if 0) pass `filter`. Returns the cursor of the next page
+    /// as well, i.e., the ID of the last item read, unless no item is left.
+    async fn read_page_from_db<T: Storable>(
+        &self,
+        after: &str,
+        limit: usize,
+        filter: impl Fn(&T) -> bool,
+    ) -> Result<(Vec<T>, Option<PageCursor>), ManagementServiceError> {
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let mut items = Vec::new();
+        let mut after = after.to_string();
+        loop {
+            let request = GetKeysByPrefixRequest::new(T::key_prefix())
+                .page(after.as_bytes(), SCAN_BATCH_SIZE);
+            let keys = self.get_key_page_from_db(request).await?;
+            let is_last_batch = keys.len() < SCAN_BATCH_SIZE as usize;
+            for key in keys {
+                let external_id =
+                    ExternalID::try_from(key.as_str()).map_err(ManagementServiceError::Service)?;
+                let item: T = self.read_from_db(&external_id).await?;
+                after = key;
+                if filter(&item) {
+                    items.push(item);
+                    if items.len() == limit {
+                        return Ok((items, Some(PageCursor::new(after))));
+                    }
+                }
+            }
+            if is_last_batch {
+                return Ok((items, None));
+            }
+        }
+    }
+
+    /// Like `read_page_from_db`, but reads the tasks of the IDs only.
+    async fn read_task_page_from_db(
+        &self,
+        task_ids: Vec<Uuid>,
+        after: &str,
+        limit: usize,
+        filter: impl Fn(&TaskState) -> bool,
+    ) -> Result<(Vec<TaskState>, Option<PageCursor>), ManagementServiceError> {
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let mut keys: Vec<ExternalID> = task_ids
+            .into_iter()
+            .map(|task_id| ExternalID::new(TaskState::key_prefix(), task_id))
+            .filter(|key| key.to_string().as_str() > after)
+            .collect();
+        keys.sort_by_key(|key| key.to_string());
+        keys.dedup();
+        let mut tasks = Vec::new();
+        for (i, key) in keys.iter().enumerate() {
+            let ts: TaskState = self.read_from_db(key).await?;
+            if filter(&ts) {
+                tasks.push(ts);
+                if tasks.len() == limit && i + 1 < keys.len() {
+                    return Ok((tasks, Some(PageCursor::new(key.to_string()))));
+                }
+            }
+        }
+        Ok((tasks, None))
+    }
+
+    /// Cancel a task not ended yet, through the scheduler if it has been
+    /// staged, or failing it with `reason` right away otherwise.
+    async fn cancel_task_state(
+        &self,
+        ts: TaskState,
+        reason: &str,
+    ) -> Result<(), ManagementServiceError> {
+        match ts.status {
+            // need scheduler to cancel the task
+            TaskStatus::Staged | TaskStatus::Running => {
+                self.enqueue_to_db(CANCEL_QUEUE_KEY.as_bytes(), &ts).await?;
+            }
+            _ => {
+                // early cancelation
+                // race will not affect correctness/privacy
+                let mut task: Task<Cancel> = ts.try_into().map_err(|e| {
+                    log::warn!("Cancel state error: {:?}", e);
+                    ManagementServiceError::TaskCancelError(
+                        "task has already been canceled".to_string(),
+                    )
+                })?;
+
+                log::debug!("Canceled Task: {:?}", task);
+
+                task.update_result(TaskResult::Err(TaskFailure::new(reason)))
+                    .map_err(|_| {
+                        ManagementServiceError::TaskCancelError("cannot update result".to_string())
+                    })?;
+                let ts: TaskState = task.into();
+                self.write_task_to_db(&ts).await?;
+                self.enqueue_to_db(
+                    TASK_EVENT_QUEUE_KEY.as_bytes(),
+                    &TaskEvent::from_task_state(&ts),
+                )
+                .await?;
+
+                log::warn!("Canceled Task: writtenback");
+            }
+        }
+        Ok(())
+    }
+
+    /// The tasks not ended yet which reference an object by `references`,
+    /// found in the secondary index of the statuses.
+    async fn find_pending_tasks(
+        &self,
+        references: impl Fn(&TaskState) -> bool,
+    ) -> Result<Vec<TaskState>, ManagementServiceError> {
+        let statuses = [
+            TaskStatus::Created,
+            TaskStatus::DataAssigned,
+            TaskStatus::Approved,
+            TaskStatus::Staged,
+            TaskStatus::Running,
+        ];
+        let mut tasks = Vec::new();
+        for status in statuses {
+            let keys = self
+                .get_keys_by_prefix_from_db(TaskIndex::Status(status).prefix())
+                .await?;
+            for task_id in keys.iter().filter_map(|key| TaskIndex::task_id(key)) {
+                let key = ExternalID::new(TaskState::key_prefix(), task_id);
+                // The index may be behind the state, which is checked again.
+                match self.read_from_db::<TaskState>(&key).await {
+                    Ok(ts) if !ts.is_ended() && references(&ts) => tasks.push(ts),
+                    _ => {}
+                }
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// Refuse to delete an object the pending tasks reference, listing them,
+    /// or cancel the tasks with `reason` first if `cascade` is set, so that
+    /// they do not fail at staging for the missing object.
+    async fn resolve_dependents(
+        &self,
+        dependents: Vec<TaskState>,
+        cascade: bool,
+        reason: &str,
+    ) -> Result<(), ManagementServiceError> {
+        if !cascade {
+            let listed: Vec<String> = dependents
+                .iter()
+                .take(MAX_LISTED_DEPENDENTS)
+                .map(|ts| ts.external_id().to_string())
+                .collect();
+            ensure!(
+                dependents.is_empty(),
+                ManagementServiceError::PendingDependents(dependents.len(), listed.join(", "))
+            );
+            return Ok(());
+        }
+        for ts in dependents {
+            log::info!("{}: {}", reason, ts.external_id());
+            self.cancel_task_state(ts, reason).await?;
+        }
+        Ok(())
+    }
+
+    /// IDs of the tasks possibly matching the filters of a listing, found in
+    /// the secondary index of the creator, else of the statuses, else of the
+    /// creation time. `None` if the listing is filtered by none of them.
+    async fn find_tasks_by_index(
+        &self,
+        request: &ListTasksRequest,
+        statuses: &[TaskStatus],
+    ) -> Result<Option<Vec<Uuid>>, ManagementServiceError> {
+        let keys = if !request.creator.is_empty() {
+            let index = TaskIndex::Creator(UserID::from(request.creator.as_str()));
+            self.get_keys_by_prefix_from_db(index.prefix()).await?
+        } else if !statuses.is_empty() {
+            let mut keys = Vec::new();
+            for status in statuses {
+                let index = TaskIndex::Status(status.clone());
+                keys.extend(self.get_keys_by_prefix_from_db(index.prefix()).await?);
+            }
+            keys
+        } else if request.created_after != 0 || request.created_before != 0 {
+            self.get_created_task_keys(request.created_after, request.created_before)
+                .await?
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(
+            keys.iter()
+                .filter_map(|key| TaskIndex::task_id(key))
+                .collect(),
+        ))
+    }
+
+    /// Keys of the tasks created in [after, before), unbounded if 0. The keys
+    /// of the creation time index are ordered by the time, so the scan starts
+    /// at `after` and stops at `before`.
+    async fn get_created_task_keys(
+        &self,
+        after: i64,
+        before: i64,
+    ) -> Result<Vec<String>, ManagementServiceError> {
+        let end = match before {
+            0 => None,
+            before => Some(TaskIndex::CreatedAt(before).prefix()),
+        };
+        let mut start = TaskIndex::CreatedAt(after).prefix();
+        let mut keys = Vec::new();
+        loop {
+            let request = GetKeysByPrefixRequest::new(TaskIndex::created_at_prefix())
+                .page(start.as_bytes(), SCAN_BATCH_SIZE);
+            let batch = self.get_key_page_from_db(request).await?;
+            let is_last_batch = batch.len() < SCAN_BATCH_SIZE as usize;
+            for key in batch {
+                if end.as_ref().map_or(false, |end| key >= *end) {
+                    return Ok(keys);
+                }
+                start = key.clone();
+                keys.push(key);
+            }
+            if is_last_batch {
+                return Ok(keys);
+            }
+        }
+    }
+
+    /// Save a new function, and add it to the functions registered by its
+    /// owner and allowed for the users on its allowlist.
+    async fn add_function(&self, function: &Function) -> Result<(), ManagementServiceError> {
+        // Versions of the same function share a name, so a (owner, name,
+        // version) triple identifies exactly one registered function.
+        if !function.version.is_empty() {
+            let functions = self.read_all_from_db::<Function>().await?;
+            ensure!(
+                !functions.iter().any(|f| f.owner == function.owner
+                    && f.name == function.name
+                    && f.version == function.version),
+                ManagementServiceError::FunctionVersionExists
+            );
+        }
+
+        self.write_to_db(function).await?;
+
+        let mut u = User {
+            id: function.owner.clone(),
+            ..Default::default()
+        };
+        let external_id = u.external_id();
+
+        let user = self.read_from_db::<User>(&external_id).await;
+        match user {
+            Ok(mut us) => {
+                us.registered_functions
+                    .push(function.external_id().to_string());
+                self.write_to_db(&us).await?;
+            }
+            Err(_) => {
+                u.registered_functions
+                    .push(function.external_id().to_string());
+                self.write_to_db(&u).await?;
+            }
+        }
+
+        // Update allowed function list for users
+        for user_id in &function.user_allowlist {
+            let mut u = User {
+                id: user_id.into(),
+                ..Default::default()
+            };
+            let external_id = u.external_id();
+            let user = self.read_from_db::<User>(&external_id).await;
+            match user {
+                Ok(mut us) => {
+                    us.allowed_functions
+                        .push(function.external_id().to_string());
+                    self.write_to_db(&us).await?;
+                }
+                Err(_) => {
+                    u.allowed_functions.push(function.external_id().to_string());
+                    self.write_to_db(&u).await?;
+                }
+            }
+        }
+
+        let usage = FunctionUsage {
+            function_id: function.id,
+            ..Default::default()
         };
+        self.write_to_db(&usage).await?;
 
-        #[cfg(test_mode)]
-        service.add_mock_data().await?;
+        Ok(())
+    }
 
-        Ok(service)
+    async fn read_function_upload(
+        &self,
+        upload_id: &str,
+        user_id: &UserID,
+    ) -> Result<FunctionUpload, ManagementServiceError> {
+        let upload_id = ExternalID::try_from(upload_id)
+            .map_err(|_| ManagementServiceError::FunctionUploadNotFound)?;
+        let upload: FunctionUpload = self
+            .read_from_db(&upload_id)
+            .await
+            .map_err(|_| ManagementServiceError::FunctionUploadNotFound)?;
+        ensure!(
+            upload.owner == *user_id,
+            ManagementServiceError::PermissionDenied
+        );
+        Ok(upload)
     }
 
-    async fn write_to_db(&self, item: &impl Storable) -> Result<(), ManagementServiceError> {
-        let k = item.key();
-        let v = item.to_vec()?;
-        let put_request = PutRequest::new(k.as_slice(), v.as_slice());
-        let _put_response = self
+    /// Write the upload or one of its chunks, expiring along with the upload.
+    async fn write_upload_to_db(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: Vec<u8>,
+        upload: &FunctionUpload,
+    ) -> Result<(), ManagementServiceError> {
+        let request = PutRequest::new(key, value).expires_at(upload.expires_at as u64);
+        let response = self
             .storage_client
             .clone()
             .lock()
             .await
-            .put(put_request)
+            .put(request)
             .await
             .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        self.latest_write.observe(response.into_inner().token);
         Ok(())
     }
 
-    async fn read_from_db<T: Storable>(
+    /// Read the usage records of a function ended in `[start, end)` (unbounded
+    /// if 0) in the order of the end time, until `limit` of them (all if 0).
+    async fn read_usage_records_from_db(
         &self,
-        key: &ExternalID,
-    ) -> Result<T, ManagementServiceError> {
-        ensure!(
-            T::match_prefix(&key.prefix),
-            anyhow!("key prefix doesn't match")
-        );
+        function_id: &Uuid,
+        start: i64,
+        end: i64,
+        limit: usize,
+    ) -> Result<Vec<UsageRecord>, ManagementServiceError> {
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let mut after = UsageRecord::index_start(function_id, start);
+        let mut records = Vec::new();
+        loop {
+            let request = GetKeysByPrefixRequest::new(UsageRecord::index_prefix(function_id))
+                .page(after.as_bytes(), SCAN_BATCH_SIZE);
+            let batch = self.get_key_page_from_db(request).await?;
+            let is_last_batch = batch.len() < SCAN_BATCH_SIZE as usize;
+            for key in batch {
+                let (ended_at, task_id) = UsageRecord::parse_index_key(&key)
+                    .ok_or_else(|| anyhow!("invalid usage index key"))?;
+                if end != 0 && ended_at >= end {
+                    return Ok(records);
+                }
+                let external_id = ExternalID::new(UsageRecord::key_prefix(), task_id);
+                records.push(self.read_from_db(&external_id).await?);
+                if records.len() == limit {
+                    return Ok(records);
+                }
+                after = key;
+            }
+            if is_last_batch {
+                return Ok(records);
+            }
+        }
+    }
 
-        let request = GetRequest::new(key.to_bytes());
-        let response = self
-            .storage_client
-            .clone()
-            .lock()
-            .await
-            .get(request)
-            .await
-            .map_err(|e| ManagementServiceError::Service(e.into()))?
-            .into_inner();
-        T::from_slice(response.value.as_slice()).map_err(ManagementServiceError::Service)
+    /// Write a task state along with its secondary index entries in a batch,
+    /// deleting the entries of the state it replaces.
+    async fn write_task_to_db(&self, ts: &TaskState) -> Result<(), ManagementServiceError> {
+        let old = self.read_from_db::<TaskState>(&ts.external_id()).await.ok();
+        let (puts, deletes) = TaskIndex::changes(old.as_ref(), ts);
+        self.write_task_index_to_db(ts, puts, deletes).await
     }
 
-    async fn get_keys_by_prefix_from_db(
+    // Tasks stored before the secondary index was maintained are indexed on
+    // start. Indexing a task again rewrites the same entries.
+    async fn index_tasks(&self) -> Result<(), ManagementServiceError> {
+        for ts in self.read_all_from_db::<TaskState>().await? {
+            let (puts, _) = TaskIndex::changes(None, &ts);
+            self.write_task_index_to_db(&ts, puts, Vec::new()).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_task_index_to_db(
         &self,
-        prefix: impl Into<Vec<u8>>,
-    ) -> Result<Vec<String>, ManagementServiceError> {
-        let request = GetKeysByPrefixRequest::new(prefix.into());
+        ts: &TaskState,
+        puts: Vec<String>,
+        deletes: Vec<String>,
+    ) -> Result<(), ManagementServiceError> {
+        let mut request = WriteBatchRequest::new().put(ts.key(), ts.to_vec()?);
+        for key in puts {
+            request = request.put(key, Vec::new());
+        }
+        for key in deletes {
+            request = request.delete(key);
+        }
         let response = self
             .storage_client
             .clone()
             .lock()
             .await
-            .get_keys_by_prefix(request)
+            .write_batch(request)
             .await
             .map_err(|e| ManagementServiceError::Service(e.into()))?;
-        Ok(response
-            .into_inner()
-            .keys
-            .into_iter()
-            .map(String::from_utf8)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|_| anyhow!("cannot convert keys"))?)
+        self.latest_write.observe(response.into_inner().token);
+        Ok(())
     }
 
     async fn delete_from_db(&self, key: &ExternalID) -> Result<(), ManagementServiceError> {
         let request = DeleteRequest::new(key.to_bytes());
-        self.storage_client
+        let response = self
+            .storage_client
             .clone()
             .lock()
             .await
             .delete(request)
             .await
             .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        self.latest_write.observe(response.into_inner().token);
         Ok(())
     }
 
@@ -1032,7 +2885,7 @@ impl TeaclaveManagementService {
     ) -> Result<(), ManagementServiceError> {
         let value = item.to_vec()?;
         let enqueue_request = EnqueueRequest::new(key, value);
-        let _enqueue_response = self
+        let enqueue_response = self
             .storage_client
             .clone()
             .lock()
@@ -1040,9 +2893,418 @@ impl TeaclaveManagementService {
             .enqueue(enqueue_request)
             .await
             .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        self.latest_write
+            .observe(enqueue_response.into_inner().token);
+        Ok(())
+    }
+
+    async fn get_queue_length(&self) -> Result<u32, ManagementServiceError> {
+        let request = GetQueueLengthRequest::new(StagedTask::get_queue_key().as_bytes())
+            .after(self.latest_write.token());
+        let response = self
+            .storage_client
+            .lock()
+            .await
+            .get_queue_length(request)
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        Ok(response.into_inner().length)
+    }
+
+    // Tasks are not staged beyond the limit so that the queue, and the
+    // scheduler holding it in memory, cannot grow without bound.
+    async fn check_queue_depth(&self, trace_id: &str) -> Result<u32, ManagementServiceError> {
+        let depth = self.get_queue_length().await?;
+        if depth >= self.max_queue_depth {
+            log::warn!(
+                trace_id = trace_id;
+                "InvokeTask: task queue depth {} reached the limit {}",
+                depth,
+                self.max_queue_depth
+            );
+            return Err(ManagementServiceError::Backpressure(depth));
+        }
+        Ok(depth)
+    }
+
+    /// The tasks ahead of a task staged behind `staged` tasks in the storage
+    /// queue, counting the ones the scheduler holds, and the time they take
+    /// to run as estimated by the scheduler.
+    async fn queue_position(&self, staged: u32) -> InvokeTaskResponse {
+        let backlog = match self.read_task_backlog().await {
+            Ok(backlog) => backlog,
+            Err(e) => {
+                log::warn!("Failed to read the task backlog: {:?}", e);
+                TaskBacklog::default()
+            }
+        };
+        let ahead = staged.saturating_add(backlog.queued);
+        InvokeTaskResponse::new(ahead, backlog.eta_secs(ahead))
+    }
+
+    // The scheduler shares its backlog with a short expiry, so that none is
+    // found once it stops.
+    async fn read_task_backlog(&self) -> Result<TaskBacklog, ManagementServiceError> {
+        let request = GetRequest::new(SCHEDULER_BACKLOG_KEY.as_bytes());
+        let response = self.storage_client.lock().await.get(request).await;
+        match response {
+            Ok(response) => serde_json::from_slice(&response.into_inner().value)
+                .map_err(|e| ManagementServiceError::Service(e.into())),
+            Err(status) if status.code() == Code::NotFound => Ok(TaskBacklog::default()),
+            Err(status) => Err(ManagementServiceError::Service(anyhow!(
+                "Failed to read the task backlog: {:?}",
+                status
+            ))),
+        }
+    }
+
+    // An empty queue is reported as an error by the storage service.
+    async fn dequeue_task_events(&self) -> Result<Vec<TaskEvent>, ManagementServiceError> {
+        let mut events = Vec::new();
+        let mut client = self.storage_client.lock().await;
+        while events.len() < MAX_EVENTS_PER_DIGEST {
+            let request = DequeueRequest::new(TASK_EVENT_QUEUE_KEY.as_bytes());
+            let value = match client.dequeue(request).await {
+                Ok(response) => {
+                    let response = response.into_inner();
+                    self.latest_write.observe(response.token);
+                    response.value
+                }
+                Err(_) => break,
+            };
+            match TaskEvent::from_slice(&value) {
+                Ok(event) => events.push(event),
+                Err(e) => log::warn!("Dropped invalid task event: {:?}", e),
+            }
+        }
+        Ok(events)
+    }
+
+    // File transfers are reported through the scheduler, which cannot reach
+    // the auditor, so they are moved into the audit log before it is read.
+    async fn flush_file_transfers(&self) {
+        let mut entries = Vec::new();
+        {
+            let mut client = self.storage_client.lock().await;
+            loop {
+                let request = DequeueRequest::new(FILE_TRANSFER_QUEUE_KEY.as_bytes());
+                let value = match client.dequeue(request).await {
+                    Ok(response) => {
+                        let response = response.into_inner();
+                        self.latest_write.observe(response.token);
+                        response.value
+                    }
+                    Err(_) => break,
+                };
+                match FileTransferRecord::from_slice(&value) {
+                    Ok(record) => entries.push(record.to_entry()),
+                    Err(e) => log::warn!("Dropped invalid file transfer record: {:?}", e),
+                }
+            }
+        }
+        if entries.is_empty() {
+            return;
+        }
+
+        let auditor = self.auditor.clone();
+        let saved = task::spawn_blocking(move || auditor.add_logs(entries))
+            .await
+            .map_err(|e| anyhow!("{}", e.to_string()))
+            .flatten();
+        if let Err(e) = saved {
+            log::warn!("Failed to save file transfer logs: {:?}", e);
+        }
+    }
+
+    // Windows not ended yet, in the order they start.
+    async fn read_maintenance_windows(
+        &self,
+    ) -> Result<Vec<teaclave_types::MaintenanceWindow>, ManagementServiceError> {
+        let now = now_secs();
+        let mut windows: Vec<_> = self
+            .read_all_from_db::<teaclave_types::MaintenanceWindow>()
+            .await?
+            .into_iter()
+            .filter(|w| !w.has_ended(now))
+            .collect();
+        windows.sort_by_key(|w| w.starts_at);
+        Ok(windows)
+    }
+
+    async fn active_maintenance_window(
+        &self,
+    ) -> Result<Option<teaclave_types::MaintenanceWindow>, ManagementServiceError> {
+        let windows = self.read_maintenance_windows().await?;
+        Ok(active_maintenance_window(&windows, now_secs()).cloned())
+    }
+
+    /// Deletes the maintenance windows which have ended. Once no window is
+    /// active, the tasks held during the windows are staged, as many as the
+    /// task queue has room for.
+    pub(crate) async fn end_maintenance_windows(&self) {
+        let windows = match self
+            .read_all_from_db::<teaclave_types::MaintenanceWindow>()
+            .await
+        {
+            Ok(windows) => windows,
+            Err(e) => {
+                log::warn!("Failed to read maintenance windows: {:?}", e);
+                return;
+            }
+        };
+        let now = now_secs();
+        for window in windows.iter().filter(|w| w.has_ended(now)) {
+            match self.delete_from_db(&window.external_id()).await {
+                Ok(_) => log::info!("Maintenance window {} ended", window.external_id()),
+                Err(e) => log::warn!("Failed to delete maintenance window: {:?}", e),
+            }
+        }
+        if active_maintenance_window(&windows, now).is_some() {
+            return;
+        }
+
+        let room = match self.get_queue_length().await {
+            Ok(depth) => self.max_queue_depth.saturating_sub(depth),
+            Err(e) => {
+                log::warn!("Failed to get the task queue length: {:?}", e);
+                return;
+            }
+        };
+        let mut client = self.storage_client.lock().await;
+        for _ in 0..room {
+            let request = DequeueRequest::new(MAINTENANCE_QUEUE_KEY.as_bytes());
+            let value = match client.dequeue(request).await {
+                Ok(response) => {
+                    let response = response.into_inner();
+                    self.latest_write.observe(response.token);
+                    response.value
+                }
+                Err(_) => break,
+            };
+            let staged_task = match StagedTask::from_slice(&value) {
+                Ok(task) => task,
+                Err(e) => {
+                    log::warn!("Dropped invalid held task: {:?}", e);
+                    continue;
+                }
+            };
+            let request =
+                EnqueueRequest::new(StagedTask::get_queue_key().as_bytes(), value.clone());
+            match client.enqueue(request).await {
+                Ok(response) => {
+                    self.latest_write.observe(response.into_inner().token);
+                    log::info!(
+                        trace_id = staged_task.trace_id.as_str();
+                        "Task {} staged after maintenance",
+                        staged_task.task_id
+                    );
+                }
+                Err(e) => {
+                    log::warn!("Failed to stage held task {}: {:?}", staged_task.task_id, e);
+                    // Held again, and staged in the next sweep.
+                    let request = EnqueueRequest::new(MAINTENANCE_QUEUE_KEY.as_bytes(), value);
+                    if let Err(e) = client.enqueue(request).await {
+                        log::error!("Lost held task {}: {:?}", staged_task.task_id, e);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Deletes the outputs past their retention period. Their keys go with
+    /// the metadata, as well as the keys of the inputs registered from them,
+    /// which share the uuid. The remote objects of finished outputs are then
+    /// deleted by the file agent, where the scheme supports it.
+    pub(crate) async fn expire_outputs(&self) {
+        let outputs = match self.read_all_from_db::<TeaclaveOutputFile>().await {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                log::warn!("Failed to read outputs to expire: {:?}", e);
+                return;
+            }
+        };
+
+        let mut entries = Vec::new();
+        for output in outputs.into_iter().filter(|o| o.is_expired()) {
+            let data_id = output.external_id();
+            let input_id = ExternalID::new(TeaclaveInputFile::key_prefix(), output.uuid);
+            let deleted = match self.delete_from_db(&data_id).await {
+                Ok(_) => self.delete_from_db(&input_id).await.is_ok(),
+                Err(_) => false,
+            };
+            let remote = if !deleted {
+                "kept"
+            } else if output.cmac.is_none() {
+                "not uploaded"
+            } else if self.delete_remote_object(&output.url).await {
+                "deleted"
+            } else {
+                "not deleted"
+            };
+            log::info!("Expired output {}, remote object {}", data_id, remote);
+
+            let mut owners: Vec<String> = output.owner.into();
+            owners.sort();
+            let entry = EntryBuilder::new()
+                .user(owners.join(","))
+                .message(format!(
+                    "expire_output: {} expires_at {} remote {} {}",
+                    data_id,
+                    output.expires_at.unwrap_or_default(),
+                    redact_url(&output.url),
+                    remote
+                ))
+                .result(deleted)
+                .build();
+            entries.push(entry);
+        }
+        if entries.is_empty() {
+            return;
+        }
+
+        let auditor = self.auditor.clone();
+        let saved = task::spawn_blocking(move || auditor.add_logs(entries))
+            .await
+            .map_err(|e| anyhow!("{}", e.to_string()))
+            .flatten();
+        if let Err(e) = saved {
+            log::warn!("Failed to save output expiration logs: {:?}", e);
+        }
+    }
+
+    async fn delete_remote_object(&self, url: &Url) -> bool {
+        if !matches!(url.scheme(), "http" | "https" | "file" | "fusion") {
+            return false;
+        }
+        let info = HandleFileInfo::new("", url);
+        let request =
+            FileAgentRequest::new(HandleFileCommand::Delete, vec![info], &self.fusion_base);
+        let deleted =
+            task::spawn_blocking(move || crate::file_handler::handle_file_request(request))
+                .await
+                .map_err(|e| anyhow!("{}", e.to_string()))
+                .flatten();
+        if let Err(e) = &deleted {
+            log::warn!("Failed to delete {}: {:?}", redact_url(url), e);
+        }
+        deleted.is_ok()
+    }
+
+    // A sink which cannot be read is skipped, so that one broken sink does
+    // not hold back the notifications of the other tasks.
+    async fn collect_webhook_notifications(
+        &self,
+        events: &[TaskEvent],
+    ) -> Vec<WebhookNotification> {
+        let mut notifications = Vec::new();
+        for event in events.iter().filter(|e| !e.webhook_sinks.is_empty()) {
+            let task_id = ExternalID::new(TaskState::key_prefix(), event.task_id);
+            let ts: TaskState = match self.read_from_db(&task_id).await {
+                Ok(ts) => ts,
+                Err(e) => {
+                    log::warn!(
+                        "Failed to read task {} for webhooks: {:?}",
+                        event.task_id,
+                        e
+                    );
+                    continue;
+                }
+            };
+            let payload = WebhookPayload::from_task_state(&ts);
+            for sink_id in &event.webhook_sinks {
+                let notification = match self.read_from_db::<WebhookSink>(sink_id).await {
+                    Ok(sink) => WebhookNotification::new(&sink, &payload)
+                        .map_err(ManagementServiceError::Service),
+                    Err(e) => Err(e),
+                };
+                match notification {
+                    Ok(notification) => notifications.push(notification),
+                    Err(e) => log::warn!("Dropped webhook {}: {:?}", sink_id.to_string(), e),
+                }
+            }
+        }
+        notifications
+    }
+
+    // Users who never set their preferences have not opted in.
+    async fn read_notification_preferences(
+        &self,
+        user_id: &UserID,
+    ) -> Result<NotificationPreferences, ManagementServiceError> {
+        let key = notification_preferences_key(user_id);
+        let request = GetRequest::new(key.as_bytes()).after(self.latest_write.token());
+        let response = self.storage_client.lock().await.get(request).await;
+        match response {
+            Ok(response) => serde_json::from_slice(&response.into_inner().value)
+                .map_err(|e| ManagementServiceError::Service(e.into())),
+            Err(status) if status.code() == Code::NotFound => {
+                Ok(NotificationPreferences::default())
+            }
+            Err(status) => Err(ManagementServiceError::Service(anyhow!(
+                "Failed to read notification preferences: {:?}",
+                status
+            ))),
+        }
+    }
+
+    async fn write_lineage(&self, record: &LineageRecord) -> Result<(), ManagementServiceError> {
+        let key = LineageRecord::key(&record.data_id);
+        let value =
+            serde_json::to_vec(record).map_err(|e| ManagementServiceError::Service(e.into()))?;
+        let response = self
+            .storage_client
+            .lock()
+            .await
+            .put(PutRequest::new(key, value))
+            .await
+            .map_err(|e| ManagementServiceError::Service(e.into()))?;
+        self.latest_write.observe(response.into_inner().token);
         Ok(())
     }
 
+    // Data uploaded by the owners has no lineage.
+    async fn read_lineage(
+        &self,
+        data_id: &ExternalID,
+    ) -> Result<Option<LineageRecord>, ManagementServiceError> {
+        let request = GetRequest::new(LineageRecord::key(data_id)).after(self.latest_write.token());
+        let response = self.storage_client.lock().await.get(request).await;
+        match response {
+            Ok(response) => serde_json::from_slice(&response.into_inner().value)
+                .map(Some)
+                .map_err(|e| ManagementServiceError::Service(e.into())),
+            Err(status) if status.code() == Code::NotFound => Ok(None),
+            Err(status) => Err(ManagementServiceError::Service(anyhow!(
+                "Failed to read lineage: {:?}",
+                status
+            ))),
+        }
+    }
+
+    /// Lineage of the data and its upstream data in breadth-first order.
+    async fn collect_lineage(
+        &self,
+        data_id: ExternalID,
+    ) -> Result<Vec<LineageRecord>, ManagementServiceError> {
+        let mut lineage = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([data_id]);
+        while let Some(data_id) = queue.pop_front() {
+            if lineage.len() >= MAX_LINEAGE_RECORDS {
+                break;
+            }
+            if !visited.insert(data_id.to_string()) {
+                continue;
+            }
+            if let Some(record) = self.read_lineage(&data_id).await? {
+                queue.extend(record.upstream());
+                lineage.push(record);
+            }
+        }
+        Ok(lineage)
+    }
+
     #[cfg(test_mode)]
     async fn add_mock_data(&self) -> anyhow::Result<()> {
         let mut output_file = create_fusion_data(vec!["mock_user1", "frontend_user"])?;
@@ -1134,6 +3396,10 @@ impl TeaclaveManagementService {
     }
 }
 
+fn notification_preferences_key(user_id: &UserID) -> String {
+    format!("{}-{}", NOTIFICATION_PREFERENCES_PREFIX, user_id)
+}
+
 fn get_request_user_id<T>(request: &Request<T>) -> Result<UserID, ManagementServiceError> {
     let user_id = request
         .metadata()
@@ -1143,6 +3409,16 @@ fn get_request_user_id<T>(request: &Request<T>) -> Result<UserID, ManagementServ
     Ok(user_id.to_string().into())
 }
 
+// Requests not coming through the frontend have no trace id.
+fn get_request_trace_id<T>(request: &Request<T>) -> String {
+    request
+        .metadata()
+        .get("trace_id")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
 fn get_request_role<T>(request: &Request<T>) -> Result<UserRole, ManagementServiceError> {
     let role = request
         .metadata()
@@ -1167,8 +3443,9 @@ pub mod tests {
     use serde_json::json;
     use std::collections::HashMap;
     use teaclave_types::{
-        hashmap, Executor, FileAuthTag, FileCrypto, FunctionArguments, FunctionInput,
-        FunctionInputFile, FunctionOutput, FunctionOutputFile,
+        hashmap, ApprovalStatus, ArgumentType, ArgumentsFormat, Executor, FileAuthTag, FileCrypto,
+        FunctionArguments, FunctionInput, FunctionInputFile, FunctionOutput, FunctionOutputFile,
+        OwnerList, TaskFileOwners,
     };
     use url::Url;
 
@@ -1192,6 +3469,25 @@ pub mod tests {
         debug!("file: {:?}", deserialized_file);
     }
 
+    pub fn expire_output_file() {
+        let url = Url::parse("s3://bucket_id/path?token=mock_token").unwrap();
+        let output_file = TeaclaveOutputFile::new(url, FileCrypto::default(), vec!["mock_user"]);
+        assert!(output_file.expires_at.is_none());
+        assert!(!output_file.is_expired());
+
+        let output_file = output_file.retention(3600);
+        assert!(!output_file.is_expired());
+
+        let output_file = TeaclaveOutputFile {
+            expires_at: Some(0),
+            ..output_file
+        };
+        assert!(output_file.is_expired());
+        let value = output_file.to_vec().unwrap();
+        let deserialized_file = TeaclaveOutputFile::from_slice(&value).unwrap();
+        assert!(deserialized_file.is_expired());
+    }
+
     pub fn handle_function() {
         let function_input = FunctionInput::new("input", "input_desc", false);
         let function_output = FunctionOutput::new("output", "output_desc", false);
@@ -1253,6 +3549,121 @@ pub mod tests {
         debug!("task: {:?}", deserialized_task);
     }
 
+    pub fn approve_and_reject_task() {
+        let function = FunctionBuilder::new()
+            .id(Uuid::new_v4())
+            .name("mock_function")
+            .payload(b"python script".to_vec())
+            .inputs(vec![FunctionInput::new("input", "", false)])
+            .public(true)
+            .owner("mock_user")
+            .build();
+        let input_owners: HashMap<String, OwnerList> =
+            hashmap!("input" => OwnerList::new(["data_owner"]));
+        let task = Task::<Create>::new(
+            UserID::from("mock_user"),
+            Executor::MesaPy,
+            FunctionArguments::default(),
+            input_owners,
+            HashMap::new(),
+            function,
+        )
+        .unwrap();
+        let mut ts: TaskState = task.into();
+        let creator = UserID::from("mock_user");
+        let owner = UserID::from("data_owner");
+        assert_eq!(ts.approval_status(&owner), ApprovalStatus::Pending);
+
+        // Data owners can reject before assigning data
+        ts.reject(&owner, "purpose is too broad").unwrap();
+        assert_eq!(ts.approval_status(&owner), ApprovalStatus::Rejected);
+        assert_eq!(ts.approvals[&owner].comment, "purpose is too broad");
+        assert!(ts.reject(&UserID::from("stranger"), "").is_err());
+
+        // and change their mind later
+        ts.status = TaskStatus::DataAssigned;
+        let mut task: Task<Approve> = ts.clone().try_into().unwrap();
+        task.approve(&creator, "").unwrap();
+        task.approve(&owner, "approved for this purpose").unwrap();
+        let approved: TaskState = task.into();
+        assert_eq!(approved.status, TaskStatus::Approved);
+        assert_eq!(approved.approval_status(&owner), ApprovalStatus::Approved);
+        assert!(approved.clone().reject(&owner, "").is_err());
+
+        // Pending participants keep a task from being approved
+        let mut task: Task<Approve> = ts.clone().try_into().unwrap();
+        task.approve(&creator, "").unwrap();
+        let pending: TaskState = task.into();
+        assert_eq!(pending.status, TaskStatus::DataAssigned);
+
+        ts.approval_deadline = 1;
+        assert!(ts.approval_expired());
+        let mut task: Task<Approve> = ts.try_into().unwrap();
+        assert!(task.approve(&owner, "").is_err());
+    }
+
+    pub fn assign_data_by_access_policy() {
+        let function = FunctionBuilder::new()
+            .id(Uuid::new_v4())
+            .name("mock_function")
+            .payload(b"python script".to_vec())
+            .inputs(vec![FunctionInput::new("input", "", false)])
+            .public(true)
+            .owner("function_owner")
+            .build();
+        let input_owners: HashMap<String, OwnerList> =
+            hashmap!("input" => OwnerList::new(["data_owner"]));
+        let task = Task::<Create>::new(
+            UserID::from("mock_user"),
+            Executor::MesaPy,
+            FunctionArguments::default(),
+            input_owners,
+            HashMap::new(),
+            function.clone(),
+        )
+        .unwrap();
+        let ts: TaskState = task.into();
+        let owner = UserID::from("data_owner");
+        let file = |policy| {
+            let url = Url::parse("s3://bucket_id/path?token=mock_token").unwrap();
+            TeaclaveInputFile::new(
+                url,
+                FileAuthTag::mock(),
+                FileCrypto::default(),
+                vec!["data_owner"],
+            )
+            .access_policy(policy)
+        };
+
+        // Files disallowing the function cannot be assigned
+        let other_function = ExternalID::new("function", Uuid::new_v4());
+        let mut task: Task<Assign> = ts.clone().try_into().unwrap();
+        let policy = InputAccessPolicy::new(vec![other_function], vec![]);
+        assert!(task.assign_input(&owner, "input", file(policy)).is_err());
+
+        // Files without policies are approved manually
+        let mut task: Task<Assign> = ts.clone().try_into().unwrap();
+        task.assign_input(&owner, "input", file(InputAccessPolicy::default()))
+            .unwrap();
+        assert!(!task.approve_by_access_policy(&owner).unwrap());
+
+        let mut task: Task<Assign> = ts.clone().try_into().unwrap();
+        let policy = InputAccessPolicy::new(vec![], vec![UserID::from("function_owner")]);
+        task.assign_input(&owner, "input", file(policy)).unwrap();
+        assert!(task.approve_by_access_policy(&owner).unwrap());
+        let approved: TaskState = task.into();
+        assert_eq!(approved.status, TaskStatus::DataAssigned);
+        assert_eq!(approved.approval_status(&owner), ApprovalStatus::Approved);
+
+        // Decisions made already are kept
+        let mut rejected = ts;
+        rejected.reject(&owner, "").unwrap();
+        let mut task: Task<Assign> = rejected.try_into().unwrap();
+        let policy = InputAccessPolicy::new(vec![function.external_id()], vec![]);
+        task.assign_input(&owner, "input", file(policy)).unwrap();
+        assert!(!task.approve_by_access_policy(&owner).unwrap());
+    }
+
     pub fn handle_staged_task() {
         let function = FunctionBuilder::new()
             .id(Uuid::new_v4())
@@ -1350,4 +3761,62 @@ pub mod tests {
         let err_msg = format!("{:?}", result.unwrap_err());
         assert!(err_msg.contains("invalid type: string \\\"10\\\", expected usize"));
     }
+
+    pub fn typed_function_arguments() {
+        let create_task = |arguments: FunctionArguments| {
+            let arguments = vec![
+                FunctionArgument::new("arg_bool", "", true).value_type(ArgumentType::Boolean),
+                FunctionArgument::new("arg_usize", "10", false).value_type(ArgumentType::Integer),
+                FunctionArgument::new("arg_list", "", true).value_type(ArgumentType::Array),
+            ];
+            let function = FunctionBuilder::new()
+                .id(Uuid::new_v4())
+                .name("mock_function3")
+                .description("mock function")
+                .arguments(arguments)
+                .public(true)
+                .owner("mock_user")
+                .build();
+            Task::<Create>::new(
+                "mock_user".into(),
+                Executor::Builtin,
+                arguments,
+                TaskFileOwners::default(),
+                TaskFileOwners::default(),
+                function,
+            )
+        };
+
+        let arguments =
+            FunctionArguments::from_json(json!({"arg_bool": true, "arg_list": [1, 2]})).unwrap();
+        let formats = [
+            ArgumentsFormat::Json,
+            ArgumentsFormat::Cbor,
+            ArgumentsFormat::Protobuf,
+        ];
+        for format in formats {
+            let typed_arguments = TypedArguments::new(format, arguments.clone()).unwrap();
+            let request = CreateTaskRequest::new().typed_function_arguments(typed_arguments);
+            let arguments: FunctionArguments = request
+                .typed_function_arguments
+                .unwrap()
+                .try_into()
+                .unwrap();
+            let ts: TaskState = create_task(arguments).unwrap().into();
+            let arguments = ts.function_arguments;
+            assert!(arguments.get_bool("arg_bool").unwrap());
+            assert_eq!(arguments.get_i64("arg_usize").unwrap(), 10);
+            assert_eq!(arguments.get_as::<Vec<u8>>("arg_list").unwrap(), vec![1, 2]);
+        }
+
+        let arguments =
+            FunctionArguments::from_json(json!({"arg_bool": "true", "arg_list": []})).unwrap();
+        assert!(create_task(arguments).is_err());
+
+        let typed_arguments = TypedArguments {
+            content_type: "text/plain".to_string(),
+            payload: b"{}".to_vec(),
+        };
+        assert!(FunctionArguments::try_from(typed_arguments).is_err());
+    }
 }
@@ -22,5 +22,90 @@ pub use proto::teaclave_access_control_server::{
 };
 pub use proto::*;
 
+pub type ManagePolicyRequest = crate::teaclave_frontend_service::ManagePolicyRequest;
+pub type ManagePolicyResponse = crate::teaclave_frontend_service::ManagePolicyResponse;
+pub type PolicyAction = crate::teaclave_frontend_service::PolicyAction;
+pub type PolicyRule = crate::teaclave_frontend_service::PolicyRule;
+pub type SetUserAttributesRequest = crate::teaclave_frontend_service::SetUserAttributesRequest;
+pub type GetUserAttributesRequest = crate::teaclave_frontend_service::GetUserAttributesRequest;
+pub type GetUserAttributesResponse = crate::teaclave_frontend_service::GetUserAttributesResponse;
+pub type SetDataAttributesRequest = crate::teaclave_frontend_service::SetDataAttributesRequest;
+pub type GetDataAttributesRequest = crate::teaclave_frontend_service::GetDataAttributesRequest;
+pub type GetDataAttributesResponse = crate::teaclave_frontend_service::GetDataAttributesResponse;
+
 impl_custom_server!(TeaclaveAccessControlServer, TeaclaveAccessControl);
 impl_custom_client!(TeaclaveAccessControlClient);
+
+impl Denial {
+    pub fn new(code: DenialCode, constraint: impl Into<String>) -> Self {
+        Self {
+            code: code as i32,
+            constraint: constraint.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn user_id(self, user_id: impl Into<String>) -> Self {
+        Self {
+            user_id: user_id.into(),
+            ..self
+        }
+    }
+
+    pub fn data_id(self, data_id: impl Into<String>) -> Self {
+        Self {
+            data_id: data_id.into(),
+            ..self
+        }
+    }
+
+    /// The denial without the constraint, which may reveal attributes of
+    /// users other than the one asking.
+    pub fn redacted(&self) -> String {
+        format!(
+            "{} may not access {} ({})",
+            self.user_id,
+            self.data_id,
+            self.code().as_str_name()
+        )
+    }
+
+    /// Carries the denial in the details of `status`, so that services
+    /// forwarding the status can log it.
+    pub fn attach(&self, status: tonic::Status) -> tonic::Status {
+        let details = prost::Message::encode_to_vec(self);
+        tonic::Status::with_details(
+            status.code(),
+            status.message(),
+            tonic::codegen::Bytes::from(details),
+        )
+    }
+
+    pub fn from_status(status: &tonic::Status) -> Option<Self> {
+        if status.details().is_empty() {
+            return None;
+        }
+        prost::Message::decode(status.details()).ok()
+    }
+}
+
+impl std::fmt::Display for Denial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.data_id.is_empty() {
+            write!(f, "{} lacks {}", self.user_id, self.constraint)
+        } else {
+            write!(
+                f,
+                "{} to {}: {} is required",
+                self.user_id, self.data_id, self.constraint
+            )
+        }
+    }
+}
+
+impl AuthorizeBatchResponse {
+    /// Denials of the objects denied, in the order of the request.
+    pub fn denials(&self) -> impl Iterator<Item = &Denial> {
+        self.decisions.iter().filter_map(|d| d.denial.as_ref())
+    }
+}
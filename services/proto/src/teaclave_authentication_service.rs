@@ -74,14 +74,31 @@ impl UserLoginRequest {
         Self {
             id: id.into(),
             password: password.into(),
+            provider: String::new(),
+            totp_code: String::new(),
+        }
+    }
+
+    pub fn provider(self, provider: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            ..self
+        }
+    }
+
+    pub fn totp_code(self, totp_code: impl Into<String>) -> Self {
+        Self {
+            totp_code: totp_code.into(),
+            ..self
         }
     }
 }
 
 impl UserLoginResponse {
-    pub fn new(token: impl Into<String>) -> Self {
+    pub fn new(token: impl Into<String>, id: impl Into<String>) -> Self {
         Self {
             token: token.into(),
+            id: id.into(),
         }
     }
 }
@@ -100,6 +117,34 @@ impl ResetUserPasswordRequest {
     }
 }
 
+impl ChangePasswordRequest {
+    pub fn new(
+        id: impl Into<String>,
+        old_password: impl Into<String>,
+        new_password: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            old_password: old_password.into(),
+            new_password: new_password.into(),
+            totp_code: String::new(),
+        }
+    }
+
+    pub fn totp_code(self, totp_code: impl Into<String>) -> Self {
+        Self {
+            totp_code: totp_code.into(),
+            ..self
+        }
+    }
+}
+
+impl ForcePasswordResetRequest {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self { id: id.into() }
+    }
+}
+
 impl DeleteUserRequest {
     pub fn new(id: impl Into<String>) -> Self {
         Self { id: id.into() }
@@ -108,13 +153,54 @@ impl DeleteUserRequest {
 
 impl ListUsersRequest {
     pub fn new(id: impl Into<String>) -> Self {
-        Self { id: id.into() }
+        Self {
+            id: id.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn page(self, offset: usize, limit: usize) -> Self {
+        Self {
+            offset: offset as u64,
+            limit: limit as u64,
+            ..self
+        }
     }
 }
 
 impl ListUsersResponse {
-    pub fn new(ids: Vec<std::string::String>) -> Self {
-        Self { ids }
+    pub fn new(ids: Vec<std::string::String>, total: usize) -> Self {
+        Self {
+            ids,
+            total: total as u64,
+        }
+    }
+}
+
+impl GenerateTotpSecretRequest {
+    pub fn new(code: impl Into<String>) -> Self {
+        Self { code: code.into() }
+    }
+}
+
+impl GenerateTotpSecretResponse {
+    pub fn new(secret: impl Into<String>, uri: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+            uri: uri.into(),
+        }
+    }
+}
+
+impl VerifyTotpRequest {
+    pub fn new(code: impl Into<String>) -> Self {
+        Self { code: code.into() }
+    }
+}
+
+impl VerifyTotpResponse {
+    pub fn new(recovery_codes: Vec<String>) -> Self {
+        Self { recovery_codes }
     }
 }
 
@@ -134,6 +220,31 @@ impl UserAuthenticateResponse {
     }
 }
 
+impl ListRevocationsRequest {
+    pub fn new(epoch: impl Into<String>, after: u64) -> Self {
+        Self {
+            epoch: epoch.into(),
+            after,
+        }
+    }
+}
+
+impl ListRevocationsResponse {
+    pub fn new(
+        epoch: impl Into<String>,
+        latest: u64,
+        user_ids: Vec<String>,
+        complete: bool,
+    ) -> Self {
+        Self {
+            epoch: epoch.into(),
+            latest,
+            user_ids,
+            complete,
+        }
+    }
+}
+
 impl std::convert::TryFrom<proto::UserAuthClaims> for UserAuthClaims {
     type Error = Error;
 
@@ -20,7 +20,9 @@ pub use proto::*;
 
 use teaclave_crypto::TeaclaveFile128Key;
 use teaclave_types::{
-    Entry, EntryBuilder, FileCrypto, TaskFailure, TaskOutputs, TaskResult, TaskStatus,
+    Entry, EntryBuilder, ExecutionReceipt, FileAuthTag, FileCrypto, FileTransferRecord,
+    ForensicBundleFile, HandleFileCommand, OutputArtifact, TaskFailure, TaskLogFile, TaskOutputs,
+    TaskProfile, TaskProgress, TaskResult, TaskStatus, TaskUsage,
 };
 
 use std::convert::TryInto;
@@ -79,6 +81,125 @@ impl std::convert::From<TeaclaveFile128Key> for proto::FileCryptoInfo {
     }
 }
 
+impl std::convert::TryFrom<proto::TaskLogFile> for TaskLogFile {
+    type Error = Error;
+    fn try_from(proto: proto::TaskLogFile) -> Result<Self> {
+        let crypto_info = proto
+            .crypto_info
+            .ok_or_else(|| anyhow::anyhow!("Missing crypto info"))?
+            .try_into()?;
+        let ret = TaskLogFile {
+            url: url::Url::parse(&proto.url)?,
+            crypto_info,
+            cmac: FileAuthTag::from_bytes(&proto.cmac)?,
+            truncated: proto.truncated,
+        };
+        Ok(ret)
+    }
+}
+
+impl std::convert::From<TaskLogFile> for proto::TaskLogFile {
+    fn from(log_file: TaskLogFile) -> Self {
+        proto::TaskLogFile {
+            url: log_file.url.to_string(),
+            crypto_info: Some(log_file.crypto_info.into()),
+            cmac: log_file.cmac.to_bytes(),
+            truncated: log_file.truncated,
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::ForensicBundleFile> for ForensicBundleFile {
+    type Error = Error;
+    fn try_from(proto: proto::ForensicBundleFile) -> Result<Self> {
+        let crypto_info = proto
+            .crypto_info
+            .ok_or_else(|| anyhow::anyhow!("Missing crypto info"))?
+            .try_into()?;
+        let ret = ForensicBundleFile {
+            url: url::Url::parse(&proto.url)?,
+            crypto_info,
+            cmac: FileAuthTag::from_bytes(&proto.cmac)?,
+        };
+        Ok(ret)
+    }
+}
+
+impl std::convert::From<ForensicBundleFile> for proto::ForensicBundleFile {
+    fn from(bundle: ForensicBundleFile) -> Self {
+        proto::ForensicBundleFile {
+            url: bundle.url.to_string(),
+            crypto_info: Some(bundle.crypto_info.into()),
+            cmac: bundle.cmac.to_bytes(),
+        }
+    }
+}
+
+impl std::convert::From<proto::TaskProgress> for TaskProgress {
+    fn from(proto: proto::TaskProgress) -> Self {
+        TaskProgress {
+            percentage: proto.percentage,
+            state: proto.state,
+            updated_at: proto.updated_at,
+        }
+    }
+}
+
+impl std::convert::From<TaskProgress> for proto::TaskProgress {
+    fn from(progress: TaskProgress) -> Self {
+        proto::TaskProgress {
+            percentage: progress.percentage,
+            state: progress.state,
+            updated_at: progress.updated_at,
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::FileTransferRecord> for FileTransferRecord {
+    type Error = Error;
+    fn try_from(proto: proto::FileTransferRecord) -> Result<Self> {
+        let cmd = match proto::FileTransferCommand::from_i32(proto.cmd) {
+            Some(proto::FileTransferCommand::Download) => HandleFileCommand::Download,
+            Some(proto::FileTransferCommand::Upload) => HandleFileCommand::Upload,
+            Some(proto::FileTransferCommand::Delete) => HandleFileCommand::Delete,
+            None => bail!("invalid file transfer command"),
+        };
+        let ret = FileTransferRecord {
+            task_id: uuid::Uuid::parse_str(&proto.task_id)?,
+            user_id: proto.user_id,
+            trace_id: proto.trace_id,
+            cmd,
+            remote: proto.remote,
+            bytes: proto.bytes,
+            duration_ms: proto.duration_ms,
+            success: proto.success,
+            timestamp: proto.timestamp,
+        };
+        Ok(ret)
+    }
+}
+
+impl std::convert::From<FileTransferRecord> for proto::FileTransferRecord {
+    fn from(record: FileTransferRecord) -> Self {
+        let cmd = match record.cmd {
+            HandleFileCommand::Download => proto::FileTransferCommand::Download,
+            HandleFileCommand::Upload => proto::FileTransferCommand::Upload,
+            HandleFileCommand::Delete => proto::FileTransferCommand::Delete,
+        };
+        proto::FileTransferRecord {
+            task_id: record.task_id.to_string(),
+            user_id: record.user_id,
+            trace_id: record.trace_id,
+            cmd: cmd as i32,
+            remote: record.remote,
+            bytes: record.bytes,
+            duration_ms: record.duration_ms,
+            success: record.success,
+            timestamp: record.timestamp,
+        }
+    }
+}
+
 pub fn i32_to_task_status(status: i32) -> Result<TaskStatus> {
     let ret = match proto::TaskStatus::from_i32(status) {
         Some(proto::TaskStatus::Created) => TaskStatus::Created,
@@ -114,6 +235,13 @@ impl std::convert::TryFrom<proto::TaskOutputs> for TaskOutputs {
             return_value: proto.return_value,
             tags_map: proto.tags_map.try_into()?,
             log: proto.log,
+            receipt: proto.receipt.map(ExecutionReceipt::from),
+            profile: proto.profile.map(TaskProfile::from),
+            artifacts: proto
+                .artifacts
+                .into_iter()
+                .map(|(name, artifact)| Ok((name, artifact.try_into()?)))
+                .collect::<Result<_>>()?,
         };
         Ok(ret)
     }
@@ -124,6 +252,113 @@ impl std::convert::From<TaskOutputs> for proto::TaskOutputs {
             return_value: outputs.return_value,
             tags_map: outputs.tags_map.into(),
             log: outputs.log,
+            receipt: outputs.receipt.map(proto::ExecutionReceipt::from),
+            profile: outputs.profile.map(proto::TaskProfile::from),
+            artifacts: outputs
+                .artifacts
+                .into_iter()
+                .map(|(name, artifact)| (name, artifact.into()))
+                .collect(),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::OutputArtifact> for OutputArtifact {
+    type Error = Error;
+    fn try_from(proto: proto::OutputArtifact) -> Result<Self> {
+        let url = url::Url::parse(&proto.url)?;
+        Ok(OutputArtifact::new(
+            url,
+            proto.size,
+            proto.sha256,
+            proto.uploaded_at,
+        ))
+    }
+}
+
+impl std::convert::From<OutputArtifact> for proto::OutputArtifact {
+    fn from(artifact: OutputArtifact) -> Self {
+        proto::OutputArtifact {
+            url: artifact.url.to_string(),
+            size: artifact.size,
+            sha256: artifact.sha256,
+            uploaded_at: artifact.uploaded_at,
+        }
+    }
+}
+
+impl std::convert::From<proto::ExecutionReceipt> for ExecutionReceipt {
+    fn from(proto: proto::ExecutionReceipt) -> Self {
+        ExecutionReceipt {
+            task_id: proto.task_id,
+            function_hash: proto.function_hash,
+            input_cmacs: proto.input_cmacs.into_iter().collect(),
+            output_cmacs: proto.output_cmacs.into_iter().collect(),
+            return_value_hash: proto.return_value_hash,
+            mr_enclave: proto.mr_enclave,
+            mr_signer: proto.mr_signer,
+            certificate: proto.certificate,
+            signature: proto.signature,
+        }
+    }
+}
+
+impl std::convert::From<ExecutionReceipt> for proto::ExecutionReceipt {
+    fn from(receipt: ExecutionReceipt) -> Self {
+        proto::ExecutionReceipt {
+            task_id: receipt.task_id,
+            function_hash: receipt.function_hash,
+            input_cmacs: receipt.input_cmacs.into_iter().collect(),
+            output_cmacs: receipt.output_cmacs.into_iter().collect(),
+            return_value_hash: receipt.return_value_hash,
+            mr_enclave: receipt.mr_enclave,
+            mr_signer: receipt.mr_signer,
+            certificate: receipt.certificate,
+            signature: receipt.signature,
+        }
+    }
+}
+
+impl std::convert::From<proto::TaskProfile> for TaskProfile {
+    fn from(proto: proto::TaskProfile) -> Self {
+        TaskProfile {
+            staging_ms: proto.staging_ms,
+            decryption_ms: proto.decryption_ms,
+            execution_ms: proto.execution_ms,
+            conversion_ms: proto.conversion_ms,
+            upload_ms: proto.upload_ms,
+        }
+    }
+}
+
+impl std::convert::From<TaskProfile> for proto::TaskProfile {
+    fn from(profile: TaskProfile) -> Self {
+        proto::TaskProfile {
+            staging_ms: profile.staging_ms,
+            decryption_ms: profile.decryption_ms,
+            execution_ms: profile.execution_ms,
+            conversion_ms: profile.conversion_ms,
+            upload_ms: profile.upload_ms,
+        }
+    }
+}
+
+impl std::convert::From<proto::TaskUsage> for TaskUsage {
+    fn from(proto: proto::TaskUsage) -> Self {
+        TaskUsage {
+            duration_ms: proto.duration_ms,
+            input_bytes: proto.input_bytes,
+            output_bytes: proto.output_bytes,
+        }
+    }
+}
+
+impl std::convert::From<TaskUsage> for proto::TaskUsage {
+    fn from(usage: TaskUsage) -> Self {
+        proto::TaskUsage {
+            duration_ms: usage.duration_ms,
+            input_bytes: usage.input_bytes,
+            output_bytes: usage.output_bytes,
         }
     }
 }
@@ -133,6 +368,7 @@ impl std::convert::TryFrom<proto::TaskFailure> for TaskFailure {
     fn try_from(proto: proto::TaskFailure) -> Result<Self> {
         let ret = TaskFailure {
             reason: proto.reason,
+            forensic_bundle: proto.forensic_bundle.map(TryInto::try_into).transpose()?,
         };
         Ok(ret)
     }
@@ -141,6 +377,7 @@ impl std::convert::From<TaskFailure> for proto::TaskFailure {
     fn from(outputs: TaskFailure) -> Self {
         proto::TaskFailure {
             reason: outputs.reason,
+            forensic_bundle: outputs.forensic_bundle.map(Into::into),
         }
     }
 }
@@ -227,6 +464,8 @@ pub enum ExecutorCommand {
     NoAction,
     Stop,
     NewTask,
+    Reattest,
+    Throttle,
 }
 
 impl Default for ExecutorCommand {
@@ -242,6 +481,8 @@ impl std::convert::TryFrom<proto::ExecutorCommand> for ExecutorCommand {
             proto::ExecutorCommand::NoAction => Ok(ExecutorCommand::NoAction),
             proto::ExecutorCommand::Stop => Ok(ExecutorCommand::Stop),
             proto::ExecutorCommand::NewTask => Ok(ExecutorCommand::NewTask),
+            proto::ExecutorCommand::Reattest => Ok(ExecutorCommand::Reattest),
+            proto::ExecutorCommand::Throttle => Ok(ExecutorCommand::Throttle),
         }
     }
 }
@@ -252,6 +493,8 @@ impl std::convert::From<ExecutorCommand> for proto::ExecutorCommand {
             ExecutorCommand::NoAction => proto::ExecutorCommand::NoAction,
             ExecutorCommand::Stop => proto::ExecutorCommand::Stop,
             ExecutorCommand::NewTask => proto::ExecutorCommand::NewTask,
+            ExecutorCommand::Reattest => proto::ExecutorCommand::Reattest,
+            ExecutorCommand::Throttle => proto::ExecutorCommand::Throttle,
         }
     }
 }
@@ -263,6 +506,8 @@ impl std::convert::TryFrom<i32> for ExecutorCommand {
             Some(proto::ExecutorCommand::NoAction) => Ok(ExecutorCommand::NoAction),
             Some(proto::ExecutorCommand::Stop) => Ok(ExecutorCommand::Stop),
             Some(proto::ExecutorCommand::NewTask) => Ok(ExecutorCommand::NewTask),
+            Some(proto::ExecutorCommand::Reattest) => Ok(ExecutorCommand::Reattest),
+            Some(proto::ExecutorCommand::Throttle) => Ok(ExecutorCommand::Throttle),
             _ => bail!("invalid executor status"),
         }
     }
@@ -274,6 +519,8 @@ impl std::convert::From<ExecutorCommand> for i32 {
             ExecutorCommand::NoAction => proto::ExecutorCommand::NoAction as i32,
             ExecutorCommand::Stop => proto::ExecutorCommand::Stop as i32,
             ExecutorCommand::NewTask => proto::ExecutorCommand::NewTask as i32,
+            ExecutorCommand::Reattest => proto::ExecutorCommand::Reattest as i32,
+            ExecutorCommand::Throttle => proto::ExecutorCommand::Throttle as i32,
         }
     }
 }
@@ -295,6 +542,7 @@ impl std::convert::TryFrom<proto::Entry> for Entry {
             .user(proto.user)
             .message(proto.message.clone())
             .result(proto.result)
+            .trace_id(proto.trace_id)
             .build();
 
         Ok(entry)
@@ -309,6 +557,26 @@ impl From<Entry> for proto::Entry {
             user: entry.user(),
             message: entry.message(),
             result: entry.result(),
+            trace_id: entry.trace_id(),
         }
     }
 }
+
+// Internal errors are only told to the clients as such, while the detailed
+// error chain is carried in this metadata for the frontend service.
+const ERROR_DETAIL_KEY: &str = "error-detail-bin";
+
+/// Carries the detailed chain of an internal error in `status`, which the
+/// frontend service shows to the users granted to see it only.
+pub fn attach_error_detail(mut status: tonic::Status, detail: &str) -> tonic::Status {
+    let value = tonic::metadata::MetadataValue::from_bytes(detail.as_bytes());
+    status.metadata_mut().insert_bin(ERROR_DETAIL_KEY, value);
+    status
+}
+
+/// Removes the detailed error chain from `status`, if any.
+pub fn take_error_detail(status: &mut tonic::Status) -> Option<String> {
+    let value = status.metadata_mut().remove_bin(ERROR_DETAIL_KEY)?;
+    let bytes = value.to_bytes().ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -16,12 +16,16 @@
 // under the License.
 
 use crate::teaclave_frontend_service_proto as proto;
-use anyhow::{Error, Result};
+use anyhow::{ensure, Error, Result};
 use core::convert::TryInto;
+use prost::Message;
 use std::collections::HashMap;
 use teaclave_types::{
-    Entry, Executor, ExecutorType, ExternalID, FileAuthTag, FileCrypto, Function, FunctionArgument,
-    FunctionArguments, FunctionBuilder, FunctionInput, FunctionOutput, OwnerList, TaskFileOwners,
+    ArgumentsFormat, Entry, Executor, ExecutorFeatures, ExecutorType, ExternalID, FileAuthTag,
+    FileCrypto, Function, FunctionArgument, FunctionArguments, FunctionBuilder, FunctionInput,
+    FunctionOutput, InputAccessPolicy, LineageRecord, LineageSource, MetadataDump,
+    NotificationPreferences, OutputPolicy, OwnerList, Storable, TaskFileOwners, TaskState,
+    TaskStatus, TaskTimeouts, UsageRecord, UserID,
 };
 use url::Url;
 
@@ -33,6 +37,28 @@ pub use proto::*;
 impl_custom_server!(TeaclaveFrontendServer, TeaclaveFrontend);
 impl_custom_client!(TeaclaveFrontendClient);
 
+/// API version of the frontend service, bumped when the messages change in
+/// ways the SDKs of older versions cannot decode.
+pub const API_VERSION: u32 = 1;
+/// SDKs of older API versions are asked to upgrade by `GetApiVersion`.
+pub const MIN_API_VERSION: u32 = 1;
+
+impl GetApiVersionRequest {
+    pub fn new() -> Self {
+        Self {
+            client_api_version: API_VERSION,
+        }
+    }
+}
+
+impl GetApiVersionResponse {
+    /// Whether an SDK of `API_VERSION` can talk to the service. The service
+    /// may lack the RPCs added after its API version.
+    pub fn is_compatible(&self) -> bool {
+        self.min_api_version <= API_VERSION
+    }
+}
+
 impl RegisterInputFileRequest {
     pub fn new(url: Url, cmac: FileAuthTag, crypto: impl Into<FileCrypto>) -> Self {
         Self {
@@ -73,6 +99,22 @@ impl RegisterOutputFileRequest {
         Self {
             url: url.as_str().to_string(),
             crypto_info: Some(crypto.into().into()),
+            bind_context: false,
+            retention_secs: 0,
+        }
+    }
+
+    pub fn bind_context(self, bind_context: bool) -> Self {
+        Self {
+            bind_context,
+            ..self
+        }
+    }
+
+    pub fn retention(self, retention: std::time::Duration) -> Self {
+        Self {
+            retention_secs: retention.as_secs(),
+            ..self
         }
     }
 }
@@ -106,10 +148,83 @@ impl RegisterFusionOutputRequest {
     pub fn new(owner_list: impl Into<OwnerList>) -> Self {
         Self {
             owner_list: owner_list.into().into(),
+            output_policies: Vec::new(),
+        }
+    }
+
+    pub fn output_policies(self, policies: impl IntoIterator<Item = OutputPolicy>) -> Self {
+        Self {
+            output_policies: policies.into_iter().map(|p| p.into()).collect(),
+            ..self
+        }
+    }
+
+    /// The policies to register with the output, which are all valid.
+    pub fn policies(&self) -> Result<Vec<OutputPolicy>> {
+        self.output_policies
+            .iter()
+            .map(|p| {
+                let policy = OutputPolicy::try_from(p.clone())?;
+                policy.validate()?;
+                Ok(policy)
+            })
+            .collect()
+    }
+}
+
+impl From<OutputPolicy> for proto::OutputPolicy {
+    fn from(policy: OutputPolicy) -> Self {
+        use proto::output_policy::Policy;
+
+        let policy = match policy {
+            OutputPolicy::MinRowCount {
+                min_rows,
+                has_headers,
+            } => Policy::MinRowCount(proto::MinRowCountPolicy {
+                min_rows,
+                has_headers,
+            }),
+            OutputPolicy::LaplaceNoise {
+                columns,
+                epsilon,
+                sensitivity,
+                has_headers,
+            } => Policy::LaplaceNoise(proto::LaplaceNoisePolicy {
+                columns: columns.into_iter().map(|c| c as u32).collect(),
+                epsilon,
+                sensitivity,
+                has_headers,
+            }),
+        };
+        Self {
+            policy: Some(policy),
         }
     }
 }
 
+impl std::convert::TryFrom<proto::OutputPolicy> for OutputPolicy {
+    type Error = Error;
+
+    fn try_from(proto: proto::OutputPolicy) -> Result<Self> {
+        use proto::output_policy::Policy;
+
+        let policy = match proto.policy {
+            Some(Policy::MinRowCount(p)) => OutputPolicy::min_row_count(p.min_rows, p.has_headers),
+            Some(Policy::LaplaceNoise(p)) => OutputPolicy::laplace_noise(
+                p.columns
+                    .into_iter()
+                    .map(|c| c as usize)
+                    .collect::<Vec<_>>(),
+                p.epsilon,
+                p.sensitivity,
+                p.has_headers,
+            ),
+            None => anyhow::bail!("Missing output policy"),
+        };
+        Ok(policy)
+    }
+}
+
 impl RegisterFusionOutputResponse {
     pub fn new(data_id: ExternalID) -> Self {
         Self {
@@ -147,6 +262,21 @@ impl GetInputFileResponse {
         Self {
             owner: owner.into(),
             cmac: cmac.to_bytes(),
+            allowed_function_ids: Vec::new(),
+            allowed_function_owners: Vec::new(),
+        }
+    }
+
+    pub fn access_policy(self, policy: Option<InputAccessPolicy>) -> Self {
+        let policy = policy.unwrap_or_default();
+        Self {
+            allowed_function_ids: policy.functions.iter().map(|id| id.to_string()).collect(),
+            allowed_function_owners: policy
+                .function_owners
+                .iter()
+                .map(|owner| owner.to_string())
+                .collect(),
+            ..self
         }
     }
 }
@@ -164,6 +294,51 @@ impl GetOutputFileResponse {
         Self {
             owner: owner.into(),
             cmac: cmac.map_or_else(Vec::new, |cmac| cmac.to_bytes()),
+            release_verdict: None,
+            aad: Vec::new(),
+            output_policies: Vec::new(),
+            expires_at: 0,
+        }
+    }
+
+    pub fn expires_at(self, expires_at: Option<i64>) -> Self {
+        Self {
+            expires_at: expires_at.unwrap_or_default(),
+            ..self
+        }
+    }
+
+    pub fn output_policies(self, policies: impl IntoIterator<Item = OutputPolicy>) -> Self {
+        Self {
+            output_policies: policies.into_iter().map(|p| p.into()).collect(),
+            ..self
+        }
+    }
+
+    pub fn context(self, context: Option<teaclave_types::EncryptionContext>) -> Self {
+        Self {
+            aad: context.map_or_else(Vec::new, |context| context.aad()),
+            ..self
+        }
+    }
+
+    pub fn release_verdict(self, verdict: Option<teaclave_types::ReleaseVerdict>) -> Self {
+        Self {
+            release_verdict: verdict.map(Into::into),
+            ..self
+        }
+    }
+}
+
+impl From<teaclave_types::ReleaseVerdict> for ReleaseVerdict {
+    fn from(verdict: teaclave_types::ReleaseVerdict) -> Self {
+        Self {
+            releasable: verdict.releasable,
+            reason: verdict.reason,
+            task_id: verdict
+                .task_id
+                .map(|id| ExternalID::new(TaskState::key_prefix(), id).to_string())
+                .unwrap_or_default(),
         }
     }
 }
@@ -235,11 +410,54 @@ impl RegisterFunctionRequestBuilder {
         self
     }
 
+    pub fn version(mut self, version: impl ToString) -> Self {
+        self.request.version = version.to_string();
+        self
+    }
+
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.request.tags = tags;
+        self
+    }
+
+    pub fn profiling(mut self, profiling: bool) -> Self {
+        self.request.profiling = profiling;
+        self
+    }
+
+    pub fn payload_crypto(mut self, crypto: FileCrypto) -> Self {
+        self.request.payload_crypto_info = Some(crypto.into());
+        self
+    }
+
+    pub fn executor_features(mut self, executor_features: ExecutorFeatures) -> Self {
+        self.request.executor_features = executor_features.into();
+        self
+    }
+
     pub fn build(self) -> RegisterFunctionRequest {
         self.request
     }
 }
 
+// Payloads are encrypted as a whole in memory, and raw ones are in plaintext.
+fn payload_crypto(
+    info: Option<crate::teaclave_common_proto::FileCryptoInfo>,
+) -> Result<Option<FileCrypto>> {
+    let crypto = match info {
+        Some(info) => FileCrypto::try_from(info)?,
+        None => return Ok(None),
+    };
+    match crypto {
+        FileCrypto::Raw => Ok(None),
+        crypto if crypto.is_in_memory() => Ok(Some(crypto)),
+        crypto => anyhow::bail!(
+            "Unsupported crypto schema for function payloads: {}",
+            crypto.schema()
+        ),
+    }
+}
+
 // We explicitly construct Function here in case of missing any field
 impl std::convert::TryFrom<RegisterFunctionRequest> for FunctionBuilder {
     type Error = Error;
@@ -272,7 +490,12 @@ impl std::convert::TryFrom<RegisterFunctionRequest> for FunctionBuilder {
                     .collect::<Result<_>>()?,
             )
             .user_allowlist(request.user_allowlist)
-            .usage_quota((request.usage_quota >= 0).then_some(request.usage_quota)))
+            .usage_quota((request.usage_quota >= 0).then_some(request.usage_quota))
+            .version(request.version)
+            .tags(request.tags)
+            .profiling(request.profiling)
+            .payload_crypto(payload_crypto(request.payload_crypto_info)?)
+            .executor_features(request.executor_features.into()))
     }
 }
 
@@ -284,6 +507,43 @@ impl RegisterFunctionResponse {
     }
 }
 
+impl BeginFunctionUploadRequest {
+    pub fn new(total_size: u64, sha256: impl ToString) -> Self {
+        Self {
+            total_size,
+            sha256: sha256.to_string(),
+        }
+    }
+}
+
+impl BeginFunctionUploadResponse {
+    pub fn new(upload_id: ExternalID, max_chunk_size: u64) -> Self {
+        Self {
+            upload_id: upload_id.to_string(),
+            max_chunk_size,
+        }
+    }
+}
+
+impl UploadFunctionChunkRequest {
+    pub fn new(upload_id: ExternalID, offset: u64, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            upload_id: upload_id.to_string(),
+            offset,
+            data: data.into(),
+        }
+    }
+}
+
+impl CommitFunctionRequest {
+    pub fn new(upload_id: ExternalID, function: RegisterFunctionRequest) -> Self {
+        Self {
+            upload_id: upload_id.to_string(),
+            function: Some(function),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct UpdateFunctionRequestBuilder {
     request: UpdateFunctionRequest,
@@ -362,6 +622,31 @@ impl UpdateFunctionRequestBuilder {
         self
     }
 
+    pub fn version(mut self, version: impl ToString) -> Self {
+        self.request.version = version.to_string();
+        self
+    }
+
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.request.tags = tags;
+        self
+    }
+
+    pub fn profiling(mut self, profiling: bool) -> Self {
+        self.request.profiling = profiling;
+        self
+    }
+
+    pub fn payload_crypto(mut self, crypto: FileCrypto) -> Self {
+        self.request.payload_crypto_info = Some(crypto.into());
+        self
+    }
+
+    pub fn executor_features(mut self, executor_features: ExecutorFeatures) -> Self {
+        self.request.executor_features = executor_features.into();
+        self
+    }
+
     pub fn build(self) -> UpdateFunctionRequest {
         self.request
     }
@@ -401,7 +686,12 @@ impl std::convert::TryFrom<UpdateFunctionRequest> for FunctionBuilder {
                     .collect::<Result<_>>()?,
             )
             .user_allowlist(request.user_allowlist)
-            .usage_quota((request.usage_quota >= 0).then_some(request.usage_quota)))
+            .usage_quota((request.usage_quota >= 0).then_some(request.usage_quota))
+            .version(request.version)
+            .tags(request.tags)
+            .profiling(request.profiling)
+            .payload_crypto(payload_crypto(request.payload_crypto_info)?)
+            .executor_features(request.executor_features.into()))
     }
 }
 
@@ -429,10 +719,53 @@ impl GetFunctionUsageStatsRequest {
     }
 }
 
+impl QueryFunctionUsageRecordsRequest {
+    pub fn new(function_id: ExternalID) -> Self {
+        Self {
+            function_id: function_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Only the runs ended in `[start_time, end_time)`, in seconds since the
+    /// UNIX epoch.
+    pub fn time_range(self, start_time: i64, end_time: i64) -> Self {
+        Self {
+            start_time,
+            end_time,
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: u32) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl std::convert::From<UsageRecord> for FunctionUsageRecord {
+    fn from(record: UsageRecord) -> Self {
+        FunctionUsageRecord {
+            task_id: ExternalID::new(TaskState::key_prefix(), record.task_id).to_string(),
+            user_id: record.user_id.to_string(),
+            status: crate::teaclave_common::i32_from_task_status(record.status),
+            ended_at: record.ended_at,
+            usage: Some(record.usage.into()),
+        }
+    }
+}
+
 impl DeleteFunctionRequest {
     pub fn new(function_id: ExternalID) -> Self {
         Self {
             function_id: function_id.to_string(),
+            cascade: false,
+        }
+    }
+
+    pub fn cascade(self) -> Self {
+        Self {
+            cascade: true,
+            ..self
         }
     }
 }
@@ -468,6 +801,14 @@ impl CreateTaskRequest {
         }
     }
 
+    /// Replaces the arguments set by `function_arguments`.
+    pub fn typed_function_arguments(self, typed_arguments: TypedArguments) -> Self {
+        Self {
+            typed_function_arguments: Some(typed_arguments),
+            ..self
+        }
+    }
+
     pub fn executor(self, executor: Executor) -> Self {
         Self {
             executor: executor.to_string(),
@@ -488,6 +829,77 @@ impl CreateTaskRequest {
             ..self
         }
     }
+
+    pub fn purpose(self, purpose: impl ToString) -> Self {
+        Self {
+            purpose: purpose.to_string(),
+            ..self
+        }
+    }
+
+    pub fn approval_expiry(self, expiry: std::time::Duration) -> Self {
+        Self {
+            approval_expiry_secs: expiry.as_secs(),
+            ..self
+        }
+    }
+
+    pub fn schedule(self, schedule: impl ToString) -> Self {
+        Self {
+            schedule: schedule.to_string(),
+            ..self
+        }
+    }
+
+    pub fn timeouts(self, timeouts: TaskTimeouts) -> Self {
+        Self {
+            staging_timeout_secs: timeouts.staging_secs,
+            execution_timeout_secs: timeouts.execution_secs,
+            upload_timeout_secs: timeouts.upload_secs,
+            ..self
+        }
+    }
+
+    pub fn resource_limits(self, limits: teaclave_types::ResourceLimits) -> Self {
+        Self {
+            resource_limits: Some(limits.into()),
+            ..self
+        }
+    }
+}
+
+impl From<teaclave_types::ResourceLimits> for ResourceLimits {
+    fn from(limits: teaclave_types::ResourceLimits) -> Self {
+        Self {
+            timeout_secs: limits.timeout_secs,
+            max_memory_bytes: limits.max_memory_bytes,
+            max_output_bytes: limits.max_output_bytes,
+        }
+    }
+}
+
+impl From<ResourceLimits> for teaclave_types::ResourceLimits {
+    fn from(limits: ResourceLimits) -> Self {
+        Self::new(
+            limits.timeout_secs,
+            limits.max_memory_bytes,
+            limits.max_output_bytes,
+        )
+    }
+}
+
+impl TypedArguments {
+    pub fn new(format: ArgumentsFormat, arguments: FunctionArguments) -> Result<Self> {
+        let payload = match format {
+            ArgumentsFormat::Json => arguments.into_string().into_bytes(),
+            ArgumentsFormat::Cbor => arguments.to_cbor_vec()?,
+            ArgumentsFormat::Protobuf => proto::ArgumentStruct::from(arguments).encode_to_vec(),
+        };
+        Ok(Self {
+            content_type: format.content_type().to_string(),
+            payload,
+        })
+    }
 }
 
 impl CreateTaskResponse {
@@ -506,6 +918,69 @@ impl GetTaskRequest {
     }
 }
 
+impl ListTasksRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn creator(self, creator: impl ToString) -> Self {
+        Self {
+            creator: creator.to_string(),
+            ..self
+        }
+    }
+
+    pub fn status(mut self, status: TaskStatus) -> Self {
+        self.statuses
+            .push(crate::teaclave_common::i32_from_task_status(status));
+        self
+    }
+
+    pub fn created_between(self, after: i64, before: i64) -> Self {
+        Self {
+            created_after: after,
+            created_before: before,
+            ..self
+        }
+    }
+
+    pub fn page(self, offset: usize, limit: usize) -> Self {
+        Self {
+            offset: offset as u64,
+            limit: limit as u64,
+            ..self
+        }
+    }
+
+    pub fn cursor(self, after: impl ToString, limit: usize) -> Self {
+        Self {
+            cursor: Some(PageCursor::new(after)),
+            limit: limit as u64,
+            ..self
+        }
+    }
+}
+
+impl PageCursor {
+    pub fn new(after: impl ToString) -> Self {
+        Self {
+            after: after.to_string(),
+        }
+    }
+}
+
+impl From<&TaskState> for TaskSummary {
+    fn from(task: &TaskState) -> Self {
+        Self {
+            task_id: task.external_id().to_string(),
+            creator: task.creator.to_string(),
+            function_id: task.function_id.to_string(),
+            status: crate::teaclave_common::i32_from_task_status(task.status.clone()),
+            created_at: task.created_at,
+        }
+    }
+}
+
 impl AssignDataRequest {
     pub fn new(
         task_id: ExternalID,
@@ -526,42 +1001,159 @@ impl ApproveTaskRequest {
     pub fn new(task_id: ExternalID) -> Self {
         Self {
             task_id: task_id.to_string(),
+            ..Default::default()
         }
     }
-}
 
-impl InvokeTaskRequest {
-    pub fn new(task_id: ExternalID) -> Self {
+    pub fn comment(self, comment: impl ToString) -> Self {
         Self {
-            task_id: task_id.to_string(),
+            comment: comment.to_string(),
+            ..self
         }
     }
+
+    pub fn input_cmac(mut self, data_name: impl ToString, cmac: FileAuthTag) -> Self {
+        self.input_cmacs.push(InputCmac {
+            data_name: data_name.to_string(),
+            cmac: cmac.to_bytes(),
+        });
+        self
+    }
 }
 
-impl CancelTaskRequest {
-    pub fn new(task_id: ExternalID) -> Self {
+impl RejectTaskRequest {
+    pub fn new(task_id: ExternalID, reason: impl ToString) -> Self {
         Self {
             task_id: task_id.to_string(),
+            reason: reason.to_string(),
         }
     }
 }
 
-impl std::convert::TryFrom<proto::FunctionInput> for FunctionInput {
-    type Error = Error;
-
-    fn try_from(proto: proto::FunctionInput) -> Result<Self> {
-        let ret = Self {
-            name: proto.name,
-            description: proto.description,
-            optional: proto.optional,
+impl ParticipantApproval {
+    pub fn from_task_state(ts: &TaskState, user_id: &UserID) -> Self {
+        let (comment, timestamp) = match ts.approvals.get(user_id) {
+            Some(approval) => (approval.comment.clone(), approval.timestamp),
+            None => (String::new(), 0),
         };
-
-        Ok(ret)
+        Self {
+            user_id: user_id.to_string(),
+            status: ts.approval_status(user_id).to_string(),
+            comment,
+            timestamp,
+        }
     }
 }
 
-impl From<FunctionInput> for proto::FunctionInput {
-    fn from(input: FunctionInput) -> Self {
+impl From<&teaclave_types::ScheduledRun> for ScheduledRun {
+    fn from(run: &teaclave_types::ScheduledRun) -> Self {
+        Self {
+            task_id: run
+                .task_id
+                .map(|task_id| ExternalID::new(TaskState::key_prefix(), task_id).to_string())
+                .unwrap_or_default(),
+            run_at: run.run_at,
+        }
+    }
+}
+
+impl InvokeTaskRequest {
+    pub fn new(task_id: ExternalID) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+        }
+    }
+}
+
+impl InvokeTaskResponse {
+    pub fn new(queue_depth: u32, eta_secs: u64) -> Self {
+        Self {
+            queue_depth,
+            eta_secs,
+        }
+    }
+}
+
+impl CancelTaskRequest {
+    pub fn new(task_id: ExternalID) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+        }
+    }
+}
+
+impl GetConsentRecordsRequest {
+    pub fn new(task_id: ExternalID) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+        }
+    }
+}
+
+impl GetTaskLogRequest {
+    pub fn new(task_id: ExternalID) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+        }
+    }
+}
+
+impl GetTaskLogResponse {
+    pub fn new(log_file: teaclave_types::TaskLogFile) -> Self {
+        Self {
+            log_file: Some(log_file.into()),
+        }
+    }
+}
+
+impl RegisterWebhookSinkRequest {
+    pub fn new(task_id: ExternalID, url: Url, secret: impl ToString) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            url: url.to_string(),
+            secret: secret.to_string(),
+        }
+    }
+}
+
+impl RegisterWebhookSinkResponse {
+    pub fn new(sink_id: ExternalID) -> Self {
+        Self {
+            sink_id: sink_id.to_string(),
+        }
+    }
+}
+
+impl From<teaclave_types::ConsentRecord> for proto::ConsentRecord {
+    fn from(record: teaclave_types::ConsentRecord) -> Self {
+        Self {
+            task_id: record.task_id.to_string(),
+            owner: record.owner.to_string(),
+            data_ids: record.data_ids,
+            function_id: record.function_id,
+            function_hash: record.function_hash,
+            purpose: record.purpose,
+            timestamp: record.timestamp,
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::FunctionInput> for FunctionInput {
+    type Error = Error;
+
+    fn try_from(proto: proto::FunctionInput) -> Result<Self> {
+        let ret = Self {
+            name: proto.name,
+            description: proto.description,
+            optional: proto.optional,
+        };
+
+        Ok(ret)
+    }
+}
+
+impl From<FunctionInput> for proto::FunctionInput {
+    fn from(input: FunctionInput) -> Self {
         Self {
             name: input.name,
             description: input.description,
@@ -602,6 +1194,7 @@ impl std::convert::TryFrom<proto::FunctionArgument> for FunctionArgument {
             key: proto.key,
             default_value: proto.default_value,
             allow_overwrite: proto.allow_overwrite,
+            value_type: proto.value_type.as_str().try_into()?,
         };
 
         Ok(ret)
@@ -614,10 +1207,92 @@ impl From<FunctionArgument> for proto::FunctionArgument {
             key: arg.key,
             default_value: arg.default_value,
             allow_overwrite: arg.allow_overwrite,
+            value_type: arg.value_type.to_string(),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::TypedArguments> for FunctionArguments {
+    type Error = Error;
+
+    fn try_from(proto: proto::TypedArguments) -> Result<Self> {
+        match proto.content_type.as_str().try_into()? {
+            ArgumentsFormat::Json => FunctionArguments::from_json_slice(&proto.payload),
+            ArgumentsFormat::Cbor => FunctionArguments::from_cbor_slice(&proto.payload),
+            ArgumentsFormat::Protobuf => {
+                let message = proto::ArgumentStruct::decode(proto.payload.as_slice())?;
+                FunctionArguments::from_json(message.into())
+            }
         }
     }
 }
 
+impl From<proto::ArgumentStruct> for serde_json::Value {
+    fn from(message: proto::ArgumentStruct) -> Self {
+        let map = message
+            .fields
+            .into_iter()
+            .map(|(k, v)| (k, v.into()))
+            .collect();
+        serde_json::Value::Object(map)
+    }
+}
+
+impl From<proto::ArgumentValue> for serde_json::Value {
+    fn from(message: proto::ArgumentValue) -> Self {
+        use proto::argument_value::Kind;
+        use serde_json::Value;
+
+        match message.kind {
+            None | Some(Kind::NullValue(_)) => Value::Null,
+            // Protobuf has only double for numbers, integral ones are
+            // restored so that they can be passed as integer arguments.
+            Some(Kind::NumberValue(n)) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                Value::from(n as i64)
+            }
+            Some(Kind::NumberValue(n)) => Value::from(n),
+            Some(Kind::StringValue(s)) => Value::String(s),
+            Some(Kind::BoolValue(b)) => Value::Bool(b),
+            Some(Kind::StructValue(s)) => s.into(),
+            Some(Kind::ListValue(l)) => {
+                Value::Array(l.values.into_iter().map(|v| v.into()).collect())
+            }
+        }
+    }
+}
+
+impl From<serde_json::Value> for proto::ArgumentValue {
+    fn from(value: serde_json::Value) -> Self {
+        use proto::argument_value::Kind;
+        use serde_json::Value;
+
+        let kind = match value {
+            Value::Null => Kind::NullValue(0),
+            Value::Bool(b) => Kind::BoolValue(b),
+            Value::Number(n) => Kind::NumberValue(n.as_f64().unwrap_or_default()),
+            Value::String(s) => Kind::StringValue(s),
+            Value::Array(a) => Kind::ListValue(proto::ArgumentList {
+                values: a.into_iter().map(|v| v.into()).collect(),
+            }),
+            Value::Object(o) => Kind::StructValue(proto::ArgumentStruct {
+                fields: o.into_iter().map(|(k, v)| (k, v.into())).collect(),
+            }),
+        };
+        Self { kind: Some(kind) }
+    }
+}
+
+impl From<FunctionArguments> for proto::ArgumentStruct {
+    fn from(arguments: FunctionArguments) -> Self {
+        let fields = arguments
+            .inner()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone().into()))
+            .collect();
+        Self { fields }
+    }
+}
+
 impl From<Function> for GetFunctionResponse {
     fn from(function: Function) -> Self {
         Self {
@@ -631,6 +1306,67 @@ impl From<Function> for GetFunctionResponse {
             inputs: function.inputs.into_iter().map(|x| x.into()).collect(),
             outputs: function.outputs.into_iter().map(|x| x.into()).collect(),
             user_allowlist: function.user_allowlist,
+            version: function.version,
+            tags: function.tags,
+            profiling: function.profiling,
+            payload_encrypted: function.payload_crypto.is_some(),
+            executor_features: function.executor_features.into(),
+        }
+    }
+}
+
+impl SearchFunctionsRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(self, name: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            ..self
+        }
+    }
+
+    pub fn tag(self, tag: impl ToString) -> Self {
+        Self {
+            tag: tag.to_string(),
+            ..self
+        }
+    }
+
+    pub fn owner(self, owner: impl ToString) -> Self {
+        Self {
+            owner: owner.to_string(),
+            ..self
+        }
+    }
+
+    pub fn page(self, offset: usize, limit: usize) -> Self {
+        Self {
+            offset: offset as u64,
+            limit: limit as u64,
+            ..self
+        }
+    }
+
+    pub fn cursor(self, after: impl ToString, limit: usize) -> Self {
+        Self {
+            cursor: Some(PageCursor::new(after)),
+            limit: limit as u64,
+            ..self
+        }
+    }
+}
+
+impl From<&Function> for FunctionSummary {
+    fn from(function: &Function) -> Self {
+        Self {
+            function_id: function.external_id().to_string(),
+            name: function.name.clone(),
+            version: function.version.clone(),
+            description: function.description.clone(),
+            owner: function.owner.to_string(),
+            tags: function.tags.clone(),
         }
     }
 }
@@ -678,17 +1414,395 @@ impl QueryAuditLogsRequest {
         Self {
             query,
             limit: limit as u64,
+            offset: 0,
+        }
+    }
+
+    pub fn offset(self, offset: usize) -> Self {
+        Self {
+            offset: offset as u64,
+            ..self
         }
     }
 }
 
 impl QueryAuditLogsResponse {
-    pub fn new(entries: Vec<Entry>) -> Self {
+    pub fn new(entries: Vec<Entry>, total: usize) -> Self {
         let logs: Vec<crate::teaclave_common_proto::Entry> = entries
             .into_iter()
             .map(crate::teaclave_common_proto::Entry::from)
             .collect();
 
-        Self { logs }
+        Self {
+            logs,
+            total: total as u64,
+        }
+    }
+}
+
+impl ExportAuditLogsRequest {
+    pub fn new(query: String, max_rows: usize, output_id: ExternalID) -> Self {
+        Self {
+            query,
+            max_rows: max_rows as u64,
+            output_id: output_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn format(mut self, format: impl Into<String>) -> Self {
+        self.format = format.into();
+        self
+    }
+
+    pub fn columns(mut self, columns: Vec<String>) -> Self {
+        self.columns = columns;
+        self
+    }
+}
+
+impl ExportAuditLogsResponse {
+    pub fn new(rows: usize) -> Self {
+        Self { rows: rows as u64 }
+    }
+}
+
+impl ExportMetadataRequest {
+    pub fn new(output_id: ExternalID) -> Self {
+        Self {
+            output_id: output_id.to_string(),
+        }
+    }
+}
+
+impl From<&MetadataDump> for ExportMetadataResponse {
+    fn from(dump: &MetadataDump) -> Self {
+        Self {
+            version: dump.version,
+            functions: dump.functions.len() as u64,
+            input_files: dump.input_files.len() as u64,
+            output_files: dump.output_files.len() as u64,
+            tasks: dump.tasks.len() as u64,
+        }
+    }
+}
+
+impl UserQuota {
+    pub fn new(
+        max_concurrent_tasks: u32,
+        max_registered_data: u32,
+        max_requests_per_minute: u32,
+    ) -> Self {
+        Self {
+            max_concurrent_tasks,
+            max_registered_data,
+            max_requests_per_minute,
+        }
+    }
+}
+
+impl From<teaclave_config::QuotaConfig> for UserQuota {
+    fn from(quota: teaclave_config::QuotaConfig) -> Self {
+        Self::new(
+            quota.max_concurrent_tasks,
+            quota.max_registered_data,
+            quota.max_requests_per_minute,
+        )
+    }
+}
+
+impl From<UserQuota> for teaclave_config::QuotaConfig {
+    fn from(quota: UserQuota) -> Self {
+        Self {
+            max_concurrent_tasks: quota.max_concurrent_tasks,
+            max_registered_data: quota.max_registered_data,
+            max_requests_per_minute: quota.max_requests_per_minute,
+        }
+    }
+}
+
+impl SetUserQuotaRequest {
+    pub fn new(user_id: impl ToString, quota: UserQuota) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            quota: Some(quota),
+        }
+    }
+}
+
+impl GetUserQuotaRequest {
+    pub fn new(user_id: impl ToString) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+        }
+    }
+}
+
+impl GrantDebugErrorsRequest {
+    pub fn new(user_id: impl ToString, duration_secs: u64) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            duration_secs,
+        }
+    }
+}
+
+impl PolicyRule {
+    pub fn new(ptype: impl ToString, values: impl IntoIterator<Item = impl ToString>) -> Self {
+        Self {
+            ptype: ptype.to_string(),
+            values: values.into_iter().map(|v| v.to_string()).collect(),
+        }
+    }
+}
+
+impl ManagePolicyRequest {
+    pub fn list_rules() -> Self {
+        Self {
+            action: PolicyAction::ListRules as i32,
+            rule: None,
+        }
+    }
+
+    pub fn add_rule(rule: PolicyRule) -> Self {
+        Self {
+            action: PolicyAction::AddRule as i32,
+            rule: Some(rule),
+        }
+    }
+
+    pub fn remove_rule(rule: PolicyRule) -> Self {
+        Self {
+            action: PolicyAction::RemoveRule as i32,
+            rule: Some(rule),
+        }
+    }
+}
+
+impl SetUserAttributesRequest {
+    pub fn new(user_id: impl ToString, attributes: HashMap<String, String>) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            attributes,
+        }
+    }
+}
+
+impl GetUserAttributesRequest {
+    pub fn new(user_id: impl ToString) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+        }
+    }
+}
+
+impl SetDataAttributesRequest {
+    pub fn new(data_id: ExternalID, attributes: HashMap<String, String>) -> Self {
+        Self {
+            data_id: data_id.to_string(),
+            attributes,
+        }
+    }
+}
+
+impl DeleteDataRequest {
+    pub fn new(data_id: ExternalID) -> Self {
+        Self {
+            data_id: data_id.to_string(),
+            cascade: false,
+        }
+    }
+
+    pub fn cascade(self) -> Self {
+        Self {
+            cascade: true,
+            ..self
+        }
+    }
+}
+
+impl SetInputAccessPolicyRequest {
+    pub fn new(data_id: ExternalID, policy: InputAccessPolicy) -> Self {
+        Self {
+            data_id: data_id.to_string(),
+            function_ids: policy.functions.iter().map(|id| id.to_string()).collect(),
+            function_owners: policy
+                .function_owners
+                .iter()
+                .map(|owner| owner.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn access_policy(&self) -> Result<InputAccessPolicy> {
+        let functions = self
+            .function_ids
+            .iter()
+            .map(|id| {
+                let id = ExternalID::try_from(id.as_str())?;
+                ensure!(
+                    id.prefix == Function::key_prefix(),
+                    "Invalid function id: {}",
+                    id
+                );
+                Ok(id)
+            })
+            .collect::<Result<_>>()?;
+        let function_owners = self
+            .function_owners
+            .iter()
+            .map(|owner| UserID::from(owner.as_str()))
+            .collect();
+        Ok(InputAccessPolicy::new(functions, function_owners))
+    }
+}
+
+impl GetDataAttributesRequest {
+    pub fn new(data_id: ExternalID) -> Self {
+        Self {
+            data_id: data_id.to_string(),
+        }
+    }
+}
+
+impl QueryDataLineageRequest {
+    pub fn new(data_id: ExternalID) -> Self {
+        Self {
+            data_id: data_id.to_string(),
+        }
+    }
+}
+
+impl QueryDataLineageResponse {
+    pub fn new(records: Vec<LineageRecord>) -> Self {
+        Self {
+            lineage: records.into_iter().map(DataLineage::from).collect(),
+        }
+    }
+}
+
+impl std::convert::From<LineageRecord> for DataLineage {
+    fn from(record: LineageRecord) -> Self {
+        let mut lineage = DataLineage {
+            data_id: record.data_id.to_string(),
+            timestamp: record.timestamp,
+            ..Default::default()
+        };
+        match record.source {
+            LineageSource::Output { output_id } => lineage.output_id = output_id.to_string(),
+            LineageSource::Task {
+                task_id,
+                function_id,
+                inputs,
+            } => {
+                lineage.task_id = ExternalID::new(TaskState::key_prefix(), task_id).to_string();
+                lineage.function_id = function_id.to_string();
+                lineage.inputs = inputs
+                    .into_iter()
+                    .map(|(name, id)| (name, id.to_string()))
+                    .collect();
+            }
+        }
+        lineage
+    }
+}
+
+impl SetNotificationPreferencesRequest {
+    pub fn new(preferences: NotificationPreferences) -> Self {
+        Self {
+            email: preferences.email.unwrap_or_default(),
+            slack_member_id: preferences.slack_member_id.unwrap_or_default(),
+        }
+    }
+}
+
+impl std::convert::From<SetNotificationPreferencesRequest> for NotificationPreferences {
+    fn from(request: SetNotificationPreferencesRequest) -> Self {
+        let non_empty = |s: String| if s.is_empty() { None } else { Some(s) };
+        NotificationPreferences {
+            email: non_empty(request.email),
+            slack_member_id: non_empty(request.slack_member_id),
+        }
+    }
+}
+
+impl DecommissionStorageRequest {
+    pub fn new(replacement_address: impl ToString) -> Self {
+        Self {
+            replacement_address: replacement_address.to_string(),
+        }
+    }
+}
+
+impl CreateStorageSnapshotRequest {
+    pub fn new(url: Url, crypto: impl Into<FileCrypto>) -> Self {
+        Self {
+            url: url.as_str().to_string(),
+            crypto_info: Some(crypto.into().into()),
+        }
+    }
+}
+
+impl RestoreStorageSnapshotRequest {
+    pub fn new(url: Url, crypto: impl Into<FileCrypto>, cmac: FileAuthTag) -> Self {
+        Self {
+            url: url.as_str().to_string(),
+            crypto_info: Some(crypto.into().into()),
+            cmac: cmac.to_bytes(),
+        }
+    }
+}
+
+impl PromoteStorageReplicaRequest {
+    pub fn new(follower_address: impl ToString) -> Self {
+        Self {
+            follower_address: follower_address.to_string(),
+        }
+    }
+}
+
+impl DeclareMaintenanceWindowRequest {
+    pub fn new(
+        starts_at: i64,
+        ends_at: i64,
+        mode: teaclave_types::MaintenanceMode,
+        reason: impl ToString,
+    ) -> Self {
+        Self {
+            starts_at,
+            ends_at,
+            mode: mode.into(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl CancelMaintenanceWindowRequest {
+    pub fn new(window_id: ExternalID) -> Self {
+        Self {
+            window_id: window_id.to_string(),
+        }
+    }
+}
+
+impl std::convert::From<&teaclave_types::MaintenanceWindow> for MaintenanceWindow {
+    fn from(window: &teaclave_types::MaintenanceWindow) -> Self {
+        Self {
+            window_id: window.external_id().to_string(),
+            starts_at: window.starts_at,
+            ends_at: window.ends_at,
+            mode: window.mode.into(),
+            reason: window.reason.clone(),
+        }
+    }
+}
+
+impl GetServiceInfoResponse {
+    pub fn new(windows: &[teaclave_types::MaintenanceWindow], now: i64) -> Self {
+        Self {
+            active_window: teaclave_types::active_maintenance_window(windows, now)
+                .map(MaintenanceWindow::from),
+            maintenance_windows: windows.iter().map(MaintenanceWindow::from).collect(),
+        }
     }
 }
@@ -22,7 +22,10 @@ pub use proto::teaclave_management_client::TeaclaveManagementClient;
 pub use proto::teaclave_management_server::TeaclaveManagement;
 pub use proto::teaclave_management_server::TeaclaveManagementServer;
 
-use teaclave_types::Entry;
+use crate::teaclave_common::{i32_from_task_status, i32_to_task_status};
+use anyhow::{Error, Result};
+use std::convert::TryFrom;
+use teaclave_types::{Entry, NotificationPreferences};
 
 pub type RegisterInputFileRequest = crate::teaclave_frontend_service::RegisterInputFileRequest;
 pub type UpdateInputFileRequest = crate::teaclave_frontend_service::UpdateInputFileRequest;
@@ -48,6 +51,13 @@ pub type RegisterFunctionRequest = crate::teaclave_frontend_service::RegisterFun
 pub type RegisterFunctionRequestBuilder =
     crate::teaclave_frontend_service::RegisterFunctionRequestBuilder;
 pub type RegisterFunctionResponse = crate::teaclave_frontend_service::RegisterFunctionResponse;
+pub type BeginFunctionUploadRequest = crate::teaclave_frontend_service::BeginFunctionUploadRequest;
+pub type BeginFunctionUploadResponse =
+    crate::teaclave_frontend_service::BeginFunctionUploadResponse;
+pub type UploadFunctionChunkRequest = crate::teaclave_frontend_service::UploadFunctionChunkRequest;
+pub type UploadFunctionChunkResponse =
+    crate::teaclave_frontend_service::UploadFunctionChunkResponse;
+pub type CommitFunctionRequest = crate::teaclave_frontend_service::CommitFunctionRequest;
 pub type UpdateFunctionRequest = crate::teaclave_frontend_service::UpdateFunctionRequest;
 pub type UpdateFunctionRequestBuilder =
     crate::teaclave_frontend_service::UpdateFunctionRequestBuilder;
@@ -56,22 +66,80 @@ pub type GetFunctionUsageStatsRequest =
     crate::teaclave_frontend_service::GetFunctionUsageStatsRequest;
 pub type GetFunctionUsageStatsResponse =
     crate::teaclave_frontend_service::GetFunctionUsageStatsResponse;
+pub type QueryFunctionUsageRecordsRequest =
+    crate::teaclave_frontend_service::QueryFunctionUsageRecordsRequest;
+pub type QueryFunctionUsageRecordsResponse =
+    crate::teaclave_frontend_service::QueryFunctionUsageRecordsResponse;
 pub type DeleteFunctionRequest = crate::teaclave_frontend_service::DeleteFunctionRequest;
 pub type DisableFunctionRequest = crate::teaclave_frontend_service::DisableFunctionRequest;
 pub type GetFunctionRequest = crate::teaclave_frontend_service::GetFunctionRequest;
 pub type GetFunctionResponse = crate::teaclave_frontend_service::GetFunctionResponse;
 pub type ListFunctionsRequest = crate::teaclave_frontend_service::ListFunctionsRequest;
 pub type ListFunctionsResponse = crate::teaclave_frontend_service::ListFunctionsResponse;
+pub type SearchFunctionsRequest = crate::teaclave_frontend_service::SearchFunctionsRequest;
+pub type SearchFunctionsResponse = crate::teaclave_frontend_service::SearchFunctionsResponse;
 pub type CreateTaskRequest = crate::teaclave_frontend_service::CreateTaskRequest;
 pub type CreateTaskResponse = crate::teaclave_frontend_service::CreateTaskResponse;
 pub type GetTaskRequest = crate::teaclave_frontend_service::GetTaskRequest;
 pub type GetTaskResponse = crate::teaclave_frontend_service::GetTaskResponse;
 pub type AssignDataRequest = crate::teaclave_frontend_service::AssignDataRequest;
 pub type ApproveTaskRequest = crate::teaclave_frontend_service::ApproveTaskRequest;
+pub type RejectTaskRequest = crate::teaclave_frontend_service::RejectTaskRequest;
 pub type InvokeTaskRequest = crate::teaclave_frontend_service::InvokeTaskRequest;
+pub type InvokeTaskResponse = crate::teaclave_frontend_service::InvokeTaskResponse;
 pub type CancelTaskRequest = crate::teaclave_frontend_service::CancelTaskRequest;
+pub type GetConsentRecordsRequest = crate::teaclave_frontend_service::GetConsentRecordsRequest;
+pub type GetConsentRecordsResponse = crate::teaclave_frontend_service::GetConsentRecordsResponse;
+pub type GetTaskLogRequest = crate::teaclave_frontend_service::GetTaskLogRequest;
+pub type GetTaskLogResponse = crate::teaclave_frontend_service::GetTaskLogResponse;
+pub type RegisterWebhookSinkRequest = crate::teaclave_frontend_service::RegisterWebhookSinkRequest;
+pub type RegisterWebhookSinkResponse =
+    crate::teaclave_frontend_service::RegisterWebhookSinkResponse;
 pub type QueryAuditLogsRequest = crate::teaclave_frontend_service::QueryAuditLogsRequest;
 pub type QueryAuditLogsResponse = crate::teaclave_frontend_service::QueryAuditLogsResponse;
+pub type ExportAuditLogsRequest = crate::teaclave_frontend_service::ExportAuditLogsRequest;
+pub type ExportAuditLogsResponse = crate::teaclave_frontend_service::ExportAuditLogsResponse;
+pub type RebuildAuditIndexRequest = crate::teaclave_frontend_service::RebuildAuditIndexRequest;
+pub type GetAuditIndexStatusRequest = crate::teaclave_frontend_service::GetAuditIndexStatusRequest;
+pub type GetAuditIndexStatusResponse =
+    crate::teaclave_frontend_service::GetAuditIndexStatusResponse;
+pub type ExportMetadataRequest = crate::teaclave_frontend_service::ExportMetadataRequest;
+pub type ExportMetadataResponse = crate::teaclave_frontend_service::ExportMetadataResponse;
+pub type DecommissionStorageRequest = crate::teaclave_frontend_service::DecommissionStorageRequest;
+pub type GetStorageDecommissionStatusRequest =
+    crate::teaclave_frontend_service::GetStorageDecommissionStatusRequest;
+pub type GetStorageDecommissionStatusResponse =
+    crate::teaclave_frontend_service::GetStorageDecommissionStatusResponse;
+pub type CreateStorageSnapshotRequest =
+    crate::teaclave_frontend_service::CreateStorageSnapshotRequest;
+pub type CreateStorageSnapshotResponse =
+    crate::teaclave_frontend_service::CreateStorageSnapshotResponse;
+pub type RestoreStorageSnapshotRequest =
+    crate::teaclave_frontend_service::RestoreStorageSnapshotRequest;
+pub type RestoreStorageSnapshotResponse =
+    crate::teaclave_frontend_service::RestoreStorageSnapshotResponse;
+pub type PromoteStorageReplicaRequest =
+    crate::teaclave_frontend_service::PromoteStorageReplicaRequest;
+pub type PromoteStorageReplicaResponse =
+    crate::teaclave_frontend_service::PromoteStorageReplicaResponse;
+pub type SetDataAttributesRequest = crate::teaclave_frontend_service::SetDataAttributesRequest;
+pub type GetDataAttributesRequest = crate::teaclave_frontend_service::GetDataAttributesRequest;
+pub type GetDataAttributesResponse = crate::teaclave_frontend_service::GetDataAttributesResponse;
+pub type SetInputAccessPolicyRequest =
+    crate::teaclave_frontend_service::SetInputAccessPolicyRequest;
+pub type DeleteDataRequest = crate::teaclave_frontend_service::DeleteDataRequest;
+pub type QueryDataLineageRequest = crate::teaclave_frontend_service::QueryDataLineageRequest;
+pub type QueryDataLineageResponse = crate::teaclave_frontend_service::QueryDataLineageResponse;
+pub type SetNotificationPreferencesRequest =
+    crate::teaclave_frontend_service::SetNotificationPreferencesRequest;
+pub type DeclareMaintenanceWindowRequest =
+    crate::teaclave_frontend_service::DeclareMaintenanceWindowRequest;
+pub type DeclareMaintenanceWindowResponse =
+    crate::teaclave_frontend_service::DeclareMaintenanceWindowResponse;
+pub type CancelMaintenanceWindowRequest =
+    crate::teaclave_frontend_service::CancelMaintenanceWindowRequest;
+pub type GetServiceInfoRequest = crate::teaclave_frontend_service::GetServiceInfoRequest;
+pub type GetServiceInfoResponse = crate::teaclave_frontend_service::GetServiceInfoResponse;
 
 impl SaveLogsRequest {
     pub fn new(entries: Vec<Entry>) -> Self {
@@ -83,5 +151,99 @@ impl SaveLogsRequest {
     }
 }
 
+impl std::convert::From<teaclave_types::TaskEvent> for TaskEvent {
+    fn from(event: teaclave_types::TaskEvent) -> Self {
+        Self {
+            task_id: event.task_id.to_string(),
+            status: i32_from_task_status(event.status),
+            reason: event.reason,
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+// The participants and webhook sinks of an event are not sent in digests.
+impl TryFrom<TaskEvent> for teaclave_types::TaskEvent {
+    type Error = Error;
+
+    fn try_from(event: TaskEvent) -> Result<Self> {
+        Ok(Self {
+            task_id: uuid::Uuid::parse_str(&event.task_id)?,
+            status: i32_to_task_status(event.status)?,
+            reason: event.reason,
+            participants: Vec::new(),
+            timestamp: event.timestamp,
+            webhook_sinks: Vec::new(),
+        })
+    }
+}
+
+impl std::convert::From<teaclave_types::NotificationDigest> for NotificationDigest {
+    fn from(digest: teaclave_types::NotificationDigest) -> Self {
+        Self {
+            user_id: digest.user_id.to_string(),
+            email: digest.preferences.email.unwrap_or_default(),
+            slack_member_id: digest.preferences.slack_member_id.unwrap_or_default(),
+            events: digest.events.into_iter().map(TaskEvent::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<NotificationDigest> for teaclave_types::NotificationDigest {
+    type Error = Error;
+
+    fn try_from(digest: NotificationDigest) -> Result<Self> {
+        let preferences = NotificationPreferences::from(SetNotificationPreferencesRequest {
+            email: digest.email,
+            slack_member_id: digest.slack_member_id,
+        });
+        let events = digest
+            .events
+            .into_iter()
+            .map(teaclave_types::TaskEvent::try_from)
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            user_id: digest.user_id.into(),
+            preferences,
+            events,
+        })
+    }
+}
+
+impl std::convert::From<teaclave_types::WebhookNotification> for WebhookNotification {
+    fn from(notification: teaclave_types::WebhookNotification) -> Self {
+        Self {
+            url: notification.url,
+            body: notification.body,
+            signature: notification.signature,
+        }
+    }
+}
+
+impl std::convert::From<WebhookNotification> for teaclave_types::WebhookNotification {
+    fn from(notification: WebhookNotification) -> Self {
+        Self {
+            url: notification.url,
+            body: notification.body,
+            signature: notification.signature,
+        }
+    }
+}
+
+impl PullNotificationDigestsResponse {
+    pub fn new(
+        digests: Vec<teaclave_types::NotificationDigest>,
+        webhooks: Vec<teaclave_types::WebhookNotification>,
+    ) -> Self {
+        Self {
+            digests: digests.into_iter().map(NotificationDigest::from).collect(),
+            webhooks: webhooks
+                .into_iter()
+                .map(WebhookNotification::from)
+                .collect(),
+        }
+    }
+}
+
 impl_custom_server!(TeaclaveManagementServer, TeaclaveManagement);
 impl_custom_client!(TeaclaveManagementClient);
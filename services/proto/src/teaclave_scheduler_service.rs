@@ -22,12 +22,15 @@ pub use proto::teaclave_scheduler_client::TeaclaveSchedulerClient;
 pub use proto::teaclave_scheduler_server::TeaclaveScheduler;
 pub use proto::teaclave_scheduler_server::TeaclaveSchedulerServer;
 pub use proto::{
-    HeartbeatRequest, PublishTaskRequest, PullTaskRequest, UpdateTaskResultRequest,
-    UpdateTaskStatusRequest,
+    HandoffReason, HandoffTaskRequest, HeartbeatRequest, PublishTaskRequest, PullTaskRequest,
+    ReportTaskProgressRequest, UpdateTaskResultRequest, UpdateTaskStatusRequest,
 };
 pub use proto::{HeartbeatResponse, PullTaskResponse, SubscribeResponse};
 use teaclave_types::Storable;
-use teaclave_types::{StagedTask, TaskFailure, TaskOutputs, TaskResult, TaskStatus};
+use teaclave_types::{
+    ExecutorFeatures, FileTransferRecord, StagedTask, TaskFailure, TaskLogFile, TaskOutputs,
+    TaskResult, TaskStatus, TaskUsage,
+};
 use uuid::Uuid;
 
 impl_custom_server!(TeaclaveSchedulerServer, TeaclaveScheduler);
@@ -42,10 +45,43 @@ impl HeartbeatRequest {
     }
 }
 
+impl PullTaskRequest {
+    pub fn new(executor_id: Uuid, executor_features: ExecutorFeatures) -> Self {
+        Self {
+            executor_id: executor_id.to_string(),
+            executor_features: executor_features.into(),
+            accepts_batch: false,
+        }
+    }
+
+    pub fn accepts_batch(self, accepts_batch: bool) -> Self {
+        Self {
+            accepts_batch,
+            ..self
+        }
+    }
+}
+
 impl HeartbeatResponse {
     pub fn new(command: ExecutorCommand) -> Self {
         Self {
             command: command.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn backlog(self, queue_depth: u32, eta_secs: u64) -> Self {
+        Self {
+            queue_depth,
+            eta_secs,
+            ..self
+        }
+    }
+
+    pub fn throttle(secs: u64) -> Self {
+        Self {
+            throttle_secs: secs,
+            ..Self::new(ExecutorCommand::Throttle)
         }
     }
 }
@@ -54,21 +90,77 @@ impl PullTaskResponse {
     pub fn new(staged_task: StagedTask) -> Self {
         Self {
             staged_task: staged_task.to_vec().unwrap(),
+            batched_tasks: Vec::new(),
+        }
+    }
+
+    pub fn batched_tasks(self, tasks: &[StagedTask]) -> Self {
+        Self {
+            batched_tasks: tasks.iter().map(|task| task.to_vec().unwrap()).collect(),
+            ..self
         }
     }
 }
 
 impl UpdateTaskResultRequest {
     pub fn new(task_id: Uuid, task_result: Result<TaskOutputs>) -> Self {
+        Self::with_failure(task_id, task_result.map_err(TaskFailure::new))
+    }
+
+    /// The result of a failed task with more than the reason, e.g., the
+    /// forensic bundle of the run.
+    pub fn with_failure(task_id: Uuid, task_result: Result<TaskOutputs, TaskFailure>) -> Self {
         let result = match task_result {
             Ok(task_output) => TaskResult::Ok(task_output),
-            Err(e) => TaskResult::Err(TaskFailure {
-                reason: e.to_string(),
-            }),
+            Err(failure) => TaskResult::Err(failure),
         };
         Self {
             task_id: task_id.to_string(),
             result: Some(result.into()),
+            log_file: None,
+            file_transfers: Vec::new(),
+            usage: None,
+        }
+    }
+
+    pub fn log_file(self, log_file: Option<TaskLogFile>) -> Self {
+        Self {
+            log_file: log_file.map(Into::into),
+            ..self
+        }
+    }
+
+    pub fn file_transfers(self, file_transfers: Vec<FileTransferRecord>) -> Self {
+        Self {
+            file_transfers: file_transfers.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    pub fn usage(self, usage: TaskUsage) -> Self {
+        Self {
+            usage: Some(usage.into()),
+            ..self
+        }
+    }
+}
+
+impl ReportTaskProgressRequest {
+    pub fn new(task_id: Uuid, percentage: u32, state: impl ToString) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            percentage,
+            state: state.to_string(),
+        }
+    }
+}
+
+impl HandoffTaskRequest {
+    pub fn new(executor_id: Uuid, task_id: Uuid, reason: HandoffReason) -> Self {
+        Self {
+            executor_id: executor_id.to_string(),
+            task_id: task_id.to_string(),
+            reason: reason.into(),
         }
     }
 }
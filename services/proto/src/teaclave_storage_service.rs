@@ -20,16 +20,63 @@ pub use proto::teaclave_storage_client::TeaclaveStorageClient;
 pub use proto::teaclave_storage_server::TeaclaveStorage;
 pub use proto::teaclave_storage_server::TeaclaveStorageServer;
 pub use proto::{
-    DeleteRequest, DequeueRequest, DequeueResponse, EnqueueRequest, GetKeysByPrefixRequest,
-    GetKeysByPrefixResponse, GetRequest, GetResponse, PutRequest,
+    ApplyChangeLogRequest, ApplyChangeLogResponse, ChangeLogEntry, CompareAndSwapRequest,
+    CompareAndSwapResponse, ConsistencyToken, CreateSnapshotRequest, CreateSnapshotResponse,
+    DeleteRequest, DequeueRequest, DequeueResponse, EnqueueRequest, EntryInfo,
+    ExportSnapshotRequest, ExportSnapshotResponse, FreezeRequest, GetKeysByPrefixRequest,
+    GetKeysByPrefixResponse, GetQueueLengthRequest, GetQueueLengthResponse, GetRequest,
+    GetResponse, ImportSnapshotRequest, KeyValue, ListEntriesRequest, ListEntriesResponse,
+    Mutation, PromoteReplicaRequest, PromoteReplicaResponse, PutRequest, RestoreSnapshotRequest,
+    RestoreSnapshotResponse, WriteBatchRequest, WriteResponse,
 };
+use teaclave_types::{FileAuthTag, FileCrypto};
+use url::Url;
 
 impl_custom_server!(TeaclaveStorageServer, TeaclaveStorage);
 impl_custom_client!(TeaclaveStorageClient);
 
+impl ConsistencyToken {
+    pub fn new(store_id: impl Into<Vec<u8>>, sequence: u64) -> Self {
+        Self {
+            store_id: store_id.into(),
+            sequence,
+        }
+    }
+
+    /// The later one of two tokens. A token of another store replaces the
+    /// current one, e.g., after the store is restored from a snapshot.
+    pub fn latest(current: Option<Self>, token: Self) -> Self {
+        match current {
+            Some(current)
+                if current.store_id == token.store_id && current.sequence > token.sequence =>
+            {
+                current
+            }
+            _ => token,
+        }
+    }
+}
+
+impl WriteResponse {
+    pub fn new(token: ConsistencyToken) -> Self {
+        Self { token: Some(token) }
+    }
+}
+
 impl GetRequest {
     pub fn new(key: impl Into<Vec<u8>>) -> Self {
-        Self { key: key.into() }
+        Self {
+            key: key.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Read only after the write identified by `token`, if any.
+    pub fn after(self, token: Option<ConsistencyToken>) -> Self {
+        Self {
+            consistency_token: token,
+            ..self
+        }
     }
 }
 
@@ -46,6 +93,54 @@ impl PutRequest {
         Self {
             key: key.into(),
             value: value.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn expires_at(self, expires_at: u64) -> Self {
+        Self { expires_at, ..self }
+    }
+}
+
+impl CompareAndSwapRequest {
+    /// Puts `value` if the key is absent.
+    pub fn new(key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+            expect_absent: true,
+            ..Default::default()
+        }
+    }
+
+    /// Puts the value if the key holds `expected` instead.
+    pub fn expect(self, expected: impl Into<Vec<u8>>) -> Self {
+        Self {
+            expected: expected.into(),
+            expect_absent: false,
+            ..self
+        }
+    }
+
+    pub fn expires_at(self, expires_at: u64) -> Self {
+        Self { expires_at, ..self }
+    }
+}
+
+impl CompareAndSwapResponse {
+    pub fn swapped(token: ConsistencyToken) -> Self {
+        Self {
+            swapped: true,
+            current: Vec::new(),
+            token: Some(token),
+        }
+    }
+
+    pub fn mismatched(current: Option<Vec<u8>>) -> Self {
+        Self {
+            swapped: false,
+            current: current.unwrap_or_default(),
+            token: None,
         }
     }
 }
@@ -56,6 +151,22 @@ impl DeleteRequest {
     }
 }
 
+impl WriteBatchRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(mut self, key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) -> Self {
+        self.puts.push(KeyValue::new(key, value));
+        self
+    }
+
+    pub fn delete(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.deletes.push(key.into());
+        self
+    }
+}
+
 impl EnqueueRequest {
     pub fn new(key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) -> Self {
         Self {
@@ -72,17 +183,58 @@ impl DequeueRequest {
 }
 
 impl DequeueResponse {
-    pub fn new(value: impl Into<Vec<u8>>) -> Self {
+    pub fn new(value: impl Into<Vec<u8>>, token: ConsistencyToken) -> Self {
         Self {
             value: value.into(),
+            token: Some(token),
+        }
+    }
+}
+
+impl GetQueueLengthRequest {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Read only after the write identified by `token`, if any.
+    pub fn after(self, token: Option<ConsistencyToken>) -> Self {
+        Self {
+            consistency_token: token,
+            ..self
         }
     }
 }
 
+impl GetQueueLengthResponse {
+    pub fn new(length: u32) -> Self {
+        Self { length }
+    }
+}
+
 impl GetKeysByPrefixRequest {
     pub fn new(prefix: impl Into<Vec<u8>>) -> Self {
         Self {
             prefix: prefix.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn page(self, start_after: impl Into<Vec<u8>>, limit: u32) -> Self {
+        Self {
+            start_after: start_after.into(),
+            limit,
+            ..self
+        }
+    }
+
+    /// Read only after the write identified by `token`, if any.
+    pub fn after(self, token: Option<ConsistencyToken>) -> Self {
+        Self {
+            consistency_token: token,
+            ..self
         }
     }
 }
@@ -93,15 +245,233 @@ impl GetKeysByPrefixResponse {
     }
 }
 
+impl ListEntriesRequest {
+    pub fn new(prefix: impl Into<Vec<u8>>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn page(self, start_after: impl Into<Vec<u8>>, limit: u32) -> Self {
+        Self {
+            start_after: start_after.into(),
+            limit,
+            ..self
+        }
+    }
+
+    /// Return the values as well, in pages of about `max_value_bytes`.
+    pub fn with_values(self, max_value_bytes: u64) -> Self {
+        Self {
+            max_value_bytes,
+            ..self
+        }
+    }
+
+    /// Read only after the write identified by `token`, if any.
+    pub fn after(self, token: Option<ConsistencyToken>) -> Self {
+        Self {
+            consistency_token: token,
+            ..self
+        }
+    }
+}
+
+impl KeyValue {
+    pub fn new(key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
+
+impl ExportSnapshotResponse {
+    pub fn new(entries: Vec<KeyValue>) -> Self {
+        let digest = snapshot_digest(&entries);
+        Self { entries, digest }
+    }
+
+    /// Check that the entries still match the digest computed by the exporter.
+    pub fn verify(&self) -> bool {
+        snapshot_digest(&self.entries) == self.digest
+    }
+
+    /// The snapshot as written to a backup.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        prost::Message::encode_to_vec(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(prost::Message::decode(bytes)?)
+    }
+}
+
+impl ImportSnapshotRequest {
+    pub fn new(entries: Vec<KeyValue>) -> Self {
+        Self { entries }
+    }
+}
+
+impl CreateSnapshotRequest {
+    pub fn new(url: Url, crypto: impl Into<FileCrypto>) -> Self {
+        Self {
+            url: url.as_str().to_string(),
+            crypto_info: Some(crypto.into().into()),
+        }
+    }
+}
+
+impl CreateSnapshotResponse {
+    pub fn new(entries: u64, cmac: FileAuthTag) -> Self {
+        Self {
+            entries,
+            cmac: cmac.to_bytes(),
+        }
+    }
+}
+
+impl RestoreSnapshotRequest {
+    pub fn new(url: Url, crypto: impl Into<FileCrypto>, cmac: FileAuthTag) -> Self {
+        Self {
+            url: url.as_str().to_string(),
+            crypto_info: Some(crypto.into().into()),
+            cmac: cmac.to_bytes(),
+        }
+    }
+}
+
+impl RestoreSnapshotResponse {
+    pub fn new(entries: u64) -> Self {
+        Self { entries }
+    }
+}
+
+impl Mutation {
+    pub fn put(key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+            delete: false,
+        }
+    }
+
+    pub fn delete(key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            value: Vec::new(),
+            delete: true,
+        }
+    }
+}
+
+impl ChangeLogEntry {
+    pub fn new(index: u64, mutations: Vec<Mutation>) -> Self {
+        Self {
+            index,
+            mutations,
+            reset: false,
+        }
+    }
+
+    pub fn reset(index: u64, entries: Vec<KeyValue>) -> Self {
+        let mutations = entries
+            .into_iter()
+            .map(|entry| Mutation::put(entry.key, entry.value))
+            .collect();
+        Self {
+            index,
+            mutations,
+            reset: true,
+        }
+    }
+}
+
+impl ApplyChangeLogRequest {
+    pub fn new(log_id: impl Into<Vec<u8>>, entries: Vec<ChangeLogEntry>) -> Self {
+        Self {
+            log_id: log_id.into(),
+            entries,
+        }
+    }
+}
+
+impl ApplyChangeLogResponse {
+    pub fn new(applied_index: u64) -> Self {
+        Self { applied_index }
+    }
+}
+
+impl PromoteReplicaResponse {
+    pub fn new(applied_index: u64) -> Self {
+        Self { applied_index }
+    }
+}
+
+/// Entries of the change log after `after`, read by the shipper of the
+/// primary store from the storage thread rather than over RPC. The entries up
+/// to `after` have been applied by the follower and are dropped.
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct ReadChangeLogRequest {
+    pub after: u64,
+    /// Read a reset entry instead, e.g., once the follower has rejected the
+    /// entries for a gap
+    pub reset: bool,
+}
+
+impl ReadChangeLogRequest {
+    pub fn new(after: u64) -> Self {
+        Self {
+            after,
+            reset: false,
+        }
+    }
+
+    pub fn reset(after: u64) -> Self {
+        Self { after, reset: true }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct ReadChangeLogResponse {
+    pub log_id: Vec<u8>,
+    pub entries: Vec<ChangeLogEntry>,
+}
+
+/// SHA-256 over the length-prefixed keys and values of an ordered snapshot.
+pub fn snapshot_digest(entries: &[KeyValue]) -> Vec<u8> {
+    let mut ctx = ring::digest::Context::new(&ring::digest::SHA256);
+    for entry in entries {
+        ctx.update(&(entry.key.len() as u64).to_le_bytes());
+        ctx.update(&entry.key);
+        ctx.update(&(entry.value.len() as u64).to_le_bytes());
+        ctx.update(&entry.value);
+    }
+    ctx.finish().as_ref().to_vec()
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum TeaclaveStorageRequest {
     Get(GetRequest),
     Put(PutRequest),
     Delete(DeleteRequest),
+    CompareAndSwap(CompareAndSwapRequest),
+    WriteBatch(WriteBatchRequest),
     Enqueue(EnqueueRequest),
     Dequeue(DequeueRequest),
+    GetQueueLength(GetQueueLengthRequest),
     GetKeysByPrefix(GetKeysByPrefixRequest),
+    ListEntries(ListEntriesRequest),
+    Freeze(FreezeRequest),
+    ExportSnapshot(ExportSnapshotRequest),
+    ImportSnapshot(ImportSnapshotRequest),
+    CreateSnapshot(CreateSnapshotRequest),
+    RestoreSnapshot(RestoreSnapshotRequest),
+    ApplyChangeLog(ApplyChangeLogRequest),
+    PromoteReplica(PromoteReplicaRequest),
+    ReadChangeLog(ReadChangeLogRequest),
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -109,7 +479,17 @@ pub enum TeaclaveStorageRequest {
 #[serde(tag = "response", content = "content", rename_all = "snake_case")]
 pub enum TeaclaveStorageResponse {
     Get(GetResponse),
+    CompareAndSwap(CompareAndSwapResponse),
     Dequeue(DequeueResponse),
+    GetQueueLength(GetQueueLengthResponse),
     GetKeysByPrefix(GetKeysByPrefixResponse),
+    ListEntries(ListEntriesResponse),
+    ExportSnapshot(ExportSnapshotResponse),
+    CreateSnapshot(CreateSnapshotResponse),
+    RestoreSnapshot(RestoreSnapshotResponse),
+    ApplyChangeLog(ApplyChangeLogResponse),
+    PromoteReplica(PromoteReplicaResponse),
+    ReadChangeLog(ReadChangeLogResponse),
+    Write(WriteResponse),
     Empty(()),
 }
@@ -29,6 +29,11 @@ fn choose_sgx_dylib(is_sim: bool) {
 }
 
 fn main() {
+    // Services in TDX guests run without enclaves, so there is nothing to link.
+    if env::var_os("CARGO_FEATURE_TDX").is_some() {
+        return;
+    }
+
     let sdk_dir = env::var("SGX_SDK").unwrap_or("/opt/intel/sgxsdk".into());
     println!("cargo:rustc-link-search=native={}/lib64", sdk_dir);
 
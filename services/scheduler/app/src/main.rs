@@ -17,9 +17,20 @@
 
 use anyhow::Result;
 use teaclave_service_app_utils::launch_teaclave_service;
+#[cfg(feature = "tdx")]
+use teaclave_service_app_utils::validate_config_requested;
 
 const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
 
+#[cfg(not(feature = "tdx"))]
 fn main() -> Result<()> {
     launch_teaclave_service(PACKAGE_NAME)
 }
+
+#[cfg(feature = "tdx")]
+fn main() -> Result<()> {
+    if validate_config_requested() {
+        return launch_teaclave_service(PACKAGE_NAME);
+    }
+    teaclave_scheduler_service_enclave::run_td_guest()
+}
@@ -0,0 +1,116 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Durations of the recent task runs, from which the scheduler estimates how
+//! long the queued tasks wait. The estimate is shared with the management
+//! service for the callers of `InvokeTask`, and sent to the executors along
+//! with the heartbeats.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+// Runs the average is taken over
+const RECENT_RUNS: usize = 32;
+
+#[derive(Default)]
+pub(crate) struct RunTimes {
+    // map task_id of running tasks to the time they started
+    started: HashMap<Uuid, SystemTime>,
+    // seconds the recent runs took, the latest last
+    recent: VecDeque<u64>,
+}
+
+impl RunTimes {
+    pub(crate) fn start(&mut self, task_id: Uuid, now: SystemTime) {
+        self.started.insert(task_id, now);
+    }
+
+    /// Record the run of the task, if it started in this instance.
+    pub(crate) fn end(&mut self, task_id: &Uuid, now: SystemTime) {
+        let started = match self.started.remove(task_id) {
+            Some(started) => started,
+            None => return,
+        };
+        let secs = now.duration_since(started).unwrap_or_default().as_secs();
+        if self.recent.len() == RECENT_RUNS {
+            self.recent.pop_front();
+        }
+        // Runs under a second still take a round of heartbeats.
+        self.recent.push_back(secs.max(1));
+    }
+
+    /// Forget the task, which is failed or queued again without a result.
+    pub(crate) fn forget(&mut self, task_id: &Uuid) {
+        self.started.remove(task_id);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.started.clear();
+        self.recent.clear();
+    }
+
+    pub(crate) fn average_secs(&self) -> u64 {
+        if self.recent.is_empty() {
+            return 0;
+        }
+        self.recent.iter().sum::<u64>() / self.recent.len() as u64
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use std::time::Duration;
+    use teaclave_types::TaskBacklog;
+
+    pub fn test_run_times() {
+        let mut run_times = RunTimes::default();
+        let now = SystemTime::now();
+        assert_eq!(run_times.average_secs(), 0);
+
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        run_times.start(a, now);
+        run_times.start(b, now);
+        run_times.start(c, now);
+        run_times.end(&a, now + Duration::from_secs(10));
+        run_times.end(&b, now + Duration::from_secs(20));
+        run_times.forget(&c);
+        run_times.end(&c, now + Duration::from_secs(90));
+        assert_eq!(run_times.average_secs(), 15);
+
+        for _ in 0..RECENT_RUNS {
+            let task_id = Uuid::new_v4();
+            run_times.start(task_id, now);
+            run_times.end(&task_id, now);
+        }
+        assert_eq!(run_times.average_secs(), 1);
+
+        let backlog = TaskBacklog {
+            queued: 5,
+            executors: 2,
+            average_run_secs: 15,
+        };
+        assert_eq!(backlog.eta_secs(5), 45);
+        assert_eq!(backlog.eta_secs(0), 0);
+        let unknown = TaskBacklog {
+            executors: 0,
+            ..backlog
+        };
+        assert_eq!(unknown.eta_secs(5), 0);
+    }
+}
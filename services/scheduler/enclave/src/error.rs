@@ -16,6 +16,7 @@
 // under the License.
 
 use teaclave_rpc::{Code, Status};
+use teaclave_types::FeatureMismatch;
 use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum SchedulerServiceError {
@@ -25,8 +26,20 @@ pub enum SchedulerServiceError {
     TaskCanceled,
     #[error("task queue is empty")]
     TaskQueueEmpty,
+    #[error("task queue is full")]
+    TaskQueueFull,
     #[error("storage service error")]
     StorageError,
+    #[error("executor is not attested")]
+    MissingExecutorIdentity,
+    #[error("task is assigned to another executor")]
+    ExecutorIdentityMismatch,
+    #[error("executor needs to re-attest")]
+    ReattestationRequired,
+    #[error("task failed on delivery: {0}")]
+    ExecutorFeatureMismatch(#[from] FeatureMismatch),
+    #[error("scheduler instance is not the leader")]
+    NotLeader,
 }
 
 impl From<SchedulerServiceError> for Status {
@@ -35,6 +48,12 @@ impl From<SchedulerServiceError> for Status {
         let msg = error.to_string();
         let code = match error {
             SchedulerServiceError::Service(_) => Code::Internal,
+            SchedulerServiceError::MissingExecutorIdentity => Code::Unauthenticated,
+            SchedulerServiceError::ExecutorIdentityMismatch => Code::PermissionDenied,
+            SchedulerServiceError::TaskQueueFull => Code::ResourceExhausted,
+            SchedulerServiceError::ReattestationRequired
+            | SchedulerServiceError::ExecutorFeatureMismatch(_) => Code::FailedPrecondition,
+            SchedulerServiceError::NotLeader => Code::Unavailable,
             _ => Code::Unknown,
         };
         Status::new(code, msg)
@@ -0,0 +1,168 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Leader election of scheduler instances. The leader holds a lease in the
+//! storage service and renews it with compare-and-swap; a standby takes the
+//! lease over once it lapses. The term of the lease grows with every
+//! takeover, so an instance tells whether another one led in between.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use teaclave_proto::teaclave_storage_service::{
+    CompareAndSwapRequest, GetRequest, TeaclaveStorageClient,
+};
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Code;
+use teaclave_types::SCHEDULER_LEASE_KEY;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Lease {
+    pub(crate) holder: Uuid,
+    pub(crate) term: u64,
+    // Seconds since the UNIX epoch
+    pub(crate) expires_at: u64,
+}
+
+/// The lease `holder` writes at `now`: a new one if there is none or the
+/// current one has lapsed, the current one renewed if the holder holds it
+/// already, or None if another instance holds it.
+pub(crate) fn next_lease(
+    current: Option<&Lease>,
+    holder: Uuid,
+    now: u64,
+    lease_secs: u64,
+) -> Option<Lease> {
+    let term = match current {
+        None => 1,
+        Some(lease) if lease.holder == holder => lease.term,
+        Some(lease) if lease.expires_at <= now => lease.term + 1,
+        Some(_) => return None,
+    };
+    Some(Lease {
+        holder,
+        term,
+        expires_at: now + lease_secs,
+    })
+}
+
+pub(crate) struct LeaderElection {
+    instance_id: Uuid,
+    lease_secs: u64,
+    // The lease this instance holds, if any
+    lease: Option<Lease>,
+}
+
+impl LeaderElection {
+    pub(crate) fn new(lease_secs: u64) -> Self {
+        let instance_id = Uuid::new_v4();
+        log::info!("Scheduler instance {} contends for the lease", instance_id);
+        Self {
+            instance_id,
+            lease_secs,
+            lease: None,
+        }
+    }
+
+    /// The leader steps down a third of the lease before it lapses, so that
+    /// it has stopped dispatching by the time a standby takes over.
+    pub(crate) fn is_leader(&self, now: u64) -> bool {
+        let margin = self.lease_secs / 3;
+        matches!(&self.lease, Some(lease) if now + margin < lease.expires_at)
+    }
+
+    pub(crate) fn term(&self) -> Option<u64> {
+        self.lease.as_ref().map(|lease| lease.term)
+    }
+
+    /// Take or renew the lease, forgetting it if another instance holds it.
+    pub(crate) async fn contend(
+        &mut self,
+        storage_client: &Arc<Mutex<TeaclaveStorageClient<Channel>>>,
+        now: u64,
+    ) -> Result<()> {
+        let mut client = storage_client.lock().await;
+        let current = match client.get(GetRequest::new(SCHEDULER_LEASE_KEY)).await {
+            Ok(response) => Some(response.into_inner().value),
+            Err(status) if status.code() == Code::NotFound => None,
+            // The lease held, if any, lapses on its own.
+            Err(status) => return Err(status.into()),
+        };
+        let current_lease: Option<Lease> = current
+            .as_ref()
+            .map(|value| serde_json::from_slice(value))
+            .transpose()?;
+        let next = match next_lease(
+            current_lease.as_ref(),
+            self.instance_id,
+            now,
+            self.lease_secs,
+        ) {
+            Some(next) => next,
+            None => {
+                self.lease = None;
+                return Ok(());
+            }
+        };
+
+        let mut request =
+            CompareAndSwapRequest::new(SCHEDULER_LEASE_KEY, serde_json::to_vec(&next)?);
+        if let Some(current) = current {
+            request = request.expect(current);
+        }
+        let swapped = client.compare_and_swap(request).await?.into_inner().swapped;
+        if swapped && self.term() != Some(next.term) {
+            log::info!(
+                "Scheduler instance {} leads in term {}",
+                self.instance_id,
+                next.term
+            );
+        }
+        self.lease = if swapped { Some(next) } else { None };
+        Ok(())
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_next_lease() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let first = next_lease(None, a, 100, 30).unwrap();
+        assert_eq!((first.term, first.expires_at), (1, 130));
+
+        // Renewed by the holder only, until it lapses
+        let renewed = next_lease(Some(&first), a, 120, 30).unwrap();
+        assert_eq!((renewed.term, renewed.expires_at), (1, 150));
+        assert!(next_lease(Some(&renewed), b, 140, 30).is_none());
+        let taken = next_lease(Some(&renewed), b, 150, 30).unwrap();
+        assert_eq!((taken.holder, taken.term), (b, 2));
+
+        let mut election = LeaderElection::new(30);
+        assert!(!election.is_leader(100));
+        election.lease = Some(Lease {
+            holder: election.instance_id,
+            term: 1,
+            expires_at: 130,
+        });
+        assert!(election.is_leader(119));
+        assert!(!election.is_leader(120));
+    }
+}
@@ -23,31 +23,33 @@ extern crate sgx_types;
 use anyhow::{anyhow, Result};
 
 use teaclave_attestation::{verifier, AttestationConfig, RemoteAttestation};
-use teaclave_binder::proto::{
-    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, InitEnclaveInput, InitEnclaveOutput,
-    StartServiceInput, StartServiceOutput,
-};
-use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::{AS_ROOT_CA_CERT, AUDITOR_PUBLIC_KEYS, SCHEDULER_INBOUND_SERVICES};
 use teaclave_config::RuntimeConfig;
 use teaclave_proto::teaclave_scheduler_service::TeaclaveSchedulerServer;
 use teaclave_service_enclave_utils::create_trusted_storage_endpoint;
-use teaclave_service_enclave_utils::ServiceEnclave;
-use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
+use teaclave_service_enclave_utils::{drain_signal, drained};
+use teaclave_types::EnclaveInfo;
 
+mod backlog;
+#[cfg(feature = "mesalock_sgx")]
+mod ecall;
 mod error;
+mod lease;
 mod publisher;
 mod service;
 
 // Sets the number of worker threads the Runtime will use.
 const N_WORKERS: usize = 8;
 
-async fn start_service(config: &RuntimeConfig) -> Result<()> {
+pub async fn start_service(config: &RuntimeConfig) -> Result<()> {
     info!("Starting Scheduler...");
 
     let listen_address = config.internal_endpoints.scheduler.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(config)?;
+    let verification_policy = verifier::VerificationPolicy::from_teaclave_config(config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
+        .endorsement_cache(config.attestation.endorsement_cache_path("scheduler"))
+        .reuse_cached_endorsement(config.attestation.reuse_cached_endorsement)
         .generate_and_endorse()?
         .attested_tls_config()
         .ok_or_else(|| anyhow!("cannot get attested TLS config"))?;
@@ -73,7 +75,13 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         accepted_enclave_attrs,
         AS_ROOT_CA_CERT,
         verifier::universal_quote_verifier,
+        verification_policy.clone(),
     )?
+    .mutual_attestation(true)
+    .min_protocol_version(config.rpc.min_protocol_version)
+    .session_freshness(std::time::Duration::from_secs(
+        teaclave_config::build::GRPC_CONFIG.session_freshness_secs,
+    ))
     .into();
     info!(" Starting Scheduler: Server config setup finished ...");
 
@@ -83,12 +91,19 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         &enclave_info,
         AS_ROOT_CA_CERT,
         verifier::universal_quote_verifier,
+        verification_policy,
         attested_tls_config,
     )?;
     info!(" Starting Scheduler: setup storage endpoint finished ...");
 
-    let service_resources =
-        service::TeaclaveSchedulerResources::new(storage_service_endpoint).await?;
+    let service_resources = service::TeaclaveSchedulerResources::new(
+        storage_service_endpoint,
+        config.scheduler.max_queue_depth,
+        config.scheduler.reattestation_interval_secs,
+        config.scheduler.lease_secs,
+        config.scheduler.max_batch_size,
+    )
+    .await?;
 
     let service_resources = Arc::new(Mutex::new(service_resources));
 
@@ -106,58 +121,45 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
 
     info!(" Starting Scheduler: start listening ...");
 
-    teaclave_rpc::transport::Server::builder()
+    let served = teaclave_rpc::transport::Server::builder()
         .tls_config(server_config)
         .map_err(|_| anyhow::anyhow!("TeaclaveFrontendServer tls config error"))?
         .add_service(TeaclaveSchedulerServer::new(service))
-        .serve(listen_address)
-        .await?;
+        .serve_with_shutdown(listen_address, drain_signal())
+        .await;
+    // The deamon keeps running until the enclave is finalized.
+    drained();
+    served?;
     deamon_handle.join().unwrap();
 
     Ok(())
 }
 
-#[handle_ecall]
-fn handle_start_service(input: &StartServiceInput) -> TeeServiceResult<StartServiceOutput> {
-    let result = tokio::runtime::Builder::new_multi_thread()
-        .worker_threads(N_WORKERS)
-        .enable_all()
-        .build()
-        .map_err(|_| TeeServiceError::SgxError)?
-        .block_on(start_service(&input.config));
-
-    match result {
-        Ok(_) => Ok(StartServiceOutput),
-        Err(e) => {
-            error!("Failed to run service: {}", e);
-            Err(TeeServiceError::ServiceError)
-        }
-    }
-}
-
-#[handle_ecall]
-fn handle_init_enclave(_: &InitEnclaveInput) -> TeeServiceResult<InitEnclaveOutput> {
-    ServiceEnclave::init(env!("CARGO_PKG_NAME"))?;
-    Ok(InitEnclaveOutput)
+/// Runs the service as a process of a TDX guest, until it is terminated.
+#[cfg(feature = "tdx")]
+pub fn run_td_guest() -> Result<()> {
+    teaclave_service_enclave_utils::td_guest::launch(
+        env!("CARGO_PKG_NAME"),
+        |config| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(N_WORKERS)
+                .enable_all()
+                .build()?
+                .block_on(start_service(config))
+        },
+        || {},
+    )
 }
 
-#[handle_ecall]
-fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<FinalizeEnclaveOutput> {
-    ServiceEnclave::finalize()?;
-    Ok(FinalizeEnclaveOutput)
-}
-
-register_ecall_handler!(
-    type ECallCommand,
-    (ECallCommand::StartService, StartServiceInput, StartServiceOutput),
-    (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
-    (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
-);
-
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
 
     pub fn run_tests() -> bool {
-        true
+        run_tests!(
+            backlog::tests::test_run_times,
+            lease::tests::test_next_lease,
+        )
     }
 }
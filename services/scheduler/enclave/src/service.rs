@@ -15,17 +15,22 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::backlog::RunTimes;
 use crate::error::SchedulerServiceError;
+use crate::lease::LeaderElection;
 
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 #[allow(unused_imports)]
+#[cfg(feature = "mesalock_sgx")]
 use std::untrusted::time::SystemTimeEx;
 use tokio::sync::Mutex;
 
 use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use teaclave_proto::teaclave_common::{ExecutorCommand, ExecutorStatus};
 use teaclave_proto::teaclave_scheduler_service::*;
 use teaclave_proto::teaclave_storage_service::*;
@@ -35,6 +40,62 @@ use teaclave_types::*;
 use uuid::Uuid;
 
 const EXECUTOR_TIMEOUT_SECS: u64 = 30;
+// Time an executor has to come back with a new quote once asked to
+const REATTESTATION_TIMEOUT_SECS: u64 = 60;
+// With leaders elected, the queued and delivered tasks are mirrored under
+// these prefixes for the next leader to take over.
+const PENDING_TASK_PREFIX: &str = "scheduler_pending/";
+const TASK_ASSIGNMENT_PREFIX: &str = "scheduler_assignment/";
+// Queued tasks looked at for a batch, so that pulls stay cheap on long queues
+const BATCH_SCAN_WINDOW: usize = 256;
+// The backlog shared with management lapses unless the leader renews it.
+const BACKLOG_TTL_SECS: u64 = 30;
+// Executors are throttled for a part of the heartbeat timeout at most, so
+// that they are not taken as lost.
+const MAX_THROTTLE_SECS: u64 = EXECUTOR_TIMEOUT_SECS / 2;
+
+/// The attested identity of the executor on the other end of the connection,
+/// i.e., the hex-encoded SHA-256 digest of the attested TLS certificate it
+/// connected with. Client certificates are not requested in test mode, where
+/// executors are told apart by their ids only.
+fn executor_identity<T>(
+    request: &Request<T>,
+) -> std::result::Result<Option<String>, SchedulerServiceError> {
+    let cert = request
+        .peer_certs()
+        .and_then(|certs| certs.first().cloned());
+    match cert {
+        Some(cert) => {
+            let digest = ring::digest::digest(&ring::digest::SHA256, cert.as_ref());
+            Ok(Some(hex::encode(digest)))
+        }
+        None if cfg!(test_mode) => Ok(None),
+        None => Err(SchedulerServiceError::MissingExecutorIdentity),
+    }
+}
+
+/// The executor a staged task has been delivered to. The task, together with
+/// the keys of its files, is never handed out to another executor, and only
+/// this executor may report the status and result of the task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TaskAssignment {
+    executor_id: Uuid,
+    identity: Option<String>,
+}
+
+/// A delivered task as mirrored in the storage. The record is created with
+/// compare-and-swap, so that a task is delivered once even if a deposed
+/// leader has not noticed yet.
+#[derive(Serialize, Deserialize)]
+struct AssignmentRecord {
+    executor_id: Uuid,
+    identity: Option<String>,
+    task: StagedTask,
+}
+
+fn mirrored_key(prefix: &str, task_id: &Uuid) -> String {
+    format!("{}{}", prefix, task_id)
+}
 
 #[derive(Clone)]
 pub(crate) struct TeaclaveSchedulerService {
@@ -45,10 +106,38 @@ pub struct TeaclaveSchedulerResources {
     storage_client: Arc<Mutex<TeaclaveStorageClient<Channel>>>,
     // map executor_id to task_id
     task_queue: VecDeque<StagedTask>,
+    // staged tasks beyond the limit are left in the storage queue
+    max_queue_depth: usize,
+    // tasks given to an executor in one pull at most
+    max_batch_size: usize,
     executors_tasks: HashMap<Uuid, Uuid>,
     executors_last_heartbeat: HashMap<Uuid, SystemTime>,
     executors_status: HashMap<Uuid, ExecutorStatus>,
     tasks_to_cancel: HashSet<Uuid>,
+    // map executor_id to the identity it first showed up with
+    executors_identity: HashMap<Uuid, Option<String>>,
+    // map task_id to the executor it has been delivered to
+    tasks_assignment: HashMap<Uuid, TaskAssignment>,
+    // delivered tasks the executor has not started running yet
+    tasks_delivered: HashMap<Uuid, StagedTask>,
+    // running tasks, kept to be queued again if handed back by the executor
+    tasks_running: HashMap<Uuid, StagedTask>,
+    // executors are asked for a new quote after this interval, if any
+    reattestation_interval: Option<Duration>,
+    // map executor_id to the time its identity was last attested
+    executors_attested_at: HashMap<Uuid, SystemTime>,
+    // map executor_id to the time it was asked to re-attest
+    executors_reattesting: HashMap<Uuid, SystemTime>,
+    // map task_id of scheduled tasks to the templates of their runs
+    scheduled_tasks: HashMap<Uuid, ScheduledTask>,
+    // maintenance windows declared in the management service
+    maintenance_windows: Vec<MaintenanceWindow>,
+    // set if scheduler instances elect a leader
+    election: Option<LeaderElection>,
+    // the term of the lease this instance has taken over the tasks in
+    led_term: Option<u64>,
+    // durations of the recent runs, for the backlog estimates
+    run_times: RunTimes,
 }
 
 pub struct TeaclaveSchedulerDeamon {
@@ -62,6 +151,16 @@ impl TeaclaveSchedulerDeamon {
 
             let mut resources = self.resources.lock().await;
 
+            // Standby instances only contend for the lease.
+            match resources.contend_for_lease().await {
+                Ok(true) => (),
+                Ok(false) => continue,
+                Err(e) => {
+                    log::warn!("Failed to take over the tasks: {:?}", e);
+                    continue;
+                }
+            }
+
             let key = StagedTask::get_queue_key().as_bytes();
 
             log::debug!("Pulling task/cancel queue");
@@ -69,9 +168,33 @@ impl TeaclaveSchedulerDeamon {
                 resources.tasks_to_cancel.insert(canceled_task.task_id);
             }
 
-            while let Ok(staged_task) = resources.pull_staged_task::<StagedTask>(key).await {
-                log::debug!("deamon: Pulled staged task: {:?}", staged_task);
-                resources.task_queue.push_back(staged_task);
+            while let Ok(scheduled_task) = resources
+                .pull_staged_task::<ScheduledTask>(SCHEDULE_QUEUE_KEY.as_bytes())
+                .await
+            {
+                log::debug!("deamon: Pulled scheduled task: {:?}", scheduled_task);
+                resources.put_into_db(&scheduled_task).await?;
+                resources
+                    .scheduled_tasks
+                    .insert(scheduled_task.uuid(), scheduled_task);
+            }
+            resources.run_scheduled_tasks().await;
+
+            if let Err(e) = resources.load_maintenance_windows().await {
+                log::warn!("Failed to load maintenance windows: {:?}", e);
+            }
+
+            while !resources.is_task_queue_full() {
+                match resources.pull_staged_task::<StagedTask>(key).await {
+                    Ok(staged_task) => {
+                        log::debug!("deamon: Pulled staged task: {:?}", staged_task);
+                        resources.stage_task(staged_task, false).await;
+                    }
+                    Err(_) => break,
+                }
+            }
+            if let Err(e) = resources.share_backlog().await {
+                log::warn!("Failed to share the task backlog: {:?}", e);
             }
 
             let current_time = SystemTime::now();
@@ -87,11 +210,22 @@ impl TeaclaveSchedulerDeamon {
                     log::warn!("Executor {} lost", executor_id);
                 }
             }
+            for (executor_id, requested_at) in resources.executors_reattesting.iter() {
+                if !to_remove.contains(executor_id)
+                    && current_time
+                        .duration_since(*requested_at)
+                        .unwrap_or_default()
+                        > Duration::from_secs(REATTESTATION_TIMEOUT_SECS)
+                {
+                    to_remove.push(*executor_id);
+                    log::warn!("Executor {} failed to re-attest", executor_id);
+                }
+            }
 
             for executor_id in to_remove {
-                resources.executors_last_heartbeat.remove(&executor_id);
-                resources.executors_status.remove(&executor_id);
-                if let Some(task_id) = resources.executors_tasks.remove(&executor_id) {
+                resources.republish_delivered_tasks(&executor_id).await;
+                if let Some(task_id) = resources.remove_executor(&executor_id) {
+                    resources.release_assignment(&task_id).await;
                     // report task faliure
                     let ts = resources.get_task_state(&task_id).await?;
                     if ts.is_ended() {
@@ -111,7 +245,8 @@ impl TeaclaveSchedulerDeamon {
                     task.update_result(result_err)?;
 
                     let ts = TaskState::from(task);
-                    resources.put_into_db(&ts).await?;
+                    resources.put_task_into_db(&ts).await?;
+                    resources.emit_task_event(&ts).await?;
                 }
             }
         }
@@ -135,7 +270,13 @@ impl TeaclaveSchedulerDeamon {
 }
 
 impl TeaclaveSchedulerResources {
-    pub(crate) async fn new(storage_service_endpoint: Endpoint) -> Result<Self> {
+    pub(crate) async fn new(
+        storage_service_endpoint: Endpoint,
+        max_queue_depth: u32,
+        reattestation_interval_secs: u64,
+        lease_secs: u64,
+        max_batch_size: u32,
+    ) -> Result<Self> {
         let channel = storage_service_endpoint
             .connect()
             .await
@@ -148,19 +289,609 @@ impl TeaclaveSchedulerResources {
         let executors_status = HashMap::new();
         let tasks_to_cancel = HashSet::new();
         let executors_last_heartbeat = HashMap::new();
+        let executors_identity = HashMap::new();
+        let tasks_assignment = HashMap::new();
+        let tasks_delivered = HashMap::new();
+        let tasks_running = HashMap::new();
+        let reattestation_interval = match reattestation_interval_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        let executors_attested_at = HashMap::new();
+        let executors_reattesting = HashMap::new();
+        let scheduled_tasks = HashMap::new();
+        let maintenance_windows = Vec::new();
+        let election = match lease_secs {
+            0 => None,
+            secs => Some(LeaderElection::new(secs)),
+        };
 
-        let resources = TeaclaveSchedulerResources {
+        let mut resources = TeaclaveSchedulerResources {
             storage_client,
             task_queue,
+            max_queue_depth: max_queue_depth as usize,
+            max_batch_size: max_batch_size as usize,
             executors_tasks,
             executors_last_heartbeat,
             executors_status,
             tasks_to_cancel,
+            executors_identity,
+            tasks_assignment,
+            tasks_delivered,
+            tasks_running,
+            reattestation_interval,
+            executors_attested_at,
+            executors_reattesting,
+            scheduled_tasks,
+            maintenance_windows,
+            election,
+            led_term: None,
+            run_times: RunTimes::default(),
         };
+        resources.load_scheduled_tasks().await?;
 
         Ok(resources)
     }
 
+    // Scheduled tasks are kept in the storage, so that they keep running
+    // after the scheduler restarts.
+    async fn load_scheduled_tasks(&mut self) -> Result<()> {
+        let request = GetKeysByPrefixRequest::new(ScheduledTask::key_prefix());
+        let keys = self
+            .storage_client
+            .lock()
+            .await
+            .get_keys_by_prefix(request)
+            .await?
+            .into_inner()
+            .keys;
+        for key in keys {
+            let key = ExternalID::try_from(String::from_utf8(key)?)?;
+            let scheduled_task: ScheduledTask = self.get_from_db(&key).await?;
+            self.scheduled_tasks
+                .insert(scheduled_task.uuid(), scheduled_task);
+        }
+        Ok(())
+    }
+
+    fn is_leader(&self) -> bool {
+        match &self.election {
+            Some(election) => self.led_term.is_some() && election.is_leader(now_secs() as u64),
+            None => true,
+        }
+    }
+
+    fn ensure_leader(&self) -> std::result::Result<(), SchedulerServiceError> {
+        if !self.is_leader() {
+            return Err(SchedulerServiceError::NotLeader);
+        }
+        Ok(())
+    }
+
+    /// Contend for the lease if leaders are elected, taking the tasks over
+    /// once the lease is taken, and forgetting them once it is lost. Returns
+    /// whether this instance leads.
+    async fn contend_for_lease(&mut self) -> Result<bool> {
+        let now = now_secs() as u64;
+        let election = match self.election.as_mut() {
+            Some(election) => election,
+            None => return Ok(true),
+        };
+        if let Err(e) = election.contend(&self.storage_client, now).await {
+            log::warn!("Failed to contend for the scheduler lease: {:?}", e);
+        }
+        let term = if election.is_leader(now) {
+            election.term()
+        } else {
+            None
+        };
+        if term != self.led_term {
+            if self.led_term.is_some() {
+                log::warn!("Scheduler stepped down in term {:?}", self.led_term);
+            }
+            self.led_term = None;
+            self.forget_tasks();
+            if term.is_some() {
+                self.take_over().await?;
+                self.led_term = term;
+            }
+        }
+        Ok(self.led_term.is_some())
+    }
+
+    fn forget_tasks(&mut self) {
+        self.task_queue.clear();
+        self.executors_tasks.clear();
+        self.executors_last_heartbeat.clear();
+        self.executors_status.clear();
+        self.tasks_to_cancel.clear();
+        self.executors_identity.clear();
+        self.tasks_assignment.clear();
+        self.tasks_delivered.clear();
+        self.tasks_running.clear();
+        self.executors_attested_at.clear();
+        self.executors_reattesting.clear();
+        self.scheduled_tasks.clear();
+        self.run_times.clear();
+    }
+
+    /// Pick up the tasks mirrored by the previous leader. The executors of
+    /// delivered tasks have a heartbeat timeout to report to this instance,
+    /// or their tasks fail as with lost executors.
+    async fn take_over(&mut self) -> Result<()> {
+        self.load_scheduled_tasks().await?;
+        let now = SystemTime::now();
+        let records: Vec<AssignmentRecord> = self.read_mirrored(TASK_ASSIGNMENT_PREFIX).await?;
+        for record in records {
+            let task_id = record.task.task_id;
+            let ts = self.get_task_state(&task_id).await?;
+            if ts.is_ended() {
+                self.release_assignment(&task_id).await;
+                continue;
+            }
+            // An executor runs one task of its batch at a time.
+            if ts.status == TaskStatus::Running {
+                self.tasks_running.insert(task_id, record.task);
+                self.executors_tasks.insert(record.executor_id, task_id);
+            } else {
+                self.tasks_delivered.insert(task_id, record.task);
+                self.executors_tasks
+                    .entry(record.executor_id)
+                    .or_insert(task_id);
+            }
+            self.executors_identity
+                .insert(record.executor_id, record.identity.clone());
+            self.executors_attested_at.insert(record.executor_id, now);
+            self.executors_last_heartbeat
+                .insert(record.executor_id, now);
+            let assignment = TaskAssignment {
+                executor_id: record.executor_id,
+                identity: record.identity,
+            };
+            self.tasks_assignment.insert(task_id, assignment);
+        }
+        let pending: Vec<StagedTask> = self.read_mirrored(PENDING_TASK_PREFIX).await?;
+        for task in pending {
+            if !self.tasks_assignment.contains_key(&task.task_id) {
+                self.task_queue.push_back(task);
+            }
+        }
+        log::info!(
+            "Took over {} queued and {} delivered tasks",
+            self.task_queue.len(),
+            self.tasks_assignment.len()
+        );
+        Ok(())
+    }
+
+    async fn read_mirrored<T: DeserializeOwned>(&self, prefix: &str) -> Result<Vec<T>> {
+        let mut storage = self.storage_client.lock().await;
+        let keys = storage
+            .get_keys_by_prefix(GetKeysByPrefixRequest::new(prefix))
+            .await?
+            .into_inner()
+            .keys;
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            let value = storage.get(GetRequest::new(key)).await?.into_inner().value;
+            values.push(serde_json::from_slice(&value)?);
+        }
+        Ok(values)
+    }
+
+    /// Queue the staged task, in front of the other tasks if it has waited
+    /// already, mirroring it if leaders are elected.
+    async fn stage_task(&mut self, task: StagedTask, front: bool) {
+        if self.election.is_some() {
+            if let Err(e) = self.mirror_pending_task(&task).await {
+                log::warn!("Failed to mirror task {}: {:?}", task.task_id, e);
+            }
+        }
+        if front {
+            self.task_queue.push_front(task);
+        } else {
+            self.task_queue.push_back(task);
+        }
+    }
+
+    async fn mirror_pending_task(&self, task: &StagedTask) -> Result<()> {
+        let key = mirrored_key(PENDING_TASK_PREFIX, &task.task_id);
+        let request = PutRequest::new(key.as_bytes(), task.to_vec()?);
+        self.storage_client.lock().await.put(request).await?;
+        Ok(())
+    }
+
+    // Tasks leave the queue once delivered or failed before delivery.
+    async fn forget_pending_task(&self, task_id: &Uuid) {
+        if self.election.is_none() {
+            return;
+        }
+        let key = mirrored_key(PENDING_TASK_PREFIX, task_id);
+        let request = DeleteRequest::new(key.as_bytes());
+        if let Err(e) = self.storage_client.lock().await.delete(request).await {
+            log::warn!("Failed to forget queued task {}: {:?}", task_id, e);
+        }
+    }
+
+    /// Record the delivery of the task, unless it has been delivered
+    /// already, by this or a deposed leader.
+    async fn claim_assignment(
+        &self,
+        task: &StagedTask,
+        assignment: &TaskAssignment,
+    ) -> Result<bool> {
+        if self.election.is_none() {
+            return Ok(true);
+        }
+        let record = AssignmentRecord {
+            executor_id: assignment.executor_id,
+            identity: assignment.identity.clone(),
+            task: task.clone(),
+        };
+        let key = mirrored_key(TASK_ASSIGNMENT_PREFIX, &task.task_id);
+        let request = CompareAndSwapRequest::new(key.as_bytes(), serde_json::to_vec(&record)?);
+        let swapped = self
+            .storage_client
+            .lock()
+            .await
+            .compare_and_swap(request)
+            .await?
+            .into_inner()
+            .swapped;
+        if swapped {
+            self.forget_pending_task(&task.task_id).await;
+        }
+        Ok(swapped)
+    }
+
+    // Delivered tasks are released once they end or are queued again.
+    async fn release_assignment(&self, task_id: &Uuid) {
+        if self.election.is_none() {
+            return;
+        }
+        let key = mirrored_key(TASK_ASSIGNMENT_PREFIX, task_id);
+        let request = DeleteRequest::new(key.as_bytes());
+        if let Err(e) = self.storage_client.lock().await.delete(request).await {
+            log::warn!(
+                "Failed to release the assignment of task {}: {:?}",
+                task_id,
+                e
+            );
+        }
+    }
+
+    async fn load_maintenance_windows(&mut self) -> Result<()> {
+        let request = GetKeysByPrefixRequest::new(MaintenanceWindow::key_prefix());
+        let keys = self
+            .storage_client
+            .lock()
+            .await
+            .get_keys_by_prefix(request)
+            .await?
+            .into_inner()
+            .keys;
+        let mut windows = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key = ExternalID::try_from(String::from_utf8(key)?)?;
+            windows.push(self.get_from_db(&key).await?);
+        }
+        self.maintenance_windows = windows;
+        Ok(())
+    }
+
+    fn maintenance_window(&self, now: i64) -> Option<&MaintenanceWindow> {
+        active_maintenance_window(&self.maintenance_windows, now)
+    }
+
+    fn task_backlog(&self) -> TaskBacklog {
+        TaskBacklog {
+            queued: self.task_queue.len() as u32,
+            executors: self.executors_last_heartbeat.len() as u32,
+            average_run_secs: self.run_times.average_secs(),
+        }
+    }
+
+    /// Share the backlog for the management service to tell the callers of
+    /// `InvokeTask` how long their tasks may wait.
+    async fn share_backlog(&self) -> Result<()> {
+        let value = serde_json::to_vec(&self.task_backlog())?;
+        let expires_at = now_secs() as u64 + BACKLOG_TTL_SECS;
+        let request =
+            PutRequest::new(SCHEDULER_BACKLOG_KEY.as_bytes(), value).expires_at(expires_at);
+        self.storage_client.lock().await.put(request).await?;
+        Ok(())
+    }
+
+    async fn run_scheduled_tasks(&mut self) {
+        let task_ids: Vec<Uuid> = self.scheduled_tasks.keys().cloned().collect();
+        for task_id in task_ids {
+            if let Err(e) = self.run_scheduled_task(&task_id).await {
+                log::warn!("Failed to run scheduled task {}: {:?}", task_id, e);
+            }
+        }
+    }
+
+    /// Materialize the due run of a scheduled task as a new staged task. A
+    /// run is skipped if the previous one has not ended, since runs write to
+    /// the same output locations.
+    async fn run_scheduled_task(&mut self, task_id: &Uuid) -> Result<()> {
+        if self.tasks_to_cancel.remove(task_id) {
+            self.cancel_task(*task_id).await?;
+            return self.remove_scheduled_task(task_id).await;
+        }
+
+        let mut template = self.get_task_state(task_id).await?;
+        if template.status != TaskStatus::Staged {
+            return self.remove_scheduled_task(task_id).await;
+        }
+        let mut schedule = template
+            .schedule
+            .take()
+            .ok_or_else(|| anyhow!("Task {} has no schedule", task_id))?;
+        if !schedule.is_due() {
+            return Ok(());
+        }
+
+        let previous_running = match schedule.last_instance() {
+            Some(instance_id) => !self.get_task_state(&instance_id).await?.is_ended(),
+            None => false,
+        };
+        if previous_running || self.is_task_queue_full() {
+            log::warn!(
+                "Skipped the run of scheduled task {} due at {}",
+                task_id,
+                schedule.next_run_at
+            );
+            schedule.record_run(None);
+        } else {
+            let scheduled_task = self
+                .scheduled_tasks
+                .get(task_id)
+                .ok_or_else(|| anyhow!("Task {} is not scheduled", task_id))?;
+            let (ts, staged_task, outputs) = scheduled_task.instantiate(&template)?;
+            for output in outputs.iter() {
+                self.put_into_db(output).await?;
+            }
+            self.put_task_into_db(&ts).await?;
+            log::info!(
+                trace_id = staged_task.trace_id.as_str();
+                "Scheduled task {} runs as task {}",
+                task_id,
+                ts.task_id
+            );
+            self.stage_task(staged_task, false).await;
+            schedule.record_run(Some(ts.task_id));
+        }
+
+        template.schedule = Some(schedule);
+        self.put_task_into_db(&template).await
+    }
+
+    async fn remove_scheduled_task(&mut self, task_id: &Uuid) -> Result<()> {
+        if let Some(scheduled_task) = self.scheduled_tasks.remove(task_id) {
+            let request = DeleteRequest::new(scheduled_task.key());
+            self.storage_client.lock().await.delete(request).await?;
+        }
+        Ok(())
+    }
+
+    fn is_task_queue_full(&self) -> bool {
+        self.task_queue.len() >= self.max_queue_depth
+    }
+
+    /// Bind the executor id to the identity it first shows up with, so that
+    /// another node cannot act on behalf of the executor by claiming its id.
+    /// An executor asked to re-attest is bound to the identity it comes back
+    /// with instead.
+    fn check_executor(
+        &mut self,
+        executor_id: Uuid,
+        identity: &Option<String>,
+    ) -> std::result::Result<(), SchedulerServiceError> {
+        let bound = match self.executors_identity.get(&executor_id) {
+            Some(bound) => bound,
+            None => {
+                self.executors_identity
+                    .insert(executor_id, identity.clone());
+                self.executors_attested_at
+                    .insert(executor_id, SystemTime::now());
+                return Ok(());
+            }
+        };
+        if self.executors_reattesting.contains_key(&executor_id)
+            && (identity.is_none() || bound != identity)
+        {
+            log::info!("Executor {} re-attested", executor_id);
+            self.executors_reattesting.remove(&executor_id);
+            self.executors_identity
+                .insert(executor_id, identity.clone());
+            self.executors_attested_at
+                .insert(executor_id, SystemTime::now());
+            return Ok(());
+        }
+        if bound != identity {
+            log::warn!("Executor {} connected with another identity", executor_id);
+            return Err(SchedulerServiceError::ExecutorIdentityMismatch);
+        }
+        Ok(())
+    }
+
+    fn is_reattestation_due(&self, executor_id: &Uuid) -> bool {
+        let (interval, attested_at) = match (
+            self.reattestation_interval,
+            self.executors_attested_at.get(executor_id),
+        ) {
+            (Some(interval), Some(attested_at)) => (interval, attested_at),
+            _ => return false,
+        };
+        SystemTime::now()
+            .duration_since(*attested_at)
+            .map(|elapsed| elapsed >= interval)
+            .unwrap_or(false)
+    }
+
+    /// Executors due for re-attestation are drained: they finish the task at
+    /// hand but are not given new ones until they come back with a new quote.
+    fn check_attestation_fresh(
+        &self,
+        executor_id: &Uuid,
+    ) -> std::result::Result<(), SchedulerServiceError> {
+        if self.executors_reattesting.contains_key(executor_id)
+            || self.is_reattestation_due(executor_id)
+        {
+            return Err(SchedulerServiceError::ReattestationRequired);
+        }
+        Ok(())
+    }
+
+    /// Put the tasks delivered to the executor but never started back to the
+    /// front of the queue, so that another executor picks them up.
+    async fn republish_delivered_tasks(&mut self, executor_id: &Uuid) {
+        let task_ids: Vec<Uuid> = self
+            .tasks_delivered
+            .keys()
+            .filter(|task_id| {
+                self.tasks_assignment
+                    .get(task_id)
+                    .map_or(false, |assignment| &assignment.executor_id == executor_id)
+            })
+            .cloned()
+            .collect();
+        for task_id in task_ids {
+            if let Some(task) = self.tasks_delivered.remove(&task_id) {
+                log::warn!(
+                    trace_id = task.trace_id.as_str();
+                    "Task {} re-published from executor {}",
+                    task_id,
+                    executor_id
+                );
+                self.tasks_assignment.remove(&task_id);
+                if self.executors_tasks.get(executor_id) == Some(&task_id) {
+                    self.executors_tasks.remove(executor_id);
+                }
+                self.release_assignment(&task_id).await;
+                self.stage_task(task, true).await;
+            }
+        }
+    }
+
+    /// Forget the executor, returning the task it was last given, if any.
+    fn remove_executor(&mut self, executor_id: &Uuid) -> Option<Uuid> {
+        self.executors_last_heartbeat.remove(executor_id);
+        self.executors_status.remove(executor_id);
+        self.executors_identity.remove(executor_id);
+        self.executors_attested_at.remove(executor_id);
+        self.executors_reattesting.remove(executor_id);
+        let task_id = self.executors_tasks.remove(executor_id)?;
+        self.tasks_delivered.remove(&task_id);
+        self.tasks_running.remove(&task_id);
+        self.run_times.forget(&task_id);
+        Some(task_id)
+    }
+
+    /// Queue a task handed back by its executor again, in front of the other
+    /// tasks as it has waited already. A running task is staged again and
+    /// runs from the beginning on another executor. The executor is
+    /// forgotten, so that the task is not failed once its heartbeat times
+    /// out.
+    async fn requeue_task(
+        &mut self,
+        executor_id: &Uuid,
+        task_id: &Uuid,
+        reason: HandoffReason,
+    ) -> Result<()> {
+        let running = self.tasks_running.remove(task_id);
+        let delivered = self.tasks_delivered.remove(task_id);
+        let was_running = running.is_some();
+        self.tasks_assignment.remove(task_id);
+        self.run_times.forget(task_id);
+        self.remove_executor(executor_id);
+        self.release_assignment(task_id).await;
+        let task = running
+            .or(delivered)
+            .ok_or_else(|| anyhow!("Task {} is not delivered", task_id))?;
+        log::warn!(
+            trace_id = task.trace_id.as_str();
+            "Task {} handed back by executor {}: {:?}",
+            task_id,
+            executor_id,
+            reason
+        );
+
+        if was_running {
+            let ts = self.get_task_state(task_id).await?;
+            // Canceled while running
+            if ts.is_ended() {
+                return Ok(());
+            }
+            let task: Task<Requeue> = ts.try_into()?;
+            self.put_task_into_db(&TaskState::from(task)).await?;
+        }
+        self.stage_task(task, true).await;
+        Ok(())
+    }
+
+    /// Deliver the queued tasks batching with `head` to the executor along
+    /// with it, up to the batch size.
+    async fn deliver_batch(
+        &mut self,
+        head: &StagedTask,
+        assignment: &TaskAssignment,
+        executor_features: &ExecutorFeatures,
+    ) -> Vec<StagedTask> {
+        let mut batch = Vec::new();
+        let mut index = 0;
+        let mut scanned = 0;
+        while batch.len() + 1 < self.max_batch_size
+            && scanned < BATCH_SCAN_WINDOW
+            && index < self.task_queue.len()
+        {
+            scanned += 1;
+            let task = &self.task_queue[index];
+            if !head.batches_with(task)
+                || self.tasks_to_cancel.contains(&task.task_id)
+                || self.tasks_assignment.contains_key(&task.task_id)
+                || task.executor_features.check(executor_features).is_err()
+            {
+                index += 1;
+                continue;
+            }
+            let task = match self.task_queue.remove(index) {
+                Some(task) => task,
+                None => break,
+            };
+            match self.claim_assignment(&task, assignment).await {
+                Ok(true) => (),
+                // Delivered by the leader which took over
+                Ok(false) => continue,
+                Err(e) => {
+                    log::warn!("Failed to claim task {}: {:?}", task.task_id, e);
+                    self.task_queue.insert(index, task);
+                    break;
+                }
+            }
+            self.tasks_assignment
+                .insert(task.task_id, assignment.clone());
+            self.tasks_delivered.insert(task.task_id, task.clone());
+            batch.push(task);
+        }
+        batch
+    }
+
+    fn check_task_assignment(
+        &self,
+        task_id: &Uuid,
+        identity: &Option<String>,
+    ) -> std::result::Result<(), SchedulerServiceError> {
+        match self.tasks_assignment.get(task_id) {
+            Some(assignment) if &assignment.identity == identity => Ok(()),
+            _ => {
+                log::warn!("Rejected report on task {} from another executor", task_id);
+                Err(SchedulerServiceError::ExecutorIdentityMismatch)
+            }
+        }
+    }
+
     async fn pull_staged_task<T: Storable>(
         &self,
         key: &[u8],
@@ -203,7 +934,26 @@ impl TeaclaveSchedulerResources {
         task.update_result(result_err)?;
 
         let ts = TaskState::from(task);
-        self.put_into_db(&ts).await?;
+        self.put_task_into_db(&ts).await?;
+        self.emit_task_event(&ts).await?;
+
+        Ok(())
+    }
+
+    async fn fail_task(
+        &self,
+        task_id: Uuid,
+        mismatch: &FeatureMismatch,
+    ) -> std::result::Result<(), SchedulerServiceError> {
+        let ts = self.get_task_state(&task_id).await?;
+        let mut task: Task<Fail> = ts.try_into()?;
+
+        let reason = format!("Runtime Error: {}", mismatch);
+        task.update_result(TaskResult::Err(TaskFailure::new(reason)))?;
+
+        let ts = TaskState::from(task);
+        self.put_task_into_db(&ts).await?;
+        self.emit_task_event(&ts).await?;
 
         Ok(())
     }
@@ -223,6 +973,128 @@ impl TeaclaveSchedulerResources {
         T::from_slice(response.value.as_slice())
     }
 
+    // The result of the release verifier is recorded on the output file its
+    // input is registered from, provided the content is still the same.
+    async fn record_release_verdict(&self, ts: &TaskState) -> Result<()> {
+        let outputs = match &ts.result {
+            TaskResult::Ok(outputs) => outputs,
+            _ => return Ok(()),
+        };
+        let function: Function = self.get_from_db(&ts.function_id).await?;
+        if function.executor_type != ExecutorType::Builtin
+            || function.name != ReleaseVerdict::VERIFIER
+        {
+            return Ok(());
+        }
+        let input = match ts.assigned_inputs.get(ReleaseVerdict::VERIFIER_INPUT) {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        let key = ExternalID::new(TeaclaveOutputFile::key_prefix(), input.uuid);
+        let mut output: TeaclaveOutputFile = match self.get_from_db(&key).await {
+            Ok(output) => output,
+            // Not registered from an output, nothing to release
+            Err(_) => return Ok(()),
+        };
+        anyhow::ensure!(
+            output.cmac == Some(input.cmac),
+            "Output changed since verified: {}",
+            key
+        );
+
+        let mut verdict: ReleaseVerdict = serde_json::from_slice(&outputs.return_value)?;
+        verdict.task_id = Some(ts.task_id);
+        log::info!(
+            "Release verdict of {}: releasable: {}, {}",
+            key,
+            verdict.releasable,
+            verdict.reason
+        );
+        output.record_release_verdict(verdict);
+        self.put_into_db(&output).await
+    }
+
+    // Participants are notified of the ended task in the next digest.
+    async fn emit_task_event(&self, ts: &TaskState) -> Result<()> {
+        let event = TaskEvent::from_task_state(ts);
+        let request = EnqueueRequest::new(TASK_EVENT_QUEUE_KEY.as_bytes(), event.to_vec()?);
+        self.storage_client.lock().await.enqueue(request).await?;
+        Ok(())
+    }
+
+    async fn record_file_transfers(&self, records: &[FileTransferRecord]) -> Result<()> {
+        for record in records {
+            let request = EnqueueRequest::new(FILE_TRANSFER_QUEUE_KEY.as_bytes(), record.to_vec()?);
+            self.storage_client.lock().await.enqueue(request).await?;
+        }
+        Ok(())
+    }
+
+    // Usage records are written along with their index by the function and
+    // the end time, for the billing queries of management.
+    async fn record_usage(&self, ts: &TaskState, usage: TaskUsage) -> Result<()> {
+        let record = UsageRecord::new(ts, usage);
+        let request = WriteBatchRequest::new()
+            .put(record.key(), record.to_vec()?)
+            .put(record.index_key(), Vec::new());
+        self.storage_client
+            .lock()
+            .await
+            .write_batch(request)
+            .await?;
+        Ok(())
+    }
+
+    async fn record_lineage(&self, ts: &TaskState) -> Result<()> {
+        for record in LineageRecord::from_finished_task(ts) {
+            let key = LineageRecord::key(&record.data_id);
+            let request = PutRequest::new(key, serde_json::to_vec(&record)?);
+            self.storage_client.lock().await.put(request).await?;
+        }
+        Ok(())
+    }
+
+    // Phase timings of profiled tasks are accumulated per function for the
+    // usage stats of management.
+    async fn record_profile(&self, ts: &TaskState) -> Result<()> {
+        let profile = match &ts.result {
+            TaskResult::Ok(TaskOutputs {
+                profile: Some(profile),
+                ..
+            }) => profile,
+            _ => return Ok(()),
+        };
+        let mut function_profile = FunctionProfile::new(ts.function_id.uuid);
+        if let Ok(existing) = self
+            .get_from_db::<FunctionProfile>(&function_profile.external_id())
+            .await
+        {
+            function_profile = existing;
+        }
+        function_profile.record(profile);
+        self.put_into_db(&function_profile).await
+    }
+
+    // Task states are written along with their secondary index for the
+    // listings of management.
+    async fn put_task_into_db(&self, ts: &TaskState) -> Result<()> {
+        let old = self.get_task_state(&ts.task_id).await.ok();
+        let (puts, deletes) = TaskIndex::changes(old.as_ref(), ts);
+        let mut request = WriteBatchRequest::new().put(ts.key(), ts.to_vec()?);
+        for key in puts {
+            request = request.put(key, Vec::new());
+        }
+        for key in deletes {
+            request = request.delete(key);
+        }
+        self.storage_client
+            .lock()
+            .await
+            .write_batch(request)
+            .await?;
+        Ok(())
+    }
+
     async fn put_into_db(&self, item: &impl Storable) -> Result<()> {
         let k = item.key();
         let v = item.to_vec()?;
@@ -245,10 +1117,16 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         // XXX: Publisher is not implemented
 
         let mut resources = self.resources.lock().await;
+        resources.ensure_leader()?;
+
+        if resources.is_task_queue_full() {
+            log::warn!("Task queue reached the limit {}", resources.max_queue_depth);
+            return Err(SchedulerServiceError::TaskQueueFull.into());
+        }
 
         let staged_task =
             StagedTask::from_slice(&request.get_ref().staged_task).map_err(tonic_error)?;
-        resources.task_queue.push_back(staged_task);
+        resources.stage_task(staged_task, false).await;
         Ok(Response::new(()))
     }
 
@@ -268,9 +1146,16 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         let mut resources = self.resources.lock().await;
 
         let mut command = ExecutorCommand::NoAction;
+        // Executors keep their connection to a standby until the advertised
+        // address routes to the new leader.
+        if !resources.is_leader() {
+            return Ok(Response::new(HeartbeatResponse::new(command)));
+        }
 
         let executor_id = Uuid::parse_str(&request.get_ref().executor_id).map_err(tonic_error)?;
         let status = request.get_ref().status.try_into().map_err(tonic_error)?;
+        let identity = executor_identity(&request)?;
+        resources.check_executor(executor_id, &identity)?;
 
         resources.executors_status.insert(executor_id, status);
 
@@ -301,11 +1186,41 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
             }
         }
 
-        if !resources.task_queue.is_empty() {
+        // Wait for the executor to come back with a new quote
+        if resources.executors_reattesting.contains_key(&executor_id) {
+            return Ok(Response::new(HeartbeatResponse::new(command)));
+        }
+        // Ask for a new quote once the executor has been drained
+        if resources.is_reattestation_due(&executor_id) {
+            if status == ExecutorStatus::Idle
+                && !resources.executors_tasks.contains_key(&executor_id)
+            {
+                log::info!("Asking executor {} to re-attest", executor_id);
+                resources
+                    .executors_reattesting
+                    .insert(executor_id, SystemTime::now());
+                command = ExecutorCommand::Reattest;
+            }
+            return Ok(Response::new(HeartbeatResponse::new(command)));
+        }
+
+        let backlog = resources.task_backlog();
+        let eta_secs = backlog.eta_secs(backlog.queued);
+        // No task is handed out during maintenance windows, so that the
+        // executors are drained. Idle executors hold off until the window
+        // ends rather than asking for tasks in vain.
+        let now = now_secs();
+        if let Some(window) = resources.maintenance_window(now) {
+            if status == ExecutorStatus::Idle {
+                let secs = window.retry_after(now).min(MAX_THROTTLE_SECS);
+                let response = HeartbeatResponse::throttle(secs).backlog(backlog.queued, eta_secs);
+                return Ok(Response::new(response));
+            }
+        } else if !resources.task_queue.is_empty() {
             command = ExecutorCommand::NewTask;
         }
 
-        let response = HeartbeatResponse::new(command);
+        let response = HeartbeatResponse::new(command).backlog(backlog.queued, eta_secs);
         Ok(Response::new(response))
     }
 
@@ -313,20 +1228,98 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         &self,
         request: Request<PullTaskRequest>,
     ) -> TeaclaveServiceResponseResult<PullTaskResponse> {
-        let request = request.get_ref();
+        let identity = executor_identity(&request)?;
+        let request = request.into_inner();
+        let executor_id = Uuid::parse_str(&request.executor_id).map_err(tonic_error)?;
+        let executor_features = ExecutorFeatures::from(request.executor_features);
+        let accepts_batch = request.accepts_batch;
         let mut resources = self.resources.lock().await;
+        resources.ensure_leader()?;
+        resources.check_executor(executor_id, &identity)?;
+        resources.check_attestation_fresh(&executor_id)?;
         match resources.task_queue.pop_front() {
             Some(task) => match resources.tasks_to_cancel.take(&task.task_id) {
                 Some(task_id) => {
+                    resources.forget_pending_task(&task_id).await;
                     resources.cancel_task(task_id).await?;
                     Err(SchedulerServiceError::TaskCanceled.into())
                 }
                 None => {
-                    resources.executors_tasks.insert(
-                        Uuid::parse_str(&request.executor_id).map_err(tonic_error)?,
+                    // Failing the task here tells the creator why, instead of
+                    // leaving the function to crash in the executor.
+                    if let Err(mismatch) = task.executor_features.check(&executor_features) {
+                        log::warn!(
+                            trace_id = task.trace_id.as_str();
+                            "Task {} failed on executor {}: {}",
+                            task.task_id,
+                            executor_id,
+                            mismatch
+                        );
+                        resources.forget_pending_task(&task.task_id).await;
+                        resources.fail_task(task.task_id, &mismatch).await?;
+                        return Err(SchedulerServiceError::ExecutorFeatureMismatch(mismatch).into());
+                    }
+                    let assignment = TaskAssignment {
+                        executor_id,
+                        identity,
+                    };
+                    // A task is delivered at most once; a copy of a task
+                    // which already went to another executor is dropped.
+                    match resources.tasks_assignment.get(&task.task_id) {
+                        Some(assigned) if assigned != &assignment => {
+                            log::warn!(
+                                "Executor {} tried to pull task {} assigned to executor {}",
+                                executor_id,
+                                task.task_id,
+                                assigned.executor_id
+                            );
+                            return Err(SchedulerServiceError::ExecutorIdentityMismatch.into());
+                        }
+                        Some(_) => (),
+                        None => match resources.claim_assignment(&task, &assignment).await {
+                            Ok(true) => (),
+                            // Delivered by the leader which took over
+                            Ok(false) => {
+                                log::warn!("Task {} delivered by another scheduler", task.task_id);
+                                return Err(SchedulerServiceError::NotLeader.into());
+                            }
+                            Err(e) => {
+                                log::warn!("Failed to claim task {}: {:?}", task.task_id, e);
+                                resources.task_queue.push_front(task);
+                                return Err(SchedulerServiceError::StorageError.into());
+                            }
+                        },
+                    }
+                    resources
+                        .tasks_assignment
+                        .insert(task.task_id, assignment.clone());
+                    resources.executors_tasks.insert(executor_id, task.task_id);
+                    resources.tasks_delivered.insert(task.task_id, task.clone());
+                    log::info!(
+                        trace_id = task.trace_id.as_str();
+                        "Task {} delivered to executor {}",
                         task.task_id,
+                        executor_id
                     );
-                    Ok(Response::new(PullTaskResponse::new(task)))
+                    let batch = if accepts_batch && task.is_batchable() {
+                        resources
+                            .deliver_batch(&task, &assignment, &executor_features)
+                            .await
+                    } else {
+                        Vec::new()
+                    };
+                    if !batch.is_empty() {
+                        log::info!(
+                            trace_id = task.trace_id.as_str();
+                            "{} more tasks delivered to executor {} in a batch with task {}",
+                            batch.len(),
+                            executor_id,
+                            task.task_id
+                        );
+                    }
+                    Ok(Response::new(
+                        PullTaskResponse::new(task).batched_tasks(&batch),
+                    ))
                 }
             },
             None => Err(SchedulerServiceError::TaskQueueEmpty.into()),
@@ -337,9 +1330,21 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         &self,
         request: Request<UpdateTaskStatusRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
-        let resources = self.resources.lock().await;
+        let mut resources = self.resources.lock().await;
+        resources.ensure_leader()?;
 
+        let identity = executor_identity(&request)?;
         let task_id = Uuid::parse_str(&request.get_ref().task_id).map_err(tonic_error)?;
+        resources.check_task_assignment(&task_id, &identity)?;
+        if let Some(task) = resources.tasks_delivered.remove(&task_id) {
+            resources.tasks_running.insert(task_id, task);
+            resources.run_times.start(task_id, SystemTime::now());
+        }
+        // The executor runs the tasks of a batch one after another.
+        if let Some(assignment) = resources.tasks_assignment.get(&task_id) {
+            let executor_id = assignment.executor_id;
+            resources.executors_tasks.insert(executor_id, task_id);
+        }
         let ts = resources
             .get_task_state(&task_id)
             .await
@@ -350,7 +1355,7 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         // Only TaskStatus::Running is implicitly allowed here.
 
         let ts = TaskState::from(task);
-        resources.put_into_db(&ts).await.map_err(tonic_error)?;
+        resources.put_task_into_db(&ts).await.map_err(tonic_error)?;
         Ok(Response::new(()))
     }
 
@@ -358,11 +1363,19 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         &self,
         request: Request<UpdateTaskResultRequest>,
     ) -> TeaclaveServiceResponseResult<()> {
-        let resources = self.resources.lock().await;
+        let mut resources = self.resources.lock().await;
+        resources.ensure_leader()?;
 
+        let identity = executor_identity(&request)?;
         let request = request.into_inner();
+        let task_id = Uuid::parse_str(&request.task_id).map_err(tonic_error)?;
+        resources.check_task_assignment(&task_id, &identity)?;
+        resources.tasks_delivered.remove(&task_id);
+        resources.tasks_running.remove(&task_id);
+        resources.run_times.end(&task_id, SystemTime::now());
+        resources.release_assignment(&task_id).await;
         let ts = resources
-            .get_task_state(&Uuid::parse_str(&request.task_id).map_err(tonic_error)?)
+            .get_task_state(&task_id)
             .await
             .map_err(tonic_error)?;
         let mut task: Task<Finish> = ts.try_into().map_err(tonic_error)?;
@@ -376,12 +1389,101 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
             }
         };
 
+        let log_file: Option<TaskLogFile> = request
+            .log_file
+            .map(TryInto::try_into)
+            .transpose()
+            .map_err(tonic_error)?;
+        task.update_log_file(log_file);
+
+        let mut file_transfers = request
+            .file_transfers
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<FileTransferRecord>>>()
+            .map_err(tonic_error)?;
+        file_transfers.retain(|record| record.task_id == task_id);
+
         // Updating task result means we have finished execution
         task.update_result(task_result).map_err(tonic_error)?;
         log::debug!("UpdateTaskResult: Task {:?}", task);
 
         let ts = TaskState::from(task);
-        resources.put_into_db(&ts).await.map_err(tonic_error)?;
+        resources.put_task_into_db(&ts).await.map_err(tonic_error)?;
+        resources.emit_task_event(&ts).await.map_err(tonic_error)?;
+        // Losing the audit entries of transfers does not fail the task.
+        if let Err(e) = resources.record_file_transfers(&file_transfers).await {
+            log::warn!(
+                "Failed to record file transfers of task {}: {:?}",
+                task_id,
+                e
+            );
+        }
+        if let Some(usage) = request.usage {
+            resources
+                .record_usage(&ts, usage.into())
+                .await
+                .map_err(tonic_error)?;
+        }
+        resources.record_lineage(&ts).await.map_err(tonic_error)?;
+        resources.record_profile(&ts).await.map_err(tonic_error)?;
+        resources
+            .record_release_verdict(&ts)
+            .await
+            .map_err(tonic_error)?;
+        Ok(Response::new(()))
+    }
+    async fn report_task_progress(
+        &self,
+        request: Request<ReportTaskProgressRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let resources = self.resources.lock().await;
+        resources.ensure_leader()?;
+
+        let identity = executor_identity(&request)?;
+        let request = request.into_inner();
+        let task_id = Uuid::parse_str(&request.task_id).map_err(tonic_error)?;
+        resources.check_task_assignment(&task_id, &identity)?;
+        let progress = TaskProgress::new(request.percentage, request.state).map_err(tonic_error)?;
+        let ts = resources
+            .get_task_state(&task_id)
+            .await
+            .map_err(tonic_error)?;
+        // Only running tasks make progress.
+        let mut task: Task<Finish> = ts.try_into().map_err(tonic_error)?;
+        task.update_progress(progress);
+
+        let ts = TaskState::from(task);
+        resources.put_task_into_db(&ts).await.map_err(tonic_error)?;
+        Ok(Response::new(()))
+    }
+
+    async fn handoff_task(
+        &self,
+        request: Request<HandoffTaskRequest>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let mut resources = self.resources.lock().await;
+        resources.ensure_leader()?;
+
+        let identity = executor_identity(&request)?;
+        let request = request.into_inner();
+        let executor_id = Uuid::parse_str(&request.executor_id).map_err(tonic_error)?;
+        let task_id = Uuid::parse_str(&request.task_id).map_err(tonic_error)?;
+        let reason = HandoffReason::from_i32(request.reason)
+            .ok_or_else(|| tonic_error("invalid handoff reason"))?;
+        resources.check_executor(executor_id, &identity)?;
+        resources.check_task_assignment(&task_id, &identity)?;
+        let assigned_to = resources
+            .tasks_assignment
+            .get(&task_id)
+            .map(|assignment| assignment.executor_id);
+        if assigned_to != Some(executor_id) {
+            return Err(SchedulerServiceError::ExecutorIdentityMismatch.into());
+        }
+        resources
+            .requeue_task(&executor_id, &task_id, reason)
+            .await
+            .map_err(tonic_error)?;
         Ok(Response::new(()))
     }
 }
@@ -21,5 +21,7 @@ use teaclave_service_app_utils::launch_teaclave_service;
 const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
 
 fn main() -> Result<()> {
+    // Use to import ocall
+    pub use teaclave_file_agent::ocall_handle_file_request;
     launch_teaclave_service(PACKAGE_NAME)
 }
@@ -26,6 +26,18 @@ pub(crate) enum StorageServiceError {
     Database(#[from] rusty_leveldb::Status),
     #[error("service internal error")]
     Service(#[from] anyhow::Error),
+    #[error("storage is frozen for decommissioning")]
+    Frozen,
+    #[error("storage has not applied the write of the consistency token")]
+    StaleRead,
+    #[error("storage follows a primary and only serves reads")]
+    Follower,
+    #[error("storage does not follow a primary")]
+    NotFollower,
+    #[error("storage has no follower to replicate to")]
+    NotPrimary,
+    #[error("change log entries are out of order or of another primary")]
+    ChangeLogGap,
 }
 
 impl From<StorageServiceError> for teaclave_rpc::Status {
@@ -34,6 +46,13 @@ impl From<StorageServiceError> for teaclave_rpc::Status {
         let msg = error.to_string();
         let code = match error {
             StorageServiceError::Service(_) => Code::Internal,
+            StorageServiceError::Frozen => Code::Unavailable,
+            StorageServiceError::StaleRead => Code::Unavailable,
+            StorageServiceError::Follower => Code::FailedPrecondition,
+            StorageServiceError::NotFollower => Code::FailedPrecondition,
+            StorageServiceError::NotPrimary => Code::FailedPrecondition,
+            StorageServiceError::ChangeLogGap => Code::OutOfRange,
+            StorageServiceError::None => Code::NotFound,
             _ => Code::Unknown,
         };
         Status::new(code, msg)
@@ -34,21 +34,29 @@ use teaclave_binder::proto::{
 use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::{AS_ROOT_CA_CERT, AUDITOR_PUBLIC_KEYS, STORAGE_INBOUND_SERVICES};
 use teaclave_config::RuntimeConfig;
-use teaclave_proto::teaclave_storage_service::TeaclaveStorageServer;
+use teaclave_proto::teaclave_storage_service::{TeaclaveStorageClient, TeaclaveStorageServer};
 use teaclave_rpc::config::SgxTrustedTlsServerConfig;
-use teaclave_service_enclave_utils::ServiceEnclave;
+use teaclave_service_enclave_utils::{
+    create_trusted_storage_endpoint, drain_signal, drained, ServiceEnclave,
+};
 use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
 
 mod error;
+mod file_handler;
 mod proxy;
+mod replication;
 mod service;
+mod snapshot;
 
 async fn start_service(config: &RuntimeConfig) -> Result<()> {
     info!("Starting Storage...");
 
     let listen_address = config.internal_endpoints.storage.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(config)?;
+    let verification_policy = verifier::VerificationPolicy::from_teaclave_config(config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
+        .endorsement_cache(config.attestation.endorsement_cache_path("storage"))
+        .reuse_cached_endorsement(config.attestation.reuse_cached_endorsement)
         .generate_and_endorse()?
         .attested_tls_config()
         .ok_or_else(|| anyhow!("cannot get attested TLS config"))?;
@@ -73,10 +81,32 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
                 accepted_enclave_attrs,
                 AS_ROOT_CA_CERT,
                 verifier::universal_quote_verifier,
+                verification_policy.clone(),
             )?
+            .mutual_attestation(true)
+            .min_protocol_version(config.rpc.min_protocol_version)
+            .session_freshness(std::time::Duration::from_secs(
+                teaclave_config::build::GRPC_CONFIG.session_freshness_secs,
+            ))
             .into();
     info!(" Starting Storage: Server config setup finished ...");
 
+    let replication = config.storage_replication.clone();
+    let follower_endpoint = match &replication {
+        Some(replication) if !replication.follower => Some(create_trusted_storage_endpoint(
+            &replication.follower_address,
+            &enclave_info,
+            AS_ROOT_CA_CERT,
+            verifier::universal_quote_verifier,
+            verification_policy,
+            attested_tls_config.clone(),
+        )?),
+        _ => None,
+    };
+    let is_primary = follower_endpoint.is_some();
+    let is_follower = matches!(&replication, Some(replication) if replication.follower);
+
+    let fusion_base = config.mount.fusion_base_dir.clone();
     let (sender, receiver) = unbounded_channel();
     let storage_handle = thread::spawn(move || {
         info!(" Starting Storage: opening database ...");
@@ -85,23 +115,52 @@ async fn start_service(config: &RuntimeConfig) -> Result<()> {
         #[cfg(not(test_mode))]
         let db = create_teaclave_db();
 
-        let mut storage_service = service::TeaclaveStorageService::new(RefCell::new(db), receiver);
+        let database = if is_primary {
+            replication::Database::with_change_log(db)
+        } else {
+            replication::Database::new(db)
+        };
+        let mut storage_service =
+            service::TeaclaveStorageService::new(RefCell::new(database), receiver, fusion_base);
+        if is_follower {
+            info!(" Starting Storage: following the primary ...");
+            storage_service = storage_service.follower();
+        }
 
         info!(" Starting Storage: database loaded ...");
         storage_service.start();
     });
 
     let service = proxy::ProxyService::new(sender);
+    let shipper = match (follower_endpoint, replication) {
+        (Some(endpoint), Some(replication)) => {
+            let follower = TeaclaveStorageClient::new_with_builtin_config(endpoint.connect_lazy());
+            Some(tokio::spawn(replication::ship_change_log(
+                service.clone(),
+                follower,
+                std::time::Duration::from_millis(replication.ship_interval_ms),
+            )))
+        }
+        _ => None,
+    };
 
     info!(" Starting Storage: start listening ...");
 
-    teaclave_rpc::transport::Server::builder()
+    let served = teaclave_rpc::transport::Server::builder()
         .tls_config(server_config)
         .map_err(|_| anyhow::anyhow!("TeaclaveFrontendServer tls config error"))?
         .add_service(TeaclaveStorageServer::new_with_builtin_config(service))
-        .serve(listen_address)
-        .await?;
+        .serve_with_shutdown(listen_address, drain_signal())
+        .await;
+    // The storage thread stops after the requests sent by the proxy service,
+    // which is dropped with the server and the shipper.
+    if let Some(shipper) = shipper {
+        shipper.abort();
+        let _ = shipper.await;
+    }
     storage_handle.join().unwrap();
+    drained();
+    served?;
     Ok(())
 }
 
@@ -154,6 +213,7 @@ fn handle_init_enclave(_: &InitEnclaveInput) -> TeeServiceResult<InitEnclaveOutp
 
 #[handle_ecall]
 fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<FinalizeEnclaveOutput> {
+    ServiceEnclave::drain();
     ServiceEnclave::finalize()?;
     Ok(FinalizeEnclaveOutput)
 }
@@ -175,10 +235,21 @@ pub mod tests {
             service::tests::test_get_key,
             service::tests::test_put_key,
             service::tests::test_delete_key,
+            service::tests::test_write_batch,
             service::tests::test_empty_value,
             service::tests::test_enqueue,
             service::tests::test_dequeue,
+            service::tests::test_get_queue_length,
             service::tests::test_get_keys_by_prefix,
+            service::tests::test_list_entries,
+            service::tests::test_put_key_with_expiry,
+            service::tests::test_freeze,
+            service::tests::test_export_import_snapshot,
+            service::tests::test_create_restore_snapshot,
+            service::tests::test_consistency_token,
+            service::tests::test_compare_and_swap,
+            service::tests::test_follower,
+            replication::tests::test_change_log,
         )
     }
 }
@@ -45,27 +45,59 @@ macro_rules! send_request {
             .map_err(|_| StorageServiceError::Service(anyhow!("send ProxyRequest error")))?;
         match receiver.recv().await {
             Some(Ok(TeaclaveStorageResponse::$response(re))) => return Ok(Response::new(re)),
+            Some(Err(e)) => return Err(e.into()),
             _ => return Err(teaclave_rpc::Status::internal("invalid response")),
         }
     }};
 }
 
+impl ProxyService {
+    /// Read the change log for the shipper, which is not served over RPC.
+    pub(crate) async fn read_change_log(
+        &self,
+        request: ReadChangeLogRequest,
+    ) -> Result<Response<ReadChangeLogResponse>, Status> {
+        let request = Request::new(request);
+        send_request!(self, request, ReadChangeLog, ReadChangeLog)
+    }
+}
+
 #[teaclave_rpc::async_trait]
 impl TeaclaveStorage for ProxyService {
     async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
         send_request!(self, request, Get, Get)
     }
 
-    async fn put(&self, request: Request<PutRequest>) -> Result<Response<()>, Status> {
-        send_request!(self, request, Put, Empty)
+    async fn put(&self, request: Request<PutRequest>) -> Result<Response<WriteResponse>, Status> {
+        send_request!(self, request, Put, Write)
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<WriteResponse>, Status> {
+        send_request!(self, request, Delete, Write)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        request: Request<CompareAndSwapRequest>,
+    ) -> Result<Response<CompareAndSwapResponse>, Status> {
+        send_request!(self, request, CompareAndSwap, CompareAndSwap)
     }
 
-    async fn delete(&self, request: Request<DeleteRequest>) -> Result<Response<()>, Status> {
-        send_request!(self, request, Delete, Empty)
+    async fn write_batch(
+        &self,
+        request: Request<WriteBatchRequest>,
+    ) -> Result<Response<WriteResponse>, Status> {
+        send_request!(self, request, WriteBatch, Write)
     }
 
-    async fn enqueue(&self, request: Request<EnqueueRequest>) -> Result<Response<()>, Status> {
-        send_request!(self, request, Enqueue, Empty)
+    async fn enqueue(
+        &self,
+        request: Request<EnqueueRequest>,
+    ) -> Result<Response<WriteResponse>, Status> {
+        send_request!(self, request, Enqueue, Write)
     }
 
     async fn dequeue(
@@ -75,12 +107,72 @@ impl TeaclaveStorage for ProxyService {
         send_request!(self, request, Dequeue, Dequeue)
     }
 
+    async fn get_queue_length(
+        &self,
+        request: Request<GetQueueLengthRequest>,
+    ) -> Result<Response<GetQueueLengthResponse>, Status> {
+        send_request!(self, request, GetQueueLength, GetQueueLength)
+    }
+
     async fn get_keys_by_prefix(
         &self,
         request: Request<GetKeysByPrefixRequest>,
     ) -> Result<Response<GetKeysByPrefixResponse>, Status> {
         send_request!(self, request, GetKeysByPrefix, GetKeysByPrefix)
     }
+
+    async fn list_entries(
+        &self,
+        request: Request<ListEntriesRequest>,
+    ) -> Result<Response<ListEntriesResponse>, Status> {
+        send_request!(self, request, ListEntries, ListEntries)
+    }
+
+    async fn freeze(&self, request: Request<FreezeRequest>) -> Result<Response<()>, Status> {
+        send_request!(self, request, Freeze, Empty)
+    }
+
+    async fn export_snapshot(
+        &self,
+        request: Request<ExportSnapshotRequest>,
+    ) -> Result<Response<ExportSnapshotResponse>, Status> {
+        send_request!(self, request, ExportSnapshot, ExportSnapshot)
+    }
+
+    async fn import_snapshot(
+        &self,
+        request: Request<ImportSnapshotRequest>,
+    ) -> Result<Response<()>, Status> {
+        send_request!(self, request, ImportSnapshot, Empty)
+    }
+
+    async fn create_snapshot(
+        &self,
+        request: Request<CreateSnapshotRequest>,
+    ) -> Result<Response<CreateSnapshotResponse>, Status> {
+        send_request!(self, request, CreateSnapshot, CreateSnapshot)
+    }
+
+    async fn restore_snapshot(
+        &self,
+        request: Request<RestoreSnapshotRequest>,
+    ) -> Result<Response<RestoreSnapshotResponse>, Status> {
+        send_request!(self, request, RestoreSnapshot, RestoreSnapshot)
+    }
+
+    async fn apply_change_log(
+        &self,
+        request: Request<ApplyChangeLogRequest>,
+    ) -> Result<Response<ApplyChangeLogResponse>, Status> {
+        send_request!(self, request, ApplyChangeLog, ApplyChangeLog)
+    }
+
+    async fn promote_replica(
+        &self,
+        request: Request<PromoteReplicaRequest>,
+    ) -> Result<Response<PromoteReplicaResponse>, Status> {
+        send_request!(self, request, PromoteReplica, PromoteReplica)
+    }
 }
 
 pub(crate) struct ProxyRequest {
@@ -0,0 +1,338 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Asynchronous replication of a primary store to a follower. The primary
+//! records the writes flushed together as an entry of an ordered change log,
+//! which a shipper sends to the follower over attested TLS, so the log never
+//! leaves the enclaves in the clear. The follower applies the entries in
+//! order and only serves reads until it is promoted.
+
+use crate::error::StorageServiceError;
+use crate::proxy::ProxyService;
+use rusty_leveldb::{LdbIterator, WriteBatch, DB};
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+use teaclave_proto::teaclave_storage_service::*;
+use teaclave_rpc::transport::Channel;
+use teaclave_rpc::Code;
+use teaclave_service_enclave_utils::bail;
+
+// Entries kept for a follower which has not applied them. A follower falling
+// further behind is sent a reset entry instead.
+const MAX_LOG_ENTRIES: usize = 4096;
+// Entries shipped to the follower at a time
+const MAX_SHIPPED_ENTRIES: usize = 64;
+
+struct ChangeLog {
+    log_id: Vec<u8>,
+    // Writes applied since the last flush, which make the next entry
+    pending: Vec<Mutation>,
+    entries: VecDeque<ChangeLogEntry>,
+    // Index of the last entry sealed
+    last_index: u64,
+}
+
+/// The database of a store, recording the writes to it in a change log if
+/// the store is the primary of a follower. The writes are only recorded
+/// through `put`, `delete` and `write` of this type, so the helpers of the
+/// service open it rather than the inner database.
+pub(crate) struct Database {
+    db: DB,
+    log: Option<ChangeLog>,
+}
+
+impl Deref for Database {
+    type Target = DB;
+
+    fn deref(&self) -> &DB {
+        &self.db
+    }
+}
+
+impl DerefMut for Database {
+    fn deref_mut(&mut self) -> &mut DB {
+        &mut self.db
+    }
+}
+
+impl Database {
+    pub(crate) fn new(db: DB) -> Self {
+        Self { db, log: None }
+    }
+
+    /// Record the writes from now on, for a follower.
+    pub(crate) fn with_change_log(db: DB) -> Self {
+        let log = ChangeLog {
+            log_id: uuid::Uuid::new_v4().as_bytes().to_vec(),
+            pending: Vec::new(),
+            entries: VecDeque::new(),
+            last_index: 0,
+        };
+        Self { db, log: Some(log) }
+    }
+
+    fn record(&mut self, mutation: Mutation) {
+        if let Some(log) = &mut self.log {
+            log.pending.push(mutation);
+        }
+    }
+
+    pub(crate) fn put(&mut self, key: &[u8], value: &[u8]) -> rusty_leveldb::Result<()> {
+        self.db.put(key, value)?;
+        self.record(Mutation::put(key, value));
+        Ok(())
+    }
+
+    pub(crate) fn delete(&mut self, key: &[u8]) -> rusty_leveldb::Result<()> {
+        self.db.delete(key)?;
+        self.record(Mutation::delete(key));
+        Ok(())
+    }
+
+    pub(crate) fn write(&mut self, batch: WriteBatch, sync: bool) -> rusty_leveldb::Result<()> {
+        let mutations: Vec<Mutation> = match self.log {
+            Some(_) => batch
+                .iter()
+                .map(|(key, value)| match value {
+                    Some(value) => Mutation::put(key, value),
+                    None => Mutation::delete(key),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        self.db.write(batch, sync)?;
+        for mutation in mutations {
+            self.record(mutation);
+        }
+        Ok(())
+    }
+
+    /// Flush the database, sealing the writes since the last flush into an
+    /// entry of the change log.
+    pub(crate) fn flush(&mut self) -> rusty_leveldb::Result<()> {
+        self.db.flush()?;
+        if let Some(log) = &mut self.log {
+            if !log.pending.is_empty() {
+                log.last_index += 1;
+                let mutations = std::mem::take(&mut log.pending);
+                log.entries
+                    .push_back(ChangeLogEntry::new(log.last_index, mutations));
+                if log.entries.len() > MAX_LOG_ENTRIES {
+                    log.entries.pop_front();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The entries after `after`, dropping the ones up to it, or a reset
+    /// entry if the entries right after it have been dropped.
+    pub(crate) fn read_change_log(
+        &mut self,
+        request: ReadChangeLogRequest,
+    ) -> Result<ReadChangeLogResponse, StorageServiceError> {
+        self.flush()?;
+        let log = match &mut self.log {
+            Some(log) => log,
+            None => bail!(StorageServiceError::NotPrimary),
+        };
+        while matches!(log.entries.front(), Some(entry) if entry.index <= request.after) {
+            log.entries.pop_front();
+        }
+        let first_index = log.entries.front().map_or(log.last_index + 1, |e| e.index);
+        let in_order = request.after < first_index && first_index <= request.after + 1;
+        if in_order && !request.reset {
+            return Ok(ReadChangeLogResponse {
+                log_id: log.log_id.clone(),
+                entries: log
+                    .entries
+                    .iter()
+                    .take(MAX_SHIPPED_ENTRIES)
+                    .cloned()
+                    .collect(),
+            });
+        }
+
+        // All the entries up to the last one are in the reset entry.
+        let (log_id, last_index) = (log.log_id.clone(), log.last_index);
+        log.entries.clear();
+        let mut entries = Vec::new();
+        let mut it = self.db.new_iter()?;
+        while let Some((key, value)) = it.next() {
+            entries.push(KeyValue::new(key, value));
+        }
+        Ok(ReadChangeLogResponse {
+            log_id,
+            entries: vec![ChangeLogEntry::reset(last_index, entries)],
+        })
+    }
+}
+
+/// The change log a follower store has applied
+pub(crate) struct FollowerState {
+    log_id: Vec<u8>,
+    applied_index: u64,
+}
+
+impl FollowerState {
+    pub(crate) fn new() -> Self {
+        Self {
+            log_id: Vec::new(),
+            applied_index: 0,
+        }
+    }
+
+    pub(crate) fn applied_index(&self) -> u64 {
+        self.applied_index
+    }
+
+    /// Apply the entries in order, skipping the ones applied already. An
+    /// entry after a gap, or of another change log, is rejected until a reset
+    /// entry is shipped.
+    pub(crate) fn apply(
+        &mut self,
+        database: &mut Database,
+        request: ApplyChangeLogRequest,
+    ) -> Result<(), StorageServiceError> {
+        for entry in request.entries {
+            let mut batch = WriteBatch::new();
+            if entry.reset {
+                let mut it = database.new_iter()?;
+                while let Some((key, _)) = it.next() {
+                    batch.delete(&key);
+                }
+            } else if request.log_id != self.log_id || entry.index > self.applied_index + 1 {
+                bail!(StorageServiceError::ChangeLogGap)
+            } else if entry.index <= self.applied_index {
+                continue;
+            }
+            for mutation in &entry.mutations {
+                if mutation.delete {
+                    batch.delete(&mutation.key);
+                } else {
+                    batch.put(&mutation.key, &mutation.value);
+                }
+            }
+            database.write(batch, false)?;
+            database.flush()?;
+            self.log_id = request.log_id.clone();
+            self.applied_index = entry.index;
+        }
+        Ok(())
+    }
+}
+
+/// Ship the change log of the primary store to the follower every
+/// `interval`, until the storage service stops.
+pub(crate) async fn ship_change_log(
+    service: ProxyService,
+    mut follower: TeaclaveStorageClient<Channel>,
+    interval: Duration,
+) {
+    let mut applied_index = 0;
+    let mut reset = true;
+    loop {
+        tokio::time::sleep(interval).await;
+        let request = ReadChangeLogRequest {
+            after: applied_index,
+            reset,
+        };
+        let log = match service.read_change_log(request).await {
+            Ok(response) => response.into_inner(),
+            // The storage thread has stopped.
+            Err(_) => return,
+        };
+        if log.entries.is_empty() {
+            continue;
+        }
+        let request = ApplyChangeLogRequest::new(log.log_id, log.entries);
+        match follower.apply_change_log(request).await {
+            Ok(response) => {
+                applied_index = response.into_inner().applied_index;
+                reset = false;
+            }
+            Err(status) if status.code() == Code::OutOfRange => {
+                warn!("The follower store needs a reset: {}", status.message());
+                reset = true;
+            }
+            // The entries are shipped again in the next round.
+            Err(status) => warn!("Failed to ship the change log: {:?}", status),
+        }
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    fn open_db(name: &str) -> DB {
+        DB::open(name, rusty_leveldb::in_memory()).unwrap()
+    }
+
+    pub fn test_change_log() {
+        let mut primary = Database::with_change_log(open_db("replication_primary"));
+        primary.put(b"a", b"1").unwrap();
+        primary.put(b"b", b"2").unwrap();
+        primary.flush().unwrap();
+        let mut batch = WriteBatch::new();
+        batch.delete(b"a");
+        batch.put(b"c", b"3");
+        primary.write(batch, false).unwrap();
+        primary.flush().unwrap();
+
+        let log = primary
+            .read_change_log(ReadChangeLogRequest::new(0))
+            .unwrap();
+        assert_eq!(log.entries.len(), 2);
+        assert_eq!(log.entries[1].mutations[0], Mutation::delete(b"a".to_vec()));
+
+        // A fresh follower follows no log yet.
+        let mut database = Database::new(open_db("replication_follower"));
+        let mut follower = FollowerState::new();
+        let request = ApplyChangeLogRequest::new(log.log_id.clone(), log.entries.clone());
+        assert!(follower.apply(&mut database, request).is_err());
+
+        database.put(b"stale", b"0").unwrap();
+        let log = primary
+            .read_change_log(ReadChangeLogRequest::reset(0))
+            .unwrap();
+        assert!(log.entries[0].reset);
+        let request = ApplyChangeLogRequest::new(log.log_id.clone(), log.entries);
+        follower.apply(&mut database, request).unwrap();
+        assert_eq!(follower.applied_index(), 2);
+        assert_eq!(database.get(b"stale"), None);
+        assert_eq!(database.get(b"c"), Some(b"3".to_vec()));
+
+        primary.delete(b"b").unwrap();
+        primary.flush().unwrap();
+        let log = primary
+            .read_change_log(ReadChangeLogRequest::new(2))
+            .unwrap();
+        assert_eq!(log.entries[0].index, 3);
+        // Entries applied already are skipped.
+        let request = ApplyChangeLogRequest::new(log.log_id, log.entries.clone());
+        follower.apply(&mut database, request.clone()).unwrap();
+        follower.apply(&mut database, request).unwrap();
+        assert_eq!(follower.applied_index(), 3);
+        assert_eq!(database.get(b"b"), None);
+
+        // A restarted primary starts another log.
+        let other = ApplyChangeLogRequest::new(b"other".to_vec(), log.entries);
+        assert!(follower.apply(&mut database, other).is_err());
+    }
+}
@@ -17,25 +17,60 @@
 
 use crate::error::StorageServiceError;
 use crate::proxy::ProxyRequest;
+use crate::replication::{Database, FollowerState};
+use crate::snapshot;
 use anyhow::anyhow;
 use rusty_leveldb::LdbIterator;
-use rusty_leveldb::DB;
-use std::cell::RefCell;
+use rusty_leveldb::WriteBatch;
+use std::cell::{Cell, RefCell};
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+#[allow(unused_imports)]
+use std::untrusted::time::SystemTimeEx;
 use teaclave_proto::teaclave_storage_service::*;
 use teaclave_service_enclave_utils::bail;
+use teaclave_types::{FileAuthTag, FileCrypto};
 use tokio::sync::mpsc::UnboundedReceiver;
+use url::Url;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
 
 pub(crate) struct TeaclaveStorageService {
     // Current LevelDB implementation is not concurrent, so we need to wrap the
     // DB with RefCell. This service is running in a single thread, it's safe to
     // use RefCell.
-    database: RefCell<DB>,
+    database: RefCell<Database>,
     receiver: UnboundedReceiver<ProxyRequest>,
+    // Set once the node is being decommissioned. A frozen node only serves
+    // reads and snapshot exports, so the exported state stays final.
+    frozen: Cell<bool>,
+    // Set while the node follows a primary store. A follower only applies the
+    // change log of the primary and serves reads, until it is promoted.
+    follower: RefCell<Option<FollowerState>>,
+    // For snapshots created at or restored from fusion URLs
+    fusion_base: PathBuf,
 }
 
 impl TeaclaveStorageService {
-    pub(crate) fn new(database: RefCell<DB>, receiver: UnboundedReceiver<ProxyRequest>) -> Self {
-        Self { database, receiver }
+    pub(crate) fn new(
+        database: RefCell<Database>,
+        receiver: UnboundedReceiver<ProxyRequest>,
+        fusion_base: impl AsRef<Path>,
+    ) -> Self {
+        Self {
+            database,
+            receiver,
+            frozen: Cell::new(false),
+            follower: RefCell::new(None),
+            fusion_base: fusion_base.as_ref().to_owned(),
+        }
+    }
+
+    /// Follow a primary store instead of accepting writes.
+    pub(crate) fn follower(self) -> Self {
+        self.follower.replace(Some(FollowerState::new()));
+        self
     }
 }
 
@@ -44,7 +79,7 @@ impl TeaclaveStorageService {
 // queue-key-index: Vec<u8>; elements
 // Todo: what if there are errors when doing get_tail and get_head
 struct DBQueue<'a> {
-    database: &'a mut DB,
+    database: &'a mut Database,
     key: &'a [u8],
 }
 
@@ -92,7 +127,7 @@ impl<'a> DBQueue<'a> {
         Some(u32::from_le_bytes(bytes))
     }
 
-    pub fn open(database: &'a mut DB, key: &'a [u8]) -> Self {
+    pub fn open(database: &'a mut Database, key: &'a [u8]) -> Self {
         DBQueue { database, key }
     }
 
@@ -138,7 +173,6 @@ impl<'a> DBQueue<'a> {
         }
     }
 
-    #[allow(unused)]
     pub fn len(&mut self) -> u32 {
         let head_index = self.get_head();
         let tail_index = self.get_tail();
@@ -151,17 +185,210 @@ impl<'a> DBQueue<'a> {
     }
 }
 
+// ttl-key: u64; expiry of the key in seconds since the Unix epoch
+// ttl_index-expiry-key: empty; ordered by expiry for sweeping, expiry is in
+// fixed-width hex and entries outdated by a later put are skipped
+struct DBExpiry<'a> {
+    database: &'a mut Database,
+}
+
+impl<'a> DBExpiry<'a> {
+    fn get_ttl_key(key: &[u8]) -> Vec<u8> {
+        let mut ttl_key = b"ttl-".to_vec();
+        ttl_key.extend_from_slice(key);
+        ttl_key
+    }
+
+    fn get_index_key(key: &[u8], expires_at: u64) -> Vec<u8> {
+        let mut index_key = format!("ttl_index-{:016x}-", expires_at).into_bytes();
+        index_key.extend_from_slice(key);
+        index_key
+    }
+
+    pub fn open(database: &'a mut Database) -> Self {
+        DBExpiry { database }
+    }
+
+    pub fn expires_at(&mut self, key: &[u8]) -> Option<u64> {
+        let bytes = self.database.get(&Self::get_ttl_key(key))?;
+        let bytes: [u8; 8] = bytes.as_slice().try_into().ok()?;
+        Some(u64::from_le_bytes(bytes))
+    }
+
+    pub fn is_expired(&mut self, key: &[u8], now: u64) -> bool {
+        matches!(self.expires_at(key), Some(expires_at) if expires_at <= now)
+    }
+
+    pub fn set(&mut self, key: &[u8], expires_at: u64) -> Result<(), StorageServiceError> {
+        self.database
+            .put(&Self::get_ttl_key(key), &expires_at.to_le_bytes())?;
+        self.database
+            .put(&Self::get_index_key(key, expires_at), b"")?;
+        Ok(())
+    }
+
+    pub fn clear(&mut self, key: &[u8]) -> Result<(), StorageServiceError> {
+        let ttl_key = Self::get_ttl_key(key);
+        if self.database.get(&ttl_key).is_some() {
+            self.database.delete(&ttl_key)?;
+        }
+        Ok(())
+    }
+
+    // Remove the keys expired by now, returning the number of them.
+    pub fn sweep(&mut self, now: u64) -> Result<usize, StorageServiceError> {
+        let first = b"ttl_index-".to_vec();
+        let last = format!("ttl_index-{:016x}.", now).into_bytes();
+        let mut it = self.database.new_iter()?;
+        it.seek(&first);
+
+        let mut index_keys = Vec::new();
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+        if it.valid() && it.current(&mut key, &mut value) {
+            let mut next = Some(key);
+            while let Some(k) = next {
+                if k >= last || !k.starts_with(&first) {
+                    break;
+                }
+                index_keys.push(k);
+                next = it.next().map(|(k, _)| k);
+            }
+        }
+
+        let mut swept = 0;
+        for index_key in index_keys {
+            // ttl_index- is followed by 16 hex digits and a '-'
+            let expiry_end = first.len() + 16;
+            if index_key.len() > expiry_end {
+                let expires_at = std::str::from_utf8(&index_key[first.len()..expiry_end])
+                    .ok()
+                    .and_then(|hex| u64::from_str_radix(hex, 16).ok());
+                let key = &index_key[expiry_end + 1..];
+                if expires_at.is_some() && self.expires_at(key) == expires_at {
+                    self.database.delete(key)?;
+                    self.database.delete(&Self::get_ttl_key(key))?;
+                    swept += 1;
+                }
+            }
+            self.database.delete(&index_key)?;
+        }
+        self.database.flush()?;
+
+        Ok(swept)
+    }
+}
+
+// consistency-store_id: [u8; 16]; random ID of the store, created on the first
+// write and kept in snapshots, so that tokens stay valid on a replacement
+// consistency-sequence: u64; number of writes applied
+struct DBConsistency<'a> {
+    database: &'a mut Database,
+}
+
+impl<'a> DBConsistency<'a> {
+    const STORE_ID_KEY: &'static [u8] = b"consistency-store_id";
+    const SEQUENCE_KEY: &'static [u8] = b"consistency-sequence";
+
+    pub fn open(database: &'a mut Database) -> Self {
+        DBConsistency { database }
+    }
+
+    fn sequence(&mut self) -> u64 {
+        self.database
+            .get(Self::SEQUENCE_KEY)
+            .and_then(|bytes| bytes.as_slice().try_into().ok())
+            .map(u64::from_le_bytes)
+            .unwrap_or(0)
+    }
+
+    // Count a write applied to the store, returning its token. The caller
+    // flushes the database along with the write.
+    pub fn record_write(&mut self) -> Result<ConsistencyToken, StorageServiceError> {
+        let store_id = match self.database.get(Self::STORE_ID_KEY) {
+            Some(store_id) => store_id,
+            None => {
+                let store_id = uuid::Uuid::new_v4().as_bytes().to_vec();
+                self.database.put(Self::STORE_ID_KEY, &store_id)?;
+                store_id
+            }
+        };
+        let sequence = self.sequence() + 1;
+        self.database
+            .put(Self::SEQUENCE_KEY, &sequence.to_le_bytes())?;
+        Ok(ConsistencyToken::new(store_id, sequence))
+    }
+
+    // Reads without a token are always served.
+    pub fn ensure_applied(
+        &mut self,
+        token: &Option<ConsistencyToken>,
+    ) -> Result<(), StorageServiceError> {
+        let token = match token {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        let store_id = self.database.get(Self::STORE_ID_KEY).unwrap_or_default();
+        if token.store_id != store_id || token.sequence > self.sequence() {
+            bail!(StorageServiceError::StaleRead)
+        }
+        Ok(())
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 impl TeaclaveStorageService {
     pub(crate) fn start(&mut self) {
-        while let Some(request) = self.receiver.blocking_recv() {
-            let database_request = request.request;
-            let sender = request.sender;
-            let response = self.dispatch(database_request);
-
-            match sender.send(response) {
-                Ok(_) => (),
-                Err(e) => error!("mpsc send error: {}", e),
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                error!("Failed to build the storage runtime: {}", e);
+                return;
+            }
+        };
+
+        runtime.block_on(async {
+            // Expired keys are removed in the same thread serving requests,
+            // so the database is never accessed concurrently.
+            let mut sweep = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                tokio::select! {
+                    request = self.receiver.recv() => {
+                        let request = match request {
+                            Some(request) => request,
+                            None => break,
+                        };
+                        let response = self.dispatch(request.request);
+                        if let Err(e) = request.sender.send(response) {
+                            error!("mpsc send error: {}", e);
+                        }
+                    }
+                    _ = sweep.tick() => self.sweep_expired(now_secs()),
+                }
             }
+        });
+    }
+
+    fn sweep_expired(&self, now: u64) {
+        // A frozen node keeps its state final for the snapshot export, and a
+        // follower removes the keys as the primary does.
+        if self.frozen.get() || self.follower.borrow().is_some() {
+            return;
+        }
+        let mut db = self.database.borrow_mut();
+        match DBExpiry::open(&mut db).sweep(now) {
+            Ok(0) => (),
+            Ok(swept) => info!("Removed {} expired keys", swept),
+            Err(e) => error!("Failed to remove expired keys: {:?}", e),
         }
     }
 
@@ -171,90 +398,247 @@ impl TeaclaveStorageService {
     ) -> std::result::Result<TeaclaveStorageResponse, StorageServiceError> {
         match request.into_inner() {
             TeaclaveStorageRequest::Get(r) => self.get(r).map(TeaclaveStorageResponse::Get),
-            TeaclaveStorageRequest::Put(r) => self.put(r).map(TeaclaveStorageResponse::Empty),
-            TeaclaveStorageRequest::Delete(r) => self.delete(r).map(TeaclaveStorageResponse::Empty),
+            TeaclaveStorageRequest::Put(r) => self.put(r).map(TeaclaveStorageResponse::Write),
+            TeaclaveStorageRequest::Delete(r) => self.delete(r).map(TeaclaveStorageResponse::Write),
+            TeaclaveStorageRequest::CompareAndSwap(r) => self
+                .compare_and_swap(r)
+                .map(TeaclaveStorageResponse::CompareAndSwap),
+            TeaclaveStorageRequest::WriteBatch(r) => {
+                self.write_batch(r).map(TeaclaveStorageResponse::Write)
+            }
             TeaclaveStorageRequest::Enqueue(r) => {
-                self.enqueue(r).map(TeaclaveStorageResponse::Empty)
+                self.enqueue(r).map(TeaclaveStorageResponse::Write)
             }
             TeaclaveStorageRequest::Dequeue(r) => {
                 self.dequeue(r).map(TeaclaveStorageResponse::Dequeue)
             }
+            TeaclaveStorageRequest::GetQueueLength(r) => self
+                .get_queue_length(r)
+                .map(TeaclaveStorageResponse::GetQueueLength),
             TeaclaveStorageRequest::GetKeysByPrefix(r) => self
                 .get_keys_by_prefix(r)
                 .map(TeaclaveStorageResponse::GetKeysByPrefix),
+            TeaclaveStorageRequest::ListEntries(r) => self
+                .list_entries(r)
+                .map(TeaclaveStorageResponse::ListEntries),
+            TeaclaveStorageRequest::Freeze(r) => self.freeze(r).map(TeaclaveStorageResponse::Empty),
+            TeaclaveStorageRequest::ExportSnapshot(r) => self
+                .export_snapshot(r)
+                .map(TeaclaveStorageResponse::ExportSnapshot),
+            TeaclaveStorageRequest::ImportSnapshot(r) => {
+                self.import_snapshot(r).map(TeaclaveStorageResponse::Empty)
+            }
+            TeaclaveStorageRequest::CreateSnapshot(r) => self
+                .create_snapshot(r)
+                .map(TeaclaveStorageResponse::CreateSnapshot),
+            TeaclaveStorageRequest::RestoreSnapshot(r) => self
+                .restore_snapshot(r)
+                .map(TeaclaveStorageResponse::RestoreSnapshot),
+            TeaclaveStorageRequest::ApplyChangeLog(r) => self
+                .apply_change_log(r)
+                .map(TeaclaveStorageResponse::ApplyChangeLog),
+            TeaclaveStorageRequest::PromoteReplica(r) => self
+                .promote_replica(r)
+                .map(TeaclaveStorageResponse::PromoteReplica),
+            TeaclaveStorageRequest::ReadChangeLog(r) => self
+                .database
+                .borrow_mut()
+                .read_change_log(r)
+                .map(TeaclaveStorageResponse::ReadChangeLog),
         }
     }
+
+    fn ensure_writable(&self) -> std::result::Result<(), StorageServiceError> {
+        if self.frozen.get() {
+            bail!(StorageServiceError::Frozen)
+        }
+        if self.follower.borrow().is_some() {
+            bail!(StorageServiceError::Follower)
+        }
+        Ok(())
+    }
 }
 
 impl TeaclaveStorageService {
     fn get(&self, request: GetRequest) -> std::result::Result<GetResponse, StorageServiceError> {
-        match self.database.borrow_mut().get(&request.key) {
+        let mut db = self.database.borrow_mut();
+        DBConsistency::open(&mut db).ensure_applied(&request.consistency_token)?;
+        // Keys expired since the last sweep are already gone for readers.
+        if DBExpiry::open(&mut db).is_expired(&request.key, now_secs()) {
+            bail!(StorageServiceError::None)
+        }
+        match db.get(&request.key) {
             Some(value) => Ok(GetResponse { value }),
             None => bail!(StorageServiceError::None),
         }
     }
 
-    fn put(&self, request: PutRequest) -> std::result::Result<(), StorageServiceError> {
+    fn put(&self, request: PutRequest) -> std::result::Result<WriteResponse, StorageServiceError> {
+        self.ensure_writable()?;
         self.database
             .borrow_mut()
             .put(&request.key, &request.value)
             .map_err(StorageServiceError::Database)?;
 
+        let mut db = self.database.borrow_mut();
+        let mut expiry = DBExpiry::open(&mut db);
+        match request.expires_at {
+            0 => expiry.clear(&request.key)?,
+            expires_at => expiry.set(&request.key, expires_at)?,
+        }
+        let token = DBConsistency::open(&mut db).record_write()?;
+        drop(db);
+
         self.database
             .borrow_mut()
             .flush()
             .map_err(StorageServiceError::Database)?;
-        Ok(())
+        Ok(WriteResponse::new(token))
     }
 
-    fn delete(&self, request: DeleteRequest) -> std::result::Result<(), StorageServiceError> {
+    fn compare_and_swap(
+        &self,
+        request: CompareAndSwapRequest,
+    ) -> std::result::Result<CompareAndSwapResponse, StorageServiceError> {
+        self.ensure_writable()?;
+        let mut db = self.database.borrow_mut();
+        // Keys expired since the last sweep are already absent.
+        let current = if DBExpiry::open(&mut db).is_expired(&request.key, now_secs()) {
+            None
+        } else {
+            db.get(&request.key)
+        };
+        let matched = match &current {
+            Some(current) => !request.expect_absent && current == &request.expected,
+            None => request.expect_absent,
+        };
+        if !matched {
+            return Ok(CompareAndSwapResponse::mismatched(current));
+        }
+
+        db.put(&request.key, &request.value)
+            .map_err(StorageServiceError::Database)?;
+        let mut expiry = DBExpiry::open(&mut db);
+        match request.expires_at {
+            0 => expiry.clear(&request.key)?,
+            expires_at => expiry.set(&request.key, expires_at)?,
+        }
+        let token = DBConsistency::open(&mut db).record_write()?;
+        db.flush().map_err(StorageServiceError::Database)?;
+        Ok(CompareAndSwapResponse::swapped(token))
+    }
+
+    fn delete(
+        &self,
+        request: DeleteRequest,
+    ) -> std::result::Result<WriteResponse, StorageServiceError> {
+        self.ensure_writable()?;
         self.database
             .borrow_mut()
             .delete(&request.key)
             .map_err(StorageServiceError::Database)?;
+        DBExpiry::open(&mut self.database.borrow_mut()).clear(&request.key)?;
+        let token = DBConsistency::open(&mut self.database.borrow_mut()).record_write()?;
 
         self.database
             .borrow_mut()
             .flush()
             .map_err(StorageServiceError::Database)?;
-        Ok(())
+        Ok(WriteResponse::new(token))
+    }
+
+    fn write_batch(
+        &self,
+        request: WriteBatchRequest,
+    ) -> std::result::Result<WriteResponse, StorageServiceError> {
+        self.ensure_writable()?;
+        // The expiries of the keys are cleared in the same batch, which leaves
+        // their stale entries in the expiry index to be skipped by the sweep.
+        let mut batch = WriteBatch::new();
+        for key in &request.deletes {
+            batch.delete(key);
+            batch.delete(&DBExpiry::get_ttl_key(key));
+        }
+        for entry in &request.puts {
+            batch.put(&entry.key, &entry.value);
+            batch.delete(&DBExpiry::get_ttl_key(&entry.key));
+        }
+
+        let mut db = self.database.borrow_mut();
+        db.write(batch, false)
+            .map_err(StorageServiceError::Database)?;
+        let token = DBConsistency::open(&mut db).record_write()?;
+        db.flush().map_err(StorageServiceError::Database)?;
+        Ok(WriteResponse::new(token))
     }
 
-    fn enqueue(&self, request: EnqueueRequest) -> std::result::Result<(), StorageServiceError> {
+    fn enqueue(
+        &self,
+        request: EnqueueRequest,
+    ) -> std::result::Result<WriteResponse, StorageServiceError> {
+        self.ensure_writable()?;
         let mut db = self.database.borrow_mut();
         let mut queue = DBQueue::open(&mut db, &request.key);
-        match queue.enqueue(&request.value) {
-            Ok(_) => Ok(()),
-            Err(e) => bail!(e),
+        if let Err(e) = queue.enqueue(&request.value) {
+            bail!(e)
         }
+        let token = DBConsistency::open(&mut db).record_write()?;
+        db.flush().map_err(StorageServiceError::Database)?;
+        Ok(WriteResponse::new(token))
     }
 
     fn dequeue(
         &self,
         request: DequeueRequest,
     ) -> std::result::Result<DequeueResponse, StorageServiceError> {
+        self.ensure_writable()?;
         let mut db = self.database.borrow_mut();
         let mut queue = DBQueue::open(&mut db, &request.key);
-        match queue.dequeue() {
-            Ok(value) => Ok(DequeueResponse { value }),
+        let value = match queue.dequeue() {
+            Ok(value) => value,
             Err(e) => bail!(e),
-        }
+        };
+        let token = DBConsistency::open(&mut db).record_write()?;
+        db.flush().map_err(StorageServiceError::Database)?;
+        Ok(DequeueResponse::new(value, token))
+    }
+
+    fn get_queue_length(
+        &self,
+        request: GetQueueLengthRequest,
+    ) -> std::result::Result<GetQueueLengthResponse, StorageServiceError> {
+        let mut db = self.database.borrow_mut();
+        DBConsistency::open(&mut db).ensure_applied(&request.consistency_token)?;
+        let mut queue = DBQueue::open(&mut db, &request.key);
+        Ok(GetQueueLengthResponse::new(queue.len()))
     }
 
     fn get_keys_by_prefix(
         &self,
         request: GetKeysByPrefixRequest,
     ) -> std::result::Result<GetKeysByPrefixResponse, StorageServiceError> {
-        let prefix = request.prefix;
         let mut db = self.database.borrow_mut();
+        DBConsistency::open(&mut db).ensure_applied(&request.consistency_token)?;
+        let prefix = request.prefix;
         let mut it = db.new_iter().map_err(StorageServiceError::Database)?;
 
         let mut first_prefix = prefix.clone();
         first_prefix.push(b'-');
         let mut last_prefix = prefix;
         last_prefix.push(b'.');
+        let limit = match request.limit as usize {
+            0 => usize::MAX,
+            limit => limit,
+        };
 
-        it.seek(&first_prefix[..]);
+        // Keys are ordered, so a scan resumed after a key skips the keys
+        // returned before and sees the ones inserted after them.
+        let start_after = request.start_after;
+        if start_after > first_prefix {
+            it.seek(&start_after[..]);
+        } else {
+            it.seek(&first_prefix[..]);
+        }
         if !it.valid() {
             return Ok(GetKeysByPrefixResponse::default());
         }
@@ -264,22 +648,189 @@ impl TeaclaveStorageService {
         if !it.current(&mut key, &mut value) {
             return Ok(GetKeysByPrefixResponse::default());
         }
-        keys.push(key);
 
-        while let Some((k, _)) = it.next() {
-            if k >= last_prefix {
+        let now = now_secs();
+        let mut next = Some(key);
+        while let Some(k) = next {
+            if k >= last_prefix || keys.len() >= limit {
                 break;
             }
-            keys.push(k);
+            if k > start_after && !DBExpiry::open(&mut db).is_expired(&k, now) {
+                keys.push(k);
+            }
+            next = it.next().map(|(k, _)| k);
         }
 
         Ok(GetKeysByPrefixResponse { keys })
     }
+
+    fn list_entries(
+        &self,
+        request: ListEntriesRequest,
+    ) -> std::result::Result<ListEntriesResponse, StorageServiceError> {
+        let mut db = self.database.borrow_mut();
+        DBConsistency::open(&mut db).ensure_applied(&request.consistency_token)?;
+        let mut it = db.new_iter().map_err(StorageServiceError::Database)?;
+        let limit = match request.limit as usize {
+            0 => usize::MAX,
+            limit => limit,
+        };
+        let max_value_bytes = match request.max_value_bytes {
+            0 => None,
+            max => Some(max),
+        };
+
+        it.seek(std::cmp::max(&request.prefix, &request.start_after));
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+        if !it.valid() || !it.current(&mut key, &mut value) {
+            return Ok(ListEntriesResponse::default());
+        }
+
+        let now = now_secs();
+        let mut entries = Vec::new();
+        let mut value_bytes = 0u64;
+        let mut next = Some((key, value));
+        while let Some((k, v)) = next {
+            if !k.starts_with(&request.prefix)
+                || entries.len() >= limit
+                || max_value_bytes.map_or(false, |max| value_bytes >= max)
+            {
+                break;
+            }
+            if k > request.start_after && !DBExpiry::open(&mut db).is_expired(&k, now) {
+                let size = v.len() as u64;
+                let value = match max_value_bytes {
+                    Some(_) => {
+                        value_bytes += size;
+                        v
+                    }
+                    None => Vec::new(),
+                };
+                entries.push(EntryInfo {
+                    key: k,
+                    size,
+                    value,
+                });
+            }
+            next = it.next();
+        }
+
+        Ok(ListEntriesResponse { entries })
+    }
+
+    fn freeze(&self, _request: FreezeRequest) -> std::result::Result<(), StorageServiceError> {
+        self.database
+            .borrow_mut()
+            .flush()
+            .map_err(StorageServiceError::Database)?;
+        self.frozen.set(true);
+        Ok(())
+    }
+
+    fn export_snapshot(
+        &self,
+        _request: ExportSnapshotRequest,
+    ) -> std::result::Result<ExportSnapshotResponse, StorageServiceError> {
+        let mut db = self.database.borrow_mut();
+        db.flush().map_err(StorageServiceError::Database)?;
+        let mut it = db.new_iter().map_err(StorageServiceError::Database)?;
+
+        let mut entries = Vec::new();
+        while let Some((key, value)) = it.next() {
+            entries.push(KeyValue::new(key, value));
+        }
+
+        Ok(ExportSnapshotResponse::new(entries))
+    }
+
+    fn import_snapshot(
+        &self,
+        request: ImportSnapshotRequest,
+    ) -> std::result::Result<(), StorageServiceError> {
+        self.ensure_writable()?;
+        let mut db = self.database.borrow_mut();
+        for entry in request.entries {
+            db.put(&entry.key, &entry.value)
+                .map_err(StorageServiceError::Database)?;
+        }
+        db.flush().map_err(StorageServiceError::Database)?;
+        Ok(())
+    }
+
+    fn create_snapshot(
+        &self,
+        request: CreateSnapshotRequest,
+    ) -> std::result::Result<CreateSnapshotResponse, StorageServiceError> {
+        let url = Url::parse(&request.url).map_err(|e| anyhow!("invalid url: {}", e))?;
+        let crypto: FileCrypto = request
+            .crypto_info
+            .ok_or_else(|| anyhow!("missing crypto info"))?
+            .try_into()?;
+
+        let snapshot = self.export_snapshot(ExportSnapshotRequest::default())?;
+        let cmac = snapshot::upload(&snapshot, &url, crypto, &self.fusion_base)?;
+        info!("Created a snapshot of {} entries", snapshot.entries.len());
+
+        Ok(CreateSnapshotResponse::new(
+            snapshot.entries.len() as u64,
+            cmac,
+        ))
+    }
+
+    fn restore_snapshot(
+        &self,
+        request: RestoreSnapshotRequest,
+    ) -> std::result::Result<RestoreSnapshotResponse, StorageServiceError> {
+        self.ensure_writable()?;
+        let url = Url::parse(&request.url).map_err(|e| anyhow!("invalid url: {}", e))?;
+        let crypto: FileCrypto = request
+            .crypto_info
+            .ok_or_else(|| anyhow!("missing crypto info"))?
+            .try_into()?;
+        let cmac = FileAuthTag::from_bytes(&request.cmac)?;
+
+        let snapshot = snapshot::download(&url, crypto, &cmac, &self.fusion_base)?;
+        let entries = snapshot.entries.len() as u64;
+        self.import_snapshot(ImportSnapshotRequest::new(snapshot.entries))?;
+        info!("Restored a snapshot of {} entries", entries);
+
+        Ok(RestoreSnapshotResponse::new(entries))
+    }
+
+    fn apply_change_log(
+        &self,
+        request: ApplyChangeLogRequest,
+    ) -> std::result::Result<ApplyChangeLogResponse, StorageServiceError> {
+        let mut follower = self.follower.borrow_mut();
+        let follower = match follower.as_mut() {
+            Some(follower) => follower,
+            None => bail!(StorageServiceError::NotFollower),
+        };
+        follower.apply(&mut self.database.borrow_mut(), request)?;
+        Ok(ApplyChangeLogResponse::new(follower.applied_index()))
+    }
+
+    fn promote_replica(
+        &self,
+        _request: PromoteReplicaRequest,
+    ) -> std::result::Result<PromoteReplicaResponse, StorageServiceError> {
+        let applied_index = match self.follower.take() {
+            Some(follower) => follower.applied_index(),
+            None => bail!(StorageServiceError::NotFollower),
+        };
+        info!(
+            "Promoted to primary after applying the change log up to {}",
+            applied_index
+        );
+        Ok(PromoteReplicaResponse::new(applied_index))
+    }
 }
 
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
+    use rusty_leveldb::DB;
     use tokio::sync::mpsc::unbounded_channel;
 
     fn get_mock_service() -> TeaclaveStorageService {
@@ -294,10 +845,11 @@ pub mod tests {
         database
             .put(b"test_delete_key", b"test_delete_value")
             .unwrap();
-        TeaclaveStorageService {
-            database: RefCell::new(database),
+        TeaclaveStorageService::new(
+            RefCell::new(Database::new(database)),
             receiver,
-        }
+            "/tmp/fusion_data",
+        )
     }
 
     pub fn test_get_key() {
@@ -322,6 +874,29 @@ pub mod tests {
         assert!(service.get(request).is_err());
     }
 
+    pub fn test_write_batch() {
+        let service = get_mock_service();
+        let request = PutRequest::new("test_batch_expiring_key", "old").expires_at(1);
+        assert!(service.put(request).is_ok());
+
+        let request = WriteBatchRequest::new()
+            .put("test_batch_key", "value")
+            .put("test_batch_expiring_key", "new")
+            .delete("test_delete_key");
+        assert!(service.write_batch(request).is_ok());
+
+        let request = GetRequest::new("test_batch_key");
+        assert_eq!(service.get(request).unwrap().value, b"value");
+        let request = GetRequest::new("test_batch_expiring_key");
+        assert_eq!(service.get(request).unwrap().value, b"new");
+        let request = GetRequest::new("test_delete_key");
+        assert!(service.get(request).is_err());
+
+        service.freeze(FreezeRequest::default()).unwrap();
+        let request = WriteBatchRequest::new().put("test_batch_frozen_key", "value");
+        assert!(service.write_batch(request).is_err());
+    }
+
     pub fn test_empty_value() {
         let service = get_mock_service();
         let request = PutRequest::new("test_empty_value", "");
@@ -353,6 +928,20 @@ pub mod tests {
         assert_eq!(service.dequeue(request).unwrap().value, b"2");
     }
 
+    pub fn test_get_queue_length() {
+        let service = get_mock_service();
+        let request = GetQueueLengthRequest::new("test_queue_length_key");
+        assert_eq!(service.get_queue_length(request).unwrap().length, 0);
+        let request = EnqueueRequest::new("test_queue_length_key", "1");
+        assert!(service.enqueue(request).is_ok());
+        let request = EnqueueRequest::new("test_queue_length_key", "2");
+        assert!(service.enqueue(request).is_ok());
+        let request = DequeueRequest::new("test_queue_length_key");
+        assert!(service.dequeue(request).is_ok());
+        let request = GetQueueLengthRequest::new("test_queue_length_key");
+        assert_eq!(service.get_queue_length(request).unwrap().length, 1);
+    }
+
     pub fn test_get_keys_by_prefix() {
         let service = get_mock_service();
         let request = PutRequest::new("function-1", "test_put_value");
@@ -377,5 +966,261 @@ pub mod tests {
                 b"function-5".to_vec()
             ]
         );
+
+        let request = GetKeysByPrefixRequest::new("function").page(b"function-22".to_vec(), 1);
+        let response = service.get_keys_by_prefix(request).unwrap();
+        assert_eq!(response.keys, std::vec![b"function-333".to_vec()]);
+
+        let request = GetKeysByPrefixRequest::new("function").page(b"function-5".to_vec(), 10);
+        let response = service.get_keys_by_prefix(request).unwrap();
+        assert!(response.keys.is_empty());
+    }
+
+    pub fn test_list_entries() {
+        let service = get_mock_service();
+        for (key, value) in [
+            ("tantivy/meta.json", "meta"),
+            ("tantivy/a.idx", "index"),
+            ("tantivy/b.store", "store"),
+            ("tantivy-other", "other"),
+        ] {
+            let request = PutRequest::new(key, value);
+            assert!(service.put(request).is_ok());
+        }
+
+        let request = ListEntriesRequest::new("tantivy/");
+        let entries = service.list_entries(request).unwrap().entries;
+        let keys: Vec<_> = entries.iter().map(|e| e.key.as_slice()).collect();
+        assert_eq!(
+            keys,
+            std::vec![
+                b"tantivy/a.idx".as_slice(),
+                b"tantivy/b.store".as_slice(),
+                b"tantivy/meta.json".as_slice()
+            ]
+        );
+        assert_eq!(entries[0].size, 5);
+        assert!(entries.iter().all(|e| e.value.is_empty()));
+
+        // Pages end once the values add up to the given bytes
+        let request = ListEntriesRequest::new("tantivy/").with_values(6);
+        let entries = service.list_entries(request).unwrap().entries;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].value, b"store".to_vec());
+
+        let request = ListEntriesRequest::new("tantivy/")
+            .page(b"tantivy/b.store".to_vec(), 0)
+            .with_values(6);
+        let entries = service.list_entries(request).unwrap().entries;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].value, b"meta".to_vec());
+    }
+
+    pub fn test_put_key_with_expiry() {
+        let service = get_mock_service();
+        let now = now_secs();
+        let request = PutRequest::new("test_expired_key", "test_put_value").expires_at(now - 1);
+        assert!(service.put(request).is_ok());
+        let request = PutRequest::new("test_expiring_key", "test_put_value").expires_at(now + 3600);
+        assert!(service.put(request).is_ok());
+        let request = PutRequest::new("test_renewed_key", "test_put_value").expires_at(now - 1);
+        assert!(service.put(request).is_ok());
+        let request = PutRequest::new("test_renewed_key", "test_put_value");
+        assert!(service.put(request).is_ok());
+
+        let request = GetRequest::new("test_expired_key");
+        assert!(service.get(request).is_err());
+        let request = GetRequest::new("test_expiring_key");
+        assert!(service.get(request).is_ok());
+        let request = GetRequest::new("test_renewed_key");
+        assert!(service.get(request).is_ok());
+
+        service.sweep_expired(now);
+        let mut db = service.database.borrow_mut();
+        assert!(db.get(b"test_expired_key").is_none());
+        assert!(db.get(b"test_expiring_key").is_some());
+        assert!(db.get(b"test_renewed_key").is_some());
+        assert_eq!(
+            DBExpiry::open(&mut db).expires_at(b"test_expiring_key"),
+            Some(now + 3600)
+        );
+    }
+
+    pub fn test_freeze() {
+        let service = get_mock_service();
+        assert!(service.freeze(FreezeRequest::default()).is_ok());
+        let request = PutRequest::new("test_freeze_key", "test_freeze_value");
+        assert!(matches!(
+            service.put(request),
+            Err(StorageServiceError::Frozen)
+        ));
+        let request = EnqueueRequest::new("test_freeze_key", "1");
+        assert!(service.enqueue(request).is_err());
+        let request = GetRequest::new("test_get_key");
+        assert!(service.get(request).is_ok());
+        assert!(service
+            .export_snapshot(ExportSnapshotRequest::default())
+            .is_ok());
+    }
+
+    pub fn test_export_import_snapshot() {
+        let service = get_mock_service();
+        let request = PutRequest::new("test_snapshot_key", "test_snapshot_value");
+        assert!(service.put(request).is_ok());
+        let snapshot = service
+            .export_snapshot(ExportSnapshotRequest::default())
+            .unwrap();
+        assert!(snapshot.verify());
+        assert!(snapshot
+            .entries
+            .iter()
+            .any(|e| e.key == b"test_snapshot_key" && e.value == b"test_snapshot_value"));
+
+        let (_sender, receiver) = unbounded_channel();
+        let opt = rusty_leveldb::in_memory();
+        let database = DB::open("mock_db_snapshot_test", opt).unwrap();
+        let replacement = TeaclaveStorageService::new(
+            RefCell::new(Database::new(database)),
+            receiver,
+            "/tmp/fusion_data",
+        );
+        let request = ImportSnapshotRequest::new(snapshot.entries.clone());
+        assert!(replacement.import_snapshot(request).is_ok());
+        let imported = replacement
+            .export_snapshot(ExportSnapshotRequest::default())
+            .unwrap();
+        assert_eq!(imported.digest, snapshot.digest);
+    }
+
+    pub fn test_create_restore_snapshot() {
+        let service = get_mock_service();
+        let request = PutRequest::new("test_backup_key", "test_backup_value");
+        assert!(service.put(request).is_ok());
+        let url = Url::parse("file:///tmp/storage_snapshot_test.enc").unwrap();
+        let crypto = FileCrypto::new("aes-gcm-128", &[0x01; 16], &[0x02; 12]).unwrap();
+        let request = CreateSnapshotRequest::new(url.clone(), crypto);
+        let created = service.create_snapshot(request).unwrap();
+        assert!(created.entries > 0);
+        let cmac = FileAuthTag::from_bytes(&created.cmac).unwrap();
+
+        let (_sender, receiver) = unbounded_channel();
+        let opt = rusty_leveldb::in_memory();
+        let database = DB::open("mock_db_restore_test", opt).unwrap();
+        let restored = TeaclaveStorageService::new(
+            RefCell::new(Database::new(database)),
+            receiver,
+            "/tmp/fusion_data",
+        );
+        // A snapshot is only restored with the tag it was created with
+        let request = RestoreSnapshotRequest::new(url.clone(), crypto, FileAuthTag::mock());
+        assert!(restored.restore_snapshot(request).is_err());
+        let request = RestoreSnapshotRequest::new(url, crypto, cmac);
+        let response = restored.restore_snapshot(request).unwrap();
+        assert_eq!(response.entries, created.entries);
+        let request = GetRequest::new("test_backup_key");
+        assert_eq!(
+            restored.get(request).unwrap().value,
+            b"test_backup_value".to_vec()
+        );
+        std::untrusted::fs::remove_file("/tmp/storage_snapshot_test.enc").unwrap();
+    }
+
+    pub fn test_consistency_token() {
+        let service = get_mock_service();
+        let request = PutRequest::new("test_token_key", "1");
+        let first = service.put(request).unwrap().token.unwrap();
+        let request = WriteBatchRequest::new().put("test_token_key", "2");
+        let second = service.write_batch(request).unwrap().token.unwrap();
+        assert_eq!(second.store_id, first.store_id);
+        assert!(second.sequence > first.sequence);
+
+        let request = GetRequest::new("test_token_key").after(Some(second.clone()));
+        assert_eq!(service.get(request).unwrap().value, b"2");
+
+        // A write not applied yet, or applied to another store
+        let ahead = ConsistencyToken::new(second.store_id.clone(), second.sequence + 1);
+        let request = GetRequest::new("test_token_key").after(Some(ahead));
+        assert!(matches!(
+            service.get(request),
+            Err(StorageServiceError::StaleRead)
+        ));
+        let other = ConsistencyToken::new(vec![0; 16], 1);
+        let request = GetKeysByPrefixRequest::new("test").after(Some(other));
+        assert!(service.get_keys_by_prefix(request).is_err());
+
+        // Tokens stay valid on a replacement importing a snapshot
+        let snapshot = service
+            .export_snapshot(ExportSnapshotRequest::default())
+            .unwrap();
+        let (_sender, receiver) = unbounded_channel();
+        let opt = rusty_leveldb::in_memory();
+        let database = DB::open("mock_db_token_test", opt).unwrap();
+        let replacement = TeaclaveStorageService::new(
+            RefCell::new(Database::new(database)),
+            receiver,
+            "/tmp/fusion_data",
+        );
+        let request = ImportSnapshotRequest::new(snapshot.entries);
+        assert!(replacement.import_snapshot(request).is_ok());
+        let request = GetQueueLengthRequest::new("test_token_queue").after(Some(second));
+        assert!(replacement.get_queue_length(request).is_ok());
+    }
+
+    pub fn test_compare_and_swap() {
+        let service = get_mock_service();
+        let request = CompareAndSwapRequest::new("test_cas_key", "a");
+        assert!(service.compare_and_swap(request).unwrap().swapped);
+        let request = CompareAndSwapRequest::new("test_cas_key", "b");
+        let response = service.compare_and_swap(request).unwrap();
+        assert!(!response.swapped);
+        assert_eq!(response.current, b"a");
+
+        let request = CompareAndSwapRequest::new("test_cas_key", "b").expect("c");
+        assert!(!service.compare_and_swap(request).unwrap().swapped);
+        let request = CompareAndSwapRequest::new("test_cas_key", "b")
+            .expect("a")
+            .expires_at(now_secs() - 1);
+        assert!(service.compare_and_swap(request).unwrap().swapped);
+
+        // Expired keys are absent.
+        let request = CompareAndSwapRequest::new("test_cas_key", "c").expect("b");
+        assert!(!service.compare_and_swap(request).unwrap().swapped);
+        let request = CompareAndSwapRequest::new("test_cas_key", "c");
+        assert!(service.compare_and_swap(request).unwrap().swapped);
+        let request = GetRequest::new("test_cas_key");
+        assert_eq!(service.get(request).unwrap().value, b"c");
+    }
+
+    pub fn test_follower() {
+        let (_sender, receiver) = unbounded_channel();
+        let database = DB::open("mock_db_follower_test", rusty_leveldb::in_memory()).unwrap();
+        let service = TeaclaveStorageService::new(
+            RefCell::new(Database::new(database)),
+            receiver,
+            "/tmp/fusion_data",
+        )
+        .follower();
+        let request = PutRequest::new("test_follower_key", "a");
+        assert!(service.put(request).is_err());
+
+        let database = DB::open("mock_db_primary_test", rusty_leveldb::in_memory()).unwrap();
+        let mut primary = Database::with_change_log(database);
+        primary.put(b"test_follower_key", b"b").unwrap();
+        primary.flush().unwrap();
+        let log = primary
+            .read_change_log(ReadChangeLogRequest::reset(0))
+            .unwrap();
+        let request = ApplyChangeLogRequest::new(log.log_id, log.entries);
+        assert_eq!(service.apply_change_log(request).unwrap().applied_index, 1);
+        let request = GetRequest::new("test_follower_key");
+        assert_eq!(service.get(request).unwrap().value, b"b");
+
+        let response = service.promote_replica(PromoteReplicaRequest::default());
+        assert_eq!(response.unwrap().applied_index, 1);
+        let request = PutRequest::new("test_follower_key", "c");
+        assert!(service.put(request).is_ok());
+        assert!(service
+            .promote_replica(PromoteReplicaRequest::default())
+            .is_err());
     }
 }
@@ -0,0 +1,98 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Backups of the database. A snapshot of all the entries is encrypted in the
+//! enclave with the key given by the operator and uploaded to its URL through
+//! the file agent. On restoring, the tag of the downloaded snapshot must match
+//! the one returned when it was created, so an older snapshot under the same
+//! key cannot be restored in its place, and the entries must match the digest.
+
+use crate::file_handler::handle_file_request;
+
+use anyhow::{ensure, Result};
+use std::io::Write;
+use std::path::Path;
+use std::untrusted::fs;
+use teaclave_proto::teaclave_storage_service::ExportSnapshotResponse;
+use teaclave_types::{
+    read_all_bytes, FileAgentRequest, FileAuthTag, FileCrypto, HandleFileCommand, HandleFileInfo,
+    FILE_AUTH_TAG_LENGTH,
+};
+use url::Url;
+use uuid::Uuid;
+
+static SNAPSHOT_BASE_DIR: &str = "/tmp/teaclave_storage_snapshot/";
+
+/// Encrypt the snapshot and upload it to `url`. Returns the tag of the
+/// uploaded snapshot.
+pub(crate) fn upload(
+    snapshot: &ExportSnapshotResponse,
+    url: &Url,
+    crypto: FileCrypto,
+    fusion_base: &Path,
+) -> Result<FileAuthTag> {
+    with_work_dir(|cwd| {
+        let mut bytes = snapshot.to_bytes();
+        let cmac = crypto.encrypt_in_memory(&mut bytes, None)?;
+        let upload_path = cwd.join("upload");
+        fs::File::create(&upload_path)?.write_all(&bytes)?;
+
+        let info = HandleFileInfo::new(&upload_path, url);
+        let request = FileAgentRequest::new(HandleFileCommand::Upload, vec![info], fusion_base);
+        log::debug!("Ocall file upload request: {:?}", request);
+        handle_file_request(request)?;
+        Ok(cmac)
+    })
+}
+
+/// Download the snapshot from `url` and decrypt it, checking it against the
+/// tag returned by `upload`.
+pub(crate) fn download(
+    url: &Url,
+    crypto: FileCrypto,
+    cmac: &FileAuthTag,
+    fusion_base: &Path,
+) -> Result<ExportSnapshotResponse> {
+    with_work_dir(|cwd| {
+        let download_path = cwd.join("download");
+        let info = HandleFileInfo::new(&download_path, url);
+        let request = FileAgentRequest::new(HandleFileCommand::Download, vec![info], fusion_base);
+        log::debug!("Ocall file download request: {:?}", request);
+        handle_file_request(request)?;
+
+        let mut bytes = read_all_bytes(&download_path)?;
+        let n = bytes.len();
+        ensure!(n > FILE_AUTH_TAG_LENGTH, "Snapshot, invalid length");
+        ensure!(
+            cmac.to_bytes() == bytes[n - FILE_AUTH_TAG_LENGTH..],
+            "Snapshot, invalid tag"
+        );
+        crypto.decrypt_in_memory(&mut bytes, None)?;
+        let snapshot = ExportSnapshotResponse::from_bytes(&bytes)?;
+        ensure!(snapshot.verify(), "Snapshot, digest mismatch");
+        Ok(snapshot)
+    })
+}
+
+// Nothing is left behind whether the transfer succeeds or not.
+fn with_work_dir<T>(f: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let cwd = Path::new(SNAPSHOT_BASE_DIR).join(Uuid::new_v4().to_string());
+    fs::create_dir_all(&cwd)?;
+    let result = f(&cwd);
+    let _ = fs::remove_dir_all(&cwd);
+    result
+}
@@ -25,6 +25,16 @@ use teaclave_binder::TeeBinder;
 use teaclave_config::RuntimeConfig;
 use teaclave_types::{TeeServiceError, TeeServiceResult};
 
+mod validate;
+
+pub use validate::{validate_runtime_config, CheckStatus, ConfigCheck, ConfigReport};
+
+const RUNTIME_CONFIG_PATH: &str = "runtime.config.toml";
+
+/// Argument of the service apps to check the runtime config and print a
+/// report instead of starting the service.
+pub const VALIDATE_CONFIG_ARG: &str = "validate-config";
+
 extern "C" {
     fn _exit(status: i32) -> !;
 }
@@ -77,6 +87,11 @@ impl TeaclaveServiceLauncher {
     }
 }
 
+/// Whether the app is run with `validate-config`.
+pub fn validate_config_requested() -> bool {
+    std::env::args().nth(1).as_deref() == Some(VALIDATE_CONFIG_ARG)
+}
+
 pub fn launch_teaclave_service(host_package_name: &str) -> Result<()> {
     env_logger::init_from_env(
         env_logger::Env::new()
@@ -84,9 +99,18 @@ pub fn launch_teaclave_service(host_package_name: &str) -> Result<()> {
             .write_style_or("TEACLAVE_LOG_STYLE", "RUST_LOG_STYLE"),
     );
 
+    if validate_config_requested() {
+        let report = validate_runtime_config(host_package_name, RUNTIME_CONFIG_PATH);
+        println!("{}", report);
+        if report.has_errors() {
+            bail!("Invalid runtime config: {}", RUNTIME_CONFIG_PATH);
+        }
+        return Ok(());
+    }
+
     let launcher = Arc::new(TeaclaveServiceLauncher::new(
         host_package_name,
-        "runtime.config.toml",
+        RUNTIME_CONFIG_PATH,
     )?);
     let launcher_ref = launcher.clone();
     thread::spawn(move || {
@@ -0,0 +1,491 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Checks of the runtime config done by the untrusted app without starting
+//! the enclave, so that operators see what is wrong with a config instead of
+//! an enclave failing to start.
+
+use std::fmt;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+use teaclave_config::build::{AUDITOR_PUBLIC_KEYS, GRPC_CONFIG};
+use teaclave_config::RuntimeConfig;
+use teaclave_types::EnclaveInfo;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const RPC_FAMILIES: [&str; 4] = ["data", "function", "task", "admin"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warning => "warning",
+            CheckStatus::Error => "error",
+        };
+        f.pad(status)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigCheck {
+    pub section: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+/// Checks of a runtime config by section. Warnings are settings which work
+/// but are likely not intended, or peers which are not reachable yet.
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    pub checks: Vec<ConfigCheck>,
+}
+
+impl ConfigReport {
+    fn push(&mut self, section: &'static str, status: CheckStatus, message: impl ToString) {
+        self.checks.push(ConfigCheck {
+            section,
+            status,
+            message: message.to_string(),
+        });
+    }
+
+    fn ok(&mut self, section: &'static str, message: impl ToString) {
+        self.push(section, CheckStatus::Ok, message);
+    }
+
+    fn warn(&mut self, section: &'static str, message: impl ToString) {
+        self.push(section, CheckStatus::Warning, message);
+    }
+
+    fn error(&mut self, section: &'static str, message: impl ToString) {
+        self.push(section, CheckStatus::Error, message);
+    }
+
+    fn count(&self, status: CheckStatus) -> usize {
+        self.checks.iter().filter(|c| c.status == status).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(CheckStatus::Error) > 0
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                f,
+                "[{:<7}] {:<12} {}",
+                check.status, check.section, check.message
+            )?;
+        }
+        write!(
+            f,
+            "{} checks, {} errors, {} warnings",
+            self.checks.len(),
+            self.count(CheckStatus::Error),
+            self.count(CheckStatus::Warning)
+        )
+    }
+}
+
+/// Check the runtime config at `config_path` for the service of
+/// `package_name`, e.g., `teaclave_frontend_service`.
+pub fn validate_runtime_config(package_name: &str, config_path: impl AsRef<Path>) -> ConfigReport {
+    let mut report = ConfigReport::default();
+    let config = match RuntimeConfig::from_toml(config_path.as_ref()) {
+        Ok(config) => {
+            report.ok(
+                "config",
+                format!("loaded {}", config_path.as_ref().display()),
+            );
+            config
+        }
+        Err(e) => {
+            // Nothing else can be checked
+            report.error("config", format!("{:#}", e));
+            return report;
+        }
+    };
+
+    check_endpoints(package_name, &config, &mut report);
+    check_attestation(&config, &mut report);
+    check_audit(package_name, &config, &mut report);
+    check_mount(package_name, &config, &mut report);
+    check_limits(&config, &mut report);
+    report
+}
+
+/// Endpoints listened on by each service, e.g., `frontend` of
+/// `teaclave_frontend_service`, in the API and internal sections.
+fn listen_addresses(config: &RuntimeConfig) -> Vec<(&'static str, &'static str, SocketAddr)> {
+    let api = &config.api_endpoints;
+    let internal = &config.internal_endpoints;
+    vec![
+        ("frontend", "api_endpoints", api.frontend.listen_address),
+        (
+            "authentication",
+            "api_endpoints",
+            api.authentication.listen_address,
+        ),
+        (
+            "access_control",
+            "internal_endpoints",
+            internal.access_control.listen_address,
+        ),
+        (
+            "authentication",
+            "internal_endpoints",
+            internal.authentication.listen_address,
+        ),
+        (
+            "management",
+            "internal_endpoints",
+            internal.management.listen_address,
+        ),
+        (
+            "storage",
+            "internal_endpoints",
+            internal.storage.listen_address,
+        ),
+        (
+            "execution",
+            "internal_endpoints",
+            internal.execution.listen_address,
+        ),
+        (
+            "scheduler",
+            "internal_endpoints",
+            internal.scheduler.listen_address,
+        ),
+    ]
+}
+
+fn is_service(package_name: &str, service: &str) -> bool {
+    package_name == format!("teaclave_{}_service", service)
+}
+
+fn check_endpoints(package_name: &str, config: &RuntimeConfig, report: &mut ConfigReport) {
+    let listens = listen_addresses(config);
+    for (i, (service, section, addr)) in listens.iter().enumerate() {
+        let overlapping = listens[..i].iter().find(|(_, _, other)| {
+            other.port() == addr.port()
+                && (other.ip() == addr.ip()
+                    || other.ip().is_unspecified()
+                    || addr.ip().is_unspecified())
+        });
+        // Which is fine only if the services run on different hosts
+        if let Some((other_service, other_section, _)) = overlapping {
+            report.warn(
+                "endpoints",
+                format!(
+                    "{}.{} and {}.{} both listen on port {}",
+                    other_section,
+                    other_service,
+                    section,
+                    service,
+                    addr.port()
+                ),
+            );
+        }
+    }
+
+    // Only the addresses of this service are bound, as the others may run on
+    // different hosts.
+    for (service, section, addr) in listens.iter() {
+        if !is_service(package_name, service) {
+            continue;
+        }
+        match TcpListener::bind(addr) {
+            Ok(_) => report.ok(
+                "endpoints",
+                format!("{}.{} can listen on {}", section, service, addr),
+            ),
+            Err(e) => report.error(
+                "endpoints",
+                format!("{}.{} cannot listen on {}: {}", section, service, addr, e),
+            ),
+        }
+    }
+
+    let internal = &config.internal_endpoints;
+    let advertised = [
+        ("access_control", &internal.access_control),
+        ("authentication", &internal.authentication),
+        ("management", &internal.management),
+        ("storage", &internal.storage),
+        ("execution", &internal.execution),
+        ("scheduler", &internal.scheduler),
+    ];
+    for (service, endpoint) in advertised.iter() {
+        let address = &endpoint.advertised_address;
+        if !address.starts_with("https://") {
+            report.error(
+                "endpoints",
+                format!("{} is not advertised at an https URL: {}", service, address),
+            );
+            continue;
+        }
+        if is_service(package_name, service) {
+            continue;
+        }
+        // Peers may not be started yet, which is not an error of the config.
+        match connect(address) {
+            Ok(addr) => report.ok("endpoints", format!("{} is reachable at {}", service, addr)),
+            Err(e) => report.warn(
+                "endpoints",
+                format!("{} is not reachable at {}: {}", service, address, e),
+            ),
+        }
+    }
+}
+
+fn check_attestation(config: &RuntimeConfig, report: &mut ConfigReport) {
+    let attestation = &config.attestation;
+    report.ok(
+        "attestation",
+        format!("algorithm {} with SPID and key set", attestation.algorithm),
+    );
+
+    let mut reachable = false;
+    for url in attestation.urls() {
+        match connect(&url) {
+            Ok(_) => {
+                reachable = true;
+                report.ok("attestation", format!("{} is reachable", url));
+            }
+            Err(e) => report.warn("attestation", format!("{} is not reachable: {}", url, e)),
+        }
+    }
+
+    match &attestation.endorsement_cache_dir {
+        Some(dir) if dir.is_dir() => report.ok(
+            "attestation",
+            format!("endorsement cache in {}", dir.display()),
+        ),
+        Some(dir) if dir.exists() => report.error(
+            "attestation",
+            format!("endorsement cache {} is not a directory", dir.display()),
+        ),
+        Some(dir) => report.warn(
+            "attestation",
+            format!("endorsement cache {} does not exist", dir.display()),
+        ),
+        None if attestation.reuse_cached_endorsement => report.warn(
+            "attestation",
+            "reuse_cached_endorsement is set without endorsement_cache_dir",
+        ),
+        None if !reachable => report.error(
+            "attestation",
+            "no attestation service is reachable and no endorsement is cached",
+        ),
+        None => (),
+    }
+}
+
+fn check_audit(package_name: &str, config: &RuntimeConfig, report: &mut ConfigReport) {
+    let audit = &config.audit;
+    let enclave_info = match EnclaveInfo::try_from_bytes(&audit.enclave_info_bytes) {
+        Ok(enclave_info) => enclave_info,
+        Err(e) => {
+            report.error("audit", format!("cannot parse enclave_info: {}", e));
+            return;
+        }
+    };
+
+    let signatures = &audit.auditor_signatures_bytes;
+    if signatures.is_empty() {
+        report.warn("audit", "enclave_info is not signed by any auditor");
+    } else if signatures.len() > AUDITOR_PUBLIC_KEYS.len() {
+        report.error(
+            "audit",
+            format!(
+                "{} auditor signatures for {} auditor public keys",
+                signatures.len(),
+                AUDITOR_PUBLIC_KEYS.len()
+            ),
+        );
+    } else if EnclaveInfo::verify(&audit.enclave_info_bytes, AUDITOR_PUBLIC_KEYS, signatures) {
+        report.ok(
+            "audit",
+            format!("enclave_info is signed by {} auditors", signatures.len()),
+        );
+    } else {
+        report.error(
+            "audit",
+            "auditor signatures do not match enclave_info or the auditor public keys",
+        );
+    }
+
+    if enclave_info.get_enclave_attr(package_name).is_some() {
+        report.ok("audit", format!("enclave_info has {}", package_name));
+    } else {
+        report.error(
+            "audit",
+            format!("enclave_info has no measurement of {}", package_name),
+        );
+    }
+}
+
+fn check_mount(package_name: &str, config: &RuntimeConfig, report: &mut ConfigReport) {
+    if package_name != "teaclave_execution_service" {
+        return;
+    }
+    let dir = &config.mount.fusion_base_dir;
+    if dir.is_dir() {
+        report.ok(
+            "mount",
+            format!("fusion_base_dir {} is mounted", dir.display()),
+        );
+    } else {
+        report.warn(
+            "mount",
+            format!("fusion_base_dir {} is not mounted", dir.display()),
+        );
+    }
+}
+
+fn check_limits(config: &RuntimeConfig, report: &mut ConfigReport) {
+    let quota = &config.quota;
+    let max_queue_depth = config.scheduler.max_queue_depth;
+    if quota.max_concurrent_tasks > max_queue_depth {
+        report.warn(
+            "quota",
+            format!(
+                "max_concurrent_tasks {} of a user exceeds max_queue_depth {} of the scheduler",
+                quota.max_concurrent_tasks, max_queue_depth
+            ),
+        );
+    } else {
+        report.ok(
+            "quota",
+            format!(
+                "max_concurrent_tasks {}, max_registered_data {}, max_requests_per_minute {} \
+                 (0 for unlimited)",
+                quota.max_concurrent_tasks,
+                quota.max_registered_data,
+                quota.max_requests_per_minute
+            ),
+        );
+    }
+
+    let payload = &config.function_payload;
+    let max_message_size = GRPC_CONFIG.max_decoding_message_size as u64;
+    if payload.max_chunk_size_bytes > payload.max_size_bytes {
+        report.warn(
+            "quota",
+            format!(
+                "function payload chunks of {} bytes are larger than payloads of {} bytes",
+                payload.max_chunk_size_bytes, payload.max_size_bytes
+            ),
+        );
+    }
+    if payload.max_chunk_size_bytes >= max_message_size {
+        report.error(
+            "quota",
+            format!(
+                "function payload chunks of {} bytes do not fit in RPC messages of {} bytes",
+                payload.max_chunk_size_bytes, max_message_size
+            ),
+        );
+    } else {
+        report.ok(
+            "quota",
+            format!(
+                "function payloads of {} bytes in chunks of {} bytes",
+                payload.max_size_bytes, payload.max_chunk_size_bytes
+            ),
+        );
+    }
+
+    let slo = &config.slo;
+    let targets = std::iter::once(("default", &slo.default)).chain(
+        slo.families
+            .iter()
+            .map(|(family, target)| (family.as_str(), target)),
+    );
+    for (family, target) in targets {
+        if family != "default" && !RPC_FAMILIES.contains(&family) {
+            report.warn("slo", format!("unknown RPC family {}", family));
+        }
+        if !(0.0..=1.0).contains(&target.max_error_rate) {
+            report.error(
+                "slo",
+                format!(
+                    "max_error_rate {} of {} is not between 0 and 1",
+                    target.max_error_rate, family
+                ),
+            );
+        }
+    }
+
+    if !config.task_log.upload_base_url.ends_with('/') {
+        report.error(
+            "task_log",
+            format!(
+                "upload_base_url {} does not end with a slash",
+                config.task_log.upload_base_url
+            ),
+        );
+    }
+
+    if let Some(forensics) = &config.forensics {
+        if !forensics.upload_base_url.ends_with('/') {
+            report.error(
+                "forensics",
+                format!(
+                    "upload_base_url {} does not end with a slash",
+                    forensics.upload_base_url
+                ),
+            );
+        }
+    }
+}
+
+/// Connect to the host of a URL, returning the address connected to.
+fn connect(url: &str) -> std::io::Result<SocketAddr> {
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid URL");
+    let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let authority = authority.rsplit('@').next().unwrap_or_default();
+    let has_port = authority
+        .rsplit_once(':')
+        .map_or(false, |(_, port)| port.parse::<u16>().is_ok());
+    let host_port = match scheme {
+        _ if has_port => authority.to_string(),
+        "https" => format!("{}:443", authority),
+        "http" => format!("{}:80", authority),
+        _ => return Err(invalid()),
+    };
+
+    let mut last_error = invalid();
+    for addr in host_port.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(_) => return Ok(addr),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
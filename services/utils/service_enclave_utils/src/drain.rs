@@ -0,0 +1,70 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Drain mode of the service enclaves. The enclave is finalized from another
+//! thread than the one serving the requests, so the servers are asked to stop
+//! through a flag: they stop accepting connections, finish the requests in
+//! flight, and are waited for until the drain timeout before the enclave is
+//! finalized.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+static DRAIN_REQUESTED: AtomicBool = AtomicBool::new(false);
+// Servers started with a drain signal which have not stopped yet
+static SERVING: AtomicUsize = AtomicUsize::new(0);
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether the enclave is being drained.
+pub fn is_draining() -> bool {
+    DRAIN_REQUESTED.load(Ordering::Acquire)
+}
+
+/// Returns the future to serve with, e.g., `serve_with_shutdown`, which
+/// resolves when the enclave is asked to drain. The server is waited for
+/// until [`drained`] is called.
+pub fn drain_signal() -> impl Future<Output = ()> {
+    SERVING.fetch_add(1, Ordering::AcqRel);
+    async {
+        while !is_draining() {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Marks a server started with [`drain_signal`] as stopped, after the
+/// requests in flight are done and their audit logs are sent.
+pub fn drained() {
+    SERVING.fetch_sub(1, Ordering::AcqRel);
+}
+
+/// Asks the servers to drain and waits for them to stop, for at most
+/// `timeout`. Returns whether all of them stopped in time.
+pub fn drain(timeout: Duration) -> bool {
+    DRAIN_REQUESTED.store(true, Ordering::Release);
+
+    let deadline = Instant::now() + timeout;
+    while SERVING.load(Ordering::Acquire) > 0 {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(DRAIN_POLL_INTERVAL);
+    }
+    true
+}
@@ -23,18 +23,28 @@ extern crate sgx_trts;
 use anyhow::Result;
 use log::debug;
 use log::error;
+use log::Record;
 #[cfg(not(feature = "mesalock_sgx"))]
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
 #[cfg(feature = "mesalock_sgx")]
 use std::untrusted::{fs, path::PathEx};
-use teaclave_attestation::verifier::AttestationReportVerificationFn;
+use teaclave_attestation::verifier::{AttestationReportVerificationFn, VerificationPolicy};
 use teaclave_attestation::AttestedTlsConfig;
 use teaclave_config::RuntimeConfig;
 use teaclave_types::{EnclaveInfo, TeeServiceResult};
 
+mod drain;
 mod macros;
+#[cfg(feature = "tdx")]
+pub mod td_guest;
+
+pub use drain::{drain_signal, drained, is_draining};
 
 #[cfg(feature = "cov")]
 #[sgx_macros::global_dtor]
@@ -43,6 +53,7 @@ fn cov_exit() {
     sgx_cov::cov_writeout();
 }
 
+#[cfg(not(feature = "tdx"))]
 extern "C" {
     pub static g_peak_heap_used: isize;
     pub static g_peak_rsrv_mem_committed: isize;
@@ -50,12 +61,37 @@ extern "C" {
 
 pub struct ServiceEnclave;
 
+/// Formats a log as one JSON object per line. Logs about a request carry its
+/// trace id, e.g., `log::info!(trace_id = id; "...")`, which is kept as a
+/// separate field so that the logs of a request can be correlated across
+/// services.
+fn format_json(buf: &mut env_logger::fmt::Formatter, record: &Record) -> std::io::Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let mut line = serde_json::json!({
+        "timestamp_ms": timestamp,
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let Some(trace_id) = record.key_values().get("trace_id".into()) {
+        line["trace_id"] = trace_id.to_string().into();
+    }
+    writeln!(buf, "{}", line)
+}
+
 impl ServiceEnclave {
     pub fn init(_name: &str) -> TeeServiceResult<()> {
         let env = env_logger::Env::new()
             .filter_or("TEACLAVE_LOG", "RUST_LOG")
             .write_style_or("TEACLAVE_LOG_STYLE", "RUST_LOG_STYLE");
-        let env_logger = env_logger::Builder::from_env(env).build();
+        let mut builder = env_logger::Builder::from_env(env);
+        if std::env::var("TEACLAVE_LOG_FORMAT").map_or(false, |f| f == "json") {
+            builder.format(format_json);
+        }
+        let env_logger = builder.build();
         teaclave_logger::Builder::new()
             .secondary_logger(env_logger)
             .init();
@@ -70,8 +106,24 @@ impl ServiceEnclave {
         Ok(())
     }
 
+    /// Stops the servers of the enclave from accepting connections and waits
+    /// for the requests in flight, for at most the drain timeout of the build
+    /// config. Called before [`ServiceEnclave::finalize`].
+    pub fn drain() {
+        let timeout =
+            std::time::Duration::from_secs(teaclave_config::build::GRPC_CONFIG.drain_timeout_secs);
+        debug!("Enclave draining");
+        if !drain::drain(timeout) {
+            log::warn!(
+                "Timed out draining the enclave after {} seconds",
+                timeout.as_secs()
+            );
+        }
+    }
+
     pub fn finalize() -> TeeServiceResult<()> {
         debug!("Enclave finalizing");
+        #[cfg(not(feature = "tdx"))]
         unsafe {
             debug!("g_peak_heap_used: {}", g_peak_heap_used);
             debug!("g_peak_rsrv_mem_committed: {}", g_peak_rsrv_mem_committed);
@@ -119,6 +171,9 @@ fn base_dir(config: &RuntimeConfig, sub_name: &str) -> Result<PathBuf> {
     Ok(sub_base)
 }
 
+/// Timeout of connecting to another service
+pub const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 macro_rules! impl_create_trusted_endpoint_fn {
     ($fn_name:ident, $enclave_attr:literal) => {
         pub fn $fn_name(
@@ -126,16 +181,28 @@ macro_rules! impl_create_trusted_endpoint_fn {
             enclave_info: &EnclaveInfo,
             as_root_ca_cert: &[u8],
             verifier: AttestationReportVerificationFn,
+            policy: VerificationPolicy,
             attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
         ) -> Result<teaclave_rpc::transport::channel::Endpoint> {
             let service_enclave_attrs = enclave_info
                 .get_enclave_attr($enclave_attr)
                 .expect("enclave attr");
+            // Services attest each other, so the attested certificate of
+            // this service is always presented to the other one.
             let client_tls_config =
                 teaclave_rpc::config::SgxTrustedTlsClientConfig::from_attested_tls_config(
                     attested_tls_config,
                 )?
-                .attestation_report_verifier(vec![service_enclave_attrs], as_root_ca_cert, verifier)
+                .attestation_report_verifier(
+                    vec![service_enclave_attrs],
+                    as_root_ca_cert,
+                    verifier,
+                    policy,
+                )
+                .mutual_attestation(true)?
+                .session_freshness(std::time::Duration::from_secs(
+                    teaclave_config::build::GRPC_CONFIG.session_freshness_secs,
+                ))
                 .into();
 
             let dst = advertised_address.parse::<teaclave_rpc::transport::Uri>()?;
@@ -147,7 +214,17 @@ macro_rules! impl_create_trusted_endpoint_fn {
             };
             let endpoint = teaclave_rpc::transport::Channel::builder(dst)
                 .tls_config(client_tls_config)?
-                .connect_timeout(std::time::Duration::from_secs(30));
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(std::time::Duration::from_secs(
+                    teaclave_config::build::GRPC_CONFIG.request_timeout_secs,
+                ))
+                .http2_keep_alive_interval(std::time::Duration::from_secs(
+                    teaclave_config::build::GRPC_CONFIG.keepalive_interval_secs,
+                ))
+                .keep_alive_timeout(std::time::Duration::from_secs(
+                    teaclave_config::build::GRPC_CONFIG.keepalive_timeout_secs,
+                ))
+                .keep_alive_while_idle(true);
             Ok(endpoint)
         }
     };
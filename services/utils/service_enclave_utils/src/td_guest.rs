@@ -0,0 +1,77 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Launcher of the services running as processes of a TDX guest, where the
+//! whole guest is attested and there is no app hosting an enclave. The
+//! service runs in a thread of its own like in the enclave, and is drained
+//! and finalized when the process is terminated or the service stops.
+
+use crate::ServiceEnclave;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use teaclave_config::RuntimeConfig;
+use tokio::signal::unix::{signal, SignalKind};
+
+const RUNTIME_CONFIG_PATH: &str = "runtime.config.toml";
+
+/// Runs the service with `run_service` until SIGTERM or SIGINT, calling
+/// `before_finalize` after the servers are drained, like the finalizing
+/// ecall of the enclave does.
+pub fn launch<F>(package_name: &str, run_service: F, before_finalize: fn()) -> Result<()>
+where
+    F: FnOnce(&RuntimeConfig) -> Result<()> + Send + 'static,
+{
+    ServiceEnclave::init(package_name)
+        .map_err(|e| anyhow!("Failed to initialize {}: {:?}", package_name, e))?;
+    let config =
+        RuntimeConfig::from_toml(RUNTIME_CONFIG_PATH).context("Failed to load config file.")?;
+
+    let (stopped_tx, stopped_rx) = tokio::sync::oneshot::channel();
+    let service = std::thread::spawn(move || {
+        let result = run_service(&config);
+        let _ = stopped_tx.send(());
+        result
+    });
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            let mut terminate = signal(SignalKind::terminate())?;
+            let mut interrupt = signal(SignalKind::interrupt())?;
+            tokio::select! {
+                _ = terminate.recv() => info!("Received SIGTERM"),
+                _ = interrupt.recv() => info!("Received SIGINT"),
+                _ = stopped_rx => info!("Service stopped"),
+            }
+            Ok::<_, std::io::Error>(())
+        })
+        .context("Failed to wait for signals")?;
+
+    ServiceEnclave::drain();
+    before_finalize();
+    ServiceEnclave::finalize().map_err(|e| anyhow!("Failed to finalize: {:?}", e))?;
+
+    // Threads of the service running until the end, e.g., the daemon of the
+    // scheduler, exit with the process.
+    if service.is_finished() {
+        service
+            .join()
+            .map_err(|_| anyhow!("The service thread panicked"))??;
+    }
+    Ok(())
+}
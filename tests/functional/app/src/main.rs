@@ -15,17 +15,38 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use anyhow::{bail, Context};
 use log::error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use structopt::StructOpt;
 use teaclave_binder::proto::{ECallCommand, RunTestInput, RunTestOutput};
 use teaclave_binder::TeeBinder;
+use teaclave_test_env::{EnvironmentBuilder, Service};
 use teaclave_types::TeeServiceResult;
 
+// Tests which need the execution service, which is started after the other
+// tests have run.
+const EXECUTION_TESTS: &[&str] = &["execution_service", "end_to_end"];
+
 #[derive(Debug, StructOpt)]
 struct Cli {
     /// Names of tests to execute.
     #[structopt(short = "t", required = false)]
     test_names: Vec<String>,
+
+    /// Start the services installed in this directory before running tests
+    /// and stop them afterwards.
+    #[structopt(long = "services-dir")]
+    services_dir: Option<PathBuf>,
+
+    /// Keep the working directory of the started services.
+    #[structopt(long = "keep-work-dir")]
+    keep_work_dir: bool,
+
+    /// Command to run after the tests while the started services are running.
+    #[structopt(last = true)]
+    command: Vec<String>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -36,12 +57,49 @@ fn main() -> anyhow::Result<()> {
             .write_style_or("TEACLAVE_LOG_STYLE", "RUST_LOG_STYLE"),
     );
     let tee = TeeBinder::new(env!("CARGO_PKG_NAME"))?;
-    start_enclave_unit_test_driver(&tee, args.test_names)?;
+    match args.services_dir {
+        Some(ref services_dir) => run_with_services(&tee, services_dir, &args)?,
+        None => start_enclave_unit_test_driver(&tee, args.test_names)?,
+    }
     tee.finalize();
 
     Ok(())
 }
 
+fn run_with_services(tee: &TeeBinder, services_dir: &Path, args: &Cli) -> anyhow::Result<()> {
+    let (execution_tests, other_tests): (Vec<String>, Vec<String>) = args
+        .test_names
+        .iter()
+        .cloned()
+        .partition(|name| EXECUTION_TESTS.contains(&name.as_str()));
+
+    let mut env = EnvironmentBuilder::new(services_dir)
+        .without(Service::Execution)
+        .keep_work_dir(args.keep_work_dir)
+        .start()?;
+    if !other_tests.is_empty() {
+        start_enclave_unit_test_driver(tee, other_tests)?;
+    }
+
+    env.start_service(Service::Execution)?;
+    // All tests are run if none is named.
+    if !execution_tests.is_empty() || args.test_names.is_empty() {
+        start_enclave_unit_test_driver(tee, execution_tests)?;
+    }
+
+    if let Some((program, program_args)) = args.command.split_first() {
+        let status = Command::new(program)
+            .args(program_args)
+            .status()
+            .with_context(|| format!("Cannot run {}", program))?;
+        if !status.success() {
+            bail!("{} exited with {}", program, status);
+        }
+    }
+
+    Ok(())
+}
+
 fn start_enclave_unit_test_driver(tee: &TeeBinder, test_names: Vec<String>) -> anyhow::Result<()> {
     let cmd = ECallCommand::RunTest;
     let input = RunTestInput::new(test_names);
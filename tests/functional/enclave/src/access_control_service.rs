@@ -64,3 +64,27 @@ async fn test_concurrency() {
         assert!(thr.join().is_ok());
     }
 }
+
+#[async_test_case]
+async fn test_authorize_batch() {
+    let mut client = get_access_control_client().await;
+    let object_id_list = vec![
+        "function-00000000-0000-0000-0000-000000000001".to_owned(),
+        "input-00000000-0000-0000-0000-000000000001".to_owned(),
+        "output-00000000-0000-0000-0000-000000000001".to_owned(),
+    ];
+    let request = AuthorizeBatchRequest {
+        subject_task_id: "task-00000000-0000-0000-0000-000000000001".to_owned(),
+        subject_user_id_list: vec!["mock_user1".to_owned(), "mock_user2".to_owned()],
+        object_id_list: object_id_list.clone(),
+    };
+    let response = client.authorize_batch(request).await.unwrap().into_inner();
+    assert!(response.accept);
+    let decided: Vec<_> = response
+        .decisions
+        .iter()
+        .map(|d| d.object_id.clone())
+        .collect();
+    assert_eq!(decided, object_id_list);
+    assert_eq!(response.denials().count(), 0);
+}
@@ -84,6 +84,74 @@ async fn test_login_fail() {
     assert!(response_result.is_err());
 }
 
+#[async_test_case]
+async fn test_login_unknown_provider() {
+    let mut client = get_api_client().await;
+    let request = UserLoginRequest::new("admin", "teaclave").provider("saml");
+    let response_result = client.user_login(request).await;
+    assert!(response_result.is_err());
+
+    // The passwords of local users are verified by the service
+    let request = UserLoginRequest::new("admin", "teaclave");
+    let response = client.user_login(request).await.unwrap().into_inner();
+    assert_eq!(response.id, "admin");
+}
+
+#[async_test_case]
+async fn test_totp_enrollment() {
+    let mut client = get_api_client_with_admin_credential().await;
+    let request = UserRegisterRequest::new("test_totp_id", "test_password", "PlatformAdmin", "");
+    client.user_register(request).await.unwrap();
+
+    let mut client = create_authentication_api_client_with_credential(
+        shared_enclave_info(),
+        AUTH_SERVICE_ADDR,
+        "test_totp_id",
+        "test_password",
+    )
+    .await
+    .unwrap();
+    let request = GenerateTotpSecretRequest::new("");
+    let response = client
+        .generate_totp_secret(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.secret.len(), 32);
+    assert!(response
+        .uri
+        .starts_with("otpauth://totp/Teaclave:test_totp_id?"));
+
+    // The pending secret is not enrolled until a code of it is verified
+    let request = VerifyTotpRequest::new("abcdef");
+    assert!(client.verify_totp(request).await.is_err());
+    let mut client = get_api_client().await;
+    let request = UserLoginRequest::new("test_totp_id", "test_password");
+    assert!(client.user_login(request).await.is_ok());
+}
+
+#[async_test_case]
+async fn test_force_password_reset() {
+    let mut admin_client = get_api_client_with_admin_credential().await;
+    let request =
+        UserRegisterRequest::new("test_force_reset_id", "test_password", "PlatformAdmin", "");
+    admin_client.user_register(request).await.unwrap();
+    let request = ForcePasswordResetRequest::new("test_force_reset_id");
+    admin_client.force_password_reset(request).await.unwrap();
+
+    let mut client = get_api_client().await;
+    let request = UserLoginRequest::new("test_force_reset_id", "test_password");
+    assert!(client.user_login(request).await.is_err());
+    // The new password is checked against the policy
+    let request = ChangePasswordRequest::new("test_force_reset_id", "test_password", "short");
+    assert!(client.change_password(request).await.is_err());
+    let request =
+        ChangePasswordRequest::new("test_force_reset_id", "test_password", "updated_password");
+    client.change_password(request).await.unwrap();
+    let request = UserLoginRequest::new("test_force_reset_id", "updated_password");
+    assert!(client.user_login(request).await.is_ok());
+}
+
 #[async_test_case]
 async fn test_authenticate_success() {
     let mut api_client = get_api_client_with_admin_credential().await;
@@ -133,6 +201,37 @@ async fn test_authenticate_fail() {
     assert!(response_result.is_err());
 }
 
+#[async_test_case]
+async fn test_list_revocations() {
+    let mut api_client = get_api_client_with_admin_credential().await;
+    let mut internal_client = get_internal_client().await;
+
+    let request = UserRegisterRequest::new("test_revocation_id", "test_password", "DataOwner", "");
+    api_client.user_register(request).await.unwrap();
+
+    let request = ListRevocationsRequest::new("", 0);
+    let response = internal_client
+        .list_revocations(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!response.complete);
+
+    let request = DeleteUserRequest::new("test_revocation_id");
+    api_client.delete_user(request).await.unwrap();
+
+    let request = ListRevocationsRequest::new(response.epoch, response.latest);
+    let response = internal_client
+        .list_revocations(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.complete);
+    assert!(response
+        .user_ids
+        .contains(&"test_revocation_id".to_string()));
+}
+
 #[async_test_case]
 async fn test_register_success() {
     let mut client = get_api_client_with_admin_credential().await;
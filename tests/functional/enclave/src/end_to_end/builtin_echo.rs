@@ -17,6 +17,8 @@
 
 use super::*;
 use futures::FutureExt;
+use teaclave_attestation::verifier;
+use teaclave_config::build::AS_ROOT_CA_CERT;
 use teaclave_test_utils::async_test_case;
 #[async_test_case]
 pub async fn test_echo_task_success() {
@@ -73,4 +75,81 @@ pub async fn test_echo_task_success() {
     // Get Task
     let ret_val = get_task_until(&mut client, &task_id, TaskStatus::Finished).await;
     assert_eq!(&ret_val, "Hello From Teaclave!");
+
+    // The result comes with a receipt signed by the execution service
+    let response = get_task(&mut client, &task_id).await;
+    let receipt = match teaclave_types::TaskResult::try_from(response.result).unwrap() {
+        TaskResult::Ok(outputs) => outputs.receipt.unwrap(),
+        _ => unreachable!(),
+    };
+    assert_eq!(receipt.task_id, task_id.uuid.to_string());
+    assert!(receipt.matches(&[], b"Hello From Teaclave!"));
+    let enclave_attr = shared_enclave_info()
+        .get_enclave_attr("teaclave_execution_service")
+        .unwrap();
+    let verifier = verifier::AttestationReportVerifier::new(
+        vec![enclave_attr],
+        AS_ROOT_CA_CERT,
+        verifier::universal_quote_verifier,
+    );
+    assert!(verifier.verify_receipt(&receipt).is_ok());
+}
+
+#[async_test_case]
+pub async fn test_echo_task_with_encrypted_payload() {
+    let mut api_client = create_authentication_api_client(shared_enclave_info(), AUTH_SERVICE_ADDR)
+        .await
+        .unwrap();
+    let cred = login(&mut api_client, USERNAME, TEST_PASSWORD)
+        .await
+        .unwrap();
+    let mut client = create_frontend_client(shared_enclave_info(), FRONTEND_SERVICE_ADDR, cred)
+        .await
+        .unwrap();
+
+    // The payload is decrypted by the execution service before running the
+    // function, even though builtin functions do not use it.
+    let crypto = FileCrypto::new("aes-gcm-128", &[1; 16], &[2; 12]).unwrap();
+    let mut payload = b"proprietary payload".to_vec();
+    crypto.encrypt_in_memory(&mut payload, None).unwrap();
+    let mut tampered = payload.clone();
+    tampered[0] ^= 1;
+
+    for (payload, status) in [
+        (payload, TaskStatus::Finished),
+        (tampered, TaskStatus::Failed),
+    ] {
+        let request = RegisterFunctionRequestBuilder::new()
+            .name("builtin-echo")
+            .description("Native Echo Function")
+            .payload(payload.clone())
+            .payload_crypto(crypto)
+            .arguments(vec![FunctionArgument::new("message", "", true)])
+            .build();
+        let response = client
+            .register_function(request)
+            .await
+            .unwrap()
+            .into_inner();
+        let function_id: ExternalID = response.function_id.try_into().unwrap();
+
+        // Only the ciphertext is stored, and the key is never returned
+        let request = GetFunctionRequest::new(function_id.clone());
+        let response = client.get_function(request).await.unwrap().into_inner();
+        assert!(response.payload_encrypted);
+        assert_eq!(response.payload, payload);
+
+        let request = CreateTaskRequest::new()
+            .function_id(function_id)
+            .function_arguments(hashmap!("message" => "Hello From Teaclave!"))
+            .executor(Executor::Builtin);
+        let response = client.create_task(request).await.unwrap().into_inner();
+        let task_id = response.task_id.try_into().unwrap();
+        invoke_task(&mut client, &task_id).await.unwrap();
+
+        let ret_val = get_task_until(&mut client, &task_id, status.clone()).await;
+        if status == TaskStatus::Finished {
+            assert_eq!(&ret_val, "Hello From Teaclave!");
+        }
+    }
 }
@@ -222,6 +222,73 @@ pub async fn test_data_fusion_success() {
     invoke_task(&mut c2, &task_id).await.unwrap();
     let ret_val = get_task_until(&mut c2, &task_id, TaskStatus::Finished).await;
     assert_eq!(&ret_val, "2");
+
+    // Verify the fusion output before releasing it
+    let function_id = register_release_verifier(&mut c1).await;
+    let arguments = serde_json::json!({"quasi_identifiers": [], "k": 0, "min_records": 5});
+    let verdict = verify_release(&mut c1, &mut c2, &function_id, fusion_id, arguments).await;
+    assert!(verdict.releasable);
+
+    // Every line is unique, which violates 2-anonymity
+    let arguments = serde_json::json!({"quasi_identifiers": [0], "k": 2, "min_records": 0});
+    let verdict = verify_release(&mut c1, &mut c2, &function_id, fusion_id, arguments).await;
+    assert!(!verdict.releasable);
+    assert!(!verdict.task_id.is_empty());
+
+    // A refusal is not overridden by looser thresholds
+    let arguments = serde_json::json!({"quasi_identifiers": [], "k": 0, "min_records": 0});
+    let verdict = verify_release(&mut c1, &mut c2, &function_id, fusion_id, arguments).await;
+    assert!(!verdict.releasable);
+}
+
+async fn register_release_verifier(client: &mut FrontendClient) -> ExternalID {
+    let arguments = vec![
+        FunctionArgument::new("quasi_identifiers", "", true),
+        FunctionArgument::new("k", "", true),
+        FunctionArgument::new("min_records", "", true),
+    ];
+    let request = RegisterFunctionRequestBuilder::new()
+        .name("builtin-k-anonymity-verify")
+        .description("Native K-Anonymity Verification Function")
+        .arguments(arguments)
+        .inputs(vec![FunctionInput::new("data", "Output to verify", false)])
+        .public(true)
+        .build();
+    let response = client
+        .register_function(request)
+        .await
+        .unwrap()
+        .into_inner();
+    response.function_id.try_into().unwrap()
+}
+
+// Runs the verifier on the fusion output owned by both users and returns the
+// verdict recorded on the output.
+async fn verify_release(
+    c1: &mut FrontendClient,
+    c2: &mut FrontendClient,
+    function_id: &ExternalID,
+    fusion_id: &ExternalID,
+    arguments: serde_json::Value,
+) -> teaclave_proto::teaclave_frontend_service::ReleaseVerdict {
+    let fusion_input = register_fusion_input_from_output(c1, fusion_id).await;
+    let request = CreateTaskRequest::new()
+        .function_id(function_id.to_owned())
+        .function_arguments(FunctionArguments::from_json(arguments).unwrap())
+        .inputs_ownership(hashmap!("data" => vec![USERNAME1, USERNAME2]))
+        .executor(Executor::Builtin);
+    let response = c1.create_task(request).await.unwrap().into_inner();
+    let task_id = response.task_id.try_into().unwrap();
+    assign_data_for_task(c1, &task_id, hashmap!("data" => fusion_input), hashmap!()).await;
+
+    approve_task(c1, &task_id).await.unwrap();
+    approve_task(c2, &task_id).await.unwrap();
+    invoke_task(c1, &task_id).await.unwrap();
+    get_task_until(c1, &task_id, TaskStatus::Finished).await;
+
+    let request = GetOutputFileRequest::new(fusion_id.clone());
+    let response = c2.get_output_file(request).await.unwrap().into_inner();
+    response.release_verdict.unwrap()
 }
 
 async fn register_fusion_input_from_output(
@@ -17,6 +17,7 @@
 
 use crate::utils::*;
 use futures::FutureExt;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use teaclave_proto::teaclave_common::*;
 use teaclave_proto::teaclave_common::{ExecutorCommand, ExecutorStatus};
@@ -204,6 +205,14 @@ async fn test_register_function() {
     let mut client = authorized_client().await;
     let response = client.register_function(request).await;
     assert!(response.is_ok());
+    let trace_id = response
+        .unwrap()
+        .metadata()
+        .get("trace_id")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
 
     let request = RegisterFunctionRequestBuilder::new().build();
     let mut client = unauthorized_client().await;
@@ -232,6 +241,16 @@ async fn test_register_function() {
         .collect();
     assert_eq!(logs.len(), 1);
     assert!(logs[0].result());
+    assert_eq!(logs[0].trace_id(), trace_id);
+
+    // query by the trace id returned to the client
+    let request = QueryAuditLogsRequest::new("trace_id:".to_string() + &trace_id, 100);
+    let response = authorized_client()
+        .await
+        .query_audit_logs(request)
+        .await
+        .unwrap();
+    assert_eq!(response.into_inner().logs.len(), 1);
 
     // query by function name stored in the message
     let request = QueryAuditLogsRequest::new("message:".to_string() + function_name, 100);
@@ -252,6 +271,18 @@ async fn test_register_function() {
 
     assert!(!logs[1].result());
 
+    // paginate from the latest logs
+    let request = QueryAuditLogsRequest::new("message:".to_string() + function_name, 1).offset(1);
+    let response = authorized_client()
+        .await
+        .query_audit_logs(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.total, 2);
+    assert_eq!(response.logs.len(), 1);
+    assert!(!Entry::try_from(response.logs[0].clone()).unwrap().result());
+
     let request = QueryAuditLogsRequest::new("message:".to_string() + "authenticate", 100);
     let response = authorized_client()
         .await
@@ -269,6 +300,64 @@ async fn test_register_function() {
         assert_eq!(log.user(), "");
         assert!(!log.result());
     }
+
+    // export the logs of the request to a registered output as CSV
+    let url = Url::parse(&format!("file:///tmp/audit_export_{}.csv", Uuid::new_v4())).unwrap();
+    let request = RegisterOutputFileRequest::new(url, FileCrypto::default());
+    let mut client = authorized_client().await;
+    let output_id = client
+        .register_output_file(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .data_id;
+    let output_id = ExternalID::try_from(output_id).unwrap();
+
+    let columns = vec!["date".to_string(), "message".to_string()];
+    let request =
+        ExportAuditLogsRequest::new("trace_id:".to_string() + &trace_id, 0, output_id.clone())
+            .format("csv")
+            .columns(columns);
+    let response = client.export_audit_logs(request.clone()).await.unwrap();
+    assert_eq!(response.into_inner().rows, 1);
+
+    // the output has been written already
+    assert!(client.export_audit_logs(request).await.is_err());
+
+    let request = GetOutputFileRequest::new(output_id);
+    let response = client.get_output_file(request).await.unwrap();
+    assert!(!response.into_inner().cmac.is_empty());
+}
+
+#[async_test_case]
+async fn test_export_metadata() {
+    let url = Url::parse(&format!(
+        "file:///tmp/metadata_export_{}.json",
+        Uuid::new_v4()
+    ))
+    .unwrap();
+    let request = RegisterOutputFileRequest::new(url, FileCrypto::default());
+    let mut client = authorized_client().await;
+    let output_id = client
+        .register_output_file(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .data_id;
+    let output_id = ExternalID::try_from(output_id).unwrap();
+
+    let request = ExportMetadataRequest::new(output_id.clone());
+    let response = client.export_metadata(request.clone()).await.unwrap();
+    let response = response.into_inner();
+    assert_eq!(response.version, METADATA_DUMP_VERSION);
+    assert!(response.output_files > 0);
+
+    // the output has been written already
+    assert!(client.export_metadata(request).await.is_err());
+
+    let mut client = unauthorized_client().await;
+    let request = ExportMetadataRequest::new(output_id);
+    assert!(client.export_metadata(request).await.is_err());
 }
 
 #[async_test_case]
@@ -408,6 +497,11 @@ async fn test_approve_task() {
     let response = unauthorized_client.approve_task(request).await;
     assert!(response.is_err());
 
+    // No input is assigned with the pinned content
+    let request = ApproveTaskRequest::new(task_id.clone()).input_cmac("input", FileAuthTag::mock());
+    let response = client.approve_task(request).await;
+    assert!(response.is_err());
+
     let request = ApproveTaskRequest::new(task_id);
     let response = client.approve_task(request).await;
     assert!(response.is_ok());
@@ -462,7 +556,10 @@ async fn test_invoke_task() {
 
     std::thread::sleep(std::time::Duration::from_secs(2));
 
-    let pull_task_request = PullTaskRequest { executor_id };
+    let pull_task_request = PullTaskRequest {
+        executor_id,
+        ..Default::default()
+    };
     let response = scheduler_client.pull_task(pull_task_request).await;
     assert!(response.is_ok());
 }
@@ -516,6 +613,7 @@ async fn test_cancel_task() {
         .unwrap()
         .into_inner();
     assert!(response.command == ExecutorCommand::NewTask as i32);
+    assert!(response.queue_depth >= 1);
 
     let request = CancelTaskRequest::new(task_id.clone());
     let response = client.cancel_task(request).await;
@@ -525,6 +623,7 @@ async fn test_cancel_task() {
 
     let pull_task_request = PullTaskRequest {
         executor_id: executor_id.to_string(),
+        ..Default::default()
     };
     let response = scheduler_client.pull_task(pull_task_request).await;
     log::debug!("response: {:?}", response);
@@ -590,6 +689,7 @@ async fn test_fail_task() {
 
     let pull_task_request = PullTaskRequest {
         executor_id: executor_id.to_string(),
+        ..Default::default()
     };
     let response = scheduler_client.pull_task(pull_task_request).await.unwrap();
     log::debug!("response: {:?}", response);
@@ -610,3 +710,272 @@ async fn test_fail_task() {
 
     assert_eq!(response.status, i32_from_task_status(TaskStatus::Failed));
 }
+
+#[async_test_case]
+async fn test_user_quota() {
+    let username = "frontend_quota_user";
+    let mut api_client = get_api_client_with_admin_credential().await;
+    let _ = register_new_account(
+        &mut api_client,
+        username,
+        TEST_PASSWORD,
+        "DataOwner",
+        "QuotaOrg",
+    )
+    .await;
+
+    let request = SetUserQuotaRequest::new(username, UserQuota::new(0, 0, 2));
+    let mut admin_client = authorized_client().await;
+    let response = admin_client.set_user_quota(request).await;
+    assert!(response.is_ok());
+
+    let mut api_client = create_authentication_api_client(shared_enclave_info(), AUTH_SERVICE_ADDR)
+        .await
+        .unwrap();
+    let cred = login(&mut api_client, username, TEST_PASSWORD)
+        .await
+        .unwrap();
+    let mut client = create_frontend_client(shared_enclave_info(), FRONTEND_SERVICE_ADDR, cred)
+        .await
+        .unwrap();
+
+    // Only admins can adjust quotas
+    let request = SetUserQuotaRequest::new(username, UserQuota::new(0, 0, 0));
+    let response = client.set_user_quota(request).await;
+    assert!(response.is_err());
+
+    let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
+    for _ in 0..2 {
+        let request =
+            RegisterInputFileRequest::new(url.clone(), FileAuthTag::mock(), FileCrypto::default());
+        let response = client.register_input_file(request).await;
+        assert!(response.is_ok());
+    }
+    let request = RegisterInputFileRequest::new(url, FileAuthTag::mock(), FileCrypto::default());
+    let response = client.register_input_file(request).await;
+    assert_eq!(
+        response.unwrap_err().code(),
+        teaclave_rpc::Code::ResourceExhausted
+    );
+
+    let request = GetUserQuotaRequest::new(username);
+    let response = admin_client
+        .get_user_quota(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.quota.unwrap().max_requests_per_minute, 2);
+    assert_eq!(response.registered_data, 2);
+    assert_eq!(response.requests_in_last_minute, 2);
+}
+
+#[async_test_case]
+async fn test_grant_debug_errors() {
+    let username = "frontend_debug_errors_user";
+    let mut api_client = get_api_client_with_admin_credential().await;
+    let _ = register_new_account(
+        &mut api_client,
+        username,
+        TEST_PASSWORD,
+        "DataOwner",
+        "DebugOrg",
+    )
+    .await;
+
+    let mut admin_client = authorized_client().await;
+    let request = GrantDebugErrorsRequest::new(username, 600);
+    let response = admin_client.grant_debug_errors(request).await;
+    assert!(response.unwrap().into_inner().expires_at > 0);
+
+    // Grants are time-limited
+    let request = GrantDebugErrorsRequest::new(username, 365 * 24 * 60 * 60);
+    let response = admin_client.grant_debug_errors(request).await;
+    assert_eq!(
+        response.unwrap_err().code(),
+        teaclave_rpc::Code::InvalidArgument
+    );
+
+    // Only admins can grant detailed errors
+    let mut api_client = create_authentication_api_client(shared_enclave_info(), AUTH_SERVICE_ADDR)
+        .await
+        .unwrap();
+    let cred = login(&mut api_client, username, TEST_PASSWORD)
+        .await
+        .unwrap();
+    let mut client = create_frontend_client(shared_enclave_info(), FRONTEND_SERVICE_ADDR, cred)
+        .await
+        .unwrap();
+    let request = GrantDebugErrorsRequest::new(username, 600);
+    assert!(client.grant_debug_errors(request).await.is_err());
+
+    let request = GrantDebugErrorsRequest::new(username, 0);
+    let response = admin_client.grant_debug_errors(request).await;
+    assert_eq!(response.unwrap().into_inner().expires_at, 0);
+}
+
+#[async_test_case]
+async fn test_get_metrics() {
+    let mut client = authorized_client().await;
+
+    let request = GetTaskRequest::new(
+        ExternalID::try_from("task-00000000-0000-0000-0000-000000000001").unwrap(),
+    );
+    let _ = client.get_task(request).await;
+
+    let response = client.get_metrics(GetMetricsRequest::default()).await;
+    let response = response.unwrap().into_inner();
+    assert_eq!(response.families.len(), 4);
+    let task = response
+        .families
+        .iter()
+        .find(|m| m.family == "task")
+        .unwrap();
+    assert!(task.requests > 0);
+}
+
+#[async_test_case]
+async fn test_get_api_version() {
+    let mut client = unauthorized_client().await;
+
+    let response = client.get_api_version(GetApiVersionRequest::new()).await;
+    let response = response.unwrap().into_inner();
+    assert_eq!(response.api_version, API_VERSION);
+    assert!(response.is_compatible());
+    assert!(response.executors.contains(&"builtin".to_string()));
+
+    let request = GetApiVersionRequest {
+        client_api_version: 0,
+    };
+    assert!(client.get_api_version(request).await.is_ok());
+}
+
+#[async_test_case]
+async fn test_list_tasks() {
+    let mut client = authorized_client().await;
+
+    let response = client.list_tasks(ListTasksRequest::new()).await;
+    let all = response.unwrap().into_inner();
+    assert_eq!(all.total as usize, all.tasks.len());
+
+    let request = ListTasksRequest::new().page(0, 1);
+    let page = client.list_tasks(request).await.unwrap().into_inner();
+    assert_eq!(page.total, all.total);
+    assert!(page.tasks.len() <= 1);
+
+    let request = ListTasksRequest::new().creator("nobody");
+    let response = client.list_tasks(request).await.unwrap().into_inner();
+    assert_eq!(response.total, 0);
+
+    let request = ListTasksRequest::new().status(TaskStatus::Created);
+    let created = client.list_tasks(request).await.unwrap().into_inner();
+    let status = i32_from_task_status(TaskStatus::Created);
+    assert!(created.tasks.iter().all(|t| t.status == status));
+    assert!(created.total <= all.total);
+
+    let request = ListTasksRequest::new().created_between(1, 2);
+    let response = client.list_tasks(request).await.unwrap().into_inner();
+    assert_eq!(response.total, 0);
+}
+
+#[async_test_case]
+async fn test_get_platform_stats() {
+    let mut client = authorized_client().await;
+
+    let response = client
+        .get_platform_stats(GetPlatformStatsRequest::default())
+        .await;
+    let stats = response.unwrap().into_inner();
+    let tasks: u64 = stats.tasks_by_status.iter().map(|c| c.count).sum();
+    assert_eq!(tasks, stats.tasks);
+    assert!(stats.max_queue_depth > 0);
+}
+
+#[async_test_case]
+async fn test_manage_policy() {
+    let mut client = authorized_client().await;
+    let rule = PolicyRule::new("p", ["rule_data_owner", "test_manage_policy"]);
+
+    let response = client
+        .manage_policy(ManagePolicyRequest::add_rule(rule.clone()))
+        .await;
+    assert!(response.unwrap().into_inner().rules.contains(&rule));
+
+    let response = client
+        .manage_policy(ManagePolicyRequest::list_rules())
+        .await;
+    assert!(response.unwrap().into_inner().rules.contains(&rule));
+
+    let response = client
+        .manage_policy(ManagePolicyRequest::remove_rule(rule.clone()))
+        .await;
+    assert!(!response.unwrap().into_inner().rules.contains(&rule));
+
+    let invalid_rule = PolicyRule::new("p", ["rule_data_owner"]);
+    let response = client
+        .manage_policy(ManagePolicyRequest::add_rule(invalid_rule))
+        .await;
+    assert_eq!(
+        response.unwrap_err().code(),
+        teaclave_rpc::Code::InvalidArgument
+    );
+
+    let mut client = unauthorized_client().await;
+    let response = client
+        .manage_policy(ManagePolicyRequest::list_rules())
+        .await;
+    assert!(response.is_err());
+}
+
+#[async_test_case]
+async fn test_data_attributes() {
+    let mut client = authorized_client().await;
+
+    let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
+    let request = RegisterOutputFileRequest::new(url, FileCrypto::default());
+    let response = client
+        .register_output_file(request)
+        .await
+        .unwrap()
+        .into_inner();
+    let data_id: ExternalID = response.data_id.try_into().unwrap();
+
+    let attributes: HashMap<String, String> =
+        [("classification".to_string(), "secret".to_string())].into();
+    let request = SetDataAttributesRequest::new(data_id.clone(), attributes.clone());
+    client.set_data_attributes(request).await.unwrap();
+
+    let request = GetDataAttributesRequest::new(data_id.clone());
+    let response = client.get_data_attributes(request).await.unwrap();
+    assert_eq!(response.into_inner().attributes, attributes);
+
+    // The user has no clearance yet
+    let request = RegisterInputFromOutputRequest::new(data_id.clone());
+    let response = client.register_input_from_output(request).await;
+    assert_eq!(
+        response.unwrap_err().code(),
+        teaclave_rpc::Code::PermissionDenied
+    );
+
+    let clearance: HashMap<String, String> =
+        [("clearance".to_string(), "secret".to_string())].into();
+    let request = SetUserAttributesRequest::new(USERNAME, clearance.clone());
+    client.set_user_attributes(request).await.unwrap();
+    let request = GetUserAttributesRequest::new(USERNAME);
+    let response = client.get_user_attributes(request).await.unwrap();
+    assert_eq!(response.into_inner().attributes, clearance);
+
+    let request = RegisterInputFromOutputRequest::new(data_id);
+    let response = client.register_input_from_output(request).await;
+    assert!(response.is_ok());
+
+    let request = SetUserAttributesRequest::new(USERNAME, HashMap::new());
+    client.set_user_attributes(request).await.unwrap();
+
+    let invalid: HashMap<String, String> = [("clearance".to_string(), "top".to_string())].into();
+    let request = SetUserAttributesRequest::new(USERNAME, invalid);
+    let response = client.set_user_attributes(request).await;
+    assert_eq!(
+        response.unwrap_err().code(),
+        teaclave_rpc::Code::InvalidArgument
+    );
+}
@@ -33,6 +33,7 @@ mod end_to_end;
 mod execution_service;
 mod frontend_service;
 mod management_service;
+mod proto_compatibility;
 mod scheduler_service;
 mod storage_service;
 mod utils;
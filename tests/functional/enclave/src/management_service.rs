@@ -21,7 +21,7 @@ use std::convert::TryFrom;
 use teaclave_proto::teaclave_common::i32_from_task_status;
 use teaclave_proto::teaclave_management_service::*;
 use teaclave_proto::teaclave_scheduler_service::*;
-use teaclave_rpc::CredentialService;
+use teaclave_rpc::{Code, CredentialService};
 use teaclave_test_utils::async_test_case;
 use teaclave_types::*;
 use url::Url;
@@ -65,6 +65,33 @@ async fn test_register_fusion_output() {
     assert!(response.is_err());
 }
 
+#[async_test_case]
+async fn test_register_fusion_output_with_policies() {
+    let policies = vec![
+        OutputPolicy::min_row_count(10, true),
+        OutputPolicy::laplace_noise([1, 2], 0.5, 1.0, true),
+    ];
+    let request = RegisterFusionOutputRequest::new(vec!["mock_user", "mock_user_b"])
+        .output_policies(policies.clone());
+    let mut client = authorized_client("mock_user").await;
+    let response = client.register_fusion_output(request).await.unwrap();
+    let data_id = ExternalID::try_from(response.into_inner().data_id).unwrap();
+
+    let request = GetOutputFileRequest::new(data_id);
+    let response = client.get_output_file(request).await.unwrap().into_inner();
+    let registered = response
+        .output_policies
+        .into_iter()
+        .map(|p| OutputPolicy::try_from(p).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(registered, policies);
+
+    let request = RegisterFusionOutputRequest::new(vec!["mock_user", "mock_user_b"])
+        .output_policies(vec![OutputPolicy::laplace_noise([1], 0.0, 1.0, true)]);
+    let response = client.register_fusion_output(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+}
+
 #[async_test_case]
 async fn test_register_input_from_output() {
     let user1_output_id =
@@ -92,6 +119,31 @@ async fn test_register_input_from_output() {
     assert!(response.is_ok());
 }
 
+#[async_test_case]
+async fn test_query_data_lineage() {
+    let output_id = ExternalID::try_from("output-00000000-0000-0000-0000-000000000001").unwrap();
+    let mut client = authorized_client("mock_user1").await;
+    let request = RegisterInputFromOutputRequest::new(output_id.clone());
+    let response = client.register_input_from_output(request).await.unwrap();
+    let input_id = ExternalID::try_from(response.into_inner().data_id).unwrap();
+
+    let request = QueryDataLineageRequest::new(input_id.clone());
+    let lineage = client
+        .query_data_lineage(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .lineage;
+    assert_eq!(lineage[0].data_id, input_id.to_string());
+    assert_eq!(lineage[0].output_id, output_id.to_string());
+
+    // not a owner
+    let request = QueryDataLineageRequest::new(input_id);
+    let mut client = authorized_client("mock_user_c").await;
+    let response = client.query_data_lineage(request).await;
+    assert!(response.is_err());
+}
+
 #[async_test_case]
 async fn test_get_output_file() {
     let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
@@ -151,6 +203,89 @@ async fn test_register_function() {
     assert!(response.is_ok());
 }
 
+#[async_test_case]
+async fn test_upload_function_in_chunks() {
+    let payload = b"def entrypoint(argv):\n    return 'uploaded in chunks'\n".to_vec();
+    let mut client = authorized_client("mock_user").await;
+    let request =
+        BeginFunctionUploadRequest::new(payload.len() as u64, FunctionUpload::digest(&payload));
+    let response = client.begin_function_upload(request).await.unwrap();
+    let response = response.into_inner();
+    assert!(response.max_chunk_size >= 16);
+    let upload_id = ExternalID::try_from(response.upload_id).unwrap();
+
+    let request = UploadFunctionChunkRequest::new(upload_id.clone(), 0, &payload[..16]);
+    let response = client.upload_function_chunk(request).await.unwrap();
+    assert_eq!(response.into_inner().received_size, 16);
+
+    // chunks are appended in order
+    let request = UploadFunctionChunkRequest::new(upload_id.clone(), 0, &payload[16..]);
+    let response = client.upload_function_chunk(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    // uploads are only continued by their owners
+    let mut other_client = authorized_client("mock_user_b").await;
+    let request = UploadFunctionChunkRequest::new(upload_id.clone(), 16, &payload[16..]);
+    let response = other_client.upload_function_chunk(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+
+    let function = RegisterFunctionRequestBuilder::new()
+        .name("mock_function")
+        .executor_type(ExecutorType::Python)
+        .public(true)
+        .build();
+    let request = CommitFunctionRequest::new(upload_id.clone(), function.clone());
+    let response = client.commit_function(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    let request = UploadFunctionChunkRequest::new(upload_id.clone(), 16, &payload[16..]);
+    client.upload_function_chunk(request).await.unwrap();
+    let request = CommitFunctionRequest::new(upload_id.clone(), function.clone());
+    let response = client.commit_function(request).await.unwrap();
+    let function_id = ExternalID::try_from(response.into_inner().function_id).unwrap();
+
+    let request = GetFunctionRequest::new(function_id);
+    let response = client.get_function(request).await.unwrap();
+    assert_eq!(response.into_inner().payload, payload);
+
+    // the upload is gone once committed
+    let request = CommitFunctionRequest::new(upload_id, function);
+    let response = client.commit_function(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::NotFound);
+}
+
+#[async_test_case]
+async fn test_register_encrypted_function() {
+    let crypto = FileCrypto::new("aes-gcm-256", &[1; 32], &[2; 12]).unwrap();
+    let mut payload = b"def entrypoint:\n\treturn".to_vec();
+    crypto.encrypt_in_memory(&mut payload, None).unwrap();
+    let request = RegisterFunctionRequestBuilder::new()
+        .name("mock_function")
+        .executor_type(ExecutorType::Python)
+        .payload(payload.clone())
+        .payload_crypto(crypto)
+        .public(true)
+        .build();
+
+    let mut client = authorized_client("mock_user").await;
+    let response = client.register_function(request).await.unwrap();
+    let function_id = ExternalID::try_from(response.into_inner().function_id).unwrap();
+    let request = GetFunctionRequest::new(function_id);
+    let response = client.get_function(request).await.unwrap().into_inner();
+    assert!(response.payload_encrypted);
+    assert_eq!(response.payload, payload);
+
+    // Payloads are encrypted as a whole, not as teaclave files
+    let request = RegisterFunctionRequestBuilder::new()
+        .name("mock_function")
+        .executor_type(ExecutorType::Python)
+        .payload(payload)
+        .payload_crypto(FileCrypto::default())
+        .build();
+    let response = client.register_function(request).await;
+    assert!(response.is_err());
+}
+
 #[async_test_case]
 async fn test_register_private_function() {
     let function_input = FunctionInput::new("input", "input_desc", false);
@@ -195,6 +330,97 @@ async fn test_delete_function() {
     assert!(response.is_ok());
 }
 
+fn register_referenced_function() -> RegisterFunctionRequest {
+    RegisterFunctionRequestBuilder::new()
+        .name("mock_function")
+        .executor_type(ExecutorType::Python)
+        .payload(b"def entrypoint:\n\treturn".to_vec())
+        .public(true)
+        .inputs(vec![FunctionInput::new("input", "input_desc", false)])
+        .build()
+}
+
+#[async_test_case]
+async fn test_delete_referenced_function() {
+    let mut client = authorized_client("mock_user").await;
+    let response = client
+        .register_function(register_referenced_function())
+        .await
+        .unwrap();
+    let function_id = ExternalID::try_from(response.into_inner().function_id).unwrap();
+
+    let request = CreateTaskRequest::new()
+        .function_id(function_id.clone())
+        .executor(Executor::MesaPy)
+        .inputs_ownership(hashmap!("input" => vec!["mock_user"]));
+    let response = client.create_task(request).await.unwrap();
+    let task_id = ExternalID::try_from(response.into_inner().task_id).unwrap();
+
+    // refused while the task is pending
+    let request = DeleteFunctionRequest::new(function_id.clone());
+    let status = client.delete_function(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    assert!(status.message().contains(&task_id.to_string()));
+
+    let request = DeleteFunctionRequest::new(function_id).cascade();
+    let response = client.delete_function(request).await;
+    assert!(response.is_ok());
+
+    let request = GetTaskRequest::new(task_id);
+    let response = client.get_task(request).await.unwrap().into_inner();
+    assert_eq!(response.status, i32_from_task_status(TaskStatus::Canceled));
+}
+
+#[async_test_case]
+async fn test_delete_data() {
+    let mut client = authorized_client("mock_user").await;
+    let response = client
+        .register_function(register_referenced_function())
+        .await
+        .unwrap();
+    let function_id = ExternalID::try_from(response.into_inner().function_id).unwrap();
+
+    let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
+    let request = RegisterInputFileRequest::new(url, FileAuthTag::mock(), FileCrypto::default());
+    let response = client.register_input_file(request).await.unwrap();
+    let data_id = ExternalID::try_from(response.into_inner().data_id).unwrap();
+
+    // only the owners delete the data
+    let mut other_client = authorized_client("mock_user1").await;
+    let request = DeleteDataRequest::new(data_id.clone());
+    let response = other_client.delete_data(request).await;
+    assert!(response.is_err());
+
+    let request = CreateTaskRequest::new()
+        .function_id(function_id)
+        .executor(Executor::MesaPy)
+        .inputs_ownership(hashmap!("input" => vec!["mock_user"]));
+    let response = client.create_task(request).await.unwrap();
+    let task_id = ExternalID::try_from(response.into_inner().task_id).unwrap();
+    let request = AssignDataRequest::new(
+        task_id.clone(),
+        hashmap!("input" => data_id.clone()),
+        hashmap!(),
+    );
+    client.assign_data(request).await.unwrap();
+
+    let request = DeleteDataRequest::new(data_id.clone());
+    let status = client.delete_data(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let request = DeleteDataRequest::new(data_id.clone()).cascade();
+    let response = client.delete_data(request).await;
+    assert!(response.is_ok());
+
+    let request = GetTaskRequest::new(task_id);
+    let response = client.get_task(request).await.unwrap().into_inner();
+    assert_eq!(response.status, i32_from_task_status(TaskStatus::Canceled));
+
+    let request = GetInputFileRequest::new(data_id);
+    let response = client.get_input_file(request).await;
+    assert!(response.is_err());
+}
+
 #[async_test_case]
 async fn test_disable_function() {
     let function_input = FunctionInput::new("input", "input_desc", false);
@@ -281,6 +507,47 @@ async fn test_list_functions() {
     assert!(response.is_ok());
 }
 
+#[async_test_case]
+async fn test_search_functions() {
+    let mut client = authorized_client("mock_user").await;
+    for version in ["1.0", "2.0"] {
+        let request = RegisterFunctionRequestBuilder::new()
+            .name("mock_versioned_function")
+            .executor_type(ExecutorType::Python)
+            .payload(b"def entrypoint:\n\treturn".to_vec())
+            .public(true)
+            .version(version)
+            .tags(vec!["mock_tag".to_string()])
+            .build();
+        let response = client.register_function(request).await;
+        assert!(response.is_ok());
+    }
+
+    // the same version cannot be registered twice
+    let request = RegisterFunctionRequestBuilder::new()
+        .name("mock_versioned_function")
+        .version("1.0")
+        .build();
+    let response = client.register_function(request).await;
+    assert!(response.is_err());
+
+    let request = SearchFunctionsRequest::new()
+        .name("mock_versioned_function")
+        .tag("mock_tag");
+    let response = client.search_functions(request).await.unwrap().into_inner();
+    assert_eq!(response.total, 2);
+    assert_eq!(response.functions[0].version, "1.0");
+    assert_eq!(response.functions[1].version, "2.0");
+
+    let request = SearchFunctionsRequest::new()
+        .name("mock_versioned_function")
+        .page(1, 1);
+    let response = client.search_functions(request).await.unwrap().into_inner();
+    assert_eq!(response.total, 2);
+    assert_eq!(response.functions.len(), 1);
+    assert_eq!(response.functions[0].version, "2.0");
+}
+
 #[async_test_case]
 async fn test_get_function() {
     let function_input = FunctionInput::new("input", "input_desc", false);
@@ -340,6 +607,40 @@ async fn test_get_function() {
     assert!(response.is_ok());
 }
 
+#[async_test_case]
+async fn test_query_function_usage_records() {
+    let request = RegisterFunctionRequestBuilder::new()
+        .name("mock_function")
+        .executor_type(ExecutorType::Python)
+        .payload(b"def entrypoint:\n\treturn".to_vec())
+        .public(true)
+        .build();
+
+    let mut client = authorized_client("mock_user").await;
+    let response = client
+        .register_function(request)
+        .await
+        .unwrap()
+        .into_inner();
+    let function_id = ExternalID::try_from(response.function_id).unwrap();
+
+    let request = QueryFunctionUsageRecordsRequest::new(function_id.clone()).time_range(0, 1);
+    let response = client.query_function_usage_records(request).await.unwrap();
+    assert!(response.into_inner().records.is_empty());
+
+    // mock_unauthorized_user is PlatformAdmin
+    let mut client = authorized_client("mock_unauthorized_user").await;
+    let request = QueryFunctionUsageRecordsRequest::new(function_id).limit(1);
+    let response = client.query_function_usage_records(request).await;
+    assert!(response.is_ok());
+
+    let function_id =
+        ExternalID::try_from("function-00000000-0000-0000-0000-0000000000ff").unwrap();
+    let request = QueryFunctionUsageRecordsRequest::new(function_id);
+    let response = client.query_function_usage_records(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+}
+
 fn create_valid_task_request() -> CreateTaskRequest {
     let function_id =
         ExternalID::try_from("function-00000000-0000-0000-0000-000000000001").unwrap();
@@ -358,6 +659,7 @@ fn create_valid_task_request() -> CreateTaskRequest {
         .executor(Executor::MesaPy)
         .inputs_ownership(input_owners)
         .outputs_ownership(output_owners)
+        .purpose("mock purpose")
 }
 
 fn create_valid_task_request_private_function() -> CreateTaskRequest {
@@ -432,6 +734,67 @@ async fn test_get_task() {
     }
 }
 
+#[async_test_case]
+async fn test_create_scheduled_task() {
+    let mut client = authorized_client("mock_user").await;
+
+    let request = create_valid_task_request().schedule("0 2 * *");
+    let response = client.create_task(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    let request = create_valid_task_request().schedule("0 2 * * *");
+    let response = client.create_task(request).await.unwrap();
+    let task_id = ExternalID::try_from(response.into_inner().task_id).unwrap();
+
+    let request = GetTaskRequest::new(task_id);
+    let response = client.get_task(request).await.unwrap().into_inner();
+    assert_eq!(response.schedule, "0 2 * * *");
+    // not invoked yet
+    assert_eq!(response.next_run_at, 0);
+    assert!(response.scheduled_runs.is_empty());
+    assert!(response.scheduled_from.is_empty());
+}
+
+#[async_test_case]
+async fn test_get_task_log() {
+    let mut client = authorized_client("mock_user").await;
+
+    let request = create_valid_task_request();
+    let response = client.create_task(request).await.unwrap();
+    let task_id = ExternalID::try_from(response.into_inner().task_id).unwrap();
+
+    // the task has not run yet
+    let request = GetTaskLogRequest::new(task_id.clone());
+    let response = client.get_task_log(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::NotFound);
+
+    // participants other than the creator cannot read the log
+    let mut client1 = authorized_client("mock_user1").await;
+    let request = GetTaskLogRequest::new(task_id);
+    let response = client1.get_task_log(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+}
+
+#[async_test_case]
+async fn test_register_webhook_sink() {
+    let mut client = authorized_client("mock_user").await;
+
+    let request = create_valid_task_request();
+    let response = client.create_task(request).await.unwrap();
+    let task_id = ExternalID::try_from(response.into_inner().task_id).unwrap();
+
+    let url = Url::parse("http://hooks.example.com/teaclave").unwrap();
+    let request = RegisterWebhookSinkRequest::new(task_id.clone(), url, "0123456789abcdef");
+    let response = client.register_webhook_sink(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    // no host is allowed outside the namespaces of data owners
+    let url = Url::parse("https://hooks.example.com/teaclave").unwrap();
+    let request = RegisterWebhookSinkRequest::new(task_id, url, "0123456789abcdef");
+    let response = client.register_webhook_sink(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+}
+
 #[async_test_case]
 async fn test_assign_data() {
     let mut client = authorized_client("mock_user").await;
@@ -718,11 +1081,182 @@ async fn test_approve_task() {
     let request = ApproveTaskRequest::new(task_id.clone());
     let response = client3.approve_task(request).await;
     assert!(response.is_ok());
+
+    // participants can only read their own consent record
+    let request = GetConsentRecordsRequest::new(task_id.clone());
+    let response = client1
+        .get_consent_records(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.records.len(), 1);
+    assert_eq!(response.records[0].owner, "mock_user1");
+    assert_eq!(response.records[0].data_ids.len(), 2);
+    assert_eq!(response.records[0].purpose, "mock purpose");
+
+    let request = GetConsentRecordsRequest::new(task_id.clone());
+    let response = unknown_client.get_consent_records(request).await;
+    assert!(response.is_err());
+
     let request = GetTaskRequest::new(task_id);
     let response = client2.get_task(request).await.unwrap().into_inner();
     assert_eq!(response.status, i32_from_task_status(TaskStatus::Approved));
 }
 
+#[async_test_case]
+async fn test_assign_data_by_access_policy() {
+    let mut client = authorized_client("mock_user").await;
+    let mut client1 = authorized_client("mock_user1").await;
+    let request = create_valid_task_request();
+    let response = client.create_task(request).await.unwrap().into_inner();
+    let task_id = ExternalID::try_from(response.task_id).unwrap();
+    let function_id =
+        ExternalID::try_from("function-00000000-0000-0000-0000-000000000001").unwrap();
+
+    let url = Url::parse("input://path").unwrap();
+    let request = RegisterInputFileRequest::new(url, FileAuthTag::mock(), FileCrypto::default());
+    let response = client1.register_input_file(request).await.unwrap();
+    let input_id = ExternalID::try_from(response.into_inner().data_id).unwrap();
+    let url = Url::parse("https://output_file_path").unwrap();
+    let request = RegisterOutputFileRequest::new(url, FileCrypto::default());
+    let response = client1.register_output_file(request).await.unwrap();
+    let output_id = ExternalID::try_from(response.into_inner().data_id).unwrap();
+
+    // only the owner sets the policy, with IDs of functions
+    let policy = InputAccessPolicy::new(vec![input_id.clone()], vec![]);
+    let request = SetInputAccessPolicyRequest::new(input_id.clone(), policy);
+    let response = client1.set_input_access_policy(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+    let policy = InputAccessPolicy::new(vec![function_id.clone()], vec![]);
+    let request = SetInputAccessPolicyRequest::new(input_id.clone(), policy.clone());
+    let response = client.set_input_access_policy(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+
+    // files disallowing the function cannot be assigned
+    let other_policy = InputAccessPolicy::new(vec![], vec![UserID::from("mock_user2")]);
+    let request = SetInputAccessPolicyRequest::new(input_id.clone(), other_policy);
+    client1.set_input_access_policy(request).await.unwrap();
+    let request = AssignDataRequest::new(
+        task_id.clone(),
+        hashmap!("input" => input_id.clone()),
+        hashmap!("output" => output_id.clone()),
+    );
+    let response = client1.assign_data(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+
+    let request = SetInputAccessPolicyRequest::new(input_id.clone(), policy);
+    client1.set_input_access_policy(request).await.unwrap();
+    let request = GetInputFileRequest::new(input_id.clone());
+    let response = client1.get_input_file(request).await.unwrap().into_inner();
+    assert_eq!(response.allowed_function_ids, vec![function_id.to_string()]);
+    assert!(response.allowed_function_owners.is_empty());
+
+    // assigning the allowed file approves the task for the owner
+    let request = AssignDataRequest::new(
+        task_id.clone(),
+        hashmap!("input" => input_id),
+        hashmap!("output" => output_id),
+    );
+    client1.assign_data(request).await.unwrap();
+    let request = GetTaskRequest::new(task_id.clone());
+    let response = client.get_task(request).await.unwrap().into_inner();
+    assert_eq!(response.status, i32_from_task_status(TaskStatus::Created));
+    let approval = response
+        .approvals
+        .iter()
+        .find(|a| a.user_id == "mock_user1")
+        .unwrap();
+    assert_eq!(approval.status, "approved");
+
+    let request = GetConsentRecordsRequest::new(task_id);
+    let response = client1
+        .get_consent_records(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.records.len(), 1);
+    assert_eq!(response.records[0].data_ids.len(), 2);
+}
+
+#[async_test_case]
+async fn test_reject_task() {
+    let mut client = authorized_client("mock_user").await;
+    let mut client1 = authorized_client("mock_user1").await;
+    let request = create_valid_task_request().approval_expiry(std::time::Duration::from_secs(600));
+    let response = client.create_task(request).await.unwrap().into_inner();
+    let task_id = ExternalID::try_from(response.task_id).unwrap();
+
+    // participants can reject before assigning data
+    let request = RejectTaskRequest::new(task_id.clone(), "purpose is too broad");
+    let response = client1.reject_task(request).await;
+    assert!(response.is_ok());
+
+    let mut unknown_client = authorized_client("non-participant").await;
+    let request = RejectTaskRequest::new(task_id.clone(), "");
+    let response = unknown_client.reject_task(request).await;
+    assert!(response.is_err());
+
+    // the creator can see who is blocking the task
+    let request = GetTaskRequest::new(task_id);
+    let response = client.get_task(request).await.unwrap().into_inner();
+    assert!(response.approval_deadline > 0);
+    let approval = response
+        .approvals
+        .iter()
+        .find(|a| a.user_id == "mock_user1")
+        .unwrap();
+    assert_eq!(approval.status, "rejected");
+    assert_eq!(approval.comment, "purpose is too broad");
+    let approval = response
+        .approvals
+        .iter()
+        .find(|a| a.user_id == "mock_user2")
+        .unwrap();
+    assert_eq!(approval.status, "pending");
+}
+
+#[async_test_case]
+async fn test_notification_digest() {
+    let mut client = authorized_client("mock_user").await;
+    let mut client1 = authorized_client("mock_user1").await;
+
+    let preferences = NotificationPreferences {
+        email: Some("mock_user1@example.com".to_string()),
+        slack_member_id: None,
+    };
+    let request = SetNotificationPreferencesRequest::new(preferences);
+    let response = client1.set_notification_preferences(request).await;
+    assert!(response.is_ok());
+
+    let preferences = NotificationPreferences {
+        email: Some("mock_user1@example.com\r\nBcc: eve@example.com".to_string()),
+        slack_member_id: None,
+    };
+    let request = SetNotificationPreferencesRequest::new(preferences);
+    let response = client1.set_notification_preferences(request).await;
+    assert!(response.is_err());
+
+    let request = create_valid_task_request();
+    let response = client.create_task(request).await.unwrap().into_inner();
+    let task_id = ExternalID::try_from(response.task_id).unwrap();
+    let request = CancelTaskRequest::new(task_id.clone());
+    let response = client.cancel_task(request).await;
+    assert!(response.is_ok());
+
+    // Only the participant who opted in gets a digest of the canceled task
+    let response = client.pull_notification_digests(()).await.unwrap();
+    let digests = response.into_inner().digests;
+    let digest = digests.iter().find(|d| d.user_id == "mock_user1").unwrap();
+    assert_eq!(digest.email, "mock_user1@example.com");
+    let event = digest
+        .events
+        .iter()
+        .find(|e| e.task_id == task_id.uuid.to_string())
+        .unwrap();
+    assert_eq!(event.status, i32_from_task_status(TaskStatus::Canceled));
+    assert!(!digests.iter().any(|d| d.user_id == "mock_user2"));
+}
+
 #[async_test_case]
 async fn test_invoke_task() {
     let mut client = authorized_client("mock_user").await;
@@ -819,7 +1353,161 @@ async fn test_invoke_task() {
 
     std::thread::sleep(std::time::Duration::from_secs(2));
 
-    let pull_task_request = PullTaskRequest { executor_id };
+    let pull_task_request = PullTaskRequest {
+        executor_id,
+        ..Default::default()
+    };
     let response = scheduler_client.pull_task(pull_task_request).await;
     assert!(response.is_ok());
 }
+
+#[async_test_case]
+async fn test_storage_decommission_status() {
+    let mut client = authorized_client("mock_user").await;
+    let request = GetStorageDecommissionStatusRequest::default();
+    let response = client
+        .get_storage_decommission_status(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.phase, "idle");
+    assert_eq!(response.transferred_entries, 0);
+
+    // The storage used by other tests must not be frozen, so only the
+    // validation of the request is checked here.
+    let request = DecommissionStorageRequest::new("");
+    let response = client.decommission_storage(request).await;
+    assert!(response.is_err());
+}
+
+#[async_test_case]
+async fn test_rebuild_audit_index() {
+    let mut client = authorized_client("mock_user").await;
+    let trace_id = Uuid::new_v4().to_simple().to_string();
+    let entry = EntryBuilder::new()
+        .message("rebuild_audit_index_test".to_owned())
+        .result(true)
+        .trace_id(trace_id.clone())
+        .build();
+    let request = SaveLogsRequest::new(vec![entry]);
+    client.save_logs(request).await.unwrap();
+
+    client
+        .rebuild_audit_index(RebuildAuditIndexRequest::default())
+        .await
+        .unwrap();
+    let mut status = GetAuditIndexStatusResponse::default();
+    for _ in 0..60 {
+        status = client
+            .get_audit_index_status(GetAuditIndexStatusRequest::default())
+            .await
+            .unwrap()
+            .into_inner();
+        if status.phase == "completed" || status.phase == "failed" {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    assert_eq!(status.phase, "completed", "{}", status.error);
+    assert!(status.available);
+    assert!(status.indexed_entries >= 1);
+
+    // The log is in the new index, once
+    let request = QueryAuditLogsRequest::new("trace_id:".to_string() + &trace_id, 10);
+    let response = client.query_audit_logs(request).await.unwrap();
+    assert_eq!(response.into_inner().total, 1);
+}
+
+#[async_test_case]
+async fn test_create_storage_snapshot() {
+    let mut client = authorized_client("mock_user").await;
+    let url = Url::parse("file:///tmp/storage_snapshot_functional_test.enc").unwrap();
+    let crypto_info = FileCrypto::new("aes-gcm-128", &[0x90u8; 16], &[0x89u8; 12]).unwrap();
+    let request = CreateStorageSnapshotRequest::new(url.clone(), crypto_info);
+    let response = client
+        .create_storage_snapshot(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.entries > 0);
+
+    // The storage used by other tests must not be rolled back, so only a
+    // restore with another tag is tried, which is rejected.
+    let request = RestoreStorageSnapshotRequest::new(url, crypto_info, FileAuthTag::mock());
+    let response = client.restore_storage_snapshot(request).await;
+    assert!(response.is_err());
+}
+
+#[async_test_case]
+async fn test_promote_storage_replica() {
+    let mut client = authorized_client("mock_user").await;
+    let request = PromoteStorageReplicaRequest::new("");
+    let response = client.promote_storage_replica(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+
+    // The storage service in the tests is no follower, and stays in use.
+    let runtime_config = teaclave_config::RuntimeConfig::from_toml("runtime.config.toml").unwrap();
+    let address = runtime_config.internal_endpoints.storage.advertised_address;
+    let request = PromoteStorageReplicaRequest::new(address);
+    let response = client.promote_storage_replica(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+    let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
+    let request = RegisterInputFileRequest::new(url, FileAuthTag::mock(), FileCrypto::default());
+    assert!(client.register_input_file(request).await.is_ok());
+}
+
+#[async_test_case]
+async fn test_maintenance_window() {
+    let mut client = authorized_client("mock_user").await;
+    let now = now_secs();
+
+    let request = DeclareMaintenanceWindowRequest::new(
+        now + 600,
+        now + 60,
+        MaintenanceMode::RejectTasks,
+        "upgrade",
+    );
+    let response = client.declare_maintenance_window(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    // Windows of other tests must not be active, so that they can invoke
+    // tasks; this one starts in an hour.
+    let request = DeclareMaintenanceWindowRequest::new(
+        now + 3600,
+        now + 7200,
+        MaintenanceMode::RejectTasks,
+        "upgrade",
+    );
+    let window_id = client
+        .declare_maintenance_window(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .window_id;
+
+    let response = client
+        .get_service_info(GetServiceInfoRequest::default())
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.active_window.is_none());
+    let window = response
+        .maintenance_windows
+        .iter()
+        .find(|w| w.window_id == window_id)
+        .unwrap();
+    assert_eq!(window.starts_at, now + 3600);
+    assert_eq!(window.reason, "upgrade");
+
+    let request = CancelMaintenanceWindowRequest::new(window_id.clone().try_into().unwrap());
+    client.cancel_maintenance_window(request).await.unwrap();
+    let response = client
+        .get_service_info(GetServiceInfoRequest::default())
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response
+        .maintenance_windows
+        .iter()
+        .all(|w| w.window_id != window_id));
+}
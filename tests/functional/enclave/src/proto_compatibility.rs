@@ -0,0 +1,188 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Compatibility of the proto messages with peers of other versions. The golden
+//! corpora under `tests/fixtures/proto` are maintained with
+//! `tests/scripts/proto_golden.py`.
+
+use futures::FutureExt;
+use prost::{DecodeError, Message};
+use std::collections::BTreeSet;
+use teaclave_test_utils::async_test_case;
+
+/// Corpora of the released versions, the current one last.
+const GOLDEN_CORPORA: &[(&str, &str)] =
+    &[("0.6.0", include_str!("../../../fixtures/proto/v0.6.0.txt"))];
+
+/// Field 1000 holding the string "x", standing for a field added by a newer
+/// version.
+const UNKNOWN_FIELD: &[u8] = &[0xc2, 0x3e, 0x01, b'x'];
+
+macro_rules! golden_messages {
+    ($($package:ident { $($message:ident),* $(,)? })*) => {
+        const MESSAGES: &[&str] = &[
+            $($(concat!(stringify!($package), ".", stringify!($message)),)*)*
+        ];
+
+        /// Decode the message of the full name and encode it again, or None if
+        /// no such message is defined.
+        fn round_trip(name: &str, bytes: &[u8]) -> Option<Result<Vec<u8>, DecodeError>> {
+            $($(
+                if name == concat!(stringify!($package), ".", stringify!($message)) {
+                    let message = teaclave_proto::$package::$message::decode(bytes);
+                    return Some(message.map(|m| m.encode_to_vec()));
+                }
+            )*)*
+            None
+        }
+    };
+}
+
+golden_messages! {
+    teaclave_access_control_service_proto {
+        AuthorizeApiRequest, AuthorizeApiResponse, AuthorizeBatchDecision, AuthorizeBatchRequest,
+        AuthorizeBatchResponse, AuthorizeDataRequest, AuthorizeDataResponse,
+        AuthorizeStagedTaskRequest, AuthorizeStagedTaskResponse, Denial,
+    }
+    teaclave_authentication_service_proto {
+        ChangePasswordRequest, DeleteUserRequest, ForcePasswordResetRequest,
+        GenerateTotpSecretRequest, GenerateTotpSecretResponse, ListRevocationsRequest,
+        ListRevocationsResponse, ListUsersRequest, ListUsersResponse, ResetUserPasswordRequest,
+        ResetUserPasswordResponse, UserAuthClaims, UserAuthenticateRequest,
+        UserAuthenticateResponse, UserChangePasswordRequest, UserLoginRequest, UserLoginResponse,
+        UserRegisterRequest, UserUpdateRequest, VerifyTotpRequest, VerifyTotpResponse,
+    }
+    teaclave_common_proto {
+        Entry, ExecutionReceipt, FileCryptoInfo, FileTransferRecord, ForensicBundleFile,
+        OutputArtifact, TaskFailure, TaskLogFile, TaskOutputs, TaskProfile, TaskProgress,
+        TaskResult, TaskUsage, UserCredential,
+    }
+    teaclave_frontend_service_proto {
+        ApproveTaskRequest, ArgumentList, ArgumentStruct, ArgumentValue, AssignDataRequest,
+        BeginFunctionUploadRequest, BeginFunctionUploadResponse, CancelMaintenanceWindowRequest,
+        CancelTaskRequest, ChannelMetrics, CommitFunctionRequest, ConsentRecord,
+        CreateStorageSnapshotRequest, CreateStorageSnapshotResponse, CreateTaskRequest,
+        CreateTaskResponse, DataLineage, DataMap, DeclareMaintenanceWindowRequest,
+        DeclareMaintenanceWindowResponse, DecommissionStorageRequest, DeleteDataRequest,
+        DeleteFunctionRequest, DeprecatedRpcMetrics, DisableFunctionRequest, ExportAuditLogsRequest,
+        ExportAuditLogsResponse, ExportMetadataRequest, ExportMetadataResponse, FunctionArgument,
+        FunctionInput, FunctionOutput, FunctionSummary, FunctionUsageRecord, GetApiVersionRequest,
+        GetApiVersionResponse, GetAuditIndexStatusRequest, GetAuditIndexStatusResponse,
+        GetConsentRecordsRequest, GetConsentRecordsResponse, GetDataAttributesRequest,
+        GetDataAttributesResponse, GetFunctionRequest, GetFunctionResponse,
+        GetFunctionUsageStatsRequest, GetFunctionUsageStatsResponse, GetInputFileRequest,
+        GetInputFileResponse, GetMetricsRequest, GetMetricsResponse, GetOutputFileRequest,
+        GetOutputFileResponse, GetPlatformStatsRequest, GetPlatformStatsResponse,
+        GetServiceInfoRequest, GetServiceInfoResponse, GetStorageDecommissionStatusRequest,
+        GetStorageDecommissionStatusResponse, GetTaskLogRequest, GetTaskLogResponse, GetTaskRequest,
+        GetTaskResponse, GetUserAttributesRequest, GetUserAttributesResponse, GetUserQuotaRequest,
+        GetUserQuotaResponse, GrantDebugErrorsRequest, GrantDebugErrorsResponse, InputCmac,
+        InvokeTaskRequest, InvokeTaskResponse, LaplaceNoisePolicy, ListFunctionsRequest,
+        ListFunctionsResponse, ListTasksRequest, ListTasksResponse, MaintenanceWindow,
+        ManagePolicyRequest, ManagePolicyResponse, MinRowCountPolicy, OutputPolicy, OwnerList,
+        PageCursor, ParticipantApproval, PolicyRule, PromoteStorageReplicaRequest,
+        PromoteStorageReplicaResponse, QueryAuditLogsRequest, QueryAuditLogsResponse,
+        QueryDataLineageRequest, QueryDataLineageResponse, QueryFunctionUsageRecordsRequest,
+        QueryFunctionUsageRecordsResponse, RebuildAuditIndexRequest, RegisterFunctionRequest,
+        RegisterFunctionResponse, RegisterFusionOutputRequest, RegisterFusionOutputResponse,
+        RegisterInputFileRequest, RegisterInputFileResponse, RegisterInputFromOutputRequest,
+        RegisterInputFromOutputResponse, RegisterOutputFileRequest, RegisterOutputFileResponse,
+        RegisterWebhookSinkRequest, RegisterWebhookSinkResponse, RejectTaskRequest, ReleaseVerdict,
+        ResourceLimits, RestoreStorageSnapshotRequest, RestoreStorageSnapshotResponse,
+        RpcFamilyMetrics, ScheduledRun, SearchFunctionsRequest, SearchFunctionsResponse,
+        SetDataAttributesRequest, SetInputAccessPolicyRequest, SetNotificationPreferencesRequest,
+        SetUserAttributesRequest, SetUserQuotaRequest, TaskStatusCount, TaskSummary, TypedArguments,
+        UpdateFunctionRequest, UpdateFunctionResponse, UpdateInputFileRequest,
+        UpdateInputFileResponse, UpdateOutputFileRequest, UpdateOutputFileResponse,
+        UploadFunctionChunkRequest, UploadFunctionChunkResponse, UserQuota,
+    }
+    teaclave_management_service_proto {
+        NotificationDigest, PullNotificationDigestsResponse, SaveLogsRequest, TaskEvent,
+        WebhookNotification,
+    }
+    teaclave_scheduler_service_proto {
+        HandoffTaskRequest, HeartbeatRequest, HeartbeatResponse, PublishTaskRequest,
+        PullTaskRequest, PullTaskResponse, ReportTaskProgressRequest, SubscribeResponse,
+        UpdateTaskResultRequest, UpdateTaskStatusRequest,
+    }
+    teaclave_storage_service_proto {
+        ApplyChangeLogRequest, ApplyChangeLogResponse, ChangeLogEntry, CompareAndSwapRequest,
+        CompareAndSwapResponse, ConsistencyToken, CreateSnapshotRequest, CreateSnapshotResponse,
+        DeleteRequest, DequeueRequest, DequeueResponse, EnqueueRequest, EntryInfo,
+        ExportSnapshotRequest, ExportSnapshotResponse, FreezeRequest, GetKeysByPrefixRequest,
+        GetKeysByPrefixResponse, GetQueueLengthRequest, GetQueueLengthResponse, GetRequest,
+        GetResponse, ImportSnapshotRequest, KeyValue, ListEntriesRequest, ListEntriesResponse,
+        Mutation, PromoteReplicaRequest, PromoteReplicaResponse, PutRequest, RestoreSnapshotRequest,
+        RestoreSnapshotResponse, WriteBatchRequest, WriteResponse,
+    }
+}
+
+fn read_corpus(corpus: &str) -> Vec<(&str, Vec<u8>)> {
+    corpus
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, encoded) = line.split_once(' ').unwrap_or((line, ""));
+            let bytes = hex::decode(encoded)
+                .unwrap_or_else(|_| panic!("invalid golden message of {}", name));
+            (name, bytes)
+        })
+        .collect()
+}
+
+#[async_test_case]
+async fn test_golden_corpus_coverage() {
+    let (_, corpus) = GOLDEN_CORPORA.last().unwrap();
+    let golden: BTreeSet<&str> = read_corpus(corpus)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    let defined: BTreeSet<&str> = MESSAGES.iter().copied().collect();
+    let missing: Vec<_> = defined.difference(&golden).collect();
+    assert!(missing.is_empty(), "no golden messages of {:?}", missing);
+    let removed: Vec<_> = golden.difference(&defined).collect();
+    assert!(removed.is_empty(), "{:?} are no longer defined", removed);
+}
+
+#[async_test_case]
+async fn test_golden_round_trip() {
+    for (version, corpus) in GOLDEN_CORPORA {
+        for (name, bytes) in read_corpus(corpus) {
+            let encoded = round_trip(name, &bytes)
+                .unwrap_or_else(|| panic!("{} of {} is no longer defined", name, version))
+                .unwrap_or_else(|e| panic!("cannot decode {} of {}: {}", name, version, e));
+            // A field removed, renumbered or changed to an incompatible type
+            // is either dropped or encoded differently.
+            assert_eq!(encoded, bytes, "{} of {} is not compatible", name, version);
+        }
+    }
+}
+
+#[async_test_case]
+async fn test_decode_unknown_fields() {
+    for (version, corpus) in GOLDEN_CORPORA {
+        for (name, bytes) in read_corpus(corpus) {
+            let mut newer = bytes.clone();
+            newer.extend_from_slice(UNKNOWN_FIELD);
+            let encoded = round_trip(name, &newer)
+                .unwrap()
+                .unwrap_or_else(|e| panic!("cannot decode newer {} of {}: {}", name, version, e));
+            assert_eq!(encoded, bytes);
+        }
+    }
+}
@@ -19,6 +19,7 @@ use crate::utils::*;
 use futures::FutureExt;
 use teaclave_proto::teaclave_scheduler_service::*;
 use teaclave_proto::teaclave_storage_service::*;
+use teaclave_rpc::Code;
 use teaclave_test_utils::async_test_case;
 use teaclave_types::*;
 use uuid::Uuid;
@@ -44,7 +45,10 @@ async fn test_pull_task() {
 
     std::thread::sleep(std::time::Duration::from_secs(2));
 
-    let pull_task_request = PullTaskRequest { executor_id };
+    let pull_task_request = PullTaskRequest {
+        executor_id,
+        ..Default::default()
+    };
     let response = client.pull_task(pull_task_request).await;
     log::debug!("response: {:?}", response);
 
@@ -90,7 +94,10 @@ async fn test_update_task_status_result() {
 
     std::thread::sleep(std::time::Duration::from_secs(2));
 
-    let pull_task_request = PullTaskRequest { executor_id };
+    let pull_task_request = PullTaskRequest {
+        executor_id,
+        ..Default::default()
+    };
     let response = client
         .pull_task(pull_task_request)
         .await
@@ -110,3 +117,197 @@ async fn test_update_task_status_result() {
     let response = client.update_task_result(request).await;
     assert!(response.is_ok());
 }
+
+#[async_test_case]
+async fn test_executor_feature_mismatch() {
+    let task_id = Uuid::new_v4();
+    let staged_task = StagedTaskBuilder::new()
+        .task_id(task_id)
+        .function_name("builtin-echo")
+        .function_id(Uuid::new_v4())
+        .executor(Executor::Builtin)
+        .executor_features(ExecutorFeatures::new().feature("wasm_simd", ""))
+        .build();
+
+    let mut storage_client = get_storage_client().await;
+    let enqueue_request = EnqueueRequest::new(
+        StagedTask::get_queue_key().as_bytes(),
+        staged_task.to_vec().unwrap(),
+    );
+    storage_client.enqueue(enqueue_request).await.unwrap();
+    let ts = TaskState {
+        task_id,
+        status: TaskStatus::Staged,
+        ..Default::default()
+    };
+    let put_request = PutRequest::new(ts.key().as_slice(), ts.to_vec().unwrap().as_slice());
+    storage_client.put(put_request).await.unwrap();
+    let mut client = get_scheduler_client().await;
+
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    let features = ExecutorFeatures::new().feature("wasm", "1.0");
+    let request = PullTaskRequest::new(Uuid::new_v4(), features);
+    let response = client.pull_task(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+
+    // The task fails with the mismatch instead of reaching the executor
+    let get_request = GetRequest::new(ts.key());
+    let response = storage_client.get(get_request).await.unwrap().into_inner();
+    let ts = TaskState::from_slice(&response.value).unwrap();
+    assert_eq!(ts.status, TaskStatus::Failed);
+    match ts.result {
+        TaskResult::Err(failure) => assert!(failure.reason.contains("wasm_simd")),
+        _ => unreachable!(),
+    }
+}
+
+#[async_test_case]
+async fn test_task_pinned_to_executor() {
+    let task_id = Uuid::new_v4();
+    let staged_task = StagedTaskBuilder::new()
+        .task_id(task_id)
+        .function_name("builtin-echo")
+        .function_id(Uuid::new_v4())
+        .executor(Executor::Builtin)
+        .build();
+
+    let mut client = get_scheduler_client().await;
+
+    // Reports on a task which was never delivered to the executor are rejected.
+    let request = UpdateTaskStatusRequest::new(task_id, TaskStatus::Running);
+    let response = client.update_task_status(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+
+    // A second copy of a delivered task can't be pulled by another executor.
+    for _ in 0..2 {
+        let request = PublishTaskRequest {
+            staged_task: staged_task.to_vec().unwrap(),
+        };
+        client.publish_task(request).await.unwrap();
+    }
+
+    let pull_task_request = PullTaskRequest {
+        executor_id: Uuid::new_v4().to_string(),
+        ..Default::default()
+    };
+    let response = client.pull_task(pull_task_request).await.unwrap();
+    let pulled = StagedTask::from_slice(&response.into_inner().staged_task).unwrap();
+    assert_eq!(pulled.task_id, task_id);
+
+    let pull_task_request = PullTaskRequest {
+        executor_id: Uuid::new_v4().to_string(),
+        ..Default::default()
+    };
+    let response = client.pull_task(pull_task_request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+}
+
+#[async_test_case]
+async fn test_handoff_task() {
+    let task_id = Uuid::new_v4();
+    let staged_task = StagedTaskBuilder::new()
+        .task_id(task_id)
+        .function_name("builtin-echo")
+        .function_id(Uuid::new_v4())
+        .executor(Executor::Builtin)
+        .build();
+
+    let mut storage_client = get_storage_client().await;
+    let ts = TaskState {
+        task_id,
+        status: TaskStatus::Staged,
+        ..Default::default()
+    };
+    let put_request = PutRequest::new(ts.key().as_slice(), ts.to_vec().unwrap().as_slice());
+    storage_client.put(put_request).await.unwrap();
+
+    let mut client = get_scheduler_client().await;
+    let request = PublishTaskRequest {
+        staged_task: staged_task.to_vec().unwrap(),
+    };
+    client.publish_task(request).await.unwrap();
+
+    let executor_id = Uuid::new_v4();
+    let pull_task_request = PullTaskRequest {
+        executor_id: executor_id.to_string(),
+        ..Default::default()
+    };
+    client.pull_task(pull_task_request).await.unwrap();
+    let request = UpdateTaskStatusRequest::new(task_id, TaskStatus::Running);
+    client.update_task_status(request).await.unwrap();
+
+    // Only the executor running the task can hand it back.
+    let request = HandoffTaskRequest::new(Uuid::new_v4(), task_id, HandoffReason::ExecutorShutdown);
+    let response = client.handoff_task(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+
+    let request = HandoffTaskRequest::new(executor_id, task_id, HandoffReason::ExecutorShutdown);
+    client.handoff_task(request).await.unwrap();
+
+    let get_request = GetRequest::new(ts.key().as_slice());
+    let response = storage_client.get(get_request).await.unwrap().into_inner();
+    let ts = TaskState::from_slice(&response.value).unwrap();
+    assert_eq!(ts.status, TaskStatus::Staged);
+
+    // The task is queued again for another executor.
+    let pull_task_request = PullTaskRequest {
+        executor_id: Uuid::new_v4().to_string(),
+        ..Default::default()
+    };
+    let response = client.pull_task(pull_task_request).await.unwrap();
+    let pulled = StagedTask::from_slice(&response.into_inner().staged_task).unwrap();
+    assert_eq!(pulled.task_id, task_id);
+}
+
+#[async_test_case]
+async fn test_report_task_progress() {
+    let task_id = Uuid::new_v4();
+    let staged_task = StagedTaskBuilder::new()
+        .task_id(task_id)
+        .function_name("builtin-echo")
+        .function_id(Uuid::new_v4())
+        .executor(Executor::Builtin)
+        .build();
+
+    let mut storage_client = get_storage_client().await;
+    let ts = TaskState {
+        task_id,
+        status: TaskStatus::Staged,
+        ..Default::default()
+    };
+    let put_request = PutRequest::new(ts.key().as_slice(), ts.to_vec().unwrap().as_slice());
+    storage_client.put(put_request).await.unwrap();
+
+    let mut client = get_scheduler_client().await;
+    let request = PublishTaskRequest {
+        staged_task: staged_task.to_vec().unwrap(),
+    };
+    client.publish_task(request).await.unwrap();
+
+    // Progress of a task not running on the executor is rejected.
+    let request = ReportTaskProgressRequest::new(task_id, 20, "executing");
+    let response = client.report_task_progress(request).await;
+    assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+
+    let pull_task_request = PullTaskRequest {
+        executor_id: Uuid::new_v4().to_string(),
+        ..Default::default()
+    };
+    client.pull_task(pull_task_request).await.unwrap();
+    let request = UpdateTaskStatusRequest::new(task_id, TaskStatus::Running);
+    client.update_task_status(request).await.unwrap();
+
+    let request = ReportTaskProgressRequest::new(task_id, 101, "executing");
+    assert!(client.report_task_progress(request).await.is_err());
+
+    let request = ReportTaskProgressRequest::new(task_id, 20, "executing");
+    client.report_task_progress(request).await.unwrap();
+
+    let get_request = GetRequest::new(ts.key().as_slice());
+    let response = storage_client.get(get_request).await.unwrap().into_inner();
+    let ts = TaskState::from_slice(&response.value).unwrap();
+    let progress = ts.progress.unwrap();
+    assert_eq!(progress.percentage, 20);
+    assert_eq!(progress.state, "executing");
+}
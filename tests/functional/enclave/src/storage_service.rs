@@ -88,6 +88,27 @@ async fn test_delete_success() {
     assert!(response_result.is_err());
 }
 
+#[async_test_case]
+async fn test_write_batch_success() {
+    let mut client = get_client().await;
+    let request = WriteBatchRequest::new()
+        .put("test_write_batch_key", "test_write_batch_value")
+        .delete("test_delete_key");
+    let response_result = client.write_batch(request).await;
+    debug!("{:?}", response_result);
+    assert!(response_result.is_ok());
+
+    let request = GetRequest::new("test_write_batch_key");
+    let response_result = client.get(request).await;
+    assert_eq!(
+        response_result.unwrap().into_inner().value,
+        b"test_write_batch_value"
+    );
+    let request = GetRequest::new("test_delete_key");
+    let response_result = client.get(request).await;
+    assert!(response_result.is_err());
+}
+
 #[async_test_case]
 async fn test_enqueue_success() {
     let mut client = get_client().await;
@@ -119,6 +140,25 @@ async fn test_dequeue_success() {
     assert_eq!(response_result.unwrap().into_inner().value, b"2");
 }
 
+#[async_test_case]
+async fn test_get_queue_length() {
+    let mut client = get_client().await;
+    let request = GetQueueLengthRequest::new("test_queue_length_key");
+    let response_result = client.get_queue_length(request).await;
+    assert_eq!(response_result.unwrap().into_inner().length, 0);
+
+    let request = EnqueueRequest::new("test_queue_length_key", "1");
+    let response_result = client.enqueue(request).await;
+    assert!(response_result.is_ok());
+    let request = GetQueueLengthRequest::new("test_queue_length_key");
+    let response_result = client.get_queue_length(request).await;
+    assert_eq!(response_result.unwrap().into_inner().length, 1);
+
+    let request = DequeueRequest::new("test_queue_length_key");
+    let response_result = client.dequeue(request).await;
+    assert!(response_result.is_ok());
+}
+
 #[async_test_case]
 async fn test_dequeue_fail() {
     let mut client = get_client().await;
@@ -137,3 +177,19 @@ async fn test_dequeue_fail() {
     let response_result = client.dequeue(request).await;
     assert!(response_result.is_err());
 }
+
+#[async_test_case]
+async fn test_replication_on_primary() {
+    let mut client = get_client().await;
+    // The storage service in the tests follows no primary.
+    let request = ApplyChangeLogRequest::new(b"log".to_vec(), vec![]);
+    let response_result = client.apply_change_log(request).await;
+    assert_eq!(
+        response_result.unwrap_err().code(),
+        teaclave_rpc::Code::FailedPrecondition
+    );
+    let response_result = client
+        .promote_replica(PromoteReplicaRequest::default())
+        .await;
+    assert!(response_result.is_err());
+}
@@ -132,6 +132,7 @@ pub fn create_client_config(
             vec![enclave_attr],
             AS_ROOT_CA_CERT,
             verifier::universal_quote_verifier,
+            verifier::VerificationPolicy::default(),
         )
         .into();
     Ok(config)
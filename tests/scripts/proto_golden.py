@@ -0,0 +1,259 @@
+#!/usr/bin/env python3
+
+# Licensed to the Apache Software Foundation (ASF) under one
+# or more contributor license agreements.  See the NOTICE file
+# distributed with this work for additional information
+# regarding copyright ownership.  The ASF licenses this file
+# to you under the Apache License, Version 2.0 (the
+# "License"); you may not use this file except in compliance
+# with the License.  You may obtain a copy of the License at
+#
+#   http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing,
+# software distributed under the License is distributed on an
+# "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+# KIND, either express or implied.  See the License for the
+# specific language governing permissions and limitations
+# under the License.
+"""Maintain the golden corpus of the proto messages.
+
+The corpus of a version holds an instance of every message with all its fields
+set, serialized in the protobuf wire format. The functional tests decode the
+corpora of all versions with the current messages, so that a change to the
+protos breaking peers of older versions fails the tests.
+
+Entries are only ever appended: running this script adds the messages missing
+from the corpus of the current version, and keeps the existing entries as they
+are. With --check, it fails if any message is missing instead.
+"""
+
+import argparse
+import os
+import re
+import struct
+import sys
+
+ROOT = os.path.normpath(
+    os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", ".."))
+PROTO_DIR = os.path.join(ROOT, "services", "proto", "src", "proto")
+CORPUS_DIR = os.path.join(ROOT, "tests", "fixtures", "proto")
+
+VARINT_TYPES = {"int32", "int64", "uint32", "uint64", "bool"}
+LENGTH_DELIMITED_TYPES = {"string", "bytes"}
+MAX_DEPTH = 8
+
+HEADER = """\
+# Licensed to the Apache Software Foundation (ASF) under one
+# or more contributor license agreements.  See the NOTICE file
+# distributed with this work for additional information
+# regarding copyright ownership.  The ASF licenses this file
+# to you under the Apache License, Version 2.0 (the
+# "License"); you may not use this file except in compliance
+# with the License.  You may obtain a copy of the License at
+#
+#   http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing,
+# software distributed under the License is distributed on an
+# "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+# KIND, either express or implied.  See the License for the
+# specific language governing permissions and limitations
+# under the License.
+
+# Golden proto messages of Teaclave {version}, generated by
+# tests/scripts/proto_golden.py. Do not edit existing entries.
+"""
+
+
+class Field:
+
+    def __init__(self, name, type, number, label=None, key_type=None):
+        self.name = name
+        self.type = type
+        self.number = number
+        # "repeated", "map" or "oneof"
+        self.label = label
+        self.key_type = key_type
+
+
+def parse_protos():
+    """Messages and enums by full names"""
+    messages, enums = {}, {}
+    for file_name in sorted(os.listdir(PROTO_DIR)):
+        if not file_name.endswith(".proto"):
+            continue
+        with open(os.path.join(PROTO_DIR, file_name)) as f:
+            text = re.sub(r"//[^\n]*", "", f.read())
+        package = re.search(r"\bpackage\s+(\w+)\s*;", text).group(1)
+        for kind, name, body in re.findall(
+                r"\b(message|enum)\s+(\w+)\s*\{((?:[^{}]|\{[^{}]*\})*)\}",
+                text):
+            full_name = f"{package}.{name}"
+            if kind == "enum":
+                enums[full_name] = [
+                    int(n) for n in re.findall(r"=\s*(-?\d+)\s*;", body)
+                ]
+            else:
+                messages[full_name] = (package, parse_fields(body))
+    return messages, enums
+
+
+def parse_fields(body):
+    fields = []
+    for oneof in re.findall(r"\boneof\s+\w+\s*\{([^}]*)\}", body):
+        members = [
+            Field(name, type, int(number), "oneof")
+            for type, name, number in re.findall(
+                r"([\w.]+)\s+(\w+)\s*=\s*(\d+)\s*;", oneof)
+        ]
+        # Only the member of the smallest number is set
+        fields.append(min(members, key=lambda f: f.number))
+    body = re.sub(r"\boneof\s+\w+\s*\{[^}]*\}", "", body)
+    for key_type, value_type, name, number in re.findall(
+            r"\bmap\s*<\s*(\w+)\s*,\s*([\w.]+)\s*>\s+(\w+)\s*=\s*(\d+)\s*;",
+            body):
+        fields.append(Field(name, value_type, int(number), "map", key_type))
+    body = re.sub(r"\bmap\s*<[^>]*>[^;]*;", "", body)
+    for label, type, name, number in re.findall(
+            r"(repeated\s+)?([\w.]+)\s+(\w+)\s*=\s*(\d+)\s*;", body):
+        label = "repeated" if label else None
+        fields.append(Field(name, type, int(number), label))
+    return sorted(fields, key=lambda f: f.number)
+
+
+def varint(value):
+    value &= (1 << 64) - 1
+    out = bytearray()
+    while True:
+        byte = value & 0x7f
+        value >>= 7
+        if value:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def key(number, wire_type):
+    return varint(number << 3 | wire_type)
+
+
+def delimited(number, payload):
+    return key(number, 2) + varint(len(payload)) + payload
+
+
+class Encoder:
+
+    def __init__(self, messages, enums):
+        self.messages = messages
+        self.enums = enums
+
+    def resolve(self, package, type):
+        if type in VARINT_TYPES | LENGTH_DELIMITED_TYPES | {"double"}:
+            return type
+        for full_name in (type, f"{package}.{type}"):
+            if full_name in self.messages or full_name in self.enums:
+                return full_name
+        raise ValueError(f"unknown type {type} in {package}")
+
+    def scalar(self, type, field):
+        """The wire type and the encoded value of a field of a scalar type"""
+        if type == "string" or type == "bytes":
+            return 2, field.name.encode()
+        if type == "bool":
+            return 0, varint(1)
+        if type == "double":
+            return 1, struct.pack("<d", field.number + 0.5)
+        if type in VARINT_TYPES:
+            # Multi-byte varints
+            return 0, varint(300 + field.number)
+        # Enums take the first non-zero value, so that they are not omitted
+        values = [v for v in self.enums[type] if v != 0]
+        return 0, varint(values[0] if values else 0)
+
+    def value(self, package, type, field, depth):
+        type = self.resolve(package, type)
+        if type in self.messages:
+            return 2, self.encode(type, depth + 1)
+        return self.scalar(type, field)
+
+    def encode(self, full_name, depth=0):
+        package, fields = self.messages[full_name]
+        out = b""
+        if depth >= MAX_DEPTH:
+            return out
+        for field in fields:
+            wire_type, value = self.value(package, field.type, field, depth)
+            if field.label == "map":
+                key_type = self.resolve(package, field.key_type)
+                key_wire_type, key_value = self.scalar(key_type, field)
+                entry = key(1, key_wire_type)
+                entry += (varint(len(key_value)) +
+                          key_value if key_wire_type == 2 else key_value)
+                entry += (delimited(2, value) if wire_type == 2 else
+                          key(2, wire_type) + value)
+                out += delimited(field.number, entry)
+            elif field.label == "repeated" and wire_type != 2:
+                # Repeated scalar numeric fields are packed in proto3
+                out += delimited(field.number, value)
+            elif wire_type == 2:
+                out += delimited(field.number, value)
+            elif value != varint(0) or field.label == "oneof":
+                out += key(field.number, wire_type) + value
+        return out
+
+
+def current_version():
+    with open(os.path.join(ROOT, "services", "proto", "Cargo.toml")) as f:
+        return re.search(r'^version\s*=\s*"([^"]+)"', f.read(),
+                         re.MULTILINE).group(1)
+
+
+def read_corpus(path):
+    entries = {}
+    if os.path.exists(path):
+        with open(path) as f:
+            for line in f:
+                line = line.strip()
+                if line and not line.startswith("#"):
+                    name, _, encoded = line.partition(" ")
+                    entries[name] = encoded
+    return entries
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--check",
+                        action="store_true",
+                        help="fail if the corpus misses any message")
+    args = parser.parse_args()
+
+    messages, enums = parse_protos()
+    version = current_version()
+    path = os.path.join(CORPUS_DIR, f"v{version}.txt")
+    entries = read_corpus(path)
+    missing = sorted(set(messages) - set(entries))
+    removed = sorted(set(entries) - set(messages))
+    for name in removed:
+        print(f"error: {name} is in the corpus but no longer defined",
+              file=sys.stderr)
+
+    if args.check:
+        for name in missing:
+            print(f"error: {name} is missing from {path}", file=sys.stderr)
+        return 1 if missing or removed else 0
+
+    encoder = Encoder(messages, enums)
+    os.makedirs(CORPUS_DIR, exist_ok=True)
+    with open(path, "a") as f:
+        if not entries:
+            f.write(HEADER.format(version=version))
+        for name in missing:
+            f.write(f"{name} {encoder.encode(name).hex()}".rstrip() + "\n")
+    print(f"{len(missing)} messages added to {path}")
+    return 1 if removed else 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,418 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Launches Teaclave services built in simulation/test mode for functional
+//! tests and CI.
+//!
+//! Services run in their own processes from an ephemeral working directory,
+//! which holds a copy of the runtime config with its file paths resolved, the
+//! enclaves of the services and their databases. The endpoints in the config
+//! are kept, so clients reading the same config find the services.
+//!
+//! ```no_run
+//! use teaclave_test_env::{EnvironmentBuilder, Service};
+//!
+//! let mut env = EnvironmentBuilder::new("release/services")
+//!     .without(Service::Execution)
+//!     .start()?;
+//! // run tests which need no execution service
+//! env.start_service(Service::Execution)?;
+//! // run end-to-end tests
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::net::{SocketAddr, TcpStream};
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CONFIG_FILE: &str = "runtime.config.toml";
+const ENCLAVE_FILE_SUFFIX: &str = "_enclave.signed.so";
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+static ENV_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    Authentication,
+    Storage,
+    AccessControl,
+    Management,
+    Scheduler,
+    Frontend,
+    Execution,
+}
+
+impl Service {
+    /// All services in the order of their dependencies.
+    pub const ALL: [Service; 7] = [
+        Service::Authentication,
+        Service::Storage,
+        Service::AccessControl,
+        Service::Management,
+        Service::Scheduler,
+        Service::Frontend,
+        Service::Execution,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Service::Authentication => "authentication",
+            Service::Storage => "storage",
+            Service::AccessControl => "access_control",
+            Service::Management => "management",
+            Service::Scheduler => "scheduler",
+            Service::Frontend => "frontend",
+            Service::Execution => "execution",
+        }
+    }
+
+    /// Name of the service binary, which is also the prefix of its enclave.
+    pub fn binary(&self) -> String {
+        format!("teaclave_{}_service", self.name())
+    }
+
+    // Services of a stage only depend on services of earlier stages.
+    fn stage(&self) -> usize {
+        match self {
+            Service::Authentication | Service::Storage | Service::AccessControl => 0,
+            Service::Management | Service::Scheduler => 1,
+            Service::Frontend => 2,
+            Service::Execution => 3,
+        }
+    }
+
+    fn listen_addresses(&self, config: &toml::Value) -> Result<Vec<SocketAddr>> {
+        let endpoints: &[(&str, &str)] = match self {
+            Service::Authentication => &[
+                ("api_endpoints", "authentication"),
+                ("internal_endpoints", "authentication"),
+            ],
+            Service::Frontend => &[("api_endpoints", "frontend")],
+            // The execution service only connects to the scheduler.
+            Service::Execution => &[],
+            _ => &[("internal_endpoints", self.name())],
+        };
+
+        endpoints
+            .iter()
+            .map(|(section, name)| {
+                let address = config
+                    .get(section)
+                    .and_then(|s| s.get(name))
+                    .and_then(|e| e.get("listen_address"))
+                    .and_then(|a| a.as_str())
+                    .ok_or_else(|| anyhow!("No listen_address of {}.{}", section, name))?;
+                let address: SocketAddr = address
+                    .parse()
+                    .with_context(|| format!("Invalid listen_address {}", address))?;
+                // Services listening on all interfaces are reached by loopback.
+                if address.ip().is_unspecified() {
+                    Ok(SocketAddr::from(([127, 0, 0, 1], address.port())))
+                } else {
+                    Ok(address)
+                }
+            })
+            .collect()
+    }
+}
+
+pub struct EnvironmentBuilder {
+    services_dir: PathBuf,
+    config_path: Option<PathBuf>,
+    services: Vec<Service>,
+    startup_timeout: Duration,
+    execution_delay: Duration,
+    keep_work_dir: bool,
+}
+
+impl EnvironmentBuilder {
+    /// Launches the services installed in `services_dir`, with the
+    /// `runtime.config.toml` there unless another config is given.
+    pub fn new<P: AsRef<Path>>(services_dir: P) -> Self {
+        Self {
+            services_dir: services_dir.as_ref().to_path_buf(),
+            config_path: None,
+            services: Service::ALL.to_vec(),
+            startup_timeout: Duration::from_secs(10),
+            execution_delay: Duration::from_secs(3),
+            keep_work_dir: false,
+        }
+    }
+
+    pub fn config<P: AsRef<Path>>(self, config_path: P) -> Self {
+        Self {
+            config_path: Some(config_path.as_ref().to_path_buf()),
+            ..self
+        }
+    }
+
+    /// Does not start `service`, which can be started later with
+    /// [`TestEnvironment::start_service`].
+    pub fn without(mut self, service: Service) -> Self {
+        self.services.retain(|s| *s != service);
+        self
+    }
+
+    /// Time to wait for each stage of services to listen.
+    pub fn startup_timeout(self, startup_timeout: Duration) -> Self {
+        Self {
+            startup_timeout,
+            ..self
+        }
+    }
+
+    /// The execution service listens on nothing, so it is deemed ready after
+    /// this delay.
+    pub fn execution_delay(self, execution_delay: Duration) -> Self {
+        Self {
+            execution_delay,
+            ..self
+        }
+    }
+
+    /// Keeps the working directory with the databases of services after the
+    /// environment is dropped.
+    pub fn keep_work_dir(self, keep_work_dir: bool) -> Self {
+        Self {
+            keep_work_dir,
+            ..self
+        }
+    }
+
+    pub fn start(self) -> Result<TestEnvironment> {
+        let services_dir = fs::canonicalize(&self.services_dir)
+            .with_context(|| format!("Cannot find services in {}", self.services_dir.display()))?;
+        let config_path = self
+            .config_path
+            .clone()
+            .unwrap_or_else(|| services_dir.join(CONFIG_FILE));
+
+        let work_dir = std::env::temp_dir().join(format!(
+            "teaclave_test_env.{}.{}",
+            std::process::id(),
+            ENV_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&work_dir)
+            .with_context(|| format!("Cannot create {}", work_dir.display()))?;
+
+        let config = write_config(&config_path, &work_dir)?;
+        let mut env = TestEnvironment {
+            services_dir,
+            work_dir,
+            config,
+            children: Vec::new(),
+            startup_timeout: self.startup_timeout,
+            execution_delay: self.execution_delay,
+            keep_work_dir: self.keep_work_dir,
+        };
+
+        for stage in 0..=Service::Execution.stage() {
+            let services: Vec<Service> = self
+                .services
+                .iter()
+                .filter(|s| s.stage() == stage)
+                .copied()
+                .collect();
+            env.start_services(&services)?;
+        }
+
+        Ok(env)
+    }
+}
+
+/// Running services, which are terminated when this is dropped.
+pub struct TestEnvironment {
+    services_dir: PathBuf,
+    work_dir: PathBuf,
+    config: toml::Value,
+    children: Vec<(Service, Child)>,
+    startup_timeout: Duration,
+    execution_delay: Duration,
+    keep_work_dir: bool,
+}
+
+impl TestEnvironment {
+    pub fn work_dir(&self) -> &Path {
+        &self.work_dir
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.work_dir.join(CONFIG_FILE)
+    }
+
+    pub fn is_running(&self, service: Service) -> bool {
+        self.children.iter().any(|(s, _)| *s == service)
+    }
+
+    pub fn start_service(&mut self, service: Service) -> Result<()> {
+        self.start_services(&[service])
+    }
+
+    fn start_services(&mut self, services: &[Service]) -> Result<()> {
+        let mut addresses = Vec::new();
+        for service in services {
+            if self.is_running(*service) {
+                bail!("The {} service is already running", service.name());
+            }
+            addresses.extend(
+                service
+                    .listen_addresses(&self.config)?
+                    .into_iter()
+                    .map(|a| (*service, a)),
+            );
+            self.spawn(*service)?;
+        }
+
+        let deadline = Instant::now() + self.startup_timeout;
+        for (service, address) in addresses {
+            while TcpStream::connect_timeout(&address, POLL_INTERVAL).is_err() {
+                self.check_alive(service)?;
+                if Instant::now() > deadline {
+                    bail!(
+                        "The {} service does not listen on {} in {:?}",
+                        service.name(),
+                        address,
+                        self.startup_timeout
+                    );
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            log::info!("The {} service listens on {}", service.name(), address);
+        }
+
+        if services.contains(&Service::Execution) {
+            thread::sleep(self.execution_delay);
+            self.check_alive(Service::Execution)?;
+        }
+
+        Ok(())
+    }
+
+    fn spawn(&mut self, service: Service) -> Result<()> {
+        let enclave = format!("{}{}", service.binary(), ENCLAVE_FILE_SUFFIX);
+        let link = self.work_dir.join(&enclave);
+        if !link.exists() {
+            symlink(self.services_dir.join(&enclave), &link)
+                .with_context(|| format!("Cannot link {}", enclave))?;
+        }
+
+        let child = Command::new(self.services_dir.join(service.binary()))
+            .current_dir(&self.work_dir)
+            .spawn()
+            .with_context(|| format!("Cannot start the {} service", service.name()))?;
+        log::info!(
+            "Started the {} service (pid {})",
+            service.name(),
+            child.id()
+        );
+        self.children.push((service, child));
+
+        Ok(())
+    }
+
+    fn check_alive(&mut self, service: Service) -> Result<()> {
+        let (_, child) = self
+            .children
+            .iter_mut()
+            .find(|(s, _)| *s == service)
+            .ok_or_else(|| anyhow!("The {} service is not started", service.name()))?;
+        if let Some(status) = child.try_wait()? {
+            bail!("The {} service exited with {}", service.name(), status);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for TestEnvironment {
+    fn drop(&mut self) {
+        // Terminate services gracefully, dependents first.
+        for (service, child) in self.children.iter_mut().rev() {
+            if let Ok(None) = child.try_wait() {
+                unsafe {
+                    libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+                }
+            }
+            if let Err(e) = child.wait() {
+                log::warn!("Cannot wait for the {} service: {:?}", service.name(), e);
+            }
+        }
+
+        if self.keep_work_dir {
+            log::info!("Kept the test environment in {}", self.work_dir.display());
+        } else {
+            let _ = fs::remove_dir_all(&self.work_dir);
+        }
+    }
+}
+
+// Copies the config into the working directory, resolving the relative paths
+// of audit files and keeping cached endorsements of this environment apart
+// from others.
+fn write_config(config_path: &Path, work_dir: &Path) -> Result<toml::Value> {
+    let contents = fs::read_to_string(config_path)
+        .with_context(|| format!("Cannot read {}", config_path.display()))?;
+    let mut config: toml::Value = contents
+        .parse()
+        .with_context(|| format!("Cannot parse {}", config_path.display()))?;
+    let base_dir = fs::canonicalize(config_path)?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let resolve = |source: &mut toml::Value| {
+        if let Some(path) = source.get_mut("path") {
+            if let Some(p) = path.as_str() {
+                *path = toml::Value::from(base_dir.join(p).to_string_lossy().as_ref());
+            }
+        }
+    };
+    if let Some(audit) = config.get_mut("audit") {
+        if let Some(enclave_info) = audit.get_mut("enclave_info") {
+            resolve(enclave_info);
+        }
+        if let Some(toml::Value::Array(signatures)) = audit.get_mut("auditor_signatures") {
+            signatures.iter_mut().for_each(resolve);
+        }
+    }
+
+    // The fusion base directory is kept, as the file agent of the execution
+    // service downloads fusion data to /tmp/fusion_data.
+    if let Some(mount) = config.get("mount").and_then(|m| m.get("fusion_base_dir")) {
+        if let Some(dir) = mount.as_str() {
+            fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir))?;
+        }
+    }
+    if let Some(attestation) = config.get_mut("attestation").and_then(|a| a.as_table_mut()) {
+        if attestation.contains_key("endorsement_cache_dir") {
+            let cache_dir = work_dir.join("endorsements");
+            attestation.insert(
+                "endorsement_cache_dir".to_string(),
+                toml::Value::from(cache_dir.to_string_lossy().as_ref()),
+            );
+        }
+    }
+
+    fs::write(work_dir.join(CONFIG_FILE), toml::to_string(&config)?)?;
+    Ok(config)
+}
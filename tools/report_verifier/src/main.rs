@@ -0,0 +1,213 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Verify the identity of a Teaclave enclave outside the platform, from an
+//! endorsed attestation report, an attested TLS certificate of a service, or
+//! a raw quote, against a policy of the security team.
+
+use anyhow::{anyhow, bail, ensure, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use teaclave_attestation::report::{AttestationReport, SgxEnclaveReport, SgxQuote};
+use teaclave_attestation::verifier::VerificationPolicy;
+use teaclave_attestation::EndorsedAttestationReport;
+use teaclave_config::VerificationPolicyConfig;
+use teaclave_types::EnclaveMeasurement;
+
+// The DEBUG bit of the flags in the attributes of an enclave
+const SGX_FLAGS_DEBUG: u8 = 0x02;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "teaclave_report_verifier",
+    about = "Verify the identity of a Teaclave enclave against a policy."
+)]
+struct Opt {
+    /// Path of an endorsed attestation report in JSON
+    #[structopt(short, long)]
+    report: Option<PathBuf>,
+
+    /// Path of an attested TLS certificate of a Teaclave service, in PEM or
+    /// DER
+    #[structopt(long)]
+    cert: Option<PathBuf>,
+
+    /// Path of a raw quote, which is not endorsed by any attestation service,
+    /// so only the identity of the enclave in it is checked
+    #[structopt(short, long)]
+    quote: Option<PathBuf>,
+
+    /// CA cert of attestation service for verifying the attestation report
+    #[structopt(short = "c", long)]
+    as_ca_cert: Option<PathBuf>,
+
+    /// Path of the policy file
+    #[structopt(short, long)]
+    policy: PathBuf,
+}
+
+/// The policy file, in TOML
+#[derive(Debug, Deserialize)]
+struct Policy {
+    /// Accept enclaves in debug mode, whose memory can be read by the host
+    #[serde(default)]
+    allow_debug: bool,
+    /// Quote statuses and age of the report accepted, same as in the runtime
+    /// config, or the defaults of the platform if absent
+    #[serde(default)]
+    verification_policy: Option<VerificationPolicyConfig>,
+    /// Measurements of the accepted enclaves by name, in the format of
+    /// enclave_info.toml
+    accepted_enclaves: HashMap<String, EnclaveMeasurement>,
+}
+
+/// Read a certificate in PEM, or in DER otherwise.
+fn read_cert(path: &Path) -> Result<Vec<u8>> {
+    let content = fs::read(path)?;
+    if content.starts_with(b"-----BEGIN") {
+        Ok(pem::parse(content)?.contents)
+    } else {
+        Ok(content)
+    }
+}
+
+fn read_endorsed(opt: &Opt) -> Result<AttestationReport> {
+    let as_ca_cert = || -> Result<Vec<u8>> {
+        let path = opt
+            .as_ca_cert
+            .as_ref()
+            .ok_or_else(|| anyhow!("--as-ca-cert is required to verify the endorsement"))?;
+        read_cert(path)
+    };
+    if let Some(path) = &opt.report {
+        let report: EndorsedAttestationReport = serde_json::from_slice(&fs::read(path)?)?;
+        return AttestationReport::from_endorsed_report(&report, &as_ca_cert()?);
+    }
+    if let Some(path) = &opt.cert {
+        let cert = rustls::Certificate(read_cert(path)?);
+        return AttestationReport::from_cert(&[cert], &as_ca_cert()?);
+    }
+    bail!("No report or certificate to verify")
+}
+
+struct Checks {
+    passed: bool,
+}
+
+impl Checks {
+    fn check(&mut self, name: &str, result: Result<String>) {
+        match result {
+            Ok(detail) => println!("[PASS] {}: {}", name, detail),
+            Err(e) => {
+                println!("[FAIL] {}: {}", name, e);
+                self.passed = false;
+            }
+        }
+    }
+}
+
+fn check_identity(checks: &mut Checks, enclave: &SgxEnclaveReport, policy: &Policy) {
+    let debug = enclave.attributes[0] & SGX_FLAGS_DEBUG != 0;
+    checks.check(
+        "debug mode",
+        if debug && !policy.allow_debug {
+            Err(anyhow!("the enclave is in debug mode"))
+        } else if debug {
+            Ok("the enclave is in debug mode, which is allowed".to_string())
+        } else {
+            Ok("the enclave is in production mode".to_string())
+        },
+    );
+
+    let accepted = policy
+        .accepted_enclaves
+        .iter()
+        .find(|(_, m)| m.mr_enclave == enclave.mr_enclave && m.mr_signer == enclave.mr_signer);
+    checks.check(
+        "measurement",
+        match accepted {
+            Some((name, _)) => Ok(format!("accepted as {}", name)),
+            None => Err(anyhow!(
+                "mr_enclave {} signed by mr_signer {} is not accepted",
+                hex::encode(enclave.mr_enclave),
+                hex::encode(enclave.mr_signer)
+            )),
+        },
+    );
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+    let opt = Opt::from_args();
+    let inputs = [&opt.report, &opt.cert, &opt.quote];
+    ensure!(
+        inputs.iter().filter(|input| input.is_some()).count() == 1,
+        "Expect exactly one of --report, --cert and --quote"
+    );
+    let policy: Policy = toml::from_slice(&fs::read(&opt.policy)?)?;
+    let verification_policy = match &policy.verification_policy {
+        Some(config) => VerificationPolicy::from_config(config)?,
+        None => VerificationPolicy::default(),
+    };
+
+    let mut checks = Checks { passed: true };
+    if let Some(path) = &opt.quote {
+        let quote = SgxQuote::parse_from(&fs::read(path)?)?;
+        println!("{}", quote);
+        println!("[SKIP] endorsement: the quote is not endorsed");
+        check_identity(&mut checks, &quote.isv_enclave_report, &policy);
+    } else {
+        let report = match read_endorsed(&opt) {
+            Ok(report) => report,
+            Err(e) => {
+                checks.check("endorsement", Err(e));
+                println!("VERDICT: REFUSED");
+                std::process::exit(1);
+            }
+        };
+        println!("{}", report);
+        checks.check(
+            "endorsement",
+            Ok("signed by the attestation service".to_string()),
+        );
+        checks.check(
+            "verification policy",
+            verification_policy
+                .check(&report)
+                .map(|_| format!("quote status {:?} is allowed", report.sgx_quote_status)),
+        );
+        check_identity(
+            &mut checks,
+            &report.sgx_quote_body.isv_enclave_report,
+            &policy,
+        );
+    }
+
+    if !checks.passed {
+        println!("VERDICT: REFUSED");
+        std::process::exit(1);
+    }
+    if opt.quote.is_some() {
+        println!("VERDICT: ACCEPTED, for the identity only");
+    } else {
+        println!("VERDICT: ACCEPTED");
+    }
+    Ok(())
+}
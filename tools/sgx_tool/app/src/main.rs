@@ -63,7 +63,8 @@ struct Opt {
 #[derive(Debug, StructOpt, serde::Serialize)]
 struct AttestationOpt {
     /// Attestation algorithm, supported algorithms are "sgx_epid" for IAS
-    /// attestation and "sgx_ecdsa" for DCAP attestation.
+    /// attestation, "sgx_ecdsa" for DCAP attestation and "sgx_maa" for
+    /// Microsoft Azure Attestation.
     #[structopt(long, default_value = "sgx_epid")]
     algorithm: String,
 
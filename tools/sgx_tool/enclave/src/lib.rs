@@ -18,10 +18,9 @@
 extern crate sgx_trts;
 extern crate sgx_types;
 
-use serde_json::Value;
 use teaclave_attestation::report::SgxQuote;
-use teaclave_attestation::EndorsedAttestationReport;
 use teaclave_attestation::{key, AttestationConfig};
+use teaclave_attestation::{EndorsedAttestationReport, ReportFormat};
 use teaclave_binder::proto::{
     ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, InitEnclaveInput, InitEnclaveOutput,
     RawJsonInput, RawJsonOutput,
@@ -46,19 +45,23 @@ fn attestation(raw_json_input: &RawJsonInput) -> anyhow::Result<()> {
             EndorsedAttestationReport::new(config, key_pair.pub_k())?
         }
     };
-    let attn_report: Value = serde_json::from_slice(&report.report)?;
-    let sgx_quote_body = {
-        let quote_encoded = attn_report["isvEnclaveQuoteBody"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("report error"))?;
-        let quote_raw = base64::decode(quote_encoded.as_bytes())?;
-        SgxQuote::parse_from(quote_raw.as_slice())?
-    };
+    let attn_report = report.body()?;
     println!("Remote Attestation Report:");
     println!("{}", serde_json::to_string_pretty(&attn_report)?);
-    println!();
-    println!("ISV Enclave Quote Body:");
-    println!("{:?}", sgx_quote_body);
+    // Tokens of Azure Attestation carry claims of the enclave instead of the
+    // quote body
+    if report.format == ReportFormat::Ias {
+        let sgx_quote_body = {
+            let quote_encoded = attn_report["isvEnclaveQuoteBody"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("report error"))?;
+            let quote_raw = base64::decode(quote_encoded.as_bytes())?;
+            SgxQuote::parse_from(quote_raw.as_slice())?
+        };
+        println!();
+        println!("ISV Enclave Quote Body:");
+        println!("{:?}", sgx_quote_body);
+    }
     Ok(())
 }
 
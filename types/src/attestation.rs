@@ -96,16 +96,20 @@ impl EnclaveInfo {
     }
 
     pub fn from_bytes(enclave_info: &[u8]) -> Self {
-        let config: EnclaveInfoToml = toml::from_slice(enclave_info)
-            .expect("Content not correct, unable to load enclave info.");
+        Self::try_from_bytes(enclave_info)
+            .expect("Content not correct, unable to load enclave info.")
+    }
+
+    pub fn try_from_bytes(enclave_info: &[u8]) -> Result<Self> {
+        let config: EnclaveInfoToml = toml::from_slice(enclave_info)?;
         let mut info_map = std::collections::HashMap::new();
         for (k, v) in config.0 {
             info_map.insert(k, EnclaveMeasurement::new(v.mr_enclave, v.mr_signer));
         }
 
-        Self {
+        Ok(Self {
             measurements: info_map,
-        }
+        })
     }
 
     pub fn verify<T, U>(enclave_info: &[u8], public_keys: &[T], signatures: &[U]) -> bool
@@ -34,6 +34,9 @@ pub struct Entry {
     /// The result for the message.
     /// true for success and false for failure
     result: bool,
+    /// The request the entry belongs to, shared by the logs of all services
+    /// handling the request.
+    trace_id: String,
 }
 
 impl Default for Entry {
@@ -43,6 +46,7 @@ impl Default for Entry {
         let user = String::new();
         let message = String::new();
         let result = false;
+        let trace_id = String::new();
 
         Self {
             datetime,
@@ -50,6 +54,7 @@ impl Default for Entry {
             user,
             message,
             result,
+            trace_id,
         }
     }
 }
@@ -74,6 +79,10 @@ impl Entry {
     pub fn result(&self) -> bool {
         self.result
     }
+
+    pub fn trace_id(&self) -> String {
+        self.trace_id.clone()
+    }
 }
 
 #[derive(Default, Clone)]
@@ -84,6 +93,7 @@ pub struct EntryBuilder {
     user: Option<String>,
     message: Option<String>,
     result: Option<bool>,
+    trace_id: Option<String>,
 }
 
 impl EntryBuilder {
@@ -116,6 +126,11 @@ impl EntryBuilder {
         self
     }
 
+    pub fn trace_id(mut self, trace_id: String) -> Self {
+        self.trace_id = Some(trace_id);
+        self
+    }
+
     pub fn build(self) -> Entry {
         let datetime = self
             .microsecond
@@ -133,6 +148,7 @@ impl EntryBuilder {
             user: self.user.unwrap_or_default(),
             message: self.message.unwrap_or_default(),
             result: self.result.unwrap_or(false),
+            trace_id: self.trace_id.unwrap_or_default(),
         }
     }
 }
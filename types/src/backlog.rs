@@ -0,0 +1,42 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use serde::{Deserialize, Serialize};
+
+/// The tasks the leading scheduler holds and how fast they run, shared in the
+/// storage service under `SCHEDULER_BACKLOG_KEY` so that callers of
+/// `InvokeTask` are told how long their tasks may wait.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TaskBacklog {
+    /// Staged tasks queued in the scheduler, not delivered yet
+    pub queued: u32,
+    /// Executors heartbeating to the scheduler
+    pub executors: u32,
+    /// Average seconds of the recent runs, 0 if no run has ended yet
+    pub average_run_secs: u64,
+}
+
+impl TaskBacklog {
+    /// Seconds until the executors have run `ahead` tasks, or 0 if unknown.
+    pub fn eta_secs(&self, ahead: u32) -> u64 {
+        if self.executors == 0 || self.average_run_secs == 0 {
+            return 0;
+        }
+        let rounds = (ahead as u64 + self.executors as u64 - 1) / self.executors as u64;
+        rounds.saturating_mul(self.average_run_secs)
+    }
+}
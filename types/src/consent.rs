@@ -0,0 +1,91 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::{ExternalID, Function, Storable, TaskState, UserID};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+const CONSENT_PREFIX: &str = "consent";
+
+/// Evidence that a data owner agreed to one specific use of their data: the
+/// data involved, the function (pinned by the digest of its payload) and the
+/// purpose stated by the task creator.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConsentRecord {
+    pub task_id: Uuid,
+    pub owner: UserID,
+    pub data_ids: Vec<String>,
+    pub function_id: String,
+    pub function_hash: String,
+    pub purpose: String,
+    /// The microsecond since the UNIX epoch
+    pub timestamp: i64,
+}
+
+impl ConsentRecord {
+    pub fn new(ts: &TaskState, owner: &UserID, function: &Function) -> Self {
+        let inputs = ts
+            .assigned_inputs
+            .clone()
+            .into_iter()
+            .filter(|(_, file)| file.owner.contains(owner))
+            .map(|(_, file)| file.external_id().to_string());
+        let outputs = ts
+            .assigned_outputs
+            .clone()
+            .into_iter()
+            .filter(|(_, file)| file.owner.contains(owner))
+            .map(|(_, file)| file.external_id().to_string());
+        let mut data_ids: Vec<String> = inputs.chain(outputs).collect();
+        data_ids.sort();
+
+        let digest = ring::digest::digest(&ring::digest::SHA256, &function.payload);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+        ConsentRecord {
+            task_id: ts.task_id,
+            owner: owner.clone(),
+            data_ids,
+            function_id: ts.function_id.to_string(),
+            function_hash: hex::encode(digest),
+            purpose: ts.purpose.clone(),
+            timestamp: now.as_micros() as i64,
+        }
+    }
+
+    /// Consent records are keyed by task and owner so that at most one record
+    /// exists for each participant of a task.
+    pub fn external_id_of(task_id: &Uuid, owner: &UserID) -> ExternalID {
+        ExternalID::new(CONSENT_PREFIX, Self::uuid_of(task_id, owner))
+    }
+
+    fn uuid_of(task_id: &Uuid, owner: &UserID) -> Uuid {
+        let name = format!("{}-{}", task_id, owner);
+        Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes())
+    }
+}
+
+impl Storable for ConsentRecord {
+    fn key_prefix() -> &'static str {
+        CONSENT_PREFIX
+    }
+
+    fn uuid(&self) -> Uuid {
+        Self::uuid_of(&self.task_id, &self.owner)
+    }
+}
@@ -18,6 +18,7 @@
 use anyhow::{bail, ensure, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
+use uuid::Uuid;
 
 use teaclave_crypto::*;
 
@@ -79,10 +80,45 @@ impl std::cmp::PartialEq<[u8; FILE_AUTH_TAG_LENGTH]> for FileAuthTag {
     }
 }
 
+/// The task, slot and data which an encrypted file is produced for. It is
+/// bound to the ciphertext as the additional authenticated data of the
+/// in-memory schemes, so that the file fails to decrypt as any other one.
+/// teaclave-file-128 has no additional authenticated data, and is not bound.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EncryptionContext {
+    pub task_id: Uuid,
+    pub slot: String,
+    pub data_id: Uuid,
+}
+
+impl EncryptionContext {
+    pub fn new(task_id: Uuid, slot: impl ToString, data_id: Uuid) -> Self {
+        Self {
+            task_id,
+            slot: slot.to_string(),
+            data_id,
+        }
+    }
+
+    pub fn aad(&self) -> Vec<u8> {
+        format!("teaclave:{}:{}:{}", self.task_id, self.slot, self.data_id).into_bytes()
+    }
+}
+
+fn aad_of(context: Option<&EncryptionContext>) -> Vec<u8> {
+    match context {
+        Some(context) => context.aad(),
+        None => DEFAULT_AAD.to_vec(),
+    }
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum FileCrypto {
     AesGcm128(AesGcm128Key),
     AesGcm256(AesGcm256Key),
+    AesGcmSiv128(AesGcmSiv128Key),
+    AesGcmSiv256(AesGcmSiv256Key),
+    ChaCha20Poly1305(ChaCha20Poly1305Key),
     TeaclaveFile128(TeaclaveFile128Key),
     Raw,
 }
@@ -98,6 +134,18 @@ impl FileCrypto {
                 let crypto = AesGcm256Key::new(key, iv)?;
                 FileCrypto::AesGcm256(crypto)
             }
+            AesGcmSiv128Key::SCHEMA => {
+                let crypto = AesGcmSiv128Key::new(key, iv)?;
+                FileCrypto::AesGcmSiv128(crypto)
+            }
+            AesGcmSiv256Key::SCHEMA => {
+                let crypto = AesGcmSiv256Key::new(key, iv)?;
+                FileCrypto::AesGcmSiv256(crypto)
+            }
+            ChaCha20Poly1305Key::SCHEMA => {
+                let crypto = ChaCha20Poly1305Key::new(key, iv)?;
+                FileCrypto::ChaCha20Poly1305(crypto)
+            }
             TeaclaveFile128Key::SCHEMA => {
                 ensure!(iv.is_empty(), "IV is not empty for teaclave_file_128");
                 let crypto = TeaclaveFile128Key::new(key)?;
@@ -114,6 +162,9 @@ impl FileCrypto {
         match self {
             FileCrypto::AesGcm128(_) => AesGcm128Key::SCHEMA,
             FileCrypto::AesGcm256(_) => AesGcm256Key::SCHEMA,
+            FileCrypto::AesGcmSiv128(_) => AesGcmSiv128Key::SCHEMA,
+            FileCrypto::AesGcmSiv256(_) => AesGcmSiv256Key::SCHEMA,
+            FileCrypto::ChaCha20Poly1305(_) => ChaCha20Poly1305Key::SCHEMA,
             FileCrypto::TeaclaveFile128(_) => TeaclaveFile128Key::SCHEMA,
             FileCrypto::Raw => "raw",
         }
@@ -123,10 +174,58 @@ impl FileCrypto {
         match self {
             FileCrypto::AesGcm128(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
             FileCrypto::AesGcm256(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
+            FileCrypto::AesGcmSiv128(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
+            FileCrypto::AesGcmSiv256(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
+            FileCrypto::ChaCha20Poly1305(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
             FileCrypto::TeaclaveFile128(crypto) => (crypto.key.to_vec(), Vec::new()),
             FileCrypto::Raw => (vec![], vec![]),
         }
     }
+
+    /// Whether the schema seals files in memory, i.e., all but
+    /// teaclave-file-128 and raw.
+    pub fn is_in_memory(&self) -> bool {
+        !matches!(self, FileCrypto::TeaclaveFile128(_) | FileCrypto::Raw)
+    }
+
+    /// Encrypt the whole content with the schemes sealing files in memory,
+    /// i.e., all but teaclave-file-128 and raw, bound to `context` if any.
+    /// The tag is appended.
+    pub fn encrypt_in_memory(
+        &self,
+        in_out: &mut Vec<u8>,
+        context: Option<&EncryptionContext>,
+    ) -> Result<FileAuthTag> {
+        let aad = aad_of(context);
+        let cmac = match self {
+            FileCrypto::AesGcm128(crypto) => crypto.encrypt_with_aad(in_out, &aad)?,
+            FileCrypto::AesGcm256(crypto) => crypto.encrypt_with_aad(in_out, &aad)?,
+            FileCrypto::AesGcmSiv128(crypto) => crypto.encrypt_with_aad(in_out, &aad)?,
+            FileCrypto::AesGcmSiv256(crypto) => crypto.encrypt_with_aad(in_out, &aad)?,
+            FileCrypto::ChaCha20Poly1305(crypto) => crypto.encrypt_with_aad(in_out, &aad)?,
+            _ => bail!("Not an in-memory crypto schema: {}", self.schema()),
+        };
+        Ok(FileAuthTag::from(cmac))
+    }
+
+    /// Decrypt the content encrypted by `encrypt_in_memory` with the same
+    /// `context`, whose tag is removed and returned.
+    pub fn decrypt_in_memory(
+        &self,
+        in_out: &mut Vec<u8>,
+        context: Option<&EncryptionContext>,
+    ) -> Result<FileAuthTag> {
+        let aad = aad_of(context);
+        let cmac = match self {
+            FileCrypto::AesGcm128(crypto) => crypto.decrypt_with_aad(in_out, &aad)?,
+            FileCrypto::AesGcm256(crypto) => crypto.decrypt_with_aad(in_out, &aad)?,
+            FileCrypto::AesGcmSiv128(crypto) => crypto.decrypt_with_aad(in_out, &aad)?,
+            FileCrypto::AesGcmSiv256(crypto) => crypto.decrypt_with_aad(in_out, &aad)?,
+            FileCrypto::ChaCha20Poly1305(crypto) => crypto.decrypt_with_aad(in_out, &aad)?,
+            _ => bail!("Not an in-memory crypto schema: {}", self.schema()),
+        };
+        Ok(FileAuthTag::from(cmac))
+    }
 }
 
 impl std::convert::From<AesGcm128Key> for FileCrypto {
@@ -141,14 +240,109 @@ impl std::convert::From<AesGcm256Key> for FileCrypto {
     }
 }
 
+impl std::convert::From<AesGcmSiv128Key> for FileCrypto {
+    fn from(crypto: AesGcmSiv128Key) -> Self {
+        FileCrypto::AesGcmSiv128(crypto)
+    }
+}
+
+impl std::convert::From<AesGcmSiv256Key> for FileCrypto {
+    fn from(crypto: AesGcmSiv256Key) -> Self {
+        FileCrypto::AesGcmSiv256(crypto)
+    }
+}
+
+impl std::convert::From<ChaCha20Poly1305Key> for FileCrypto {
+    fn from(crypto: ChaCha20Poly1305Key) -> Self {
+        FileCrypto::ChaCha20Poly1305(crypto)
+    }
+}
+
 impl std::convert::From<TeaclaveFile128Key> for FileCrypto {
     fn from(crypto: TeaclaveFile128Key) -> Self {
         FileCrypto::TeaclaveFile128(crypto)
     }
 }
 
+impl Zeroize for FileCrypto {
+    fn zeroize(&mut self) {
+        match self {
+            FileCrypto::AesGcm128(crypto) => crypto.zeroize(),
+            FileCrypto::AesGcm256(crypto) => crypto.zeroize(),
+            FileCrypto::AesGcmSiv128(crypto) => crypto.zeroize(),
+            FileCrypto::AesGcmSiv256(crypto) => crypto.zeroize(),
+            FileCrypto::ChaCha20Poly1305(crypto) => crypto.zeroize(),
+            FileCrypto::TeaclaveFile128(crypto) => crypto.zeroize(),
+            FileCrypto::Raw => (),
+        }
+    }
+}
+
 impl Default for FileCrypto {
     fn default() -> Self {
         FileCrypto::TeaclaveFile128(TeaclaveFile128Key::random())
     }
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_file_crypto_in_memory() {
+        let schemas = [
+            (AesGcmSiv128Key::SCHEMA, 16),
+            (AesGcmSiv256Key::SCHEMA, 32),
+            (ChaCha20Poly1305Key::SCHEMA, 32),
+        ];
+        for (schema, key_length) in schemas {
+            let crypto = FileCrypto::new(schema, &vec![1; key_length], &[2; 12]).unwrap();
+            assert_eq!(crypto.schema(), schema);
+            assert!(crypto.is_in_memory());
+            assert!(FileCrypto::new(schema, &vec![1; key_length], &[]).is_err());
+
+            let mut content = b"hello".to_vec();
+            let cmac = crypto.encrypt_in_memory(&mut content, None).unwrap();
+            assert_eq!(cmac, content[content.len() - FILE_AUTH_TAG_LENGTH..]);
+            assert_eq!(crypto.decrypt_in_memory(&mut content, None).unwrap(), cmac);
+            assert_eq!(content, b"hello");
+        }
+
+        let mut crypto = FileCrypto::default();
+        assert!(!crypto.is_in_memory());
+        assert!(crypto
+            .encrypt_in_memory(&mut b"hello".to_vec(), None)
+            .is_err());
+        crypto.zeroize();
+        assert_eq!(crypto.key_iv().0, vec![0; 16]);
+    }
+
+    pub fn test_file_crypto_context() {
+        let crypto = FileCrypto::new(AesGcm128Key::SCHEMA, &[1; 16], &[2; 12]).unwrap();
+        let task_id = Uuid::new_v4();
+        let data_id = Uuid::new_v4();
+        let context = EncryptionContext::new(task_id, "model", data_id);
+
+        let mut content = b"hello".to_vec();
+        crypto
+            .encrypt_in_memory(&mut content, Some(&context))
+            .unwrap();
+
+        // Neither unbound nor bound to another slot or task
+        assert!(crypto
+            .decrypt_in_memory(&mut content.clone(), None)
+            .is_err());
+        let other_slot = EncryptionContext::new(task_id, "report", data_id);
+        assert!(crypto
+            .decrypt_in_memory(&mut content.clone(), Some(&other_slot))
+            .is_err());
+        let other_task = EncryptionContext::new(Uuid::new_v4(), "model", data_id);
+        assert!(crypto
+            .decrypt_in_memory(&mut content.clone(), Some(&other_task))
+            .is_err());
+
+        crypto
+            .decrypt_in_memory(&mut content, Some(&context))
+            .unwrap();
+        assert_eq!(content, b"hello");
+    }
+}
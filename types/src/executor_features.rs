@@ -0,0 +1,154 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Features of executors by name, each with a version, e.g., `python` of
+/// `2.7`, or an empty one for features without versions, e.g., `wasm_simd`.
+/// Functions list the features they need with the least versions, and
+/// executors advertise the features they have when pulling tasks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExecutorFeatures {
+    inner: BTreeMap<String, String>,
+}
+
+/// The reason an executor cannot run a task.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FeatureMismatch {
+    #[error("executor feature {0} is not supported")]
+    Unsupported(String),
+    #[error("executor feature {feature} {required} is required, but {advertised} is supported")]
+    Version {
+        feature: String,
+        required: String,
+        advertised: String,
+    },
+}
+
+impl ExecutorFeatures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feature(mut self, name: impl ToString, version: impl ToString) -> Self {
+        self.inner.insert(name.to_string(), version.to_string());
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.inner.get(name).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.inner.iter()
+    }
+
+    /// Check that an executor with `advertised` features runs the functions
+    /// needing these ones.
+    pub fn check(&self, advertised: &ExecutorFeatures) -> Result<(), FeatureMismatch> {
+        for (feature, required) in self.iter() {
+            let version = advertised
+                .get(feature)
+                .ok_or_else(|| FeatureMismatch::Unsupported(feature.to_string()))?;
+            if let Some(Ordering::Less) | None = compare_versions(version, required) {
+                return Err(FeatureMismatch::Version {
+                    feature: feature.to_string(),
+                    required: required.to_string(),
+                    advertised: version.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Versions are compared by their dot-separated numbers, with the missing ones
+/// taken as zero, and any version satisfies an empty one. Versions not made of
+/// numbers only match themselves.
+fn compare_versions(version: &str, required: &str) -> Option<Ordering> {
+    if required.is_empty() || version == required {
+        return Some(Ordering::Equal);
+    }
+    let parse = |v: &str| {
+        v.split('.')
+            .map(|n| n.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()
+    };
+    let (mut version, mut required) = (parse(version)?, parse(required)?);
+    let len = version.len().max(required.len());
+    version.resize(len, 0);
+    required.resize(len, 0);
+    Some(version.cmp(&required))
+}
+
+impl From<HashMap<String, String>> for ExecutorFeatures {
+    fn from(features: HashMap<String, String>) -> Self {
+        Self {
+            inner: features.into_iter().collect(),
+        }
+    }
+}
+
+impl From<ExecutorFeatures> for HashMap<String, String> {
+    fn from(features: ExecutorFeatures) -> Self {
+        features.inner.into_iter().collect()
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_executor_features() {
+        let advertised = ExecutorFeatures::new()
+            .feature("python", "2.7")
+            .feature("wasm_simd", "");
+
+        assert!(ExecutorFeatures::new().check(&advertised).is_ok());
+        let required = ExecutorFeatures::new()
+            .feature("python", "2")
+            .feature("wasm_simd", "");
+        assert!(required.check(&advertised).is_ok());
+        let required = ExecutorFeatures::new().feature("python", "2.7.0");
+        assert!(required.check(&advertised).is_ok());
+
+        let required = ExecutorFeatures::new().feature("python", "3.6");
+        assert_eq!(
+            required.check(&advertised),
+            Err(FeatureMismatch::Version {
+                feature: "python".to_string(),
+                required: "3.6".to_string(),
+                advertised: "2.7".to_string(),
+            })
+        );
+        let required = ExecutorFeatures::new().feature("python", "stdlib-min");
+        assert!(required.check(&advertised).is_err());
+        let required = ExecutorFeatures::new().feature("wasm_threads", "");
+        assert_eq!(
+            required.check(&advertised),
+            Err(FeatureMismatch::Unsupported("wasm_threads".to_string()))
+        );
+    }
+}
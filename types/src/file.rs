@@ -16,7 +16,10 @@
 // under the License.
 
 use crate::storage::Storable;
-use crate::{FileAuthTag, FileCrypto, OwnerList};
+use crate::task_state::now_secs;
+use crate::{
+    EncryptionContext, ExternalID, FileAuthTag, FileCrypto, OutputPolicy, OwnerList, UserID,
+};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use url::Url;
@@ -36,6 +39,38 @@ pub struct TeaclaveInputFile {
     pub crypto_info: FileCrypto,
     pub owner: OwnerList,
     pub uuid: Uuid,
+    /// Set if the file is an output bound to the task and slot producing it
+    #[serde(default)]
+    pub context: Option<EncryptionContext>,
+    /// Set if the owner restricts the functions using the file
+    #[serde(default)]
+    pub access_policy: Option<InputAccessPolicy>,
+}
+
+/// Functions allowed to use an input file, by ID or by owner. Tasks of other
+/// functions cannot be assigned the file, and assigning the file to the tasks
+/// of the allowed ones approves them for the owner.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InputAccessPolicy {
+    pub functions: Vec<ExternalID>,
+    pub function_owners: Vec<UserID>,
+}
+
+impl InputAccessPolicy {
+    pub fn new(functions: Vec<ExternalID>, function_owners: Vec<UserID>) -> Self {
+        InputAccessPolicy {
+            functions,
+            function_owners,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.function_owners.is_empty()
+    }
+
+    pub fn allows(&self, function_id: &ExternalID, function_owner: &UserID) -> bool {
+        self.functions.contains(function_id) || self.function_owners.contains(function_owner)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -45,6 +80,54 @@ pub struct TeaclaveOutputFile {
     pub crypto_info: FileCrypto,
     pub owner: OwnerList,
     pub uuid: Uuid,
+    #[serde(default)]
+    pub release_verdict: Option<ReleaseVerdict>,
+    /// Whether to bind the content to the task and slot producing it
+    #[serde(default)]
+    pub bind_context: bool,
+    /// Set when the content is produced if `bind_context`
+    #[serde(default)]
+    pub context: Option<EncryptionContext>,
+    /// Applied in order to the content before it is uploaded
+    #[serde(default)]
+    pub output_policies: Vec<OutputPolicy>,
+    /// The second since the UNIX epoch the output is deleted after, if any
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+/// Whether an output file can be released to its owners, as decided by the
+/// release verifier builtin function on the finished content of the output.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ReleaseVerdict {
+    pub releasable: bool,
+    pub reason: String,
+    // The verifier task, set when the verdict is recorded on the output
+    #[serde(default)]
+    pub task_id: Option<Uuid>,
+}
+
+impl ReleaseVerdict {
+    /// Name of the builtin function whose results are recorded as verdicts.
+    pub const VERIFIER: &'static str = "builtin-k-anonymity-verify";
+    /// Input of the verifier that should be registered from the output.
+    pub const VERIFIER_INPUT: &'static str = "data";
+
+    pub fn releasable(reason: impl ToString) -> Self {
+        Self {
+            releasable: true,
+            reason: reason.to_string(),
+            task_id: None,
+        }
+    }
+
+    pub fn refused(reason: impl ToString) -> Self {
+        Self {
+            releasable: false,
+            reason: reason.to_string(),
+            task_id: None,
+        }
+    }
 }
 
 impl TeaclaveInputFile {
@@ -60,9 +143,21 @@ impl TeaclaveInputFile {
             crypto_info,
             owner: owner.into(),
             uuid: create_uuid(),
+            context: None,
+            access_policy: None,
         }
     }
 
+    /// An empty policy allows all the functions.
+    pub fn access_policy(mut self, policy: InputAccessPolicy) -> Self {
+        self.access_policy = if policy.is_empty() {
+            None
+        } else {
+            Some(policy)
+        };
+        self
+    }
+
     pub fn from_output(output: TeaclaveOutputFile) -> Result<TeaclaveInputFile> {
         let input = TeaclaveInputFile {
             url: output.url,
@@ -72,6 +167,8 @@ impl TeaclaveInputFile {
             crypto_info: output.crypto_info,
             owner: output.owner,
             uuid: output.uuid,
+            context: output.context,
+            access_policy: None,
         };
         Ok(input)
     }
@@ -99,6 +196,44 @@ impl TeaclaveOutputFile {
             crypto_info,
             owner: owner.into(),
             uuid: create_uuid(),
+            release_verdict: None,
+            bind_context: false,
+            context: None,
+            output_policies: Vec::new(),
+            expires_at: None,
+        }
+    }
+
+    pub fn bind_context(mut self, bind_context: bool) -> Self {
+        self.bind_context = bind_context;
+        self
+    }
+
+    pub fn output_policies(mut self, policies: impl Into<Vec<OutputPolicy>>) -> Self {
+        self.output_policies = policies.into();
+        self
+    }
+
+    /// Keeps the output for `secs` seconds from now. Zero keeps it forever.
+    pub fn retention(mut self, secs: u64) -> Self {
+        self.expires_at = if secs == 0 {
+            None
+        } else {
+            Some(now_secs().saturating_add(secs as i64))
+        };
+        self
+    }
+
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= now_secs())
+    }
+
+    /// Context of the content produced by the task `task_id` in `slot`.
+    pub fn context_for(&self, task_id: Uuid, slot: &str) -> Option<EncryptionContext> {
+        if self.bind_context {
+            Some(EncryptionContext::new(task_id, slot, self.uuid))
+        } else {
+            None
         }
     }
 
@@ -107,6 +242,19 @@ impl TeaclaveOutputFile {
         self.cmac = Some(cmac.to_owned());
         Ok(())
     }
+
+    pub fn is_releasable(&self) -> bool {
+        matches!(&self.release_verdict, Some(verdict) if verdict.releasable)
+    }
+
+    /// A refusal is kept once recorded, so that running the verifier again
+    /// with looser thresholds cannot make the output releasable.
+    pub fn record_release_verdict(&mut self, verdict: ReleaseVerdict) {
+        if matches!(&self.release_verdict, Some(v) if !v.releasable) {
+            return;
+        }
+        self.release_verdict = Some(verdict);
+    }
 }
 
 impl Storable for TeaclaveOutputFile {
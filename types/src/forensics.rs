@@ -0,0 +1,136 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::task_state::now_secs;
+use crate::{FileAuthTag, FileCrypto, StagedTask, Storable, TaskUsage};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// What the executor knows about a failed run of a task, so that crashes which
+/// are hard to reproduce can be analyzed offline. The bundle is serialized in
+/// JSON and sealed with a new key before it is uploaded.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ForensicBundle {
+    pub task_id: String,
+    /// SHA-256 of the staged task in hex, which tells whether two crashes are
+    /// of the same function, arguments and data without disclosing them
+    pub task_hash: String,
+    pub executor: String,
+    pub function_name: String,
+    /// The reason in the failure record of the task
+    pub failure: String,
+    /// The message of the panic, if the function panicked
+    pub panic: Option<String>,
+    /// The last lines of the execution log of the run
+    pub log_tail: Vec<String>,
+    pub usage: TaskUsage,
+    /// Peak bytes of the enclave heap used, which the crashed run may have
+    /// run out of
+    pub peak_heap_bytes: u64,
+    /// The second since the UNIX epoch the bundle was made at
+    pub created_at: i64,
+}
+
+impl ForensicBundle {
+    pub fn new(task: &StagedTask, failure: impl ToString) -> Result<Self> {
+        let staged = task.to_vec()?;
+        Ok(Self {
+            task_id: task.task_id.to_string(),
+            task_hash: hex::encode(ring::digest::digest(&ring::digest::SHA256, &staged)),
+            executor: task.executor.to_string(),
+            function_name: task.function_name.clone(),
+            failure: failure.to_string(),
+            created_at: now_secs(),
+            ..Default::default()
+        })
+    }
+
+    pub fn panic(self, panic: Option<String>) -> Self {
+        Self { panic, ..self }
+    }
+
+    /// Keep the last `lines` lines of `log`.
+    pub fn log_tail(self, log: &[String], lines: usize) -> Self {
+        let start = log.len().saturating_sub(lines);
+        Self {
+            log_tail: log[start..].to_vec(),
+            ..self
+        }
+    }
+
+    pub fn usage(self, usage: TaskUsage) -> Self {
+        Self { usage, ..self }
+    }
+
+    pub fn peak_heap_bytes(self, peak_heap_bytes: u64) -> Self {
+        Self {
+            peak_heap_bytes,
+            ..self
+        }
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+/// A sealed forensic bundle uploaded by the executor, referred to from the
+/// failure record of the task.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ForensicBundleFile {
+    pub url: url::Url,
+    pub crypto_info: FileCrypto,
+    pub cmac: FileAuthTag,
+}
+
+impl ForensicBundleFile {
+    pub fn new(url: url::Url, crypto_info: FileCrypto, cmac: FileAuthTag) -> Self {
+        ForensicBundleFile {
+            url,
+            crypto_info,
+            cmac,
+        }
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use crate::StagedTaskBuilder;
+    use uuid::Uuid;
+
+    pub fn test_forensic_bundle() {
+        let task = StagedTaskBuilder::new()
+            .task_id(Uuid::new_v4())
+            .function_name("builtin-echo")
+            .build();
+        let log: Vec<String> = (0..5).map(|i| format!("line {}", i)).collect();
+        let bundle = ForensicBundle::new(&task, "The function panicked")
+            .unwrap()
+            .panic(Some("index out of bounds".to_string()))
+            .log_tail(&log, 2)
+            .peak_heap_bytes(4096);
+        assert_eq!(bundle.task_id, task.task_id.to_string());
+        assert_eq!(bundle.task_hash.len(), 64);
+        assert_eq!(bundle.log_tail, vec!["line 3", "line 4"]);
+        assert_eq!(bundle.clone().log_tail(&log, 10).log_tail.len(), 5);
+
+        // The same staged task has the same hash.
+        let again = ForensicBundle::new(&task, "another failure").unwrap();
+        assert_eq!(again.task_hash, bundle.task_hash);
+    }
+}
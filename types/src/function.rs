@@ -15,7 +15,8 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::{ExecutorType, Storable, UserID};
+use crate::{ExecutorFeatures, ExecutorType, FileCrypto, Storable, UserID};
+use anyhow::{bail, ensure, Context, Result};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -88,6 +89,20 @@ pub struct Function {
     pub owner: UserID,
     pub user_allowlist: Vec<String>,
     pub usage_quota: Option<i32>,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether the execution services report phase timings of its tasks
+    #[serde(default)]
+    pub profiling: bool,
+    /// Key of the payload encrypted by the owner, with which only the
+    /// execution service decrypts the payload
+    #[serde(default)]
+    pub payload_crypto: Option<FileCrypto>,
+    /// Features the executors running the function need
+    #[serde(default)]
+    pub executor_features: ExecutorFeatures,
 }
 
 #[derive(Default)]
@@ -167,6 +182,31 @@ impl FunctionBuilder {
         self
     }
 
+    pub fn version(mut self, version: impl ToString) -> Self {
+        self.function.version = version.to_string();
+        self
+    }
+
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.function.tags = tags;
+        self
+    }
+
+    pub fn profiling(mut self, profiling: bool) -> Self {
+        self.function.profiling = profiling;
+        self
+    }
+
+    pub fn payload_crypto(mut self, payload_crypto: Option<FileCrypto>) -> Self {
+        self.function.payload_crypto = payload_crypto;
+        self
+    }
+
+    pub fn executor_features(mut self, executor_features: ExecutorFeatures) -> Self {
+        self.function.executor_features = executor_features;
+        self
+    }
+
     pub fn build(self) -> Function {
         self.function
     }
@@ -190,6 +230,8 @@ pub struct FunctionArgument {
     pub key: String,
     pub default_value: String,
     pub allow_overwrite: bool,
+    #[serde(default)]
+    pub value_type: ArgumentType,
 }
 
 impl FunctionArgument {
@@ -202,8 +244,109 @@ impl FunctionArgument {
             key: key.into(),
             default_value: default_value.into(),
             allow_overwrite,
+            value_type: ArgumentType::Any,
+        }
+    }
+
+    pub fn value_type(mut self, value_type: ArgumentType) -> Self {
+        self.value_type = value_type;
+        self
+    }
+
+    /// The default value is a JSON text unless the argument is untyped or a
+    /// string.
+    pub fn default_json_value(&self) -> Result<serde_json::Value> {
+        match self.value_type {
+            ArgumentType::Any | ArgumentType::String => {
+                Ok(serde_json::Value::String(self.default_value.clone()))
+            }
+            value_type => {
+                let value = serde_json::from_str(&self.default_value).with_context(|| {
+                    format!("invalid default value of function argument {}", self.key)
+                })?;
+                value_type.check(&self.key, &value)?;
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Type of a function argument declared on registration. Untyped arguments
+/// accept any value.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgumentType {
+    Any,
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl std::default::Default for ArgumentType {
+    fn default() -> Self {
+        ArgumentType::Any
+    }
+}
+
+impl ArgumentType {
+    pub fn matches(&self, value: &serde_json::Value) -> bool {
+        use serde_json::Value;
+        match self {
+            ArgumentType::Any => true,
+            ArgumentType::String => value.is_string(),
+            ArgumentType::Integer => value.is_i64() || value.is_u64(),
+            ArgumentType::Number => value.is_number(),
+            ArgumentType::Boolean => value.is_boolean(),
+            ArgumentType::Array => matches!(value, Value::Array(_)),
+            ArgumentType::Object => matches!(value, Value::Object(_)),
         }
     }
+
+    pub fn check(&self, key: &str, value: &serde_json::Value) -> Result<()> {
+        ensure!(
+            self.matches(value),
+            "function argument {} should be of type {}",
+            key,
+            self
+        );
+        Ok(())
+    }
+}
+
+impl std::convert::TryFrom<&str> for ArgumentType {
+    type Error = anyhow::Error;
+
+    fn try_from(value_type: &str) -> Result<Self> {
+        let value_type = match value_type {
+            "" | "any" => ArgumentType::Any,
+            "string" => ArgumentType::String,
+            "integer" => ArgumentType::Integer,
+            "number" => ArgumentType::Number,
+            "boolean" => ArgumentType::Boolean,
+            "array" => ArgumentType::Array,
+            "object" => ArgumentType::Object,
+            _ => bail!("Invalid argument type: {}", value_type),
+        };
+        Ok(value_type)
+    }
+}
+
+impl std::fmt::Display for ArgumentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let value_type = match self {
+            ArgumentType::Any => "any",
+            ArgumentType::String => "string",
+            ArgumentType::Integer => "integer",
+            ArgumentType::Number => "number",
+            ArgumentType::Boolean => "boolean",
+            ArgumentType::Array => "array",
+            ArgumentType::Object => "object",
+        };
+        write!(f, "{}", value_type)
+    }
 }
 
 const FUNCION_USAGE_PREFIX: &str = "usage";
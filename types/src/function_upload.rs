@@ -0,0 +1,167 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::task_state::now_secs;
+use crate::{Storable, UserID};
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const FUNCTION_UPLOAD_PREFIX: &str = "function_upload";
+const FUNCTION_UPLOAD_CHUNK_PREFIX: &str = "function_upload_chunk";
+/// Uploads not committed in time are removed along with their chunks.
+pub const FUNCTION_UPLOAD_TTL_SECS: i64 = 24 * 3600;
+
+/// A function payload uploaded in chunks, which are stored apart from the
+/// upload under `function_upload_chunk-<upload id>-<index>`. The payload is
+/// registered as a function once all of it is received and matches the
+/// digest given at the beginning.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FunctionUpload {
+    pub upload_id: Uuid,
+    pub owner: UserID,
+    /// Size of the whole payload in bytes
+    pub total_size: u64,
+    /// Hex-encoded SHA-256 of the whole payload
+    pub sha256: String,
+    pub received_size: u64,
+    pub chunks: u32,
+    /// The second since the UNIX epoch the upload expires at
+    pub expires_at: i64,
+}
+
+impl Storable for FunctionUpload {
+    fn key_prefix() -> &'static str {
+        FUNCTION_UPLOAD_PREFIX
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.upload_id
+    }
+}
+
+impl FunctionUpload {
+    pub fn new(
+        owner: impl Into<UserID>,
+        total_size: u64,
+        sha256: impl ToString,
+        max_size: u64,
+    ) -> Result<Self> {
+        let sha256 = sha256.to_string().to_lowercase();
+        ensure!(total_size > 0, "The payload is empty");
+        ensure!(
+            total_size <= max_size,
+            "The payload exceeds the limit of {} bytes",
+            max_size
+        );
+        ensure!(
+            sha256.len() == 64 && hex::decode(&sha256).is_ok(),
+            "Invalid SHA-256 digest"
+        );
+        Ok(FunctionUpload {
+            upload_id: Uuid::new_v4(),
+            owner: owner.into(),
+            total_size,
+            sha256,
+            received_size: 0,
+            chunks: 0,
+            expires_at: now_secs() + FUNCTION_UPLOAD_TTL_SECS,
+        })
+    }
+
+    /// Storage key of the chunk at `index`.
+    pub fn chunk_key(&self, index: u32) -> String {
+        format!(
+            "{}-{}-{:08x}",
+            FUNCTION_UPLOAD_CHUNK_PREFIX, self.upload_id, index
+        )
+    }
+
+    /// Accept a chunk of `len` bytes at `offset`, returning its index.
+    /// Chunks are taken in order, each right after the ones before.
+    pub fn accept_chunk(&mut self, offset: u64, len: u64, max_chunk_size: u64) -> Result<u32> {
+        ensure!(
+            offset == self.received_size,
+            "Expected the chunk at offset {}",
+            self.received_size
+        );
+        ensure!(len > 0, "The chunk is empty");
+        ensure!(
+            len <= max_chunk_size,
+            "The chunk exceeds the limit of {} bytes",
+            max_chunk_size
+        );
+        ensure!(
+            self.received_size + len <= self.total_size,
+            "The chunk exceeds the size of the payload"
+        );
+        let index = self.chunks;
+        self.received_size += len;
+        self.chunks += 1;
+        Ok(index)
+    }
+
+    /// The hex-encoded SHA-256 of a payload to begin uploading.
+    pub fn digest(payload: &[u8]) -> String {
+        let digest = ring::digest::digest(&ring::digest::SHA256, payload);
+        hex::encode(digest.as_ref())
+    }
+
+    /// Check the assembled payload against the size and digest.
+    pub fn verify(&self, payload: &[u8]) -> Result<()> {
+        ensure!(
+            self.received_size == self.total_size && payload.len() as u64 == self.total_size,
+            "Received {} of {} bytes",
+            self.received_size,
+            self.total_size
+        );
+        ensure!(
+            Self::digest(payload) == self.sha256,
+            "The payload does not match the SHA-256 digest"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_function_upload() {
+        let payload = b"def entrypoint(argv):\n    return 'ok'\n";
+        let digest = hex::encode(ring::digest::digest(&ring::digest::SHA256, payload).as_ref());
+        let size = payload.len() as u64;
+        assert!(FunctionUpload::new("alice", size, &digest, size - 1).is_err());
+        assert!(FunctionUpload::new("alice", size, "abc", size).is_err());
+
+        let mut upload = FunctionUpload::new("alice", size, &digest, size).unwrap();
+        assert_eq!(upload.accept_chunk(0, 16, 16).unwrap(), 0);
+        // out of order, too large and beyond the payload
+        assert!(upload.accept_chunk(0, 16, 16).is_err());
+        assert!(upload.accept_chunk(16, 17, 16).is_err());
+        assert_eq!(upload.accept_chunk(16, 16, 16).unwrap(), 1);
+        assert!(upload.verify(payload).is_err());
+        assert!(upload.accept_chunk(32, 16, 16).is_err());
+        assert_eq!(upload.accept_chunk(32, size - 32, 16).unwrap(), 2);
+        assert!(upload.chunk_key(1) < upload.chunk_key(2));
+
+        assert!(upload.verify(payload).is_ok());
+        assert!(upload
+            .verify(b"def entrypoint(argv):\n    return 'no'\n")
+            .is_err());
+    }
+}
@@ -19,43 +19,100 @@ extern crate sgx_types;
 
 mod attestation;
 mod audit;
+mod backlog;
+mod consent;
 mod crypto;
 mod error;
+mod executor_features;
 mod file;
 mod file_agent;
+mod forensics;
 mod function;
+mod function_upload;
+mod lineage;
+mod metadata_dump;
 mod macros;
+mod maintenance;
+mod notification;
+mod output_policy;
+mod profile;
+mod receipt;
+mod schedule;
 mod staged_file;
 mod staged_function;
 mod staged_task;
 mod storage;
 mod task;
+mod task_index;
 mod task_state;
+mod usage;
 mod user;
+mod webhook;
 mod worker;
 
 pub use attestation::*;
 pub use audit::*;
+pub use backlog::*;
+pub use consent::*;
 pub use crypto::*;
 pub use error::*;
+pub use executor_features::*;
 pub use file::*;
 pub use file_agent::*;
+pub use forensics::*;
 pub use function::*;
+pub use function_upload::*;
+pub use lineage::*;
+pub use metadata_dump::*;
 pub use macros::*;
+pub use maintenance::*;
+pub use notification::*;
+pub use output_policy::*;
+pub use profile::*;
+pub use receipt::*;
+pub use schedule::*;
 pub use staged_file::*;
 pub use staged_function::*;
 pub use staged_task::*;
 pub use storage::*;
 pub use task::*;
+pub use task_index::*;
 pub use task_state::*;
+pub use usage::*;
 pub use user::*;
+pub use webhook::*;
 pub use worker::*;
 
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
 
+    use teaclave_test_utils::*;
+
     pub fn run_tests() -> bool {
         worker::tests::run_tests()
+            && run_tests!(
+                receipt::tests::test_sign_and_verify_receipt,
+                metadata_dump::tests::test_sign_and_verify_metadata_dump,
+                profile::tests::test_function_profile_average,
+                task_index::tests::test_task_index_changes,
+                notification::tests::test_collect_digests,
+                notification::tests::test_validate_preferences,
+                lineage::tests::test_lineage_from_task,
+                crypto::tests::test_file_crypto_in_memory,
+                crypto::tests::test_file_crypto_context,
+                file_agent::tests::test_file_transfer_redaction,
+                forensics::tests::test_forensic_bundle,
+                webhook::tests::test_webhook_notification,
+                schedule::tests::test_cron_schedule,
+                schedule::tests::test_instantiate_scheduled_task,
+                usage::tests::test_usage_record,
+                function_upload::tests::test_function_upload,
+                executor_features::tests::test_executor_features,
+                output_policy::tests::test_validate_output_policy,
+                maintenance::tests::test_maintenance_window,
+                staged_task::tests::test_batch_tasks,
+                staged_task::tests::test_resource_limits,
+            )
     }
 }
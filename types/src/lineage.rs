@@ -0,0 +1,140 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::task_state::now_secs;
+use crate::{ExternalID, Storable, TaskResult, TaskState};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+const LINEAGE_PREFIX: &str = "lineage";
+
+/// Where a piece of data comes from.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum LineageSource {
+    /// The input is registered from the output.
+    Output { output_id: ExternalID },
+    /// The output is produced by the task, which runs the function with the
+    /// inputs, keyed by the input names.
+    Task {
+        task_id: Uuid,
+        function_id: ExternalID,
+        inputs: BTreeMap<String, ExternalID>,
+    },
+}
+
+/// The lineage of a piece of data, i.e., an edge to its upstream in the
+/// lineage graph. Data uploaded by the owners has no lineage.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LineageRecord {
+    pub data_id: ExternalID,
+    pub source: LineageSource,
+    /// The second since the UNIX epoch
+    pub timestamp: i64,
+}
+
+impl LineageRecord {
+    pub fn from_output(input_id: ExternalID, output_id: ExternalID) -> Self {
+        LineageRecord {
+            data_id: input_id,
+            source: LineageSource::Output { output_id },
+            timestamp: now_secs(),
+        }
+    }
+
+    pub fn from_task(output_id: ExternalID, ts: &TaskState) -> Self {
+        let source = LineageSource::Task {
+            task_id: ts.task_id,
+            function_id: ts.function_id.clone(),
+            inputs: ts.assigned_inputs.external_ids().into_iter().collect(),
+        };
+        LineageRecord {
+            data_id: output_id,
+            source,
+            timestamp: now_secs(),
+        }
+    }
+
+    /// Lineage of the outputs which the finished task has produced.
+    pub fn from_finished_task(ts: &TaskState) -> Vec<Self> {
+        let outputs = match &ts.result {
+            TaskResult::Ok(outputs) => outputs,
+            _ => return Vec::new(),
+        };
+        outputs
+            .tags_map
+            .keys()
+            .filter_map(|name| ts.assigned_outputs.get(name))
+            .map(|output| Self::from_task(output.external_id(), ts))
+            .collect()
+    }
+
+    /// An input registered from an output shares the uuid of the output, so
+    /// records are keyed by the whole external id of the data.
+    pub fn key(data_id: &ExternalID) -> Vec<u8> {
+        format!("{}-{}", LINEAGE_PREFIX, data_id.to_string()).into_bytes()
+    }
+
+    /// The data which this one is derived from.
+    pub fn upstream(&self) -> Vec<ExternalID> {
+        match &self.source {
+            LineageSource::Output { output_id } => vec![output_id.clone()],
+            LineageSource::Task { inputs, .. } => inputs.values().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use crate::{FileAuthTag, FileCrypto, TaskOutputs, TeaclaveInputFile, TeaclaveOutputFile};
+    use url::Url;
+
+    pub fn test_lineage_from_task() {
+        let url = Url::parse("fusion:///TEACLAVE_FUSION_BASE/a.fusion").unwrap();
+        let input = TeaclaveInputFile::new(
+            url.clone(),
+            FileAuthTag::mock(),
+            FileCrypto::default(),
+            vec!["user"],
+        );
+        let output = TeaclaveOutputFile::new(url, FileCrypto::default(), vec!["user"]);
+        let output_id = output.external_id();
+
+        let mut ts = TaskState::default();
+        ts.function_id = ExternalID::new("function", Uuid::new_v4());
+        ts.assigned_inputs.assign("input", input.clone()).unwrap();
+        ts.assigned_outputs.assign("output", output).unwrap();
+        assert!(LineageRecord::from_finished_task(&ts).is_empty());
+
+        let tags = crate::hashmap!("output".to_string() => FileAuthTag::mock());
+        ts.result = TaskResult::Ok(TaskOutputs::new(Vec::new(), tags, vec![]));
+        let records = LineageRecord::from_finished_task(&ts);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].data_id, output_id);
+        assert_eq!(records[0].upstream(), vec![input.external_id()]);
+
+        // The input registered from the output shares its uuid
+        let registered = ExternalID::new(TeaclaveInputFile::key_prefix(), output_id.uuid);
+        let record = LineageRecord::from_output(registered.clone(), output_id.clone());
+        assert_eq!(record.upstream(), vec![output_id.clone()]);
+        assert_ne!(
+            LineageRecord::key(&registered),
+            LineageRecord::key(&output_id)
+        );
+    }
+}
@@ -0,0 +1,211 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::Storable;
+use anyhow::{bail, ensure, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const MAINTENANCE_WINDOW_PREFIX: &str = "maintenance_window";
+const MAX_REASON_LEN: usize = 256;
+
+/// What `InvokeTask` does during a maintenance window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MaintenanceMode {
+    /// Tasks are invoked but not staged until the window ends.
+    QueueTasks,
+    /// Tasks are rejected, telling the caller when to retry.
+    RejectTasks,
+}
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        MaintenanceMode::QueueTasks
+    }
+}
+
+impl std::convert::TryFrom<i32> for MaintenanceMode {
+    type Error = anyhow::Error;
+
+    fn try_from(mode: i32) -> Result<Self> {
+        let mode = match mode {
+            0 => MaintenanceMode::QueueTasks,
+            1 => MaintenanceMode::RejectTasks,
+            _ => bail!("Invalid maintenance mode: {}", mode),
+        };
+        Ok(mode)
+    }
+}
+
+impl From<MaintenanceMode> for i32 {
+    fn from(mode: MaintenanceMode) -> i32 {
+        match mode {
+            MaintenanceMode::QueueTasks => 0,
+            MaintenanceMode::RejectTasks => 1,
+        }
+    }
+}
+
+/// A period declared by platform admins, from `starts_at` to `ends_at` in
+/// seconds since the UNIX epoch, in which no task is staged and the executors
+/// are drained.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MaintenanceWindow {
+    pub id: Uuid,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub mode: MaintenanceMode,
+    /// Shown to the users, e.g., "storage upgrade"
+    pub reason: String,
+    pub declared_by: String,
+}
+
+impl Storable for MaintenanceWindow {
+    fn key_prefix() -> &'static str {
+        MAINTENANCE_WINDOW_PREFIX
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl MaintenanceWindow {
+    pub fn new(
+        starts_at: i64,
+        ends_at: i64,
+        mode: MaintenanceMode,
+        reason: impl ToString,
+        declared_by: impl ToString,
+        now: i64,
+    ) -> Result<Self> {
+        let reason = reason.to_string();
+        ensure!(
+            starts_at < ends_at,
+            "The window should start before it ends"
+        );
+        ensure!(ends_at > now, "The window has already ended");
+        ensure!(
+            reason.len() <= MAX_REASON_LEN,
+            "The reason is longer than {} bytes",
+            MAX_REASON_LEN
+        );
+        Ok(Self {
+            id: Uuid::new_v4(),
+            starts_at,
+            ends_at,
+            mode,
+            reason,
+            declared_by: declared_by.to_string(),
+        })
+    }
+
+    pub fn is_active(&self, now: i64) -> bool {
+        self.starts_at <= now && now < self.ends_at
+    }
+
+    pub fn has_ended(&self, now: i64) -> bool {
+        self.ends_at <= now
+    }
+
+    /// Seconds until the window ends, for callers told to retry.
+    pub fn retry_after(&self, now: i64) -> u64 {
+        (self.ends_at - now).max(0) as u64
+    }
+}
+
+/// The window the service is in at `now`, the one ending last if windows
+/// overlap.
+pub fn active_maintenance_window(
+    windows: &[MaintenanceWindow],
+    now: i64,
+) -> Option<&MaintenanceWindow> {
+    windows
+        .iter()
+        .filter(|w| w.is_active(now))
+        .max_by_key(|w| w.ends_at)
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_maintenance_window() {
+        let now = 1_000;
+        assert!(
+            MaintenanceWindow::new(now + 10, now, MaintenanceMode::QueueTasks, "", "a", now)
+                .is_err()
+        );
+        assert!(MaintenanceWindow::new(
+            now - 20,
+            now - 10,
+            MaintenanceMode::QueueTasks,
+            "",
+            "a",
+            now
+        )
+        .is_err());
+        assert!(MaintenanceWindow::new(
+            now,
+            now + 10,
+            MaintenanceMode::QueueTasks,
+            "x".repeat(MAX_REASON_LEN + 1),
+            "admin",
+            now
+        )
+        .is_err());
+
+        let early = MaintenanceWindow::new(
+            now - 10,
+            now + 60,
+            MaintenanceMode::QueueTasks,
+            "upgrade",
+            "admin",
+            now,
+        )
+        .unwrap();
+        let late = MaintenanceWindow::new(
+            now,
+            now + 120,
+            MaintenanceMode::RejectTasks,
+            "upgrade",
+            "admin",
+            now,
+        )
+        .unwrap();
+        let next = MaintenanceWindow::new(
+            now + 600,
+            now + 900,
+            MaintenanceMode::RejectTasks,
+            "upgrade",
+            "admin",
+            now,
+        )
+        .unwrap();
+        assert!(early.is_active(now) && !next.is_active(now));
+        assert_eq!(late.retry_after(now), 120);
+        assert!(early.has_ended(now + 60));
+
+        let windows = vec![early, late.clone(), next];
+        assert_eq!(active_maintenance_window(&windows, now), Some(&late));
+        assert_eq!(active_maintenance_window(&windows, now + 300), None);
+        assert_eq!(
+            active_maintenance_window(&windows, now + 600).map(|w| w.starts_at),
+            Some(now + 600)
+        );
+    }
+}
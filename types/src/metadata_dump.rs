@@ -0,0 +1,354 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::task_state::now_secs;
+use crate::{
+    EnclaveMeasurement, Function, FunctionArgument, FunctionInput, FunctionOutput, OwnerList,
+    Storable, TaskState, TaskStatus, TeaclaveInputFile, TeaclaveOutputFile,
+};
+use anyhow::{anyhow, ensure, Result};
+use ring::signature;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use url::Url;
+
+/// Version of the dump format, bumped whenever the records change.
+pub const METADATA_DUMP_VERSION: u32 = 1;
+
+/// A function without its payload.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FunctionMetadata {
+    pub function_id: String,
+    pub name: String,
+    pub description: String,
+    pub public: bool,
+    pub executor_type: String,
+    /// SHA-256 of the payload in hex
+    pub payload_hash: String,
+    pub arguments: Vec<FunctionArgument>,
+    pub inputs: Vec<FunctionInput>,
+    pub outputs: Vec<FunctionOutput>,
+    pub owner: String,
+    pub user_allowlist: Vec<String>,
+    pub usage_quota: Option<i32>,
+    pub version: String,
+    pub tags: Vec<String>,
+}
+
+impl From<Function> for FunctionMetadata {
+    fn from(function: Function) -> Self {
+        FunctionMetadata {
+            function_id: function.external_id().to_string(),
+            name: function.name,
+            description: function.description,
+            public: function.public,
+            executor_type: function.executor_type.to_string(),
+            payload_hash: sha256_hex(&function.payload),
+            arguments: function.arguments,
+            inputs: function.inputs,
+            outputs: function.outputs,
+            owner: function.owner.to_string(),
+            user_allowlist: function.user_allowlist,
+            usage_quota: function.usage_quota,
+            version: function.version,
+            tags: function.tags,
+        }
+    }
+}
+
+/// An input or output file without its key. The query of the URL is dropped
+/// as well, since it may carry credentials, e.g., of a presigned URL.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DataMetadata {
+    pub data_id: String,
+    pub url: String,
+    /// CMAC of the content in hex, empty if not produced yet
+    pub cmac: String,
+    pub owners: Vec<String>,
+}
+
+impl From<&TeaclaveInputFile> for DataMetadata {
+    fn from(file: &TeaclaveInputFile) -> Self {
+        DataMetadata {
+            data_id: file.external_id().to_string(),
+            url: strip_query(&file.url),
+            cmac: file.cmac.to_hex(),
+            owners: sorted_owners(&file.owner),
+        }
+    }
+}
+
+impl From<&TeaclaveOutputFile> for DataMetadata {
+    fn from(file: &TeaclaveOutputFile) -> Self {
+        DataMetadata {
+            data_id: file.external_id().to_string(),
+            url: strip_query(&file.url),
+            cmac: file.cmac.map(|cmac| cmac.to_hex()).unwrap_or_default(),
+            owners: sorted_owners(&file.owner),
+        }
+    }
+}
+
+/// A task without its arguments and result, referring to its data by IDs.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TaskMetadata {
+    pub task_id: String,
+    pub creator: String,
+    pub function_id: String,
+    pub function_owner: String,
+    pub participants: Vec<String>,
+    pub approved_users: Vec<String>,
+    /// Data IDs of the assigned files, keyed by the input and output names
+    pub inputs: BTreeMap<String, String>,
+    pub outputs: BTreeMap<String, String>,
+    pub status: TaskStatus,
+    pub purpose: String,
+    pub created_at: i64,
+}
+
+impl From<&TaskState> for TaskMetadata {
+    fn from(ts: &TaskState) -> Self {
+        TaskMetadata {
+            task_id: ts.external_id().to_string(),
+            creator: ts.creator.to_string(),
+            function_id: ts.function_id.to_string(),
+            function_owner: ts.function_owner.to_string(),
+            participants: sorted_owners(&ts.participants),
+            approved_users: sorted_owners(&ts.approved_users),
+            inputs: ts
+                .assigned_inputs
+                .external_ids()
+                .into_iter()
+                .map(|(name, id)| (name, id.to_string()))
+                .collect(),
+            outputs: ts
+                .assigned_outputs
+                .external_ids()
+                .into_iter()
+                .map(|(name, id)| (name, id.to_string()))
+                .collect(),
+            status: ts.status.clone(),
+            purpose: ts.purpose.clone(),
+            created_at: ts.created_at,
+        }
+    }
+}
+
+/// A versioned dump of the metadata of functions, data and tasks for audits,
+/// which has no payload or key. Like an execution receipt, the dump is signed
+/// with the key of the attested TLS certificate of the management service, so
+/// that it can be verified offline.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MetadataDump {
+    pub version: u32,
+    /// The second since the UNIX epoch the dump was made at
+    pub created_at: i64,
+    pub functions: Vec<FunctionMetadata>,
+    pub input_files: Vec<DataMetadata>,
+    pub output_files: Vec<DataMetadata>,
+    pub tasks: Vec<TaskMetadata>,
+    pub mr_enclave: String,
+    pub mr_signer: String,
+    /// Attested TLS certificate in DER of the enclave which signed the dump
+    pub certificate: Vec<u8>,
+    /// ECDSA P-256 signature in ASN.1 over `signed_bytes()`
+    pub signature: Vec<u8>,
+}
+
+// Fields covered by the signature
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    version: u32,
+    created_at: i64,
+    functions: &'a [FunctionMetadata],
+    input_files: &'a [DataMetadata],
+    output_files: &'a [DataMetadata],
+    tasks: &'a [TaskMetadata],
+    mr_enclave: &'a str,
+    mr_signer: &'a str,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(ring::digest::digest(&ring::digest::SHA256, bytes))
+}
+
+fn strip_query(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url.to_string()
+}
+
+fn sorted_owners(owners: &OwnerList) -> Vec<String> {
+    let mut owners: Vec<String> = owners.uids.iter().map(|uid| uid.to_string()).collect();
+    owners.sort();
+    owners
+}
+
+impl MetadataDump {
+    /// Records are sorted by their IDs, so the same metadata gives the same
+    /// dump.
+    pub fn new(
+        functions: Vec<Function>,
+        input_files: &[TeaclaveInputFile],
+        output_files: &[TeaclaveOutputFile],
+        tasks: &[TaskState],
+    ) -> Self {
+        let mut functions: Vec<FunctionMetadata> =
+            functions.into_iter().map(FunctionMetadata::from).collect();
+        functions.sort_by(|a, b| a.function_id.cmp(&b.function_id));
+        let mut input_files: Vec<DataMetadata> =
+            input_files.iter().map(DataMetadata::from).collect();
+        input_files.sort_by(|a, b| a.data_id.cmp(&b.data_id));
+        let mut output_files: Vec<DataMetadata> =
+            output_files.iter().map(DataMetadata::from).collect();
+        output_files.sort_by(|a, b| a.data_id.cmp(&b.data_id));
+        let mut tasks: Vec<TaskMetadata> = tasks.iter().map(TaskMetadata::from).collect();
+        tasks.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+
+        MetadataDump {
+            version: METADATA_DUMP_VERSION,
+            created_at: now_secs(),
+            functions,
+            input_files,
+            output_files,
+            tasks,
+            ..Default::default()
+        }
+    }
+
+    /// Parse a dump, which is rejected if made in a format of another
+    /// version. The signature is not verified.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let dump: MetadataDump = serde_json::from_slice(bytes)?;
+        ensure!(
+            dump.version == METADATA_DUMP_VERSION,
+            "Unsupported metadata dump version {}",
+            dump.version
+        );
+        Ok(dump)
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn signed_bytes(&self) -> Result<Vec<u8>> {
+        let fields = SignedFields {
+            version: self.version,
+            created_at: self.created_at,
+            functions: &self.functions,
+            input_files: &self.input_files,
+            output_files: &self.output_files,
+            tasks: &self.tasks,
+            mr_enclave: &self.mr_enclave,
+            mr_signer: &self.mr_signer,
+        };
+        Ok(serde_json::to_vec(&fields)?)
+    }
+
+    /// Sign the dump with the PKCS#8 private key of the attested TLS
+    /// `certificate`, which is attached for verification.
+    pub fn sign(
+        mut self,
+        measurement: &EnclaveMeasurement,
+        certificate: &[u8],
+        private_key: &[u8],
+    ) -> Result<Self> {
+        self.mr_enclave = hex::encode(measurement.mr_enclave);
+        self.mr_signer = hex::encode(measurement.mr_signer);
+        self.certificate = certificate.to_vec();
+
+        let key_pair = signature::EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            private_key,
+        )
+        .map_err(|_| anyhow!("Invalid metadata dump signing key"))?;
+        let rng = ring::rand::SystemRandom::new();
+        let signature = key_pair
+            .sign(&rng, &self.signed_bytes()?)
+            .map_err(|_| anyhow!("Failed to sign the metadata dump"))?;
+        self.signature = signature.as_ref().to_vec();
+        Ok(self)
+    }
+
+    /// Verify the signature with the uncompressed P-256 public key taken from
+    /// the attested certificate.
+    pub fn verify_signature(&self, public_key: &[u8]) -> Result<()> {
+        signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, public_key)
+            .verify(&self.signed_bytes()?, &self.signature)
+            .map_err(|_| anyhow!("Invalid metadata dump signature"))
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use crate::{FileAuthTag, FileCrypto, FunctionBuilder};
+    use ring::signature::KeyPair;
+
+    pub fn test_sign_and_verify_metadata_dump() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = signature::EcdsaKeyPair::generate_pkcs8(
+            &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            &rng,
+        )
+        .unwrap();
+        let key_pair = signature::EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            pkcs8.as_ref(),
+        )
+        .unwrap();
+        let public_key = key_pair.public_key().as_ref();
+
+        let function = FunctionBuilder::new()
+            .payload(b"def entrypoint(argv): pass".to_vec())
+            .build();
+        let url =
+            Url::parse("https://bucket.s3.amazonaws.com/data?X-Amz-Signature=secret").unwrap();
+        let input = TeaclaveInputFile::new(
+            url,
+            FileAuthTag::mock(),
+            FileCrypto::default(),
+            vec!["user"],
+        );
+        let measurement = EnclaveMeasurement::new([1; 32], [2; 32]);
+        let dump = MetadataDump::new(vec![function], &[input], &[], &[])
+            .sign(&measurement, b"certificate", pkcs8.as_ref())
+            .unwrap();
+
+        assert_eq!(
+            dump.input_files[0].url,
+            "https://bucket.s3.amazonaws.com/data"
+        );
+        assert_eq!(
+            dump.functions[0].payload_hash,
+            sha256_hex(b"def entrypoint(argv): pass")
+        );
+        assert!(dump.verify_signature(public_key).is_ok());
+
+        let bytes = dump.to_vec().unwrap();
+        let mut imported = MetadataDump::from_slice(&bytes).unwrap();
+        assert!(imported.verify_signature(public_key).is_ok());
+        imported.input_files.clear();
+        assert!(imported.verify_signature(public_key).is_err());
+
+        let mut newer = MetadataDump::from_slice(&bytes).unwrap();
+        newer.version += 1;
+        assert!(MetadataDump::from_slice(&newer.to_vec().unwrap()).is_err());
+    }
+}
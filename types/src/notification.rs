@@ -0,0 +1,220 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::task_state::now_secs;
+use crate::{ExternalID, Storable, TaskResult, TaskState, TaskStatus, UserID};
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+const TASK_EVENT_PREFIX: &str = "task_event";
+
+/// A task reached a terminal state, i.e., finished, failed or canceled.
+/// Events are queued at `TASK_EVENT_QUEUE_KEY` and delivered to the
+/// participants in digests, and to the webhook sinks of the task.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TaskEvent {
+    pub task_id: Uuid,
+    pub status: TaskStatus,
+    /// Reason of the failure or cancellation
+    pub reason: String,
+    pub participants: Vec<UserID>,
+    /// The second since the UNIX epoch
+    pub timestamp: i64,
+    #[serde(default)]
+    pub webhook_sinks: Vec<ExternalID>,
+}
+
+impl TaskEvent {
+    pub fn from_task_state(ts: &TaskState) -> Self {
+        let reason = match &ts.result {
+            TaskResult::Err(failure) => failure.reason.clone(),
+            _ => String::new(),
+        };
+        TaskEvent {
+            task_id: ts.task_id,
+            status: ts.status.clone(),
+            reason,
+            participants: ts.participants.clone().into_iter().collect(),
+            timestamp: now_secs(),
+            webhook_sinks: ts.webhook_sinks.clone(),
+        }
+    }
+}
+
+impl Storable for TaskEvent {
+    fn key_prefix() -> &'static str {
+        TASK_EVENT_PREFIX
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.task_id
+    }
+}
+
+/// Channels through which a user wants to receive digests. A user without
+/// any channel has not opted in.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct NotificationPreferences {
+    pub email: Option<String>,
+    /// Member ID to mention in the Slack channel of the webhook
+    pub slack_member_id: Option<String>,
+}
+
+impl NotificationPreferences {
+    pub fn is_opted_in(&self) -> bool {
+        self.email.is_some() || self.slack_member_id.is_some()
+    }
+
+    /// The values end up in email headers and Slack messages, so only
+    /// plain addresses and member IDs are accepted.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(email) = &self.email {
+            let is_address_char = |c: char| c.is_ascii_graphic() && !"<>()[],;:\\\"".contains(c);
+            ensure!(
+                email.chars().all(is_address_char) && email.matches('@').count() == 1,
+                "invalid email address: {:?}",
+                email
+            );
+        }
+        if let Some(member_id) = &self.slack_member_id {
+            ensure!(
+                !member_id.is_empty() && member_id.chars().all(|c| c.is_ascii_alphanumeric()),
+                "invalid Slack member ID: {:?}",
+                member_id
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Events of the tasks a user participates in, to be delivered by the
+/// notifier of the frontend service app.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NotificationDigest {
+    pub user_id: UserID,
+    pub preferences: NotificationPreferences,
+    pub events: Vec<TaskEvent>,
+}
+
+impl NotificationDigest {
+    /// Group `events` into one digest per participant who opted in.
+    pub fn collect(
+        events: &[TaskEvent],
+        preferences: &HashMap<UserID, NotificationPreferences>,
+    ) -> Vec<NotificationDigest> {
+        let mut digests: BTreeMap<String, NotificationDigest> = BTreeMap::new();
+        for event in events {
+            for user_id in &event.participants {
+                let preferences = match preferences.get(user_id) {
+                    Some(p) if p.is_opted_in() => p,
+                    _ => continue,
+                };
+                digests
+                    .entry(user_id.to_string())
+                    .or_insert_with(|| NotificationDigest {
+                        user_id: user_id.clone(),
+                        preferences: preferences.clone(),
+                        events: Vec::new(),
+                    })
+                    .events
+                    .push(event.clone());
+            }
+        }
+        digests.into_values().collect()
+    }
+
+    pub fn subject(&self) -> String {
+        format!("Teaclave: {} task(s) ended", self.events.len())
+    }
+
+    pub fn body(&self) -> String {
+        self.events
+            .iter()
+            .map(|event| {
+                let mut line = format!("- task {}: {:?}", event.task_id, event.status);
+                if !event.reason.is_empty() {
+                    line.push_str(&format!(" ({})", event.reason));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    fn event(participants: &[&str], status: TaskStatus) -> TaskEvent {
+        TaskEvent {
+            task_id: Uuid::new_v4(),
+            status,
+            reason: String::new(),
+            participants: participants.iter().map(|&p| p.into()).collect(),
+            timestamp: 0,
+            webhook_sinks: Vec::new(),
+        }
+    }
+
+    pub fn test_collect_digests() {
+        let finished = event(&["alice", "bob"], TaskStatus::Finished);
+        let mut canceled = event(&["alice", "carol"], TaskStatus::Canceled);
+        canceled.reason = "Task canceled".to_string();
+
+        let mut preferences = HashMap::new();
+        preferences.insert(
+            UserID::from("alice"),
+            NotificationPreferences {
+                email: Some("alice@example.com".to_string()),
+                slack_member_id: None,
+            },
+        );
+        // Opted out
+        preferences.insert(UserID::from("bob"), NotificationPreferences::default());
+
+        let digests = NotificationDigest::collect(&[finished, canceled.clone()], &preferences);
+        assert_eq!(digests.len(), 1);
+        let digest = &digests[0];
+        assert_eq!(digest.user_id, UserID::from("alice"));
+        assert_eq!(digest.events.len(), 2);
+        assert_eq!(digest.subject(), "Teaclave: 2 task(s) ended");
+        assert!(digest.body().contains(&format!(
+            "task {}: Canceled (Task canceled)",
+            canceled.task_id
+        )));
+    }
+
+    pub fn test_validate_preferences() {
+        let mut preferences = NotificationPreferences {
+            email: Some("alice@example.com".to_string()),
+            slack_member_id: Some("U012AB3CD".to_string()),
+        };
+        assert!(preferences.validate().is_ok());
+
+        preferences.email = Some("alice@example.com\r\nBcc: eve@example.com".to_string());
+        assert!(preferences.validate().is_err());
+        preferences.email = Some("alice".to_string());
+        assert!(preferences.validate().is_err());
+
+        preferences.email = None;
+        preferences.slack_member_id = Some("<!channel>".to_string());
+        assert!(preferences.validate().is_err());
+    }
+}
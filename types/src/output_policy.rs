@@ -0,0 +1,119 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+/// A policy registered with a fusion output, which the execution service
+/// applies to the plaintext of the output before it is encrypted and
+/// uploaded. Outputs are CSV records with fields separated by commas.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputPolicy {
+    /// Refuse to release outputs with fewer than `min_rows` records
+    MinRowCount { min_rows: u64, has_headers: bool },
+    /// Add noise drawn from Laplace(0, sensitivity / epsilon) to the numbers
+    /// in `columns`, counted from 0
+    LaplaceNoise {
+        columns: Vec<usize>,
+        epsilon: f64,
+        sensitivity: f64,
+        has_headers: bool,
+    },
+}
+
+impl OutputPolicy {
+    pub fn min_row_count(min_rows: u64, has_headers: bool) -> Self {
+        OutputPolicy::MinRowCount {
+            min_rows,
+            has_headers,
+        }
+    }
+
+    pub fn laplace_noise(
+        columns: impl Into<Vec<usize>>,
+        epsilon: f64,
+        sensitivity: f64,
+        has_headers: bool,
+    ) -> Self {
+        OutputPolicy::LaplaceNoise {
+            columns: columns.into(),
+            epsilon,
+            sensitivity,
+            has_headers,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputPolicy::MinRowCount { .. } => "min_row_count",
+            OutputPolicy::LaplaceNoise { .. } => "laplace_noise",
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            OutputPolicy::MinRowCount { min_rows, .. } => {
+                ensure!(*min_rows > 0, "min_rows must be positive");
+            }
+            OutputPolicy::LaplaceNoise {
+                columns,
+                epsilon,
+                sensitivity,
+                ..
+            } => {
+                ensure!(!columns.is_empty(), "no columns to add noise to");
+                ensure!(
+                    epsilon.is_finite() && *epsilon > 0.0,
+                    "epsilon must be positive"
+                );
+                ensure!(
+                    sensitivity.is_finite() && *sensitivity > 0.0,
+                    "sensitivity must be positive"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_validate_output_policy() {
+        assert!(OutputPolicy::min_row_count(10, true).validate().is_ok());
+        assert!(OutputPolicy::min_row_count(0, true).validate().is_err());
+        assert!(OutputPolicy::laplace_noise([1], 0.5, 1.0, false)
+            .validate()
+            .is_ok());
+        assert!(OutputPolicy::laplace_noise([], 0.5, 1.0, false)
+            .validate()
+            .is_err());
+        assert!(OutputPolicy::laplace_noise([1], 0.0, 1.0, false)
+            .validate()
+            .is_err());
+        assert!(OutputPolicy::laplace_noise([1], 0.5, f64::NAN, false)
+            .validate()
+            .is_err());
+
+        let policy = OutputPolicy::laplace_noise([0, 2], 0.5, 1.0, true);
+        let json = serde_json::to_string(&policy).unwrap();
+        assert!(json.contains(r#""type":"laplace_noise""#));
+        assert_eq!(serde_json::from_str::<OutputPolicy>(&json).unwrap(), policy);
+    }
+}
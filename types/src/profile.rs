@@ -0,0 +1,143 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Time spent in each phase of a task run by an execution service, in
+/// milliseconds. Only collected for functions registered with profiling
+/// enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TaskProfile {
+    /// Downloading the input files into the enclave
+    pub staging_ms: u64,
+    /// Decrypting the input files into staged files
+    pub decryption_ms: u64,
+    /// Running the function in the executor
+    pub execution_ms: u64,
+    /// Encrypting the output files for upload
+    pub conversion_ms: u64,
+    /// Uploading the output files
+    pub upload_ms: u64,
+}
+
+impl TaskProfile {
+    pub fn total_ms(&self) -> u64 {
+        self.staging_ms
+            + self.decryption_ms
+            + self.execution_ms
+            + self.conversion_ms
+            + self.upload_ms
+    }
+
+    fn add(&mut self, other: &TaskProfile) {
+        self.staging_ms = self.staging_ms.saturating_add(other.staging_ms);
+        self.decryption_ms = self.decryption_ms.saturating_add(other.decryption_ms);
+        self.execution_ms = self.execution_ms.saturating_add(other.execution_ms);
+        self.conversion_ms = self.conversion_ms.saturating_add(other.conversion_ms);
+        self.upload_ms = self.upload_ms.saturating_add(other.upload_ms);
+    }
+
+    fn div(&self, n: u64) -> TaskProfile {
+        TaskProfile {
+            staging_ms: self.staging_ms / n,
+            decryption_ms: self.decryption_ms / n,
+            execution_ms: self.execution_ms / n,
+            conversion_ms: self.conversion_ms / n,
+            upload_ms: self.upload_ms / n,
+        }
+    }
+}
+
+const FUNCTION_PROFILE_PREFIX: &str = "profile";
+
+/// Phase timings of the profiled tasks of a function, accumulated by the
+/// scheduler as task results come in.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct FunctionProfile {
+    pub function_id: Uuid,
+    pub profiled_tasks: u64,
+    pub total: TaskProfile,
+}
+
+impl FunctionProfile {
+    pub fn new(function_id: Uuid) -> Self {
+        Self {
+            function_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&mut self, profile: &TaskProfile) {
+        self.profiled_tasks += 1;
+        self.total.add(profile);
+    }
+
+    /// Average phase timings over the profiled tasks, `None` if no task has
+    /// been profiled yet.
+    pub fn average(&self) -> Option<TaskProfile> {
+        if self.profiled_tasks == 0 {
+            return None;
+        }
+        Some(self.total.div(self.profiled_tasks))
+    }
+}
+
+impl Storable for FunctionProfile {
+    fn key_prefix() -> &'static str {
+        FUNCTION_PROFILE_PREFIX
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.function_id
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_function_profile_average() {
+        let mut profile = FunctionProfile::new(Uuid::new_v4());
+        assert!(profile.average().is_none());
+
+        profile.record(&TaskProfile {
+            staging_ms: 10,
+            decryption_ms: 4,
+            execution_ms: 100,
+            conversion_ms: 6,
+            upload_ms: 20,
+        });
+        profile.record(&TaskProfile {
+            staging_ms: 30,
+            decryption_ms: 8,
+            execution_ms: 300,
+            conversion_ms: 2,
+            upload_ms: 40,
+        });
+
+        let average = profile.average().unwrap();
+        assert_eq!(profile.profiled_tasks, 2);
+        assert_eq!(average.staging_ms, 20);
+        assert_eq!(average.decryption_ms, 6);
+        assert_eq!(average.execution_ms, 200);
+        assert_eq!(average.conversion_ms, 4);
+        assert_eq!(average.upload_ms, 30);
+        assert_eq!(average.total_ms(), 260);
+    }
+}
@@ -0,0 +1,217 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::{EnclaveMeasurement, FileAuthTag, FunctionInputFiles, OutputsTags};
+use anyhow::{anyhow, ensure, Result};
+use ring::signature;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Evidence that a task result was produced by a function in an execution
+/// enclave. The receipt is signed with the key of the attested TLS
+/// certificate of the execution service, whose attestation report binds the
+/// key to the enclave measurement, so that it can be verified offline.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExecutionReceipt {
+    pub task_id: String,
+    /// SHA-256 of the function payload in hex
+    pub function_hash: String,
+    /// CMACs of the input files in hex, keyed by the input names
+    pub input_cmacs: BTreeMap<String, String>,
+    /// CMACs of the output files in hex, keyed by the output names
+    pub output_cmacs: BTreeMap<String, String>,
+    /// SHA-256 of the return value in hex
+    pub return_value_hash: String,
+    pub mr_enclave: String,
+    pub mr_signer: String,
+    /// Attested TLS certificate in DER of the enclave which signed the receipt
+    pub certificate: Vec<u8>,
+    /// ECDSA P-256 signature in ASN.1 over `signed_bytes()`
+    pub signature: Vec<u8>,
+}
+
+// Fields covered by the signature, in a stable order
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    task_id: &'a str,
+    function_hash: &'a str,
+    input_cmacs: &'a BTreeMap<String, String>,
+    output_cmacs: &'a BTreeMap<String, String>,
+    return_value_hash: &'a str,
+    mr_enclave: &'a str,
+    mr_signer: &'a str,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(ring::digest::digest(&ring::digest::SHA256, bytes))
+}
+
+impl ExecutionReceipt {
+    pub fn new(
+        task_id: &Uuid,
+        function_payload: &[u8],
+        inputs: &FunctionInputFiles,
+        outputs: &OutputsTags,
+        return_value: &[u8],
+    ) -> Self {
+        ExecutionReceipt {
+            task_id: task_id.to_string(),
+            function_hash: sha256_hex(function_payload),
+            input_cmacs: inputs
+                .iter()
+                .map(|(name, file)| (name.clone(), file.cmac.to_hex()))
+                .collect(),
+            output_cmacs: outputs
+                .iter()
+                .map(|(name, cmac)| (name.clone(), cmac.to_hex()))
+                .collect(),
+            return_value_hash: sha256_hex(return_value),
+            ..Default::default()
+        }
+    }
+
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let fields = SignedFields {
+            task_id: &self.task_id,
+            function_hash: &self.function_hash,
+            input_cmacs: &self.input_cmacs,
+            output_cmacs: &self.output_cmacs,
+            return_value_hash: &self.return_value_hash,
+            mr_enclave: &self.mr_enclave,
+            mr_signer: &self.mr_signer,
+        };
+        // Serializing string fields and maps never fails.
+        serde_json::to_vec(&fields).unwrap()
+    }
+
+    /// Sign the receipt with the PKCS#8 private key of the attested TLS
+    /// `certificate`, which is attached for verification.
+    pub fn sign(
+        mut self,
+        measurement: &EnclaveMeasurement,
+        certificate: &[u8],
+        private_key: &[u8],
+    ) -> Result<Self> {
+        self.mr_enclave = hex::encode(measurement.mr_enclave);
+        self.mr_signer = hex::encode(measurement.mr_signer);
+        self.certificate = certificate.to_vec();
+
+        let key_pair = signature::EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            private_key,
+        )
+        .map_err(|_| anyhow!("Invalid receipt signing key"))?;
+        let rng = ring::rand::SystemRandom::new();
+        let signature = key_pair
+            .sign(&rng, &self.signed_bytes())
+            .map_err(|_| anyhow!("Failed to sign the receipt"))?;
+        self.signature = signature.as_ref().to_vec();
+        Ok(self)
+    }
+
+    /// Verify the signature with the uncompressed P-256 public key taken from
+    /// the attested certificate.
+    pub fn verify_signature(&self, public_key: &[u8]) -> Result<()> {
+        signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, public_key)
+            .verify(&self.signed_bytes(), &self.signature)
+            .map_err(|_| anyhow!("Invalid receipt signature"))
+    }
+
+    /// Check that the receipt is about the function with `function_payload`
+    /// and the result with `return_value`.
+    pub fn matches(&self, function_payload: &[u8], return_value: &[u8]) -> bool {
+        self.function_hash == sha256_hex(function_payload)
+            && self.return_value_hash == sha256_hex(return_value)
+    }
+
+    /// Check that the receipt is about the function with `function_payload`.
+    pub fn check_function(&self, function_payload: &[u8]) -> Result<()> {
+        ensure!(
+            self.function_hash == sha256_hex(function_payload),
+            "The receipt is about another function"
+        );
+        Ok(())
+    }
+
+    /// Check that the output `name` of the task was written with the tag
+    /// `cmac`, i.e., that a file authenticated by it is the task result.
+    pub fn check_output(&self, name: &str, cmac: &FileAuthTag) -> Result<()> {
+        let recorded = self
+            .output_cmacs
+            .get(name)
+            .ok_or_else(|| anyhow!("The receipt records no output {}", name))?;
+        ensure!(
+            *recorded == cmac.to_hex(),
+            "The tag of the output {} differs from the receipt",
+            name
+        );
+        Ok(())
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use ring::signature::KeyPair;
+
+    pub fn test_sign_and_verify_receipt() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = signature::EcdsaKeyPair::generate_pkcs8(
+            &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            &rng,
+        )
+        .unwrap();
+        let key_pair = signature::EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            pkcs8.as_ref(),
+        )
+        .unwrap();
+        let public_key = key_pair.public_key().as_ref();
+
+        let cmac = FileAuthTag::mock();
+        let outputs = OutputsTags::new(crate::hashmap!("model".to_string() => cmac));
+        let measurement = EnclaveMeasurement::new([1; 32], [2; 32]);
+        let receipt = ExecutionReceipt::new(
+            &Uuid::new_v4(),
+            b"def entrypoint(argv): pass",
+            &FunctionInputFiles::default(),
+            &outputs,
+            b"ok",
+        )
+        .sign(&measurement, b"certificate", pkcs8.as_ref())
+        .unwrap();
+
+        assert_eq!(receipt.output_cmacs["model"], cmac.to_hex());
+        assert_eq!(receipt.mr_enclave, hex::encode([1; 32]));
+        assert!(receipt.matches(b"def entrypoint(argv): pass", b"ok"));
+        assert!(!receipt.matches(b"def entrypoint(argv): pass", b"not ok"));
+        assert!(receipt
+            .check_function(b"def entrypoint(argv): pass")
+            .is_ok());
+        assert!(receipt.check_function(b"def entrypoint(argv): 0").is_err());
+        assert!(receipt.check_output("model", &cmac).is_ok());
+        assert!(receipt.check_output("report", &cmac).is_err());
+        let other = FileAuthTag::from_hex("ff".repeat(16)).unwrap();
+        assert!(receipt.check_output("model", &other).is_err());
+        assert!(receipt.verify_signature(public_key).is_ok());
+
+        let mut tampered = receipt;
+        tampered.output_cmacs.clear();
+        assert!(tampered.verify_signature(public_key).is_err());
+    }
+}
@@ -0,0 +1,416 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::task_state::now_secs;
+use crate::{
+    FunctionOutputFile, FunctionOutputFiles, StagedTask, Storable, TaskFiles, TaskResult,
+    TaskState, TaskStatus, TeaclaveOutputFile,
+};
+use anyhow::{anyhow, bail, ensure, Result};
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+const SCHEDULED_TASK_PREFIX: &str = "scheduled";
+// Runs kept in the history of a scheduled task, the oldest are dropped first
+pub const MAX_SCHEDULED_RUNS: usize = 100;
+// Schedules without any run in this many days are rejected
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+const SECS_PER_MINUTE: i64 = 60;
+const SECS_PER_HOUR: i64 = 3600;
+const SECS_PER_DAY: i64 = 86400;
+
+/// A cron expression of five fields: minute, hour, day of month, month and
+/// day of week (0 or 7 for Sunday), evaluated in UTC. A field is `*`, a
+/// number, a range `a-b`, a step `*/n`, `a/n` or `a-b/n`, or a
+/// comma-separated list of them. As in cron, a day matches either of the
+/// day fields if both are restricted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+}
+
+fn full_mask(min: u32, max: u32) -> u64 {
+    (min..=max).fold(0, |mask, value| mask | 1 << value)
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u32>()?)),
+            None => (part, None),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (lo.parse()?, hi.parse()?)
+        } else {
+            let value = range.parse()?;
+            match step {
+                Some(_) => (value, max),
+                None => (value, value),
+            }
+        };
+        ensure!(
+            min <= lo && lo <= hi && hi <= max,
+            "Invalid range {} in {}",
+            range,
+            field
+        );
+        let step = step.unwrap_or(1);
+        ensure!(step > 0, "Invalid step in {}", field);
+        for value in (lo..=hi).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        ensure!(
+            fields.len() == 5,
+            "Expected 5 fields in cron expression {}",
+            expression
+        );
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        let schedule = CronSchedule {
+            expression: fields.join(" "),
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+        };
+        ensure!(
+            schedule.next_after(now_secs()).is_some(),
+            "Cron expression {} never matches",
+            expression
+        );
+        Ok(schedule)
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    fn matches_day(&self, datetime: &NaiveDateTime) -> bool {
+        if self.months & (1 << datetime.month()) == 0 {
+            return false;
+        }
+        let dom = self.days_of_month & (1 << datetime.day()) != 0;
+        let dow = self.days_of_week & (1 << datetime.weekday().num_days_from_sunday()) != 0;
+        if self.days_of_month != full_mask(1, 31) && self.days_of_week != full_mask(0, 6) {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+
+    /// The first minute matching the schedule strictly after the second
+    /// `secs` since the UNIX epoch, if any in the next few years.
+    pub fn next_after(&self, secs: i64) -> Option<i64> {
+        let mut time = (secs.div_euclid(SECS_PER_MINUTE) + 1) * SECS_PER_MINUTE;
+        let limit = time + MAX_SEARCH_DAYS * SECS_PER_DAY;
+        while time < limit {
+            let datetime = NaiveDateTime::from_timestamp_opt(time, 0)?;
+            if !self.matches_day(&datetime) {
+                time = time - time.rem_euclid(SECS_PER_DAY) + SECS_PER_DAY;
+            } else if self.hours & (1 << datetime.hour()) == 0 {
+                time = time - time.rem_euclid(SECS_PER_HOUR) + SECS_PER_HOUR;
+            } else if self.minutes & (1 << datetime.minute()) == 0 {
+                time += SECS_PER_MINUTE;
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(expression: String) -> Result<Self> {
+        Self::parse(&expression)
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> String {
+        schedule.expression
+    }
+}
+
+/// A run of a scheduled task.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ScheduledRun {
+    /// The task instance of the run, none if the run was skipped as the
+    /// previous instance had not ended yet
+    pub task_id: Option<Uuid>,
+    /// The second since the UNIX epoch the run was due at
+    pub run_at: i64,
+}
+
+/// The schedule of a task and the history of its runs, kept in the state of
+/// the scheduled task. The scheduled task itself never runs; each run is a
+/// new task with the same function, arguments, participants and data.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TaskSchedule {
+    pub cron: CronSchedule,
+    /// The second since the UNIX epoch the next run is due at, 0 until the
+    /// task is invoked
+    pub next_run_at: i64,
+    /// The latest runs, oldest first
+    pub runs: Vec<ScheduledRun>,
+}
+
+impl TaskSchedule {
+    pub fn new(cron: CronSchedule) -> Self {
+        TaskSchedule {
+            cron,
+            next_run_at: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Start the schedule from now on.
+    pub fn activate(&mut self) -> Result<()> {
+        self.activate_at(now_secs())
+    }
+
+    pub fn activate_at(&mut self, now: i64) -> Result<()> {
+        self.next_run_at = self
+            .cron
+            .next_after(now)
+            .ok_or_else(|| anyhow!("No more runs of {}", self.cron.expression()))?;
+        Ok(())
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.is_due_at(now_secs())
+    }
+
+    pub fn is_due_at(&self, now: i64) -> bool {
+        self.next_run_at != 0 && self.next_run_at <= now
+    }
+
+    /// Record the run due at `next_run_at` and move on to the first run from
+    /// now on, so that runs missed while the scheduler was down are not made
+    /// up for.
+    pub fn record_run(&mut self, task_id: Option<Uuid>) {
+        self.record_run_at(task_id, now_secs())
+    }
+
+    pub fn record_run_at(&mut self, task_id: Option<Uuid>, now: i64) {
+        self.runs.push(ScheduledRun {
+            task_id,
+            run_at: self.next_run_at,
+        });
+        if self.runs.len() > MAX_SCHEDULED_RUNS {
+            self.runs.remove(0);
+        }
+        self.next_run_at = self.cron.next_after(now).unwrap_or_default();
+    }
+
+    /// The latest instance which has actually been run, if any.
+    pub fn last_instance(&self) -> Option<Uuid> {
+        self.runs.iter().rev().find_map(|r| r.task_id)
+    }
+}
+
+/// The staged task of a scheduled task, kept by the scheduler service as the
+/// template of its runs.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ScheduledTask {
+    pub staged_task: StagedTask,
+}
+
+impl Storable for ScheduledTask {
+    fn key_prefix() -> &'static str {
+        SCHEDULED_TASK_PREFIX
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.staged_task.task_id
+    }
+}
+
+impl ScheduledTask {
+    pub fn new(staged_task: StagedTask) -> Self {
+        ScheduledTask { staged_task }
+    }
+
+    /// Materialize a run of the scheduled task `template`: the state of a
+    /// new staged task, and the task to be sent to an executor. Outputs of a
+    /// run are new output files at the locations of the template outputs,
+    /// since the CMAC of an output file is written once. Each run overwrites
+    /// the content of the previous one at the same locations.
+    pub fn instantiate(
+        &self,
+        template: &TaskState,
+    ) -> Result<(TaskState, StagedTask, Vec<TeaclaveOutputFile>)> {
+        ensure!(
+            template.task_id == self.staged_task.task_id,
+            "Template mismatch"
+        );
+        if template.status != TaskStatus::Staged {
+            bail!("Scheduled task is in status {:?}", template.status);
+        }
+
+        let task_id = Uuid::new_v4();
+        let mut outputs = TaskFiles::<TeaclaveOutputFile>::default();
+        let mut output_data = HashMap::new();
+        let mut output_files = Vec::new();
+        for (slot, file) in template.assigned_outputs.clone().into_iter() {
+            let file = TeaclaveOutputFile {
+                uuid: Uuid::new_v4(),
+                cmac: None,
+                release_verdict: None,
+                context: None,
+                ..file
+            };
+            let context = file.context_for(task_id, &slot);
+            output_data.insert(
+                slot.clone(),
+                FunctionOutputFile::from(file.clone()).context(context),
+            );
+            outputs.assign(&slot, file.clone())?;
+            output_files.push(file);
+        }
+        let staged_task = StagedTask {
+            task_id,
+            output_data: FunctionOutputFiles::new(output_data),
+            ..self.staged_task.clone()
+        };
+
+        let ts = TaskState {
+            task_id,
+            assigned_outputs: outputs,
+            result: TaskResult::NotReady,
+            status: TaskStatus::Staged,
+            created_at: now_secs(),
+            log_file: None,
+            progress: None,
+            schedule: None,
+            scheduled_from: Some(template.task_id),
+            ..template.clone()
+        };
+        Ok((ts, staged_task, output_files))
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use crate::{FileCrypto, OwnerList};
+    use url::Url;
+
+    pub fn test_cron_schedule() {
+        // 2023-01-02 (Monday) 03:04:05 UTC
+        let monday = 1672628645;
+        let every_minute = CronSchedule::parse("* * * * *").unwrap();
+        assert_eq!(every_minute.next_after(monday), Some(monday - 5 + 60));
+
+        let hourly = CronSchedule::parse("30 */2 * * *").unwrap();
+        // 04:30
+        assert_eq!(hourly.next_after(monday), Some(1672633800));
+
+        let weekly = CronSchedule::parse("0 0 * * 0").unwrap();
+        // 2023-01-08 00:00
+        assert_eq!(weekly.next_after(monday), Some(1673136000));
+        assert_eq!(CronSchedule::parse("0 0 * * 7").unwrap(), weekly);
+
+        // The 1st of the month or any Friday
+        let either = CronSchedule::parse("0 0 1 * 5").unwrap();
+        // 2023-01-06 00:00
+        assert_eq!(either.next_after(monday), Some(1672963200));
+
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("0 0 31 2 *").is_err());
+
+        let json = serde_json::to_string(&hourly).unwrap();
+        assert_eq!(json, "\"30 */2 * * *\"");
+        assert_eq!(serde_json::from_str::<CronSchedule>(&json).unwrap(), hourly);
+    }
+
+    pub fn test_instantiate_scheduled_task() {
+        let mut schedule = TaskSchedule::new(CronSchedule::parse("*/5 * * * *").unwrap());
+        assert!(!schedule.is_due());
+        schedule.activate_at(1672628645).unwrap();
+        assert_eq!(schedule.next_run_at, 1672628700);
+        assert!(!schedule.is_due_at(1672628699));
+        assert!(schedule.is_due_at(1672628700));
+
+        let url = Url::parse("s3://bucket/output").unwrap();
+        let output =
+            TeaclaveOutputFile::new(url, FileCrypto::default(), OwnerList::from(vec!["alice"]))
+                .bind_context(true);
+        let mut template = TaskState {
+            status: TaskStatus::Staged,
+            schedule: Some(schedule.clone()),
+            ..Default::default()
+        };
+        template
+            .assigned_outputs
+            .assign("output", output.clone())
+            .unwrap();
+        let scheduled = ScheduledTask::new(StagedTask {
+            task_id: template.task_id,
+            ..Default::default()
+        });
+
+        let (ts, staged_task, files) = scheduled.instantiate(&template).unwrap();
+        assert_ne!(ts.task_id, template.task_id);
+        assert_eq!(ts.scheduled_from, Some(template.task_id));
+        assert!(ts.schedule.is_none());
+        assert_eq!(staged_task.task_id, ts.task_id);
+        assert_eq!(files.len(), 1);
+        assert_ne!(files[0].uuid, output.uuid);
+        assert_eq!(files[0].url, output.url);
+        let (_, output_data) = staged_task.output_data.iter().next().unwrap();
+        assert_eq!(
+            output_data.context,
+            files[0].context_for(ts.task_id, "output")
+        );
+
+        schedule.record_run_at(Some(ts.task_id), 1672628701);
+        assert_eq!(schedule.next_run_at, 1672629000);
+        assert_eq!(schedule.last_instance(), Some(ts.task_id));
+        schedule.record_run_at(None, 1672629000);
+        assert_eq!(schedule.runs.len(), 2);
+        assert_eq!(schedule.last_instance(), Some(ts.task_id));
+
+        template.status = TaskStatus::Canceled;
+        assert!(scheduled.instantiate(&template).is_err());
+    }
+}
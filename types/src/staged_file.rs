@@ -15,16 +15,17 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use teaclave_crypto::TeaclaveFile128Key;
+use teaclave_crypto::{read_to_end_zeroizing, TeaclaveFile128Key, Zeroize, Zeroizing};
 
 use std::collections::HashMap;
 #[cfg(not(feature = "mesalock_sgx"))]
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read, Write};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 #[cfg(feature = "mesalock_sgx")]
 use std::untrusted::fs::File;
 
+use crate::EncryptionContext;
 use crate::FileAuthTag;
 use crate::FileCrypto;
 use anyhow::Context;
@@ -37,6 +38,13 @@ pub struct StagedFileInfo {
     pub cmac: FileAuthTag,
 }
 
+// The key of a staged file is only needed during the task.
+impl Drop for StagedFileInfo {
+    fn drop(&mut self) {
+        self.crypto_info.zeroize();
+    }
+}
+
 impl StagedFileInfo {
     pub fn new(
         path: impl AsRef<Path>,
@@ -59,6 +67,14 @@ impl StagedFileInfo {
         Ok(Box::new(f))
     }
 
+    /// Read the plaintext written by the function, which is not tagged until
+    /// it is converted for uploading.
+    pub fn read_plaintext(&self) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        let mut f = SgxFile::open_with_key(&self.path, self.crypto_info.key)
+            .context("Failed to open staged file")?;
+        Ok(read_to_end_zeroizing(&mut f)?)
+    }
+
     pub fn create_writable_io(&self) -> anyhow::Result<Box<dyn io::Write>> {
         let f = SgxFile::create_with_key(&self.path, self.crypto_info.key)?;
         Ok(Box::new(f))
@@ -68,32 +84,24 @@ impl StagedFileInfo {
         &self,
         dst: impl AsRef<Path>,
         crypto_info: FileCrypto,
+        context: Option<&EncryptionContext>,
     ) -> anyhow::Result<FileAuthTag> {
         match crypto_info {
             FileCrypto::TeaclaveFile128(cipher) => {
                 self.convert_to_teaclave_file(dst, cipher.to_owned())
             }
-            FileCrypto::AesGcm128(cipher) => {
-                let mut src_file = SgxFile::open_with_key(&self.path, self.crypto_info.key)
-                    .context("Convert aes-gcm-128: failed to open src file")?;
-                let mut buffer = Vec::new();
-                src_file.read_to_end(&mut buffer)?;
-                let cmac = cipher.encrypt(&mut buffer)?;
-                let mut file = File::create(dst)?;
-                file.write_all(&buffer)?;
-                FileAuthTag::from_bytes(&cmac)
-            }
-            FileCrypto::AesGcm256(cipher) => {
+            FileCrypto::Raw => anyhow::bail!("OutputFile: unsupported type"),
+            _ => {
                 let mut src_file = SgxFile::open_with_key(&self.path, self.crypto_info.key)
-                    .context("Convert aes-gcm-256: failed to open src file")?;
-                let mut buffer = Vec::new();
-                src_file.read_to_end(&mut buffer)?;
-                let cmac = cipher.encrypt(&mut buffer)?;
+                    .with_context(|| {
+                        format!("Convert {}: failed to open src file", crypto_info.schema())
+                    })?;
+                let mut buffer = read_to_end_zeroizing(&mut src_file)?;
+                let cmac = crypto_info.encrypt_in_memory(&mut buffer, context)?;
                 let mut file = File::create(dst)?;
                 file.write_all(&buffer)?;
-                FileAuthTag::from_bytes(&cmac)
+                Ok(cmac)
             }
-            FileCrypto::Raw => anyhow::bail!("OutputFile: unsupported type"),
         }
     }
 
@@ -102,26 +110,25 @@ impl StagedFileInfo {
         dst: impl AsRef<Path>,
         crypto: TeaclaveFile128Key,
     ) -> anyhow::Result<FileAuthTag> {
-        let src_file = SgxFile::open_with_key(&self.path, self.crypto_info.key)
+        let mut src_file = SgxFile::open_with_key(&self.path, self.crypto_info.key)
             .context("Convert teaclave_file: failed to open src file")?;
         let mut dest_file = SgxFile::create_with_key(dst.as_ref(), crypto.key)
             .context("Convert teaclave_file: failed to create dst file")?;
 
-        let mut reader = BufReader::with_capacity(4096, src_file);
+        // The plaintext passing through is cleared when done.
+        let mut buffer = Zeroizing::new(vec![0; 4096]);
         loop {
-            let buffer = reader.fill_buf()?;
-            let rd_len = buffer.len();
+            let rd_len = src_file.read(&mut buffer)?;
             if rd_len == 0 {
                 break;
             }
-            let wt_len = dest_file.write(buffer)?;
+            let wt_len = dest_file.write(&buffer[..rd_len])?;
             anyhow::ensure!(
                 rd_len == wt_len,
                 "Cannot fully write to dest file: Rd({:?}) != Wt({:?})",
                 rd_len,
                 wt_len
             );
-            reader.consume(rd_len);
         }
         dest_file
             .flush()
@@ -51,7 +51,61 @@ impl std::convert::TryFrom<String> for FunctionArguments {
     }
 }
 
+/// Serialization of the function arguments in a task creation request, which
+/// may be a JSON object, a CBOR map, or a protobuf message in the wire format
+/// of `google.protobuf.Struct`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentsFormat {
+    Json,
+    Cbor,
+    Protobuf,
+}
+
+impl ArgumentsFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ArgumentsFormat::Json => "application/json",
+            ArgumentsFormat::Cbor => "application/cbor",
+            ArgumentsFormat::Protobuf => "application/x-protobuf",
+        }
+    }
+}
+
+impl std::convert::TryFrom<&str> for ArgumentsFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(content_type: &str) -> Result<Self> {
+        let format = match content_type {
+            "application/json" => ArgumentsFormat::Json,
+            "application/cbor" => ArgumentsFormat::Cbor,
+            "application/x-protobuf" | "application/protobuf" => ArgumentsFormat::Protobuf,
+            _ => anyhow::bail!("Unsupported content type of arguments: {}", content_type),
+        };
+        Ok(format)
+    }
+}
+
 impl FunctionArguments {
+    pub fn from_json_slice(bytes: &[u8]) -> Result<Self> {
+        let json: ArgumentValue =
+            serde_json::from_slice(bytes).context("Invalid JSON arguments")?;
+        Self::from_json(json)
+    }
+
+    /// Byte strings and non-string keys have no JSON counterpart and are
+    /// rejected.
+    pub fn from_cbor_slice(bytes: &[u8]) -> Result<Self> {
+        let json: ArgumentValue =
+            ciborium::de::from_reader(bytes).context("Invalid CBOR arguments")?;
+        Self::from_json(json)
+    }
+
+    pub fn to_cbor_vec(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&self.inner, &mut bytes)?;
+        Ok(bytes)
+    }
+
     pub fn from_json(json: ArgumentValue) -> Result<Self> {
         let inner = match json {
             ArgumentValue::Object(o) => o,
@@ -80,6 +134,36 @@ impl FunctionArguments {
             .with_context(|| format!("key not found: {}", key))
     }
 
+    pub fn get_as<T: serde::de::DeserializeOwned>(&self, key: &str) -> anyhow::Result<T> {
+        let value = self.get(key)?;
+        serde_json::from_value(value.clone())
+            .with_context(|| format!("invalid type of argument: {}", key))
+    }
+
+    pub fn get_str(&self, key: &str) -> anyhow::Result<&str> {
+        self.get(key)?
+            .as_str()
+            .with_context(|| format!("argument {} is not a string", key))
+    }
+
+    pub fn get_i64(&self, key: &str) -> anyhow::Result<i64> {
+        self.get(key)?
+            .as_i64()
+            .with_context(|| format!("argument {} is not an integer", key))
+    }
+
+    pub fn get_f64(&self, key: &str) -> anyhow::Result<f64> {
+        self.get(key)?
+            .as_f64()
+            .with_context(|| format!("argument {} is not a number", key))
+    }
+
+    pub fn get_bool(&self, key: &str) -> anyhow::Result<bool> {
+        self.get(key)?
+            .as_bool()
+            .with_context(|| format!("argument {} is not a boolean", key))
+    }
+
     pub fn into_vec(self) -> Vec<String> {
         let mut vector = Vec::new();
 
@@ -113,6 +197,8 @@ pub struct StagedFunction {
     pub executor_type: ExecutorType,
     pub executor: Executor,
     pub runtime_name: String,
+    /// Bytes the function may write to all its outputs, 0 for no limit
+    pub max_output_bytes: u64,
 }
 
 #[derive(Default)]
@@ -167,6 +253,11 @@ impl StagedFunctionBuilder {
         self
     }
 
+    pub fn max_output_bytes(mut self, max_output_bytes: u64) -> Self {
+        self.function.max_output_bytes = max_output_bytes;
+        self
+    }
+
     pub fn build(self) -> StagedFunction {
         self.function
     }
@@ -17,18 +17,25 @@
 
 use std::collections::hash_map::{IntoIter, Iter, IterMut};
 use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
 
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use url::Url;
 use uuid::Uuid;
 
 use crate::{
-    Executor, ExecutorType, FileAuthTag, FileCrypto, FunctionArguments, Storable,
-    TeaclaveInputFile, TeaclaveOutputFile,
+    EncryptionContext, Executor, ExecutorFeatures, ExecutorType, FileAuthTag, FileCrypto,
+    FunctionArguments, OutputPolicy, Storable, TeaclaveInputFile, TeaclaveOutputFile, UserList,
 };
 
 const STAGED_TASK_PREFIX: &str = "staged-"; // staged-task-uuid
 pub const QUEUE_KEY: &str = "staged-task";
+// The sizes of input files are not known before they are fetched, so tasks
+// are batched only if they have few inputs and small arguments.
+const MAX_BATCHED_INPUTS: usize = 4;
+const MAX_BATCHED_ARGUMENT_BYTES: usize = 4096;
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct FunctionInputFiles {
@@ -42,6 +49,14 @@ impl FunctionInputFiles {
     pub fn iter(&self) -> Iter<String, FunctionInputFile> {
         self.inner.iter()
     }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
 }
 
 impl IntoIterator for FunctionInputFiles {
@@ -128,6 +143,8 @@ pub struct FunctionInputFile {
     pub url: Url,
     pub cmac: FileAuthTag,
     pub crypto_info: FileCrypto,
+    #[serde(default)]
+    pub context: Option<EncryptionContext>,
 }
 
 impl FunctionInputFile {
@@ -136,6 +153,7 @@ impl FunctionInputFile {
             url,
             cmac,
             crypto_info: crypto.into(),
+            context: None,
         }
     }
 }
@@ -146,6 +164,7 @@ impl From<TeaclaveInputFile> for FunctionInputFile {
             url: file.url,
             cmac: file.cmac,
             crypto_info: file.crypto_info,
+            context: file.context,
         }
     }
 }
@@ -154,6 +173,10 @@ impl From<TeaclaveInputFile> for FunctionInputFile {
 pub struct FunctionOutputFile {
     pub url: Url,
     pub crypto_info: FileCrypto,
+    #[serde(default)]
+    pub context: Option<EncryptionContext>,
+    #[serde(default)]
+    pub output_policies: Vec<OutputPolicy>,
 }
 
 impl FunctionOutputFile {
@@ -161,8 +184,15 @@ impl FunctionOutputFile {
         Self {
             url,
             crypto_info: crypto.into(),
+            context: None,
+            output_policies: Vec::new(),
         }
     }
+
+    pub fn context(mut self, context: Option<EncryptionContext>) -> Self {
+        self.context = context;
+        self
+    }
 }
 
 impl From<TeaclaveOutputFile> for FunctionOutputFile {
@@ -170,11 +200,13 @@ impl From<TeaclaveOutputFile> for FunctionOutputFile {
         Self {
             url: file.url,
             crypto_info: file.crypto_info,
+            context: None,
+            output_policies: file.output_policies,
         }
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct StagedTask {
     pub task_id: Uuid,
     pub function_id: Uuid,
@@ -184,8 +216,30 @@ pub struct StagedTask {
     pub function_name: String,
     pub function_arguments: FunctionArguments,
     pub function_payload: Vec<u8>,
+    /// Key of the function payload if it is encrypted by the owner
+    #[serde(default)]
+    pub function_payload_crypto: Option<FileCrypto>,
+    /// Features of the executor the function needs, checked on delivery
+    #[serde(default)]
+    pub executor_features: ExecutorFeatures,
     pub input_data: FunctionInputFiles,
     pub output_data: FunctionOutputFiles,
+    /// Trace id of the request invoking the task
+    #[serde(default)]
+    pub trace_id: String,
+    /// Whether to report phase timings with the task result
+    #[serde(default)]
+    pub profiling: bool,
+    /// Time budgets of the stages set by the task creator
+    #[serde(default)]
+    pub timeouts: TaskTimeouts,
+    /// Resource limits of a Python function, none for the other executors
+    #[serde(default)]
+    pub resource_limits: ResourceLimits,
+    /// Owners of the input and output files, who see each other's tasks in a
+    /// batch
+    #[serde(default)]
+    pub participants: UserList,
 }
 
 impl Storable for StagedTask {
@@ -202,6 +256,134 @@ impl StagedTask {
     pub fn get_queue_key() -> &'static str {
         QUEUE_KEY
     }
+
+    /// The function payload in plaintext, decrypted if it is encrypted by the
+    /// owner.
+    pub fn decrypt_function_payload(&self) -> Result<Vec<u8>> {
+        let mut payload = self.function_payload.clone();
+        if let Some(crypto) = &self.function_payload_crypto {
+            crypto
+                .decrypt_in_memory(&mut payload, None)
+                .context("Failed to decrypt the function payload")?;
+        }
+        Ok(payload)
+    }
+
+    /// Whether the task is small enough to be batched with others.
+    pub fn is_batchable(&self) -> bool {
+        self.input_data.len() <= MAX_BATCHED_INPUTS
+            && serde_json::to_vec(&self.function_arguments).map_or(false, |arguments| {
+                arguments.len() <= MAX_BATCHED_ARGUMENT_BYTES
+            })
+    }
+
+    /// Whether the tasks can run one after another in a batch given to one
+    /// executor: small tasks of the same function, creator and participants.
+    pub fn batches_with(&self, other: &StagedTask) -> bool {
+        self.is_batchable()
+            && other.is_batchable()
+            && self.function_id == other.function_id
+            && self.executor == other.executor
+            && self.user_id == other.user_id
+            && self.participants == other.participants
+    }
+}
+
+/// The stages an executor runs a task in, each with its own time budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStage {
+    /// Fetching and decrypting the inputs
+    Staging,
+    /// Running the function
+    Execution,
+    /// Encrypting and uploading the outputs
+    Upload,
+}
+
+impl fmt::Display for TaskStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stage = match self {
+            TaskStage::Staging => "staging",
+            TaskStage::Execution => "execution",
+            TaskStage::Upload => "upload",
+        };
+        write!(f, "{}", stage)
+    }
+}
+
+/// Seconds each stage of a task may take, 0 for no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TaskTimeouts {
+    pub staging_secs: u64,
+    pub execution_secs: u64,
+    pub upload_secs: u64,
+}
+
+impl TaskTimeouts {
+    pub fn new(staging_secs: u64, execution_secs: u64, upload_secs: u64) -> Self {
+        Self {
+            staging_secs,
+            execution_secs,
+            upload_secs,
+        }
+    }
+
+    /// The budget of `stage`, if it is limited.
+    pub fn limit(&self, stage: TaskStage) -> Option<Duration> {
+        let secs = match stage {
+            TaskStage::Staging => self.staging_secs,
+            TaskStage::Execution => self.execution_secs,
+            TaskStage::Upload => self.upload_secs,
+        };
+        (secs != 0).then(|| Duration::from_secs(secs))
+    }
+}
+
+/// Resources a Python function may take in a run, 0 for no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ResourceLimits {
+    /// Wall-clock seconds of the function
+    pub timeout_secs: u64,
+    /// Bytes the function adds to the peak heap of the enclave
+    pub max_memory_bytes: u64,
+    /// Bytes written to all the outputs
+    pub max_output_bytes: u64,
+}
+
+impl ResourceLimits {
+    pub fn new(timeout_secs: u64, max_memory_bytes: u64, max_output_bytes: u64) -> Self {
+        Self {
+            timeout_secs,
+            max_memory_bytes,
+            max_output_bytes,
+        }
+    }
+
+    /// The limits set here, and `defaults` for the ones which are not.
+    pub fn or(self, defaults: ResourceLimits) -> Self {
+        let or = |value: u64, default: u64| if value == 0 { default } else { value };
+        Self {
+            timeout_secs: or(self.timeout_secs, defaults.timeout_secs),
+            max_memory_bytes: or(self.max_memory_bytes, defaults.max_memory_bytes),
+            max_output_bytes: or(self.max_output_bytes, defaults.max_output_bytes),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A function going beyond its resource limits, which fails the task with a
+/// reason of its own rather than an error of the function.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceLimitExceeded {
+    #[error("Resource limit exceeded: the function ran longer than {0}s")]
+    Timeout(u64),
+    #[error("Resource limit exceeded: the function took more than {0} bytes of memory")]
+    Memory(u64),
+    #[error("Resource limit exceeded: the function wrote more than {0} bytes of outputs")]
+    Output(u64),
 }
 
 #[derive(Default)]
@@ -251,6 +433,16 @@ impl StagedTaskBuilder {
         self
     }
 
+    pub fn function_payload_crypto(mut self, crypto: Option<FileCrypto>) -> Self {
+        self.task.function_payload_crypto = crypto;
+        self
+    }
+
+    pub fn executor_features(mut self, executor_features: ExecutorFeatures) -> Self {
+        self.task.executor_features = executor_features;
+        self
+    }
+
     pub fn input_data(mut self, input_data: impl Into<FunctionInputFiles>) -> Self {
         self.task.input_data = input_data.into();
         self
@@ -266,7 +458,78 @@ impl StagedTaskBuilder {
         self
     }
 
+    pub fn trace_id(mut self, trace_id: impl ToString) -> Self {
+        self.task.trace_id = trace_id.to_string();
+        self
+    }
+
+    pub fn profiling(mut self, profiling: bool) -> Self {
+        self.task.profiling = profiling;
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: TaskTimeouts) -> Self {
+        self.task.timeouts = timeouts;
+        self
+    }
+
+    pub fn resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        self.task.resource_limits = resource_limits;
+        self
+    }
+
+    pub fn participants(mut self, participants: UserList) -> Self {
+        self.task.participants = participants;
+        self
+    }
+
     pub fn build(self) -> StagedTask {
         self.task
     }
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    pub fn test_batch_tasks() {
+        let function_id = Uuid::new_v4();
+        let task = |user_id: &str| {
+            StagedTaskBuilder::new()
+                .task_id(Uuid::new_v4())
+                .function_id(function_id)
+                .user_id(user_id)
+                .participants(UserList::new(vec!["owner"]))
+                .build()
+        };
+        let first = task("user");
+        assert!(first.batches_with(&task("user")));
+        assert!(!first.batches_with(&task("another_user")));
+
+        let mut other_function = task("user");
+        other_function.function_id = Uuid::new_v4();
+        assert!(!first.batches_with(&other_function));
+        let mut other_participants = task("user");
+        other_participants.participants = UserList::new(vec!["owner", "another_owner"]);
+        assert!(!first.batches_with(&other_participants));
+
+        let arguments = format!(r#"{{"arg": "{}"}}"#, "x".repeat(MAX_BATCHED_ARGUMENT_BYTES));
+        let mut large = task("user");
+        large.function_arguments = FunctionArguments::try_from(arguments).unwrap();
+        assert!(!large.is_batchable());
+        assert!(!first.batches_with(&large));
+    }
+
+    pub fn test_resource_limits() {
+        let defaults = ResourceLimits::new(3600, 1 << 28, 1 << 30);
+        let limits = ResourceLimits::new(60, 0, 1024).or(defaults);
+        assert_eq!(limits, ResourceLimits::new(60, 1 << 28, 1024));
+        assert_eq!(ResourceLimits::default().or(defaults), defaults);
+        assert!(ResourceLimits::default().is_unlimited());
+        assert!(!limits.is_unlimited());
+
+        let reason = ResourceLimitExceeded::Timeout(60).to_string();
+        assert!(reason.starts_with("Resource limit exceeded"));
+    }
+}
@@ -20,6 +20,15 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 pub const CANCEL_QUEUE_KEY: &str = "cancel_queue";
+pub const TASK_EVENT_QUEUE_KEY: &str = "task_event_queue";
+pub const FILE_TRANSFER_QUEUE_KEY: &str = "file_transfer_queue";
+pub const SCHEDULE_QUEUE_KEY: &str = "schedule_queue";
+/// Staged tasks invoked in a maintenance window, staged once it ends
+pub const MAINTENANCE_QUEUE_KEY: &str = "maintenance_queue";
+/// The lease of the scheduler instance being the leader
+pub const SCHEDULER_LEASE_KEY: &str = "scheduler_lease";
+/// The backlog of tasks shared by the leading scheduler
+pub const SCHEDULER_BACKLOG_KEY: &str = "scheduler_backlog";
 
 pub trait Storable: Serialize + for<'de> Deserialize<'de> {
     fn key_prefix() -> &'static str;
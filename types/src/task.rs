@@ -15,6 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::task_state::now_secs;
 use crate::*;
 use anyhow::{anyhow, bail, ensure, Error, Result};
 use serde::{Deserialize, Serialize};
@@ -95,6 +96,10 @@ impl OwnerList {
         self.uids.insert(value)
     }
 
+    pub fn remove(&mut self, value: &UserID) -> bool {
+        self.uids.remove(value)
+    }
+
     pub fn union(mut self, other: Self) -> Self {
         for value in other.uids {
             self.uids.insert(value);
@@ -214,6 +219,13 @@ pub struct TaskOutputs {
     pub return_value: Vec<u8>,
     pub tags_map: OutputsTags,
     pub log: Vec<String>,
+    #[serde(default)]
+    pub receipt: Option<ExecutionReceipt>,
+    #[serde(default)]
+    pub profile: Option<TaskProfile>,
+    /// The uploaded output files, keyed by the output names
+    #[serde(default)]
+    pub artifacts: HashMap<String, OutputArtifact>,
 }
 
 impl TaskOutputs {
@@ -226,6 +238,48 @@ impl TaskOutputs {
             return_value: value.into(),
             tags_map: OutputsTags::new(tags_map),
             log,
+            receipt: None,
+            profile: None,
+            artifacts: HashMap::new(),
+        }
+    }
+
+    pub fn receipt(self, receipt: ExecutionReceipt) -> Self {
+        Self {
+            receipt: Some(receipt),
+            ..self
+        }
+    }
+
+    pub fn profile(self, profile: Option<TaskProfile>) -> Self {
+        Self { profile, ..self }
+    }
+
+    pub fn artifacts(self, artifacts: HashMap<String, OutputArtifact>) -> Self {
+        Self { artifacts, ..self }
+    }
+}
+
+/// An output file as the executor uploaded it, so that clients can check the
+/// file at `url` against the size and digest without downloading it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OutputArtifact {
+    pub url: url::Url,
+    /// Bytes of the uploaded file, i.e., of the ciphertext
+    pub size: u64,
+    /// SHA-256 of the uploaded file in hex
+    pub sha256: String,
+    /// Seconds since the UNIX epoch the upload completed at
+    pub uploaded_at: i64,
+}
+
+impl OutputArtifact {
+    pub fn new(url: url::Url, size: u64, sha256: impl ToString, uploaded_at: i64) -> Self {
+        Self {
+            url,
+            size,
+            sha256: sha256.to_string(),
+            uploaded_at,
         }
     }
 }
@@ -233,12 +287,23 @@ impl TaskOutputs {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TaskFailure {
     pub reason: String,
+    /// The forensic bundle of the failed run, if the executor made one
+    #[serde(default)]
+    pub forensic_bundle: Option<ForensicBundleFile>,
 }
 
 impl TaskFailure {
     pub fn new(reason: impl ToString) -> Self {
         TaskFailure {
             reason: reason.to_string(),
+            forensic_bundle: None,
+        }
+    }
+
+    pub fn forensic_bundle(self, forensic_bundle: Option<ForensicBundleFile>) -> Self {
+        Self {
+            forensic_bundle,
+            ..self
         }
     }
 }
@@ -249,6 +314,57 @@ impl std::fmt::Display for TaskFailure {
     }
 }
 
+/// The encrypted execution log of a task, uploaded by the executor and
+/// retrievable by the task creator.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TaskLogFile {
+    pub url: url::Url,
+    pub crypto_info: FileCrypto,
+    pub cmac: FileAuthTag,
+    /// Whether the head of the log was dropped to fit in the size limit
+    pub truncated: bool,
+}
+
+impl TaskLogFile {
+    pub fn new(url: url::Url, crypto_info: FileCrypto, cmac: FileAuthTag, truncated: bool) -> Self {
+        TaskLogFile {
+            url,
+            crypto_info,
+            cmac,
+            truncated,
+        }
+    }
+}
+
+const MAX_PROGRESS_STATE_LEN: usize = 256;
+
+/// The progress of a running task reported by its executor.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct TaskProgress {
+    /// From 0 to 100
+    pub percentage: u32,
+    /// What the task is doing, e.g., "executing"
+    pub state: String,
+    /// The second since the UNIX epoch the progress was reported at
+    pub updated_at: i64,
+}
+
+impl TaskProgress {
+    pub fn new(percentage: u32, state: impl ToString) -> Result<Self> {
+        let state = state.to_string();
+        ensure!(percentage <= 100, "Invalid percentage: {}", percentage);
+        ensure!(
+            state.len() <= MAX_PROGRESS_STATE_LEN,
+            "Progress state is too long"
+        );
+        Ok(TaskProgress {
+            percentage,
+            state,
+            updated_at: now_secs(),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
 pub struct ExternalID {
     pub prefix: String,
@@ -462,6 +578,10 @@ where
         self.inner.keys()
     }
 
+    pub fn get(&self, fname: &str) -> Option<&T> {
+        self.inner.get(fname)
+    }
+
     pub fn external_ids(&self) -> HashMap<String, ExternalID> {
         self.inner
             .iter()
@@ -473,12 +593,14 @@ where
 impl TaskFiles<TeaclaveOutputFile> {
     pub fn update_cmac(
         &mut self,
+        task_id: Uuid,
         fname: &str,
         auth_tag: &FileAuthTag,
     ) -> Result<&TeaclaveOutputFile> {
         let file = match self.inner.get_mut(fname) {
             Some(file) => {
                 file.assign_cmac(auth_tag)?;
+                file.context = file.context_for(task_id, fname);
                 file
             }
             _ => bail!("Upadate_cmac: file not found. {:?}", fname),
@@ -0,0 +1,142 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::{TaskState, TaskStatus, UserID};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+const TASK_INDEX_PREFIX: &str = "task_index";
+const UUID_LEN: usize = 36;
+
+/// Secondary index of task states for filtered listings. An indexed task has
+/// an empty entry under `task_index-<index>-<value>-<task id>`, written along
+/// with its state, so the tasks with a value are found by a prefix scan
+/// instead of reading all of them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskIndex {
+    Creator(UserID),
+    Status(TaskStatus),
+    /// The second since the UNIX epoch the task was created at. Keys of this
+    /// index are ordered by the creation time.
+    CreatedAt(i64),
+}
+
+impl TaskIndex {
+    /// All the index values of a task.
+    pub fn of(ts: &TaskState) -> Vec<TaskIndex> {
+        vec![
+            TaskIndex::Creator(ts.creator.clone()),
+            TaskIndex::Status(ts.status.clone()),
+            TaskIndex::CreatedAt(ts.created_at),
+        ]
+    }
+
+    /// Prefix of the keys of all the tasks created in any time, to be scanned
+    /// from `TaskIndex::CreatedAt(..).prefix()` on.
+    pub fn created_at_prefix() -> String {
+        format!("{}-created", TASK_INDEX_PREFIX)
+    }
+
+    /// Prefix of the keys of the tasks with this index value.
+    pub fn prefix(&self) -> String {
+        match self {
+            // User IDs may contain '-', which would make a creator a prefix of
+            // another one.
+            TaskIndex::Creator(creator) => format!(
+                "{}-creator-{}",
+                TASK_INDEX_PREFIX,
+                hex::encode(creator.to_string())
+            ),
+            TaskIndex::Status(status) => format!("{}-status-{:?}", TASK_INDEX_PREFIX, status),
+            TaskIndex::CreatedAt(created_at) => format!(
+                "{}-{:016x}",
+                Self::created_at_prefix(),
+                (*created_at).max(0) as u64
+            ),
+        }
+    }
+
+    pub fn key(&self, task_id: &Uuid) -> String {
+        format!("{}-{}", self.prefix(), task_id)
+    }
+
+    /// The task ID at the end of an index key.
+    pub fn task_id(key: &str) -> Option<Uuid> {
+        let start = key.len().checked_sub(UUID_LEN)?;
+        if !key.is_char_boundary(start) || !key[..start].ends_with('-') {
+            return None;
+        }
+        Uuid::parse_str(&key[start..]).ok()
+    }
+
+    /// Keys to put and to delete when a task state is updated from `old`,
+    /// `None` for a new task.
+    pub fn changes(old: Option<&TaskState>, new: &TaskState) -> (Vec<String>, Vec<String>) {
+        let puts: Vec<String> = Self::of(new)
+            .iter()
+            .map(|index| index.key(&new.task_id))
+            .collect();
+        let current: HashSet<&String> = puts.iter().collect();
+        let deletes = old
+            .map(|old| {
+                Self::of(old)
+                    .iter()
+                    .map(|index| index.key(&old.task_id))
+                    .filter(|key| !current.contains(key))
+                    .collect()
+            })
+            .unwrap_or_default();
+        (puts, deletes)
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_task_index_changes() {
+        let old = TaskState {
+            task_id: Uuid::new_v4(),
+            creator: UserID::from("user-1"),
+            created_at: 1_600_000_000,
+            ..Default::default()
+        };
+        let new = TaskState {
+            status: TaskStatus::Running,
+            ..old.clone()
+        };
+
+        let (puts, deletes) = TaskIndex::changes(None, &old);
+        assert_eq!(puts.len(), 3);
+        assert!(deletes.is_empty());
+
+        let (puts, deletes) = TaskIndex::changes(Some(&old), &new);
+        assert!(puts.contains(&TaskIndex::Status(TaskStatus::Running).key(&new.task_id)));
+        assert_eq!(
+            deletes,
+            vec![TaskIndex::Status(TaskStatus::Created).key(&old.task_id)]
+        );
+
+        for key in puts {
+            assert_eq!(TaskIndex::task_id(&key), Some(new.task_id));
+        }
+        let creator = TaskIndex::Creator(UserID::from("user-1")).prefix();
+        let other = TaskIndex::Creator(UserID::from("user")).prefix();
+        assert!(!creator.starts_with(&format!("{}-", other)));
+        assert!(TaskIndex::CreatedAt(1).prefix() < TaskIndex::CreatedAt(16).prefix());
+    }
+}
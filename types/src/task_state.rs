@@ -18,12 +18,55 @@
 use crate::*;
 use anyhow::{bail, ensure, Error, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 const TASK_PREFIX: &str = "task";
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ApprovalDecision {
+    Approved,
+    Rejected,
+}
+
+/// The latest decision of a participant on a task.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Approval {
+    pub decision: ApprovalDecision,
+    pub comment: String,
+    /// The second since the UNIX epoch
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl std::fmt::Display for ApprovalStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            ApprovalStatus::Pending => "pending",
+            ApprovalStatus::Approved => "approved",
+            ApprovalStatus::Rejected => "rejected",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Seconds since the UNIX epoch, the unit of the time stamps in the states.
+pub fn now_secs() -> i64 {
+    // UNIX_EPOCH is the earliest time stamp.
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct TaskState {
     pub task_id: Uuid,
@@ -40,6 +83,40 @@ pub struct TaskState {
     pub assigned_outputs: TaskFiles<TeaclaveOutputFile>,
     pub result: TaskResult,
     pub status: TaskStatus,
+    #[serde(default)]
+    pub purpose: String,
+    /// Decisions of the participants, including comments and rejections
+    #[serde(default)]
+    pub approvals: HashMap<UserID, Approval>,
+    /// The second since the UNIX epoch after which participants can no
+    /// longer approve or reject the task; 0 if approvals never expire.
+    #[serde(default)]
+    pub approval_deadline: i64,
+    /// The second since the UNIX epoch the task was created at; 0 for the
+    /// tasks created before it was recorded
+    #[serde(default)]
+    pub created_at: i64,
+    /// The execution log uploaded by the executor, if any
+    #[serde(default)]
+    pub log_file: Option<TaskLogFile>,
+    /// The latest progress reported by the executor, if any
+    #[serde(default)]
+    pub progress: Option<TaskProgress>,
+    /// Webhook sinks notified once the task ends
+    #[serde(default)]
+    pub webhook_sinks: Vec<ExternalID>,
+    /// The schedule of a scheduled task, which runs as new task instances
+    #[serde(default)]
+    pub schedule: Option<TaskSchedule>,
+    /// The scheduled task this task is an instance of, if any
+    #[serde(default)]
+    pub scheduled_from: Option<Uuid>,
+    /// Time budgets of the stages the executor runs the task in
+    #[serde(default)]
+    pub timeouts: TaskTimeouts,
+    /// Resource limits of the function if it runs in Python
+    #[serde(default)]
+    pub resource_limits: ResourceLimits,
 }
 
 impl Storable for TaskState {
@@ -74,6 +151,68 @@ impl TaskState {
         true
     }
 
+    pub fn approval_expired(&self) -> bool {
+        self.approval_expired_at(now_secs())
+    }
+
+    pub fn approval_expired_at(&self, now: i64) -> bool {
+        self.approval_deadline != 0 && now > self.approval_deadline
+    }
+
+    pub fn approval_status(&self, user_id: &UserID) -> ApprovalStatus {
+        match self.approvals.get(user_id).map(|a| a.decision) {
+            Some(ApprovalDecision::Rejected) => ApprovalStatus::Rejected,
+            Some(ApprovalDecision::Approved) => ApprovalStatus::Approved,
+            None if self.approved_users.contains(user_id) || self.participants.len() == 1 => {
+                ApprovalStatus::Approved
+            }
+            None => ApprovalStatus::Pending,
+        }
+    }
+
+    /// Participants who rejected the task can change their decision until the
+    /// task is approved by everyone. A rejection can be made before the data
+    /// is assigned, so that data owners can decline without assigning data.
+    pub fn reject(&mut self, requester: &UserID, reason: impl ToString) -> Result<()> {
+        ensure!(
+            matches!(self.status, TaskStatus::Created | TaskStatus::DataAssigned),
+            "Cannot reject a task in status {:?}",
+            self.status
+        );
+        self.record_decision(requester, ApprovalDecision::Rejected, reason, now_secs())
+    }
+
+    fn record_decision(
+        &mut self,
+        requester: &UserID,
+        decision: ApprovalDecision,
+        comment: impl ToString,
+        now: i64,
+    ) -> Result<()> {
+        ensure!(
+            self.participants.contains(requester),
+            "Unexpected user trying to approve or reject a task: {:?}",
+            requester
+        );
+        ensure!(
+            !self.approval_expired_at(now),
+            "Approval of the task expired at {}",
+            self.approval_deadline
+        );
+
+        match decision {
+            ApprovalDecision::Approved => self.approved_users.insert(requester.clone()),
+            ApprovalDecision::Rejected => self.approved_users.remove(requester),
+        };
+        let approval = Approval {
+            decision,
+            comment: comment.to_string(),
+            timestamp: now,
+        };
+        self.approvals.insert(requester.clone(), approval);
+        Ok(())
+    }
+
     pub fn has_participant(&self, user_id: &UserID) -> bool {
         self.participants.contains(user_id)
     }
@@ -106,6 +245,7 @@ impl StateTag for Finish {}
 impl StateTag for Done {}
 impl StateTag for Cancel {}
 impl StateTag for Fail {}
+impl StateTag for Requeue {}
 
 impl Task<Create> {
     pub fn new(
@@ -140,11 +280,11 @@ impl Task<Create> {
 
         let mut func_args = req_func_args;
         for arg in &function.arguments {
-            if !arg.allow_overwrite || !func_args.inner().contains_key(&arg.key) {
-                func_args.insert(
-                    arg.key.clone(),
-                    serde_json::Value::String(arg.default_value.clone()),
-                );
+            match func_args.inner().get(&arg.key) {
+                Some(value) if arg.allow_overwrite => arg.value_type.check(&arg.key, value)?,
+                _ => {
+                    func_args.insert(arg.key.clone(), arg.default_json_value()?);
+                }
             }
         }
 
@@ -185,6 +325,7 @@ impl Task<Create> {
             inputs_ownership: req_input_owners,
             outputs_ownership: req_output_owners,
             participants,
+            created_at: now_secs(),
             ..Default::default()
         };
 
@@ -193,6 +334,36 @@ impl Task<Create> {
             extra: Create,
         })
     }
+
+    pub fn purpose(mut self, purpose: impl ToString) -> Self {
+        self.state.purpose = purpose.to_string();
+        self
+    }
+
+    /// Participants should approve or reject the task within `expiry` after
+    /// its creation.
+    pub fn approval_expiry(mut self, expiry: Duration) -> Self {
+        self.state.approval_deadline = now_secs() + expiry.as_secs() as i64;
+        self
+    }
+
+    /// Once invoked, the task runs as a new task on each match of `cron`.
+    pub fn schedule(mut self, cron: CronSchedule) -> Self {
+        self.state.schedule = Some(TaskSchedule::new(cron));
+        self
+    }
+
+    /// The executor fails the task once any of its stages runs out of time.
+    pub fn timeouts(mut self, timeouts: TaskTimeouts) -> Self {
+        self.state.timeouts = timeouts;
+        self
+    }
+
+    /// The executor fails the task once its function goes beyond the limits.
+    pub fn resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        self.state.resource_limits = resource_limits;
+        self
+    }
 }
 
 impl Task<Assign> {
@@ -216,11 +387,53 @@ impl Task<Assign> {
             file.external_id()
         );
 
+        if let Some(policy) = &file.access_policy {
+            ensure!(
+                policy.allows(&self.state.function_id, &self.state.function_owner),
+                "Assign: function is not allowed by the access policy. {:?}.",
+                file.external_id()
+            );
+        }
+
         self.state.inputs_ownership.check(fname, &file.owner)?;
         self.state.assigned_inputs.assign(fname, file)?;
         Ok(())
     }
 
+    /// Approve the task for `requester` once all the inputs of theirs are
+    /// assigned with files allowing the function by their access policies,
+    /// unless they have decided already. Returns whether the task is approved.
+    pub fn approve_by_access_policy(&mut self, requester: &UserID) -> Result<bool> {
+        let state = &self.state;
+        let mut inputs = state
+            .inputs_ownership
+            .keys()
+            .filter(|fname| {
+                state
+                    .inputs_ownership
+                    .get(fname)
+                    .map_or(false, |owners| owners.contains(requester))
+            })
+            .peekable();
+        let allowed = inputs.peek().is_some()
+            && inputs.all(|fname| {
+                state
+                    .assigned_inputs
+                    .get(fname)
+                    .map_or(false, |file| file.access_policy.is_some())
+            });
+        if !allowed || state.approvals.contains_key(requester) {
+            return Ok(false);
+        }
+        self.state.record_decision(
+            requester,
+            ApprovalDecision::Approved,
+            "approved by the access policies of the input files",
+            now_secs(),
+        )?;
+        Ok(true)
+    }
+
     pub fn assign_output(
         &mut self,
         requester: &UserID,
@@ -248,15 +461,9 @@ impl Task<Approve> {
         Ok(task)
     }
 
-    pub fn approve(&mut self, requester: &UserID) -> Result<()> {
-        ensure!(
-            self.state.participants.contains(requester),
-            "Unexpected user trying to approve a task: {:?}",
-            requester
-        );
-
-        self.state.approved_users.insert(requester.clone());
-        Ok(())
+    pub fn approve(&mut self, requester: &UserID, comment: impl ToString) -> Result<()> {
+        self.state
+            .record_decision(requester, ApprovalDecision::Approved, comment, now_secs())
     }
 }
 
@@ -280,6 +487,17 @@ impl Task<Stage> {
         );
 
         let function_arguments = self.state.function_arguments.clone();
+        let task_id = self.state.task_id;
+        let output_data = self
+            .state
+            .assigned_outputs
+            .clone()
+            .into_iter()
+            .map(|(slot, file)| {
+                let context = file.context_for(task_id, &slot);
+                (slot, FunctionOutputFile::from(file).context(context))
+            })
+            .collect();
         let staged_task = StagedTask {
             task_id: self.state.task_id,
             user_id: requester.into(),
@@ -288,9 +506,16 @@ impl Task<Stage> {
             function_id: function.id,
             function_name: function.name,
             function_payload: function.payload,
+            function_payload_crypto: function.payload_crypto,
+            executor_features: function.executor_features,
             function_arguments,
             input_data: self.state.assigned_inputs.clone().into(),
-            output_data: self.state.assigned_outputs.clone().into(),
+            output_data,
+            trace_id: String::new(),
+            profiling: function.profiling,
+            timeouts: self.state.timeouts,
+            resource_limits: self.state.resource_limits,
+            participants: self.state.participants.clone(),
         };
         Ok(staged_task)
     }
@@ -320,13 +545,24 @@ impl Task<Finish> {
         fname: &str,
         auth_tag: &FileAuthTag,
     ) -> Result<&TeaclaveOutputFile> {
-        self.state.assigned_outputs.update_cmac(fname, auth_tag)
+        let task_id = self.state.task_id;
+        self.state
+            .assigned_outputs
+            .update_cmac(task_id, fname, auth_tag)
     }
 
     pub fn update_result(&mut self, result: TaskResult) -> Result<()> {
         self.state.result = result;
         Ok(())
     }
+
+    pub fn update_log_file(&mut self, log_file: Option<TaskLogFile>) {
+        self.state.log_file = log_file;
+    }
+
+    pub fn update_progress(&mut self, progress: TaskProgress) {
+        self.state.progress = Some(progress);
+    }
 }
 
 impl Task<Done> {
@@ -361,6 +597,18 @@ impl Task<Fail> {
     }
 }
 
+// A running task handed back by its executor is staged again, so that
+// another executor runs it from the beginning.
+impl Task<Requeue> {
+    pub fn new(ts: TaskState) -> Result<Self> {
+        let task = Task::<Requeue> {
+            state: ts,
+            extra: Requeue,
+        };
+        Ok(task)
+    }
+}
+
 impl Task<Cancel> {
     pub fn new(ts: TaskState) -> Result<Self> {
         let task = Task::<Cancel> {
@@ -515,6 +763,18 @@ impl std::convert::TryFrom<TaskState> for Task<Fail> {
     }
 }
 
+impl std::convert::TryFrom<TaskState> for Task<Requeue> {
+    type Error = Error;
+
+    fn try_from(ts: TaskState) -> Result<Self> {
+        let task = match ts.status {
+            TaskStatus::Running => Task::<Requeue>::new(ts)?,
+            _ => bail!("Cannot restore to Requeue from saved state"),
+        };
+        Ok(task)
+    }
+}
+
 impl std::convert::TryFrom<TaskState> for Task<Cancel> {
     type Error = Error;
 
@@ -552,6 +812,15 @@ impl std::convert::From<Task<Cancel>> for TaskState {
     }
 }
 
+impl std::convert::From<Task<Requeue>> for TaskState {
+    fn from(mut task: Task<Requeue>) -> TaskState {
+        task.state.status = TaskStatus::Staged;
+        // The task runs again from the beginning.
+        task.state.progress = None;
+        task.state
+    }
+}
+
 impl_transit_and_into_task_state!(Assign => Approve);
 impl_transit_and_into_task_state!(Approve => Stage);
 impl_transit_and_into_task_state!(Stage => Run);
@@ -594,6 +863,8 @@ pub struct Done;
 pub struct Cancel;
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct Fail;
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Requeue;
 
 impl std::convert::From<Create> for TaskStatus {
     fn from(_tag: Create) -> TaskStatus {
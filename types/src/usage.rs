@@ -0,0 +1,187 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::task_state::now_secs;
+use crate::{
+    FileTransferRecord, HandleFileCommand, Storable, TaskResult, TaskState, TaskStatus, UserID,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const USAGE_RECORD_PREFIX: &str = "usage_record";
+const USAGE_INDEX_PREFIX: &str = "usage_index";
+const UUID_LEN: usize = 36;
+
+/// Resources used by a task run, reported by the executor along with the
+/// result of the task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TaskUsage {
+    /// Wall-clock time from staging the inputs to uploading the outputs
+    pub duration_ms: u64,
+    /// Bytes of the input files downloaded
+    pub input_bytes: u64,
+    /// Bytes of the output files uploaded
+    pub output_bytes: u64,
+}
+
+impl TaskUsage {
+    /// The usage of a run taking `duration_ms` with the successful file
+    /// transfers in `transfers`.
+    pub fn from_transfers(duration_ms: u64, transfers: &[FileTransferRecord]) -> Self {
+        let bytes = |cmd: HandleFileCommand| {
+            transfers
+                .iter()
+                .filter(|r| r.success && r.cmd == cmd)
+                .fold(0u64, |sum, r| sum.saturating_add(r.bytes))
+        };
+        TaskUsage {
+            duration_ms,
+            input_bytes: bytes(HandleFileCommand::Download),
+            output_bytes: bytes(HandleFileCommand::Upload),
+        }
+    }
+}
+
+/// The billing record of a task run, written by the scheduler once the
+/// executor reports the result. Records of a function are indexed by the
+/// time they end at under `usage_index-<function id>-<ended at>-<task id>`,
+/// so that a time range is found by a prefix scan.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UsageRecord {
+    pub task_id: Uuid,
+    pub function_id: Uuid,
+    /// The creator of the task, who is charged for the run
+    pub user_id: UserID,
+    /// Finished if the function returned, Failed otherwise
+    pub status: TaskStatus,
+    /// The second since the UNIX epoch the run ended at
+    pub ended_at: i64,
+    pub usage: TaskUsage,
+}
+
+impl Storable for UsageRecord {
+    fn key_prefix() -> &'static str {
+        USAGE_RECORD_PREFIX
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.task_id
+    }
+}
+
+impl UsageRecord {
+    pub fn new(ts: &TaskState, usage: TaskUsage) -> Self {
+        UsageRecord {
+            task_id: ts.task_id,
+            function_id: ts.function_id.uuid,
+            user_id: ts.creator.clone(),
+            status: match ts.result {
+                TaskResult::Ok(_) => TaskStatus::Finished,
+                _ => TaskStatus::Failed,
+            },
+            ended_at: now_secs(),
+            usage,
+        }
+    }
+
+    /// Prefix of the index keys of all the records of a function, which are
+    /// followed by '-' as the storage service scans by.
+    pub fn index_prefix(function_id: &Uuid) -> String {
+        format!("{}-{}", USAGE_INDEX_PREFIX, function_id)
+    }
+
+    /// The index key right before those of the records of a function ended
+    /// at or after `ended_at`, to be scanned from.
+    pub fn index_start(function_id: &Uuid, ended_at: i64) -> String {
+        format!(
+            "{}-{:016x}",
+            Self::index_prefix(function_id),
+            ended_at.max(0) as u64
+        )
+    }
+
+    pub fn index_key(&self) -> String {
+        format!(
+            "{}-{}",
+            Self::index_start(&self.function_id, self.ended_at),
+            self.task_id
+        )
+    }
+
+    /// The end time and task ID of an index key.
+    pub fn parse_index_key(key: &str) -> Option<(i64, Uuid)> {
+        let start = key.len().checked_sub(UUID_LEN)?;
+        if !key.is_char_boundary(start) || !key[..start].ends_with('-') {
+            return None;
+        }
+        let task_id = Uuid::parse_str(&key[start..]).ok()?;
+        let time = key[..start - 1].rsplit('-').next()?;
+        let ended_at = u64::from_str_radix(time, 16).ok()? as i64;
+        Some((ended_at, task_id))
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use crate::{ExternalID, TaskFailure};
+    use url::Url;
+
+    pub fn test_usage_record() {
+        let task_id = Uuid::new_v4();
+        let url = Url::parse("https://storage.example.com/data").unwrap();
+        let transfer = |cmd, bytes, success| {
+            FileTransferRecord::new(task_id, "alice", "", cmd, &url).outcome(bytes, 1, success)
+        };
+        let transfers = vec![
+            transfer(HandleFileCommand::Download, 100, true),
+            transfer(HandleFileCommand::Download, 20, true),
+            transfer(HandleFileCommand::Upload, 7, true),
+            transfer(HandleFileCommand::Upload, 1000, false),
+        ];
+        let usage = TaskUsage::from_transfers(42, &transfers);
+        assert_eq!(usage.duration_ms, 42);
+        assert_eq!(usage.input_bytes, 120);
+        assert_eq!(usage.output_bytes, 7);
+
+        let function_id = Uuid::new_v4();
+        let ts = TaskState {
+            task_id,
+            function_id: ExternalID::new("function", function_id),
+            creator: UserID::from("alice"),
+            status: TaskStatus::Finished,
+            result: TaskResult::Err(TaskFailure::new("error")),
+            ..Default::default()
+        };
+        let record = UsageRecord::new(&ts, usage);
+        assert_eq!(record.function_id, function_id);
+        assert_eq!(record.status, TaskStatus::Failed);
+
+        let key = record.index_key();
+        let prefix = UsageRecord::index_prefix(&function_id) + "-";
+        assert!(key.starts_with(&prefix));
+        assert!(key.as_str() > UsageRecord::index_start(&function_id, record.ended_at).as_str());
+        assert!(
+            key.as_str() < UsageRecord::index_start(&function_id, record.ended_at + 1).as_str()
+        );
+        assert_eq!(
+            UsageRecord::parse_index_key(&key),
+            Some((record.ended_at, task_id))
+        );
+        assert_eq!(UsageRecord::parse_index_key("usage_index-abc"), None);
+    }
+}
@@ -86,7 +86,7 @@ impl fmt::Display for UserRole {
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct UserAuthClaims {
     // user id
     pub sub: String,
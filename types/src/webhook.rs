@@ -0,0 +1,170 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::task_state::now_secs;
+use crate::{Storable, TaskResult, TaskState, TaskStatus, UserID};
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use url::Url;
+use uuid::Uuid;
+
+const WEBHOOK_SINK_PREFIX: &str = "webhook";
+const MIN_SECRET_LEN: usize = 16;
+const MAX_SECRET_LEN: usize = 256;
+
+/// An output sink which the result notification of a task is pushed to once
+/// the task ends. Notifications are signed with HMAC-SHA256 using the secret,
+/// which never leaves the enclaves.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookSink {
+    pub sink_id: Uuid,
+    pub owner: UserID,
+    pub url: Url,
+    pub secret: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: Url, secret: impl ToString, owner: impl Into<UserID>) -> Result<Self> {
+        let secret = secret.to_string();
+        ensure!(url.scheme() == "https", "Webhooks must use HTTPS");
+        ensure!(url.host_str().is_some(), "Webhook URL has no host");
+        ensure!(
+            (MIN_SECRET_LEN..=MAX_SECRET_LEN).contains(&secret.len()),
+            "Signing secret must have {} to {} bytes",
+            MIN_SECRET_LEN,
+            MAX_SECRET_LEN
+        );
+        Ok(WebhookSink {
+            sink_id: Uuid::new_v4(),
+            owner: owner.into(),
+            url,
+            secret,
+        })
+    }
+
+    /// Hex-encoded HMAC-SHA256 of `body`
+    pub fn sign(&self, body: &[u8]) -> String {
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, self.secret.as_bytes());
+        hex::encode(ring::hmac::sign(&key, body).as_ref())
+    }
+}
+
+impl Storable for WebhookSink {
+    fn key_prefix() -> &'static str {
+        WEBHOOK_SINK_PREFIX
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.sink_id
+    }
+}
+
+/// What a webhook sink is told about an ended task. Only the hashes of the
+/// result are pushed, so the receiver can check the outputs it gets through
+/// other channels without the platform sending any data out.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WebhookPayload {
+    pub task_id: String,
+    pub status: TaskStatus,
+    /// Reason of the failure or cancellation
+    pub reason: String,
+    /// Hex-encoded SHA-256 of the return value, empty unless finished
+    pub return_value_sha256: String,
+    /// Hex-encoded CMACs of the outputs, keyed by the output names
+    pub output_cmacs: BTreeMap<String, String>,
+    /// The second since the UNIX epoch
+    pub timestamp: i64,
+}
+
+impl WebhookPayload {
+    pub fn from_task_state(ts: &TaskState) -> Self {
+        let (reason, return_value_sha256, output_cmacs) = match &ts.result {
+            TaskResult::Ok(outputs) => {
+                let digest = ring::digest::digest(&ring::digest::SHA256, &outputs.return_value);
+                let cmacs = outputs
+                    .tags_map
+                    .iter()
+                    .map(|(name, tag)| (name.to_owned(), tag.to_hex()))
+                    .collect();
+                (String::new(), hex::encode(digest.as_ref()), cmacs)
+            }
+            TaskResult::Err(failure) => (failure.reason.clone(), String::new(), BTreeMap::new()),
+            TaskResult::NotReady => (String::new(), String::new(), BTreeMap::new()),
+        };
+        WebhookPayload {
+            task_id: ts.external_id().to_string(),
+            status: ts.status.clone(),
+            reason,
+            return_value_sha256,
+            output_cmacs,
+            timestamp: now_secs(),
+        }
+    }
+}
+
+/// A signed notification to be posted by the notifier of the frontend
+/// service app, with the signature in the `X-Teaclave-Signature` header.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WebhookNotification {
+    pub url: String,
+    pub body: String,
+    pub signature: String,
+}
+
+impl WebhookNotification {
+    pub fn new(sink: &WebhookSink, payload: &WebhookPayload) -> Result<Self> {
+        let body = serde_json::to_string(payload)?;
+        Ok(WebhookNotification {
+            url: sink.url.to_string(),
+            signature: sink.sign(body.as_bytes()),
+            body,
+        })
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use crate::{FileAuthTag, TaskOutputs};
+    use std::collections::HashMap;
+
+    pub fn test_webhook_notification() {
+        let url = Url::parse("https://hooks.example.com/teaclave").unwrap();
+        let secret = "0123456789abcdef";
+        assert!(WebhookSink::new(url.clone(), "short", "alice").is_err());
+        let http = Url::parse("http://hooks.example.com/teaclave").unwrap();
+        assert!(WebhookSink::new(http, secret, "alice").is_err());
+        let sink = WebhookSink::new(url, secret, "alice").unwrap();
+
+        let mut tags = HashMap::new();
+        tags.insert("output".to_string(), FileAuthTag::mock());
+        let ts = TaskState {
+            status: TaskStatus::Finished,
+            result: TaskResult::Ok(TaskOutputs::new("ok", tags, vec![])),
+            ..Default::default()
+        };
+        let payload = WebhookPayload::from_task_state(&ts);
+        assert_eq!(payload.output_cmacs["output"], FileAuthTag::mock().to_hex());
+        assert_eq!(payload.return_value_sha256.len(), 64);
+
+        let notification = WebhookNotification::new(&sink, &payload).unwrap();
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+        let signature = hex::decode(&notification.signature).unwrap();
+        assert!(ring::hmac::verify(&key, notification.body.as_bytes(), &signature).is_ok());
+    }
+}
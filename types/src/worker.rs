@@ -24,6 +24,10 @@ use std::io;
 pub trait TeaclaveRuntime {
     fn open_input(&self, identifier: &str) -> anyhow::Result<Box<dyn io::Read>>;
     fn create_output(&self, identifier: &str) -> anyhow::Result<Box<dyn io::Write>>;
+    /// Milliseconds since the Unix epoch, which never go backwards in a task
+    fn current_time(&self) -> anyhow::Result<u64>;
+    /// Fill `buf` with random bytes from the enclave
+    fn random_bytes(&self, buf: &mut [u8]) -> anyhow::Result<()>;
 }
 
 pub trait TeaclaveExecutor {
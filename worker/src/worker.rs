@@ -18,8 +18,10 @@
 use std::collections::HashMap;
 use std::format;
 
-use teaclave_runtime::DefaultRuntime;
-use teaclave_types::{Executor, ExecutorType, StagedFiles, StagedFunction};
+use teaclave_runtime::{DefaultRuntime, OutputLimitedRuntime};
+use teaclave_types::{
+    Executor, ExecutorFeatures, ExecutorType, ResourceLimitExceeded, StagedFiles, StagedFunction,
+};
 use teaclave_types::{TeaclaveExecutor, TeaclaveRuntime};
 
 type BoxedTeaclaveExecutor = Box<dyn TeaclaveExecutor + Send + Sync>;
@@ -30,6 +32,7 @@ type RuntimeBuilder = fn(StagedFiles, StagedFiles) -> BoxedTeaclaveRuntime;
 pub struct Worker {
     runtimes: HashMap<String, RuntimeBuilder>,
     executors: HashMap<(ExecutorType, Executor), ExecutorBuilder>,
+    features: ExecutorFeatures,
 }
 
 impl Default for Worker {
@@ -51,6 +54,9 @@ impl Default for Worker {
         worker.register_executor((ExecutorType::Python, Executor::MesaPy), || {
             Box::<teaclave_executor::MesaPy>::default()
         });
+        // MesaPy is based on PyPy of Python 2.7
+        #[cfg(all(executor_mesapy, not(feature = "app")))]
+        worker.register_feature("python", "2.7");
         #[cfg(executor_builtin)]
         worker.register_executor((ExecutorType::Builtin, Executor::Builtin), || {
             Box::<teaclave_executor::BuiltinFunctionExecutor>::default()
@@ -60,6 +66,9 @@ impl Default for Worker {
             (ExecutorType::WAMicroRuntime, Executor::WAMicroRuntime),
             || Box::<teaclave_executor::WAMicroRuntime>::default(),
         );
+        // The MVP of WebAssembly, without extensions such as SIMD
+        #[cfg(executor_wamr)]
+        worker.register_feature("wasm", "1.0");
 
         worker
     }
@@ -70,6 +79,7 @@ impl Worker {
         Self {
             runtimes: HashMap::new(),
             executors: HashMap::new(),
+            features: ExecutorFeatures::new(),
         }
     }
 
@@ -81,6 +91,15 @@ impl Worker {
         self.executors.insert(key, builder);
     }
 
+    /// Advertise a feature of the executors, which functions may need.
+    pub fn register_feature(&mut self, name: impl ToString, version: impl ToString) {
+        self.features = std::mem::take(&mut self.features).feature(name, version);
+    }
+
+    pub fn features(&self) -> &ExecutorFeatures {
+        &self.features
+    }
+
     pub fn invoke_function(&self, function: StagedFunction) -> anyhow::Result<String> {
         let executor = self.get_executor(function.executor_type, function.executor)?;
         let runtime = self.get_runtime(
@@ -88,7 +107,23 @@ impl Worker {
             function.input_files,
             function.output_files,
         )?;
-        executor.execute(function.name, function.arguments, function.payload, runtime)
+        let max_output_bytes = function.max_output_bytes;
+        if max_output_bytes == 0 {
+            return executor.execute(function.name, function.arguments, function.payload, runtime);
+        }
+
+        let runtime = OutputLimitedRuntime::new(runtime, max_output_bytes);
+        let usage = runtime.usage();
+        let result = executor.execute(
+            function.name,
+            function.arguments,
+            function.payload,
+            Box::new(runtime),
+        );
+        if usage.exceeded() {
+            return Err(ResourceLimitExceeded::Output(max_output_bytes).into());
+        }
+        result
     }
 
     fn get_runtime(